@@ -0,0 +1,620 @@
+//! Verification of [in-toto](https://in-toto.io) attestations for targets.
+//!
+//! TUF guarantees that a target is the one the repository intended to publish. An in-toto
+//! attestation adds provenance on top of that, such as which builder produced the target from
+//! which sources. This module verifies an attestation for a target after the target has been
+//! verified by TUF, and enforces a simple [AttestationPolicy].
+//!
+//! Attestations are published as targets themselves, so they are verified by TUF before they are
+//! parsed. The attestation for a target is located through the `attestation` custom field of the
+//! [TargetDescription] of the target, which holds the path of the attestation target:
+//!
+//! ```json
+//! "custom": {
+//!   "attestation": "app/foo-2.1.0.tar.gz.intoto.json"
+//! }
+//! ```
+//!
+//! If the field is missing, the attestation is expected at the path of the target with an
+//! `.intoto.json` suffix. The attestation is an in-toto statement wrapped in a
+//! [DSSE](https://github.com/secure-systems-lab/dsse) envelope.
+
+use chrono::{offset::Utc, DateTime};
+use data_encoding::{BASE64, BASE64URL, HEXLOWER_PERMISSIVE};
+use futures_util::io::AsyncReadExt as _;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::client::Client;
+use crate::crypto::{HashAlgorithm, PrivateKey, PublicKey, Signature, SignatureValue};
+use crate::error::{Error, Result};
+use crate::metadata::{MetadataPath, TargetDescription, TargetPath};
+use crate::pouf::Pouf;
+use crate::repository::{RepositoryProvider, RepositoryStorage};
+
+/// The name of the custom field of a [TargetDescription] that holds the path of the attestation
+/// target.
+pub const ATTESTATION_CUSTOM_FIELD: &str = "attestation";
+
+/// The suffix appended to the path of a target to find its attestation, if the target does not
+/// have an `attestation` custom field.
+pub const ATTESTATION_SUFFIX: &str = ".intoto.json";
+
+/// The DSSE payload type of in-toto statements.
+pub const IN_TOTO_PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+
+const IN_TOTO_STATEMENT_TYPES: &[&str] = &[
+    "https://in-toto.io/Statement/v1",
+    "https://in-toto.io/Statement/v0.1",
+];
+
+/// A DSSE envelope that wraps a signed payload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Envelope {
+    #[serde(rename = "payloadType")]
+    payload_type: String,
+    payload: String,
+    signatures: Vec<EnvelopeSignature>,
+}
+
+/// A signature in a DSSE envelope.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvelopeSignature {
+    #[serde(default)]
+    keyid: String,
+    sig: String,
+}
+
+impl Envelope {
+    /// Create an envelope for an in-toto `statement`, signed by each of `keys`.
+    pub fn sign_statement(statement: &Statement, keys: &[&dyn PrivateKey]) -> Result<Self> {
+        let payload =
+            serde_json::to_vec(statement).map_err(|err| Error::Encoding(err.to_string()))?;
+        let pae = pre_authentication_encoding(IN_TOTO_PAYLOAD_TYPE, &payload);
+
+        let signatures = keys
+            .iter()
+            .map(|key| {
+                let sig = key.sign(&pae)?;
+                Ok(EnvelopeSignature {
+                    keyid: sig.key_id().to_string(),
+                    sig: BASE64.encode(sig.value().as_bytes()),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Envelope {
+            payload_type: IN_TOTO_PAYLOAD_TYPE.into(),
+            payload: BASE64.encode(&payload),
+            signatures,
+        })
+    }
+
+    /// The type of the payload.
+    pub fn payload_type(&self) -> &str {
+        &self.payload_type
+    }
+
+    /// The signatures over the payload.
+    pub fn signatures(&self) -> &[EnvelopeSignature] {
+        &self.signatures
+    }
+}
+
+/// An in-toto statement about a set of subjects.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Statement {
+    #[serde(rename = "_type")]
+    typ: String,
+    subject: Vec<Subject>,
+    #[serde(rename = "predicateType")]
+    predicate_type: String,
+    #[serde(default)]
+    predicate: serde_json::Value,
+}
+
+impl Statement {
+    /// Create a new in-toto v1 statement.
+    pub fn new(
+        subject: Vec<Subject>,
+        predicate_type: String,
+        predicate: serde_json::Value,
+    ) -> Self {
+        Statement {
+            typ: IN_TOTO_STATEMENT_TYPES[0].into(),
+            subject,
+            predicate_type,
+            predicate,
+        }
+    }
+
+    /// The artifacts the statement is about.
+    pub fn subject(&self) -> &[Subject] {
+        &self.subject
+    }
+
+    /// The type of the predicate, such as `https://slsa.dev/provenance/v1`.
+    pub fn predicate_type(&self) -> &str {
+        &self.predicate_type
+    }
+
+    /// The predicate, whose format is determined by the predicate type.
+    pub fn predicate(&self) -> &serde_json::Value {
+        &self.predicate
+    }
+}
+
+/// An artifact an in-toto statement is about.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Subject {
+    name: String,
+    digest: HashMap<String, String>,
+}
+
+impl Subject {
+    /// Create a subject for the target `path` with the hashes in `description`.
+    pub fn from_target(path: &TargetPath, description: &TargetDescription) -> Self {
+        let digest = description
+            .hashes()
+            .iter()
+            .filter_map(|(alg, value)| Some((digest_name(alg)?.into(), value.to_string())))
+            .collect();
+
+        Subject {
+            name: path.as_str().into(),
+            digest,
+        }
+    }
+
+    /// The name of the artifact.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The digests of the artifact, keyed by the in-toto name of the digest algorithm.
+    pub fn digest(&self) -> &HashMap<String, String> {
+        &self.digest
+    }
+
+    /// Returns `true` if a digest of this subject matches a hash in `description`, and none of them
+    /// disagree.
+    fn matches(&self, description: &TargetDescription) -> bool {
+        let mut matched = false;
+        for (alg, value) in description.hashes() {
+            let digest = match digest_name(alg).and_then(|name| self.digest.get(name)) {
+                Some(digest) => digest,
+                None => continue,
+            };
+
+            match HEXLOWER_PERMISSIVE.decode(digest.as_bytes()) {
+                Ok(digest) if digest == value.value() => matched = true,
+                _ => return false,
+            }
+        }
+        matched
+    }
+}
+
+fn digest_name(alg: &HashAlgorithm) -> Option<&'static str> {
+    match alg {
+        HashAlgorithm::Sha256 => Some("sha256"),
+        HashAlgorithm::Sha384 => Some("sha384"),
+        HashAlgorithm::Sha512 => Some("sha512"),
+        _ => None,
+    }
+}
+
+/// The DSSE pre-authentication encoding of a payload, which is what is actually signed.
+fn pre_authentication_encoding(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut pae = format!(
+        "DSSEv1 {} {} {} ",
+        payload_type.len(),
+        payload_type,
+        payload.len()
+    )
+    .into_bytes();
+    pae.extend_from_slice(payload);
+    pae
+}
+
+/// The requirements an attestation must satisfy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttestationPolicy {
+    keys: Vec<PublicKey>,
+    threshold: u32,
+    predicate_types: Vec<String>,
+}
+
+impl AttestationPolicy {
+    /// Create a policy that requires an attestation to be signed by at least `threshold` of
+    /// `keys`.
+    pub fn new(keys: Vec<PublicKey>, threshold: u32) -> Result<Self> {
+        if threshold == 0 {
+            return Err(Error::IllegalArgument(
+                "Threshold must be at least 1".into(),
+            ));
+        }
+
+        if (keys.len() as u64) < u64::from(threshold) {
+            return Err(Error::IllegalArgument(format!(
+                "Cannot have a threshold of {} with only {} keys",
+                threshold,
+                keys.len()
+            )));
+        }
+
+        Ok(AttestationPolicy {
+            keys,
+            threshold,
+            predicate_types: vec![],
+        })
+    }
+
+    /// Only accept attestations with one of the given predicate types. By default, every
+    /// predicate type is accepted.
+    pub fn predicate_types<I, S>(mut self, predicate_types: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.predicate_types = predicate_types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// The keys that may sign attestations.
+    pub fn keys(&self) -> &[PublicKey] {
+        &self.keys
+    }
+
+    /// The number of keys that must sign an attestation.
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    /// Verify that the DSSE envelope `envelope` is a valid attestation for the trusted target
+    /// `target`, which is described by `description`. Returns the attested statement.
+    pub fn verify(
+        &self,
+        target: &TargetPath,
+        description: &TargetDescription,
+        envelope: &[u8],
+    ) -> Result<Statement> {
+        let fail = |reason: String| Error::AttestationVerificationFailed {
+            target: target.clone(),
+            reason,
+        };
+
+        let envelope: Envelope = serde_json::from_slice(envelope)
+            .map_err(|err| fail(format!("invalid envelope: {}", err)))?;
+
+        if envelope.payload_type != IN_TOTO_PAYLOAD_TYPE {
+            return Err(fail(format!(
+                "unexpected payload type {:?}",
+                envelope.payload_type
+            )));
+        }
+
+        let payload = decode_base64(&envelope.payload)
+            .ok_or_else(|| fail("payload is not base64 encoded".into()))?;
+        let pae = pre_authentication_encoding(&envelope.payload_type, &payload);
+
+        // Count each key at most once, no matter how many signatures it made.
+        let signatures = envelope
+            .signatures
+            .iter()
+            .filter_map(|sig| decode_base64(&sig.sig))
+            .collect::<Vec<_>>();
+        let signed_by = self
+            .keys
+            .iter()
+            .filter(|key| {
+                signatures.iter().any(|sig| {
+                    let sig =
+                        Signature::new(key.key_id().clone(), SignatureValue::new(sig.clone()));
+                    key.verify(&MetadataPath::targets(), &pae, &sig).is_ok()
+                })
+            })
+            .map(|key| key.key_id())
+            .collect::<HashSet<_>>();
+
+        if (signed_by.len() as u64) < u64::from(self.threshold) {
+            return Err(fail(format!(
+                "signed by {} of the required {} keys",
+                signed_by.len(),
+                self.threshold
+            )));
+        }
+
+        let statement: Statement = serde_json::from_slice(&payload)
+            .map_err(|err| fail(format!("invalid statement: {}", err)))?;
+
+        if !IN_TOTO_STATEMENT_TYPES.contains(&statement.typ.as_str()) {
+            return Err(fail(format!(
+                "unexpected statement type {:?}",
+                statement.typ
+            )));
+        }
+
+        if !self.predicate_types.is_empty()
+            && !self.predicate_types.contains(&statement.predicate_type)
+        {
+            return Err(fail(format!(
+                "predicate type {:?} is not allowed",
+                statement.predicate_type
+            )));
+        }
+
+        if !statement
+            .subject
+            .iter()
+            .any(|subject| subject.matches(description))
+        {
+            return Err(fail("no subject matches the target".into()));
+        }
+
+        Ok(statement)
+    }
+}
+
+fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    BASE64
+        .decode(s.as_bytes())
+        .or_else(|_| BASE64URL.decode(s.as_bytes()))
+        .ok()
+}
+
+/// The path of the attestation for `target`.
+pub fn attestation_path(
+    target: &TargetPath,
+    description: &TargetDescription,
+) -> Result<TargetPath> {
+    match description.custom().get(ATTESTATION_CUSTOM_FIELD) {
+        Some(path) => {
+            let path = path.as_str().ok_or_else(|| {
+                Error::Encoding(format!(
+                    "custom field {:?} must be a string",
+                    ATTESTATION_CUSTOM_FIELD
+                ))
+            })?;
+            TargetPath::new(path)
+        }
+        None => TargetPath::new(format!("{}{}", target.as_str(), ATTESTATION_SUFFIX)),
+    }
+}
+
+impl<D, L, R> Client<D, L, R>
+where
+    D: Pouf,
+    L: RepositoryProvider<D> + RepositoryStorage<D>,
+    R: RepositoryProvider<D>,
+{
+    /// Fetch the attestation for `target` and verify it against `policy`. The attestation is
+    /// located as described in the [attestation](crate::attestation) module, and is verified by
+    /// TUF before it is parsed. Returns the attested statement.
+    pub async fn verify_target_attestation(
+        &mut self,
+        target: &TargetPath,
+        policy: &AttestationPolicy,
+    ) -> Result<Statement> {
+        self.verify_target_attestation_with_start_time(
+            target,
+            policy,
+            &self.database().clock().now(),
+        )
+        .await
+    }
+
+    /// Fetch the attestation for `target` and verify it against `policy`, using the specified time
+    /// to determine if the metadata is expired.
+    ///
+    /// See [Client::verify_target_attestation] for more details.
+    pub async fn verify_target_attestation_with_start_time(
+        &mut self,
+        target: &TargetPath,
+        policy: &AttestationPolicy,
+        start_time: &DateTime<Utc>,
+    ) -> Result<Statement> {
+        let description = self
+            .fetch_target_description_with_start_time(target, start_time)
+            .await?;
+        let path = attestation_path(target, &description)?;
+
+        let mut envelope = Vec::new();
+        self.fetch_target_with_start_time(&path, start_time)
+            .await?
+            .read_to_end(&mut envelope)
+            .await?;
+
+        policy.verify(target, &description, &envelope)
+    }
+
+    /// Verify the attestation for `target` against `policy`, and then fetch the target and
+    /// atomically install it at `path`. Nothing is written if the attestation is rejected.
+    ///
+    /// See [Client::verify_target_attestation] and [Client::fetch_target_to_path] for more
+    /// details.
+    pub async fn fetch_target_to_path_with_attestation<P>(
+        &mut self,
+        target: &TargetPath,
+        path: P,
+        policy: &AttestationPolicy,
+    ) -> Result<Statement>
+    where
+        P: AsRef<Path>,
+    {
+        let start_time = self.database().clock().now();
+        let statement = self
+            .verify_target_attestation_with_start_time(target, policy, &start_time)
+            .await?;
+        self.fetch_target_to_path_with_start_time(target, path, &start_time)
+            .await?;
+        Ok(statement)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::Config;
+    use crate::crypto::Ed25519PrivateKey;
+    use crate::pouf::Pouf1;
+    use crate::repo_builder::RepoBuilder;
+    use crate::repository::EphemeralRepository;
+    use assert_matches::assert_matches;
+    use futures_executor::block_on;
+    use futures_util::io::Cursor;
+    use lazy_static::lazy_static;
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[
+                include_bytes!("../tests/ed25519/ed25519-1.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-2.pk8.der"),
+            ];
+            keys.iter()
+                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+                .collect()
+        };
+    }
+
+    const PROVENANCE: &str = "https://slsa.dev/provenance/v1";
+
+    fn signed_statement(
+        path: &TargetPath,
+        description: &TargetDescription,
+        key: &Ed25519PrivateKey,
+    ) -> Vec<u8> {
+        let statement = Statement::new(
+            vec![Subject::from_target(path, description)],
+            PROVENANCE.into(),
+            serde_json::json!({ "builder": { "id": "https://example.com/builder" } }),
+        );
+        serde_json::to_vec(&Envelope::sign_statement(&statement, &[key]).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn policy_verifies_signatures_subject_and_predicate_type() {
+        let path = TargetPath::new("foo").unwrap();
+        let description = TargetDescription::from_slice(b"foo", &[HashAlgorithm::Sha256]).unwrap();
+        let other = TargetDescription::from_slice(b"bar", &[HashAlgorithm::Sha256]).unwrap();
+        let envelope = signed_statement(&path, &description, &KEYS[0]);
+
+        let policy = AttestationPolicy::new(vec![KEYS[0].public().clone()], 1)
+            .unwrap()
+            .predicate_types([PROVENANCE]);
+        let statement = policy.verify(&path, &description, &envelope).unwrap();
+        assert_eq!(statement.predicate_type(), PROVENANCE);
+
+        // The subject must match the target.
+        assert_matches!(
+            policy.verify(&path, &other, &envelope),
+            Err(Error::AttestationVerificationFailed { .. })
+        );
+
+        // The attestation must be signed by a trusted key.
+        let policy = AttestationPolicy::new(vec![KEYS[1].public().clone()], 1).unwrap();
+        assert_matches!(
+            policy.verify(&path, &description, &envelope),
+            Err(Error::AttestationVerificationFailed { .. })
+        );
+
+        // A key only counts once towards the threshold.
+        let envelope = serde_json::to_vec(
+            &Envelope::sign_statement(
+                &Statement::new(
+                    vec![Subject::from_target(&path, &description)],
+                    PROVENANCE.into(),
+                    serde_json::Value::Null,
+                ),
+                &[&KEYS[0], &KEYS[0]],
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let policy =
+            AttestationPolicy::new(vec![KEYS[0].public().clone(), KEYS[1].public().clone()], 2)
+                .unwrap();
+        assert_matches!(
+            policy.verify(&path, &description, &envelope),
+            Err(Error::AttestationVerificationFailed { .. })
+        );
+
+        // Only the allowed predicate types are accepted.
+        let policy = AttestationPolicy::new(vec![KEYS[0].public().clone()], 1)
+            .unwrap()
+            .predicate_types(["https://example.com/other"]);
+        assert_matches!(
+            policy.verify(&path, &description, &envelope),
+            Err(Error::AttestationVerificationFailed { .. })
+        );
+
+        assert_matches!(
+            AttestationPolicy::new(vec![KEYS[0].public().clone()], 2),
+            Err(Error::IllegalArgument(_))
+        );
+    }
+
+    #[test]
+    fn client_verifies_attestation_before_installing_target() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let target_path = TargetPath::new("foo").unwrap();
+            let target_file: &[u8] = b"foo";
+            let description =
+                TargetDescription::from_slice(target_file, &[HashAlgorithm::Sha256]).unwrap();
+            let attestation_path = TargetPath::new("foo.intoto.json").unwrap();
+            let envelope = signed_statement(&target_path, &description, &KEYS[0]);
+
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target(target_path.clone(), Cursor::new(target_file))
+                .await
+                .unwrap()
+                .add_target(attestation_path, Cursor::new(envelope))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
+
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let dest = temp_dir.path().join("foo");
+
+            // An attestation signed by an untrusted key is rejected, and the target is not
+            // installed.
+            let policy = AttestationPolicy::new(vec![KEYS[1].public().clone()], 1).unwrap();
+            assert_matches!(
+                client
+                    .fetch_target_to_path_with_attestation(&target_path, &dest, &policy)
+                    .await,
+                Err(Error::AttestationVerificationFailed { .. })
+            );
+            assert!(!dest.exists());
+
+            let policy = AttestationPolicy::new(vec![KEYS[0].public().clone()], 1).unwrap();
+            let statement = client
+                .fetch_target_to_path_with_attestation(&target_path, &dest, &policy)
+                .await
+                .unwrap();
+            assert_eq!(statement.subject()[0].name(), "foo");
+            assert_eq!(std::fs::read(&dest).unwrap(), target_file);
+        })
+    }
+}
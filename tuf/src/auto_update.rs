@@ -0,0 +1,282 @@
+//! Periodically update a [Client] in a background task.
+//!
+//! This saves long-running services from each implementing their own polling loop. The client is
+//! moved into a task on the current [Tokio](tokio) runtime, which calls [Client::update] on an
+//! interval and publishes the outcome of each update to an [AutoUpdateHandle]. The client remains
+//! accessible through the handle, for example to fetch targets after an update.
+
+use chrono::{offset::Utc, DateTime};
+use futures_util::future::{self, Either};
+use ring::rand::SystemRandom;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Mutex, MutexGuard, Notify};
+use tokio::task::JoinHandle;
+
+use crate::client::Client;
+use crate::error::Result;
+use crate::pouf::Pouf;
+use crate::repository::{RepositoryProvider, RepositoryStorage};
+use crate::util::random_jitter;
+
+/// Failed updates are retried with exponential backoff, doubling the interval at most this many
+/// times.
+const MAX_BACKOFF_DOUBLINGS: u32 = 5;
+
+impl<D, L, R> Client<D, L, R>
+where
+    D: Pouf + Send + Sync + 'static,
+    L: RepositoryProvider<D> + RepositoryStorage<D> + Send + Sync + 'static,
+    R: RepositoryProvider<D> + Send + Sync + 'static,
+{
+    /// Move the client into a background task that calls [Client::update] immediately, and then
+    /// again every `interval` plus a random delay of up to `jitter`. The jitter spreads out the
+    /// load when many clients are started at the same time.
+    ///
+    /// When an update fails, the delay before the next attempt is doubled for every consecutive
+    /// failure, up to 32 times `interval`, and is reset once an update succeeds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a Tokio runtime.
+    pub fn spawn_auto_update(
+        self,
+        interval: Duration,
+        jitter: Duration,
+    ) -> AutoUpdateHandle<D, L, R> {
+        let client = Arc::new(Mutex::new(self));
+        let stop = Arc::new(Notify::new());
+        let (sender, receiver) = watch::channel(None);
+
+        let task = tokio::spawn(auto_update(
+            Arc::clone(&client),
+            Arc::clone(&stop),
+            sender,
+            interval,
+            jitter,
+        ));
+
+        AutoUpdateHandle {
+            client,
+            stop,
+            receiver,
+            task,
+        }
+    }
+}
+
+async fn auto_update<D, L, R>(
+    client: Arc<Mutex<Client<D, L, R>>>,
+    stop: Arc<Notify>,
+    sender: watch::Sender<Option<Arc<AutoUpdateResult>>>,
+    interval: Duration,
+    jitter: Duration,
+) where
+    D: Pouf + Send + Sync + 'static,
+    L: RepositoryProvider<D> + RepositoryStorage<D> + Send + Sync + 'static,
+    R: RepositoryProvider<D> + Send + Sync + 'static,
+{
+    let rng = SystemRandom::new();
+    let mut failures = 0;
+
+    loop {
+        let (finished_at, result) = {
+            let mut client = client.lock().await;
+            let result = client.update().await;
+            (client.database().clock().now(), result)
+        };
+
+        failures = if result.is_ok() {
+            0
+        } else {
+            (failures + 1).min(MAX_BACKOFF_DOUBLINGS)
+        };
+
+        // Stop once the handle and all of the subscribed receivers have been dropped.
+        if sender
+            .send(Some(Arc::new(AutoUpdateResult {
+                finished_at,
+                result,
+            })))
+            .is_err()
+        {
+            return;
+        }
+
+        let delay = backoff(interval, failures).saturating_add(random_jitter(&rng, jitter));
+        let sleep = tokio::time::sleep(delay);
+        let stopped = stop.notified();
+        futures_util::pin_mut!(sleep, stopped);
+
+        if let Either::Right(_) = future::select(sleep, stopped).await {
+            return;
+        }
+    }
+}
+
+/// The delay before the next update after `failures` consecutive failed updates. This saturates
+/// rather than overflows, so very long intervals such as [Duration::MAX] can be used to update
+/// rarely.
+fn backoff(interval: Duration, failures: u32) -> Duration {
+    interval.saturating_mul(2u32.pow(failures))
+}
+
+/// The outcome of an update that was run by [Client::spawn_auto_update].
+#[derive(Debug)]
+pub struct AutoUpdateResult {
+    finished_at: DateTime<Utc>,
+    result: Result<bool>,
+}
+
+impl AutoUpdateResult {
+    /// When the update finished, according to the [Clock](crate::clock::Clock) of the client.
+    pub fn finished_at(&self) -> &DateTime<Utc> {
+        &self.finished_at
+    }
+
+    /// The result returned by [Client::update].
+    pub fn result(&self) -> &Result<bool> {
+        &self.result
+    }
+}
+
+/// A handle to a [Client] that is being updated in the background by
+/// [Client::spawn_auto_update].
+///
+/// Dropping the handle, along with any receivers returned by [AutoUpdateHandle::subscribe], stops
+/// the background task after its next update.
+#[derive(Debug)]
+pub struct AutoUpdateHandle<D, L, R>
+where
+    D: Pouf,
+    L: RepositoryProvider<D> + RepositoryStorage<D>,
+    R: RepositoryProvider<D>,
+{
+    client: Arc<Mutex<Client<D, L, R>>>,
+    stop: Arc<Notify>,
+    receiver: watch::Receiver<Option<Arc<AutoUpdateResult>>>,
+    task: JoinHandle<()>,
+}
+
+impl<D, L, R> AutoUpdateHandle<D, L, R>
+where
+    D: Pouf,
+    L: RepositoryProvider<D> + RepositoryStorage<D>,
+    R: RepositoryProvider<D>,
+{
+    /// The outcome of the most recent update, or `None` if the first update has not finished yet.
+    pub fn latest(&self) -> Option<Arc<AutoUpdateResult>> {
+        self.receiver.borrow().clone()
+    }
+
+    /// Subscribe to the outcome of every update. The receiver is notified each time an update
+    /// finishes.
+    pub fn subscribe(&self) -> watch::Receiver<Option<Arc<AutoUpdateResult>>> {
+        self.receiver.clone()
+    }
+
+    /// Lock the client, waiting for the update in progress, if any, to finish. Updates are paused
+    /// while the lock is held.
+    pub async fn client(&self) -> MutexGuard<'_, Client<D, L, R>> {
+        self.client.lock().await
+    }
+
+    /// Stop the background task and return the client. This waits for the update in progress, if
+    /// any, to finish, so an update is never interrupted.
+    pub async fn stop(self) -> Client<D, L, R> {
+        self.stop.notify_one();
+        let _ = self.task.await;
+
+        match Arc::try_unwrap(self.client) {
+            Ok(client) => client.into_inner(),
+            Err(_) => unreachable!("the auto update task has exited"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::Config;
+    use crate::crypto::Ed25519PrivateKey;
+    use crate::metadata::TargetPath;
+    use crate::pouf::Pouf1;
+    use crate::repo_builder::RepoBuilder;
+    use crate::repository::EphemeralRepository;
+    use assert_matches::assert_matches;
+    use futures_util::io::Cursor;
+    use lazy_static::lazy_static;
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[include_bytes!("../tests/ed25519/ed25519-1.pk8.der")];
+            keys.iter()
+                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+                .collect()
+        };
+    }
+
+    #[test]
+    fn backoff_saturates() {
+        assert_eq!(backoff(Duration::from_secs(60), 0), Duration::from_secs(60));
+        assert_eq!(
+            backoff(Duration::from_secs(60), 3),
+            Duration::from_secs(480)
+        );
+        assert_eq!(backoff(Duration::MAX, MAX_BACKOFF_DOUBLINGS), Duration::MAX);
+    }
+
+    #[test]
+    fn auto_update_publishes_results() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let target_path = TargetPath::new("foo").unwrap();
+
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target(target_path.clone(), Cursor::new(&b"foo"[..]))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+
+            let handle =
+                client.spawn_auto_update(Duration::from_secs(3600), Duration::from_secs(60));
+
+            let mut receiver = handle.subscribe();
+            receiver.changed().await.unwrap();
+            let latest = handle.latest().unwrap();
+            assert_matches!(latest.result(), Ok(true));
+
+            assert_matches!(
+                handle
+                    .client()
+                    .await
+                    .fetch_target_description(&target_path)
+                    .await,
+                Ok(_)
+            );
+
+            let client = handle.stop().await;
+            assert!(client.database().trusted_targets().is_some());
+        });
+    }
+}
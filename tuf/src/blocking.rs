@@ -0,0 +1,376 @@
+//! A blocking wrapper around the asynchronous [Client](crate::client::Client).
+//!
+//! This is intended for command line tools and build scripts that do not otherwise use an async
+//! runtime. Each [Client] owns a single threaded runtime that is used to drive the underlying
+//! asynchronous client, so repositories that depend on a reactor (such as
+//! [HttpRepository](crate::repository::HttpRepository)) work without any additional setup.
+//!
+//! These methods must not be called from within an async context, since they block the current
+//! thread until the operation completes.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # #[cfg(feature = "hyper")]
+//! # use hyper::client::Client as HttpClient;
+//! # use std::path::PathBuf;
+//! # use tuf::Result;
+//! # use tuf::blocking::Client;
+//! # use tuf::client::Config;
+//! # use tuf::crypto::PublicKey;
+//! # use tuf::metadata::{MetadataVersion, TargetPath};
+//! # use tuf::pouf::Pouf1;
+//! # use tuf::repository::FileSystemRepository;
+//! # #[cfg(feature = "hyper")]
+//! # use tuf::repository::HttpRepositoryBuilder;
+//! #
+//! # const PUBLIC_KEY: &'static [u8] = include_bytes!("../tests/ed25519/ed25519-1.pub");
+//! #
+//! # #[cfg(not(feature = "hyper"))]
+//! # fn main() {}
+//! #
+//! # #[cfg(feature = "hyper")]
+//! # fn main() -> Result<()> {
+//! let root_public_keys = vec![PublicKey::from_ed25519(PUBLIC_KEY)?];
+//! let local = FileSystemRepository::<Pouf1>::new(PathBuf::from("~/.rustup"));
+//!
+//! let remote = HttpRepositoryBuilder::new_with_uri(
+//!     "https://static.rust-lang.org/".parse::<http::Uri>().unwrap(),
+//!     HttpClient::new(),
+//! )
+//! .build();
+//!
+//! let mut client = Client::with_trusted_root_keys(
+//!     Config::default(),
+//!     MetadataVersion::Number(1),
+//!     1,
+//!     &root_public_keys,
+//!     local,
+//!     remote,
+//! )?;
+//!
+//! let _ = client.update()?;
+//! client.fetch_target_to_path(&TargetPath::new("foo")?, "/tmp/foo")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use chrono::{offset::Utc, DateTime};
+use futures_io::AsyncRead;
+use futures_util::io::AsyncReadExt as _;
+use std::io::{self, Read};
+use std::path::Path;
+use tokio::runtime::{Builder, Runtime};
+
+use crate::client::{self, Config, Parts};
+use crate::crypto::PublicKey;
+use crate::database::Database;
+use crate::error::Result;
+use crate::metadata::{
+    MetadataVersion, RawSignedMetadata, RootMetadata, TargetDescription, TargetPath,
+};
+use crate::pouf::Pouf;
+use crate::repository::{RepositoryProvider, RepositoryStorage};
+
+/// A blocking client that interacts with TUF repositories.
+///
+/// See [client::Client] for details on each of the operations.
+#[derive(Debug)]
+pub struct Client<D, L, R>
+where
+    D: Pouf,
+    L: RepositoryProvider<D> + RepositoryStorage<D>,
+    R: RepositoryProvider<D>,
+{
+    runtime: Runtime,
+    client: client::Client<D, L, R>,
+}
+
+impl<D, L, R> Client<D, L, R>
+where
+    D: Pouf,
+    L: RepositoryProvider<D> + RepositoryStorage<D>,
+    R: RepositoryProvider<D>,
+{
+    /// Create a new TUF client from the trusted root stored in the local repository.
+    ///
+    /// See [client::Client::with_trusted_local].
+    pub fn with_trusted_local(config: Config, local: L, remote: R) -> Result<Self> {
+        let runtime = new_runtime()?;
+        let client = runtime.block_on(client::Client::with_trusted_local(config, local, remote))?;
+        Ok(Self { runtime, client })
+    }
+
+    /// Create a new TUF client from the given trusted root metadata.
+    ///
+    /// See [client::Client::with_trusted_root].
+    pub fn with_trusted_root(
+        config: Config,
+        trusted_root: &RawSignedMetadata<D, RootMetadata>,
+        local: L,
+        remote: R,
+    ) -> Result<Self> {
+        let runtime = new_runtime()?;
+        let client = runtime.block_on(client::Client::with_trusted_root(
+            config,
+            trusted_root,
+            local,
+            remote,
+        ))?;
+        Ok(Self { runtime, client })
+    }
+
+    /// Create a new TUF client, using the provided keys to pin the verification of the initial
+    /// root metadata.
+    ///
+    /// See [client::Client::with_trusted_root_keys].
+    pub fn with_trusted_root_keys<'a, I>(
+        config: Config,
+        root_version: MetadataVersion,
+        root_threshold: u32,
+        trusted_root_keys: I,
+        local: L,
+        remote: R,
+    ) -> Result<Self>
+    where
+        I: IntoIterator<Item = &'a PublicKey>,
+    {
+        let runtime = new_runtime()?;
+        let client = runtime.block_on(client::Client::with_trusted_root_keys(
+            config,
+            root_version,
+            root_threshold,
+            trusted_root_keys,
+            local,
+            remote,
+        ))?;
+        Ok(Self { runtime, client })
+    }
+
+    /// Create a new TUF client that will trust and update the TUF database.
+    ///
+    /// See [client::Client::from_database].
+    pub fn from_database(config: Config, tuf: Database<D>, local: L, remote: R) -> Result<Self> {
+        Ok(Self {
+            runtime: new_runtime()?,
+            client: client::Client::from_database(config, tuf, local, remote),
+        })
+    }
+
+    /// Construct a client with the given parts.
+    ///
+    /// See [client::Client::from_parts].
+    pub fn from_parts(parts: Parts<D, L, R>) -> Result<Self> {
+        Ok(Self {
+            runtime: new_runtime()?,
+            client: client::Client::from_parts(parts),
+        })
+    }
+
+    /// Update TUF metadata from the remote repository.
+    ///
+    /// Returns `true` if an update occurred and `false` otherwise.
+    pub fn update(&mut self) -> Result<bool> {
+        self.runtime.block_on(self.client.update())
+    }
+
+    /// Update TUF metadata from the remote repository, using the specified time to determine if
+    /// the metadata is expired.
+    ///
+    /// Returns `true` if an update occurred and `false` otherwise.
+    pub fn update_with_start_time(&mut self, start_time: &DateTime<Utc>) -> Result<bool> {
+        self.runtime
+            .block_on(self.client.update_with_start_time(start_time))
+    }
+
+    /// Fetch a target from the remote repo.
+    ///
+    /// It is **critical** that none of the bytes read from the returned [TargetReader] are used
+    /// until it has been fully consumed, as the hash of the target is not verified until all bytes
+    /// are read from the repository.
+    pub fn fetch_target(
+        &mut self,
+        target: &TargetPath,
+    ) -> Result<TargetReader<'_, impl AsyncRead + Send + Unpin + '_>> {
+        let start_time = self.client.database().clock().now();
+        self.fetch_target_with_start_time(target, &start_time)
+    }
+
+    /// Fetch a target from the remote repo.
+    ///
+    /// See [Client::fetch_target] for more details.
+    pub fn fetch_target_with_start_time(
+        &mut self,
+        target: &TargetPath,
+        start_time: &DateTime<Utc>,
+    ) -> Result<TargetReader<'_, impl AsyncRead + Send + Unpin + '_>> {
+        let runtime = &self.runtime;
+        let read =
+            runtime.block_on(self.client.fetch_target_with_start_time(target, start_time))?;
+
+        Ok(TargetReader { runtime, read })
+    }
+
+    /// Fetch a target from the remote repo and write it to the local repo.
+    pub fn fetch_target_to_local(&mut self, target: &TargetPath) -> Result<()> {
+        self.runtime
+            .block_on(self.client.fetch_target_to_local(target))
+    }
+
+    /// Fetch a target from the remote repo, verify it, and atomically install it at `path`.
+    ///
+    /// See [client::Client::fetch_target_to_path].
+    pub fn fetch_target_to_path<P>(&mut self, target: &TargetPath, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        self.runtime
+            .block_on(self.client.fetch_target_to_path(target, path))
+    }
+
+    /// Fetch the trusted description of a target, updating delegated targets metadata as needed.
+    pub fn fetch_target_description(&mut self, target: &TargetPath) -> Result<TargetDescription> {
+        self.runtime
+            .block_on(self.client.fetch_target_description(target))
+    }
+
+    /// Access the trusted TUF database.
+    pub fn database(&self) -> &Database<D> {
+        self.client.database()
+    }
+
+    /// Access the local repository.
+    pub fn local_repo(&self) -> &L {
+        self.client.local_repo()
+    }
+
+    /// Access the remote repository.
+    pub fn remote_repo(&self) -> &R {
+        self.client.remote_repo()
+    }
+
+    /// Access the underlying asynchronous client.
+    pub fn as_async(&mut self) -> &mut client::Client<D, L, R> {
+        &mut self.client
+    }
+
+    /// Consumes the blocking client and returns the underlying asynchronous client.
+    pub fn into_async(self) -> client::Client<D, L, R> {
+        self.client
+    }
+
+    /// Consumes the client and returns the complete state of the client.
+    pub fn into_parts(self) -> Parts<D, L, R> {
+        self.client.into_parts()
+    }
+}
+
+/// A blocking reader for a target that is being fetched from a remote repository.
+///
+/// See [client::TargetReader] for details.
+#[derive(Debug)]
+pub struct TargetReader<'a, R> {
+    runtime: &'a Runtime,
+    read: client::TargetReader<R>,
+}
+
+impl<'a, R> TargetReader<'a, R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// The trusted description of the target.
+    pub fn target_description(&self) -> &TargetDescription {
+        self.read.target_description()
+    }
+
+    /// The expected length of the target, if the target description specifies it.
+    pub fn length(&self) -> Option<u64> {
+        self.read.length()
+    }
+}
+
+impl<'a, R> Read for TargetReader<'a, R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.runtime.block_on(self.read.read(buf))
+    }
+}
+
+fn new_runtime() -> Result<Runtime> {
+    Ok(Builder::new_current_thread().enable_all().build()?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::Ed25519PrivateKey;
+    use crate::pouf::Pouf1;
+    use crate::repo_builder::RepoBuilder;
+    use crate::repository::EphemeralRepository;
+    use assert_matches::assert_matches;
+    use futures_executor::block_on;
+    use futures_util::io::Cursor;
+    use lazy_static::lazy_static;
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[
+                include_bytes!("../tests/ed25519/ed25519-1.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-2.pk8.der"),
+            ];
+            keys.iter()
+                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+                .collect()
+        };
+    }
+
+    #[test]
+    fn blocking_client_update_and_fetch_target() {
+        let mut remote = EphemeralRepository::<Pouf1>::new();
+        let target_path = TargetPath::new("foo/bar").unwrap();
+        let target_file: &[u8] = b"things fade, alternatives exclude";
+
+        let metadata = block_on(async {
+            RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target(target_path.clone(), Cursor::new(target_file))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap()
+        });
+
+        let mut client = Client::with_trusted_root(
+            Config::default(),
+            metadata.root().unwrap(),
+            EphemeralRepository::new(),
+            remote,
+        )
+        .unwrap();
+
+        assert_matches!(client.update(), Ok(true));
+        assert_matches!(client.update(), Ok(false));
+
+        let mut reader = client.fetch_target(&target_path).unwrap();
+        assert_eq!(reader.length(), Some(target_file.len() as u64));
+
+        let mut buf = vec![];
+        reader.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, target_file);
+        drop(reader);
+
+        let temp_dir = tempfile::Builder::new()
+            .prefix("rust-tuf")
+            .tempdir()
+            .unwrap();
+        let dest = temp_dir.path().join("bar");
+        client.fetch_target_to_path(&target_path, &dest).unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), target_file);
+    }
+}
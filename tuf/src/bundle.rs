@@ -0,0 +1,819 @@
+//! Offline bundles of a whole repository, for delivering updates to air-gapped sites.
+//!
+//! [export_bundle] verifies the current metadata of a repository with a [Client], then packages
+//! the chain of root metadata, every other trusted metadata, and every target they describe into
+//! a gzip compressed tar archive. The first entry of the archive is a [BundleManifest], which
+//! lists the length and hashes of every other entry.
+//!
+//! Entries are laid out like a [FileSystemRepository](crate::repository::FileSystemRepository)
+//! with the default [RepositoryLayout], under the `metadata` and `targets` prefixes, so an
+//! extracted bundle can be served as is. [import_bundle] instead copies the entries into any
+//! [RepositoryStorage], checking each one against the manifest as it is read, and an
+//! [ArchiveRepository] serves the entries of a bundle to a [Client] directly.
+//!
+//! The manifest is not signed. Clients at the site must still verify the imported metadata from
+//! a trusted root, as with any other repository.
+
+use async_compression::futures::bufread::GzipDecoder;
+use async_compression::futures::write::GzipEncoder;
+use async_tar::{Archive, Builder, EntryType, Header};
+use futures_io::{AsyncRead, AsyncWrite};
+use futures_util::future::BoxFuture;
+use futures_util::io::{AsyncReadExt as _, AsyncWriteExt as _, BufReader};
+use futures_util::stream::StreamExt as _;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path};
+
+use crate::client::{Client, Config};
+use crate::crypto::{self, HashAlgorithm, HashValue};
+use crate::error::{Error, Result};
+use crate::metadata::{
+    Metadata as _, MetadataPath, MetadataVersion, RawSignedMetadata, RootMetadata,
+    TargetDescription, TargetPath,
+};
+use crate::pouf::Pouf;
+use crate::repository::{
+    EphemeralRepository, Repository, RepositoryLayout, RepositoryProvider, RepositoryStorage,
+};
+use crate::util::SafeAsyncRead as _;
+
+/// The name of the manifest entry, which is always the first entry of a bundle.
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+/// The manifest of a bundle is read into memory, so it may be at most this long.
+const MAX_MANIFEST_LENGTH: u64 = 64 * 1024 * 1024;
+
+const METADATA_PREFIX: &str = "metadata";
+const TARGETS_PREFIX: &str = "targets";
+
+/// Lists every metadata and target in a bundle, in the order they appear in it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleManifest {
+    metadata: Vec<BundledMetadata>,
+    targets: Vec<BundledTarget>,
+}
+
+impl BundleManifest {
+    /// The metadata in the bundle.
+    pub fn metadata(&self) -> &[BundledMetadata] {
+        &self.metadata
+    }
+
+    /// The targets in the bundle.
+    pub fn targets(&self) -> &[BundledTarget] {
+        &self.targets
+    }
+}
+
+/// A metadata in a bundle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundledMetadata {
+    name: String,
+    role: MetadataPath,
+    version: Option<u32>,
+    length: u64,
+    hashes: HashMap<HashAlgorithm, HashValue>,
+}
+
+impl BundledMetadata {
+    /// The name of the entry in the bundle.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The role of the metadata.
+    pub fn role(&self) -> &MetadataPath {
+        &self.role
+    }
+
+    /// The version the metadata is stored as.
+    pub fn version(&self) -> MetadataVersion {
+        match self.version {
+            Some(version) => MetadataVersion::Number(version),
+            None => MetadataVersion::None,
+        }
+    }
+
+    /// The length of the metadata in bytes.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// The hashes of the metadata.
+    pub fn hashes(&self) -> &HashMap<HashAlgorithm, HashValue> {
+        &self.hashes
+    }
+}
+
+/// A target in a bundle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundledTarget {
+    name: String,
+    path: TargetPath,
+    length: u64,
+    hashes: HashMap<HashAlgorithm, HashValue>,
+}
+
+impl BundledTarget {
+    /// The name of the entry in the bundle.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The path the target is stored at, which includes the hash prefix when the repository uses
+    /// consistent snapshots.
+    pub fn path(&self) -> &TargetPath {
+        &self.path
+    }
+
+    /// The length of the target in bytes.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// The hashes of the target, as described by the trusted targets metadata.
+    pub fn hashes(&self) -> &HashMap<HashAlgorithm, HashValue> {
+        &self.hashes
+    }
+}
+
+/// Write a bundle of the repository `repo` to `writer`, and return its manifest. The metadata is
+/// updated and verified with a [Client] that starts from the trusted `root`, and the bundle
+/// includes every version of the root metadata from `root` to the latest one, so pass the first
+/// root metadata of the repository for sites that don't trust any root yet.
+///
+/// Every target described by the trusted metadata is fetched from `repo` and checked against its
+/// description while it is written. Targets that can't be verified, because none of their hashes
+/// is supported, are left out. The entries are ordered like the repository is updated: the
+/// targets first, then the metadata, ending with the timestamp metadata.
+///
+/// Returns an error if the metadata of any reachable delegated role can't be fetched or verified,
+/// rather than writing a bundle without its targets.
+///
+/// `writer` is closed once the bundle is written. If an error is returned, the bundle may have
+/// been partially written, and must be discarded.
+pub async fn export_bundle<D, R, W>(
+    repo: R,
+    root: &RawSignedMetadata<D, RootMetadata>,
+    writer: W,
+) -> Result<BundleManifest>
+where
+    D: Pouf,
+    R: RepositoryProvider<D>,
+    W: AsyncWrite + Unpin + Send + Sync,
+{
+    let mut client =
+        Client::with_trusted_root(Config::default(), root, EphemeralRepository::new(), repo)
+            .await?;
+    client.update().await?;
+
+    // Listing the targets fetches the metadata of every reachable delegated role.
+    let _ = client.trusted_targets_iter_strict().await?;
+
+    let parts = client.into_parts();
+    let db = parts.database;
+    let repo = Repository::<_, D>::new(parts.remote);
+    let consistent_snapshot = db.trusted_root().consistent_snapshot();
+    let raw = db.to_raw_metadata_set();
+
+    // Collect the metadata, in the order it is written.
+    let mut metadata: Vec<(MetadataPath, MetadataVersion, &[u8])> = vec![];
+    for raw_root in raw.root_history() {
+        let version = raw_root.parse_untrusted()?.assume_valid()?.version();
+        metadata.push((
+            MetadataPath::root(),
+            MetadataVersion::Number(version),
+            raw_root.as_bytes(),
+        ));
+    }
+    metadata.push((
+        MetadataPath::root(),
+        MetadataVersion::None,
+        raw.root().as_bytes(),
+    ));
+
+    let mut versioned = vec![];
+    for (role, raw_delegation) in raw.delegations() {
+        if let Some(delegation) = db.trusted_delegation(role) {
+            versioned.push((
+                role.clone(),
+                delegation.version(),
+                raw_delegation.as_bytes(),
+            ));
+        }
+    }
+    if let (Some(targets), Some(raw_targets)) = (db.trusted_targets(), raw.targets()) {
+        versioned.push((
+            MetadataPath::targets(),
+            targets.version(),
+            raw_targets.as_bytes(),
+        ));
+    }
+    if let (Some(snapshot), Some(raw_snapshot)) = (db.trusted_snapshot(), raw.snapshot()) {
+        versioned.push((
+            MetadataPath::snapshot(),
+            snapshot.version(),
+            raw_snapshot.as_bytes(),
+        ));
+    }
+    for (role, version, bytes) in versioned {
+        if consistent_snapshot {
+            metadata.push((role.clone(), MetadataVersion::Number(version), bytes));
+        }
+        metadata.push((role, MetadataVersion::None, bytes));
+    }
+    if let Some(raw_timestamp) = raw.timestamp() {
+        metadata.push((
+            MetadataPath::timestamp(),
+            MetadataVersion::None,
+            raw_timestamp.as_bytes(),
+        ));
+    }
+
+    // Collect the targets described by the top-level targets metadata, and then by every
+    // delegated role.
+    let mut descriptions = vec![];
+    if let Some(targets) = db.trusted_targets() {
+        descriptions.extend(targets.targets().iter());
+    }
+    let mut roles = db.trusted_delegations().iter().collect::<Vec<_>>();
+    roles.sort_by(|a, b| a.0.cmp(b.0));
+    for (_, delegation) in roles {
+        descriptions.extend(delegation.targets().iter());
+    }
+
+    let mut targets: BTreeMap<TargetPath, TargetDescription> = BTreeMap::new();
+    for (target_path, description) in descriptions {
+        if !description.is_verifiable() {
+            continue;
+        }
+
+        let stored_paths = if consistent_snapshot {
+            description
+                .hashes()
+                .values()
+                .map(|hash| target_path.with_hash_prefix(hash))
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            vec![target_path.clone()]
+        };
+
+        for stored_path in stored_paths {
+            match targets.get(&stored_path) {
+                Some(existing) if existing != description => {
+                    return Err(Error::IllegalArgument(format!(
+                        "target {} is described differently by several roles",
+                        stored_path
+                    )));
+                }
+                Some(_) => {}
+                None => {
+                    let _ = targets.insert(stored_path, description.clone());
+                }
+            }
+        }
+    }
+
+    let layout = RepositoryLayout::new();
+    let manifest = BundleManifest {
+        metadata: metadata
+            .iter()
+            .map(|(role, version, bytes)| {
+                Ok(BundledMetadata {
+                    name: entry_name(
+                        METADATA_PREFIX,
+                        layout.metadata_components::<D>(role, *version),
+                    ),
+                    role: role.clone(),
+                    version: match version {
+                        MetadataVersion::Number(version) => Some(*version),
+                        MetadataVersion::None => None,
+                    },
+                    length: bytes.len() as u64,
+                    hashes: crypto::calculate_hashes_from_slice(bytes, &[HashAlgorithm::Sha256])?,
+                })
+            })
+            .collect::<Result<_>>()?,
+        targets: targets
+            .iter()
+            .map(|(stored_path, description)| {
+                Ok(BundledTarget {
+                    name: entry_name(TARGETS_PREFIX, layout.target_components(stored_path)),
+                    path: stored_path.clone(),
+                    // Unverifiable targets were skipped above, so every target has a length.
+                    length: description
+                        .length()
+                        .ok_or_else(|| Error::UnverifiableTarget(stored_path.clone()))?,
+                    hashes: description.hashes().clone(),
+                })
+            })
+            .collect::<Result<_>>()?,
+    };
+
+    let mut builder = Builder::new(GzipEncoder::new(writer));
+
+    let manifest_bytes = serde_json::to_vec_pretty(&manifest)?;
+    builder
+        .append_data(
+            &mut entry_header(manifest_bytes.len() as u64),
+            MANIFEST_NAME,
+            &manifest_bytes[..],
+        )
+        .await?;
+
+    for (entry, description) in manifest.targets.iter().zip(targets.values()) {
+        // The stored path already has its hash prefix, so fetch it as is. The target is checked
+        // against its description as it is read, so a modified target fails the export.
+        let reader = repo
+            .fetch_target(false, &entry.path, description.clone())
+            .await?;
+        builder
+            .append_data(&mut entry_header(entry.length), &entry.name, reader)
+            .await?;
+    }
+
+    for (entry, (_, _, bytes)) in manifest.metadata.iter().zip(&metadata) {
+        builder
+            .append_data(&mut entry_header(entry.length), &entry.name, *bytes)
+            .await?;
+    }
+
+    let mut writer = builder.into_inner().await?;
+    writer.close().await?;
+
+    Ok(manifest)
+}
+
+/// Read a bundle written by [export_bundle] from `reader`, and store its metadata and targets in
+/// `storage`, in the order they appear in the bundle. Every entry is checked against the length
+/// and hashes listed in the manifest while it is stored, and the manifest is returned once every
+/// entry it lists was stored.
+///
+/// Returns an error if the bundle contains an entry that is not listed in the manifest, is
+/// missing an entry, or if an entry doesn't match the manifest. Entries stored before the error
+/// was found are left in `storage`.
+pub async fn import_bundle<D, R, Rd>(reader: Rd, storage: &R) -> Result<BundleManifest>
+where
+    D: Pouf,
+    R: RepositoryStorage<D>,
+    Rd: AsyncRead + Send + Unpin,
+{
+    let mut entries = Archive::new(GzipDecoder::new(BufReader::new(reader))).entries()?;
+
+    let manifest: BundleManifest = match entries.next().await.transpose()? {
+        Some(mut entry) if entry_name_of(&*entry.path()?)? == MANIFEST_NAME => {
+            let size = entry_size(&entry)?;
+            if size > MAX_MANIFEST_LENGTH {
+                return Err(Error::Encoding(format!(
+                    "bundle manifest is larger than {} bytes",
+                    MAX_MANIFEST_LENGTH
+                )));
+            }
+
+            let mut buf = vec![];
+            let _ = (&mut entry).take(size).read_to_end(&mut buf).await?;
+            if buf.len() as u64 != size {
+                return Err(Error::Encoding("bundle manifest is truncated".into()));
+            }
+
+            serde_json::from_slice(&buf)?
+        }
+        _ => {
+            return Err(Error::Encoding(format!(
+                "bundle must start with {}",
+                MANIFEST_NAME
+            )))
+        }
+    };
+
+    let mut metadata = manifest
+        .metadata
+        .iter()
+        .map(|entry| (entry.name.as_str(), entry))
+        .collect::<HashMap<_, _>>();
+    let mut targets = manifest
+        .targets
+        .iter()
+        .map(|entry| (entry.name.as_str(), entry))
+        .collect::<HashMap<_, _>>();
+
+    while let Some(mut entry) = entries.next().await.transpose()? {
+        let name = entry_name_of(&*entry.path()?)?;
+        let size = entry_size(&entry)?;
+        let (length, hashes) = if let Some(entry) = metadata.get(name.as_str()) {
+            (entry.length, &entry.hashes)
+        } else if let Some(entry) = targets.get(name.as_str()) {
+            (entry.length, &entry.hashes)
+        } else {
+            return Err(Error::Encoding(format!(
+                "bundle entry {} is not listed in the manifest, or is listed more than once",
+                name
+            )));
+        };
+
+        if size != length {
+            return Err(Error::Encoding(format!(
+                "bundle entry {} is {} bytes long, but the manifest lists {} bytes",
+                name, size, length
+            )));
+        }
+
+        let hash_data = crypto::retain_supported_hashes(hashes);
+        if hash_data.is_empty() {
+            return Err(Error::Encoding(format!(
+                "bundle entry {} has no supported hashes",
+                name
+            )));
+        }
+
+        let mut take = (&mut entry).take(size);
+        {
+            let mut entry_reader = (&mut take).check_length_and_hash(size, hash_data)?;
+            if let Some(entry) = metadata.remove(name.as_str()) {
+                storage
+                    .store_metadata(&entry.role, entry.version(), &mut entry_reader)
+                    .await?;
+            } else if let Some(entry) = targets.remove(name.as_str()) {
+                storage.store_target(&entry.path, &mut entry_reader).await?;
+            }
+        }
+
+        // The hashes are only checked once the entry is read to its end.
+        if take.limit() != 0 {
+            return Err(Error::Encoding(format!(
+                "bundle entry {} was not read to its end",
+                name
+            )));
+        }
+    }
+
+    if let Some(name) = metadata.keys().chain(targets.keys()).next() {
+        return Err(Error::Encoding(format!(
+            "bundle is missing the entry {}",
+            name
+        )));
+    }
+
+    Ok(manifest)
+}
+
+fn entry_name(prefix: &str, components: Vec<String>) -> String {
+    let mut name = prefix.to_string();
+    for component in components {
+        name.push('/');
+        name.push_str(&component);
+    }
+    name
+}
+
+/// The header of a regular file of `size` bytes. The modification time is always zero, so bundles
+/// of the same repository are identical.
+fn entry_header(size: u64) -> Header {
+    let mut header = Header::new_gnu();
+    header.set_entry_type(EntryType::Regular);
+    header.set_mode(0o644);
+    header.set_mtime(0);
+    header.set_size(size);
+    header
+}
+
+/// The name of the entry at `path`, with its components separated by `/` on every platform.
+fn entry_name_of<P>(path: &P) -> Result<String>
+where
+    P: AsRef<Path> + ?Sized,
+{
+    let path = path.as_ref();
+    let mut components = vec![];
+    for component in path.components() {
+        match component {
+            Component::Normal(component) => components.push(
+                component
+                    .to_str()
+                    .ok_or_else(|| Error::Encoding("invalid name in bundle".into()))?,
+            ),
+            _ => {
+                return Err(Error::Encoding(format!(
+                    "invalid name {:?} in bundle",
+                    path
+                )))
+            }
+        }
+    }
+    Ok(components.join("/"))
+}
+
+/// The size of a regular file entry of a bundle.
+fn entry_size<R>(entry: &async_tar::Entry<R>) -> Result<u64>
+where
+    R: AsyncRead + Unpin,
+{
+    let header = entry.header();
+    if !header.entry_type().is_file() {
+        return Err(Error::Encoding(format!(
+            "unsupported entry type {:?} in bundle",
+            header.entry_type()
+        )));
+    }
+    Ok(header.size()?)
+}
+
+/// A [RepositoryProvider] that serves the metadata and targets of a bundle written by
+/// [export_bundle], such as to update a [Client] at a site that doesn't run a repository.
+///
+/// The entries of the bundle are read into memory when it is opened, and each one is checked
+/// against the manifest as it is read, like [import_bundle] does. The manifest is not signed, so
+/// the metadata is still verified by the [Client], as with any other repository.
+#[derive(Debug)]
+pub struct ArchiveRepository<D> {
+    repo: EphemeralRepository<D>,
+    manifest: BundleManifest,
+}
+
+impl<D> ArchiveRepository<D>
+where
+    D: Pouf,
+{
+    /// Read the bundle from `reader`.
+    pub async fn new<R>(reader: R) -> Result<Self>
+    where
+        R: AsyncRead + Send + Unpin,
+    {
+        let repo = EphemeralRepository::new();
+        let manifest = import_bundle(reader, &repo).await?;
+        Ok(Self { repo, manifest })
+    }
+
+    /// The manifest of the bundle.
+    pub fn manifest(&self) -> &BundleManifest {
+        &self.manifest
+    }
+}
+
+impl<D> RepositoryProvider<D> for ArchiveRepository<D>
+where
+    D: Pouf,
+{
+    fn fetch_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        self.repo.fetch_metadata(meta_path, version)
+    }
+
+    fn fetch_target<'a>(
+        &'a self,
+        target_path: &TargetPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        self.repo.fetch_target(target_path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{Ed25519PrivateKey, PrivateKey as _};
+    use crate::metadata::Delegation;
+    use crate::pouf::Pouf1;
+    use crate::repo_builder::RepoBuilder;
+    use assert_matches::assert_matches;
+    use futures_executor::block_on;
+    use futures_util::io::{AsyncReadExt, AsyncWriteExt, Cursor};
+    use lazy_static::lazy_static;
+    use pretty_assertions::assert_eq;
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[include_bytes!("../tests/ed25519/ed25519-1.pk8.der")];
+            keys.iter()
+                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+                .collect()
+        };
+    }
+
+    #[test]
+    fn long_entry_names_round_trip() {
+        block_on(async {
+            let long_name = format!("targets/{}/{}", "a".repeat(120), "b".repeat(90));
+            let mut builder = Builder::new(vec![]);
+            builder
+                .append_data(&mut entry_header(3), &long_name, &b"foo"[..])
+                .await
+                .unwrap();
+            let archive = builder.into_inner().await.unwrap();
+
+            let mut entries = Archive::new(&archive[..]).entries().unwrap();
+            let entry = entries.next().await.unwrap().unwrap();
+            assert_eq!(entry_name_of(&*entry.path().unwrap()).unwrap(), long_name);
+            assert_eq!(entry_size(&entry).unwrap(), 3);
+        })
+    }
+
+    #[test]
+    fn export_and_import_bundle() {
+        block_on(async {
+            let remote = EphemeralRepository::<Pouf1>::new();
+            let target_path = TargetPath::new("foo/bar").unwrap();
+            let target_file: &[u8] = b"foo bar file";
+
+            let metadata1 = RepoBuilder::create(&remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target(target_path.clone(), Cursor::new(target_file))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+            let root1 = metadata1.root().unwrap().clone();
+
+            // Rotate the root, so the bundle has to include a chain of root metadata.
+            let _ = RepoBuilder::from_repo(&remote, &root1)
+                .await
+                .unwrap()
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let mut bundle = vec![];
+            let manifest = export_bundle(&remote, &root1, &mut bundle).await.unwrap();
+            let names = manifest
+                .metadata()
+                .iter()
+                .map(|entry| entry.name())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                names[..3],
+                [
+                    "metadata/1.root.json",
+                    "metadata/2.root.json",
+                    "metadata/root.json"
+                ]
+            );
+            assert!(names.contains(&"metadata/targets.json"));
+            assert_eq!(names.last(), Some(&"metadata/timestamp.json"));
+            assert_eq!(manifest.targets().len(), 1);
+            assert!(manifest.targets()[0].name().starts_with("targets/foo/"));
+
+            // Import the bundle at the site, and update a client from it.
+            let site = EphemeralRepository::<Pouf1>::new();
+            let imported = import_bundle(&bundle[..], &site).await.unwrap();
+            assert_eq!(imported, manifest);
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                &root1,
+                EphemeralRepository::new(),
+                site,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
+            assert_eq!(client.database().trusted_root().version(), 2);
+
+            let mut buf = vec![];
+            client
+                .fetch_target(&target_path)
+                .await
+                .unwrap()
+                .read_to_end(&mut buf)
+                .await
+                .unwrap();
+            assert_eq!(buf, target_file);
+
+            // A client can also be updated from the bundle itself.
+            let archive = ArchiveRepository::<Pouf1>::new(&bundle[..]).await.unwrap();
+            assert_eq!(archive.manifest(), &manifest);
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                &root1,
+                EphemeralRepository::new(),
+                archive,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
+            assert_eq!(client.database().trusted_root().version(), 2);
+
+            let mut buf = vec![];
+            client
+                .fetch_target(&target_path)
+                .await
+                .unwrap()
+                .read_to_end(&mut buf)
+                .await
+                .unwrap();
+            assert_eq!(buf, target_file);
+
+            // A bundle without its manifest is rejected.
+            let mut builder = Builder::new(GzipEncoder::new(vec![]));
+            builder
+                .append_data(&mut entry_header(0), "metadata/root.json", &b""[..])
+                .await
+                .unwrap();
+            let mut encoder = builder.into_inner().await.unwrap();
+            encoder.close().await.unwrap();
+            let truncated = encoder.into_inner();
+            assert_matches!(
+                import_bundle(&truncated[..], &EphemeralRepository::<Pouf1>::new()).await,
+                Err(Error::Encoding(_))
+            );
+        })
+    }
+
+    #[test]
+    fn export_bundle_rejects_modified_targets() {
+        block_on(async {
+            let remote = EphemeralRepository::<Pouf1>::new();
+            let target_path = TargetPath::new("foo").unwrap();
+
+            let metadata = RepoBuilder::create(&remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target(target_path.clone(), Cursor::new(b"foo file"))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let description =
+                TargetDescription::from_slice(b"foo file", &[HashAlgorithm::Sha256]).unwrap();
+            remote
+                .store_target(
+                    &target_path
+                        .with_hash_prefix(&description.hashes()[&HashAlgorithm::Sha256])
+                        .unwrap(),
+                    &mut &b"bar file"[..],
+                )
+                .await
+                .unwrap();
+
+            let mut bundle = vec![];
+            assert!(
+                export_bundle(&remote, metadata.root().unwrap(), &mut bundle)
+                    .await
+                    .is_err()
+            );
+        })
+    }
+
+    #[test]
+    fn export_bundle_requires_delegated_metadata() {
+        block_on(async {
+            let remote = EphemeralRepository::<Pouf1>::new();
+            let role = MetadataPath::new("delegated").unwrap();
+            let delegation = Delegation::builder(role.clone())
+                .key(KEYS[0].public())
+                .delegate_path(TargetPath::new("delegated/").unwrap())
+                .build()
+                .unwrap();
+
+            let metadata = RepoBuilder::create(&remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .add_delegated_role(delegation, &[&KEYS[0]])
+                .add_delegated_target(
+                    &role,
+                    TargetPath::new("delegated/foo").unwrap(),
+                    Cursor::new(b"foo file"),
+                )
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            // Corrupt the metadata of the delegated role, so its targets can't be trusted.
+            for version in [MetadataVersion::Number(1), MetadataVersion::None] {
+                remote
+                    .store_metadata(&role, version, &mut &b"corrupt"[..])
+                    .await
+                    .unwrap();
+            }
+
+            let mut bundle = vec![];
+            assert!(
+                export_bundle(&remote, metadata.root().unwrap(), &mut bundle)
+                    .await
+                    .is_err()
+            );
+        })
+    }
+}
@@ -0,0 +1,264 @@
+//! Cooperative cancellation of long running [Client](crate::client::Client) operations.
+
+use futures_io::AsyncRead;
+use futures_util::future::{self, Either};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::error::{Error, Result};
+
+/// A token that is used to cancel in-flight operations of a [Client](crate::client::Client).
+///
+/// Clones of a token share the same state, so one clone can be handed to a shutdown handler while
+/// another is given to the client with
+/// [Client::set_cancellation_token](crate::client::Client::set_cancellation_token). Once
+/// cancelled, a token stays cancelled.
+///
+/// Cancellation only interrupts fetches from the remote repository. Metadata that has already
+/// been verified is always written to the local repository in full, and targets are only
+/// installed once they have been completely downloaded and verified, so cancelling leaves the
+/// local state consistent.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    wakers: Mutex<Wakers>,
+}
+
+/// The wakers of the futures and readers that are waiting on a token. Each waiter registers under
+/// its own key, and removes its waker when it is dropped, so that a long-lived token doesn't
+/// accumulate the wakers of every operation it was ever used for.
+#[derive(Debug, Default)]
+struct Wakers {
+    next_key: u64,
+    wakers: HashMap<u64, Waker>,
+}
+
+impl CancellationToken {
+    /// Create a new token that has not been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel all operations that are using this token.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+
+        let wakers = std::mem::take(&mut self.inner.wakers.lock().unwrap().wakers);
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+    }
+
+    /// Returns `true` if [CancellationToken::cancel] has been called on this token or any of its
+    /// clones.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns a future that completes once this token has been cancelled.
+    pub fn cancelled(&self) -> Cancelled<'_> {
+        Cancelled {
+            token: self,
+            key: None,
+        }
+    }
+
+    /// Returns [Error::Cancelled] if this token has been cancelled.
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Run `fut` to completion, or return [Error::Cancelled] if this token is cancelled first.
+    pub(crate) async fn run<F, T>(&self, fut: F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        self.check()?;
+
+        futures_util::pin_mut!(fut);
+        match future::select(fut, self.cancelled()).await {
+            Either::Left((res, _)) => res,
+            Either::Right(((), _)) => Err(Error::Cancelled),
+        }
+    }
+
+    /// Poll whether this token has been cancelled, registering the waker of `cx` under `key` if
+    /// it hasn't. `key` is assigned on the first registration, and must be passed to
+    /// [CancellationToken::deregister] once the waiter is dropped.
+    fn poll_cancelled(&self, key: &mut Option<u64>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        let mut wakers = self.inner.wakers.lock().unwrap();
+
+        // Check again while holding the lock, so we can't miss a concurrent call to `cancel`.
+        if self.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        let wakers = &mut *wakers;
+        let key = *key.get_or_insert_with(|| {
+            let key = wakers.next_key;
+            wakers.next_key += 1;
+            key
+        });
+
+        let waker = wakers
+            .wakers
+            .entry(key)
+            .or_insert_with(|| cx.waker().clone());
+        if !waker.will_wake(cx.waker()) {
+            *waker = cx.waker().clone();
+        }
+
+        Poll::Pending
+    }
+
+    /// Remove the waker registered under `key` by [CancellationToken::poll_cancelled], if any.
+    fn deregister(&self, key: Option<u64>) {
+        if let Some(key) = key {
+            let _ = self.inner.wakers.lock().unwrap().wakers.remove(&key);
+        }
+    }
+}
+
+/// A future that completes once a [CancellationToken] has been cancelled.
+#[derive(Debug)]
+pub struct Cancelled<'a> {
+    token: &'a CancellationToken,
+    key: Option<u64>,
+}
+
+impl Future for Cancelled<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        this.token.poll_cancelled(&mut this.key, cx)
+    }
+}
+
+impl Drop for Cancelled<'_> {
+    fn drop(&mut self) {
+        self.token.deregister(self.key);
+    }
+}
+
+/// An [AsyncRead] that fails with an error once its [CancellationToken] has been cancelled.
+pub(crate) struct CancellableRead<R> {
+    read: R,
+    cancel: Option<CancellationToken>,
+    key: Option<u64>,
+}
+
+impl<R> CancellableRead<R> {
+    pub(crate) fn new(read: R, cancel: Option<CancellationToken>) -> Self {
+        Self {
+            read,
+            cancel,
+            key: None,
+        }
+    }
+}
+
+impl<R> Drop for CancellableRead<R> {
+    fn drop(&mut self) {
+        if let Some(cancel) = &self.cancel {
+            cancel.deregister(self.key);
+        }
+    }
+}
+
+impl<R> AsyncRead for CancellableRead<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if let Some(cancel) = &this.cancel {
+            if cancel.poll_cancelled(&mut this.key, cx).is_ready() {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    Error::Cancelled.to_string(),
+                )));
+            }
+        }
+
+        Pin::new(&mut this.read).poll_read(cx, buf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert_matches::assert_matches;
+    use futures_executor::block_on;
+
+    #[test]
+    fn run_completes_if_not_cancelled() {
+        let token = CancellationToken::new();
+        assert_eq!(block_on(token.run(async { Ok(5) })).unwrap(), 5);
+    }
+
+    #[test]
+    fn run_fails_if_already_cancelled() {
+        let token = CancellationToken::new();
+        token.clone().cancel();
+        assert!(token.is_cancelled());
+        assert_matches!(block_on(token.run(async { Ok(5) })), Err(Error::Cancelled));
+    }
+
+    #[test]
+    fn cancel_wakes_pending_run() {
+        let token = CancellationToken::new();
+        let (res, ()) = block_on(future::join(
+            token.run(future::pending::<Result<()>>()),
+            async { token.cancel() },
+        ));
+        assert_matches!(res, Err(Error::Cancelled));
+    }
+
+    #[test]
+    fn dropped_waiters_are_deregistered() {
+        let token = CancellationToken::new();
+        let registered = || token.inner.wakers.lock().unwrap().wakers.len();
+        let mut cx = Context::from_waker(futures_util::task::noop_waker_ref());
+
+        for _ in 0..3 {
+            let mut cancelled = token.cancelled();
+            assert!(Pin::new(&mut cancelled).poll(&mut cx).is_pending());
+            assert!(Pin::new(&mut cancelled).poll(&mut cx).is_pending());
+            assert_eq!(registered(), 1);
+        }
+        assert_eq!(registered(), 0);
+
+        let mut read = CancellableRead::new(&b"foo"[..], Some(token.clone()));
+        let mut buf = [0; 3];
+        assert_matches!(
+            Pin::new(&mut read).poll_read(&mut cx, &mut buf),
+            Poll::Ready(Ok(3))
+        );
+        assert_eq!(registered(), 1);
+        drop(read);
+        assert_eq!(registered(), 0);
+    }
+}
@@ -47,25 +47,169 @@
 //! # }
 //! ```
 
-use chrono::{offset::Utc, DateTime};
-use futures_io::AsyncRead;
+use chrono::{offset::Utc, DateTime, Duration};
+use futures_io::{AsyncRead, AsyncSeek};
+use futures_util::io::{copy, AllowStdIo, AsyncReadExt as _, AsyncSeekExt as _};
+use futures_util::stream::{self, StreamExt as _};
 use log::{error, warn};
+use ring::rand::SystemRandom;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
 use std::future::Future;
+use std::io::{self, SeekFrom};
+use std::path::Path;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration as StdDuration, Instant};
 
+use crate::cancel::CancellationToken;
+use crate::clock::{Clock, Sleep};
 use crate::crypto::{self, HashAlgorithm, HashValue, PublicKey};
-use crate::database::Database;
+use crate::database::{Database, MetadataExpiration, MetadataIntegrityPolicy};
+use crate::delta::{self, DeltaPatcher};
 use crate::error::{Error, Result};
+use crate::lazy_targets::LazyTargetsMetadata;
 use crate::metadata::{
-    Metadata, MetadataPath, MetadataVersion, RawSignedMetadata, RootMetadata, SnapshotMetadata,
-    TargetDescription, TargetPath, TargetsMetadata,
+    diff_root, CustomMetadata, Delegation, DelegationEntry, Metadata, MetadataDescription,
+    MetadataPath, MetadataVersion, MultiRoleDelegation, PathMatching, RawSignedMetadata, RootDiff,
+    RootMetadata, SpecVersion, TargetDescription, TargetPath, TargetsMetadata,
 };
+use crate::policy::Policy;
 use crate::pouf::Pouf;
-use crate::repository::{Repository, RepositoryProvider, RepositoryStorage};
+use crate::repository::{create_temp_file, Repository, RepositoryProvider, RepositoryStorage};
+use crate::rollback::RollbackState;
+use crate::util::{random_jitter, SafeAsyncRead};
 use crate::verify::Verified;
 
+/// A hook that is notified as the bytes of a target are downloaded.
+///
+/// The hook is given the path of the target, the number of bytes read so far, and the expected
+/// length of the target from the trusted metadata, which is 0 if the metadata does not specify it.
+/// Note that the bytes have not been verified until the whole target has been read.
+///
+/// Any closure of the form `Fn(&TargetPath, u64, u64)` implements this trait.
+pub trait TargetFetchProgress: Send + Sync {
+    /// Called every time more bytes of `target_path` have been read.
+    fn on_progress(&self, target_path: &TargetPath, bytes_read: u64, expected_length: u64);
+}
+
+impl<F> TargetFetchProgress for F
+where
+    F: Fn(&TargetPath, u64, u64) + Send + Sync,
+{
+    fn on_progress(&self, target_path: &TargetPath, bytes_read: u64, expected_length: u64) {
+        (self)(target_path, bytes_read, expected_length)
+    }
+}
+
+/// The kinds of attacks that a [Client] can detect while updating metadata.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AttackKind {
+    /// The repository served metadata that is older than the trusted metadata.
+    Rollback,
+    /// The repository served expired metadata.
+    Freeze,
+    /// The repository served metadata whose version does not match the version listed in its
+    /// parent metadata.
+    MixAndMatch,
+    /// The repository served metadata that was not signed by a threshold of trusted keys.
+    InvalidSignatures,
+    /// The repository served metadata from a role that was not delegated to.
+    UnauthorizedDelegation,
+}
+
+impl AttackKind {
+    /// Classify `err` as an attack, or return `None` if the error does not indicate an attack.
+    pub fn from_error(err: &Error) -> Option<Self> {
+        match err {
+            Error::AttemptedMetadataRollBack { .. } => Some(AttackKind::Rollback),
+            Error::ExpiredMetadata { .. } => Some(AttackKind::Freeze),
+            Error::WrongMetadataVersion { .. } => Some(AttackKind::MixAndMatch),
+            Error::BadSignature(_) | Error::MetadataMissingSignatures { .. } => {
+                Some(AttackKind::InvalidSignatures)
+            }
+            Error::UnauthorizedDelegation { .. } => Some(AttackKind::UnauthorizedDelegation),
+            _ => None,
+        }
+    }
+}
+
+/// An observer that is notified of events during the update lifecycle of a [Client].
+///
+/// All methods have empty default implementations, so implementors only need to override the
+/// events they care about.
+pub trait ClientObserver: Send + Sync {
+    /// Called after the trusted root metadata has been updated to `version`.
+    fn on_root_updated(&self, version: u32) {
+        let _ = version;
+    }
+
+    /// Called after the trusted top-level targets metadata has changed. `added` contains the
+    /// targets that are new or whose description has changed, and `removed` contains the targets
+    /// that are no longer listed.
+    fn on_new_targets(&self, added: &[TargetPath], removed: &[TargetPath]) {
+        let _ = (added, removed);
+    }
+
+    /// Called when an update failed because the repository served metadata that indicates an
+    /// attack. `err` is the error that will be returned from the update.
+    fn on_attack_detected(&self, kind: AttackKind, err: &Error) {
+        let _ = (kind, err);
+    }
+
+    /// Called after a successful update if any trusted metadata expires within the
+    /// [Config::expiration_warning_window]. `expiring` is sorted in the same order as
+    /// [Database::trusted_metadata_expirations].
+    fn on_metadata_expiring(&self, expiring: &[MetadataExpiration]) {
+        let _ = expiring;
+    }
+}
+
+/// The phases of a [Client] update that are reported to [Metrics].
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UpdatePhase {
+    /// Updating the root metadata.
+    Root,
+    /// Updating the timestamp metadata.
+    Timestamp,
+    /// Updating the snapshot metadata.
+    Snapshot,
+    /// Updating the top-level targets metadata.
+    Targets,
+    /// Updating delegated targets metadata.
+    Delegations,
+}
+
+/// A sink for metrics about the performance of a [Client], so that regressions in update
+/// performance can be tracked across a fleet.
+///
+/// All methods have empty default implementations, so implementors only need to override the
+/// metrics they care about.
+pub trait Metrics: Send + Sync {
+    /// Called after a phase of an update has completed, successfully or not. The
+    /// [UpdatePhase::Delegations] phase is reported once for each delegated role that is fetched.
+    fn record_phase_duration(&self, phase: UpdatePhase, duration: StdDuration) {
+        let _ = (phase, duration);
+    }
+
+    /// Called after `bytes` of metadata have been downloaded from the remote repository.
+    fn record_bytes_downloaded(&self, phase: UpdatePhase, bytes: u64) {
+        let _ = (phase, bytes);
+    }
+
+    /// Called each time the signatures of downloaded metadata have been checked. `verified` is
+    /// `false` if the metadata was rejected because it was not signed by a threshold of trusted
+    /// keys.
+    fn record_signature_verification(&self, phase: UpdatePhase, verified: bool) {
+        let _ = (phase, verified);
+    }
+}
+
 /// A client that interacts with TUF repositories.
-#[derive(Debug)]
 pub struct Client<D, L, R>
 where
     D: Pouf,
@@ -76,6 +220,33 @@ where
     tuf: Database<D>,
     local: Repository<L, D>,
     remote: Repository<R, D>,
+    target_fetch_progress: Option<Arc<dyn TargetFetchProgress>>,
+    observer: Option<Arc<dyn ClientObserver>>,
+    metrics: Option<Arc<dyn Metrics>>,
+    sleep: Option<Arc<dyn Sleep>>,
+}
+
+impl<D, L, R> fmt::Debug for Client<D, L, R>
+where
+    D: Pouf + fmt::Debug,
+    L: RepositoryProvider<D> + RepositoryStorage<D> + fmt::Debug,
+    R: RepositoryProvider<D> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("config", &self.config)
+            .field("tuf", &self.tuf)
+            .field("local", &self.local)
+            .field("remote", &self.remote)
+            .field(
+                "target_fetch_progress",
+                &self.target_fetch_progress.is_some(),
+            )
+            .field("observer", &self.observer.is_some())
+            .field("metrics", &self.metrics.is_some())
+            .field("sleep", &self.sleep.is_some())
+            .finish()
+    }
 }
 
 impl<D, L, R> Client<D, L, R>
@@ -142,14 +313,19 @@ where
     /// # }
     /// ```
     pub async fn with_trusted_local(config: Config, local: L, remote: R) -> Result<Self> {
-        let (local, remote) = (Repository::new(local), Repository::new(remote));
+        let (local, remote) = (Repository::new(local), remote_repository(&config, remote));
         let root_path = MetadataPath::root();
 
         // FIXME should this be MetadataVersion::None so we bootstrap with the latest version?
         let root_version = MetadataVersion::Number(1);
 
         let raw_root: RawSignedMetadata<_, RootMetadata> = local
-            .fetch_metadata(&root_path, root_version, config.max_root_length, vec![])
+            .fetch_metadata(
+                &root_path,
+                root_version,
+                config.max_root_length.max_length(),
+                vec![],
+            )
             .await?;
 
         let tuf = Database::from_trusted_root(&raw_root)?;
@@ -212,12 +388,27 @@ where
         local: L,
         remote: R,
     ) -> Result<Self> {
-        let (local, remote) = (Repository::new(local), Repository::new(remote));
+        let (local, remote) = (Repository::new(local), remote_repository(&config, remote));
         let tuf = Database::from_trusted_root(trusted_root)?;
 
         Self::new(config, tuf, local, remote).await
     }
 
+    /// Create a new TUF client from the serialized bytes of trusted root metadata, such as a
+    /// `1.root.json` that is embedded in the application with [include_root!](crate::include_root).
+    ///
+    /// The root metadata is parsed and its signatures are checked before any repository is
+    /// accessed. See [Client::with_trusted_root] for more details.
+    pub async fn with_trusted_root_bytes(
+        config: Config,
+        trusted_root: &[u8],
+        local: L,
+        remote: R,
+    ) -> Result<Self> {
+        let trusted_root = RawSignedMetadata::new(trusted_root.to_vec());
+        Self::with_trusted_root(config, &trusted_root, local, remote).await
+    }
+
     /// Create a new TUF client. It will attempt to load initial root metadata from the local and remote
     /// repositories using the provided keys to pin the verification.
     ///
@@ -288,13 +479,13 @@ where
     where
         I: IntoIterator<Item = &'a PublicKey>,
     {
-        let (mut local, remote) = (Repository::new(local), Repository::new(remote));
+        let (mut local, remote) = (Repository::new(local), remote_repository(&config, remote));
 
         let root_path = MetadataPath::root();
         let (fetched, raw_root) = fetch_metadata_from_local_or_else_remote(
             &root_path,
             root_version,
-            config.max_root_length,
+            config.max_root_length.max_length(),
             vec![],
             &local,
             &remote,
@@ -332,12 +523,22 @@ where
     }
 
     /// Create a new TUF client. It will trust and update the TUF database.
-    pub fn from_database(config: Config, tuf: Database<D>, local: L, remote: R) -> Self {
+    pub fn from_database(config: Config, mut tuf: Database<D>, local: L, remote: R) -> Self {
+        tuf.set_expiration_grace_period(config.expiration_grace_period);
+        tuf.set_spec_major_version(config.spec_major_version);
+        tuf.set_path_matching(config.path_matching);
+        tuf.set_metadata_integrity_policy(config.metadata_integrity_policy);
+        tuf.set_policy(config.policy.clone());
+        let remote = remote_repository(&config, remote);
         Self {
             config,
             tuf,
             local: Repository::new(local),
-            remote: Repository::new(remote),
+            remote,
+            target_fetch_progress: None,
+            observer: None,
+            metrics: None,
+            sleep: None,
         }
     }
 
@@ -349,15 +550,25 @@ where
     pub fn from_parts(parts: Parts<D, L, R>) -> Self {
         let Parts {
             config,
-            database,
+            mut database,
             local,
             remote,
         } = parts;
+        database.set_expiration_grace_period(config.expiration_grace_period);
+        database.set_spec_major_version(config.spec_major_version);
+        database.set_path_matching(config.path_matching);
+        database.set_metadata_integrity_policy(config.metadata_integrity_policy);
+        database.set_policy(config.policy.clone());
+        let remote = remote_repository(&config, remote);
         Self {
             config,
             tuf: database,
             local: Repository::new(local),
-            remote: Repository::new(remote),
+            remote,
+            target_fetch_progress: None,
+            observer: None,
+            metrics: None,
+            sleep: None,
         }
     }
 
@@ -368,14 +579,26 @@ where
         local: Repository<L, D>,
         remote: Repository<R, D>,
     ) -> Result<Self> {
-        let start_time = Utc::now();
+        tuf.set_expiration_grace_period(config.expiration_grace_period);
+        tuf.set_spec_major_version(config.spec_major_version);
+        tuf.set_path_matching(config.path_matching);
+        tuf.set_metadata_integrity_policy(config.metadata_integrity_policy);
+        tuf.set_policy(config.policy.clone());
+        let start_time = tuf.clock().now();
 
         let res = async {
             let _r =
-                Self::update_root_with_repos(&start_time, &config, &mut tuf, None, &local).await?;
-            let _ts =
-                Self::update_timestamp_with_repos(&start_time, &config, &mut tuf, None, &local)
+                Self::update_root_with_repos(&start_time, &config, &mut tuf, None, &local, None)
                     .await?;
+            let _ts = Self::update_timestamp_with_repos(
+                &start_time,
+                &config,
+                &mut tuf,
+                None,
+                &local,
+                None,
+            )
+            .await?;
             let _sn = Self::update_snapshot_with_repos(
                 &start_time,
                 &config,
@@ -383,6 +606,7 @@ where
                 None,
                 &local,
                 false,
+                None,
             )
             .await?;
             let _ta = Self::update_targets_with_repos(
@@ -392,6 +616,7 @@ where
                 None,
                 &local,
                 false,
+                None,
             )
             .await?;
 
@@ -411,6 +636,10 @@ where
             config,
             local,
             remote,
+            target_fetch_progress: None,
+            observer: None,
+            metrics: None,
+            sleep: None,
         })
     }
 
@@ -418,7 +647,7 @@ where
     ///
     /// Returns `true` if an update occurred and `false` otherwise.
     pub async fn update(&mut self) -> Result<bool> {
-        self.update_with_start_time(&Utc::now()).await
+        self.update_with_start_time(&self.tuf.clock().now()).await
     }
 
     /// Update TUF metadata from the remote repository, using the specified time to determine if
@@ -427,7 +656,181 @@ where
     /// Returns `true` if an update occurred and `false` otherwise.
     ///
     /// **WARNING**: Using an older time opens up users to a freeze attack.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "info", skip_all, fields(start_time = %start_time), err)
+    )]
     pub async fn update_with_start_time(&mut self, start_time: &DateTime<Utc>) -> Result<bool> {
+        let observer = match self.observer.clone() {
+            Some(observer) => observer,
+            None => {
+                let updated = self.update_with_retries(start_time).await?;
+                self.warn_expiring_metadata(start_time);
+                return Ok(updated);
+            }
+        };
+
+        let old_root_version = self.tuf.trusted_root().version();
+        let old_targets = self
+            .tuf
+            .trusted_targets()
+            .map(|targets| targets.targets().clone())
+            .unwrap_or_default();
+
+        let updated = match self.update_with_retries(start_time).await {
+            Ok(updated) => updated,
+            Err(err) => {
+                if let Some(kind) = AttackKind::from_error(&err) {
+                    observer.on_attack_detected(kind, &err);
+                }
+                return Err(err);
+            }
+        };
+
+        let new_root_version = self.tuf.trusted_root().version();
+        if new_root_version != old_root_version {
+            observer.on_root_updated(new_root_version);
+        }
+
+        if let Some(new_targets) = self.tuf.trusted_targets().map(|targets| targets.targets()) {
+            let mut added = new_targets
+                .iter()
+                .filter(|(path, description)| old_targets.get(*path) != Some(*description))
+                .map(|(path, _)| path.clone())
+                .collect::<Vec<_>>();
+            let mut removed = old_targets
+                .keys()
+                .filter(|path| !new_targets.contains_key(*path))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if !added.is_empty() || !removed.is_empty() {
+                added.sort();
+                removed.sort();
+                observer.on_new_targets(&added, &removed);
+            }
+        }
+
+        let expiring = self.warn_expiring_metadata(start_time);
+        if !expiring.is_empty() {
+            observer.on_metadata_expiring(&expiring);
+        }
+
+        Ok(updated)
+    }
+
+    /// Returns the trusted metadata that expires within the [Config::expiration_warning_window],
+    /// including any metadata that has already expired but is still accepted because of the
+    /// [Config::expiration_grace_period].
+    ///
+    /// This lets an operator tell an update that succeeded, but with metadata that is about to
+    /// expire, from a clean pass.
+    pub fn expiring_metadata(&self) -> Vec<MetadataExpiration> {
+        self.expiring_metadata_with_start_time(&self.tuf.clock().now())
+    }
+
+    /// Returns the trusted metadata that expires within the [Config::expiration_warning_window]
+    /// of `start_time`.
+    ///
+    /// See [Client::expiring_metadata] for more details.
+    pub fn expiring_metadata_with_start_time(
+        &self,
+        start_time: &DateTime<Utc>,
+    ) -> Vec<MetadataExpiration> {
+        let deadline = *start_time + self.config.expiration_warning_window;
+        self.tuf
+            .trusted_metadata_expirations()
+            .into_iter()
+            .filter(|expiration| expiration.expires() <= &deadline)
+            .collect()
+    }
+
+    fn warn_expiring_metadata(&self, start_time: &DateTime<Utc>) -> Vec<MetadataExpiration> {
+        let expiring = self.expiring_metadata_with_start_time(start_time);
+        for expiration in &expiring {
+            warn!(
+                "metadata {} version {} expires soon, at {}",
+                expiration.path(),
+                expiration.version(),
+                expiration.expires()
+            );
+        }
+        expiring
+    }
+
+    /// Check if the remote repository has newer targets metadata, without downloading it.
+    ///
+    /// This updates the root, timestamp, and snapshot metadata, and returns `true` if the trusted
+    /// snapshot lists a version of the top-level targets metadata that has not been fetched yet.
+    /// Call [Client::update] to download the targets metadata.
+    pub async fn check_for_updates(&mut self) -> Result<bool> {
+        self.check_for_updates_with_start_time(&self.tuf.clock().now())
+            .await
+    }
+
+    /// Check if the remote repository has newer targets metadata, without downloading it, using
+    /// the specified time to determine if the metadata is expired.
+    ///
+    /// See [Client::check_for_updates] for more details.
+    ///
+    /// **WARNING**: Using an older time opens up users to a freeze attack.
+    pub async fn check_for_updates_with_start_time(
+        &mut self,
+        start_time: &DateTime<Utc>,
+    ) -> Result<bool> {
+        self.update_root(start_time).await?;
+        self.update_timestamp(start_time).await?;
+        self.update_snapshot(start_time).await?;
+
+        let targets_version = self
+            .snapshot_description(start_time, &MetadataPath::targets())
+            .await?
+            .map(|description| description.version());
+        let trusted_targets_version = self.tuf.trusted_targets().map(|targets| targets.version());
+
+        Ok(targets_version != trusted_targets_version)
+    }
+
+    /// Run the update, retrying it according to the [Config::update_retry_policy].
+    async fn update_with_retries(&mut self, start_time: &DateTime<Utc>) -> Result<bool> {
+        let policy = self.config.update_retry_policy.clone();
+        if policy.waits() && self.sleep.is_none() {
+            return Err(Error::IllegalArgument(
+                "the update retry policy waits between attempts, which requires a sleep timer \
+                 set with Client::set_sleep"
+                    .into(),
+            ));
+        }
+
+        let rng = SystemRandom::new();
+        let mut failures = 0;
+
+        loop {
+            let err = match self.update_with_start_time_impl(start_time).await {
+                Ok(updated) => return Ok(updated),
+                Err(err) => err,
+            };
+
+            failures += 1;
+            if failures >= policy.max_attempts() || !policy.is_retryable(&err) {
+                return Err(err);
+            }
+
+            let delay = policy.backoff(failures) + random_jitter(&rng, policy.jitter);
+            warn!(
+                "update attempt {} of {} failed, retrying in {:?}: {}",
+                failures,
+                policy.max_attempts(),
+                delay,
+                err
+            );
+            if let Some(sleep) = &self.sleep {
+                sleep.sleep(delay).await;
+            }
+        }
+    }
+
+    async fn update_with_start_time_impl(&mut self, start_time: &DateTime<Utc>) -> Result<bool> {
         let r = self.update_root(start_time).await?;
         let ts = self.update_timestamp(start_time).await?;
         let sn = self.update_snapshot(start_time).await?;
@@ -443,6 +846,7 @@ where
             tuf,
             local,
             remote,
+            ..
         } = self;
         Parts {
             config,
@@ -452,6 +856,171 @@ where
         }
     }
 
+    /// Set a [TargetFetchProgress] hook that will be notified as targets are downloaded by
+    /// [Client::fetch_target], [Client::fetch_target_to_local], and [Client::fetch_targets].
+    pub fn set_target_fetch_progress<P>(&mut self, progress: P)
+    where
+        P: TargetFetchProgress + 'static,
+    {
+        self.target_fetch_progress = Some(Arc::new(progress));
+    }
+
+    /// Set a [ClientObserver] that will be notified of events during [Client::update].
+    pub fn set_observer<O>(&mut self, observer: O)
+    where
+        O: ClientObserver + 'static,
+    {
+        self.observer = Some(Arc::new(observer));
+    }
+
+    /// Set a [Metrics] sink that will record the performance of [Client::update] and of fetching
+    /// delegated targets metadata.
+    pub fn set_metrics<M>(&mut self, metrics: M)
+    where
+        M: Metrics + 'static,
+    {
+        self.metrics = Some(Arc::new(metrics));
+    }
+
+    /// Set the [Sleep] timer used to wait between the attempts of an update that is retried under
+    /// the [Config::update_retry_policy]. Without one, updates fail if the policy would wait.
+    pub fn set_sleep<S>(&mut self, sleep: S)
+    where
+        S: Sleep + 'static,
+    {
+        self.sleep = Some(Arc::new(sleep));
+    }
+
+    /// Set a [CancellationToken] that aborts in-flight fetches from the remote repository once it
+    /// is cancelled. Operations that are interrupted return [Error::Cancelled], or an
+    /// [io::Error] when reading a target.
+    ///
+    /// Cancellation happens between, and never during, writes to the local repository, so the
+    /// trusted metadata that has been persisted stays consistent.
+    pub fn set_cancellation_token(&mut self, cancel: CancellationToken) {
+        self.remote.set_cancellation_token(Some(cancel));
+    }
+
+    /// Set the [Clock] used to check metadata expiration by the methods that do not take an
+    /// explicit start time. This is shorthand for calling [Database::set_clock] on the
+    /// [Client]'s database.
+    pub fn set_clock<C>(&mut self, clock: C)
+    where
+        C: Clock + 'static,
+    {
+        self.tuf.set_clock(clock);
+    }
+
+    /// Set the [RollbackState] that records the highest version of every role the [Client] has
+    /// trusted, so that old metadata is still rejected after the local repository is wiped. This
+    /// is shorthand for calling [Database::set_rollback_state] on the [Client]'s database.
+    pub fn set_rollback_state<S>(&mut self, rollback_state: S)
+    where
+        S: RollbackState + 'static,
+    {
+        self.tuf.set_rollback_state(rollback_state);
+    }
+
+    /// The versions and expiration times of all of the trusted metadata. See
+    /// [Database::trusted_metadata_expirations].
+    pub fn trusted_metadata_expirations(&self) -> Vec<MetadataExpiration> {
+        self.tuf.trusted_metadata_expirations()
+    }
+
+    /// Fetch the latest metadata of the custom top-level `role` from the remote repository, and
+    /// trust it if it is signed by a threshold of the role's keys in the trusted root metadata.
+    /// Returns `true` if new metadata was trusted, which is then available from
+    /// [Database::trusted_custom_role], and `false` otherwise. See [CustomMetadata] for details.
+    ///
+    /// The metadata is fetched as `ROLE.json`, like the timestamp metadata, since custom roles are
+    /// not listed in the snapshot metadata.
+    pub async fn update_custom_role(&mut self, role: &MetadataPath) -> Result<bool> {
+        let start_time = self.tuf.clock().now();
+
+        let raw_signed_custom: RawSignedMetadata<D, CustomMetadata> = self
+            .remote
+            .fetch_metadata(
+                role,
+                MetadataVersion::None,
+                self.config.max_custom_role_length.max_length(),
+                vec![],
+            )
+            .await?;
+
+        self.config
+            .max_custom_role_length
+            .warn_if_exceeded(role, &raw_signed_custom);
+
+        if self
+            .tuf
+            .update_custom_role(&start_time, role, &raw_signed_custom)?
+        {
+            self.local
+                .store_metadata(role, MetadataVersion::None, &raw_signed_custom)
+                .await?;
+
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Download every version of the root metadata from the remote repository, from
+    /// `1.root.json` through the version of the trusted root metadata, and verify each version
+    /// was signed by a threshold of the keys of the version before it and of its own keys.
+    ///
+    /// The first version is only checked to be signed by its own keys. The chain is anchored by
+    /// requiring its last version to be the trusted root metadata, and fails with
+    /// [Error::RootChainMismatch] otherwise. Each link is annotated with the changes to the keys
+    /// and thresholds since the version before it, so the rotation history of the repository can
+    /// be audited. The trusted metadata of the [Client] is not changed, and expiration is not
+    /// checked.
+    pub async fn fetch_root_chain(&self) -> Result<Vec<RootChainLink<D>>> {
+        let trusted_root = self.tuf.trusted_root();
+
+        let raw_root = self.fetch_root_version(1).await?;
+        let mut tuf = Database::from_trusted_root(&raw_root)?;
+        tuf.set_spec_major_version(self.tuf.spec_major_version());
+
+        let mut chain = vec![RootChainLink {
+            raw_root,
+            root: tuf.trusted_root().clone(),
+            diff: None,
+        }];
+
+        for version in 2..=trusted_root.version() {
+            let raw_root = self.fetch_root_version(version).await?;
+            tuf.update_root(&raw_root)?;
+
+            let diff = chain
+                .last()
+                .map(|link| diff_root(&link.root, tuf.trusted_root()));
+
+            chain.push(RootChainLink {
+                raw_root,
+                root: tuf.trusted_root().clone(),
+                diff,
+            });
+        }
+
+        if tuf.trusted_root() != trusted_root {
+            return Err(Error::RootChainMismatch(trusted_root.version()));
+        }
+
+        Ok(chain)
+    }
+
+    async fn fetch_root_version(&self, version: u32) -> Result<RawSignedMetadata<D, RootMetadata>> {
+        self.remote
+            .fetch_metadata(
+                &MetadataPath::root(),
+                MetadataVersion::Number(version),
+                self.config.max_root_length.max_length(),
+                vec![],
+            )
+            .await
+    }
+
     /// Returns a reference to the TUF database.
     pub fn database(&self) -> &Database<D> {
         &self.tuf
@@ -485,15 +1054,27 @@ where
     /// Update TUF root metadata from the remote repository.
     ///
     /// Returns `true` if an update occurred and `false` otherwise.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
     pub async fn update_root(&mut self, start_time: &DateTime<Utc>) -> Result<bool> {
-        Self::update_root_with_repos(
+        let started = Instant::now();
+        let res = Self::update_root_with_repos(
             start_time,
             &self.config,
             &mut self.tuf,
             Some(&mut self.local),
             &self.remote,
+            self.metrics.as_deref(),
         )
-        .await
+        .await;
+
+        if let Some(metrics) = &self.metrics {
+            metrics.record_phase_duration(UpdatePhase::Root, started.elapsed());
+        }
+
+        res
     }
 
     async fn update_root_with_repos<Remote>(
@@ -502,6 +1083,7 @@ where
         tuf: &mut Database<D>,
         mut local: Option<&mut Repository<L, D>>,
         remote: &Repository<Remote, D>,
+        metrics: Option<&dyn Metrics>,
     ) -> Result<bool>
     where
         Remote: RepositoryProvider<D>,
@@ -509,6 +1091,7 @@ where
         let root_path = MetadataPath::root();
 
         let mut updated = false;
+        let mut rotations = 0;
 
         loop {
             /////////////////////////////////////////
@@ -523,18 +1106,33 @@ where
             //     exact number is as yet unknown), then go to step 5.1.9. The value for Y is set
             //     by the authors of the application using TUF. For example, Y may be 2^10.
 
-            // FIXME(#306) We do not have an upper bound on the number of root metadata we'll
-            // fetch. This means that an attacker that's stolen the root keys could cause a client
-            // to fall into an infinite loop (but if an attacker has stolen the root keys, the
-            // client probably has worse problems to worry about).
+            if rotations >= config.max_root_rotations {
+                warn!(
+                    "stopped updating root metadata after {} versions",
+                    config.max_root_rotations
+                );
+                break;
+            }
+            rotations += 1;
 
             let next_version = MetadataVersion::Number(tuf.trusted_root().version() + 1);
             let res = remote
-                .fetch_metadata(&root_path, next_version, config.max_root_length, vec![])
+                .fetch_metadata(
+                    &root_path,
+                    next_version,
+                    config.max_root_length.max_length(),
+                    vec![],
+                )
                 .await;
 
             let raw_signed_root = match res {
-                Ok(raw_signed_root) => raw_signed_root,
+                Ok(raw_signed_root) => {
+                    record_bytes_downloaded(metrics, UpdatePhase::Root, &raw_signed_root);
+                    config
+                        .max_root_length
+                        .warn_if_exceeded(&root_path, &raw_signed_root);
+                    raw_signed_root
+                }
                 Err(Error::MetadataNotFound { .. }) => {
                     break;
                 }
@@ -545,7 +1143,9 @@ where
 
             updated = true;
 
-            tuf.update_root(&raw_signed_root)?;
+            let res = tuf.update_root(&raw_signed_root);
+            record_signature_verification(metrics, UpdatePhase::Root, &res);
+            res?;
 
             /////////////////////////////////////////
             // TUF-1.0.9 §5.1.7:
@@ -585,11 +1185,16 @@ where
             error!("Root metadata expired, potential freeze attack");
             return Err(Error::ExpiredMetadata {
                 path: MetadataPath::root(),
+                version: tuf.trusted_root().version(),
+                trusted_version: Some(tuf.trusted_root().version()),
                 expiration: *tuf.trusted_root().expires(),
                 now: *start_time,
             });
         }
 
+        // Make sure the remote did not withhold root metadata we have trusted before.
+        tuf.check_root_rollback_state()?;
+
         /////////////////////////////////////////
         // TUF-1.0.5 §5.1.10:
         //
@@ -600,15 +1205,27 @@ where
     }
 
     /// Returns `true` if an update occurred and `false` otherwise.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
     async fn update_timestamp(&mut self, start_time: &DateTime<Utc>) -> Result<bool> {
-        Self::update_timestamp_with_repos(
+        let started = Instant::now();
+        let res = Self::update_timestamp_with_repos(
             start_time,
             &self.config,
             &mut self.tuf,
             Some(&mut self.local),
             &self.remote,
+            self.metrics.as_deref(),
         )
-        .await
+        .await;
+
+        if let Some(metrics) = &self.metrics {
+            metrics.record_phase_duration(UpdatePhase::Timestamp, started.elapsed());
+        }
+
+        res
     }
 
     async fn update_timestamp_with_repos<Remote>(
@@ -617,6 +1234,7 @@ where
         tuf: &mut Database<D>,
         local: Option<&mut Repository<L, D>>,
         remote: &Repository<Remote, D>,
+        metrics: Option<&dyn Metrics>,
     ) -> Result<bool>
     where
         Remote: RepositoryProvider<D>,
@@ -635,15 +1253,20 @@ where
             .fetch_metadata(
                 &timestamp_path,
                 MetadataVersion::None,
-                config.max_timestamp_length,
+                config.max_timestamp_length.max_length(),
                 vec![],
             )
             .await?;
 
-        if tuf
-            .update_timestamp(start_time, &raw_signed_timestamp)?
-            .is_some()
-        {
+        record_bytes_downloaded(metrics, UpdatePhase::Timestamp, &raw_signed_timestamp);
+        config
+            .max_timestamp_length
+            .warn_if_exceeded(&timestamp_path, &raw_signed_timestamp);
+
+        let res = tuf.update_timestamp(start_time, &raw_signed_timestamp);
+        record_signature_verification(metrics, UpdatePhase::Timestamp, &res);
+
+        if res?.is_some() {
             /////////////////////////////////////////
             // TUF-1.0.9 §5.2.4:
             //
@@ -667,17 +1290,29 @@ where
     }
 
     /// Returns `true` if an update occurred and `false` otherwise.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
     async fn update_snapshot(&mut self, start_time: &DateTime<Utc>) -> Result<bool> {
         let consistent_snapshot = self.tuf.trusted_root().consistent_snapshot();
-        Self::update_snapshot_with_repos(
+        let started = Instant::now();
+        let res = Self::update_snapshot_with_repos(
             start_time,
             &self.config,
             &mut self.tuf,
             Some(&mut self.local),
             &self.remote,
             consistent_snapshot,
+            self.metrics.as_deref(),
         )
-        .await
+        .await;
+
+        if let Some(metrics) = &self.metrics {
+            metrics.record_phase_duration(UpdatePhase::Snapshot, started.elapsed());
+        }
+
+        res
     }
 
     async fn update_snapshot_with_repos<Remote>(
@@ -687,6 +1322,7 @@ where
         local: Option<&mut Repository<L, D>>,
         remote: &Repository<Remote, D>,
         consistent_snapshots: bool,
+        metrics: Option<&dyn Metrics>,
     ) -> Result<bool>
     where
         Remote: RepositoryProvider<D>,
@@ -700,6 +1336,12 @@ where
         }?
         .clone();
 
+        // A repository that publishes a snapshot Merkle tree lets us verify the snapshot entry of
+        // each role as we need it, so we don't need to download the whole snapshot metadata.
+        if tuf.trusted_merkle_root().is_some() {
+            return Ok(false);
+        }
+
         if snapshot_description.version()
             <= tuf.trusted_snapshot().map(|s| s.version()).unwrap_or(0)
         {
@@ -718,21 +1360,34 @@ where
 
         // Download snapshot metadata file, up to either the number of bytes specified in the
         // timestamp metadata file, or some Y number of bytes.
-        let snapshot_length = snapshot_description.length().or(config.max_snapshot_length);
+        let snapshot_length = snapshot_description
+            .length()
+            .or_else(|| config.max_snapshot_length.max_length());
 
         // https://theupdateframework.github.io/specification/v1.0.26/#update-snapshot 5.5.2:
         //
         // [...] The hashes of the new snapshot metadata file MUST match the hashes, if any, listed
         // in the trusted timestamp metadata.
-        let snapshot_hashes = crypto::retain_supported_hashes(snapshot_description.hashes());
+        let snapshot_hashes =
+            crypto::retain_allowed_hashes(snapshot_description.hashes(), &config.hash_algorithms);
 
         let raw_signed_snapshot = remote
             .fetch_metadata(&snapshot_path, version, snapshot_length, snapshot_hashes)
             .await?;
+        record_bytes_downloaded(metrics, UpdatePhase::Snapshot, &raw_signed_snapshot);
+
+        if snapshot_description.length().is_none() {
+            config
+                .max_snapshot_length
+                .warn_if_exceeded(&snapshot_path, &raw_signed_snapshot);
+        }
 
         // https://theupdateframework.github.io/specification/v1.0.26/#update-snapshot 5.5.3 through
         // 5.5.6 are checked in [Database].
-        if tuf.update_snapshot(start_time, &raw_signed_snapshot)? {
+        let res = tuf.update_snapshot(start_time, &raw_signed_snapshot);
+        record_signature_verification(metrics, UpdatePhase::Snapshot, &res);
+
+        if res? {
             // https://theupdateframework.github.io/specification/v1.0.26/#update-snapshot 5.5.7:
             //
             // Persist snapshot metadata. The client MUST write the file to non-volatile storage as
@@ -750,44 +1405,56 @@ where
     }
 
     /// Returns `true` if an update occurred and `false` otherwise.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
     async fn update_targets(&mut self, start_time: &DateTime<Utc>) -> Result<bool> {
         let consistent_snapshot = self.tuf.trusted_root().consistent_snapshot();
-        Self::update_targets_with_repos(
+        let started = Instant::now();
+        let res = Self::update_targets_with_repos(
             start_time,
             &self.config,
             &mut self.tuf,
             Some(&mut self.local),
             &self.remote,
             consistent_snapshot,
+            self.metrics.as_deref(),
         )
-        .await
+        .await;
+
+        if let Some(metrics) = &self.metrics {
+            metrics.record_phase_duration(UpdatePhase::Targets, started.elapsed());
+        }
+
+        res
     }
 
     async fn update_targets_with_repos<Remote>(
         start_time: &DateTime<Utc>,
         config: &Config,
         tuf: &mut Database<D>,
-        local: Option<&mut Repository<L, D>>,
+        mut local: Option<&mut Repository<L, D>>,
         remote: &Repository<Remote, D>,
         consistent_snapshot: bool,
+        metrics: Option<&dyn Metrics>,
     ) -> Result<bool>
     where
         Remote: RepositoryProvider<D>,
     {
-        let targets_description = match tuf.trusted_snapshot() {
-            Some(sn) => match sn.meta().get(&MetadataPath::targets()) {
-                Some(d) => Ok(d),
-                None => Err(Error::MissingMetadataDescription {
-                    parent_role: MetadataPath::snapshot(),
-                    child_role: MetadataPath::targets(),
-                }),
-            },
-            None => Err(Error::MetadataNotFound {
-                path: MetadataPath::snapshot(),
-                version: MetadataVersion::None,
-            }),
-        }?
-        .clone();
+        let targets_description = Self::snapshot_description_with_repos(
+            start_time,
+            config,
+            tuf,
+            local.as_deref_mut(),
+            remote,
+            &MetadataPath::targets(),
+        )
+        .await?
+        .ok_or_else(|| Error::MissingMetadataDescription {
+            parent_role: MetadataPath::snapshot(),
+            child_role: MetadataPath::targets(),
+        })?;
 
         if targets_description.version() <= tuf.trusted_targets().map(|t| t.version()).unwrap_or(0)
         {
@@ -806,19 +1473,32 @@ where
         //
         // Download the top-level targets metadata file, up to either the number of bytes specified
         // in the snapshot metadata file, or some Z number of bytes. [...]
-        let targets_length = targets_description.length().or(config.max_targets_length);
+        let targets_length = targets_description
+            .length()
+            .or_else(|| config.max_targets_length.max_length());
 
         // https://theupdateframework.github.io/specification/v1.0.26/#update-targets 5.6.2:
         //
         // Check against snapshot role’s targets hash. The hashes of the new targets metadata file
         // MUST match the hashes, if any, listed in the trusted snapshot metadata. [...]
-        let target_hashes = crypto::retain_supported_hashes(targets_description.hashes());
+        let target_hashes =
+            crypto::retain_allowed_hashes(targets_description.hashes(), &config.hash_algorithms);
 
         let raw_signed_targets = remote
             .fetch_metadata(&targets_path, version, targets_length, target_hashes)
             .await?;
+        record_bytes_downloaded(metrics, UpdatePhase::Targets, &raw_signed_targets);
+
+        if targets_description.length().is_none() {
+            config
+                .max_targets_length
+                .warn_if_exceeded(&targets_path, &raw_signed_targets);
+        }
 
-        if tuf.update_targets(start_time, &raw_signed_targets)? {
+        let res = tuf.update_targets(start_time, &raw_signed_targets);
+        record_signature_verification(metrics, UpdatePhase::Targets, &res);
+
+        if res? {
             /////////////////////////////////////////
             // TUF-1.0.9 §5.4.4:
             //
@@ -837,39 +1517,247 @@ where
         }
     }
 
-    /// Fetch a target from the remote repo.
-    ///
-    /// It is **critical** that none of the bytes written to the `write` are used until this future
-    /// returns `Ok`, as the hash of the target is not verified until all bytes are read from the
-    /// repository.
-    pub async fn fetch_target(
-        &mut self,
-        target: &TargetPath,
-    ) -> Result<impl AsyncRead + Send + Unpin + '_> {
-        self.fetch_target_with_start_time(target, &Utc::now()).await
+    /// Check that the snapshot entries of roles can be looked up, either because the snapshot
+    /// metadata is trusted or because the repository publishes a snapshot Merkle tree.
+    fn require_snapshot(&self) -> Result<()> {
+        if self.tuf.trusted_snapshot().is_none() && self.tuf.trusted_merkle_root().is_none() {
+            return Err(Error::MetadataNotFound {
+                path: MetadataPath::snapshot(),
+                version: MetadataVersion::None,
+            });
+        }
+        Ok(())
     }
 
-    /// Fetch a target from the remote repo.
-    ///
-    /// It is **critical** that none of the bytes written to the `write` are used until this future
-    /// returns `Ok`, as the hash of the target is not verified until all bytes are read from the
-    /// repository.
+    /// Look up the snapshot entry of `role`. See [Client::snapshot_description_with_repos].
+    async fn snapshot_description(
+        &mut self,
+        start_time: &DateTime<Utc>,
+        role: &MetadataPath,
+    ) -> Result<Option<MetadataDescription<TargetsMetadata>>> {
+        Self::snapshot_description_with_repos(
+            start_time,
+            &self.config,
+            &mut self.tuf,
+            Some(&mut self.local),
+            &self.remote,
+            role,
+        )
+        .await
+    }
+
+    /// Look up the snapshot entry of `role` in the trusted snapshot metadata or, if the repository
+    /// publishes a snapshot Merkle tree, by fetching and verifying the inclusion proof of `role`.
+    ///
+    /// Returns `None` if the repository doesn't describe `role`.
+    async fn snapshot_description_with_repos<Remote>(
+        start_time: &DateTime<Utc>,
+        config: &Config,
+        tuf: &mut Database<D>,
+        local: Option<&mut Repository<L, D>>,
+        remote: &Repository<Remote, D>,
+        role: &MetadataPath,
+    ) -> Result<Option<MetadataDescription<TargetsMetadata>>>
+    where
+        Remote: RepositoryProvider<D>,
+    {
+        if let Some(description) = tuf.trusted_snapshot_description(role) {
+            return Ok(Some(description.clone()));
+        }
+
+        if tuf.trusted_merkle_root().is_none() {
+            return match tuf.trusted_snapshot() {
+                Some(_) => Ok(None),
+                None => Err(Error::MetadataNotFound {
+                    path: MetadataPath::snapshot(),
+                    version: MetadataVersion::None,
+                }),
+            };
+        }
+
+        // Proofs grow with the logarithm of the number of roles, so they are limited to the size of
+        // the timestamp metadata that signs the Merkle root.
+        let proof = match remote
+            .fetch_snapshot_merkle_proof(role, config.max_timestamp_length.max_length())
+            .await
+        {
+            Ok(proof) => proof,
+            Err(Error::MetadataNotFound { .. }) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let description = tuf
+            .update_snapshot_merkle_proof(start_time, role, &proof)?
+            .clone();
+
+        // Persist the proof, so that the targets metadata of `role` can be loaded from the local
+        // repository.
+        if let Some(local) = local {
+            if let Err(err) = local.store_snapshot_merkle_proof(role, &proof).await {
+                warn!(
+                    "Error storing snapshot merkle proof of {:?} locally: {:?}",
+                    role, err
+                );
+            }
+        }
+
+        Ok(Some(description))
+    }
+
+    /// Fetch a target from the remote repo.
+    ///
+    /// It is **critical** that none of the bytes read from the returned [TargetReader] are used
+    /// until it has been fully consumed, as the hash of the target is not verified until all bytes
+    /// are read from the repository. Use [TargetReader::into_seekable] if random access to the
+    /// verified target is needed.
+    pub async fn fetch_target(
+        &mut self,
+        target: &TargetPath,
+    ) -> Result<TargetReader<impl AsyncRead + Send + Unpin + '_>> {
+        self.fetch_target_with_start_time(target, &self.tuf.clock().now())
+            .await
+    }
+
+    /// Fetch a target from the remote repo.
+    ///
+    /// See [Client::fetch_target] for more details.
     pub async fn fetch_target_with_start_time(
         &mut self,
         target: &TargetPath,
         start_time: &DateTime<Utc>,
-    ) -> Result<impl AsyncRead + Send + Unpin + '_> {
+    ) -> Result<TargetReader<impl AsyncRead + Send + Unpin + '_>> {
         let target_description = self
             .fetch_target_description_with_start_time(target, start_time)
             .await?;
 
+        let length = target_description.length();
+
         // TODO: Check the local repository to see if it already has the target.
-        self.remote
+        let read = self
+            .remote
             .fetch_target(
                 self.tuf.trusted_root().consistent_snapshot(),
                 target,
-                target_description,
+                target_description.clone(),
             )
+            .await?;
+
+        Ok(TargetReader {
+            read: read.report_progress(target, length, self.target_fetch_progress.clone()),
+            target_description,
+        })
+    }
+
+    /// Fetch a compressed target from the remote repo, and decompress it. See the
+    /// [compression](crate::compression) module for how compressed targets are described.
+    ///
+    /// The transferred bytes are verified against the target description, and the decompressed
+    /// bytes are verified against the decompressed length and hashes recorded in its custom
+    /// metadata. Verification errors are reported by a read failure on the returned `AsyncRead`.
+    ///
+    /// It is **critical** that none of the bytes from the returned `AsyncRead` are used until it
+    /// has been fully consumed as the data is untrusted.
+    #[cfg(feature = "compression")]
+    pub async fn fetch_target_decompressed(
+        &mut self,
+        target: &TargetPath,
+    ) -> Result<impl AsyncRead + Send + Unpin + '_> {
+        self.fetch_target_decompressed_with_start_time(target, &self.tuf.clock().now())
+            .await
+    }
+
+    /// Fetch a compressed target from the remote repo, and decompress it.
+    ///
+    /// See [Client::fetch_target_decompressed] for more details.
+    #[cfg(feature = "compression")]
+    pub async fn fetch_target_decompressed_with_start_time(
+        &mut self,
+        target: &TargetPath,
+        start_time: &DateTime<Utc>,
+    ) -> Result<impl AsyncRead + Send + Unpin + '_> {
+        use crate::compression::{self, Compression, DecompressedDescription};
+
+        let compression = Compression::from_target_path(target).ok_or_else(|| {
+            Error::IllegalArgument(format!("target {} is not compressed", target))
+        })?;
+
+        let target_description = self
+            .fetch_target_description_with_start_time(target, start_time)
+            .await?;
+
+        let decompressed = DecompressedDescription::from_target_description(&target_description)?
+            .ok_or_else(|| {
+            Error::IllegalArgument(format!(
+                "target {} does not describe its decompressed contents",
+                target
+            ))
+        })?;
+
+        let read = self
+            .fetch_target_with_start_time(target, start_time)
+            .await?;
+
+        compression::decompress(compression, read, &decompressed)
+    }
+
+    /// Fetch a batch of targets from the remote repo, downloading up to `concurrency` targets at
+    /// the same time.
+    ///
+    /// The target descriptions are resolved first, and then each target is downloaded into memory
+    /// and verified against its description. The returned results are in the same order as
+    /// `targets`, and a failure to fetch one target does not prevent the others from being
+    /// fetched. Since every target is fully verified before it is returned, the bytes of a
+    /// successful result are safe to use. A `concurrency` of `0` is treated as `1`.
+    pub async fn fetch_targets(
+        &mut self,
+        targets: &[TargetPath],
+        concurrency: usize,
+    ) -> Vec<Result<Vec<u8>>> {
+        self.fetch_targets_with_start_time(targets, concurrency, &self.tuf.clock().now())
+            .await
+    }
+
+    /// Fetch a batch of targets from the remote repo, downloading up to `concurrency` targets at
+    /// the same time.
+    ///
+    /// See [Client::fetch_targets] for more details.
+    pub async fn fetch_targets_with_start_time(
+        &mut self,
+        targets: &[TargetPath],
+        concurrency: usize,
+        start_time: &DateTime<Utc>,
+    ) -> Vec<Result<Vec<u8>>> {
+        // Resolving the descriptions may need to fetch delegated metadata and update the
+        // database, so this is done serially.
+        let mut target_descriptions = Vec::with_capacity(targets.len());
+        for target in targets {
+            target_descriptions.push(
+                self.fetch_target_description_with_start_time(target, start_time)
+                    .await,
+            );
+        }
+
+        let consistent_snapshot = self.tuf.trusted_root().consistent_snapshot();
+        let remote = &self.remote;
+        let target_fetch_progress = &self.target_fetch_progress;
+
+        stream::iter(targets.iter().zip(target_descriptions))
+            .map(|(target, target_description)| async move {
+                let target_description = target_description?;
+                let length = target_description.length();
+
+                let mut read = remote
+                    .fetch_target(consistent_snapshot, target, target_description)
+                    .await?
+                    .report_progress(target, length, target_fetch_progress.clone());
+
+                let mut buf = Vec::new();
+                read.read_to_end(&mut buf).await?;
+
+                Ok::<_, Error>(buf)
+            })
+            .buffered(concurrency.max(1))
+            .collect()
             .await
     }
 
@@ -879,7 +1767,7 @@ where
     /// returns `Ok`, as the hash of the target is not verified until all bytes are read from the
     /// repository.
     pub async fn fetch_target_to_local(&mut self, target: &TargetPath) -> Result<()> {
-        self.fetch_target_to_local_with_start_time(target, &Utc::now())
+        self.fetch_target_to_local_with_start_time(target, &self.tuf.clock().now())
             .await
     }
 
@@ -902,9 +1790,15 @@ where
         // won't complain about trying to borrow `&self` for the fetch, and
         // `&mut self` for the store.
         let Client {
-            tuf, local, remote, ..
+            tuf,
+            local,
+            remote,
+            target_fetch_progress,
+            ..
         } = self;
 
+        let length = target_description.length();
+
         // TODO: Check the local repository to see if it already has the target.
         let mut read = remote
             .fetch_target(
@@ -912,866 +1806,4958 @@ where
                 target,
                 target_description,
             )
-            .await?;
+            .await?
+            .report_progress(target, length, target_fetch_progress.clone());
 
         local.store_target(target, &mut read).await
     }
 
-    /// Fetch a target description from the remote repo and return it.
-    pub async fn fetch_target_description(
-        &mut self,
-        target: &TargetPath,
-    ) -> Result<TargetDescription> {
-        self.fetch_target_description_with_start_time(target, &Utc::now())
+    /// Fetch a target from the remote repo and atomically install it at `path`.
+    ///
+    /// The target is streamed into a temporary file in the same directory as `path`. Only once the
+    /// length and hashes of the target have been verified is the temporary file synced to disk and
+    /// renamed to `path`, so unverified bytes are never visible at `path`. If the target fails to
+    /// verify, the temporary file is removed and `path` is left untouched.
+    pub async fn fetch_target_to_path<P>(&mut self, target: &TargetPath, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        self.fetch_target_to_path_with_start_time(target, path, &self.tuf.clock().now())
             .await
     }
 
-    /// Fetch a target description from the remote repo and return it.
-    pub async fn fetch_target_description_with_start_time(
+    /// Fetch a target from the remote repo and atomically install it at `path`.
+    ///
+    /// See [Client::fetch_target_to_path] for more details.
+    pub async fn fetch_target_to_path_with_start_time<P>(
         &mut self,
         target: &TargetPath,
+        path: P,
         start_time: &DateTime<Utc>,
-    ) -> Result<TargetDescription> {
-        let snapshot = self
-            .tuf
-            .trusted_snapshot()
-            .ok_or_else(|| Error::MetadataNotFound {
-                path: MetadataPath::snapshot(),
-                version: MetadataVersion::None,
-            })?
-            .clone();
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
 
-        /////////////////////////////////////////
-        // https://theupdateframework.github.io/specification/v1.0.30/#update-targets:
-        //
-        //     7. **Perform a pre-order depth-first search for metadata about the
-        //     desired target, beginning with the top-level targets role.** Note: If
-        //     any metadata requested in steps 5.6.7.1 - 5.6.7.2 cannot be downloaded nor
-        //     validated, end the search and report that the target cannot be found.
+        let mut read = self
+            .fetch_target_with_start_time(target, start_time)
+            .await?;
 
-        let (_, target_description) = self
-            .lookup_target_description(start_time, false, 0, target, &snapshot, None)
-            .await;
+        let mut temp_file = AllowStdIo::new(create_temp_file(path)?);
+        if let Err(err) = copy(&mut read, &mut temp_file).await {
+            return Err(Error::IoPath {
+                path: path.to_path_buf(),
+                err,
+            });
+        }
 
-        target_description
+        // The target has been fully read and verified, so it is now safe to install it.
+        let temp_file = temp_file.into_inner();
+        temp_file
+            .as_file()
+            .sync_all()
+            .map_err(|err| Error::IoPath {
+                path: temp_file.path().to_path_buf(),
+                err,
+            })?;
+        temp_file.persist(path).map_err(|err| Error::IoPath {
+            path: path.to_path_buf(),
+            err: err.error,
+        })?;
+
+        Ok(())
     }
 
-    async fn lookup_target_description(
+    /// Atomically install a target at `path`, reusing the local file `candidate` if it already
+    /// matches the trusted description of the target, and otherwise fetching the target from the
+    /// remote repo as with [Client::fetch_target_to_path].
+    ///
+    /// This avoids downloading a large target again when a previous download completed, for
+    /// example when a device rebooted before the update finished. `candidate` may be the same as
+    /// `path`, in which case a matching file is left in place. A candidate that is missing or does
+    /// not match is ignored, and is not modified.
+    ///
+    /// Returns `true` if the target was downloaded, or `false` if `candidate` was used.
+    pub async fn fetch_target_to_path_with_candidate<P, C>(
         &mut self,
-        start_time: &DateTime<Utc>,
-        default_terminate: bool,
-        current_depth: u32,
         target: &TargetPath,
-        snapshot: &SnapshotMetadata,
-        targets: Option<(&Verified<TargetsMetadata>, MetadataPath)>,
-    ) -> (bool, Result<TargetDescription>) {
-        if current_depth > self.config.max_delegation_depth {
-            warn!(
-                "Walking the delegation graph would have exceeded the configured max depth: {}",
-                self.config.max_delegation_depth
-            );
-            return (
-                default_terminate,
-                Err(Error::TargetNotFound(target.clone())),
-            );
-        }
+        path: P,
+        candidate: C,
+    ) -> Result<bool>
+    where
+        P: AsRef<Path>,
+        C: AsRef<Path>,
+    {
+        self.fetch_target_to_path_with_candidate_and_start_time(
+            target,
+            path,
+            candidate,
+            &self.tuf.clock().now(),
+        )
+        .await
+    }
 
-        // these clones are dumb, but we need immutable values and not references for update
-        // tuf in the loop below
-        let (targets, targets_role) = match targets {
-            Some((t, role)) => (t.clone(), role),
-            None => match self.tuf.trusted_targets() {
-                Some(t) => (t.clone(), MetadataPath::targets()),
-                None => {
-                    return (
-                        default_terminate,
-                        Err(Error::MetadataNotFound {
-                            path: MetadataPath::targets(),
-                            version: MetadataVersion::None,
-                        }),
-                    );
-                }
-            },
-        };
+    /// Atomically install a target at `path`, reusing the local file `candidate` if it already
+    /// matches the trusted description of the target.
+    ///
+    /// See [Client::fetch_target_to_path_with_candidate] for more details.
+    pub async fn fetch_target_to_path_with_candidate_and_start_time<P, C>(
+        &mut self,
+        target: &TargetPath,
+        path: P,
+        candidate: C,
+        start_time: &DateTime<Utc>,
+    ) -> Result<bool>
+    where
+        P: AsRef<Path>,
+        C: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let candidate = candidate.as_ref();
 
-        if let Some(t) = targets.targets().get(target) {
-            return (default_terminate, Ok(t.clone()));
-        }
+        // Make sure the metadata describing the target is trusted, which may require fetching
+        // delegated targets metadata.
+        let _ = self
+            .fetch_target_description_with_start_time(target, start_time)
+            .await?;
 
-        for delegation in targets.delegations().roles() {
-            if !delegation.paths().iter().any(|p| target.is_child(p)) {
-                if delegation.terminating() {
-                    return (true, Err(Error::TargetNotFound(target.clone())));
-                } else {
-                    continue;
-                }
+        let file = match File::open(candidate) {
+            Ok(file) => Some(file),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => {
+                return Err(Error::IoPath {
+                    path: candidate.to_path_buf(),
+                    err,
+                })
             }
+        };
 
-            let role_meta = match snapshot.meta().get(delegation.name()) {
-                Some(m) => m,
-                None if delegation.terminating() => {
-                    return (true, Err(Error::TargetNotFound(target.clone())));
-                }
-                None => {
-                    continue;
+        if let Some(file) = file {
+            if candidate == path {
+                match self
+                    .tuf
+                    .verify_target_with_start_time(start_time, target, AllowStdIo::new(file))
+                    .await
+                {
+                    Ok(_) => return Ok(false),
+                    Err(err) => {
+                        warn!(
+                            "Candidate {:?} does not match target {}: {}",
+                            candidate, target, err
+                        );
+                    }
                 }
-            };
-
-            /////////////////////////////////////////
-            // TUF-1.0.9 §5.4:
-            //
-            //     Download the top-level targets metadata file, up to either the number of bytes
-            //     specified in the snapshot metadata file, or some Z number of bytes. The value
-            //     for Z is set by the authors of the application using TUF. For example, Z may be
-            //     tens of kilobytes. If consistent snapshots are not used (see Section 7), then
-            //     the filename used to download the targets metadata file is of the fixed form
-            //     FILENAME.EXT (e.g., targets.json). Otherwise, the filename is of the form
-            //     VERSION_NUMBER.FILENAME.EXT (e.g., 42.targets.json), where VERSION_NUMBER is the
-            //     version number of the targets metadata file listed in the snapshot metadata
-            //     file.
-
-            let version = if self.tuf.trusted_root().consistent_snapshot() {
-                MetadataVersion::Number(role_meta.version())
             } else {
-                MetadataVersion::None
-            };
-
-            let role_length = role_meta.length().or(self.config.max_targets_length);
-
-            // https://theupdateframework.github.io/specification/v1.0.26/#update-targets
-            //
-            //     [...] The hashes of the new targets metadata file MUST match the hashes, if
-            //      any, listed in the trusted snapshot metadata.
-            let role_hashes = crypto::retain_supported_hashes(role_meta.hashes());
-
-            let raw_signed_meta = match self
-                .remote
-                .fetch_metadata(delegation.name(), version, role_length, role_hashes)
-                .await
-            {
-                Ok(m) => m,
-                Err(e) => {
-                    warn!("Failed to fetch metadata {:?}: {:?}", delegation.name(), e);
-                    if delegation.terminating() {
-                        return (true, Err(e));
-                    } else {
-                        continue;
+                // Copy the candidate before verifying it, so that it cannot change between being
+                // verified and being installed.
+                let mut temp_file = create_temp_file(path)?;
+                io::copy(&mut io::BufReader::new(file), &mut temp_file).map_err(|err| {
+                    Error::IoPath {
+                        path: temp_file.path().to_path_buf(),
+                        err,
                     }
-                }
-            };
+                })?;
 
-            match self.tuf.update_delegated_targets(
-                start_time,
-                &targets_role,
-                delegation.name(),
-                &raw_signed_meta,
-            ) {
-                Ok(_) => {
-                    /////////////////////////////////////////
-                    // TUF-1.0.9 §5.4.4:
-                    //
-                    //     Persist targets metadata. The client MUST write the file to non-volatile
-                    //     storage as FILENAME.EXT (e.g. targets.json).
-
-                    match self
-                        .local
-                        .store_metadata(delegation.name(), MetadataVersion::None, &raw_signed_meta)
-                        .await
-                    {
-                        Ok(_) => (),
-                        Err(e) => {
-                            warn!(
-                                "Error storing metadata {:?} locally: {:?}",
-                                delegation.name(),
-                                e
-                            )
-                        }
-                    }
+                let read = temp_file.reopen().map_err(|err| Error::IoPath {
+                    path: temp_file.path().to_path_buf(),
+                    err,
+                })?;
 
-                    let meta = self
-                        .tuf
-                        .trusted_delegations()
-                        .get(delegation.name())
-                        .unwrap()
-                        .clone();
-                    let f: Pin<Box<dyn Future<Output = _>>> =
-                        Box::pin(self.lookup_target_description(
-                            start_time,
-                            delegation.terminating(),
-                            current_depth + 1,
-                            target,
-                            snapshot,
-                            Some((&meta, delegation.name().clone())),
-                        ));
-                    let (term, res) = f.await;
-
-                    if term && res.is_err() {
-                        return (true, res);
+                match self
+                    .tuf
+                    .verify_target_with_start_time(start_time, target, AllowStdIo::new(read))
+                    .await
+                {
+                    Ok(_) => {
+                        temp_file
+                            .as_file()
+                            .sync_all()
+                            .map_err(|err| Error::IoPath {
+                                path: temp_file.path().to_path_buf(),
+                                err,
+                            })?;
+                        temp_file.persist(path).map_err(|err| Error::IoPath {
+                            path: path.to_path_buf(),
+                            err: err.error,
+                        })?;
+
+                        return Ok(false);
+                    }
+                    Err(err) => {
+                        warn!(
+                            "Candidate {:?} does not match target {}: {}",
+                            candidate, target, err
+                        );
                     }
-
-                    // TODO end recursion early
                 }
-                Err(_) if !delegation.terminating() => continue,
-                Err(e) => return (true, Err(e)),
-            };
+            }
         }
 
-        (
-            default_terminate,
-            Err(Error::TargetNotFound(target.clone())),
+        self.fetch_target_to_path_with_start_time(target, path, start_time)
+            .await?;
+
+        Ok(true)
+    }
+
+    /// Fetch a target and write it to `path`, reconstructing it from the previous release at
+    /// `source` if the target lists a delta for it. See the [delta](crate::delta) module for how
+    /// deltas are described in targets metadata.
+    ///
+    /// The patch is fetched and verified like any other target, applied to `source` with
+    /// `patcher`, and the result is verified against the trusted description of `target` before it
+    /// is written to `path`. If `source` does not exist, there is no delta for it, or the delta
+    /// can't be fetched or does not produce the expected target, the full target is fetched
+    /// instead.
+    ///
+    /// Returns `true` if the target was reconstructed from a delta, and `false` if the full target
+    /// was fetched.
+    pub async fn fetch_target_to_path_with_delta<P, S>(
+        &mut self,
+        target: &TargetPath,
+        path: P,
+        source: S,
+        patcher: &dyn DeltaPatcher,
+    ) -> Result<bool>
+    where
+        P: AsRef<Path>,
+        S: AsRef<Path>,
+    {
+        self.fetch_target_to_path_with_delta_and_start_time(
+            target,
+            path,
+            source,
+            patcher,
+            &self.tuf.clock().now(),
         )
+        .await
     }
-}
 
-/// Deconstructed parts of a [Client].
-///
-/// This allows taking apart a [Client] in order to reclaim the [Database],
-/// local, and remote repositories.
-#[non_exhaustive]
-#[derive(Debug)]
-pub struct Parts<D, L, R>
-where
-    D: Pouf,
-    L: RepositoryProvider<D> + RepositoryStorage<D>,
-    R: RepositoryProvider<D>,
-{
-    /// The client configuration.
-    pub config: Config,
-
-    /// The Tuf database, which is updated by the [Client].
-    pub database: Database<D>,
+    /// Fetch a target and write it to `path`, reconstructing it from `source` with a delta if
+    /// possible.
+    ///
+    /// See [Client::fetch_target_to_path_with_delta] for more details.
+    pub async fn fetch_target_to_path_with_delta_and_start_time<P, S>(
+        &mut self,
+        target: &TargetPath,
+        path: P,
+        source: S,
+        patcher: &dyn DeltaPatcher,
+        start_time: &DateTime<Utc>,
+    ) -> Result<bool>
+    where
+        P: AsRef<Path>,
+        S: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let source = source.as_ref();
 
-    /// The local repository, which is used to initialize the database, and
-    /// is updated by the [Client].
-    pub local: L,
+        let target_description = self
+            .fetch_target_description_with_start_time(target, start_time)
+            .await?;
 
-    /// The remote repository, which is used by the client to update the database.
-    pub remote: R,
-}
+        let source_bytes = match std::fs::read(source) {
+            Ok(source_bytes) => Some(source_bytes),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => {
+                return Err(Error::IoPath {
+                    path: source.to_path_buf(),
+                    err,
+                })
+            }
+        };
 
-/// Helper function that first tries to fetch the metadata from the local store, and if it doesn't
-/// exist or does and fails to parse, try fetching it from the remote store.
-async fn fetch_metadata_from_local_or_else_remote<'a, D, L, R, M>(
-    path: &'a MetadataPath,
-    version: MetadataVersion,
-    max_length: Option<usize>,
-    hashes: Vec<(&'static HashAlgorithm, HashValue)>,
-    local: &'a Repository<L, D>,
-    remote: &'a Repository<R, D>,
-) -> Result<(bool, RawSignedMetadata<D, M>)>
-where
-    D: Pouf,
-    L: RepositoryProvider<D> + RepositoryStorage<D>,
-    R: RepositoryProvider<D>,
-    M: Metadata + 'static,
-{
-    match local
-        .fetch_metadata(path, version, max_length, hashes.clone())
-        .await
-    {
-        Ok(raw_meta) => Ok((false, raw_meta)),
-        Err(Error::MetadataNotFound { .. }) => {
-            let raw_meta = remote
-                .fetch_metadata(path, version, max_length, hashes)
-                .await?;
-            Ok((true, raw_meta))
+        if let Some(source_bytes) = source_bytes {
+            match self
+                .apply_delta(
+                    target,
+                    &target_description,
+                    &source_bytes,
+                    patcher,
+                    start_time,
+                )
+                .await
+            {
+                Ok(Some(output)) => {
+                    let mut temp_file = create_temp_file(path)?;
+                    io::Write::write_all(&mut temp_file, &output)
+                        .and_then(|()| temp_file.as_file().sync_all())
+                        .map_err(|err| Error::IoPath {
+                            path: temp_file.path().to_path_buf(),
+                            err,
+                        })?;
+                    temp_file.persist(path).map_err(|err| Error::IoPath {
+                        path: path.to_path_buf(),
+                        err: err.error,
+                    })?;
+
+                    return Ok(true);
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    warn!(
+                        "Failed to apply delta from {:?} to target {}: {}",
+                        source, target, err
+                    );
+                }
+            }
         }
-        Err(err) => Err(err),
-    }
-}
 
-/// Configuration for a TUF `Client`.
-///
-/// # Defaults
-///
-/// The following values are considered reasonably safe defaults, however these values may change
-/// as this crate moves out of beta. If you are concered about them changing, you should use the
-/// `ConfigBuilder` and set your own values.
-///
-/// ```
-/// # use tuf::client::{Config};
-/// let config = Config::default();
-/// assert_eq!(config.max_root_length(), &Some(500 * 1024));
-/// assert_eq!(config.max_timestamp_length(), &Some(16 * 1024));
-/// assert_eq!(config.max_snapshot_length(), &Some(2000000));
-/// assert_eq!(config.max_targets_length(), &Some(5000000));
-/// assert_eq!(config.max_delegation_depth(), 8);
-/// ```
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub struct Config {
-    max_root_length: Option<usize>,
-    max_timestamp_length: Option<usize>,
-    max_snapshot_length: Option<usize>,
-    max_targets_length: Option<usize>,
-    max_delegation_depth: u32,
-}
+        self.fetch_target_to_path_with_start_time(target, path, start_time)
+            .await?;
 
-impl Config {
-    /// Initialize a `ConfigBuilder` with the default values.
-    pub fn build() -> ConfigBuilder {
-        ConfigBuilder::default()
+        Ok(false)
     }
 
-    /// Return the optional maximum root metadata length.
-    pub fn max_root_length(&self) -> &Option<usize> {
-        &self.max_root_length
+    /// Reconstruct `target` from `source` with the delta listed in `target_description`, and
+    /// verify the result. Returns `None` if there is no delta for `source`.
+    async fn apply_delta(
+        &mut self,
+        target: &TargetPath,
+        target_description: &TargetDescription,
+        source: &[u8],
+        patcher: &dyn DeltaPatcher,
+        start_time: &DateTime<Utc>,
+    ) -> Result<Option<Vec<u8>>> {
+        let delta = match delta::find_delta(target_description, source)? {
+            Some(delta) => delta,
+            None => return Ok(None),
+        };
+
+        let mut patch = Vec::new();
+        self.fetch_target_with_start_time(delta.patch(), start_time)
+            .await?
+            .read_to_end(&mut patch)
+            .await?;
+
+        let output = patcher.apply_patch(source, &patch)?;
+
+        // The patch is trusted, but the result must still match the trusted description of the
+        // target, in case the patch was built against a different source.
+        self.tuf
+            .verify_target_with_start_time(start_time, target, &output[..])
+            .await?;
+
+        Ok(Some(output))
     }
 
-    /// Return the optional maximum timestamp metadata size.
-    pub fn max_timestamp_length(&self) -> &Option<usize> {
-        &self.max_timestamp_length
+    /// Verify that a previously fetched target read from `read` matches the trusted description
+    /// of `target_path`, without any network access. Returns the trusted [TargetDescription] if
+    /// the target is intact.
+    ///
+    /// Only the metadata already trusted by the client is consulted, so targets that are
+    /// delegated to roles that have not yet been fetched will not be found. See
+    /// [Database::verify_target].
+    pub async fn verify_cached_target<Rd>(
+        &self,
+        target_path: &TargetPath,
+        read: Rd,
+    ) -> Result<TargetDescription>
+    where
+        Rd: AsyncRead + Unpin,
+    {
+        self.tuf.verify_target(target_path, read).await
     }
 
-    /// Return the optional maximum snapshot metadata size.
-    pub fn max_snapshot_length(&self) -> &Option<usize> {
-        &self.max_snapshot_length
+    /// Fetch a target description from the remote repo and return it.
+    pub async fn fetch_target_description(
+        &mut self,
+        target: &TargetPath,
+    ) -> Result<TargetDescription> {
+        self.fetch_target_description_with_start_time(target, &self.tuf.clock().now())
+            .await
     }
 
-    /// Return the optional maximum targets metadata size.
-    pub fn max_targets_length(&self) -> &Option<usize> {
-        &self.max_targets_length
+    /// Fetch a target description from the remote repo and return it.
+    pub async fn fetch_target_description_with_start_time(
+        &mut self,
+        target: &TargetPath,
+        start_time: &DateTime<Utc>,
+    ) -> Result<TargetDescription> {
+        let (target_description, _) = self
+            .fetch_target_description_with_trace_and_start_time(target, start_time)
+            .await;
+
+        target_description
     }
 
-    /// The maximum number of steps used when walking the delegation graph.
-    pub fn max_delegation_depth(&self) -> u32 {
-        self.max_delegation_depth
+    /// Fetch a target description from the remote repo and return it, along with a
+    /// [DelegationTrace] of the roles that were consulted while resolving it.
+    ///
+    /// The trace is returned even if the target could not be resolved, which helps to debug
+    /// delegations that do not route a target to the expected role.
+    pub async fn fetch_target_description_with_trace(
+        &mut self,
+        target: &TargetPath,
+    ) -> (Result<TargetDescription>, DelegationTrace) {
+        self.fetch_target_description_with_trace_and_start_time(target, &self.tuf.clock().now())
+            .await
     }
-}
 
-impl Default for Config {
-    fn default() -> Self {
-        Config {
-            max_root_length: Some(500 * 1024),
-            max_timestamp_length: Some(16 * 1024),
-            max_snapshot_length: Some(2000000),
-            max_targets_length: Some(5000000),
-            max_delegation_depth: 8,
+    /// Fetch a target description from the remote repo and return it, along with a
+    /// [DelegationTrace] of the roles that were consulted while resolving it.
+    ///
+    /// See [Client::fetch_target_description_with_trace] for more details.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(target = %target))
+    )]
+    pub async fn fetch_target_description_with_trace_and_start_time(
+        &mut self,
+        target: &TargetPath,
+        start_time: &DateTime<Utc>,
+    ) -> (Result<TargetDescription>, DelegationTrace) {
+        let mut trace = DelegationTrace { steps: vec![] };
+
+        if let Err(err) = self.require_snapshot() {
+            return (Err(err), trace);
         }
-    }
-}
 
-/// Helper for building and validating a TUF client `Config`.
-#[derive(Debug, Default, PartialEq, Eq)]
-pub struct ConfigBuilder {
-    cfg: Config,
-}
+        /////////////////////////////////////////
+        // https://theupdateframework.github.io/specification/v1.0.30/#update-targets:
+        //
+        //     7. **Perform a pre-order depth-first search for metadata about the
+        //     desired target, beginning with the top-level targets role.** Note: If
+        //     any metadata requested in steps 5.6.7.1 - 5.6.7.2 cannot be downloaded nor
+        //     validated, end the search and report that the target cannot be found.
 
-impl ConfigBuilder {
-    /// Validate this builder return a `Config` if validation succeeds.
-    pub fn finish(self) -> Result<Config> {
-        Ok(self.cfg)
+        let (_, target_description) = self
+            .lookup_target_description(start_time, false, 0, &mut 0, target, None, &mut trace.steps)
+            .await;
+
+        (target_description, trace)
     }
 
-    /// Set the optional maximum download length for root metadata.
-    pub fn max_root_length(mut self, max: Option<usize>) -> Self {
-        self.cfg.max_root_length = max;
-        self
+    /// Update the root, timestamp and snapshot metadata, and fetch the top-level targets metadata
+    /// as a [LazyTargetsMetadata], which only parses the descriptions of the targets that are
+    /// looked up in it.
+    ///
+    /// This is meant for lookup-only flows that need a handful of targets from very large targets
+    /// metadata. The returned metadata is verified like [Client::update] would, but it is not
+    /// trusted by the [Database] nor stored in the local repository, and its delegations are not
+    /// followed.
+    pub async fn fetch_lazy_targets(&mut self) -> Result<Verified<LazyTargetsMetadata>> {
+        self.fetch_lazy_targets_with_start_time(&self.tuf.clock().now())
+            .await
     }
 
-    /// Set the optional maximum download length for timestamp metadata.
-    pub fn max_timestamp_length(mut self, max: Option<usize>) -> Self {
-        self.cfg.max_timestamp_length = max;
-        self
+    /// Fetch the top-level targets metadata as a [LazyTargetsMetadata], using the specified time
+    /// to determine if the metadata is expired.
+    ///
+    /// See [Client::fetch_lazy_targets] for more details.
+    pub async fn fetch_lazy_targets_with_start_time(
+        &mut self,
+        start_time: &DateTime<Utc>,
+    ) -> Result<Verified<LazyTargetsMetadata>> {
+        let _ = self.update_root(start_time).await?;
+        let _ = self.update_timestamp(start_time).await?;
+        let _ = self.update_snapshot(start_time).await?;
+
+        let targets_path = MetadataPath::targets();
+        let targets_description = self
+            .snapshot_description(start_time, &targets_path)
+            .await?
+            .ok_or_else(|| Error::MissingMetadataDescription {
+                parent_role: MetadataPath::snapshot(),
+                child_role: targets_path.clone(),
+            })?;
+
+        let version = if self.tuf.trusted_root().consistent_snapshot() {
+            MetadataVersion::Number(targets_description.version())
+        } else {
+            MetadataVersion::None
+        };
+        let targets_length = targets_description
+            .length()
+            .or_else(|| self.config.max_targets_length.max_length());
+        let target_hashes = crypto::retain_allowed_hashes(
+            targets_description.hashes(),
+            &self.config.hash_algorithms,
+        );
+
+        let raw_signed_targets = self
+            .remote
+            .fetch_metadata(&targets_path, version, targets_length, target_hashes)
+            .await?;
+        record_bytes_downloaded(
+            self.metrics.as_deref(),
+            UpdatePhase::Targets,
+            &raw_signed_targets,
+        );
+
+        let res = self
+            .tuf
+            .verify_targets_lazily(start_time, &raw_signed_targets);
+        record_signature_verification(self.metrics.as_deref(), UpdatePhase::Targets, &res);
+
+        res
     }
 
-    /// Set the optional maximum download length for snapshot metadata.
-    pub fn max_snapshot_length(mut self, max: Option<usize>) -> Self {
-        self.cfg.max_snapshot_length = max;
-        self
+    /// List every target reachable from the trusted targets metadata, fetching delegated targets
+    /// metadata from the remote repository as needed.
+    ///
+    /// Each [TrustedTarget] carries the description that [Client::fetch_target_description] would
+    /// resolve for its path, along with the role that signed it. Targets are listed in the order
+    /// their roles are visited in a pre-order walk of the delegation graph, and sorted by path
+    /// within each role. Delegated roles that cannot be fetched or verified are skipped, so their
+    /// targets are not listed.
+    ///
+    /// Returns [Error::MaxDelegationDepthExceeded] or [Error::MaxVisitedRolesExceeded] if the
+    /// delegation graph is larger than the limits set in the [Config].
+    pub async fn trusted_targets_iter(&mut self) -> Result<impl Iterator<Item = TrustedTarget>> {
+        self.trusted_targets_iter_with_start_time(&self.tuf.clock().now())
+            .await
     }
 
-    /// Set the optional maximum download length for targets metadata.
-    pub fn max_targets_length(mut self, max: Option<usize>) -> Self {
-        self.cfg.max_targets_length = max;
-        self
+    /// List every target reachable from the trusted targets metadata, using the specified time to
+    /// determine if the metadata is expired.
+    ///
+    /// See [Client::trusted_targets_iter] for more details.
+    pub async fn trusted_targets_iter_with_start_time(
+        &mut self,
+        start_time: &DateTime<Utc>,
+    ) -> Result<impl Iterator<Item = TrustedTarget>> {
+        self.list_trusted_targets(start_time, false).await
     }
 
-    /// Set the maximum number of steps used when walking the delegation graph.
-    pub fn max_delegation_depth(mut self, max: u32) -> Self {
-        self.cfg.max_delegation_depth = max;
-        self
+    /// List every target reachable from the trusted targets metadata, like
+    /// [Client::trusted_targets_iter], but return an error instead of skipping a delegated role
+    /// that is not described by the snapshot, or that cannot be fetched or verified. Use this when
+    /// the listed targets must be complete, such as before removing the targets that aren't listed.
+    pub async fn trusted_targets_iter_strict(
+        &mut self,
+    ) -> Result<impl Iterator<Item = TrustedTarget>> {
+        self.list_trusted_targets(&self.tuf.clock().now(), true)
+            .await
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::crypto::{Ed25519PrivateKey, HashAlgorithm, PrivateKey};
-    use crate::metadata::{
-        MetadataDescription, MetadataPath, MetadataVersion, RootMetadataBuilder,
-        SnapshotMetadataBuilder, TargetsMetadataBuilder, TimestampMetadataBuilder,
-    };
-    use crate::pouf::Pouf1;
-    use crate::repo_builder::RepoBuilder;
-    use crate::repository::{
-        fetch_metadata_to_string, EphemeralRepository, ErrorRepository, Track, TrackRepository,
-    };
-    use assert_matches::assert_matches;
-    use chrono::prelude::*;
-    use futures_executor::block_on;
-    use lazy_static::lazy_static;
-    use maplit::hashmap;
-    use pretty_assertions::assert_eq;
-    use serde_json::json;
-    use std::collections::HashMap;
-    use std::iter::once;
+    /// List the trusted targets, skipping the delegated roles that cannot be trusted unless
+    /// `strict` is set.
+    async fn list_trusted_targets(
+        &mut self,
+        start_time: &DateTime<Utc>,
+        strict: bool,
+    ) -> Result<std::vec::IntoIter<TrustedTarget>> {
+        self.require_snapshot()?;
+        let targets = self
+            .tuf
+            .trusted_targets()
+            .ok_or_else(|| Error::MetadataNotFound {
+                path: MetadataPath::targets(),
+                version: MetadataVersion::None,
+            })?
+            .clone();
 
-    lazy_static! {
-        static ref KEYS: Vec<Ed25519PrivateKey> = {
-            let keys: &[&[u8]] = &[
-                include_bytes!("../tests/ed25519/ed25519-1.pk8.der"),
-                include_bytes!("../tests/ed25519/ed25519-2.pk8.der"),
-                include_bytes!("../tests/ed25519/ed25519-3.pk8.der"),
-                include_bytes!("../tests/ed25519/ed25519-4.pk8.der"),
-                include_bytes!("../tests/ed25519/ed25519-5.pk8.der"),
-                include_bytes!("../tests/ed25519/ed25519-6.pk8.der"),
-            ];
-            keys.iter()
-                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
-                .collect()
-        };
-    }
+        // Walk the delegation graph in pre-order, the same order used when resolving a single
+        // target, and collect every target any reachable role claims to describe.
+        let mut candidates = vec![];
+        let mut visited = HashSet::new();
+        let mut stack = vec![(MetadataPath::targets(), Arc::new(targets), 0)];
+
+        while let Some((role, targets, depth)) = stack.pop() {
+            let mut role_targets = targets.targets().keys().collect::<Vec<_>>();
+            role_targets.sort();
+            for path in role_targets {
+                candidates.push((path.clone(), role.clone()));
+            }
 
-    #[allow(clippy::enum_variant_names)]
-    enum ConstructorMode {
-        WithTrustedLocal,
-        WithTrustedRoot,
-        WithTrustedRootKeys,
-        FromDatabase,
-    }
+            if !targets.delegations().has_roles() {
+                continue;
+            }
 
-    #[test]
-    fn client_constructors_err_with_not_found() {
-        block_on(async {
-            let mut local = EphemeralRepository::<Pouf1>::new();
-            let remote = EphemeralRepository::<Pouf1>::new();
+            if depth >= self.config.max_delegation_depth {
+                return Err(Error::MaxDelegationDepthExceeded(
+                    self.config.max_delegation_depth,
+                ));
+            }
 
-            let private_key =
-                Ed25519PrivateKey::from_pkcs8(&Ed25519PrivateKey::pkcs8().unwrap()).unwrap();
-            let public_key = private_key.public().clone();
+            // Listing the targets of succinct hash bins requires fetching every bin.
+            let delegated_roles: Box<dyn Iterator<Item = MetadataPath> + '_> =
+                match targets.delegations().succinct_roles() {
+                    Some(succinct_roles) => Box::new(succinct_roles.role_names()),
+                    None => Box::new(targets.delegations().role_names().cloned()),
+                };
 
-            assert_matches!(
-                Client::with_trusted_local(Config::default(), &mut local, &remote).await,
-                Err(Error::MetadataNotFound { path, version })
-                if path == MetadataPath::root() && version == MetadataVersion::Number(1)
-            );
+            let mut children = vec![];
+            for delegated_role in delegated_roles {
+                if !visited.insert(delegated_role.clone()) {
+                    continue;
+                }
 
-            assert_matches!(
+                if visited.len() > self.config.max_visited_roles as usize {
+                    return Err(Error::MaxVisitedRolesExceeded(
+                        self.config.max_visited_roles,
+                    ));
+                }
+
+                let role_meta = match self.snapshot_description(start_time, &delegated_role).await {
+                    Ok(Some(m)) => m,
+                    Ok(None) if strict => {
+                        return Err(Error::MissingMetadataDescription {
+                            parent_role: MetadataPath::snapshot(),
+                            child_role: delegated_role,
+                        });
+                    }
+                    Err(e) if strict => return Err(e),
+                    Ok(None) => {
+                        warn!(
+                            "Delegated role {:?} is not described by the snapshot",
+                            delegated_role
+                        );
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Skipping targets delegated to {:?}: {:?}",
+                            delegated_role, e
+                        );
+                        continue;
+                    }
+                };
+
+                let trusted = self
+                    .tuf
+                    .trusted_delegations()
+                    .get(&delegated_role)
+                    .filter(|t| t.version() == role_meta.version())
+                    .cloned();
+
+                let meta = match trusted {
+                    Some(meta) => meta,
+                    None => match self
+                        .fetch_delegated_targets(start_time, &role, &delegated_role, &role_meta)
+                        .await
+                    {
+                        Ok(meta) => meta,
+                        Err(e) if strict => return Err(e),
+                        Err(e) => {
+                            warn!(
+                                "Skipping targets delegated to {:?}: {:?}",
+                                delegated_role, e
+                            );
+                            continue;
+                        }
+                    },
+                };
+
+                children.push((delegated_role, meta, depth + 1));
+            }
+
+            // Push in reverse so that the first delegation is visited next.
+            stack.extend(children.into_iter().rev());
+        }
+
+        // A target may be listed by several roles, possibly with identical descriptions, but only
+        // the role the delegation rules resolve it to is trusted.
+        let mut resolved = HashMap::new();
+        let mut result = vec![];
+        for (path, role) in candidates {
+            let resolution = resolved.entry(path.clone()).or_insert_with(|| {
+                self.tuf
+                    .resolve_target_with_start_time(start_time, &path)
+                    .ok()
+            });
+
+            if let Some((description, resolved_role)) = resolution {
+                if *resolved_role == role {
+                    result.push(TrustedTarget {
+                        path,
+                        description: description.clone(),
+                        role,
+                    });
+
+                    // List each target only once.
+                    *resolution = None;
+                }
+            }
+        }
+
+        Ok(result.into_iter())
+    }
+
+    async fn lookup_target_description(
+        &mut self,
+        start_time: &DateTime<Utc>,
+        default_terminate: bool,
+        current_depth: u32,
+        visited_roles: &mut u32,
+        target: &TargetPath,
+        targets: Option<(&Verified<TargetsMetadata>, MetadataPath)>,
+        trace: &mut Vec<DelegationStep>,
+    ) -> (bool, Result<TargetDescription>) {
+        if current_depth > self.config.max_delegation_depth {
+            warn!(
+                "Walking the delegation graph would have exceeded the configured max depth: {}",
+                self.config.max_delegation_depth
+            );
+            trace.push(DelegationStep {
+                role: targets
+                    .map(|(_, role)| role)
+                    .unwrap_or_else(MetadataPath::targets),
+                depth: current_depth,
+                terminating: default_terminate,
+                outcome: DelegationOutcome::MaxDepthExceeded,
+            });
+            return (
+                true,
+                Err(Error::MaxDelegationDepthExceeded(
+                    self.config.max_delegation_depth,
+                )),
+            );
+        }
+
+        // these clones are dumb, but we need immutable values and not references for update
+        // tuf in the loop below
+        let (targets, targets_role) = match targets {
+            Some((t, role)) => (t.clone(), role),
+            None => match self.tuf.trusted_targets() {
+                Some(t) => (t.clone(), MetadataPath::targets()),
+                None => {
+                    return (
+                        default_terminate,
+                        Err(Error::MetadataNotFound {
+                            path: MetadataPath::targets(),
+                            version: MetadataVersion::None,
+                        }),
+                    );
+                }
+            },
+        };
+
+        let step_index = trace.len();
+        trace.push(DelegationStep {
+            role: targets_role.clone(),
+            depth: current_depth,
+            terminating: default_terminate,
+            outcome: DelegationOutcome::NotFound,
+        });
+
+        if let Some(t) = targets.targets().get(target) {
+            trace[step_index].outcome = DelegationOutcome::Found;
+            return (default_terminate, Ok(t.clone()));
+        }
+
+        // The delegations are consulted in the order they are listed. With succinct hash bins, only
+        // the bin that `target` hashes to is consulted, and it is always authorized to sign
+        // `target`.
+        for entry in targets.delegations().entries_for_target(target).iter() {
+            let (term, res) = match entry {
+                DelegationEntry::Single(delegation) => {
+                    self.lookup_delegated_target_description(
+                        start_time,
+                        current_depth,
+                        visited_roles,
+                        target,
+                        &targets_role,
+                        delegation,
+                        trace,
+                    )
+                    .await
+                }
+                DelegationEntry::Multi(multi_role_delegation) => {
+                    self.lookup_multi_role_target_description(
+                        start_time,
+                        current_depth,
+                        visited_roles,
+                        target,
+                        &targets_role,
+                        multi_role_delegation,
+                        trace,
+                    )
+                    .await
+                }
+            };
+
+            // A target that is found ends the search, like it does in
+            // [Database::target_description].
+            if term || res.is_ok() {
+                return (term, res);
+            }
+        }
+
+        (
+            default_terminate,
+            Err(Error::TargetNotFound(target.clone())),
+        )
+    }
+
+    /// Look up `target` in the roles of `multi_role_delegation`, which is delegated to by
+    /// `targets_role`. Every role is consulted on its own, and the target is only trusted if
+    /// enough of them agree on its description. The returned flag is set if the search should
+    /// not continue with the next delegation.
+    async fn lookup_multi_role_target_description(
+        &mut self,
+        start_time: &DateTime<Utc>,
+        current_depth: u32,
+        visited_roles: &mut u32,
+        target: &TargetPath,
+        targets_role: &MetadataPath,
+        multi_role_delegation: &MultiRoleDelegation,
+        trace: &mut Vec<DelegationStep>,
+    ) -> (bool, Result<TargetDescription>) {
+        let terminating = multi_role_delegation.terminating();
+        if !multi_role_delegation.matches_target_with_policy(target, self.tuf.path_matching()) {
+            return (terminating, Err(Error::TargetNotFound(target.clone())));
+        }
+
+        let mut descriptions = vec![];
+        for delegation in multi_role_delegation.roles() {
+            let (_, res) = self
+                .lookup_delegated_target_description(
+                    start_time,
+                    current_depth,
+                    visited_roles,
+                    target,
+                    targets_role,
+                    delegation,
+                    trace,
+                )
+                .await;
+
+            match res {
+                Ok(description) => descriptions.push(description),
+                Err(e @ Error::MaxVisitedRolesExceeded(_))
+                | Err(e @ Error::MaxDelegationDepthExceeded(_)) => return (true, Err(e)),
+                Err(_) => {}
+            }
+        }
+
+        match multi_role_delegation.agreed_description(&descriptions) {
+            Some(description) => (terminating, Ok(description)),
+            None => (terminating, Err(Error::TargetNotFound(target.clone()))),
+        }
+    }
+
+    /// Look up `target` in the targets metadata `delegation`, which is delegated to by
+    /// `targets_role`, and in its own delegations. The returned flag is set if the search should
+    /// not continue with the next delegation.
+    async fn lookup_delegated_target_description(
+        &mut self,
+        start_time: &DateTime<Utc>,
+        current_depth: u32,
+        visited_roles: &mut u32,
+        target: &TargetPath,
+        targets_role: &MetadataPath,
+        delegation: &Delegation,
+        trace: &mut Vec<DelegationStep>,
+    ) -> (bool, Result<TargetDescription>) {
+        let mut skipped = |outcome| {
+            trace.push(DelegationStep {
+                role: delegation.name().clone(),
+                depth: current_depth + 1,
+                terminating: delegation.terminating(),
+                outcome,
+            })
+        };
+
+        // A delegation that isn't trusted with `target` is skipped, even if it is terminating.
+        if !delegation.matches_target_with_policy(target, self.tuf.path_matching()) {
+            skipped(DelegationOutcome::PathMismatch);
+            return (false, Err(Error::TargetNotFound(target.clone())));
+        }
+
+        let role_meta = match self
+            .snapshot_description(start_time, delegation.name())
+            .await
+        {
+            Ok(Some(m)) => m,
+            Ok(None) => {
+                skipped(DelegationOutcome::MissingFromSnapshot);
+                return (
+                    delegation.terminating(),
+                    Err(Error::TargetNotFound(target.clone())),
+                );
+            }
+            Err(e) => {
+                skipped(DelegationOutcome::Unavailable);
+                return (delegation.terminating(), Err(e));
+            }
+        };
+
+        *visited_roles += 1;
+        if *visited_roles > self.config.max_visited_roles {
+            warn!(
+                "Walking the delegation graph exceeded the configured max visited roles: {}",
+                self.config.max_visited_roles
+            );
+            skipped(DelegationOutcome::MaxVisitedRolesExceeded);
+            return (
+                true,
+                Err(Error::MaxVisitedRolesExceeded(
+                    self.config.max_visited_roles,
+                )),
+            );
+        }
+
+        let meta = match self
+            .fetch_delegated_targets(start_time, targets_role, delegation.name(), &role_meta)
+            .await
+        {
+            Ok(meta) => meta,
+            Err(e) => {
+                skipped(DelegationOutcome::Unavailable);
+                return (delegation.terminating(), Err(e));
+            }
+        };
+
+        let f: Pin<Box<dyn Future<Output = _>>> = Box::pin(self.lookup_target_description(
+            start_time,
+            delegation.terminating(),
+            current_depth + 1,
+            visited_roles,
+            target,
+            Some((&*meta, delegation.name().clone())),
+            trace,
+        ));
+        f.await
+    }
+
+    /// Fetch, verify, and persist the delegated targets metadata `role`, which is delegated to by
+    /// `parent_role` and described by `role_meta` in the trusted snapshot.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(parent_role = %parent_role, role = %role, version = role_meta.version()),
+            err,
+        )
+    )]
+    async fn fetch_delegated_targets(
+        &mut self,
+        start_time: &DateTime<Utc>,
+        parent_role: &MetadataPath,
+        role: &MetadataPath,
+        role_meta: &MetadataDescription<TargetsMetadata>,
+    ) -> Result<Arc<Verified<TargetsMetadata>>> {
+        let started = Instant::now();
+        let res = self
+            .fetch_delegated_targets_impl(start_time, parent_role, role, role_meta)
+            .await;
+
+        if let Some(metrics) = &self.metrics {
+            metrics.record_phase_duration(UpdatePhase::Delegations, started.elapsed());
+        }
+
+        res
+    }
+
+    async fn fetch_delegated_targets_impl(
+        &mut self,
+        start_time: &DateTime<Utc>,
+        parent_role: &MetadataPath,
+        role: &MetadataPath,
+        role_meta: &MetadataDescription<TargetsMetadata>,
+    ) -> Result<Arc<Verified<TargetsMetadata>>> {
+        /////////////////////////////////////////
+        // TUF-1.0.9 §5.4:
+        //
+        //     Download the top-level targets metadata file, up to either the number of bytes
+        //     specified in the snapshot metadata file, or some Z number of bytes. The value
+        //     for Z is set by the authors of the application using TUF. For example, Z may be
+        //     tens of kilobytes. If consistent snapshots are not used (see Section 7), then
+        //     the filename used to download the targets metadata file is of the fixed form
+        //     FILENAME.EXT (e.g., targets.json). Otherwise, the filename is of the form
+        //     VERSION_NUMBER.FILENAME.EXT (e.g., 42.targets.json), where VERSION_NUMBER is the
+        //     version number of the targets metadata file listed in the snapshot metadata
+        //     file.
+
+        let version = if self.tuf.trusted_root().consistent_snapshot() {
+            MetadataVersion::Number(role_meta.version())
+        } else {
+            MetadataVersion::None
+        };
+
+        let role_length = role_meta
+            .length()
+            .or_else(|| self.config.max_delegated_targets_length.max_length());
+
+        // https://theupdateframework.github.io/specification/v1.0.26/#update-targets
+        //
+        //     [...] The hashes of the new targets metadata file MUST match the hashes, if
+        //      any, listed in the trusted snapshot metadata.
+        let role_hashes =
+            crypto::retain_allowed_hashes(role_meta.hashes(), &self.config.hash_algorithms);
+
+        let raw_signed_meta = match self
+            .remote
+            .fetch_metadata(role, version, role_length, role_hashes)
+            .await
+        {
+            Ok(m) => m,
+            Err(e) => {
+                warn!("Failed to fetch metadata {:?}: {:?}", role, e);
+                return Err(e);
+            }
+        };
+
+        record_bytes_downloaded(
+            self.metrics.as_deref(),
+            UpdatePhase::Delegations,
+            &raw_signed_meta,
+        );
+
+        if role_meta.length().is_none() {
+            self.config
+                .max_delegated_targets_length
+                .warn_if_exceeded(role, &raw_signed_meta);
+        }
+
+        let res =
+            self.tuf
+                .update_delegated_targets(start_time, parent_role, role, &raw_signed_meta);
+        record_signature_verification(self.metrics.as_deref(), UpdatePhase::Delegations, &res);
+        res?;
+
+        /////////////////////////////////////////
+        // TUF-1.0.9 §5.4.4:
+        //
+        //     Persist targets metadata. The client MUST write the file to non-volatile
+        //     storage as FILENAME.EXT (e.g. targets.json).
+
+        match self
+            .local
+            .store_metadata(role, MetadataVersion::None, &raw_signed_meta)
+            .await
+        {
+            Ok(_) => (),
+            Err(e) => {
+                warn!("Error storing metadata {:?} locally: {:?}", role, e)
+            }
+        }
+
+        Ok(Arc::clone(&self.tuf.trusted_delegations()[role]))
+    }
+}
+
+/// Trusted root metadata that is embedded in the application binary.
+///
+/// This is usually created with the [include_root!](crate::include_root) macro, and can be
+/// stored in a `const` or `static`. The bytes are not checked until [EmbeddedRoot::validate] is
+/// called or a [Client] is created from them with [Client::with_trusted_root_bytes], so it is
+/// recommended to call [EmbeddedRoot::validate] in a test.
+///
+/// ```
+/// # use tuf::client::EmbeddedRoot;
+/// # use tuf::pouf::Pouf1;
+/// static ROOT: EmbeddedRoot = EmbeddedRoot::new(b"not a root");
+///
+/// assert!(ROOT.validate::<Pouf1>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmbeddedRoot {
+    bytes: &'static [u8],
+}
+
+impl EmbeddedRoot {
+    /// Create a new `EmbeddedRoot` from the serialized bytes of the root metadata.
+    pub const fn new(bytes: &'static [u8]) -> Self {
+        EmbeddedRoot { bytes }
+    }
+
+    /// The serialized bytes of the root metadata.
+    pub const fn as_bytes(&self) -> &'static [u8] {
+        self.bytes
+    }
+
+    /// Convert the embedded bytes into [RawSignedMetadata].
+    pub fn to_raw<D>(&self) -> RawSignedMetadata<D, RootMetadata>
+    where
+        D: Pouf,
+    {
+        RawSignedMetadata::new(self.bytes.to_vec())
+    }
+
+    /// Check that the embedded bytes are root metadata in the `D` format that is signed by a
+    /// threshold of its own root keys.
+    pub fn validate<D>(&self) -> Result<()>
+    where
+        D: Pouf,
+    {
+        Database::<D>::from_trusted_root(&self.to_raw()).map(|_| ())
+    }
+}
+
+/// Embed the root metadata at `path` into the application binary as an [EmbeddedRoot]. The path
+/// is resolved the same way as with [include_bytes!].
+///
+/// ```ignore
+/// use tuf::client::EmbeddedRoot;
+///
+/// static ROOT: EmbeddedRoot = tuf::include_root!("../metadata/1.root.json");
+/// ```
+#[macro_export]
+macro_rules! include_root {
+    ($path:expr) => {
+        $crate::client::EmbeddedRoot::new(include_bytes!($path))
+    };
+}
+
+fn record_bytes_downloaded<D, M>(
+    metrics: Option<&dyn Metrics>,
+    phase: UpdatePhase,
+    raw_meta: &RawSignedMetadata<D, M>,
+) where
+    D: Pouf,
+    M: Metadata,
+{
+    if let Some(metrics) = metrics {
+        metrics.record_bytes_downloaded(phase, raw_meta.as_bytes().len() as u64);
+    }
+}
+
+fn record_signature_verification<T>(
+    metrics: Option<&dyn Metrics>,
+    phase: UpdatePhase,
+    res: &Result<T>,
+) {
+    if let Some(metrics) = metrics {
+        let verified = !matches!(
+            res,
+            Err(Error::BadSignature(_)) | Err(Error::MetadataMissingSignatures { .. })
+        );
+        metrics.record_signature_verification(phase, verified);
+    }
+}
+
+/// The roles that were consulted while resolving the description of a target, as returned by
+/// [Client::fetch_target_description_with_trace].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DelegationTrace {
+    steps: Vec<DelegationStep>,
+}
+
+impl DelegationTrace {
+    /// The roles that were consulted, in the order they were visited by the pre-order
+    /// depth-first search of the delegation graph.
+    pub fn steps(&self) -> &[DelegationStep] {
+        &self.steps
+    }
+
+    /// The role that ultimately provided the description of the target, if any.
+    pub fn resolved_by(&self) -> Option<&MetadataPath> {
+        self.steps
+            .iter()
+            .find(|step| step.outcome == DelegationOutcome::Found)
+            .map(|step| &step.role)
+    }
+}
+
+/// A single role that was consulted while resolving the description of a target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DelegationStep {
+    role: MetadataPath,
+    depth: u32,
+    terminating: bool,
+    outcome: DelegationOutcome,
+}
+
+impl DelegationStep {
+    /// The targets role that was consulted.
+    pub fn role(&self) -> &MetadataPath {
+        &self.role
+    }
+
+    /// The depth of the role in the delegation graph. The top-level targets role has a depth of 0.
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Whether the delegation to this role is terminating.
+    pub fn terminating(&self) -> bool {
+        self.terminating
+    }
+
+    /// What happened when this role was consulted.
+    pub fn outcome(&self) -> DelegationOutcome {
+        self.outcome
+    }
+}
+
+/// What happened when a role was consulted while resolving the description of a target.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DelegationOutcome {
+    /// The role provided the description of the target.
+    Found,
+    /// Neither the role nor any of the roles it delegates to provided the description of the
+    /// target.
+    NotFound,
+    /// The delegation to the role does not cover the path of the target, so it was skipped.
+    PathMismatch,
+    /// The role is not described by the trusted snapshot metadata, so it was skipped.
+    MissingFromSnapshot,
+    /// The metadata for the role could not be fetched or verified.
+    Unavailable,
+    /// Consulting the role would have exceeded [Config::max_delegation_depth].
+    MaxDepthExceeded,
+    /// Consulting the role would have exceeded [Config::max_visited_roles].
+    MaxVisitedRolesExceeded,
+}
+
+/// A version of the root metadata in the chain returned by [Client::fetch_root_chain].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootChainLink<D: Pouf> {
+    raw_root: RawSignedMetadata<D, RootMetadata>,
+    root: Verified<RootMetadata>,
+    diff: Option<RootDiff>,
+}
+
+impl<D: Pouf> RootChainLink<D> {
+    /// The version of the root metadata.
+    pub fn version(&self) -> u32 {
+        self.root.version()
+    }
+
+    /// The signed root metadata, as it was fetched from the remote repository.
+    pub fn raw_root(&self) -> &RawSignedMetadata<D, RootMetadata> {
+        &self.raw_root
+    }
+
+    /// The verified root metadata.
+    pub fn root(&self) -> &Verified<RootMetadata> {
+        &self.root
+    }
+
+    /// The changes to the keys and thresholds since the version before this one, or `None` for
+    /// the first version.
+    pub fn diff(&self) -> Option<&RootDiff> {
+        self.diff.as_ref()
+    }
+}
+
+/// A target that is described by the trusted metadata, as returned by
+/// [Client::trusted_targets_iter].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustedTarget {
+    path: TargetPath,
+    description: TargetDescription,
+    role: MetadataPath,
+}
+
+impl TrustedTarget {
+    /// The path of the target.
+    pub fn path(&self) -> &TargetPath {
+        &self.path
+    }
+
+    /// The trusted description of the target.
+    pub fn description(&self) -> &TargetDescription {
+        &self.description
+    }
+
+    /// The targets role that signed the description of the target. For a target trusted through
+    /// a multi-role delegation, this is the first of the roles that agree on its description.
+    pub fn role(&self) -> &MetadataPath {
+        &self.role
+    }
+}
+
+/// A reader for a target that is being fetched from a remote repository.
+///
+/// The reader knows the trusted [TargetDescription] of the target, and verifies the length and
+/// hashes of the target as it is read. As with any streaming verification, it is **critical** that
+/// none of the bytes read are used until the reader has been fully consumed without error.
+#[derive(Debug)]
+pub struct TargetReader<R> {
+    read: R,
+    target_description: TargetDescription,
+}
+
+impl<R> TargetReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// The trusted description of the target.
+    pub fn target_description(&self) -> &TargetDescription {
+        &self.target_description
+    }
+
+    /// The expected length of the target, if the target description specifies it.
+    pub fn length(&self) -> Option<u64> {
+        self.target_description.length()
+    }
+
+    /// Read and verify the whole target, spooling it to an anonymous temporary file, and return a
+    /// [SeekableTarget] that supports random access to the verified bytes.
+    ///
+    /// Seeking is served from the local copy, and never by re-requesting ranges of the target
+    /// from the remote repository. The trusted hashes cover the whole target, so the bytes of a
+    /// ranged fetch could not be verified against them, and a repository could answer a ranged
+    /// fetch with bytes that differ from the target it served in full. The temporary file needs
+    /// as much disk space as the target.
+    pub async fn into_seekable(mut self) -> Result<SeekableTarget> {
+        let file = tempfile::tempfile()?;
+        let mut file = AllowStdIo::new(file);
+
+        copy(&mut self.read, &mut file).await?;
+        file.seek(SeekFrom::Start(0)).await?;
+
+        Ok(SeekableTarget {
+            file,
+            target_description: self.target_description,
+        })
+    }
+}
+
+impl<R> AsyncRead for TargetReader<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.read).poll_read(cx, buf)
+    }
+}
+
+/// A fully verified target that supports random access.
+///
+/// This is created by [TargetReader::into_seekable]. Unlike [TargetReader], the length and hashes
+/// of the target have already been verified, so the bytes can be used as soon as they are read.
+#[derive(Debug)]
+pub struct SeekableTarget {
+    file: AllowStdIo<File>,
+    target_description: TargetDescription,
+}
+
+impl SeekableTarget {
+    /// The trusted description of the target.
+    pub fn target_description(&self) -> &TargetDescription {
+        &self.target_description
+    }
+
+    /// The length of the target, if the target description specifies it.
+    pub fn length(&self) -> Option<u64> {
+        self.target_description.length()
+    }
+}
+
+impl AsyncRead for SeekableTarget {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.file).poll_read(cx, buf)
+    }
+}
+
+impl AsyncSeek for SeekableTarget {
+    fn poll_seek(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        Pin::new(&mut self.file).poll_seek(cx, pos)
+    }
+}
+
+/// Deconstructed parts of a [Client].
+///
+/// This allows taking apart a [Client] in order to reclaim the [Database],
+/// local, and remote repositories.
+#[non_exhaustive]
+#[derive(Debug)]
+pub struct Parts<D, L, R>
+where
+    D: Pouf,
+    L: RepositoryProvider<D> + RepositoryStorage<D>,
+    R: RepositoryProvider<D>,
+{
+    /// The client configuration.
+    pub config: Config,
+
+    /// The Tuf database, which is updated by the [Client].
+    pub database: Database<D>,
+
+    /// The local repository, which is used to initialize the database, and
+    /// is updated by the [Client].
+    pub local: L,
+
+    /// The remote repository, which is used by the client to update the database.
+    pub remote: R,
+}
+
+/// Helper function that first tries to fetch the metadata from the local store, and if it doesn't
+/// exist or does and fails to parse, try fetching it from the remote store.
+async fn fetch_metadata_from_local_or_else_remote<'a, D, L, R, M>(
+    path: &'a MetadataPath,
+    version: MetadataVersion,
+    max_length: Option<usize>,
+    hashes: Vec<(&'static HashAlgorithm, HashValue)>,
+    local: &'a Repository<L, D>,
+    remote: &'a Repository<R, D>,
+) -> Result<(bool, RawSignedMetadata<D, M>)>
+where
+    D: Pouf,
+    L: RepositoryProvider<D> + RepositoryStorage<D>,
+    R: RepositoryProvider<D>,
+    M: Metadata + 'static,
+{
+    match local
+        .fetch_metadata(path, version, max_length, hashes.clone())
+        .await
+    {
+        Ok(raw_meta) => Ok((false, raw_meta)),
+        Err(Error::MetadataNotFound { .. }) => {
+            let raw_meta = remote
+                .fetch_metadata(path, version, max_length, hashes)
+                .await?;
+            Ok((true, raw_meta))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Wrap the remote repository `remote`, applying the retry and hash algorithm settings from
+/// `config`.
+fn remote_repository<R, D>(config: &Config, remote: R) -> Repository<R, D> {
+    let mut remote = Repository::new(remote);
+    remote.set_max_retries(config.max_fetch_retries);
+    remote.set_hash_algorithms(config.hash_algorithms.clone());
+    remote.set_allow_unverifiable_targets(config.allow_unverifiable_targets);
+    remote
+}
+
+/// Configuration for a TUF `Client`.
+///
+/// # Defaults
+///
+/// The following values are considered reasonably safe defaults, however these values may change
+/// as this crate moves out of beta. If you are concered about them changing, you should use the
+/// `ConfigBuilder` and set your own values.
+///
+/// ```
+/// # use tuf::client::{Config, MetadataLengthLimit};
+/// # use tuf::crypto::HashAlgorithm;
+/// # use tuf::metadata::PathMatching;
+/// # use tuf::policy::Policy;
+/// # use tuf::MetadataIntegrityPolicy;
+/// let config = Config::default();
+/// assert_eq!(config.max_root_length_limit(), &MetadataLengthLimit::Bounded(500 * 1024));
+/// assert_eq!(config.max_timestamp_length_limit(), &MetadataLengthLimit::Bounded(16 * 1024));
+/// assert_eq!(config.max_snapshot_length_limit(), &MetadataLengthLimit::Bounded(2000000));
+/// assert_eq!(config.max_targets_length_limit(), &MetadataLengthLimit::Bounded(5000000));
+/// assert_eq!(
+///     config.max_delegated_targets_length_limit(),
+///     &MetadataLengthLimit::Bounded(5000000),
+/// );
+/// assert_eq!(config.max_custom_role_length_limit(), &MetadataLengthLimit::Bounded(500 * 1024));
+/// assert_eq!(config.max_root_length(), &Some(500 * 1024));
+/// assert_eq!(config.max_root_rotations(), 1024);
+/// assert_eq!(config.max_delegation_depth(), 8);
+/// assert_eq!(config.max_visited_roles(), 32);
+/// assert_eq!(config.max_fetch_retries(), 0);
+/// assert_eq!(config.update_retry_policy().max_attempts(), 1);
+/// assert_eq!(config.hash_algorithms(), &[HashAlgorithm::Sha256, HashAlgorithm::Sha512]);
+/// assert_eq!(config.expiration_grace_period(), chrono::Duration::zero());
+/// assert_eq!(config.expiration_warning_window(), chrono::Duration::zero());
+/// assert_eq!(config.spec_major_version(), 1);
+/// assert!(!config.allow_unverifiable_targets());
+/// assert_eq!(config.path_matching(), PathMatching::Prefix { case_sensitive: true });
+/// assert_eq!(config.metadata_integrity_policy(), MetadataIntegrityPolicy::Lenient);
+/// assert_eq!(config.policy(), &Policy::default());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    max_root_length: ConfiguredLengthLimit,
+    max_timestamp_length: ConfiguredLengthLimit,
+    max_snapshot_length: ConfiguredLengthLimit,
+    max_targets_length: ConfiguredLengthLimit,
+    max_delegated_targets_length: ConfiguredLengthLimit,
+    max_custom_role_length: ConfiguredLengthLimit,
+    max_root_rotations: u32,
+    max_delegation_depth: u32,
+    max_visited_roles: u32,
+    max_fetch_retries: u32,
+    update_retry_policy: UpdateRetryPolicy,
+    hash_algorithms: Vec<HashAlgorithm>,
+    expiration_grace_period: Duration,
+    expiration_warning_window: Duration,
+    spec_major_version: u32,
+    allow_unverifiable_targets: bool,
+    path_matching: PathMatching,
+    metadata_integrity_policy: MetadataIntegrityPolicy,
+    policy: Policy,
+}
+
+impl Config {
+    /// Initialize a `ConfigBuilder` with the default values.
+    pub fn build() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    /// Return the maximum root metadata length, or `None` if it is unbounded. See
+    /// [Config::max_root_length_limit] for whether a warning is logged for large metadata.
+    pub fn max_root_length(&self) -> &Option<usize> {
+        &self.max_root_length.max_length
+    }
+
+    /// Return the limit on the length of root metadata.
+    pub fn max_root_length_limit(&self) -> &MetadataLengthLimit {
+        &self.max_root_length.limit
+    }
+
+    /// Return the maximum timestamp metadata length, or `None` if it is unbounded. See
+    /// [Config::max_timestamp_length_limit] for whether a warning is logged for large metadata.
+    pub fn max_timestamp_length(&self) -> &Option<usize> {
+        &self.max_timestamp_length.max_length
+    }
+
+    /// Return the limit on the length of timestamp metadata.
+    pub fn max_timestamp_length_limit(&self) -> &MetadataLengthLimit {
+        &self.max_timestamp_length.limit
+    }
+
+    /// Return the maximum snapshot metadata length, or `None` if it is unbounded. See
+    /// [Config::max_snapshot_length_limit] for whether a warning is logged for large metadata.
+    pub fn max_snapshot_length(&self) -> &Option<usize> {
+        &self.max_snapshot_length.max_length
+    }
+
+    /// Return the limit on the length of snapshot metadata.
+    pub fn max_snapshot_length_limit(&self) -> &MetadataLengthLimit {
+        &self.max_snapshot_length.limit
+    }
+
+    /// Return the maximum top-level targets metadata length, or `None` if it is unbounded. See
+    /// [Config::max_targets_length_limit] for whether a warning is logged for large metadata.
+    pub fn max_targets_length(&self) -> &Option<usize> {
+        &self.max_targets_length.max_length
+    }
+
+    /// Return the limit on the length of top-level targets metadata.
+    pub fn max_targets_length_limit(&self) -> &MetadataLengthLimit {
+        &self.max_targets_length.limit
+    }
+
+    /// Return the maximum delegated targets metadata length, or `None` if it is unbounded. See
+    /// [Config::max_delegated_targets_length_limit] for whether a warning is logged for large metadata.
+    pub fn max_delegated_targets_length(&self) -> &Option<usize> {
+        &self.max_delegated_targets_length.max_length
+    }
+
+    /// Return the limit on the length of delegated targets metadata.
+    pub fn max_delegated_targets_length_limit(&self) -> &MetadataLengthLimit {
+        &self.max_delegated_targets_length.limit
+    }
+
+    /// Return the maximum metadata of custom top-level roles length, or `None` if it is unbounded. See
+    /// [Config::max_custom_role_length_limit] for whether a warning is logged for large metadata.
+    pub fn max_custom_role_length(&self) -> &Option<usize> {
+        &self.max_custom_role_length.max_length
+    }
+
+    /// Return the limit on the length of metadata of custom top-level roles.
+    pub fn max_custom_role_length_limit(&self) -> &MetadataLengthLimit {
+        &self.max_custom_role_length.limit
+    }
+
+    /// The maximum number of new root metadata versions that are fetched during a single update.
+    pub fn max_root_rotations(&self) -> u32 {
+        self.max_root_rotations
+    }
+
+    /// The maximum number of steps used when walking the delegation graph.
+    pub fn max_delegation_depth(&self) -> u32 {
+        self.max_delegation_depth
+    }
+
+    /// The maximum number of delegated roles whose metadata is consulted when walking the
+    /// delegation graph.
+    pub fn max_visited_roles(&self) -> u32 {
+        self.max_visited_roles
+    }
+
+    /// The maximum number of times a fetch from the remote repository is retried after a
+    /// transient error.
+    pub fn max_fetch_retries(&self) -> u32 {
+        self.max_fetch_retries
+    }
+
+    /// The policy for retrying [Client::update] after it fails.
+    pub fn update_retry_policy(&self) -> &UpdateRetryPolicy {
+        &self.update_retry_policy
+    }
+
+    /// The hash algorithms that are used to verify metadata and targets fetched from the remote
+    /// repository.
+    pub fn hash_algorithms(&self) -> &[HashAlgorithm] {
+        &self.hash_algorithms
+    }
+
+    /// The period of time after expiration during which timestamp and snapshot metadata is still
+    /// accepted.
+    pub fn expiration_grace_period(&self) -> Duration {
+        self.expiration_grace_period
+    }
+
+    /// The period of time before expiration during which trusted metadata is reported by
+    /// [Client::expiring_metadata] and [ClientObserver::on_metadata_expiring].
+    pub fn expiration_warning_window(&self) -> Duration {
+        self.expiration_warning_window
+    }
+
+    /// The major version of the specification that new metadata must follow.
+    pub fn spec_major_version(&self) -> u32 {
+        self.spec_major_version
+    }
+
+    /// Whether or not targets whose descriptions are missing their length or hashes may be
+    /// fetched.
+    pub fn allow_unverifiable_targets(&self) -> bool {
+        self.allow_unverifiable_targets
+    }
+
+    /// The rules used to match targets against the paths of delegations.
+    pub fn path_matching(&self) -> PathMatching {
+        self.path_matching
+    }
+
+    /// How strictly the length and hashes the timestamp metadata declares for the snapshot
+    /// metadata, and the snapshot metadata declares for targets and delegated targets metadata,
+    /// are enforced.
+    pub fn metadata_integrity_policy(&self) -> MetadataIntegrityPolicy {
+        self.metadata_integrity_policy
+    }
+
+    /// The hash algorithms and signature schemes that metadata may use.
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            max_root_length: MetadataLengthLimit::Bounded(500 * 1024).into(),
+            max_timestamp_length: MetadataLengthLimit::Bounded(16 * 1024).into(),
+            max_snapshot_length: MetadataLengthLimit::Bounded(2000000).into(),
+            max_targets_length: MetadataLengthLimit::Bounded(5000000).into(),
+            max_delegated_targets_length: MetadataLengthLimit::Bounded(5000000).into(),
+            max_custom_role_length: MetadataLengthLimit::Bounded(500 * 1024).into(),
+            max_root_rotations: 1024,
+            max_delegation_depth: 8,
+            max_visited_roles: 32,
+            max_fetch_retries: 0,
+            update_retry_policy: UpdateRetryPolicy::default(),
+            hash_algorithms: vec![HashAlgorithm::Sha256, HashAlgorithm::Sha512],
+            expiration_grace_period: Duration::zero(),
+            expiration_warning_window: Duration::zero(),
+            spec_major_version: SpecVersion::SUPPORTED_MAJOR_VERSION,
+            allow_unverifiable_targets: false,
+            path_matching: PathMatching::default(),
+            metadata_integrity_policy: MetadataIntegrityPolicy::default(),
+            policy: Policy::default(),
+        }
+    }
+}
+
+/// The maximum number of bytes a [Client] will download for a piece of metadata.
+///
+/// These limits only apply when the length of the metadata is not already listed in trusted
+/// metadata, which is always the case for root and timestamp metadata, and is the case for
+/// snapshot and targets metadata when the repository omits their lengths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataLengthLimit {
+    /// Reject metadata that is larger than this many bytes.
+    Bounded(usize),
+    /// Accept metadata of any length, but log a warning if it is larger than this many bytes.
+    UnboundedWithWarning(usize),
+    /// Accept metadata of any length.
+    Unbounded,
+}
+
+impl MetadataLengthLimit {
+    /// The maximum number of bytes that will be downloaded, or `None` if there is no limit.
+    pub fn max_length(&self) -> Option<usize> {
+        match self {
+            MetadataLengthLimit::Bounded(max) => Some(*max),
+            MetadataLengthLimit::UnboundedWithWarning(_) | MetadataLengthLimit::Unbounded => None,
+        }
+    }
+
+    fn warn_if_exceeded<D, M>(&self, path: &MetadataPath, raw: &RawSignedMetadata<D, M>)
+    where
+        D: Pouf,
+        M: Metadata,
+    {
+        if let MetadataLengthLimit::UnboundedWithWarning(warn_length) = self {
+            let length = raw.as_bytes().len();
+            if length > *warn_length {
+                warn!(
+                    "metadata {} is {} bytes, which exceeds the expected maximum of {} bytes",
+                    path, length, warn_length
+                );
+            }
+        }
+    }
+}
+
+impl From<Option<usize>> for MetadataLengthLimit {
+    fn from(max: Option<usize>) -> Self {
+        match max {
+            Some(max) => MetadataLengthLimit::Bounded(max),
+            None => MetadataLengthLimit::Unbounded,
+        }
+    }
+}
+
+/// A [MetadataLengthLimit] along with the maximum length it allows, which is kept so that
+/// [Config::max_root_length] and friends can return a reference to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ConfiguredLengthLimit {
+    limit: MetadataLengthLimit,
+    max_length: Option<usize>,
+}
+
+impl ConfiguredLengthLimit {
+    fn max_length(&self) -> Option<usize> {
+        self.max_length
+    }
+
+    fn warn_if_exceeded<D, M>(&self, path: &MetadataPath, raw: &RawSignedMetadata<D, M>)
+    where
+        D: Pouf,
+        M: Metadata,
+    {
+        self.limit.warn_if_exceeded(path, raw)
+    }
+}
+
+impl From<MetadataLengthLimit> for ConfiguredLengthLimit {
+    fn from(limit: MetadataLengthLimit) -> Self {
+        ConfiguredLengthLimit {
+            limit,
+            max_length: limit.max_length(),
+        }
+    }
+}
+
+/// A class of errors that [Client::update] can be retried after, as configured by an
+/// [UpdateRetryPolicy].
+///
+/// Errors that indicate the repository may be under attack, such as bad signatures or rollbacks,
+/// don't belong to any class and are never retried.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RetryableErrorClass {
+    /// IO errors and HTTP transport errors, such as a dropped connection or a timeout.
+    Network,
+    /// HTTP responses with a server error status code.
+    ServerError,
+    /// Metadata or targets that were not found, for example because the repository was in the
+    /// middle of publishing new metadata.
+    NotFound,
+}
+
+impl RetryableErrorClass {
+    /// The class of `err`, or `None` if errors like it should never be retried.
+    pub fn of(err: &Error) -> Option<Self> {
+        match err {
+            Error::Io(_) => Some(RetryableErrorClass::Network),
+            #[cfg(feature = "hyper")]
+            Error::Hyper { .. } => Some(RetryableErrorClass::Network),
+            Error::BadHttpStatus { code, .. } if code.is_server_error() => {
+                Some(RetryableErrorClass::ServerError)
+            }
+            Error::MetadataNotFound { .. } | Error::TargetNotFound(_) => {
+                Some(RetryableErrorClass::NotFound)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// How [Client::update] is retried after it fails.
+///
+/// After a failed attempt whose error is in one of the [retryable
+/// classes](UpdateRetryPolicy::retry_on), the client waits before the next attempt. The wait
+/// starts at the initial backoff and doubles after each attempt up to the maximum backoff, plus a
+/// random delay of up to the jitter, which spreads out retries from many clients. The client
+/// waits with the timer set by [Client::set_sleep]. A policy that retries with a backoff or jitter
+/// requires one, and updates fail with [Error::IllegalArgument] without it.
+///
+/// The default makes a single attempt, so updates are not retried.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use tuf::client::{Config, RetryableErrorClass, UpdateRetryPolicy};
+/// let config = Config::build()
+///     .update_retry_policy(
+///         UpdateRetryPolicy::new(4)
+///             .initial_backoff(Duration::from_millis(500))
+///             .max_backoff(Duration::from_secs(10))
+///             .jitter(Duration::from_millis(250))
+///             .retry_on([RetryableErrorClass::Network, RetryableErrorClass::ServerError]),
+///     )
+///     .finish()
+///     .unwrap();
+/// assert_eq!(config.update_retry_policy().max_attempts(), 4);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateRetryPolicy {
+    max_attempts: u32,
+    initial_backoff: StdDuration,
+    max_backoff: StdDuration,
+    jitter: StdDuration,
+    retry_on: Vec<RetryableErrorClass>,
+}
+
+impl UpdateRetryPolicy {
+    /// Create a policy that makes at most `max_attempts` attempts, including the first. The other
+    /// settings start with their defaults: an initial backoff of 1 second, a maximum backoff of 1
+    /// minute, a jitter of 1 second, and retrying network and server errors.
+    pub fn new(max_attempts: u32) -> Self {
+        UpdateRetryPolicy {
+            max_attempts,
+            initial_backoff: StdDuration::from_secs(1),
+            max_backoff: StdDuration::from_secs(60),
+            jitter: StdDuration::from_secs(1),
+            retry_on: vec![
+                RetryableErrorClass::Network,
+                RetryableErrorClass::ServerError,
+            ],
+        }
+    }
+
+    /// Set the time to wait after the first failed attempt.
+    pub fn initial_backoff(mut self, backoff: StdDuration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Set the longest time to wait between attempts, not counting the jitter.
+    pub fn max_backoff(mut self, backoff: StdDuration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Set the longest random delay that is added to each wait.
+    pub fn jitter(mut self, jitter: StdDuration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Set the classes of errors that are retried. Other errors fail the update immediately.
+    pub fn retry_on<I>(mut self, classes: I) -> Self
+    where
+        I: IntoIterator<Item = RetryableErrorClass>,
+    {
+        self.retry_on = classes.into_iter().collect();
+        self
+    }
+
+    /// The maximum number of attempts, including the first.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// The classes of errors that are retried.
+    pub fn retryable_classes(&self) -> &[RetryableErrorClass] {
+        &self.retry_on
+    }
+
+    /// Returns `true` if an update that failed with `err` should be retried.
+    pub fn is_retryable(&self, err: &Error) -> bool {
+        match RetryableErrorClass::of(err) {
+            Some(class) => self.retry_on.contains(&class),
+            None => false,
+        }
+    }
+
+    /// Returns `true` if the policy ever waits between attempts.
+    fn waits(&self) -> bool {
+        self.max_attempts > 1 && (!self.initial_backoff.is_zero() || !self.jitter.is_zero())
+    }
+
+    /// The time to wait after the `failures`th failed attempt, without the jitter.
+    fn backoff(&self, failures: u32) -> StdDuration {
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        self.initial_backoff
+            .checked_mul(factor)
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff))
+    }
+}
+
+impl Default for UpdateRetryPolicy {
+    fn default() -> Self {
+        UpdateRetryPolicy::new(1)
+    }
+}
+
+/// Helper for building and validating a TUF client `Config`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ConfigBuilder {
+    cfg: Config,
+}
+
+impl ConfigBuilder {
+    /// Validate this builder return a `Config` if validation succeeds.
+    pub fn finish(self) -> Result<Config> {
+        let lengths = [
+            ("root", &self.cfg.max_root_length),
+            ("timestamp", &self.cfg.max_timestamp_length),
+            ("snapshot", &self.cfg.max_snapshot_length),
+            ("targets", &self.cfg.max_targets_length),
+            ("delegated targets", &self.cfg.max_delegated_targets_length),
+            ("custom role", &self.cfg.max_custom_role_length),
+        ];
+        for (role, length) in lengths {
+            if length.limit == MetadataLengthLimit::Bounded(0) {
+                return Err(Error::IllegalArgument(format!(
+                    "maximum {} metadata length must be greater than 0",
+                    role
+                )));
+            }
+        }
+
+        if self.cfg.max_root_rotations == 0 {
+            return Err(Error::IllegalArgument(
+                "maximum root rotations must be greater than 0".into(),
+            ));
+        }
+
+        if self.cfg.update_retry_policy.max_attempts == 0 {
+            return Err(Error::IllegalArgument(
+                "update retry policy must make at least 1 attempt".into(),
+            ));
+        }
+
+        let policy = &self.cfg.update_retry_policy;
+        if policy.initial_backoff > policy.max_backoff {
+            return Err(Error::IllegalArgument(
+                "update retry policy initial backoff must not exceed the maximum backoff".into(),
+            ));
+        }
+
+        if self.cfg.hash_algorithms.is_empty() {
+            return Err(Error::IllegalArgument(
+                "at least one hash algorithm must be allowed".into(),
+            ));
+        }
+
+        if let Some(alg) = self
+            .cfg
+            .hash_algorithms
+            .iter()
+            .find(|alg| !crypto::is_supported_hash_algorithm(alg))
+        {
+            return Err(Error::IllegalArgument(format!(
+                "unsupported hash algorithm {:?}",
+                alg
+            )));
+        }
+
+        if self.cfg.expiration_grace_period < Duration::zero() {
+            return Err(Error::IllegalArgument(
+                "expiration grace period must not be negative".into(),
+            ));
+        }
+
+        if self.cfg.expiration_warning_window < Duration::zero() {
+            return Err(Error::IllegalArgument(
+                "expiration warning window must not be negative".into(),
+            ));
+        }
+
+        Ok(self.cfg)
+    }
+
+    /// Set the maximum download length for root metadata.
+    pub fn max_root_length<T>(mut self, max: T) -> Self
+    where
+        T: Into<MetadataLengthLimit>,
+    {
+        let max: MetadataLengthLimit = max.into();
+        self.cfg.max_root_length = max.into();
+        self
+    }
+
+    /// Set the maximum download length for timestamp metadata.
+    pub fn max_timestamp_length<T>(mut self, max: T) -> Self
+    where
+        T: Into<MetadataLengthLimit>,
+    {
+        let max: MetadataLengthLimit = max.into();
+        self.cfg.max_timestamp_length = max.into();
+        self
+    }
+
+    /// Set the maximum download length for snapshot metadata.
+    pub fn max_snapshot_length<T>(mut self, max: T) -> Self
+    where
+        T: Into<MetadataLengthLimit>,
+    {
+        let max: MetadataLengthLimit = max.into();
+        self.cfg.max_snapshot_length = max.into();
+        self
+    }
+
+    /// Set the maximum download length for top-level targets metadata.
+    pub fn max_targets_length<T>(mut self, max: T) -> Self
+    where
+        T: Into<MetadataLengthLimit>,
+    {
+        let max: MetadataLengthLimit = max.into();
+        self.cfg.max_targets_length = max.into();
+        self
+    }
+
+    /// Set the maximum download length for delegated targets metadata.
+    pub fn max_delegated_targets_length<T>(mut self, max: T) -> Self
+    where
+        T: Into<MetadataLengthLimit>,
+    {
+        let max: MetadataLengthLimit = max.into();
+        self.cfg.max_delegated_targets_length = max.into();
+        self
+    }
+
+    /// Set the maximum download length for the metadata of custom top-level roles.
+    pub fn max_custom_role_length<T>(mut self, max: T) -> Self
+    where
+        T: Into<MetadataLengthLimit>,
+    {
+        let max: MetadataLengthLimit = max.into();
+        self.cfg.max_custom_role_length = max.into();
+        self
+    }
+
+    /// Set the maximum number of new root metadata versions that are fetched during a single
+    /// update. Once reached, the client stops rotating and continues the update with the newest
+    /// root it has verified.
+    pub fn max_root_rotations(mut self, max: u32) -> Self {
+        self.cfg.max_root_rotations = max;
+        self
+    }
+
+    /// Set the maximum number of steps used when walking the delegation graph.
+    pub fn max_delegation_depth(mut self, max: u32) -> Self {
+        self.cfg.max_delegation_depth = max;
+        self
+    }
+
+    /// Set the maximum number of delegated roles whose metadata is consulted when walking the
+    /// delegation graph.
+    pub fn max_visited_roles(mut self, max: u32) -> Self {
+        self.cfg.max_visited_roles = max;
+        self
+    }
+
+    /// Set the maximum number of times a fetch from the remote repository is retried after a
+    /// transient error, such as an IO error or an HTTP server error. Missing metadata and
+    /// verification failures are never retried.
+    pub fn max_fetch_retries(mut self, max: u32) -> Self {
+        self.cfg.max_fetch_retries = max;
+        self
+    }
+
+    /// Set the policy for retrying [Client::update] after it fails. Unlike
+    /// [ConfigBuilder::max_fetch_retries], which retries individual fetches, this retries the
+    /// whole update, with a backoff between attempts.
+    pub fn update_retry_policy(mut self, policy: UpdateRetryPolicy) -> Self {
+        self.cfg.update_retry_policy = policy;
+        self
+    }
+
+    /// Set the hash algorithms that are used to verify metadata and targets fetched from the
+    /// remote repository. Hashes of other algorithms listed in trusted metadata are ignored.
+    pub fn hash_algorithms<I>(mut self, algorithms: I) -> Self
+    where
+        I: IntoIterator<Item = HashAlgorithm>,
+    {
+        self.cfg.hash_algorithms = algorithms.into_iter().collect();
+        self
+    }
+
+    /// Set the period of time after expiration during which timestamp and snapshot metadata is
+    /// still accepted. See [Database::set_expiration_grace_period] for details.
+    pub fn expiration_grace_period(mut self, grace_period: Duration) -> Self {
+        self.cfg.expiration_grace_period = grace_period;
+        self
+    }
+
+    /// Set the period of time before expiration during which trusted metadata is reported as
+    /// expiring soon after an update. See [Client::expiring_metadata] for details.
+    pub fn expiration_warning_window(mut self, window: Duration) -> Self {
+        self.cfg.expiration_warning_window = window;
+        self
+    }
+
+    /// Set the major version of the specification that new metadata must follow. See
+    /// [Database::set_spec_major_version] for details.
+    pub fn spec_major_version(mut self, spec_major_version: u32) -> Self {
+        self.cfg.spec_major_version = spec_major_version;
+        self
+    }
+
+    /// Set whether or not targets whose descriptions are missing their length or hashes may be
+    /// fetched. This defaults to `false`, in which case fetching such a target fails with
+    /// [Error::UnverifiableTarget].
+    ///
+    /// **WARNING**: An unverifiable target is only checked against the length or hashes that its
+    /// description does specify, so a target without hashes may be arbitrarily modified by an
+    /// attacker, and a target without a length may be of any size.
+    pub fn allow_unverifiable_targets(mut self, allow_unverifiable_targets: bool) -> Self {
+        self.cfg.allow_unverifiable_targets = allow_unverifiable_targets;
+        self
+    }
+
+    /// Set the rules used to match targets against the paths of delegations. This should match
+    /// the rules of the tooling that produced the repository. See [PathMatching] for details.
+    pub fn path_matching(mut self, path_matching: PathMatching) -> Self {
+        self.cfg.path_matching = path_matching;
+        self
+    }
+
+    /// Set how strictly the length and hashes the timestamp metadata declares for the snapshot
+    /// metadata, and the snapshot metadata declares for targets and delegated targets metadata,
+    /// are enforced. See [Database::set_metadata_integrity_policy] for details.
+    pub fn metadata_integrity_policy(mut self, policy: MetadataIntegrityPolicy) -> Self {
+        self.cfg.metadata_integrity_policy = policy;
+        self
+    }
+
+    /// Set the hash algorithms and signature schemes that metadata may use. See
+    /// [Database::set_policy] for details.
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.cfg.policy = policy;
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{Ed25519PrivateKey, HashAlgorithm, PrivateKey};
+    use crate::metadata::{
+        Delegation, MetadataDescription, MetadataPath, MetadataVersion, MultiRoleDelegation,
+        RootMetadataBuilder, SnapshotMetadataBuilder, SuccinctRoles, TargetsMetadataBuilder,
+        TimestampMetadataBuilder,
+    };
+    use crate::pouf::Pouf1;
+    use crate::repo_builder::RepoBuilder;
+    use crate::repository::{
+        fetch_metadata_to_string, EphemeralRepository, ErrorRepository, MetadataCacheRepository,
+        Track, TrackRepository,
+    };
+    use crate::rollback::EphemeralRollbackState;
+    use assert_matches::assert_matches;
+    use chrono::prelude::*;
+    use futures_executor::block_on;
+    use futures_util::future::FutureExt as _;
+    use futures_util::io::Cursor;
+    use lazy_static::lazy_static;
+    use maplit::hashmap;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::iter::once;
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[
+                include_bytes!("../tests/ed25519/ed25519-1.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-2.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-3.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-4.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-5.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-6.pk8.der"),
+            ];
+            keys.iter()
+                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+                .collect()
+        };
+    }
+
+    #[allow(clippy::enum_variant_names)]
+    enum ConstructorMode {
+        WithTrustedLocal,
+        WithTrustedRoot,
+        WithTrustedRootKeys,
+        FromDatabase,
+    }
+
+    #[test]
+    fn client_constructors_err_with_not_found() {
+        block_on(async {
+            let mut local = EphemeralRepository::<Pouf1>::new();
+            let remote = EphemeralRepository::<Pouf1>::new();
+
+            let private_key =
+                Ed25519PrivateKey::from_pkcs8(&Ed25519PrivateKey::pkcs8().unwrap()).unwrap();
+            let public_key = private_key.public().clone();
+
+            assert_matches!(
+                Client::with_trusted_local(Config::default(), &mut local, &remote).await,
+                Err(Error::MetadataNotFound { path, version })
+                if path == MetadataPath::root() && version == MetadataVersion::Number(1)
+            );
+
+            assert_matches!(
+                Client::with_trusted_root_keys(
+                    Config::default(),
+                    MetadataVersion::Number(1),
+                    1,
+                    once(&public_key),
+                    local,
+                    &remote,
+                )
+                .await,
+                Err(Error::MetadataNotFound { path, version })
+                if path == MetadataPath::root() && version == MetadataVersion::Number(1)
+            );
+        })
+    }
+
+    #[test]
+    fn client_constructors_err_with_invalid_keys() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+
+            let good_private_key = &KEYS[0];
+            let bad_private_key = &KEYS[1];
+
+            let _ = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[good_private_key])
+                .trusted_targets_keys(&[good_private_key])
+                .trusted_snapshot_keys(&[good_private_key])
+                .trusted_timestamp_keys(&[good_private_key])
+                .commit()
+                .await
+                .unwrap();
+
+            assert_matches!(
                 Client::with_trusted_root_keys(
                     Config::default(),
                     MetadataVersion::Number(1),
-                    1,
-                    once(&public_key),
-                    local,
+                    1,
+                    once(bad_private_key.public()),
+                    EphemeralRepository::new(),
+                    &remote,
+                )
+                .await,
+                Err(Error::MetadataMissingSignatures {
+                    role,
+                    trusted_version: None,
+                    number_of_valid_signatures: 0,
+                    threshold: 1,
+                })
+                if role == MetadataPath::root()
+            );
+        })
+    }
+
+    #[test]
+    fn with_trusted_local_loads_metadata_from_local_repo() {
+        block_on(constructors_load_metadata_from_local_repo(
+            ConstructorMode::WithTrustedLocal,
+        ))
+    }
+
+    #[test]
+    fn with_trusted_root_loads_metadata_from_local_repo() {
+        block_on(constructors_load_metadata_from_local_repo(
+            ConstructorMode::WithTrustedRoot,
+        ))
+    }
+
+    #[test]
+    fn with_trusted_root_keys_loads_metadata_from_local_repo() {
+        block_on(constructors_load_metadata_from_local_repo(
+            ConstructorMode::WithTrustedRootKeys,
+        ))
+    }
+
+    #[test]
+    fn from_database_loads_metadata_from_local_repo() {
+        block_on(constructors_load_metadata_from_local_repo(
+            ConstructorMode::FromDatabase,
+        ))
+    }
+
+    async fn constructors_load_metadata_from_local_repo(constructor_mode: ConstructorMode) {
+        // Store an expired root in the local store.
+        let mut local = EphemeralRepository::<Pouf1>::new();
+        let metadata1 = RepoBuilder::create(&mut local)
+            .current_time(Utc.timestamp_opt(0, 0).unwrap())
+            .trusted_root_keys(&[&KEYS[0]])
+            .trusted_targets_keys(&[&KEYS[0]])
+            .trusted_snapshot_keys(&[&KEYS[0]])
+            .trusted_timestamp_keys(&[&KEYS[0]])
+            .stage_root_with_builder(|bld| bld.consistent_snapshot(true))
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        // Remote repo has unexpired metadata.
+        let mut remote = EphemeralRepository::<Pouf1>::new();
+        let metadata2 = RepoBuilder::create(&mut remote)
+            .trusted_root_keys(&[&KEYS[0]])
+            .trusted_targets_keys(&[&KEYS[0]])
+            .trusted_snapshot_keys(&[&KEYS[0]])
+            .trusted_timestamp_keys(&[&KEYS[0]])
+            .stage_root_with_builder(|bld| bld.version(2).consistent_snapshot(true))
+            .unwrap()
+            .stage_targets_with_builder(|bld| bld.version(2))
+            .unwrap()
+            .stage_snapshot_with_builder(|bld| bld.version(2))
+            .unwrap()
+            .stage_timestamp_with_builder(|bld| bld.version(2))
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        // Now, make sure that the local metadata got version 1.
+        let track_local = TrackRepository::new(local);
+        let track_remote = TrackRepository::new(remote);
+
+        // Make sure the client initialized metadata in the right order. Each has a slightly
+        // different usage of the local repository.
+        let mut client = match constructor_mode {
+            ConstructorMode::WithTrustedLocal => {
+                Client::with_trusted_local(Config::default(), track_local, track_remote)
+                    .await
+                    .unwrap()
+            }
+            ConstructorMode::WithTrustedRoot => Client::with_trusted_root(
+                Config::default(),
+                metadata1.root().unwrap(),
+                track_local,
+                track_remote,
+            )
+            .await
+            .unwrap(),
+            ConstructorMode::WithTrustedRootKeys => Client::with_trusted_root_keys(
+                Config::default(),
+                MetadataVersion::Number(1),
+                1,
+                once(&KEYS[0].public().clone()),
+                track_local,
+                track_remote,
+            )
+            .await
+            .unwrap(),
+            ConstructorMode::FromDatabase => Client::from_database(
+                Config::default(),
+                Database::from_trusted_root(metadata1.root().unwrap()).unwrap(),
+                track_local,
+                track_remote,
+            ),
+        };
+
+        assert_eq!(client.tuf.trusted_root().version(), 1);
+        assert_eq!(client.remote_repo().take_tracks(), vec![]);
+
+        // According to [1], "Check for freeze attack", only the root should be
+        // fetched since it has expired.
+        //
+        // [1]: https://theupdateframework.github.io/specification/latest/#update-root
+        match constructor_mode {
+            ConstructorMode::WithTrustedLocal => {
+                assert_eq!(
+                    client.local_repo().take_tracks(),
+                    vec![
+                        Track::fetch_meta_found(
+                            MetadataVersion::Number(1),
+                            metadata1.root().unwrap()
+                        ),
+                        Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(2)),
+                    ],
+                );
+            }
+            ConstructorMode::WithTrustedRoot => {
+                assert_eq!(
+                    client.local_repo().take_tracks(),
+                    vec![Track::FetchErr(
+                        MetadataPath::root(),
+                        MetadataVersion::Number(2)
+                    )],
+                );
+            }
+            ConstructorMode::WithTrustedRootKeys => {
+                assert_eq!(
+                    client.local_repo().take_tracks(),
+                    vec![
+                        Track::fetch_meta_found(
+                            MetadataVersion::Number(1),
+                            metadata1.root().unwrap()
+                        ),
+                        Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(2)),
+                    ],
+                );
+            }
+            ConstructorMode::FromDatabase => {
+                assert_eq!(client.local_repo().take_tracks(), vec![],);
+            }
+        };
+
+        assert_matches!(client.update().await, Ok(true));
+        assert_eq!(client.tuf.trusted_root().version(), 2);
+
+        // We should only fetch metadata from the remote repository and write it to the local
+        // repository.
+        assert_eq!(
+            client.remote_repo().take_tracks(),
+            vec![
+                Track::fetch_meta_found(MetadataVersion::Number(2), metadata2.root().unwrap()),
+                Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(3)),
+                Track::fetch_meta_found(MetadataVersion::None, metadata2.timestamp().unwrap()),
+                Track::fetch_meta_found(MetadataVersion::Number(2), metadata2.snapshot().unwrap()),
+                Track::fetch_meta_found(MetadataVersion::Number(2), metadata2.targets().unwrap()),
+            ],
+        );
+        assert_eq!(
+            client.local_repo().take_tracks(),
+            vec![
+                Track::store_meta(MetadataVersion::None, metadata2.root().unwrap()),
+                Track::store_meta(MetadataVersion::Number(2), metadata2.root().unwrap()),
+                Track::store_meta(MetadataVersion::None, metadata2.timestamp().unwrap()),
+                Track::store_meta(MetadataVersion::None, metadata2.snapshot().unwrap()),
+                Track::store_meta(MetadataVersion::None, metadata2.targets().unwrap()),
+            ],
+        );
+
+        // Another update should not fetch anything.
+        assert_matches!(client.update().await, Ok(false));
+        assert_eq!(client.tuf.trusted_root().version(), 2);
+
+        // Make sure we only fetched the next root and timestamp, and didn't store anything.
+        assert_eq!(
+            client.remote_repo().take_tracks(),
+            vec![
+                Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(3)),
+                Track::fetch_meta_found(MetadataVersion::None, metadata2.timestamp().unwrap()),
+            ]
+        );
+        assert_eq!(client.local_repo().take_tracks(), vec![]);
+    }
+
+    #[test]
+    fn constructor_succeeds_with_missing_metadata() {
+        block_on(async {
+            let mut local = EphemeralRepository::<Pouf1>::new();
+            let remote = EphemeralRepository::<Pouf1>::new();
+
+            // Store only a root in the local store.
+            let metadata1 = RepoBuilder::create(&mut local)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root_with_builder(|bld| bld.consistent_snapshot(true))
+                .unwrap()
+                .skip_targets()
+                .skip_snapshot()
+                .skip_timestamp()
+                .commit()
+                .await
+                .unwrap();
+
+            let track_local = TrackRepository::new(local);
+            let track_remote = TrackRepository::new(remote);
+
+            // Create a client, which should try to fetch metadata from the local store.
+            let client = Client::with_trusted_root(
+                Config::default(),
+                metadata1.root().unwrap(),
+                track_local,
+                track_remote,
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(client.tuf.trusted_root().version(), 1);
+
+            // We shouldn't fetch metadata.
+            assert_eq!(client.remote_repo().take_tracks(), vec![]);
+
+            // We should have tried fetching a new timestamp, but it shouldn't exist in the
+            // repository.
+            assert_eq!(
+                client.local_repo().take_tracks(),
+                vec![
+                    Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(2)),
+                    Track::FetchErr(MetadataPath::timestamp(), MetadataVersion::None)
+                ],
+            );
+
+            // An update should succeed.
+            let mut parts = client.into_parts();
+            let metadata2 = RepoBuilder::create(parts.remote.as_inner_mut())
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root_with_builder(|bld| bld.version(2).consistent_snapshot(true))
+                .unwrap()
+                .stage_targets_with_builder(|bld| bld.version(2))
+                .unwrap()
+                .stage_snapshot_with_builder(|bld| bld.version(2))
+                .unwrap()
+                .stage_timestamp_with_builder(|bld| bld.version(2))
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let mut client = Client::from_parts(parts);
+            assert_matches!(client.update().await, Ok(true));
+            assert_eq!(client.tuf.trusted_root().version(), 2);
+
+            // We should have fetched the metadata, and written it to the local database.
+            assert_eq!(
+                client.remote_repo().take_tracks(),
+                vec![
+                    Track::fetch_meta_found(MetadataVersion::Number(2), metadata2.root().unwrap()),
+                    Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(3)),
+                    Track::fetch_meta_found(MetadataVersion::None, metadata2.timestamp().unwrap()),
+                    Track::fetch_meta_found(
+                        MetadataVersion::Number(2),
+                        metadata2.snapshot().unwrap()
+                    ),
+                    Track::fetch_meta_found(
+                        MetadataVersion::Number(2),
+                        metadata2.targets().unwrap()
+                    ),
+                ],
+            );
+            assert_eq!(
+                client.local_repo().take_tracks(),
+                vec![
+                    Track::store_meta(MetadataVersion::None, metadata2.root().unwrap()),
+                    Track::store_meta(MetadataVersion::Number(2), metadata2.root().unwrap()),
+                    Track::store_meta(MetadataVersion::None, metadata2.timestamp().unwrap()),
+                    Track::store_meta(MetadataVersion::None, metadata2.snapshot().unwrap()),
+                    Track::store_meta(MetadataVersion::None, metadata2.targets().unwrap()),
+                ],
+            );
+        })
+    }
+
+    #[test]
+    fn constructor_succeeds_with_expired_metadata() {
+        block_on(async {
+            let mut local = EphemeralRepository::<Pouf1>::new();
+            let remote = EphemeralRepository::<Pouf1>::new();
+
+            // Store an expired root in the local store.
+            let metadata1 = RepoBuilder::create(&mut local)
+                .current_time(Utc.timestamp_opt(0, 0).unwrap())
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root_with_builder(|bld| bld.version(1).consistent_snapshot(true))
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let metadata2 = RepoBuilder::create(&mut local)
+                .current_time(Utc.timestamp_opt(0, 0).unwrap())
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root_with_builder(|bld| bld.version(2).consistent_snapshot(true))
+                .unwrap()
+                .stage_targets_with_builder(|bld| bld.version(2))
+                .unwrap()
+                .stage_snapshot_with_builder(|bld| bld.version(2))
+                .unwrap()
+                .stage_timestamp_with_builder(|bld| bld.version(2))
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            // Now, make sure that the local metadata got version 1.
+            let track_local = TrackRepository::new(local);
+            let track_remote = TrackRepository::new(remote);
+
+            let client = Client::with_trusted_root(
+                Config::default(),
+                metadata1.root().unwrap(),
+                track_local,
+                track_remote,
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(client.tuf.trusted_root().version(), 2);
+
+            // We shouldn't fetch metadata.
+            assert_eq!(client.remote_repo().take_tracks(), vec![]);
+
+            // We should only load the root metadata, but because it's expired we don't try
+            // fetching the other local metadata.
+            assert_eq!(
+                client.local_repo().take_tracks(),
+                vec![
+                    Track::fetch_meta_found(MetadataVersion::Number(2), metadata2.root().unwrap()),
+                    Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(3))
+                ],
+            );
+
+            // An update should succeed.
+            let mut parts = client.into_parts();
+            let _metadata3 = RepoBuilder::create(&mut parts.remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root_with_builder(|bld| {
+                    bld.version(3)
+                        .consistent_snapshot(true)
+                        .expires(Utc.with_ymd_and_hms(2038, 1, 1, 0, 0, 0).unwrap())
+                })
+                .unwrap()
+                .stage_targets_with_builder(|bld| bld.version(2))
+                .unwrap()
+                .stage_snapshot_with_builder(|bld| bld.version(2))
+                .unwrap()
+                .stage_timestamp_with_builder(|bld| bld.version(2))
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let mut client = Client::from_parts(parts);
+            assert_matches!(client.update().await, Ok(true));
+            assert_eq!(client.tuf.trusted_root().version(), 3);
+        })
+    }
+
+    #[test]
+    fn constructor_succeeds_with_malformed_metadata() {
+        block_on(async {
+            // Store a malformed timestamp in the local repository.
+            let local = EphemeralRepository::<Pouf1>::new();
+            let junk_timestamp = "junk timestamp";
+
+            local
+                .store_metadata(
+                    &MetadataPath::timestamp(),
+                    MetadataVersion::None,
+                    &mut junk_timestamp.as_bytes(),
+                )
+                .await
+                .unwrap();
+
+            // Create a normal repository on the remote server.
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let metadata1 = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            // Create the client. It should ignore the malformed timestamp.
+            let track_local = TrackRepository::new(local);
+            let track_remote = TrackRepository::new(remote);
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata1.root().unwrap(),
+                track_local,
+                track_remote,
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(client.tuf.trusted_root().version(), 1);
+
+            // We shouldn't fetch metadata.
+            assert_eq!(client.remote_repo().take_tracks(), vec![]);
+
+            // We should only load the root metadata, but because it's expired we don't try
+            // fetching the other local metadata.
+            assert_eq!(
+                client.local_repo().take_tracks(),
+                vec![
+                    Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(2)),
+                    Track::FetchFound {
+                        path: MetadataPath::timestamp(),
+                        version: MetadataVersion::None,
+                        metadata: junk_timestamp.into(),
+                    },
+                ],
+            );
+
+            // An update should work.
+            assert_matches!(client.update().await, Ok(true));
+        })
+    }
+
+    #[test]
+    fn root_chain_update_consistent_snapshot_false() {
+        block_on(root_chain_update(false))
+    }
+
+    #[test]
+    fn root_chain_update_consistent_snapshot_true() {
+        block_on(root_chain_update(true))
+    }
+
+    async fn root_chain_update(consistent_snapshot: bool) {
+        let mut repo = EphemeralRepository::<Pouf1>::new();
+
+        // First, create the initial metadata. We want to use the same non-root
+        // metadata, so sign it with all the keys.
+        let metadata1 = RepoBuilder::create(&mut repo)
+            .trusted_root_keys(&[&KEYS[0]])
+            .signing_targets_keys(&[&KEYS[1], &KEYS[2]])
+            .trusted_targets_keys(&[&KEYS[0]])
+            .signing_snapshot_keys(&[&KEYS[1], &KEYS[2]])
+            .trusted_snapshot_keys(&[&KEYS[0]])
+            .signing_timestamp_keys(&[&KEYS[1], &KEYS[2]])
+            .trusted_timestamp_keys(&[&KEYS[0]])
+            .stage_root_with_builder(|bld| bld.consistent_snapshot(consistent_snapshot))
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        let root_path = MetadataPath::root();
+        let timestamp_path = MetadataPath::timestamp();
+
+        let targets_version;
+        let snapshot_version;
+        if consistent_snapshot {
+            targets_version = MetadataVersion::Number(1);
+            snapshot_version = MetadataVersion::Number(1);
+        } else {
+            targets_version = MetadataVersion::None;
+            snapshot_version = MetadataVersion::None;
+        };
+
+        // Now, make sure that the local metadata got version 1.
+        let track_local = TrackRepository::new(EphemeralRepository::new());
+        let track_remote = TrackRepository::new(repo);
+
+        let mut client = Client::with_trusted_root_keys(
+            Config::default(),
+            MetadataVersion::Number(1),
+            1,
+            once(&KEYS[0].public().clone()),
+            track_local,
+            track_remote,
+        )
+        .await
+        .unwrap();
+
+        // Check that we tried to load metadata from the local repository.
+        assert_eq!(
+            client.remote_repo().take_tracks(),
+            vec![Track::fetch_found(
+                &root_path,
+                MetadataVersion::Number(1),
+                metadata1.root().unwrap().as_bytes()
+            ),]
+        );
+        assert_eq!(
+            client.local_repo().take_tracks(),
+            vec![
+                Track::FetchErr(root_path.clone(), MetadataVersion::Number(1)),
+                Track::store_meta(MetadataVersion::Number(1), metadata1.root().unwrap()),
+                Track::FetchErr(root_path.clone(), MetadataVersion::Number(2)),
+                Track::FetchErr(timestamp_path.clone(), MetadataVersion::None),
+            ]
+        );
+
+        assert_matches!(client.update().await, Ok(true));
+        assert_eq!(client.tuf.trusted_root().version(), 1);
+
+        // Make sure we fetched the metadata in the right order.
+        assert_eq!(
+            client.remote_repo().take_tracks(),
+            vec![
+                Track::FetchErr(root_path.clone(), MetadataVersion::Number(2)),
+                Track::fetch_meta_found(MetadataVersion::None, metadata1.timestamp().unwrap()),
+                Track::fetch_meta_found(snapshot_version, metadata1.snapshot().unwrap()),
+                Track::fetch_meta_found(targets_version, metadata1.targets().unwrap()),
+            ]
+        );
+        assert_eq!(
+            client.local_repo().take_tracks(),
+            vec![
+                Track::store_meta(MetadataVersion::None, metadata1.timestamp().unwrap()),
+                Track::store_meta(MetadataVersion::None, metadata1.snapshot().unwrap()),
+                Track::store_meta(MetadataVersion::None, metadata1.targets().unwrap()),
+            ],
+        );
+
+        // Another update should not fetch anything.
+        assert_matches!(client.update().await, Ok(false));
+        assert_eq!(client.tuf.trusted_root().version(), 1);
+
+        // Make sure we only fetched the next root and timestamp, and didn't store anything.
+        assert_eq!(
+            client.remote_repo().take_tracks(),
+            vec![
+                Track::FetchErr(root_path.clone(), MetadataVersion::Number(2)),
+                Track::fetch_meta_found(MetadataVersion::None, metadata1.timestamp().unwrap()),
+            ]
+        );
+        assert_eq!(client.local_repo().take_tracks(), vec![]);
+
+        ////
+        // Now bump the root to version 3
+
+        // Make sure the version 2 is also signed by version 1's keys.
+        //
+        // Note that we write to the underlying store so TrackRepo doesn't track
+        // this new metadata.
+        let mut parts = client.into_parts();
+        let metadata2 = RepoBuilder::create(parts.remote.as_inner_mut())
+            .signing_root_keys(&[&KEYS[0]])
+            .trusted_root_keys(&[&KEYS[1]])
+            .trusted_targets_keys(&[&KEYS[1]])
+            .trusted_snapshot_keys(&[&KEYS[1]])
+            .trusted_timestamp_keys(&[&KEYS[1]])
+            .stage_root_with_builder(|bld| bld.version(2).consistent_snapshot(consistent_snapshot))
+            .unwrap()
+            .skip_targets()
+            .skip_snapshot()
+            .skip_timestamp()
+            .commit()
+            .await
+            .unwrap();
+
+        // Make sure the version 3 is also signed by version 2's keys.
+        let metadata3 = RepoBuilder::create(parts.remote.as_inner_mut())
+            .signing_root_keys(&[&KEYS[1]])
+            .trusted_root_keys(&[&KEYS[2]])
+            .trusted_targets_keys(&[&KEYS[2]])
+            .trusted_snapshot_keys(&[&KEYS[2]])
+            .trusted_timestamp_keys(&[&KEYS[2]])
+            .stage_root_with_builder(|bld| bld.version(3).consistent_snapshot(consistent_snapshot))
+            .unwrap()
+            .skip_targets()
+            .skip_snapshot()
+            .skip_timestamp()
+            .commit()
+            .await
+            .unwrap();
+
+        ////
+        // Finally, check that the update brings us to version 3.
+        let mut client = Client::from_parts(parts);
+        assert_matches!(client.update().await, Ok(true));
+        assert_eq!(client.tuf.trusted_root().version(), 3);
+
+        // Make sure we fetched and stored the metadata in the expected order. Note that we
+        // re-fetch snapshot and targets because we rotated keys, which caused `tuf::Database` to delete
+        // the metadata.
+        assert_eq!(
+            client.remote_repo().take_tracks(),
+            vec![
+                Track::fetch_meta_found(MetadataVersion::Number(2), metadata2.root().unwrap()),
+                Track::fetch_meta_found(MetadataVersion::Number(3), metadata3.root().unwrap()),
+                Track::FetchErr(root_path.clone(), MetadataVersion::Number(4)),
+                Track::fetch_meta_found(MetadataVersion::None, metadata1.timestamp().unwrap()),
+                Track::fetch_meta_found(snapshot_version, metadata1.snapshot().unwrap()),
+                Track::fetch_meta_found(targets_version, metadata1.targets().unwrap()),
+            ]
+        );
+        assert_eq!(
+            client.local_repo().take_tracks(),
+            vec![
+                Track::store_meta(MetadataVersion::None, metadata2.root().unwrap()),
+                Track::store_meta(MetadataVersion::Number(2), metadata2.root().unwrap()),
+                Track::store_meta(MetadataVersion::None, metadata3.root().unwrap()),
+                Track::store_meta(MetadataVersion::Number(3), metadata3.root().unwrap()),
+                Track::store_meta(MetadataVersion::None, metadata1.timestamp().unwrap()),
+                Track::store_meta(MetadataVersion::None, metadata1.snapshot().unwrap()),
+                Track::store_meta(MetadataVersion::None, metadata1.targets().unwrap()),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_fetch_target_description_standard() {
+        block_on(test_fetch_target_description(
+            "standard/metadata".to_string(),
+            TargetDescription::from_slice(
+                "target with no custom metadata".as_bytes(),
+                &[HashAlgorithm::Sha256],
+            )
+            .unwrap(),
+        ));
+    }
+
+    #[test]
+    fn test_fetch_target_description_custom_empty() {
+        block_on(test_fetch_target_description(
+            "custom-empty".to_string(),
+            TargetDescription::from_slice_with_custom(
+                "target with empty custom metadata".as_bytes(),
+                &[HashAlgorithm::Sha256],
+                hashmap!(),
+            )
+            .unwrap(),
+        ));
+    }
+
+    #[test]
+    fn test_fetch_target_description_custom() {
+        block_on(test_fetch_target_description(
+            "custom/metadata".to_string(),
+            TargetDescription::from_slice_with_custom(
+                "target with lots of custom metadata".as_bytes(),
+                &[HashAlgorithm::Sha256],
+                hashmap!(
+                    "string".to_string() => json!("string"),
+                    "bool".to_string() => json!(true),
+                    "int".to_string() => json!(42),
+                    "object".to_string() => json!({
+                        "string": json!("string"),
+                        "bool": json!(true),
+                        "int": json!(42),
+                    }),
+                    "array".to_string() => json!([1, 2, 3]),
+                ),
+            )
+            .unwrap(),
+        ));
+    }
+
+    async fn test_fetch_target_description(path: String, expected_description: TargetDescription) {
+        // Generate an ephemeral repository with a single target.
+        let mut remote = EphemeralRepository::<Pouf1>::new();
+
+        let metadata = RepoBuilder::create(&mut remote)
+            .trusted_root_keys(&[&KEYS[0]])
+            .trusted_targets_keys(&[&KEYS[0]])
+            .trusted_snapshot_keys(&[&KEYS[0]])
+            .trusted_timestamp_keys(&[&KEYS[0]])
+            .stage_root()
+            .unwrap()
+            .stage_targets_with_builder(|bld| {
+                bld.insert_target_description(
+                    TargetPath::new(path.clone()).unwrap(),
+                    expected_description.clone(),
+                )
+            })
+            .unwrap()
+            .commit()
+            .await
+            .unwrap();
+
+        // Initialize and update client.
+        let mut client = Client::with_trusted_root(
+            Config::default(),
+            metadata.root().unwrap(),
+            EphemeralRepository::new(),
+            remote,
+        )
+        .await
+        .unwrap();
+
+        assert_matches!(client.update().await, Ok(true));
+
+        // Verify fetch_target_description returns expected target metadata
+        let description = client
+            .fetch_target_description(&TargetPath::new(path).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(description, expected_description);
+    }
+
+    #[test]
+    fn test_trusted_targets_iter() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let delegation_path = MetadataPath::new("delegation").unwrap();
+            let top_path = TargetPath::new("top").unwrap();
+            let foo_path = TargetPath::new("foo").unwrap();
+            let bar_path = TargetPath::new("bar").unwrap();
+
+            // The delegation is only trusted for `foo`, so `bar` should not be listed.
+            let raw_delegation = TargetsMetadataBuilder::new()
+                .insert_target_from_slice(foo_path.clone(), b"foo", &[HashAlgorithm::Sha256])
+                .unwrap()
+                .insert_target_from_slice(bar_path.clone(), b"bar", &[HashAlgorithm::Sha256])
+                .unwrap()
+                .signed::<Pouf1>(&KEYS[1])
+                .unwrap()
+                .to_raw()
+                .unwrap();
+            let delegation_description = MetadataDescription::from_slice(
+                raw_delegation.as_bytes(),
+                1,
+                &[HashAlgorithm::Sha256],
+            )
+            .unwrap();
+
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .add_target(top_path.clone(), Cursor::new(b"top"))
+                .await
+                .unwrap()
+                .add_delegation_key(KEYS[1].public().clone())
+                .add_delegation_role(
+                    Delegation::builder(delegation_path.clone())
+                        .key(KEYS[1].public())
+                        .delegate_path(foo_path.clone())
+                        .build()
+                        .unwrap(),
+                )
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot_with_builder(|builder| {
+                    builder.insert_metadata_description(
+                        delegation_path.clone(),
+                        delegation_description.clone(),
+                    )
+                })
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            remote
+                .store_metadata(
+                    &delegation_path,
+                    MetadataVersion::Number(1),
+                    &mut raw_delegation.as_bytes(),
+                )
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+
+            assert_matches!(client.update().await, Ok(true));
+            assert!(!client
+                .database()
+                .trusted_delegations()
+                .contains_key(&delegation_path));
+
+            let targets = client
+                .trusted_targets_iter()
+                .await
+                .unwrap()
+                .collect::<Vec<_>>();
+            assert_eq!(
+                targets
+                    .iter()
+                    .map(|t| (t.path().clone(), t.role().clone()))
+                    .collect::<Vec<_>>(),
+                vec![
+                    (top_path.clone(), MetadataPath::targets()),
+                    (foo_path.clone(), delegation_path.clone()),
+                ]
+            );
+            assert_eq!(
+                targets[1].description(),
+                &client.database().target_description(&foo_path).unwrap()
+            );
+
+            // The delegated metadata was fetched and is now trusted.
+            assert!(client
+                .database()
+                .trusted_delegations()
+                .contains_key(&delegation_path));
+        })
+    }
+
+    #[test]
+    fn test_trusted_targets_iter_credits_resolved_role() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let a_path = MetadataPath::new("a").unwrap();
+            let b_path = MetadataPath::new("b").unwrap();
+            let foo_path = TargetPath::new("foo").unwrap();
+
+            // Both roles list `foo` with the same description, but only `b` is trusted for it.
+            let raw_delegation = TargetsMetadataBuilder::new()
+                .insert_target_from_slice(foo_path.clone(), b"foo", &[HashAlgorithm::Sha256])
+                .unwrap()
+                .signed::<Pouf1>(&KEYS[1])
+                .unwrap()
+                .to_raw()
+                .unwrap();
+            let delegation_description = MetadataDescription::from_slice(
+                raw_delegation.as_bytes(),
+                1,
+                &[HashAlgorithm::Sha256],
+            )
+            .unwrap();
+
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .add_delegation_key(KEYS[1].public().clone())
+                .add_delegation_role(
+                    Delegation::builder(a_path.clone())
+                        .key(KEYS[1].public())
+                        .delegate_path(TargetPath::new("bar").unwrap())
+                        .build()
+                        .unwrap(),
+                )
+                .add_delegation_role(
+                    Delegation::builder(b_path.clone())
+                        .key(KEYS[1].public())
+                        .delegate_path(foo_path.clone())
+                        .build()
+                        .unwrap(),
+                )
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot_with_builder(|builder| {
+                    builder
+                        .insert_metadata_description(a_path.clone(), delegation_description.clone())
+                        .insert_metadata_description(b_path.clone(), delegation_description.clone())
+                })
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            for path in [&a_path, &b_path] {
+                remote
+                    .store_metadata(
+                        path,
+                        MetadataVersion::Number(1),
+                        &mut raw_delegation.as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+            }
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+            assert_matches!(client.update().await, Ok(true));
+
+            let targets = client
+                .trusted_targets_iter()
+                .await
+                .unwrap()
+                .map(|t| (t.path().clone(), t.role().clone()))
+                .collect::<Vec<_>>();
+            assert_eq!(targets, vec![(foo_path, b_path)]);
+        })
+    }
+
+    #[test]
+    fn test_succinct_delegations() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+
+            // sha256("foo") starts with 0b00 and sha256("bar") with 0b11, so with two bits "foo"
+            // is delegated to "bins-0" and "bar" to "bins-3".
+            let foo_path = TargetPath::new("foo").unwrap();
+            let bar_path = TargetPath::new("bar").unwrap();
+            let succinct_roles = SuccinctRoles::new(
+                HashSet::from([KEYS[1].public().key_id().clone()]),
+                1,
+                2,
+                "bins".into(),
+            )
+            .unwrap();
+            assert_eq!(
+                succinct_roles.role_for_target(&foo_path),
+                MetadataPath::new("bins-0").unwrap()
+            );
+            assert_eq!(
+                succinct_roles.role_for_target(&bar_path),
+                MetadataPath::new("bins-3").unwrap()
+            );
+
+            // "bins-1" is not authorized to sign "bar".
+            let mut bins = vec![];
+            for (bin, target, contents) in
+                [("bins-0", &foo_path, b"foo"), ("bins-1", &bar_path, b"bar")]
+            {
+                let raw = TargetsMetadataBuilder::new()
+                    .insert_target_from_slice(target.clone(), contents, &[HashAlgorithm::Sha256])
+                    .unwrap()
+                    .signed::<Pouf1>(&KEYS[1])
+                    .unwrap()
+                    .to_raw()
+                    .unwrap();
+                let description =
+                    MetadataDescription::from_slice(raw.as_bytes(), 1, &[HashAlgorithm::Sha256])
+                        .unwrap();
+                bins.push((MetadataPath::new(bin).unwrap(), raw, description));
+            }
+
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .add_delegation_key(KEYS[1].public().clone())
+                .succinct_delegation_roles(succinct_roles)
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot_with_builder(|mut builder| {
+                    for (bin, _, description) in &bins {
+                        builder =
+                            builder.insert_metadata_description(bin.clone(), description.clone());
+                    }
+                    builder
+                })
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            for (bin, raw, _) in &bins {
+                remote
+                    .store_metadata(bin, MetadataVersion::Number(1), &mut raw.as_bytes())
+                    .await
+                    .unwrap();
+            }
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                TrackRepository::new(remote),
+            )
+            .await
+            .unwrap();
+            assert_matches!(client.update().await, Ok(true));
+            let _ = client.remote_repo().take_tracks();
+
+            // Only the bin "foo" hashes to is fetched.
+            assert_eq!(
+                client.fetch_target_description(&foo_path).await.unwrap(),
+                TargetDescription::from_slice(b"foo", &[HashAlgorithm::Sha256]).unwrap()
+            );
+            let fetched = client
+                .remote_repo()
+                .take_tracks()
+                .into_iter()
+                .filter_map(|track| match track {
+                    Track::FetchFound { path, .. } | Track::FetchErr(path, _) => Some(path),
+                    Track::Store { .. } => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(fetched, vec![MetadataPath::new("bins-0").unwrap()]);
+
+            assert_matches!(
+                client.fetch_target_description(&bar_path).await,
+                Err(Error::TargetNotFound(_))
+            );
+        })
+    }
+
+    #[test]
+    fn test_multi_role_delegations() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let dev_path = MetadataPath::new("dev").unwrap();
+            let release_path = MetadataPath::new("release").unwrap();
+            let foo_path = TargetPath::new("foo").unwrap();
+            let bar_path = TargetPath::new("bar").unwrap();
+
+            // Both roles agree on "foo", but disagree on "bar".
+            let mut roles = vec![];
+            for (role, key, bar) in [
+                (&dev_path, &KEYS[1], &b"bar"[..]),
+                (&release_path, &KEYS[2], &b"baz"[..]),
+            ] {
+                let raw = TargetsMetadataBuilder::new()
+                    .insert_target_from_slice(foo_path.clone(), b"foo", &[HashAlgorithm::Sha256])
+                    .unwrap()
+                    .insert_target_from_slice(bar_path.clone(), bar, &[HashAlgorithm::Sha256])
+                    .unwrap()
+                    .signed::<Pouf1>(key)
+                    .unwrap()
+                    .to_raw()
+                    .unwrap();
+                let description =
+                    MetadataDescription::from_slice(raw.as_bytes(), 1, &[HashAlgorithm::Sha256])
+                        .unwrap();
+                roles.push((role.clone(), raw, description));
+            }
+
+            let multi_role = MultiRoleDelegation::new(
+                2,
+                vec![
+                    Delegation::builder(dev_path.clone())
+                        .key(KEYS[1].public())
+                        .delegate_path(foo_path.clone())
+                        .delegate_path(bar_path.clone())
+                        .build()
+                        .unwrap(),
+                    Delegation::builder(release_path.clone())
+                        .key(KEYS[2].public())
+                        .delegate_path(foo_path.clone())
+                        .delegate_path(bar_path.clone())
+                        .build()
+                        .unwrap(),
+                ],
+            )
+            .unwrap();
+
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .add_delegation_key(KEYS[1].public().clone())
+                .add_delegation_key(KEYS[2].public().clone())
+                .add_multi_role_delegation(multi_role)
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot_with_builder(|mut builder| {
+                    for (role, _, description) in &roles {
+                        builder =
+                            builder.insert_metadata_description(role.clone(), description.clone());
+                    }
+                    builder
+                })
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            for (role, raw, _) in &roles {
+                remote
+                    .store_metadata(role, MetadataVersion::Number(1), &mut raw.as_bytes())
+                    .await
+                    .unwrap();
+            }
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+            assert_matches!(client.update().await, Ok(true));
+
+            let foo_description =
+                TargetDescription::from_slice(b"foo", &[HashAlgorithm::Sha256]).unwrap();
+            assert_eq!(
+                client.fetch_target_description(&foo_path).await.unwrap(),
+                foo_description
+            );
+            assert_matches!(
+                client.fetch_target_description(&bar_path).await,
+                Err(Error::TargetNotFound(_))
+            );
+
+            // Both roles were fetched, so the database agrees.
+            assert_eq!(
+                client.database().target_description(&foo_path).unwrap(),
+                foo_description
+            );
+            assert_matches!(
+                client.database().target_description(&bar_path),
+                Err(Error::TargetNotFound(_))
+            );
+        })
+    }
+
+    #[test]
+    fn test_delegation_path_matching() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let delegation_path = MetadataPath::new("images").unwrap();
+            let image_path = TargetPath::new("images/foo.img").unwrap();
+
+            let raw_delegation = TargetsMetadataBuilder::new()
+                .insert_target_from_slice(image_path.clone(), b"foo", &[HashAlgorithm::Sha256])
+                .unwrap()
+                .signed::<Pouf1>(&KEYS[1])
+                .unwrap()
+                .to_raw()
+                .unwrap();
+            let delegation_description = MetadataDescription::from_slice(
+                raw_delegation.as_bytes(),
+                1,
+                &[HashAlgorithm::Sha256],
+            )
+            .unwrap();
+
+            // The delegated path is a glob, which does not match with the default rules.
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .add_delegation_key(KEYS[1].public().clone())
+                .add_delegation_role(
+                    Delegation::builder(delegation_path.clone())
+                        .key(KEYS[1].public())
+                        .delegate_path(TargetPath::new("images/*.img").unwrap())
+                        .build()
+                        .unwrap(),
+                )
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot_with_builder(|builder| {
+                    builder.insert_metadata_description(
+                        delegation_path.clone(),
+                        delegation_description.clone(),
+                    )
+                })
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            remote
+                .store_metadata(
+                    &delegation_path,
+                    MetadataVersion::Number(1),
+                    &mut raw_delegation.as_bytes(),
+                )
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                &remote,
+            )
+            .await
+            .unwrap();
+            assert_matches!(client.update().await, Ok(true));
+            assert_matches!(
+                client.fetch_target_description(&image_path).await,
+                Err(Error::TargetNotFound(_))
+            );
+
+            let mut client = Client::with_trusted_root(
+                Config::build()
+                    .path_matching(PathMatching::Glob {
+                        case_sensitive: true,
+                    })
+                    .finish()
+                    .unwrap(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                &remote,
+            )
+            .await
+            .unwrap();
+            assert_matches!(client.update().await, Ok(true));
+
+            let image_description =
+                TargetDescription::from_slice(b"foo", &[HashAlgorithm::Sha256]).unwrap();
+            assert_eq!(
+                client.fetch_target_description(&image_path).await.unwrap(),
+                image_description
+            );
+            assert_eq!(
+                client.database().target_description(&image_path).unwrap(),
+                image_description
+            );
+        })
+    }
+
+    #[test]
+    fn test_snapshot_merkle_tree() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let delegation_path = MetadataPath::new("delegation").unwrap();
+            let top_path = TargetPath::new("top").unwrap();
+            let foo_path = TargetPath::new("foo/bar").unwrap();
+
+            let raw_delegation = TargetsMetadataBuilder::new()
+                .insert_target_from_slice(foo_path.clone(), b"foo", &[HashAlgorithm::Sha256])
+                .unwrap()
+                .signed::<Pouf1>(&KEYS[1])
+                .unwrap()
+                .to_raw()
+                .unwrap();
+            let delegation_description = MetadataDescription::from_slice(
+                raw_delegation.as_bytes(),
+                1,
+                &[HashAlgorithm::Sha256],
+            )
+            .unwrap();
+
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .add_target(top_path.clone(), Cursor::new(b"top"))
+                .await
+                .unwrap()
+                .add_delegation_key(KEYS[1].public().clone())
+                .add_delegation_role(
+                    Delegation::builder(delegation_path.clone())
+                        .key(KEYS[1].public())
+                        .delegate_path(TargetPath::new("foo/").unwrap())
+                        .build()
+                        .unwrap(),
+                )
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot_with_builder(|builder| {
+                    builder.insert_metadata_description(
+                        delegation_path.clone(),
+                        delegation_description.clone(),
+                    )
+                })
+                .unwrap()
+                .timestamp_includes_merkle_root(true)
+                .commit()
+                .await
+                .unwrap();
+
+            remote
+                .store_metadata(
+                    &delegation_path,
+                    MetadataVersion::Number(1),
+                    &mut raw_delegation.as_bytes(),
+                )
+                .await
+                .unwrap();
+
+            let timestamp = metadata
+                .timestamp()
+                .unwrap()
+                .parse_untrusted()
+                .unwrap()
+                .assume_valid()
+                .unwrap();
+            assert!(timestamp.merkle_root().is_some());
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                TrackRepository::new(remote),
+            )
+            .await
+            .unwrap();
+
+            // The client verifies the targets metadata against its Merkle proof, without
+            // downloading the snapshot metadata.
+            assert_matches!(client.update().await, Ok(true));
+            assert_eq!(client.database().trusted_snapshot(), None);
+            assert_eq!(
+                client
+                    .database()
+                    .trusted_targets()
+                    .unwrap()
+                    .targets()
+                    .keys()
+                    .collect::<Vec<_>>(),
+                vec![&top_path]
+            );
+
+            // Delegated roles are verified the same way.
+            assert_eq!(
+                client.fetch_target_description(&foo_path).await.unwrap(),
+                TargetDescription::from_slice(b"foo", &[HashAlgorithm::Sha256]).unwrap()
+            );
+            assert_eq!(
+                client
+                    .database()
+                    .trusted_snapshot_description(&delegation_path),
+                Some(&delegation_description)
+            );
+
+            for track in client.remote_repo().take_tracks() {
+                if let Track::FetchFound { path, .. } | Track::FetchErr(path, _) = track {
+                    assert_ne!(path, MetadataPath::snapshot());
+                }
+            }
+
+            // The proofs are cached locally along with the targets metadata, apart from the
+            // metadata of any role.
+            client
+                .local_repo()
+                .fetch_snapshot_merkle_proof(&MetadataPath::targets())
+                .await
+                .unwrap();
+            assert_matches!(
+                fetch_metadata_to_string(
+                    client.local_repo(),
+                    &MetadataPath::new("targets-snapshot").unwrap(),
+                    MetadataVersion::None
+                )
+                .await,
+                Err(Error::MetadataNotFound { .. })
+            );
+        })
+    }
+
+    #[test]
+    fn test_snapshot_merkle_proof_must_match_timestamp() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .add_target(TargetPath::new("foo").unwrap(), Cursor::new(b"foo"))
+                .await
+                .unwrap()
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot()
+                .unwrap()
+                .timestamp_includes_merkle_root(true)
+                .commit()
+                .await
+                .unwrap();
+
+            // Replace the proof of the targets role with one from a different tree.
+            let targets_description = MetadataDescription::new(2, None, HashMap::new()).unwrap();
+            let targets_path = MetadataPath::targets();
+            let tree =
+                crate::merkle::SnapshotMerkleTree::new(once((&targets_path, &targets_description)))
+                    .unwrap();
+            let proof = serde_json::to_vec(&tree.proof(&targets_path).unwrap()).unwrap();
+            remote
+                .store_snapshot_merkle_proof(&targets_path, &mut proof.as_slice())
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+
+            assert_matches!(
+                client.update().await,
+                Err(Error::InvalidSnapshotMerkleProof { .. })
+            );
+        })
+    }
+
+    #[test]
+    fn test_fetch_delegated_target_description() {
+        block_on(async {
+            for terminating in [false, true] {
+                let mut remote = EphemeralRepository::<Pouf1>::new();
+                let delegation_path = MetadataPath::new("delegation").unwrap();
+                let foo_path = TargetPath::new("foo").unwrap();
+
+                let raw_delegation = TargetsMetadataBuilder::new()
+                    .insert_target_from_slice(foo_path.clone(), b"foo", &[HashAlgorithm::Sha256])
+                    .unwrap()
+                    .signed::<Pouf1>(&KEYS[1])
+                    .unwrap()
+                    .to_raw()
+                    .unwrap();
+                let delegation_description = MetadataDescription::from_slice(
+                    raw_delegation.as_bytes(),
+                    1,
+                    &[HashAlgorithm::Sha256],
+                )
+                .unwrap();
+
+                let metadata = RepoBuilder::create(&mut remote)
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .trusted_targets_keys(&[&KEYS[0]])
+                    .trusted_snapshot_keys(&[&KEYS[0]])
+                    .trusted_timestamp_keys(&[&KEYS[0]])
+                    .stage_root()
+                    .unwrap()
+                    .add_delegation_key(KEYS[1].public().clone())
+                    .add_delegation_role(
+                        Delegation::new(
+                            delegation_path.clone(),
+                            terminating,
+                            1,
+                            HashSet::from([KEYS[1].public().key_id().clone()]),
+                            HashSet::from([foo_path.clone()]),
+                        )
+                        .unwrap(),
+                    )
+                    .stage_targets()
+                    .unwrap()
+                    .stage_snapshot_with_builder(|builder| {
+                        builder.insert_metadata_description(
+                            delegation_path.clone(),
+                            delegation_description.clone(),
+                        )
+                    })
+                    .unwrap()
+                    .commit()
+                    .await
+                    .unwrap();
+
+                remote
+                    .store_metadata(
+                        &delegation_path,
+                        MetadataVersion::Number(1),
+                        &mut raw_delegation.as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+
+                let mut client = Client::with_trusted_root(
+                    Config::default(),
+                    metadata.root().unwrap(),
+                    EphemeralRepository::new(),
+                    remote,
+                )
+                .await
+                .unwrap();
+                assert_matches!(client.update().await, Ok(true));
+
+                // The target is found in the delegated role, whether or not the delegation is
+                // terminating.
+                let description = client
+                    .fetch_target_description(&foo_path)
+                    .await
+                    .unwrap_or_else(|err| panic!("terminating {}: {:?}", terminating, err));
+                assert_eq!(
+                    description,
+                    TargetDescription::from_slice(b"foo", &[HashAlgorithm::Sha256]).unwrap()
+                );
+                assert_eq!(
+                    client.database().target_description(&foo_path).unwrap(),
+                    description
+                );
+            }
+        })
+    }
+
+    #[test]
+    fn test_fetch_target_description_with_trace() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let other_path = MetadataPath::new("other").unwrap();
+            let delegation_path = MetadataPath::new("delegation").unwrap();
+            let foo_path = TargetPath::new("foo/bar").unwrap();
+
+            let raw_delegation = TargetsMetadataBuilder::new()
+                .insert_target_from_slice(foo_path.clone(), b"foo", &[HashAlgorithm::Sha256])
+                .unwrap()
+                .signed::<Pouf1>(&KEYS[1])
+                .unwrap()
+                .to_raw()
+                .unwrap();
+            let delegation_description = MetadataDescription::from_slice(
+                raw_delegation.as_bytes(),
+                1,
+                &[HashAlgorithm::Sha256],
+            )
+            .unwrap();
+
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .add_delegation_key(KEYS[1].public().clone())
+                // A terminating delegation is skipped if it isn't trusted with the target.
+                .add_delegation_role(
+                    Delegation::new(
+                        other_path.clone(),
+                        true,
+                        1,
+                        HashSet::from([KEYS[1].public().key_id().clone()]),
+                        HashSet::from([TargetPath::new("bar/").unwrap()]),
+                    )
+                    .unwrap(),
+                )
+                .add_delegation_role(
+                    Delegation::builder(delegation_path.clone())
+                        .key(KEYS[1].public())
+                        .delegate_path(TargetPath::new("foo/").unwrap())
+                        .build()
+                        .unwrap(),
+                )
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot_with_builder(|builder| {
+                    builder.insert_metadata_description(
+                        delegation_path.clone(),
+                        delegation_description.clone(),
+                    )
+                })
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            remote
+                .store_metadata(
+                    &delegation_path,
+                    MetadataVersion::Number(1),
+                    &mut raw_delegation.as_bytes(),
+                )
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+            assert_matches!(client.update().await, Ok(true));
+
+            let (description, trace) = client.fetch_target_description_with_trace(&foo_path).await;
+            assert_eq!(
+                description.unwrap(),
+                client.database().target_description(&foo_path).unwrap()
+            );
+            assert_eq!(trace.resolved_by(), Some(&delegation_path));
+            assert_eq!(
+                trace
+                    .steps()
+                    .iter()
+                    .map(|step| (step.role().clone(), step.depth(), step.outcome()))
+                    .collect::<Vec<_>>(),
+                vec![
+                    (MetadataPath::targets(), 0, DelegationOutcome::NotFound),
+                    (other_path.clone(), 1, DelegationOutcome::PathMismatch),
+                    (delegation_path.clone(), 1, DelegationOutcome::Found),
+                ]
+            );
+
+            // A target that no role describes is not resolved, but the trace is still returned.
+            let missing_path = TargetPath::new("missing").unwrap();
+            let (description, trace) = client
+                .fetch_target_description_with_trace(&missing_path)
+                .await;
+            assert_matches!(description, Err(Error::TargetNotFound(p)) if p == missing_path);
+            assert_eq!(trace.resolved_by(), None);
+            assert_eq!(
+                trace
+                    .steps()
+                    .iter()
+                    .map(|step| step.outcome())
+                    .collect::<Vec<_>>(),
+                vec![
+                    DelegationOutcome::NotFound,
+                    DelegationOutcome::PathMismatch,
+                    DelegationOutcome::PathMismatch,
+                ]
+            );
+        })
+    }
+
+    #[test]
+    fn test_delegation_traversal_limits() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let delegation_path = MetadataPath::new("delegation").unwrap();
+            let foo_path = TargetPath::new("foo/bar").unwrap();
+
+            let raw_delegation = TargetsMetadataBuilder::new()
+                .insert_target_from_slice(foo_path.clone(), b"foo", &[HashAlgorithm::Sha256])
+                .unwrap()
+                .signed::<Pouf1>(&KEYS[1])
+                .unwrap()
+                .to_raw()
+                .unwrap();
+            let delegation_description = MetadataDescription::from_slice(
+                raw_delegation.as_bytes(),
+                1,
+                &[HashAlgorithm::Sha256],
+            )
+            .unwrap();
+
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .add_delegation_key(KEYS[1].public().clone())
+                .add_delegation_role(
+                    Delegation::builder(delegation_path.clone())
+                        .key(KEYS[1].public())
+                        .delegate_path(TargetPath::new("foo/").unwrap())
+                        .build()
+                        .unwrap(),
+                )
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot_with_builder(|builder| {
+                    builder.insert_metadata_description(
+                        delegation_path.clone(),
+                        delegation_description.clone(),
+                    )
+                })
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            remote
+                .store_metadata(
+                    &delegation_path,
+                    MetadataVersion::Number(1),
+                    &mut raw_delegation.as_bytes(),
+                )
+                .await
+                .unwrap();
+
+            let new_client = |config| {
+                Client::with_trusted_root(
+                    config,
+                    metadata.root().unwrap(),
+                    EphemeralRepository::new(),
                     &remote,
                 )
-                .await,
-                Err(Error::MetadataNotFound { path, version })
-                if path == MetadataPath::root() && version == MetadataVersion::Number(1)
-            );
+            };
+
+            let config = Config::build().max_delegation_depth(0).finish().unwrap();
+            let mut client = new_client(config).await.unwrap();
+            assert_matches!(client.update().await, Ok(true));
+            assert_matches!(
+                client.fetch_target_description(&foo_path).await,
+                Err(Error::MaxDelegationDepthExceeded(0))
+            );
+            assert_matches!(
+                client.trusted_targets_iter().await.map(|_| ()),
+                Err(Error::MaxDelegationDepthExceeded(0))
+            );
+
+            let config = Config::build().max_visited_roles(0).finish().unwrap();
+            let mut client = new_client(config).await.unwrap();
+            assert_matches!(client.update().await, Ok(true));
+            assert_matches!(
+                client.fetch_target_description(&foo_path).await,
+                Err(Error::MaxVisitedRolesExceeded(0))
+            );
+            assert_matches!(
+                client.trusted_targets_iter().await.map(|_| ()),
+                Err(Error::MaxVisitedRolesExceeded(0))
+            );
+
+            // The default limits are large enough for this repository.
+            let mut client = new_client(Config::default()).await.unwrap();
+            assert_matches!(client.update().await, Ok(true));
+            assert_matches!(client.fetch_target_description(&foo_path).await, Ok(_));
+        })
+    }
+
+    #[test]
+    fn update_eventually_succeeds_if_cannot_write_to_repo() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+
+            // First, create the metadata.
+            let _ = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            // Now, make sure that the local metadata got version 1.
+            let local = ErrorRepository::new(EphemeralRepository::new());
+            let mut client = Client::with_trusted_root_keys(
+                Config::default(),
+                MetadataVersion::Number(1),
+                1,
+                once(&KEYS[0].public().clone()),
+                local,
+                remote,
+            )
+            .await
+            .unwrap();
+
+            // The first update should succeed.
+            assert_matches!(client.update().await, Ok(true));
+
+            // Make sure the database is correct.
+            let mut parts = client.into_parts();
+            assert_eq!(parts.database.trusted_root().version(), 1);
+            assert_eq!(parts.database.trusted_timestamp().unwrap().version(), 1);
+            assert_eq!(parts.database.trusted_snapshot().unwrap().version(), 1);
+            assert_eq!(parts.database.trusted_targets().unwrap().version(), 1);
+
+            // Publish new metadata.
+            let _ = RepoBuilder::create(&mut parts.remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root_with_builder(|bld| bld.version(2))
+                .unwrap()
+                .stage_targets_with_builder(|bld| bld.version(2))
+                .unwrap()
+                .stage_snapshot_with_builder(|bld| bld.version(2))
+                .unwrap()
+                .stage_timestamp_with_builder(|bld| bld.version(2))
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            // Make sure we fail to write metadata to the local store.
+            parts.local.fail_metadata_stores(true);
+
+            // The second update should fail.
+            let mut client = Client::from_parts(parts);
+            assert_matches!(client.update().await, Err(Error::Encoding(_)));
+
+            // FIXME(#297): rust-tuf diverges from the spec by throwing away the
+            // metadata if the root is updated.
+            assert_eq!(client.database().trusted_root().version(), 2);
+            assert_eq!(client.database().trusted_timestamp(), None);
+            assert_eq!(client.database().trusted_snapshot(), None);
+            assert_eq!(client.database().trusted_targets(), None);
+
+            // However, due to https://github.com/theupdateframework/specification/issues/131, if
+            // the update is retried a few times it will still succeed.
+            assert_matches!(client.update().await, Err(Error::Encoding(_)));
+            assert_eq!(client.database().trusted_root().version(), 2);
+            assert_eq!(client.database().trusted_timestamp().unwrap().version(), 2);
+            assert_eq!(client.database().trusted_snapshot(), None);
+            assert_eq!(client.database().trusted_targets(), None);
+
+            assert_matches!(client.update().await, Err(Error::Encoding(_)));
+            assert_eq!(client.database().trusted_root().version(), 2);
+            assert_eq!(client.database().trusted_timestamp().unwrap().version(), 2);
+            assert_eq!(client.database().trusted_snapshot().unwrap().version(), 2);
+            assert_eq!(client.database().trusted_targets(), None);
+
+            assert_matches!(client.update().await, Err(Error::Encoding(_)));
+            assert_eq!(client.database().trusted_root().version(), 2);
+            assert_eq!(client.database().trusted_timestamp().unwrap().version(), 2);
+            assert_eq!(client.database().trusted_snapshot().unwrap().version(), 2);
+            assert_eq!(client.database().trusted_targets().unwrap().version(), 2);
+
+            assert_matches!(client.update().await, Ok(false));
+            assert_eq!(client.database().trusted_root().version(), 2);
+            assert_eq!(client.database().trusted_timestamp().unwrap().version(), 2);
+            assert_eq!(client.database().trusted_snapshot().unwrap().version(), 2);
+            assert_eq!(client.database().trusted_targets().unwrap().version(), 2);
+        });
+    }
+
+    #[test]
+    fn test_fetch_targets() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+
+            let target_path1 = TargetPath::new("foo/bar").unwrap();
+            let target_path2 = TargetPath::new("baz").unwrap();
+            let missing_path = TargetPath::new("missing").unwrap();
+
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target(target_path1.clone(), Cursor::new(&b"target 1"[..]))
+                .await
+                .unwrap()
+                .add_target(target_path2.clone(), Cursor::new(&b"target 2"[..]))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
+
+            let results = client
+                .fetch_targets(
+                    &[
+                        target_path1.clone(),
+                        missing_path.clone(),
+                        target_path2.clone(),
+                    ],
+                    2,
+                )
+                .await;
+
+            assert_eq!(results.len(), 3);
+            assert_eq!(results[0].as_ref().unwrap(), b"target 1");
+            assert_matches!(&results[1], Err(Error::TargetNotFound(p)) if p == &missing_path);
+            assert_eq!(results[2].as_ref().unwrap(), b"target 2");
+
+            // Corrupt one of the targets in the remote repository.
+            let target_description = client
+                .fetch_target_description(&target_path2)
+                .await
+                .unwrap();
+            let (_, hash) = crypto::retain_supported_hashes(target_description.hashes())
+                .pop()
+                .unwrap();
+            client
+                .remote_repo_mut()
+                .store_target(
+                    &target_path2.with_hash_prefix(&hash).unwrap(),
+                    &mut &b"target 3"[..],
+                )
+                .await
+                .unwrap();
+
+            let results = client.fetch_targets(&[target_path1, target_path2], 0).await;
+            assert_eq!(results[0].as_ref().unwrap(), b"target 1");
+            assert_matches!(&results[1], Err(_));
         })
     }
 
     #[test]
-    fn client_constructors_err_with_invalid_keys() {
+    fn test_fetch_unverifiable_target() {
         block_on(async {
             let mut remote = EphemeralRepository::<Pouf1>::new();
+            let target_path = TargetPath::new("foo").unwrap();
+            let target_file: &[u8] = b"a target without a length or hashes";
 
-            let good_private_key = &KEYS[0];
-            let bad_private_key = &KEYS[1];
-
-            let _ = RepoBuilder::create(&mut remote)
-                .trusted_root_keys(&[good_private_key])
-                .trusted_targets_keys(&[good_private_key])
-                .trusted_snapshot_keys(&[good_private_key])
-                .trusted_timestamp_keys(&[good_private_key])
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .stage_targets_with_builder(|bld| {
+                    bld.insert_target_description(
+                        target_path.clone(),
+                        TargetDescription::new(None, HashMap::new(), HashMap::new()).unwrap(),
+                    )
+                })
+                .unwrap()
                 .commit()
                 .await
                 .unwrap();
 
+            remote
+                .store_target(&target_path, &mut &*target_file)
+                .await
+                .unwrap();
+
+            // Unverifiable targets are not fetched by default.
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                &remote,
+            )
+            .await
+            .unwrap();
+            assert_matches!(client.update().await, Ok(true));
             assert_matches!(
-                Client::with_trusted_root_keys(
-                    Config::default(),
-                    MetadataVersion::Number(1),
-                    1,
-                    once(bad_private_key.public()),
-                    EphemeralRepository::new(),
-                    &remote,
-                )
-                .await,
-                Err(Error::MetadataMissingSignatures { role, number_of_valid_signatures: 0, threshold: 1 })
-                if role == MetadataPath::root()
+                client.fetch_target(&target_path).await.map(|_| ()),
+                Err(Error::UnverifiableTarget(p)) if p == target_path
+            );
+
+            // Unless the client allows them.
+            let mut client = Client::with_trusted_root(
+                Config::build()
+                    .allow_unverifiable_targets(true)
+                    .finish()
+                    .unwrap(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                &remote,
+            )
+            .await
+            .unwrap();
+            assert_matches!(client.update().await, Ok(true));
+
+            let mut reader = client.fetch_target(&target_path).await.unwrap();
+            assert_eq!(reader.length(), None);
+            let mut buf = vec![];
+            reader.read_to_end(&mut buf).await.unwrap();
+            assert_eq!(buf, target_file);
+            drop(reader);
+
+            // The database cannot verify the target either.
+            assert_matches!(
+                client
+                    .database()
+                    .verify_target(&target_path, target_file)
+                    .await,
+                Err(Error::UnverifiableTarget(p)) if p == target_path
             );
         })
     }
 
     #[test]
-    fn with_trusted_local_loads_metadata_from_local_repo() {
-        block_on(constructors_load_metadata_from_local_repo(
-            ConstructorMode::WithTrustedLocal,
-        ))
+    fn test_fetch_target_reports_progress() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let target_path = TargetPath::new("foo").unwrap();
+            let target_file: &[u8] = b"things fade, alternatives exclude";
+
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target(target_path.clone(), Cursor::new(target_file))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
+
+            let progress = Arc::new(std::sync::Mutex::new(vec![]));
+            let progress_clone = Arc::clone(&progress);
+            client.set_target_fetch_progress(
+                move |path: &TargetPath, bytes_read: u64, expected_length: u64| {
+                    progress_clone.lock().unwrap().push((
+                        path.clone(),
+                        bytes_read,
+                        expected_length,
+                    ));
+                },
+            );
+
+            let mut buf = Vec::new();
+            client
+                .fetch_target(&target_path)
+                .await
+                .unwrap()
+                .read_to_end(&mut buf)
+                .await
+                .unwrap();
+            assert_eq!(buf, target_file);
+
+            let length = target_file.len() as u64;
+            let progress = progress.lock().unwrap();
+            assert!(!progress.is_empty());
+            assert!(progress
+                .iter()
+                .all(|(path, bytes_read, expected_length)| path == &target_path
+                    && *bytes_read <= length
+                    && *expected_length == length));
+            assert_eq!(progress.last(), Some(&(target_path, length, length)));
+        })
     }
 
     #[test]
-    fn with_trusted_root_loads_metadata_from_local_repo() {
-        block_on(constructors_load_metadata_from_local_repo(
-            ConstructorMode::WithTrustedRoot,
-        ))
+    fn test_fetch_target_to_path() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let target_path = TargetPath::new("foo").unwrap();
+            let target_file: &[u8] = b"a fine day to install things";
+
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target(target_path.clone(), Cursor::new(target_file))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
+
+            let dest = temp_dir.path().join("installed");
+            client
+                .fetch_target_to_path(&target_path, &dest)
+                .await
+                .unwrap();
+            assert_eq!(std::fs::read(&dest).unwrap(), target_file);
+
+            // Corrupt the target in the remote repository.
+            let target_description = client.fetch_target_description(&target_path).await.unwrap();
+            let (_, hash) = crypto::retain_supported_hashes(target_description.hashes())
+                .pop()
+                .unwrap();
+            client
+                .remote_repo_mut()
+                .store_target(
+                    &target_path.with_hash_prefix(&hash).unwrap(),
+                    &mut &b"a fine day to install malware"[..],
+                )
+                .await
+                .unwrap();
+
+            // The installed file is left untouched, and no temporary files are left behind.
+            assert_matches!(
+                client.fetch_target_to_path(&target_path, &dest).await,
+                Err(Error::IoPath { .. })
+            );
+            assert_eq!(std::fs::read(&dest).unwrap(), target_file);
+            assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+        })
     }
 
     #[test]
-    fn with_trusted_root_keys_loads_metadata_from_local_repo() {
-        block_on(constructors_load_metadata_from_local_repo(
-            ConstructorMode::WithTrustedRootKeys,
-        ))
+    fn test_fetch_target_into_seekable() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let target_path = TargetPath::new("foo").unwrap();
+            let target_file: &[u8] = b"0123456789abcdef";
+
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target(target_path.clone(), Cursor::new(target_file))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
+
+            let expected_description = client.fetch_target_description(&target_path).await.unwrap();
+
+            let reader = client.fetch_target(&target_path).await.unwrap();
+            assert_eq!(reader.length(), Some(target_file.len() as u64));
+            assert_eq!(reader.target_description(), &expected_description);
+
+            let mut seekable = reader.into_seekable().await.unwrap();
+            assert_eq!(seekable.target_description(), &expected_description);
+
+            let mut buf = [0; 4];
+            seekable.seek(SeekFrom::Start(10)).await.unwrap();
+            seekable.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"abcd");
+
+            seekable.seek(SeekFrom::Start(2)).await.unwrap();
+            seekable.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"2345");
+
+            // Corrupt the target in the remote repository, which should fail verification.
+            let (_, hash) = crypto::retain_supported_hashes(expected_description.hashes())
+                .pop()
+                .unwrap();
+            client
+                .remote_repo_mut()
+                .store_target(
+                    &target_path.with_hash_prefix(&hash).unwrap(),
+                    &mut &b"0123456789abcdeF"[..],
+                )
+                .await
+                .unwrap();
+
+            let reader = client.fetch_target(&target_path).await.unwrap();
+            assert_matches!(reader.into_seekable().await, Err(Error::Io(_)));
+        })
     }
 
     #[test]
-    fn from_database_loads_metadata_from_local_repo() {
-        block_on(constructors_load_metadata_from_local_repo(
-            ConstructorMode::FromDatabase,
-        ))
-    }
+    fn test_clock_is_used_for_expiration() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
 
-    async fn constructors_load_metadata_from_local_repo(constructor_mode: ConstructorMode) {
-        // Store an expired root in the local store.
-        let mut local = EphemeralRepository::<Pouf1>::new();
-        let metadata1 = RepoBuilder::create(&mut local)
-            .current_time(Utc.timestamp_opt(0, 0).unwrap())
-            .trusted_root_keys(&[&KEYS[0]])
-            .trusted_targets_keys(&[&KEYS[0]])
-            .trusted_snapshot_keys(&[&KEYS[0]])
-            .trusted_timestamp_keys(&[&KEYS[0]])
-            .stage_root_with_builder(|bld| bld.consistent_snapshot(true))
-            .unwrap()
-            .commit()
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
             .await
             .unwrap();
 
-        // Remote repo has unexpired metadata.
-        let mut remote = EphemeralRepository::<Pouf1>::new();
-        let metadata2 = RepoBuilder::create(&mut remote)
-            .trusted_root_keys(&[&KEYS[0]])
-            .trusted_targets_keys(&[&KEYS[0]])
-            .trusted_snapshot_keys(&[&KEYS[0]])
-            .trusted_timestamp_keys(&[&KEYS[0]])
-            .stage_root_with_builder(|bld| bld.version(2).consistent_snapshot(true))
-            .unwrap()
-            .stage_targets_with_builder(|bld| bld.version(2))
-            .unwrap()
-            .stage_snapshot_with_builder(|bld| bld.version(2))
-            .unwrap()
-            .stage_timestamp_with_builder(|bld| bld.version(2))
-            .unwrap()
-            .commit()
+            // The timestamp expires in a day, so it should be rejected by a clock that is a week
+            // in the future.
+            let future = Utc::now() + chrono::Duration::weeks(1);
+            client.set_clock(move || future);
+            assert_eq!(client.database().clock().now(), future);
+            assert_matches!(client.update().await, Err(Error::ExpiredMetadata { .. }));
+
+            client.set_clock(crate::clock::SystemClock);
+            assert_matches!(client.update().await, Ok(true));
+        })
+    }
+
+    #[test]
+    fn test_fetch_root_chain() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let metadata1 = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata1.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
             .await
             .unwrap();
+            assert_matches!(client.update().await, Ok(true));
+
+            // Rotate the root key.
+            let mut parts = client.into_parts();
+            let _ = RepoBuilder::from_database(&mut parts.remote, &parts.database)
+                .signing_root_keys(&[&KEYS[0]])
+                .trusted_root_keys(&[&KEYS[1]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
 
-        // Now, make sure that the local metadata got version 1.
-        let track_local = TrackRepository::new(local);
-        let track_remote = TrackRepository::new(remote);
+            let mut client = Client::from_parts(parts);
+            assert_matches!(client.update().await, Ok(true));
+            assert_eq!(client.database().trusted_root().version(), 2);
 
-        // Make sure the client initialized metadata in the right order. Each has a slightly
-        // different usage of the local repository.
-        let mut client = match constructor_mode {
-            ConstructorMode::WithTrustedLocal => {
-                Client::with_trusted_local(Config::default(), track_local, track_remote)
-                    .await
-                    .unwrap()
-            }
-            ConstructorMode::WithTrustedRoot => Client::with_trusted_root(
+            let chain = client.fetch_root_chain().await.unwrap();
+            assert_eq!(
+                chain.iter().map(|link| link.version()).collect::<Vec<_>>(),
+                vec![1, 2]
+            );
+            assert_eq!(chain[0].raw_root(), metadata1.root().unwrap());
+            assert_eq!(chain[0].diff(), None);
+            assert_eq!(chain[1].root(), client.database().trusted_root());
+
+            let diff = chain[1].diff().unwrap();
+            assert_eq!(diff.version().before(), &1);
+            assert_eq!(diff.version().after(), &2);
+            assert_eq!(diff.root().threshold(), None);
+            assert_eq!(
+                diff.root().added_key_ids(),
+                &once(KEYS[1].public().key_id().clone()).collect()
+            );
+            assert_eq!(
+                diff.root().removed_key_ids(),
+                &once(KEYS[0].public().key_id().clone()).collect()
+            );
+            assert!(diff.snapshot().is_empty());
+
+            // A chain that does not end with the trusted root is rejected.
+            let mut other_remote = EphemeralRepository::<Pouf1>::new();
+            let _ = RepoBuilder::create(&mut other_remote)
+                .trusted_root_keys(&[&KEYS[2]])
+                .trusted_targets_keys(&[&KEYS[2]])
+                .trusted_snapshot_keys(&[&KEYS[2]])
+                .trusted_timestamp_keys(&[&KEYS[2]])
+                .commit()
+                .await
+                .unwrap();
+
+            let client = Client::with_trusted_root(
                 Config::default(),
                 metadata1.root().unwrap(),
-                track_local,
-                track_remote,
+                EphemeralRepository::new(),
+                other_remote,
             )
             .await
-            .unwrap(),
-            ConstructorMode::WithTrustedRootKeys => Client::with_trusted_root_keys(
+            .unwrap();
+            assert_matches!(
+                client.fetch_root_chain().await,
+                Err(Error::RootChainMismatch(1))
+            );
+        })
+    }
+
+    #[test]
+    fn test_rollback_state() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            // A client with an empty local repository records the versions it trusts.
+            let mut client = Client::with_trusted_root(
                 Config::default(),
-                MetadataVersion::Number(1),
-                1,
-                once(&KEYS[0].public().clone()),
-                track_local,
-                track_remote,
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                &remote,
             )
             .await
-            .unwrap(),
-            ConstructorMode::FromDatabase => Client::from_database(
-                Config::default(),
-                Database::from_trusted_root(metadata1.root().unwrap()).unwrap(),
-                track_local,
-                track_remote,
-            ),
-        };
-
-        assert_eq!(client.tuf.trusted_root().version(), 1);
-        assert_eq!(client.remote_repo().take_tracks(), vec![]);
+            .unwrap();
+            client.set_rollback_state(EphemeralRollbackState::new());
+            assert_matches!(client.update().await, Ok(true));
 
-        // According to [1], "Check for freeze attack", only the root should be
-        // fetched since it has expired.
-        //
-        // [1]: https://theupdateframework.github.io/specification/latest/#update-root
-        match constructor_mode {
-            ConstructorMode::WithTrustedLocal => {
-                assert_eq!(
-                    client.local_repo().take_tracks(),
-                    vec![
-                        Track::fetch_meta_found(
-                            MetadataVersion::Number(1),
-                            metadata1.root().unwrap()
-                        ),
-                        Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(2)),
-                    ],
-                );
-            }
-            ConstructorMode::WithTrustedRoot => {
-                assert_eq!(
-                    client.local_repo().take_tracks(),
-                    vec![Track::FetchErr(
-                        MetadataPath::root(),
-                        MetadataVersion::Number(2)
-                    )],
-                );
+            let rollback_state = client.database().rollback_state().unwrap();
+            for path in [
+                MetadataPath::root(),
+                MetadataPath::timestamp(),
+                MetadataPath::snapshot(),
+                MetadataPath::targets(),
+            ] {
+                assert_eq!(rollback_state.highest_version(&path).unwrap(), Some(1));
             }
-            ConstructorMode::WithTrustedRootKeys => {
-                assert_eq!(
-                    client.local_repo().take_tracks(),
-                    vec![
-                        Track::fetch_meta_found(
-                            MetadataVersion::Number(1),
-                            metadata1.root().unwrap()
-                        ),
-                        Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(2)),
-                    ],
+
+            // A client that previously trusted newer metadata rejects it, even though its local
+            // repository is empty.
+            for path in [
+                MetadataPath::root(),
+                MetadataPath::timestamp(),
+                MetadataPath::snapshot(),
+                MetadataPath::targets(),
+            ] {
+                let rollback_state = EphemeralRollbackState::new();
+                rollback_state.record_version(&path, 2).unwrap();
+
+                let mut client = Client::with_trusted_root(
+                    Config::default(),
+                    metadata.root().unwrap(),
+                    EphemeralRepository::new(),
+                    &remote,
+                )
+                .await
+                .unwrap();
+                client.set_rollback_state(rollback_state);
+
+                assert_matches!(
+                    client.update().await,
+                    Err(Error::AttemptedMetadataRollBack {
+                        role,
+                        trusted_version: 2,
+                        new_version: 1,
+                    }) if role == path
                 );
             }
-            ConstructorMode::FromDatabase => {
-                assert_eq!(client.local_repo().take_tracks(), vec![],);
-            }
-        };
-
-        assert_matches!(client.update().await, Ok(true));
-        assert_eq!(client.tuf.trusted_root().version(), 2);
+        })
+    }
 
-        // We should only fetch metadata from the remote repository and write it to the local
-        // repository.
-        assert_eq!(
-            client.remote_repo().take_tracks(),
-            vec![
-                Track::fetch_meta_found(MetadataVersion::Number(2), metadata2.root().unwrap()),
-                Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(3)),
-                Track::fetch_meta_found(MetadataVersion::None, metadata2.timestamp().unwrap()),
-                Track::fetch_meta_found(MetadataVersion::Number(2), metadata2.snapshot().unwrap()),
-                Track::fetch_meta_found(MetadataVersion::Number(2), metadata2.targets().unwrap()),
-            ],
-        );
-        assert_eq!(
-            client.local_repo().take_tracks(),
-            vec![
-                Track::store_meta(MetadataVersion::None, metadata2.root().unwrap()),
-                Track::store_meta(MetadataVersion::Number(2), metadata2.root().unwrap()),
-                Track::store_meta(MetadataVersion::None, metadata2.timestamp().unwrap()),
-                Track::store_meta(MetadataVersion::None, metadata2.snapshot().unwrap()),
-                Track::store_meta(MetadataVersion::None, metadata2.targets().unwrap()),
-            ],
-        );
+    #[test]
+    fn test_expiration_grace_period() {
+        block_on(async {
+            // The timestamp expires in a day, so with a clock two days in the future it is only
+            // accepted if the grace period is longer than a day.
+            let future = Utc::now() + Duration::days(2);
+
+            for (grace_period, succeeds) in [
+                (Duration::zero(), false),
+                (Duration::hours(12), false),
+                (Duration::days(3), true),
+            ] {
+                let mut remote = EphemeralRepository::<Pouf1>::new();
+                let metadata = RepoBuilder::create(&mut remote)
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .trusted_targets_keys(&[&KEYS[0]])
+                    .trusted_snapshot_keys(&[&KEYS[0]])
+                    .trusted_timestamp_keys(&[&KEYS[0]])
+                    .commit()
+                    .await
+                    .unwrap();
 
-        // Another update should not fetch anything.
-        assert_matches!(client.update().await, Ok(false));
-        assert_eq!(client.tuf.trusted_root().version(), 2);
+                let config = Config::build()
+                    .expiration_grace_period(grace_period)
+                    .finish()
+                    .unwrap();
 
-        // Make sure we only fetched the next root and timestamp, and didn't store anything.
-        assert_eq!(
-            client.remote_repo().take_tracks(),
-            vec![
-                Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(3)),
-                Track::fetch_meta_found(MetadataVersion::None, metadata2.timestamp().unwrap()),
-            ]
-        );
-        assert_eq!(client.local_repo().take_tracks(), vec![]);
+                let mut client = Client::with_trusted_root(
+                    config,
+                    metadata.root().unwrap(),
+                    EphemeralRepository::new(),
+                    remote,
+                )
+                .await
+                .unwrap();
+                assert_eq!(client.database().expiration_grace_period(), grace_period);
+
+                client.set_clock(move || future);
+                if succeeds {
+                    assert_matches!(client.update().await, Ok(true));
+                    assert_matches!(
+                        client
+                            .fetch_target_description(&TargetPath::new("missing").unwrap())
+                            .await,
+                        Err(Error::TargetNotFound(_))
+                    );
+                } else {
+                    assert_matches!(
+                        client.update().await,
+                        Err(Error::ExpiredMetadata { path, .. }) if path == MetadataPath::timestamp()
+                    );
+                }
+            }
+        })
     }
 
     #[test]
-    fn constructor_succeeds_with_missing_metadata() {
+    fn test_verify_cached_target() {
         block_on(async {
-            let mut local = EphemeralRepository::<Pouf1>::new();
-            let remote = EphemeralRepository::<Pouf1>::new();
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let target_path = TargetPath::new("foo/bar").unwrap();
+            let target_file: &[u8] = b"installed artifact";
 
-            // Store only a root in the local store.
-            let metadata1 = RepoBuilder::create(&mut local)
+            let metadata = RepoBuilder::create(&mut remote)
                 .trusted_root_keys(&[&KEYS[0]])
                 .trusted_targets_keys(&[&KEYS[0]])
                 .trusted_snapshot_keys(&[&KEYS[0]])
                 .trusted_timestamp_keys(&[&KEYS[0]])
-                .stage_root_with_builder(|bld| bld.consistent_snapshot(true))
+                .add_target(target_path.clone(), Cursor::new(target_file))
+                .await
                 .unwrap()
-                .skip_targets()
-                .skip_snapshot()
-                .skip_timestamp()
                 .commit()
                 .await
                 .unwrap();
 
-            let track_local = TrackRepository::new(local);
-            let track_remote = TrackRepository::new(remote);
-
-            // Create a client, which should try to fetch metadata from the local store.
-            let client = Client::with_trusted_root(
+            let mut client = Client::with_trusted_root(
                 Config::default(),
-                metadata1.root().unwrap(),
-                track_local,
-                track_remote,
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                TrackRepository::new(remote),
             )
             .await
             .unwrap();
+            client.update().await.unwrap();
+            let _ = client.remote_repo().take_tracks();
 
-            assert_eq!(client.tuf.trusted_root().version(), 1);
+            let description = client
+                .verify_cached_target(&target_path, target_file)
+                .await
+                .unwrap();
+            assert_eq!(description.length(), Some(target_file.len() as u64));
 
-            // We shouldn't fetch metadata.
+            assert_matches!(
+                client
+                    .verify_cached_target(&target_path, &b"installed artifacT"[..])
+                    .await,
+                Err(Error::Io(_))
+            );
+            assert_matches!(
+                client
+                    .verify_cached_target(&target_path, &b"installed"[..])
+                    .await,
+                Err(Error::Io(_))
+            );
+            assert_matches!(
+                client
+                    .verify_cached_target(&target_path, &b"installed artifact!"[..])
+                    .await,
+                Err(Error::Io(_))
+            );
+            assert_matches!(
+                client
+                    .verify_cached_target(&TargetPath::new("missing").unwrap(), target_file)
+                    .await,
+                Err(Error::TargetNotFound(_))
+            );
+
+            // None of this touched the network.
             assert_eq!(client.remote_repo().take_tracks(), vec![]);
+        })
+    }
 
-            // We should have tried fetching a new timestamp, but it shouldn't exist in the
-            // repository.
-            assert_eq!(
-                client.local_repo().take_tracks(),
-                vec![
-                    Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(2)),
-                    Track::FetchErr(MetadataPath::timestamp(), MetadataVersion::None)
-                ],
-            );
+    #[test]
+    fn test_fetch_target_to_path_with_candidate() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let target_path = TargetPath::new("foo/bar").unwrap();
+            let target_file: &[u8] = b"large artifact";
 
-            // An update should succeed.
-            let mut parts = client.into_parts();
-            let metadata2 = RepoBuilder::create(parts.remote.as_inner_mut())
+            let metadata = RepoBuilder::create(&mut remote)
                 .trusted_root_keys(&[&KEYS[0]])
                 .trusted_targets_keys(&[&KEYS[0]])
                 .trusted_snapshot_keys(&[&KEYS[0]])
                 .trusted_timestamp_keys(&[&KEYS[0]])
-                .stage_root_with_builder(|bld| bld.version(2).consistent_snapshot(true))
-                .unwrap()
-                .stage_targets_with_builder(|bld| bld.version(2))
-                .unwrap()
-                .stage_snapshot_with_builder(|bld| bld.version(2))
-                .unwrap()
-                .stage_timestamp_with_builder(|bld| bld.version(2))
+                .add_target(target_path.clone(), Cursor::new(target_file))
+                .await
                 .unwrap()
                 .commit()
                 .await
                 .unwrap();
 
-            let mut client = Client::from_parts(parts);
-            assert_matches!(client.update().await, Ok(true));
-            assert_eq!(client.tuf.trusted_root().version(), 2);
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
+
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let candidate = temp_dir.path().join("candidate");
+            let dest = temp_dir.path().join("installed");
+
+            // A missing candidate falls back to downloading the target.
+            assert_matches!(
+                client
+                    .fetch_target_to_path_with_candidate(&target_path, &dest, &candidate)
+                    .await,
+                Ok(true)
+            );
+            assert_eq!(std::fs::read(&dest).unwrap(), target_file);
+
+            // So does a candidate that doesn't match, which is left untouched.
+            std::fs::write(&candidate, b"partial").unwrap();
+            std::fs::remove_file(&dest).unwrap();
+            assert_matches!(
+                client
+                    .fetch_target_to_path_with_candidate(&target_path, &dest, &candidate)
+                    .await,
+                Ok(true)
+            );
+            assert_eq!(std::fs::read(&dest).unwrap(), target_file);
+            assert_eq!(std::fs::read(&candidate).unwrap(), b"partial");
+
+            // Corrupt the target in the remote repository, so any download would fail.
+            let description = client.fetch_target_description(&target_path).await.unwrap();
+            let (_, hash) = crypto::retain_supported_hashes(description.hashes())
+                .pop()
+                .unwrap();
+            client
+                .remote_repo_mut()
+                .store_target(
+                    &target_path.with_hash_prefix(&hash).unwrap(),
+                    &mut &b"corrupted!!!!!"[..],
+                )
+                .await
+                .unwrap();
 
-            // We should have fetched the metadata, and written it to the local database.
-            assert_eq!(
-                client.remote_repo().take_tracks(),
-                vec![
-                    Track::fetch_meta_found(MetadataVersion::Number(2), metadata2.root().unwrap()),
-                    Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(3)),
-                    Track::fetch_meta_found(MetadataVersion::None, metadata2.timestamp().unwrap()),
-                    Track::fetch_meta_found(
-                        MetadataVersion::Number(2),
-                        metadata2.snapshot().unwrap()
-                    ),
-                    Track::fetch_meta_found(
-                        MetadataVersion::Number(2),
-                        metadata2.targets().unwrap()
-                    ),
-                ],
+            // A matching candidate is installed without downloading the target.
+            std::fs::write(&candidate, target_file).unwrap();
+            std::fs::remove_file(&dest).unwrap();
+            assert_matches!(
+                client
+                    .fetch_target_to_path_with_candidate(&target_path, &dest, &candidate)
+                    .await,
+                Ok(false)
             );
-            assert_eq!(
-                client.local_repo().take_tracks(),
-                vec![
-                    Track::store_meta(MetadataVersion::None, metadata2.root().unwrap()),
-                    Track::store_meta(MetadataVersion::Number(2), metadata2.root().unwrap()),
-                    Track::store_meta(MetadataVersion::None, metadata2.timestamp().unwrap()),
-                    Track::store_meta(MetadataVersion::None, metadata2.snapshot().unwrap()),
-                    Track::store_meta(MetadataVersion::None, metadata2.targets().unwrap()),
-                ],
+            assert_eq!(std::fs::read(&dest).unwrap(), target_file);
+
+            // A matching file that is already installed is left in place.
+            assert_matches!(
+                client
+                    .fetch_target_to_path_with_candidate(&target_path, &dest, &dest)
+                    .await,
+                Ok(false)
             );
+            assert_eq!(std::fs::read(&dest).unwrap(), target_file);
         })
     }
 
     #[test]
-    fn constructor_succeeds_with_expired_metadata() {
+    fn test_fetch_target_to_path_with_delta() {
         block_on(async {
-            let mut local = EphemeralRepository::<Pouf1>::new();
-            let remote = EphemeralRepository::<Pouf1>::new();
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let target_path = TargetPath::new("app").unwrap();
+            let patch_path = TargetPath::new("app.patch-from-1").unwrap();
+            let source_file: &[u8] = b"version 1";
+            let target_file: &[u8] = b"version 1, then 2";
+
+            let delta = delta::Delta::new(
+                crypto::calculate_hash(source_file, &HashAlgorithm::Sha256),
+                patch_path.clone(),
+            );
 
-            // Store an expired root in the local store.
-            let metadata1 = RepoBuilder::create(&mut local)
-                .current_time(Utc.timestamp_opt(0, 0).unwrap())
+            let metadata = RepoBuilder::create(&mut remote)
                 .trusted_root_keys(&[&KEYS[0]])
                 .trusted_targets_keys(&[&KEYS[0]])
                 .trusted_snapshot_keys(&[&KEYS[0]])
                 .trusted_timestamp_keys(&[&KEYS[0]])
-                .stage_root_with_builder(|bld| bld.version(1).consistent_snapshot(true))
+                .stage_root()
+                .unwrap()
+                .add_target_with_custom(
+                    target_path.clone(),
+                    Cursor::new(target_file),
+                    delta::deltas_custom(&[delta]),
+                )
+                .await
+                .unwrap()
+                .add_target(patch_path, Cursor::new(&b", then 2"[..]))
+                .await
                 .unwrap()
                 .commit()
                 .await
                 .unwrap();
 
-            let metadata2 = RepoBuilder::create(&mut local)
-                .current_time(Utc.timestamp_opt(0, 0).unwrap())
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
+
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let source = temp_dir.path().join("source");
+            let dest = temp_dir.path().join("installed");
+
+            // The patch is appended to the source.
+            let append =
+                |source: &[u8], patch: &[u8]| -> Result<Vec<u8>> { Ok([source, patch].concat()) };
+
+            // Without a source, the full target is fetched.
+            assert_matches!(
+                client
+                    .fetch_target_to_path_with_delta(&target_path, &dest, &source, &append)
+                    .await,
+                Ok(false)
+            );
+            assert_eq!(std::fs::read(&dest).unwrap(), target_file);
+
+            // With the source the delta was built against, the target is reconstructed.
+            std::fs::write(&source, source_file).unwrap();
+            std::fs::remove_file(&dest).unwrap();
+            assert_matches!(
+                client
+                    .fetch_target_to_path_with_delta(&target_path, &dest, &source, &append)
+                    .await,
+                Ok(true)
+            );
+            assert_eq!(std::fs::read(&dest).unwrap(), target_file);
+
+            // A delta that doesn't produce the target is discarded.
+            let corrupt = |_: &[u8], _: &[u8]| -> Result<Vec<u8>> { Ok(b"corrupt".to_vec()) };
+            std::fs::remove_file(&dest).unwrap();
+            assert_matches!(
+                client
+                    .fetch_target_to_path_with_delta(&target_path, &dest, &source, &corrupt)
+                    .await,
+                Ok(false)
+            );
+            assert_eq!(std::fs::read(&dest).unwrap(), target_file);
+        })
+    }
+
+    #[test]
+    fn test_check_for_updates() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+
+            let metadata1 = RepoBuilder::create(&mut remote)
                 .trusted_root_keys(&[&KEYS[0]])
                 .trusted_targets_keys(&[&KEYS[0]])
                 .trusted_snapshot_keys(&[&KEYS[0]])
                 .trusted_timestamp_keys(&[&KEYS[0]])
-                .stage_root_with_builder(|bld| bld.version(2).consistent_snapshot(true))
-                .unwrap()
-                .stage_targets_with_builder(|bld| bld.version(2))
-                .unwrap()
-                .stage_snapshot_with_builder(|bld| bld.version(2))
-                .unwrap()
-                .stage_timestamp_with_builder(|bld| bld.version(2))
-                .unwrap()
                 .commit()
                 .await
                 .unwrap();
 
-            // Now, make sure that the local metadata got version 1.
-            let track_local = TrackRepository::new(local);
-            let track_remote = TrackRepository::new(remote);
-
-            let client = Client::with_trusted_root(
+            let mut client = Client::with_trusted_root(
                 Config::default(),
                 metadata1.root().unwrap(),
-                track_local,
-                track_remote,
+                EphemeralRepository::new(),
+                TrackRepository::new(remote),
             )
             .await
             .unwrap();
 
-            assert_eq!(client.tuf.trusted_root().version(), 2);
-
-            // We shouldn't fetch metadata.
-            assert_eq!(client.remote_repo().take_tracks(), vec![]);
-
-            // We should only load the root metadata, but because it's expired we don't try
-            // fetching the other local metadata.
-            assert_eq!(
-                client.local_repo().take_tracks(),
-                vec![
-                    Track::fetch_meta_found(MetadataVersion::Number(2), metadata2.root().unwrap()),
-                    Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(3))
-                ],
-            );
+            // The targets metadata has not been fetched yet.
+            assert_matches!(client.check_for_updates().await, Ok(true));
+            assert_matches!(client.update().await, Ok(true));
+            assert_matches!(client.check_for_updates().await, Ok(false));
+            let _ = client.remote_repo().take_tracks();
 
-            // An update should succeed.
-            let mut parts = client.into_parts();
-            let _metadata3 = RepoBuilder::create(&mut parts.remote)
+            // Publish new targets metadata.
+            let database = client.database().clone();
+            let metadata2 = RepoBuilder::from_database(client.remote_repo_mut(), &database)
                 .trusted_root_keys(&[&KEYS[0]])
                 .trusted_targets_keys(&[&KEYS[0]])
                 .trusted_snapshot_keys(&[&KEYS[0]])
                 .trusted_timestamp_keys(&[&KEYS[0]])
-                .stage_root_with_builder(|bld| {
-                    bld.version(3)
-                        .consistent_snapshot(true)
-                        .expires(Utc.with_ymd_and_hms(2038, 1, 1, 0, 0, 0).unwrap())
-                })
-                .unwrap()
+                .skip_root()
                 .stage_targets_with_builder(|bld| bld.version(2))
                 .unwrap()
                 .stage_snapshot_with_builder(|bld| bld.version(2))
@@ -1781,32 +6767,41 @@ mod test {
                 .commit()
                 .await
                 .unwrap();
+            let _ = client.remote_repo().take_tracks();
+
+            // Checking for updates should not fetch the targets metadata.
+            assert_matches!(client.check_for_updates().await, Ok(true));
+            assert_eq!(
+                client.database().trusted_targets().map(|t| t.version()),
+                None
+            );
+            assert_eq!(
+                client.remote_repo().take_tracks(),
+                vec![
+                    Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(2)),
+                    Track::fetch_meta_found(MetadataVersion::None, metadata2.timestamp().unwrap()),
+                    Track::fetch_meta_found(
+                        MetadataVersion::Number(2),
+                        metadata2.snapshot().unwrap()
+                    ),
+                ],
+            );
 
-            let mut client = Client::from_parts(parts);
             assert_matches!(client.update().await, Ok(true));
-            assert_eq!(client.tuf.trusted_root().version(), 3);
+            assert_eq!(
+                client.database().trusted_targets().map(|t| t.version()),
+                Some(2)
+            );
+            assert_matches!(client.check_for_updates().await, Ok(false));
         })
     }
 
     #[test]
-    fn constructor_succeeds_with_malformed_metadata() {
+    fn test_with_trusted_root_bytes() {
         block_on(async {
-            // Store a malformed timestamp in the local repository.
-            let local = EphemeralRepository::<Pouf1>::new();
-            let junk_timestamp = "junk timestamp";
-
-            local
-                .store_metadata(
-                    &MetadataPath::timestamp(),
-                    MetadataVersion::None,
-                    &mut junk_timestamp.as_bytes(),
-                )
-                .await
-                .unwrap();
-
-            // Create a normal repository on the remote server.
             let mut remote = EphemeralRepository::<Pouf1>::new();
-            let metadata1 = RepoBuilder::create(&mut remote)
+
+            let metadata = RepoBuilder::create(&mut remote)
                 .trusted_root_keys(&[&KEYS[0]])
                 .trusted_targets_keys(&[&KEYS[0]])
                 .trusted_snapshot_keys(&[&KEYS[0]])
@@ -1815,365 +6810,436 @@ mod test {
                 .await
                 .unwrap();
 
-            // Create the client. It should ignore the malformed timestamp.
-            let track_local = TrackRepository::new(local);
-            let track_remote = TrackRepository::new(remote);
+            // Leak the bytes to mimic `include_bytes!`.
+            let bytes: &'static [u8] = Box::leak(
+                metadata
+                    .root()
+                    .unwrap()
+                    .as_bytes()
+                    .to_vec()
+                    .into_boxed_slice(),
+            );
+            let root = EmbeddedRoot::new(bytes);
+            assert_matches!(root.validate::<Pouf1>(), Ok(()));
 
-            let mut client = Client::with_trusted_root(
+            let mut client = Client::with_trusted_root_bytes(
                 Config::default(),
-                metadata1.root().unwrap(),
-                track_local,
-                track_remote,
+                root.as_bytes(),
+                EphemeralRepository::new(),
+                remote,
             )
             .await
             .unwrap();
+            assert_matches!(client.update().await, Ok(true));
 
-            assert_eq!(client.tuf.trusted_root().version(), 1);
-
-            // We shouldn't fetch metadata.
-            assert_eq!(client.remote_repo().take_tracks(), vec![]);
-
-            // We should only load the root metadata, but because it's expired we don't try
-            // fetching the other local metadata.
-            assert_eq!(
-                client.local_repo().take_tracks(),
-                vec![
-                    Track::FetchErr(MetadataPath::root(), MetadataVersion::Number(2)),
-                    Track::FetchFound {
-                        path: MetadataPath::timestamp(),
-                        version: MetadataVersion::None,
-                        metadata: junk_timestamp.into(),
-                    },
-                ],
+            // Garbage and roots that are not signed by their own keys are rejected before any
+            // repository is accessed.
+            assert_matches!(
+                Client::with_trusted_root_bytes(
+                    Config::default(),
+                    b"{}",
+                    EphemeralRepository::<Pouf1>::new(),
+                    EphemeralRepository::new(),
+                )
+                .await,
+                Err(Error::Json(_))
             );
 
-            // An update should work.
-            assert_matches!(client.update().await, Ok(true));
+            let unsigned_root = RootMetadataBuilder::new()
+                .root_key(KEYS[0].public().clone())
+                .snapshot_key(KEYS[0].public().clone())
+                .targets_key(KEYS[0].public().clone())
+                .timestamp_key(KEYS[0].public().clone())
+                .signed::<Pouf1>(&KEYS[1])
+                .unwrap()
+                .to_raw()
+                .unwrap();
+            let root = EmbeddedRoot::new(Box::leak(
+                unsigned_root.as_bytes().to_vec().into_boxed_slice(),
+            ));
+            assert_matches!(
+                root.validate::<Pouf1>(),
+                Err(Error::MetadataMissingSignatures { .. })
+            );
         })
     }
 
     #[test]
-    fn root_chain_update_consistent_snapshot_false() {
-        block_on(root_chain_update(false))
-    }
-
-    #[test]
-    fn root_chain_update_consistent_snapshot_true() {
-        block_on(root_chain_update(true))
-    }
-
-    async fn root_chain_update(consistent_snapshot: bool) {
-        let mut repo = EphemeralRepository::<Pouf1>::new();
-
-        // First, create the initial metadata. We want to use the same non-root
-        // metadata, so sign it with all the keys.
-        let metadata1 = RepoBuilder::create(&mut repo)
-            .trusted_root_keys(&[&KEYS[0]])
-            .signing_targets_keys(&[&KEYS[1], &KEYS[2]])
-            .trusted_targets_keys(&[&KEYS[0]])
-            .signing_snapshot_keys(&[&KEYS[1], &KEYS[2]])
-            .trusted_snapshot_keys(&[&KEYS[0]])
-            .signing_timestamp_keys(&[&KEYS[1], &KEYS[2]])
-            .trusted_timestamp_keys(&[&KEYS[0]])
-            .stage_root_with_builder(|bld| bld.consistent_snapshot(consistent_snapshot))
-            .unwrap()
-            .commit()
-            .await
-            .unwrap();
+    fn test_metadata_length_limits() {
+        block_on(async {
+            type Limit = fn(usize) -> MetadataLengthLimit;
+            let limits: &[(Limit, bool)] = &[
+                (MetadataLengthLimit::Bounded, true),
+                (|len| MetadataLengthLimit::Bounded(len - 1), false),
+                (
+                    |len| MetadataLengthLimit::UnboundedWithWarning(len - 1),
+                    true,
+                ),
+                (|_| MetadataLengthLimit::Unbounded, true),
+            ];
 
-        let root_path = MetadataPath::root();
-        let timestamp_path = MetadataPath::timestamp();
+            for (limit, succeeds) in limits {
+                let mut remote = EphemeralRepository::<Pouf1>::new();
+                let metadata = RepoBuilder::create(&mut remote)
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .trusted_targets_keys(&[&KEYS[0]])
+                    .trusted_snapshot_keys(&[&KEYS[0]])
+                    .trusted_timestamp_keys(&[&KEYS[0]])
+                    .add_target(TargetPath::new("foo").unwrap(), Cursor::new(&b"foo"[..]))
+                    .await
+                    .unwrap()
+                    .commit()
+                    .await
+                    .unwrap();
 
-        let targets_version;
-        let snapshot_version;
-        if consistent_snapshot {
-            targets_version = MetadataVersion::Number(1);
-            snapshot_version = MetadataVersion::Number(1);
-        } else {
-            targets_version = MetadataVersion::None;
-            snapshot_version = MetadataVersion::None;
-        };
+                // The snapshot doesn't list the length of the targets metadata, so it is limited
+                // by the config.
+                let limit = limit(metadata.targets().unwrap().as_bytes().len());
+                let config = Config::build().max_targets_length(limit).finish().unwrap();
+                assert_eq!(config.max_targets_length_limit(), &limit);
+                assert_eq!(config.max_targets_length(), &limit.max_length());
 
-        // Now, make sure that the local metadata got version 1.
-        let track_local = TrackRepository::new(EphemeralRepository::new());
-        let track_remote = TrackRepository::new(repo);
+                let mut client = Client::with_trusted_root(
+                    config,
+                    metadata.root().unwrap(),
+                    EphemeralRepository::new(),
+                    remote,
+                )
+                .await
+                .unwrap();
 
-        let mut client = Client::with_trusted_root_keys(
-            Config::default(),
-            MetadataVersion::Number(1),
-            1,
-            once(&KEYS[0].public().clone()),
-            track_local,
-            track_remote,
-        )
-        .await
-        .unwrap();
+                if *succeeds {
+                    assert_matches!(client.update().await, Ok(true), "{:?}", limit);
+                } else {
+                    assert_matches!(client.update().await, Err(Error::Io(_)), "{:?}", limit);
+                }
+            }
 
-        // Check that we tried to load metadata from the local repository.
-        assert_eq!(
-            client.remote_repo().take_tracks(),
-            vec![Track::fetch_found(
-                &root_path,
-                MetadataVersion::Number(1),
-                metadata1.root().unwrap().as_bytes()
-            ),]
+            let config = Config::build().max_root_length(None).finish().unwrap();
+            assert_eq!(
+                config.max_root_length_limit(),
+                &MetadataLengthLimit::Unbounded
+            );
+            assert_eq!(config.max_root_length(), &None);
+        })
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert_matches!(
+            Config::build()
+                .max_snapshot_length(MetadataLengthLimit::Bounded(0))
+                .finish(),
+            Err(Error::IllegalArgument(_))
         );
-        assert_eq!(
-            client.local_repo().take_tracks(),
-            vec![
-                Track::FetchErr(root_path.clone(), MetadataVersion::Number(1)),
-                Track::store_meta(MetadataVersion::Number(1), metadata1.root().unwrap()),
-                Track::FetchErr(root_path.clone(), MetadataVersion::Number(2)),
-                Track::FetchErr(timestamp_path.clone(), MetadataVersion::None),
-            ]
+        assert_matches!(
+            Config::build().max_root_rotations(0).finish(),
+            Err(Error::IllegalArgument(_))
         );
-
-        assert_matches!(client.update().await, Ok(true));
-        assert_eq!(client.tuf.trusted_root().version(), 1);
-
-        // Make sure we fetched the metadata in the right order.
-        assert_eq!(
-            client.remote_repo().take_tracks(),
-            vec![
-                Track::FetchErr(root_path.clone(), MetadataVersion::Number(2)),
-                Track::fetch_meta_found(MetadataVersion::None, metadata1.timestamp().unwrap()),
-                Track::fetch_meta_found(snapshot_version, metadata1.snapshot().unwrap()),
-                Track::fetch_meta_found(targets_version, metadata1.targets().unwrap()),
-            ]
+        assert_matches!(
+            Config::build().hash_algorithms(vec![]).finish(),
+            Err(Error::IllegalArgument(_))
         );
-        assert_eq!(
-            client.local_repo().take_tracks(),
-            vec![
-                Track::store_meta(MetadataVersion::None, metadata1.timestamp().unwrap()),
-                Track::store_meta(MetadataVersion::None, metadata1.snapshot().unwrap()),
-                Track::store_meta(MetadataVersion::None, metadata1.targets().unwrap()),
-            ],
+        assert_matches!(
+            Config::build()
+                .hash_algorithms(vec![HashAlgorithm::Unknown("md5".into())])
+                .finish(),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            Config::build()
+                .expiration_grace_period(Duration::seconds(-1))
+                .finish(),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            Config::build()
+                .update_retry_policy(UpdateRetryPolicy::new(0))
+                .finish(),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            Config::build()
+                .update_retry_policy(
+                    UpdateRetryPolicy::new(3)
+                        .initial_backoff(StdDuration::from_secs(2))
+                        .max_backoff(StdDuration::from_secs(1))
+                )
+                .finish(),
+            Err(Error::IllegalArgument(_))
         );
 
-        // Another update should not fetch anything.
-        assert_matches!(client.update().await, Ok(false));
-        assert_eq!(client.tuf.trusted_root().version(), 1);
+        let config = Config::build()
+            .max_root_rotations(3)
+            .max_fetch_retries(2)
+            .hash_algorithms(vec![HashAlgorithm::Sha512])
+            .finish()
+            .unwrap();
+        assert_eq!(config.max_root_rotations(), 3);
+        assert_eq!(config.max_fetch_retries(), 2);
+        assert_eq!(config.hash_algorithms(), &[HashAlgorithm::Sha512]);
+    }
 
-        // Make sure we only fetched the next root and timestamp, and didn't store anything.
-        assert_eq!(
-            client.remote_repo().take_tracks(),
-            vec![
-                Track::FetchErr(root_path.clone(), MetadataVersion::Number(2)),
-                Track::fetch_meta_found(MetadataVersion::None, metadata1.timestamp().unwrap()),
-            ]
-        );
-        assert_eq!(client.local_repo().take_tracks(), vec![]);
+    #[test]
+    fn test_max_root_rotations() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let metadata1 = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
 
-        ////
-        // Now bump the root to version 3
+            for version in 2..=3 {
+                RepoBuilder::create(&mut remote)
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .trusted_targets_keys(&[&KEYS[0]])
+                    .trusted_snapshot_keys(&[&KEYS[0]])
+                    .trusted_timestamp_keys(&[&KEYS[0]])
+                    .stage_root_with_builder(|bld| bld.version(version))
+                    .unwrap()
+                    .stage_targets_with_builder(|bld| bld.version(version))
+                    .unwrap()
+                    .stage_snapshot_with_builder(|bld| bld.version(version))
+                    .unwrap()
+                    .stage_timestamp_with_builder(|bld| bld.version(version))
+                    .unwrap()
+                    .commit()
+                    .await
+                    .unwrap();
+            }
 
-        // Make sure the version 2 is also signed by version 1's keys.
-        //
-        // Note that we write to the underlying store so TrackRepo doesn't track
-        // this new metadata.
-        let mut parts = client.into_parts();
-        let metadata2 = RepoBuilder::create(parts.remote.as_inner_mut())
-            .signing_root_keys(&[&KEYS[0]])
-            .trusted_root_keys(&[&KEYS[1]])
-            .trusted_targets_keys(&[&KEYS[1]])
-            .trusted_snapshot_keys(&[&KEYS[1]])
-            .trusted_timestamp_keys(&[&KEYS[1]])
-            .stage_root_with_builder(|bld| bld.version(2).consistent_snapshot(consistent_snapshot))
-            .unwrap()
-            .skip_targets()
-            .skip_snapshot()
-            .skip_timestamp()
-            .commit()
+            let config = Config::build().max_root_rotations(1).finish().unwrap();
+            let mut client = Client::with_trusted_root(
+                config,
+                metadata1.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
             .await
             .unwrap();
 
-        // Make sure the version 3 is also signed by version 2's keys.
-        let metadata3 = RepoBuilder::create(parts.remote.as_inner_mut())
-            .signing_root_keys(&[&KEYS[1]])
-            .trusted_root_keys(&[&KEYS[2]])
-            .trusted_targets_keys(&[&KEYS[2]])
-            .trusted_snapshot_keys(&[&KEYS[2]])
-            .trusted_timestamp_keys(&[&KEYS[2]])
-            .stage_root_with_builder(|bld| bld.version(3).consistent_snapshot(consistent_snapshot))
-            .unwrap()
-            .skip_targets()
-            .skip_snapshot()
-            .skip_timestamp()
-            .commit()
+            // Each update only rotates the root once.
+            assert_eq!(client.database().trusted_root().version(), 1);
+            assert_matches!(client.update().await, Ok(true));
+            assert_eq!(client.database().trusted_root().version(), 2);
+            assert_matches!(client.update().await, Ok(true));
+            assert_eq!(client.database().trusted_root().version(), 3);
+        })
+    }
+
+    #[test]
+    fn test_max_fetch_retries() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            let config = Config::build().max_fetch_retries(2).finish().unwrap();
+            let mut client = Client::with_trusted_root(
+                config,
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                ErrorRepository::new(remote),
+            )
             .await
             .unwrap();
 
-        ////
-        // Finally, check that the update brings us to version 3.
-        let mut client = Client::from_parts(parts);
-        assert_matches!(client.update().await, Ok(true));
-        assert_eq!(client.tuf.trusted_root().version(), 3);
+            // Transient errors are retried up to the configured number of times, after which the
+            // metadata is fetched as usual.
+            client.remote_repo().fail_next_metadata_fetches(2);
+            assert_matches!(client.update().await, Ok(true));
 
-        // Make sure we fetched and stored the metadata in the expected order. Note that we
-        // re-fetch snapshot and targets because we rotated keys, which caused `tuf::Database` to delete
-        // the metadata.
-        assert_eq!(
-            client.remote_repo().take_tracks(),
-            vec![
-                Track::fetch_meta_found(MetadataVersion::Number(2), metadata2.root().unwrap()),
-                Track::fetch_meta_found(MetadataVersion::Number(3), metadata3.root().unwrap()),
-                Track::FetchErr(root_path.clone(), MetadataVersion::Number(4)),
-                Track::fetch_meta_found(MetadataVersion::None, metadata1.timestamp().unwrap()),
-                Track::fetch_meta_found(snapshot_version, metadata1.snapshot().unwrap()),
-                Track::fetch_meta_found(targets_version, metadata1.targets().unwrap()),
-            ]
-        );
-        assert_eq!(
-            client.local_repo().take_tracks(),
-            vec![
-                Track::store_meta(MetadataVersion::None, metadata2.root().unwrap()),
-                Track::store_meta(MetadataVersion::Number(2), metadata2.root().unwrap()),
-                Track::store_meta(MetadataVersion::None, metadata3.root().unwrap()),
-                Track::store_meta(MetadataVersion::Number(3), metadata3.root().unwrap()),
-                Track::store_meta(MetadataVersion::None, metadata1.timestamp().unwrap()),
-                Track::store_meta(MetadataVersion::None, metadata1.snapshot().unwrap()),
-                Track::store_meta(MetadataVersion::None, metadata1.targets().unwrap()),
-            ],
-        );
+            client.remote_repo().fail_next_metadata_fetches(3);
+            assert_matches!(client.update().await, Err(Error::Io(_)));
+        })
     }
 
     #[test]
-    fn test_fetch_target_description_standard() {
-        block_on(test_fetch_target_description(
-            "standard/metadata".to_string(),
-            TargetDescription::from_slice(
-                "target with no custom metadata".as_bytes(),
-                &[HashAlgorithm::Sha256],
-            )
-            .unwrap(),
-        ));
+    fn test_update_retry_policy_backoff() {
+        let policy = UpdateRetryPolicy::new(10)
+            .initial_backoff(StdDuration::from_millis(100))
+            .max_backoff(StdDuration::from_millis(500));
+
+        let backoffs = (1..=5)
+            .map(|failures| policy.backoff(failures).as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(backoffs, vec![100, 200, 400, 500, 500]);
+        assert_eq!(policy.backoff(u32::MAX), StdDuration::from_millis(500));
+
+        assert!(policy.is_retryable(&Error::Io(io::Error::new(io::ErrorKind::Other, "failed"))));
+        assert!(!policy.is_retryable(&Error::TargetNotFound(TargetPath::new("foo").unwrap())));
+        assert!(!policy.is_retryable(&Error::BadSignature(MetadataPath::root())));
+
+        let policy = policy.retry_on([RetryableErrorClass::NotFound]);
+        assert!(!policy.is_retryable(&Error::Io(io::Error::new(io::ErrorKind::Other, "failed"))));
+        assert!(policy.is_retryable(&Error::TargetNotFound(TargetPath::new("foo").unwrap())));
     }
 
     #[test]
-    fn test_fetch_target_description_custom_empty() {
-        block_on(test_fetch_target_description(
-            "custom-empty".to_string(),
-            TargetDescription::from_slice_with_custom(
-                "target with empty custom metadata".as_bytes(),
-                &[HashAlgorithm::Sha256],
-                hashmap!(),
-            )
-            .unwrap(),
-        ));
-    }
+    fn test_update_retry_policy() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
 
-    #[test]
-    fn test_fetch_target_description_custom() {
-        block_on(test_fetch_target_description(
-            "custom/metadata".to_string(),
-            TargetDescription::from_slice_with_custom(
-                "target with lots of custom metadata".as_bytes(),
-                &[HashAlgorithm::Sha256],
-                hashmap!(
-                    "string".to_string() => json!("string"),
-                    "bool".to_string() => json!(true),
-                    "int".to_string() => json!(42),
-                    "object".to_string() => json!({
-                        "string": json!("string"),
-                        "bool": json!(true),
-                        "int": json!(42),
-                    }),
-                    "array".to_string() => json!([1, 2, 3]),
-                ),
+            let policy = UpdateRetryPolicy::new(3)
+                .initial_backoff(StdDuration::from_millis(1))
+                .max_backoff(StdDuration::from_millis(1))
+                .jitter(StdDuration::ZERO);
+            let config = Config::build()
+                .update_retry_policy(policy.clone())
+                .finish()
+                .unwrap();
+            let mut client = Client::with_trusted_root(
+                config,
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                ErrorRepository::new(remote),
             )
-            .unwrap(),
-        ));
-    }
-
-    async fn test_fetch_target_description(path: String, expected_description: TargetDescription) {
-        // Generate an ephemeral repository with a single target.
-        let mut remote = EphemeralRepository::<Pouf1>::new();
-
-        let metadata = RepoBuilder::create(&mut remote)
-            .trusted_root_keys(&[&KEYS[0]])
-            .trusted_targets_keys(&[&KEYS[0]])
-            .trusted_snapshot_keys(&[&KEYS[0]])
-            .trusted_timestamp_keys(&[&KEYS[0]])
-            .stage_root()
-            .unwrap()
-            .stage_targets_with_builder(|bld| {
-                bld.insert_target_description(
-                    TargetPath::new(path.clone()).unwrap(),
-                    expected_description.clone(),
-                )
-            })
-            .unwrap()
-            .commit()
             .await
             .unwrap();
 
-        // Initialize and update client.
-        let mut client = Client::with_trusted_root(
-            Config::default(),
-            metadata.root().unwrap(),
-            EphemeralRepository::new(),
-            remote,
-        )
-        .await
-        .unwrap();
+            // The policy waits between attempts, which requires a sleep timer.
+            assert_matches!(client.update().await, Err(Error::IllegalArgument(_)));
+
+            let sleeps = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let recorded = Arc::clone(&sleeps);
+            client.set_sleep(move |duration: StdDuration| {
+                recorded.lock().unwrap().push(duration);
+                futures_util::future::ready(()).boxed()
+            });
+
+            // Every failed attempt fails a single fetch, so the third attempt succeeds.
+            client.remote_repo().fail_next_metadata_fetches(2);
+            assert_matches!(client.update().await, Ok(true));
+            assert_eq!(
+                *sleeps.lock().unwrap(),
+                vec![StdDuration::from_millis(1); 2]
+            );
+
+            client.remote_repo().fail_next_metadata_fetches(3);
+            assert_matches!(client.update().await, Err(Error::Io(_)));
+            assert_eq!(sleeps.lock().unwrap().len(), 4);
+
+            // Errors outside of the retryable classes are not retried.
+            client.config.update_retry_policy = policy.retry_on([]);
+            client.remote_repo().fail_next_metadata_fetches(1);
+            assert_matches!(client.update().await, Err(Error::Io(_)));
+            assert_matches!(client.update().await, Ok(false));
+        })
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Event {
+        RootUpdated(u32),
+        NewTargets(Vec<TargetPath>, Vec<TargetPath>),
+        AttackDetected(AttackKind),
+        MetadataExpiring(Vec<MetadataPath>),
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        events: std::sync::Mutex<Vec<Event>>,
+    }
 
-        assert_matches!(client.update().await, Ok(true));
+    impl ClientObserver for Arc<RecordingObserver> {
+        fn on_root_updated(&self, version: u32) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(Event::RootUpdated(version));
+        }
 
-        // Verify fetch_target_description returns expected target metadata
-        let description = client
-            .fetch_target_description(&TargetPath::new(path).unwrap())
-            .await
-            .unwrap();
+        fn on_new_targets(&self, added: &[TargetPath], removed: &[TargetPath]) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(Event::NewTargets(added.to_vec(), removed.to_vec()));
+        }
 
-        assert_eq!(description, expected_description);
+        fn on_attack_detected(&self, kind: AttackKind, _err: &Error) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(Event::AttackDetected(kind));
+        }
+
+        fn on_metadata_expiring(&self, expiring: &[MetadataExpiration]) {
+            self.events.lock().unwrap().push(Event::MetadataExpiring(
+                expiring.iter().map(|e| e.path().clone()).collect(),
+            ));
+        }
     }
 
     #[test]
-    fn update_eventually_succeeds_if_cannot_write_to_repo() {
+    fn test_observer_is_notified_of_update_events() {
         block_on(async {
             let mut remote = EphemeralRepository::<Pouf1>::new();
+            let foo = TargetPath::new("foo").unwrap();
+            let bar = TargetPath::new("bar").unwrap();
 
-            // First, create the metadata.
-            let _ = RepoBuilder::create(&mut remote)
+            let metadata1 = RepoBuilder::create(&mut remote)
                 .trusted_root_keys(&[&KEYS[0]])
                 .trusted_targets_keys(&[&KEYS[0]])
                 .trusted_snapshot_keys(&[&KEYS[0]])
                 .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target(foo.clone(), Cursor::new(&b"foo"[..]))
+                .await
+                .unwrap()
                 .commit()
                 .await
                 .unwrap();
 
-            // Now, make sure that the local metadata got version 1.
-            let local = ErrorRepository::new(EphemeralRepository::new());
-            let mut client = Client::with_trusted_root_keys(
+            let mut client = Client::with_trusted_root(
                 Config::default(),
-                MetadataVersion::Number(1),
-                1,
-                once(&KEYS[0].public().clone()),
-                local,
+                metadata1.root().unwrap(),
+                EphemeralRepository::new(),
                 remote,
             )
             .await
             .unwrap();
 
-            // The first update should succeed.
-            assert_matches!(client.update().await, Ok(true));
+            let observer = Arc::new(RecordingObserver::default());
+            client.set_observer(Arc::clone(&observer));
 
-            // Make sure the database is correct.
-            let mut parts = client.into_parts();
-            assert_eq!(parts.database.trusted_root().version(), 1);
-            assert_eq!(parts.database.trusted_timestamp().unwrap().version(), 1);
-            assert_eq!(parts.database.trusted_snapshot().unwrap().version(), 1);
-            assert_eq!(parts.database.trusted_targets().unwrap().version(), 1);
+            assert_matches!(client.update().await, Ok(true));
+            assert_eq!(
+                observer
+                    .events
+                    .lock()
+                    .unwrap()
+                    .drain(..)
+                    .collect::<Vec<_>>(),
+                vec![Event::NewTargets(vec![foo.clone()], vec![])],
+            );
 
-            // Publish new metadata.
-            let _ = RepoBuilder::create(&mut parts.remote)
+            // Rotate the root and replace `foo` with `bar`.
+            RepoBuilder::create(client.remote_repo_mut())
                 .trusted_root_keys(&[&KEYS[0]])
                 .trusted_targets_keys(&[&KEYS[0]])
                 .trusted_snapshot_keys(&[&KEYS[0]])
                 .trusted_timestamp_keys(&[&KEYS[0]])
                 .stage_root_with_builder(|bld| bld.version(2))
                 .unwrap()
+                .add_target(bar.clone(), Cursor::new(&b"bar"[..]))
+                .await
+                .unwrap()
                 .stage_targets_with_builder(|bld| bld.version(2))
                 .unwrap()
                 .stage_snapshot_with_builder(|bld| bld.version(2))
@@ -2184,46 +7250,251 @@ mod test {
                 .await
                 .unwrap();
 
-            // Make sure we fail to write metadata to the local store.
-            parts.local.fail_metadata_stores(true);
+            assert_matches!(client.update().await, Ok(true));
+            assert_eq!(
+                observer
+                    .events
+                    .lock()
+                    .unwrap()
+                    .drain(..)
+                    .collect::<Vec<_>>(),
+                vec![
+                    Event::RootUpdated(2),
+                    Event::NewTargets(vec![bar.clone()], vec![foo.clone()]),
+                ],
+            );
 
-            // The second update should fail.
-            let mut client = Client::from_parts(parts);
-            assert_matches!(client.update().await, Err(Error::Encoding(_)));
+            // Nothing changed, so there are no events.
+            assert_matches!(client.update().await, Ok(false));
+            assert_eq!(*observer.events.lock().unwrap(), vec![]);
 
-            // FIXME(#297): rust-tuf diverges from the spec by throwing away the
-            // metadata if the root is updated.
-            assert_eq!(client.database().trusted_root().version(), 2);
+            // Serving an old timestamp is reported as a rollback attack.
+            client
+                .remote_repo_mut()
+                .store_metadata(
+                    &MetadataPath::timestamp(),
+                    MetadataVersion::None,
+                    &mut metadata1.timestamp().unwrap().as_bytes(),
+                )
+                .await
+                .unwrap();
+
+            assert_matches!(
+                client.update().await,
+                Err(Error::AttemptedMetadataRollBack { .. })
+            );
+            assert_eq!(
+                observer
+                    .events
+                    .lock()
+                    .unwrap()
+                    .drain(..)
+                    .collect::<Vec<_>>(),
+                vec![Event::AttackDetected(AttackKind::Rollback)],
+            );
+        })
+    }
+
+    #[test]
+    fn test_observer_is_notified_of_expiring_metadata() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            // The timestamp expires in a day and the snapshot in a week.
+            let config = Config::build()
+                .expiration_warning_window(Duration::days(2))
+                .finish()
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                config,
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+
+            let observer = Arc::new(RecordingObserver::default());
+            client.set_observer(Arc::clone(&observer));
+
+            assert_matches!(client.update().await, Ok(true));
+            assert_eq!(
+                *observer.events.lock().unwrap(),
+                vec![Event::MetadataExpiring(vec![MetadataPath::timestamp()])],
+            );
+
+            let expiring =
+                client.expiring_metadata_with_start_time(&(Utc::now() + Duration::days(8)));
+            assert_eq!(
+                expiring
+                    .iter()
+                    .map(|e| e.path().clone())
+                    .collect::<Vec<_>>(),
+                vec![MetadataPath::timestamp(), MetadataPath::snapshot()],
+            );
+            assert_eq!(
+                expiring.iter().map(|e| e.version()).collect::<Vec<_>>(),
+                vec![1, 1],
+            );
+        })
+    }
+
+    #[test]
+    fn test_cancellation_token_aborts_update_and_fetch() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+            let target_path = TargetPath::new("foo").unwrap();
+            let target_file: &[u8] = b"foo";
+
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target(target_path.clone(), Cursor::new(target_file))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+
+            // A cancelled token aborts the update before anything is fetched or persisted.
+            let cancel = CancellationToken::new();
+            client.set_cancellation_token(cancel.clone());
+            cancel.cancel();
+
+            assert_matches!(client.update().await, Err(Error::Cancelled));
             assert_eq!(client.database().trusted_timestamp(), None);
-            assert_eq!(client.database().trusted_snapshot(), None);
-            assert_eq!(client.database().trusted_targets(), None);
+            assert_matches!(
+                fetch_metadata_to_string(
+                    client.local_repo(),
+                    &MetadataPath::timestamp(),
+                    MetadataVersion::None
+                )
+                .await,
+                Err(Error::MetadataNotFound { .. })
+            );
 
-            // However, due to https://github.com/theupdateframework/specification/issues/131, if
-            // the update is retried a few times it will still succeed.
-            assert_matches!(client.update().await, Err(Error::Encoding(_)));
-            assert_eq!(client.database().trusted_root().version(), 2);
-            assert_eq!(client.database().trusted_timestamp().unwrap().version(), 2);
-            assert_eq!(client.database().trusted_snapshot(), None);
-            assert_eq!(client.database().trusted_targets(), None);
+            // A fresh token lets the update complete.
+            let cancel = CancellationToken::new();
+            client.set_cancellation_token(cancel.clone());
+            assert_matches!(client.update().await, Ok(true));
 
-            assert_matches!(client.update().await, Err(Error::Encoding(_)));
-            assert_eq!(client.database().trusted_root().version(), 2);
-            assert_eq!(client.database().trusted_timestamp().unwrap().version(), 2);
-            assert_eq!(client.database().trusted_snapshot().unwrap().version(), 2);
-            assert_eq!(client.database().trusted_targets(), None);
+            // Cancelling while a target is being read fails the read.
+            let mut reader = client.fetch_target(&target_path).await.unwrap();
+            cancel.cancel();
+            let mut buf = vec![];
+            assert_matches!(reader.read_to_end(&mut buf).await, Err(_));
+        })
+    }
 
-            assert_matches!(client.update().await, Err(Error::Encoding(_)));
-            assert_eq!(client.database().trusted_root().version(), 2);
-            assert_eq!(client.database().trusted_timestamp().unwrap().version(), 2);
-            assert_eq!(client.database().trusted_snapshot().unwrap().version(), 2);
-            assert_eq!(client.database().trusted_targets().unwrap().version(), 2);
+    #[derive(Default)]
+    struct RecordingMetrics {
+        durations: std::sync::Mutex<Vec<UpdatePhase>>,
+        bytes: std::sync::Mutex<Vec<(UpdatePhase, u64)>>,
+        verifications: std::sync::Mutex<Vec<(UpdatePhase, bool)>>,
+    }
 
-            assert_matches!(client.update().await, Ok(false));
-            assert_eq!(client.database().trusted_root().version(), 2);
-            assert_eq!(client.database().trusted_timestamp().unwrap().version(), 2);
-            assert_eq!(client.database().trusted_snapshot().unwrap().version(), 2);
-            assert_eq!(client.database().trusted_targets().unwrap().version(), 2);
-        });
+    impl Metrics for Arc<RecordingMetrics> {
+        fn record_phase_duration(&self, phase: UpdatePhase, _duration: StdDuration) {
+            self.durations.lock().unwrap().push(phase);
+        }
+
+        fn record_bytes_downloaded(&self, phase: UpdatePhase, bytes: u64) {
+            self.bytes.lock().unwrap().push((phase, bytes));
+        }
+
+        fn record_signature_verification(&self, phase: UpdatePhase, verified: bool) {
+            self.verifications.lock().unwrap().push((phase, verified));
+        }
+    }
+
+    #[test]
+    fn test_metrics_are_recorded_during_update() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target(TargetPath::new("foo").unwrap(), Cursor::new(&b"foo"[..]))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+
+            let metrics = Arc::new(RecordingMetrics::default());
+            client.set_metrics(Arc::clone(&metrics));
+
+            assert_matches!(client.update().await, Ok(true));
+
+            assert_eq!(
+                *metrics.durations.lock().unwrap(),
+                vec![
+                    UpdatePhase::Root,
+                    UpdatePhase::Timestamp,
+                    UpdatePhase::Snapshot,
+                    UpdatePhase::Targets,
+                ]
+            );
+
+            // There is no new root metadata, so nothing is downloaded for the root phase.
+            assert_eq!(
+                *metrics.bytes.lock().unwrap(),
+                vec![
+                    (
+                        UpdatePhase::Timestamp,
+                        metadata.timestamp().unwrap().as_bytes().len() as u64
+                    ),
+                    (
+                        UpdatePhase::Snapshot,
+                        metadata.snapshot().unwrap().as_bytes().len() as u64
+                    ),
+                    (
+                        UpdatePhase::Targets,
+                        metadata.targets().unwrap().as_bytes().len() as u64
+                    ),
+                ]
+            );
+            assert_eq!(
+                *metrics.verifications.lock().unwrap(),
+                vec![
+                    (UpdatePhase::Timestamp, true),
+                    (UpdatePhase::Snapshot, true),
+                    (UpdatePhase::Targets, true),
+                ]
+            );
+        })
     }
 
     #[test]
@@ -2331,6 +7602,60 @@ mod test {
         })
     }
 
+    #[test]
+    fn client_refetches_corrupted_metadata_cache_entries() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+
+            let metadata = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            let local = MetadataCacheRepository::<Pouf1>::open(temp_dir.path()).unwrap();
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                local,
+                &remote,
+            )
+            .await
+            .unwrap();
+            assert!(client.update().await.unwrap());
+            drop(client);
+
+            // Corrupt the cached targets metadata.
+            let targets_path = temp_dir.path().join("metadata").join("targets.json");
+            std::fs::write(&targets_path, b"corrupted").unwrap();
+
+            // The rest of the cache is still used, but the corrupted entry is discarded.
+            let local = MetadataCacheRepository::<Pouf1>::open(temp_dir.path()).unwrap();
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                local,
+                &remote,
+            )
+            .await
+            .unwrap();
+            assert_eq!(client.database().trusted_snapshot().unwrap().version(), 1);
+            assert!(client.database().trusted_targets().is_none());
+
+            // Updating refetches the discarded entry and heals the cache.
+            assert!(client.update().await.unwrap());
+            assert_eq!(client.database().trusted_targets().unwrap().version(), 1);
+            assert!(targets_path.exists());
+        })
+    }
+
     #[test]
     fn client_can_update_with_unknown_len_and_hashes() {
         block_on(async {
@@ -0,0 +1,60 @@
+//! Sources of the current time used when checking metadata expiration, and timers used when
+//! waiting between retries.
+
+use chrono::{offset::Utc, DateTime};
+use futures_util::future::BoxFuture;
+use std::time::Duration;
+
+/// A source of the current time.
+///
+/// [Database](crate::Database) and [Client](crate::client::Client) use a `Clock` to determine if
+/// metadata has expired when a start time is not explicitly provided. The default is
+/// [SystemClock], but devices without a reliable real time clock may want to provide a secure
+/// time source instead, and tests may want a fixed time.
+///
+/// Any closure of the form `Fn() -> DateTime<Utc>` implements this trait.
+pub trait Clock: Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> DateTime<Utc> + Send + Sync,
+{
+    fn now(&self) -> DateTime<Utc> {
+        (self)()
+    }
+}
+
+/// A [Clock] that returns the system time.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A timer that waits for a duration to pass.
+///
+/// [Client](crate::client::Client) uses a `Sleep` to wait between the attempts of an update
+/// that is retried under an [UpdateRetryPolicy](crate::client::UpdateRetryPolicy). This crate
+/// does not depend on an async runtime, so the timer is provided by the caller, such as one built
+/// on `tokio::time::sleep`.
+///
+/// Any closure of the form `Fn(Duration) -> BoxFuture<'static, ()>` implements this trait.
+pub trait Sleep: Send + Sync {
+    /// Returns a future that completes once `duration` has passed.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+impl<F> Sleep for F
+where
+    F: Fn(Duration) -> BoxFuture<'static, ()> + Send + Sync,
+{
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        (self)(duration)
+    }
+}
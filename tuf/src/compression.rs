@@ -0,0 +1,249 @@
+//! Transparent decompression of compressed targets.
+//!
+//! Targets may be published compressed with gzip (`.gz`) or zstd (`.zst`) to save bandwidth. The
+//! [TargetDescription] of such a target describes the compressed bytes that are transferred, as
+//! usual, and its `decompressed` custom field records the length and hashes of the decompressed
+//! contents:
+//!
+//! ```json
+//! "custom": {
+//!   "decompressed": {
+//!     "length": 1048576,
+//!     "hashes": {
+//!       "sha256": "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03"
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! [Client::fetch_target_decompressed](crate::client::Client::fetch_target_decompressed) verifies
+//! both: the transferred bytes against the target description, and the decompressed bytes
+//! against the `decompressed` custom field. This protects clients from decompression bombs and
+//! from bugs in the decompressor, in addition to the usual guarantees of TUF.
+
+use async_compression::futures::bufread::{GzipDecoder, ZstdDecoder};
+use futures_io::AsyncRead;
+use futures_util::io::BufReader;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::crypto::{self, HashAlgorithm, HashValue};
+use crate::error::{Error, Result};
+use crate::metadata::{TargetDescription, TargetPath};
+use crate::util::SafeAsyncRead;
+
+/// The name of the custom field of a [TargetDescription] that describes the decompressed contents
+/// of a compressed target.
+pub const DECOMPRESSED_CUSTOM_FIELD: &str = "decompressed";
+
+/// The compression formats that can be decompressed.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// gzip, for targets with the `.gz` extension.
+    Gzip,
+    /// zstd, for targets with the `.zst` extension.
+    Zstd,
+}
+
+impl Compression {
+    /// Determine the compression format of a target from the extension of its path, or `None` if
+    /// the target is not compressed.
+    pub fn from_target_path(path: &TargetPath) -> Option<Self> {
+        let path = path.as_str();
+        if path.ends_with(".gz") {
+            Some(Compression::Gzip)
+        } else if path.ends_with(".zst") {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// The length and hashes of the decompressed contents of a compressed target.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecompressedDescription {
+    length: u64,
+    hashes: HashMap<HashAlgorithm, HashValue>,
+}
+
+impl DecompressedDescription {
+    /// Create a new description of decompressed contents.
+    pub fn new(length: u64, hashes: HashMap<HashAlgorithm, HashValue>) -> Result<Self> {
+        if hashes.is_empty() {
+            return Err(Error::IllegalArgument(
+                "Cannot have empty set of hashes".into(),
+            ));
+        }
+
+        Ok(DecompressedDescription { length, hashes })
+    }
+
+    /// Describe the decompressed contents `buf`, calculating its length and hashes.
+    pub fn from_slice(buf: &[u8], hash_algs: &[HashAlgorithm]) -> Result<Self> {
+        let description = TargetDescription::from_slice(buf, hash_algs)?;
+        Self::new(buf.len() as u64, description.hashes().clone())
+    }
+
+    /// Parse the description of the decompressed contents from the custom metadata of a target.
+    /// Returns `None` if the target does not have a `decompressed` custom field.
+    pub fn from_target_description(description: &TargetDescription) -> Result<Option<Self>> {
+        match description.custom().get(DECOMPRESSED_CUSTOM_FIELD) {
+            Some(value) => {
+                let decompressed: Self = serde_json::from_value(value.clone()).map_err(|err| {
+                    Error::Encoding(format!(
+                        "invalid custom field {:?}: {}",
+                        DECOMPRESSED_CUSTOM_FIELD, err
+                    ))
+                })?;
+                Self::new(decompressed.length, decompressed.hashes).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Build custom metadata for a compressed target that records this description, for use with
+    /// [TargetDescription::from_reader_with_custom].
+    pub fn to_custom(&self) -> Result<HashMap<String, serde_json::Value>> {
+        let value = serde_json::to_value(self).map_err(|err| Error::Encoding(err.to_string()))?;
+
+        let mut custom = HashMap::new();
+        custom.insert(DECOMPRESSED_CUSTOM_FIELD.into(), value);
+        Ok(custom)
+    }
+
+    /// The length of the decompressed contents.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// The hashes of the decompressed contents.
+    pub fn hashes(&self) -> &HashMap<HashAlgorithm, HashValue> {
+        &self.hashes
+    }
+}
+
+/// Decompress `read`, verifying that the decompressed contents match `description`. As with any
+/// untrusted stream, verification errors are reported by a read failure, so none of the bytes may
+/// be used until the returned `AsyncRead` has been fully consumed.
+pub(crate) fn decompress<'a, R>(
+    compression: Compression,
+    read: R,
+    description: &DecompressedDescription,
+) -> Result<impl AsyncRead + Send + Unpin + 'a>
+where
+    R: AsyncRead + Send + Unpin + 'a,
+{
+    let hashes = crypto::retain_supported_hashes(description.hashes());
+    if hashes.is_empty() {
+        return Err(Error::NoSupportedHashAlgorithm);
+    }
+
+    // Decode every member of the stream, so that the compressed stream is always read to the end
+    // and its own hashes are checked.
+    let read = BufReader::new(read);
+    let decoder: Box<dyn AsyncRead + Send + Unpin + 'a> = match compression {
+        Compression::Gzip => {
+            let mut decoder = GzipDecoder::new(read);
+            decoder.multiple_members(true);
+            Box::new(decoder)
+        }
+        Compression::Zstd => {
+            let mut decoder = ZstdDecoder::new(read);
+            decoder.multiple_members(true);
+            Box::new(decoder)
+        }
+    };
+
+    decoder.check_exact_length_and_hash(description.length(), hashes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert_matches::assert_matches;
+    use async_compression::futures::bufread::{GzipEncoder, ZstdEncoder};
+    use futures_executor::block_on;
+    use futures_util::io::AsyncReadExt;
+
+    async fn compress(compression: Compression, buf: &[u8]) -> Vec<u8> {
+        let mut compressed = Vec::new();
+        match compression {
+            Compression::Gzip => GzipEncoder::new(buf).read_to_end(&mut compressed).await,
+            Compression::Zstd => ZstdEncoder::new(buf).read_to_end(&mut compressed).await,
+        }
+        .unwrap();
+        compressed
+    }
+
+    #[test]
+    fn compression_from_target_path() {
+        for (path, compression) in [
+            ("foo.tar.gz", Some(Compression::Gzip)),
+            ("foo.tar.zst", Some(Compression::Zstd)),
+            ("foo.tar", None),
+        ] {
+            assert_eq!(
+                Compression::from_target_path(&TargetPath::new(path).unwrap()),
+                compression
+            );
+        }
+    }
+
+    #[test]
+    fn decompressed_description_round_trips_through_custom_metadata() {
+        let decompressed =
+            DecompressedDescription::from_slice(b"contents", &[HashAlgorithm::Sha256]).unwrap();
+
+        let description = TargetDescription::from_slice_with_custom(
+            b"compressed",
+            &[HashAlgorithm::Sha256],
+            decompressed.to_custom().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            DecompressedDescription::from_target_description(&description).unwrap(),
+            Some(decompressed)
+        );
+
+        let description =
+            TargetDescription::from_slice(b"compressed", &[HashAlgorithm::Sha256]).unwrap();
+        assert_eq!(
+            DecompressedDescription::from_target_description(&description).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn decompress_verifies_contents() {
+        block_on(async {
+            let contents: &[u8] = b"hello world, hello world, hello world";
+            let decompressed =
+                DecompressedDescription::from_slice(contents, &[HashAlgorithm::Sha256]).unwrap();
+            let other =
+                DecompressedDescription::from_slice(b"other", &[HashAlgorithm::Sha256]).unwrap();
+
+            for compression in [Compression::Gzip, Compression::Zstd] {
+                let compressed = compress(compression, contents).await;
+
+                let mut buf = Vec::new();
+                decompress(compression, &compressed[..], &decompressed)
+                    .unwrap()
+                    .read_to_end(&mut buf)
+                    .await
+                    .unwrap();
+                assert_eq!(buf, contents);
+
+                let mut buf = Vec::new();
+                assert_matches!(
+                    decompress(compression, &compressed[..], &other)
+                        .unwrap()
+                        .read_to_end(&mut buf)
+                        .await,
+                    Err(_)
+                );
+            }
+        })
+    }
+}
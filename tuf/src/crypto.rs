@@ -5,7 +5,7 @@ use {
     futures_io::AsyncRead,
     futures_util::AsyncReadExt as _,
     ring::{
-        digest::{self, SHA256, SHA512},
+        digest::{self, SHA256, SHA384, SHA512},
         rand::SystemRandom,
         signature::{Ed25519KeyPair, KeyPair, ED25519},
     },
@@ -28,7 +28,11 @@ use crate::error::{Error, Result};
 use crate::metadata::MetadataPath;
 use crate::pouf::pouf1::shims;
 
-const HASH_ALG_PREFS: &[HashAlgorithm] = &[HashAlgorithm::Sha512, HashAlgorithm::Sha256];
+const HASH_ALG_PREFS: &[HashAlgorithm] = &[
+    HashAlgorithm::Sha512,
+    HashAlgorithm::Sha384,
+    HashAlgorithm::Sha256,
+];
 
 /// 1.3.101.112 curveEd25519(EdDSA 25519 signature algorithm)
 const ED25519_SPKI_HEADER: &[u8] = &[
@@ -100,6 +104,21 @@ pub fn retain_supported_hashes(
     data
 }
 
+/// Like [retain_supported_hashes], but only keep the hashes whose algorithm is in `allowed`.
+pub(crate) fn retain_allowed_hashes(
+    hashes: &HashMap<HashAlgorithm, HashValue>,
+    allowed: &[HashAlgorithm],
+) -> Vec<(&'static HashAlgorithm, HashValue)> {
+    let mut data = retain_supported_hashes(hashes);
+    data.retain(|(alg, _)| allowed.contains(alg));
+    data
+}
+
+/// Returns `true` if this crate is able to calculate hashes with `alg`.
+pub(crate) fn is_supported_hash_algorithm(alg: &HashAlgorithm) -> bool {
+    HASH_ALG_PREFS.contains(alg)
+}
+
 #[cfg(test)]
 pub(crate) fn calculate_hash(data: &[u8], hash_alg: &HashAlgorithm) -> HashValue {
     let mut context = hash_alg.digest_context().unwrap();
@@ -132,7 +151,8 @@ pub fn calculate_hashes_from_slice(
     Ok(hashes)
 }
 
-/// Calculate the size and hash digest from a given `AsyncRead`.
+/// Calculate the size and hash digest from a given `AsyncRead`. The data is only read once, no
+/// matter how many hash algorithms are given.
 pub async fn calculate_hashes_from_reader<R>(
     mut read: R,
     hash_algs: &[HashAlgorithm],
@@ -177,6 +197,49 @@ where
     Ok((size, hashes))
 }
 
+/// Calculate the size and hash digest from a given [std::io::Read], which is read only once.
+pub(crate) fn calculate_hashes_from_std_reader<R>(
+    mut read: R,
+    hash_algs: &[HashAlgorithm],
+) -> Result<(u64, HashMap<HashAlgorithm, HashValue>)>
+where
+    R: std::io::Read,
+{
+    if hash_algs.is_empty() {
+        return Err(Error::IllegalArgument(
+            "Cannot provide empty set of hash algorithms".into(),
+        ));
+    }
+
+    let mut size = 0;
+    let mut hashes = HashMap::new();
+    for alg in hash_algs {
+        let _ = hashes.insert(alg, alg.digest_context()?);
+    }
+
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let read_bytes = match read.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read_bytes) => read_bytes,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        size += read_bytes as u64;
+
+        for context in hashes.values_mut() {
+            context.update(&buf[0..read_bytes]);
+        }
+    }
+
+    let hashes = hashes
+        .drain()
+        .map(|(k, v)| (k.clone(), HashValue::new(v.finish().as_ref().to_vec())))
+        .collect();
+    Ok((size, hashes))
+}
+
 fn shim_public_key(
     key_type: &KeyType,
     signature_scheme: &SignatureScheme,
@@ -786,6 +849,11 @@ pub struct Signature {
 }
 
 impl Signature {
+    /// Create a new `Signature` made by the key `key_id`.
+    pub(crate) fn new(key_id: KeyId, value: SignatureValue) -> Self {
+        Signature { key_id, value }
+    }
+
     /// An immutable reference to the `KeyId` of the key that produced the signature.
     pub fn key_id(&self) -> &KeyId {
         &self.key_id
@@ -816,6 +884,9 @@ pub enum HashAlgorithm {
     /// SHA256 as describe in [RFC-6234](https://tools.ietf.org/html/rfc6234)
     #[serde(rename = "sha256")]
     Sha256,
+    /// SHA384 as describe in [RFC-6234](https://tools.ietf.org/html/rfc6234)
+    #[serde(rename = "sha384")]
+    Sha384,
     /// SHA512 as describe in [RFC-6234](https://tools.ietf.org/html/rfc6234)
     #[serde(rename = "sha512")]
     Sha512,
@@ -829,6 +900,7 @@ impl HashAlgorithm {
     pub(crate) fn digest_context(&self) -> Result<digest::Context> {
         match self {
             HashAlgorithm::Sha256 => Ok(digest::Context::new(&SHA256)),
+            HashAlgorithm::Sha384 => Ok(digest::Context::new(&SHA384)),
             HashAlgorithm::Sha512 => Ok(digest::Context::new(&SHA512)),
             HashAlgorithm::Unknown(ref s) => Err(Error::IllegalArgument(format!(
                 "Unknown hash algorithm: {}",
@@ -838,6 +910,17 @@ impl HashAlgorithm {
     }
 }
 
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashAlgorithm::Sha256 => f.write_str("sha256"),
+            HashAlgorithm::Sha384 => f.write_str("sha384"),
+            HashAlgorithm::Sha512 => f.write_str("sha512"),
+            HashAlgorithm::Unknown(s) => f.write_str(s),
+        }
+    }
+}
+
 /// Wrapper for the value of a hash digest.
 #[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct HashValue(#[serde(with = "crate::format_hex")] Vec<u8>);
@@ -1,32 +1,294 @@
 //! Components needed to verify TUF metadata and targets.
 
-use chrono::{offset::Utc, DateTime};
+use chrono::{offset::Utc, DateTime, Duration};
+use futures_io::AsyncRead;
+use futures_util::io::{copy, sink};
+use log::warn;
+use serde_derive::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::io;
 use std::marker::PhantomData;
+use std::sync::Arc;
 
-use crate::crypto::PublicKey;
+use crate::clock::{Clock, SystemClock};
+use crate::crypto::{self, HashValue, PublicKey};
 use crate::error::Error;
+use crate::lazy_targets::LazyTargetsMetadata;
+use crate::merkle::SnapshotMerkleProof;
 use crate::metadata::{
-    Delegations, Metadata, MetadataPath, MetadataVersion, RawSignedMetadata, RawSignedMetadataSet,
-    RootMetadata, SnapshotMetadata, TargetDescription, TargetPath, TargetsMetadata,
-    TimestampMetadata,
+    CustomMetadata, Delegation, DelegationEntry, Delegations, Metadata, MetadataDescription,
+    MetadataPath, MetadataVersion, MultiRoleDelegation, PathMatching, RawSignedMetadata,
+    RawSignedMetadataSet, RootMetadata, SnapshotMetadata, SpecVersion, TargetDescription,
+    TargetPath, TargetsMetadata, TimestampMetadata,
 };
+use crate::policy::Policy;
 use crate::pouf::Pouf;
-use crate::verify::{self, Verified};
+use crate::rollback::RollbackState;
+use crate::util::SafeAsyncRead;
+use crate::verify::{self, SignatureReport, Verified};
 use crate::Result;
 
+mod cache;
+mod shared;
+
+pub use self::shared::{DatabaseUpdate, SharedDatabase};
+
 /// Contains trusted TUF metadata and can be used to verify other metadata and targets.
-#[derive(Debug)]
+///
+/// The trusted metadata is immutable once it has been verified, and is shared between clones of
+/// the database, so cloning a `Database` is cheap no matter how much metadata it trusts. Updating
+/// a clone only copies the metadata that the update replaces. See [SharedDatabase] to read a
+/// database from many tasks while it is being updated.
 pub struct Database<D: Pouf> {
-    trusted_root: Verified<RootMetadata>,
-    trusted_targets: Option<Verified<TargetsMetadata>>,
-    trusted_snapshot: Option<Verified<SnapshotMetadata>>,
-    trusted_timestamp: Option<Verified<TimestampMetadata>>,
-    trusted_delegations: HashMap<MetadataPath, Verified<TargetsMetadata>>,
+    trusted_root: Arc<Verified<RootMetadata>>,
+    trusted_targets: Option<Arc<Verified<TargetsMetadata>>>,
+    trusted_snapshot: Option<Arc<Verified<SnapshotMetadata>>>,
+    trusted_timestamp: Option<Arc<Verified<TimestampMetadata>>>,
+    trusted_delegations: Arc<HashMap<MetadataPath, Arc<Verified<TargetsMetadata>>>>,
+    trusted_custom_roles: Arc<HashMap<MetadataPath, Arc<Verified<CustomMetadata>>>>,
+    // The raw signed metadata of everything trusted above, and of every root metadata trusted
+    // since the database was created, so they can be exported with their signatures.
+    raw_root_history: Arc<Vec<Arc<RawSignedMetadata<D, RootMetadata>>>>,
+    raw_timestamp: Option<Arc<RawSignedMetadata<D, TimestampMetadata>>>,
+    raw_snapshot: Option<Arc<RawSignedMetadata<D, SnapshotMetadata>>>,
+    raw_targets: Option<Arc<RawSignedMetadata<D, TargetsMetadata>>>,
+    raw_delegations: Arc<HashMap<MetadataPath, Arc<RawSignedMetadata<D, TargetsMetadata>>>>,
+    // Snapshot entries verified against the Merkle root in the trusted timestamp metadata.
+    snapshot_merkle_entries: Arc<HashMap<MetadataPath, Arc<MetadataDescription<TargetsMetadata>>>>,
+    clock: Arc<dyn Clock>,
+    rollback_state: Option<Arc<dyn RollbackState>>,
+    expiration_grace_period: Duration,
+    spec_major_version: u32,
+    path_matching: PathMatching,
+    metadata_integrity_policy: MetadataIntegrityPolicy,
+    policy: Arc<Policy>,
+    // How the trusted snapshot metadata was checked against its description in the trusted
+    // timestamp metadata, and the trusted targets and delegated targets metadata against their
+    // descriptions in the trusted snapshot metadata.
+    snapshot_integrity: Option<MetadataIntegrity>,
+    targets_integrity: Arc<HashMap<MetadataPath, MetadataIntegrity>>,
     pouf: PhantomData<D>,
 }
 
+/// How strictly the length and hashes that trusted metadata declares for other metadata are
+/// enforced when that metadata is verified: the description of the snapshot metadata in the
+/// timestamp metadata, and the descriptions of targets and delegated targets metadata in the
+/// snapshot metadata.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MetadataIntegrityPolicy {
+    /// Check the length and hashes that the description declares, but accept metadata whose
+    /// description declares neither. This is the default.
+    #[default]
+    Lenient,
+
+    /// Like [MetadataIntegrityPolicy::Lenient], but also reject metadata whose description does
+    /// not declare both a length and a hash with a supported algorithm.
+    Strict,
+}
+
+/// Which parts of its description in the trusted timestamp or snapshot metadata a piece of metadata
+/// was checked against when it was trusted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MetadataIntegrity {
+    length_checked: bool,
+    hashes_checked: bool,
+}
+
+impl MetadataIntegrity {
+    /// Whether the parent metadata declared the length of the metadata, and it was checked.
+    pub fn length_checked(&self) -> bool {
+        self.length_checked
+    }
+
+    /// Whether the parent metadata declared at least one hash of the metadata with a supported
+    /// algorithm, and all such hashes were checked.
+    pub fn hashes_checked(&self) -> bool {
+        self.hashes_checked
+    }
+}
+
+/// The version and expiration time of a piece of trusted metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataExpiration {
+    path: MetadataPath,
+    version: u32,
+    expires: DateTime<Utc>,
+}
+
+impl MetadataExpiration {
+    fn new<M: Metadata>(path: MetadataPath, metadata: &M) -> Self {
+        MetadataExpiration {
+            path,
+            version: metadata.version(),
+            expires: *metadata.expires(),
+        }
+    }
+
+    /// The path of the metadata.
+    pub fn path(&self) -> &MetadataPath {
+        &self.path
+    }
+
+    /// The version of the metadata.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The time at which the metadata expires.
+    pub fn expires(&self) -> &DateTime<Utc> {
+        &self.expires
+    }
+}
+
+/// The raw signed metadata trusted by a [Database], as exported by
+/// [Database::to_raw_metadata_set].
+///
+/// Besides the latest root metadata, this includes every version of the root metadata trusted
+/// since the database was created, so that the chain of trust can be verified again when the
+/// database is restored with [Database::from_raw_metadata_set].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawTrustedMetadataSet<D> {
+    root_history: Vec<RawSignedMetadata<D, RootMetadata>>,
+    timestamp: Option<RawSignedMetadata<D, TimestampMetadata>>,
+    snapshot: Option<RawSignedMetadata<D, SnapshotMetadata>>,
+    targets: Option<RawSignedMetadata<D, TargetsMetadata>>,
+    delegations: BTreeMap<MetadataPath, RawSignedMetadata<D, TargetsMetadata>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawTrustedMetadataSetShim {
+    root_history: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    snapshot: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    targets: Option<String>,
+    #[serde(default)]
+    delegations: BTreeMap<MetadataPath, String>,
+}
+
+impl<D: Pouf> RawTrustedMetadataSet<D> {
+    /// Every version of the trusted root metadata, from the oldest to the latest.
+    pub fn root_history(&self) -> &[RawSignedMetadata<D, RootMetadata>] {
+        &self.root_history
+    }
+
+    /// The latest trusted root metadata.
+    pub fn root(&self) -> &RawSignedMetadata<D, RootMetadata> {
+        self.root_history
+            .last()
+            .expect("the root history is never empty")
+    }
+
+    /// The trusted timestamp metadata, if any.
+    pub fn timestamp(&self) -> Option<&RawSignedMetadata<D, TimestampMetadata>> {
+        self.timestamp.as_ref()
+    }
+
+    /// The trusted snapshot metadata, if any.
+    pub fn snapshot(&self) -> Option<&RawSignedMetadata<D, SnapshotMetadata>> {
+        self.snapshot.as_ref()
+    }
+
+    /// The trusted top-level targets metadata, if any.
+    pub fn targets(&self) -> Option<&RawSignedMetadata<D, TargetsMetadata>> {
+        self.targets.as_ref()
+    }
+
+    /// The trusted delegated targets metadata.
+    pub fn delegations(&self) -> &BTreeMap<MetadataPath, RawSignedMetadata<D, TargetsMetadata>> {
+        &self.delegations
+    }
+
+    /// Serialize the metadata set, such as to persist it or to ship it to an auditor. The raw
+    /// metadata is base64 encoded, so that it is preserved byte for byte.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let encode = |raw: &[u8]| data_encoding::BASE64.encode(raw);
+        let shim = RawTrustedMetadataSetShim {
+            root_history: self
+                .root_history
+                .iter()
+                .map(|raw| encode(raw.as_bytes()))
+                .collect(),
+            timestamp: self.timestamp.as_ref().map(|raw| encode(raw.as_bytes())),
+            snapshot: self.snapshot.as_ref().map(|raw| encode(raw.as_bytes())),
+            targets: self.targets.as_ref().map(|raw| encode(raw.as_bytes())),
+            delegations: self
+                .delegations
+                .iter()
+                .map(|(role, raw)| (role.clone(), encode(raw.as_bytes())))
+                .collect(),
+        };
+
+        Ok(serde_json::to_vec(&shim)?)
+    }
+
+    /// Deserialize a metadata set serialized with [RawTrustedMetadataSet::to_vec]. The metadata
+    /// is not verified until the set is passed to [Database::from_raw_metadata_set].
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        fn decode<D: Pouf, M: Metadata>(raw: String) -> Result<RawSignedMetadata<D, M>> {
+            let bytes = data_encoding::BASE64
+                .decode(raw.as_bytes())
+                .map_err(|e| Error::Encoding(format!("invalid base64 metadata: {}", e)))?;
+            Ok(RawSignedMetadata::new(bytes))
+        }
+
+        let shim: RawTrustedMetadataSetShim = serde_json::from_slice(bytes)?;
+        if shim.root_history.is_empty() {
+            return Err(Error::MetadataNotFound {
+                path: MetadataPath::root(),
+                version: MetadataVersion::None,
+            });
+        }
+
+        Ok(RawTrustedMetadataSet {
+            root_history: shim
+                .root_history
+                .into_iter()
+                .map(decode)
+                .collect::<Result<_>>()?,
+            timestamp: shim.timestamp.map(decode).transpose()?,
+            snapshot: shim.snapshot.map(decode).transpose()?,
+            targets: shim.targets.map(decode).transpose()?,
+            delegations: shim
+                .delegations
+                .into_iter()
+                .map(|(role, raw)| Ok((role, decode(raw)?)))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+// `RawSignedMetadata` is only `Clone` if the pouf is.
+fn clone_raw<D: Pouf, M: Metadata>(raw: &RawSignedMetadata<D, M>) -> RawSignedMetadata<D, M> {
+    RawSignedMetadata::new(raw.as_bytes().to_vec())
+}
+
+impl<D: Pouf> fmt::Debug for Database<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Database")
+            .field("trusted_root", &self.trusted_root)
+            .field("trusted_targets", &self.trusted_targets)
+            .field("trusted_snapshot", &self.trusted_snapshot)
+            .field("trusted_timestamp", &self.trusted_timestamp)
+            .field("trusted_delegations", &self.trusted_delegations)
+            .field("trusted_custom_roles", &self.trusted_custom_roles)
+            .field("snapshot_merkle_entries", &self.snapshot_merkle_entries)
+            .field("expiration_grace_period", &self.expiration_grace_period)
+            .field("spec_major_version", &self.spec_major_version)
+            .field("path_matching", &self.path_matching)
+            .field("metadata_integrity_policy", &self.metadata_integrity_policy)
+            .field("policy", &self.policy)
+            .field("snapshot_integrity", &self.snapshot_integrity)
+            .field("targets_integrity", &self.targets_integrity)
+            .finish_non_exhaustive()
+    }
+}
+
 impl<D: Pouf> Database<D> {
     /// Create a new [`Database`] struct from a set of trusted root keys that are used to verify
     /// the signed metadata. The signed root metadata must be signed with at least a
@@ -65,11 +327,27 @@ impl<D: Pouf> Database<D> {
         };
 
         Ok(Database {
-            trusted_root: verified_root,
+            trusted_root: Arc::new(verified_root),
             trusted_snapshot: None,
             trusted_targets: None,
             trusted_timestamp: None,
-            trusted_delegations: HashMap::new(),
+            trusted_delegations: Arc::default(),
+            trusted_custom_roles: Arc::default(),
+            raw_root_history: Arc::new(vec![Arc::new(clone_raw(raw_root))]),
+            raw_timestamp: None,
+            raw_snapshot: None,
+            raw_targets: None,
+            raw_delegations: Arc::default(),
+            snapshot_merkle_entries: Arc::default(),
+            clock: Arc::new(SystemClock),
+            rollback_state: None,
+            expiration_grace_period: Duration::zero(),
+            spec_major_version: SpecVersion::SUPPORTED_MAJOR_VERSION,
+            path_matching: PathMatching::default(),
+            metadata_integrity_policy: MetadataIntegrityPolicy::default(),
+            policy: Arc::default(),
+            snapshot_integrity: None,
+            targets_integrity: Arc::default(),
             pouf: PhantomData,
         })
     }
@@ -97,11 +375,27 @@ impl<D: Pouf> Database<D> {
         };
 
         Ok(Database {
-            trusted_root: verified_root,
+            trusted_root: Arc::new(verified_root),
             trusted_snapshot: None,
             trusted_targets: None,
             trusted_timestamp: None,
-            trusted_delegations: HashMap::new(),
+            trusted_delegations: Arc::default(),
+            trusted_custom_roles: Arc::default(),
+            raw_root_history: Arc::new(vec![Arc::new(clone_raw(raw_root))]),
+            raw_timestamp: None,
+            raw_snapshot: None,
+            raw_targets: None,
+            raw_delegations: Arc::default(),
+            snapshot_merkle_entries: Arc::default(),
+            clock: Arc::new(SystemClock),
+            rollback_state: None,
+            expiration_grace_period: Duration::zero(),
+            spec_major_version: SpecVersion::SUPPORTED_MAJOR_VERSION,
+            path_matching: PathMatching::default(),
+            metadata_integrity_policy: MetadataIntegrityPolicy::default(),
+            policy: Arc::default(),
+            snapshot_integrity: None,
+            targets_integrity: Arc::default(),
             pouf: PhantomData,
         })
     }
@@ -187,6 +481,214 @@ impl<D: Pouf> Database<D> {
         Ok(db)
     }
 
+    /// Restore a [`Database`] from the metadata exported by [Database::to_raw_metadata_set].
+    ///
+    /// Every version of the root metadata in `metadata_set` is verified against the previous
+    /// one, and the rest of the metadata is verified as if it were fetched from a repository.
+    /// Delegated targets metadata that is not delegated to by the restored targets metadata is
+    /// ignored.
+    ///
+    /// **WARNING**: The oldest root metadata in `metadata_set` is trusted on first use, as with
+    /// [`Database::from_trusted_root`]. This method should only be used if the metadata is loaded
+    /// from a trusted source.
+    pub fn from_raw_metadata_set(metadata_set: &RawTrustedMetadataSet<D>) -> Result<Self> {
+        Self::from_raw_metadata_set_with_start_time(metadata_set, &Utc::now())
+    }
+
+    /// Restore a [`Database`] from the metadata exported by [Database::to_raw_metadata_set],
+    /// checking for expired metadata as of `start_time`. See [Database::from_raw_metadata_set].
+    pub fn from_raw_metadata_set_with_start_time(
+        metadata_set: &RawTrustedMetadataSet<D>,
+        start_time: &DateTime<Utc>,
+    ) -> Result<Self> {
+        let (first_root, root_history) = metadata_set
+            .root_history
+            .split_first()
+            .expect("the root history is never empty");
+        let mut db = Database::from_trusted_root(first_root)?;
+        for raw_root in root_history {
+            db.update_root(raw_root)?;
+        }
+
+        if let Some(raw_timestamp) = &metadata_set.timestamp {
+            let _ = db.update_timestamp(start_time, raw_timestamp)?;
+        }
+
+        if let Some(raw_snapshot) = &metadata_set.snapshot {
+            let _ = db.update_snapshot(start_time, raw_snapshot)?;
+        }
+
+        if let Some(raw_targets) = &metadata_set.targets {
+            let _ = db.update_targets(start_time, raw_targets)?;
+        }
+
+        // Restore the delegations top-down, so that every delegation is verified by its parent.
+        let mut parents = vec![MetadataPath::targets()];
+        while let Some(parent) = parents.pop() {
+            for (role, raw_delegation) in &metadata_set.delegations {
+                if db.trusted_delegations.contains_key(role) {
+                    continue;
+                }
+
+                let trusted_parent = if parent == MetadataPath::targets() {
+                    db.trusted_targets.as_ref()
+                } else {
+                    db.trusted_delegations.get(&parent)
+                };
+                let delegated = trusted_parent
+                    .map(|targets| targets.delegations().delegation(role).is_some())
+                    .unwrap_or(false);
+
+                if delegated {
+                    let _ =
+                        db.update_delegated_targets(start_time, &parent, role, raw_delegation)?;
+                    parents.push(role.clone());
+                }
+            }
+        }
+
+        Ok(db)
+    }
+
+    /// The [Clock] used to check metadata expiration when a start time is not provided.
+    pub fn clock(&self) -> &dyn Clock {
+        &*self.clock
+    }
+
+    /// Set the [Clock] used to check metadata expiration when a start time is not provided.
+    pub fn set_clock<C>(&mut self, clock: C)
+    where
+        C: Clock + 'static,
+    {
+        self.clock = Arc::new(clock);
+    }
+
+    /// The [RollbackState] consulted when new metadata is verified, if any.
+    pub fn rollback_state(&self) -> Option<&dyn RollbackState> {
+        self.rollback_state.as_deref()
+    }
+
+    /// Set the [RollbackState] that records the highest trusted version of every role, in
+    /// addition to the metadata trusted by the database. Timestamp, snapshot, targets, and
+    /// delegated targets metadata older than the recorded version is rejected with
+    /// [Error::AttemptedMetadataRollBack], even if the database has never trusted a version of
+    /// it. See [Database::check_root_rollback_state] for root metadata.
+    pub fn set_rollback_state<S>(&mut self, rollback_state: S)
+    where
+        S: RollbackState + 'static,
+    {
+        self.rollback_state = Some(Arc::new(rollback_state));
+    }
+
+    /// Check that the trusted root metadata is not older than the highest root version recorded
+    /// in the [RollbackState], and then record its version. Since new root metadata is trusted one
+    /// version at a time, this can only be checked once all of the available root metadata has
+    /// been trusted.
+    pub fn check_root_rollback_state(&self) -> Result<()> {
+        let version = self.trusted_root.version();
+        self.check_rollback_state(&MetadataPath::root(), version)?;
+        // The root may have been trusted before the rollback state was set, such as the initial
+        // root of a client, so its version isn't necessarily recorded yet.
+        self.record_rollback_state(&MetadataPath::root(), version)
+    }
+
+    /// The period of time after expiration during which timestamp and snapshot metadata is still
+    /// accepted.
+    pub fn expiration_grace_period(&self) -> Duration {
+        self.expiration_grace_period
+    }
+
+    /// Set the period of time after expiration during which timestamp and snapshot metadata is
+    /// still accepted. A warning is logged whenever expired metadata is used within the grace
+    /// period. This defaults to zero.
+    ///
+    /// **WARNING**: A non-zero grace period weakens the protection against freeze attacks, since
+    /// an attacker can keep serving stale metadata until the grace period ends. It should only be
+    /// used by clients that prefer stale metadata over being unable to update at all, such as
+    /// devices that are offline for long periods of time.
+    pub fn set_expiration_grace_period(&mut self, grace_period: Duration) {
+        self.expiration_grace_period = grace_period;
+    }
+
+    /// The major version of the specification that new metadata must follow.
+    pub fn spec_major_version(&self) -> u32 {
+        self.spec_major_version
+    }
+
+    /// Set the major version of the specification that new metadata must follow. Metadata with
+    /// any minor or patch version of it is accepted, and metadata with any other major version is
+    /// rejected with [Error::UnsupportedSpecVersion]. This defaults to
+    /// [SpecVersion::SUPPORTED_MAJOR_VERSION].
+    ///
+    /// The trusted root the database is created with is not checked, since it is already trusted.
+    pub fn set_spec_major_version(&mut self, spec_major_version: u32) {
+        self.spec_major_version = spec_major_version;
+    }
+
+    /// The rules used to match targets against the paths of delegations.
+    pub fn path_matching(&self) -> PathMatching {
+        self.path_matching
+    }
+
+    /// Set the rules used to match targets against the paths of delegations. This defaults to
+    /// [PathMatching::default].
+    pub fn set_path_matching(&mut self, path_matching: PathMatching) {
+        self.path_matching = path_matching;
+    }
+
+    /// How strictly the length and hashes declared in the snapshot metadata are enforced.
+    pub fn metadata_integrity_policy(&self) -> MetadataIntegrityPolicy {
+        self.metadata_integrity_policy
+    }
+
+    /// Set how strictly the length and hashes the trusted timestamp metadata declares for the
+    /// snapshot metadata, and the trusted snapshot metadata declares for targets and delegated
+    /// targets metadata, are enforced. Metadata that does not match the declared length or hashes
+    /// is always rejected with [Error::MetadataIntegrityMismatch]. This defaults to
+    /// [MetadataIntegrityPolicy::Lenient]. Use [MetadataIntegrityPolicy::Strict] to require that
+    /// they are declared.
+    pub fn set_metadata_integrity_policy(&mut self, policy: MetadataIntegrityPolicy) {
+        self.metadata_integrity_policy = policy;
+    }
+
+    /// The hash algorithms and signature schemes that new metadata may use.
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// Set the hash algorithms and signature schemes that new metadata may use. Metadata that
+    /// does not satisfy the policy is rejected when it is updated, but metadata that is already
+    /// trusted is not checked again. This defaults to [Policy::default], which allows everything.
+    ///
+    /// The targets of lazily verified targets metadata are not checked, since they are not
+    /// parsed. See [Database::verify_targets_lazily].
+    pub fn set_policy(&mut self, policy: Policy) {
+        self.policy = Arc::new(policy);
+    }
+
+    /// How the trusted snapshot metadata was checked against its description in the trusted
+    /// timestamp metadata, or `None` if no snapshot metadata is trusted.
+    pub fn snapshot_integrity(&self) -> Option<MetadataIntegrity> {
+        self.snapshot_integrity
+    }
+
+    /// How the trusted targets or delegated targets metadata for `role` was checked against its
+    /// description in the trusted snapshot metadata, or `None` if no metadata is trusted for
+    /// `role`.
+    pub fn targets_integrity(&self, role: &MetadataPath) -> Option<MetadataIntegrity> {
+        let trusted = if role == &MetadataPath::targets() {
+            self.trusted_targets.is_some()
+        } else {
+            self.trusted_delegations.contains_key(role)
+        };
+
+        if trusted {
+            self.targets_integrity.get(role).copied()
+        } else {
+            None
+        }
+    }
+
     /// An immutable reference to the root metadata.
     pub fn trusted_root(&self) -> &Verified<RootMetadata> {
         &self.trusted_root
@@ -194,27 +696,177 @@ impl<D: Pouf> Database<D> {
 
     /// An immutable reference to the optional targets metadata.
     pub fn trusted_targets(&self) -> Option<&Verified<TargetsMetadata>> {
-        self.trusted_targets.as_ref()
+        self.trusted_targets.as_deref()
     }
 
     /// An immutable reference to the optional snapshot metadata.
     pub fn trusted_snapshot(&self) -> Option<&Verified<SnapshotMetadata>> {
-        self.trusted_snapshot.as_ref()
+        self.trusted_snapshot.as_deref()
     }
 
     /// An immutable reference to the optional timestamp metadata.
     pub fn trusted_timestamp(&self) -> Option<&Verified<TimestampMetadata>> {
-        self.trusted_timestamp.as_ref()
+        self.trusted_timestamp.as_deref()
     }
 
     /// An immutable reference to the delegated metadata.
-    pub fn trusted_delegations(&self) -> &HashMap<MetadataPath, Verified<TargetsMetadata>> {
+    pub fn trusted_delegations(&self) -> &HashMap<MetadataPath, Arc<Verified<TargetsMetadata>>> {
         &self.trusted_delegations
     }
 
+    /// An immutable reference to the metadata of the delegated targets `role`, if it is trusted.
+    pub fn trusted_delegation(&self, role: &MetadataPath) -> Option<&Verified<TargetsMetadata>> {
+        self.trusted_delegations.get(role).map(|targets| &**targets)
+    }
+
+    /// The versions and expiration times of the trusted delegated targets metadata, sorted by
+    /// path. Use [Database::trusted_delegation] to look up the metadata itself.
+    pub fn trusted_delegation_expirations(&self) -> impl Iterator<Item = MetadataExpiration> {
+        let mut delegations = self
+            .trusted_delegations
+            .iter()
+            .map(|(path, targets)| MetadataExpiration::new(path.clone(), &***targets))
+            .collect::<Vec<_>>();
+        delegations.sort_by(|a, b| a.path.cmp(&b.path));
+        delegations.into_iter()
+    }
+
+    /// An immutable reference to the metadata of the custom top-level `role`, if any is trusted.
+    pub fn trusted_custom_role(&self, role: &MetadataPath) -> Option<&Verified<CustomMetadata>> {
+        self.trusted_custom_roles.get(role).map(|custom| &**custom)
+    }
+
+    /// Export the raw signed metadata of everything this database trusts, so that it can be
+    /// persisted, shipped to an auditor, or restored with [Database::from_raw_metadata_set]
+    /// without downloading it again.
+    pub fn to_raw_metadata_set(&self) -> RawTrustedMetadataSet<D> {
+        RawTrustedMetadataSet {
+            root_history: self
+                .raw_root_history
+                .iter()
+                .map(|raw| clone_raw(raw))
+                .collect(),
+            timestamp: self.raw_timestamp.as_deref().map(clone_raw),
+            snapshot: self.raw_snapshot.as_deref().map(clone_raw),
+            targets: self.raw_targets.as_deref().map(clone_raw),
+            delegations: self
+                .raw_delegations
+                .iter()
+                .map(|(role, raw)| (role.clone(), clone_raw(raw)))
+                .collect(),
+        }
+    }
+
+    /// The root of the snapshot Merkle tree in the trusted timestamp metadata, if any.
+    pub fn trusted_merkle_root(&self) -> Option<&HashValue> {
+        self.trusted_timestamp
+            .as_ref()
+            .and_then(|timestamp| timestamp.merkle_root())
+    }
+
+    /// The trusted snapshot entry of `role`, from either the trusted snapshot metadata or a
+    /// verified [SnapshotMerkleProof].
+    pub fn trusted_snapshot_description(
+        &self,
+        role: &MetadataPath,
+    ) -> Option<&MetadataDescription<TargetsMetadata>> {
+        self.snapshot_merkle_entries
+            .get(role)
+            .map(|description| &**description)
+            .or_else(|| {
+                self.trusted_snapshot
+                    .as_ref()
+                    .and_then(|snapshot| snapshot.meta().get(role))
+            })
+    }
+
+    /// Verify the snapshot entry of `role` against the Merkle root in the trusted timestamp
+    /// metadata, and trust it until the next timestamp metadata is trusted. Targets metadata of
+    /// `role` can then be verified without the snapshot metadata.
+    ///
+    /// Returns the verified snapshot entry.
+    pub fn update_snapshot_merkle_proof(
+        &mut self,
+        start_time: &DateTime<Utc>,
+        role: &MetadataPath,
+        proof: &SnapshotMerkleProof,
+    ) -> Result<&MetadataDescription<TargetsMetadata>> {
+        let description = {
+            let trusted_timestamp = self.trusted_timestamp_unexpired(start_time)?;
+            let merkle_root = trusted_timestamp.merkle_root().ok_or_else(|| {
+                Error::MissingMetadataDescription {
+                    parent_role: MetadataPath::timestamp(),
+                    child_role: role.clone(),
+                }
+            })?;
+
+            let description = proof.verify(role, merkle_root)?;
+
+            // Without the snapshot metadata, the client can only protect itself against rollbacks
+            // of the roles it has already trusted.
+            let trusted_version = if role == &MetadataPath::targets() {
+                self.trusted_targets
+                    .as_ref()
+                    .map(|targets| targets.version())
+            } else {
+                self.trusted_delegations
+                    .get(role)
+                    .map(|targets| targets.version())
+            };
+            if let Some(trusted_version) = trusted_version {
+                if description.version() < trusted_version {
+                    return Err(Error::AttemptedMetadataRollBack {
+                        role: role.clone(),
+                        trusted_version,
+                        new_version: description.version(),
+                    });
+                }
+            }
+
+            description
+        };
+
+        let _ = Arc::make_mut(&mut self.snapshot_merkle_entries)
+            .insert(role.clone(), Arc::new(description));
+        Ok(&*self.snapshot_merkle_entries[role])
+    }
+
+    /// The versions and expiration times of all of the trusted metadata, including delegated
+    /// targets metadata. The top-level roles are listed first, in the order root, timestamp,
+    /// snapshot, and targets, followed by the delegated roles sorted by path.
+    pub fn trusted_metadata_expirations(&self) -> Vec<MetadataExpiration> {
+        let mut expirations = vec![MetadataExpiration::new(
+            MetadataPath::root(),
+            &**self.trusted_root,
+        )];
+
+        if let Some(timestamp) = self.trusted_timestamp() {
+            expirations.push(MetadataExpiration::new(
+                MetadataPath::timestamp(),
+                &**timestamp,
+            ));
+        }
+
+        if let Some(snapshot) = self.trusted_snapshot() {
+            expirations.push(MetadataExpiration::new(
+                MetadataPath::snapshot(),
+                &**snapshot,
+            ));
+        }
+
+        if let Some(targets) = self.trusted_targets() {
+            expirations.push(MetadataExpiration::new(MetadataPath::targets(), &**targets));
+        }
+
+        expirations.extend(self.trusted_delegation_expirations());
+
+        expirations
+    }
+
     /// Verify and update metadata. Returns true if any of the metadata was updated.
     pub fn update_metadata(&mut self, metadata: &RawSignedMetadataSet<D>) -> Result<bool> {
-        self.update_metadata_with_start_time(metadata, &Utc::now())
+        let start_time = self.clock.now();
+        self.update_metadata_with_start_time(metadata, &start_time)
     }
 
     /// Verify and update metadata. Returns true if any of the metadata was updated.
@@ -265,6 +917,19 @@ impl<D: Pouf> Database<D> {
     }
 
     /// Verify and update the root metadata.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                role = "root",
+                bytes = raw_root.as_bytes().len(),
+                trusted_version = self.trusted_root.version(),
+            ),
+            err,
+        )
+    )]
     pub fn update_root(&mut self, raw_root: &RawSignedMetadata<D, RootMetadata>) -> Result<()> {
         let verified = {
             let trusted_root = &self.trusted_root;
@@ -284,7 +949,8 @@ impl<D: Pouf> Database<D> {
                 raw_root,
                 trusted_root.root().threshold(),
                 trusted_root.root_keys(),
-            )?;
+            )
+            .map_err(|err| err.with_trusted_version(Some(trusted_root.version())))?;
 
             // Verify the new root signed itself.
             let new_root = verify::verify_signatures(
@@ -292,7 +958,11 @@ impl<D: Pouf> Database<D> {
                 raw_root,
                 new_root.root().threshold(),
                 new_root.root_keys(),
-            )?;
+            )
+            .map_err(|err| err.with_trusted_version(Some(trusted_root.version())))?;
+
+            self.check_spec_version(&MetadataPath::root(), new_root.spec_version())?;
+            self.policy.check_root(&new_root)?;
 
             /////////////////////////////////////////
             // TUF-1.0.5 §5.1.4:
@@ -337,6 +1007,8 @@ impl<D: Pouf> Database<D> {
             new_root
         };
 
+        self.record_rollback_state(&MetadataPath::root(), verified.version())?;
+
         /////////////////////////////////////////
         // TUF-1.0.5 §5.1.9:
         //
@@ -355,7 +1027,8 @@ impl<D: Pouf> Database<D> {
         //
         //     1.6. Set the trusted root metadata file to the new root metadata file.
 
-        self.trusted_root = verified;
+        self.trusted_root = Arc::new(verified);
+        Arc::make_mut(&mut self.raw_root_history).push(Arc::new(clone_raw(raw_root)));
 
         Ok(())
     }
@@ -363,6 +1036,19 @@ impl<D: Pouf> Database<D> {
     /// Verify and update the timestamp metadata.
     ///
     /// Returns a reference to the parsed metadata if the metadata was newer.
+    // Unlike the other updates, errors aren't recorded, since the result borrows from `self`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                role = "timestamp",
+                bytes = raw_timestamp.as_bytes().len(),
+                trusted_version = ?self.trusted_timestamp.as_ref().map(|t| t.version()),
+            ),
+        )
+    )]
     pub fn update_timestamp(
         &mut self,
         start_time: &DateTime<Utc>,
@@ -387,7 +1073,13 @@ impl<D: Pouf> Database<D> {
                 raw_timestamp,
                 trusted_root.timestamp().threshold(),
                 trusted_root.timestamp_keys(),
-            )?;
+            )
+            .map_err(|err| {
+                err.with_trusted_version(self.trusted_timestamp.as_ref().map(|t| t.version()))
+            })?;
+
+            self.check_spec_version(&MetadataPath::timestamp(), new_timestamp.spec_version())?;
+            self.policy.check_timestamp(&new_timestamp)?;
 
             /////////////////////////////////////////
             // TUF-1.0.5 §5.2.2: Check for a rollback attack.
@@ -416,6 +1108,8 @@ impl<D: Pouf> Database<D> {
                 }
             }
 
+            self.check_rollback_state(&MetadataPath::timestamp(), new_timestamp.version())?;
+
             /////////////////////////////////////////
             // TUF-1.0.5 §5.2.2.2:
             //
@@ -433,9 +1127,14 @@ impl<D: Pouf> Database<D> {
             if let Some(trusted_snapshot) = &self.trusted_snapshot {
                 if trusted_snapshot.version() != new_timestamp.snapshot().version() {
                     self.trusted_snapshot = None;
+                    self.snapshot_integrity = None;
+                    self.raw_snapshot = None;
                 }
             }
 
+            // Snapshot entries proven against an older Merkle root may be out of date.
+            self.snapshot_merkle_entries = Arc::default();
+
             /////////////////////////////////////////
             // TUF-1.0.5 §5.2.3:
             //
@@ -445,28 +1144,43 @@ impl<D: Pouf> Database<D> {
             //     timestamp metadata file has expired, discard it, abort the update cycle, and
             //     report the potential freeze attack.
 
-            if new_timestamp.expires() <= start_time {
-                return Err(Error::ExpiredMetadata {
-                    path: MetadataPath::timestamp(),
-                    expiration: *new_timestamp.expires(),
-                    now: *start_time,
-                });
-            }
+            self.check_expiration_with_grace_period(
+                &MetadataPath::timestamp(),
+                new_timestamp.version(),
+                self.trusted_timestamp.as_ref().map(|t| t.version()),
+                new_timestamp.expires(),
+                start_time,
+            )?;
 
             new_timestamp
         };
 
-        self.trusted_timestamp = Some(verified);
-        Ok(self.trusted_timestamp.as_ref())
+        self.record_rollback_state(&MetadataPath::timestamp(), verified.version())?;
+        self.trusted_timestamp = Some(Arc::new(verified));
+        self.raw_timestamp = Some(Arc::new(clone_raw(raw_timestamp)));
+        Ok(self.trusted_timestamp())
     }
 
     /// Verify and update the snapshot metadata.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                role = "snapshot",
+                bytes = raw_snapshot.as_bytes().len(),
+                trusted_version = ?self.trusted_snapshot.as_ref().map(|s| s.version()),
+            ),
+            err,
+        )
+    )]
     pub fn update_snapshot(
         &mut self,
         start_time: &DateTime<Utc>,
         raw_snapshot: &RawSignedMetadata<D, SnapshotMetadata>,
     ) -> Result<bool> {
-        let verified = {
+        let (verified, integrity) = {
             /////////////////////////////////////////
             // FIXME(https://github.com/theupdateframework/specification/issues/113) Checking if
             // this metadata expired isn't part of the spec. Do we actually want to do this?
@@ -501,9 +1215,15 @@ impl<D: Pouf> Database<D> {
             //     in the trusted timestamp metadata. If hashes and version do not match, discard
             //     the new snapshot metadata, abort the update cycle, and report the failure.
 
-            // FIXME: rust-tuf checks the hash during download, but it would be better if we
-            // checked the hash here to make it easier to validate we've correctly implemented the
-            // spec.
+            // NOTE: rust-tuf also checks the hashes during download, so that a mismatched file is
+            // not downloaded in full. Checking them again here, before the snapshot is parsed,
+            // keeps the database safe when it is used without a client.
+            let integrity = self.check_metadata_integrity(
+                &MetadataPath::timestamp(),
+                &MetadataPath::snapshot(),
+                raw_snapshot,
+                trusted_timestamp.snapshot(),
+            )?;
 
             // NOTE(https://github.com/theupdateframework/specification/pull/112): Technically
             // we're supposed to check the version before checking the signature, but we do it
@@ -523,7 +1243,13 @@ impl<D: Pouf> Database<D> {
                 raw_snapshot,
                 trusted_root.snapshot().threshold(),
                 trusted_root.snapshot_keys(),
-            )?;
+            )
+            .map_err(|err| {
+                err.with_trusted_version(self.trusted_snapshot.as_ref().map(|s| s.version()))
+            })?;
+
+            self.check_spec_version(&MetadataPath::snapshot(), new_snapshot.spec_version())?;
+            self.policy.check_snapshot(&new_snapshot)?;
 
             /////////////////////////////////////////
             // FIXME(https://github.com/theupdateframework/specification/pull/112): Actually check
@@ -559,6 +1285,8 @@ impl<D: Pouf> Database<D> {
                 }
             }
 
+            self.check_rollback_state(&MetadataPath::snapshot(), new_snapshot.version())?;
+
             /////////////////////////////////////////
             // TUF-1.0.5 §5.3.3.2:
             //
@@ -586,9 +1314,11 @@ impl<D: Pouf> Database<D> {
             // Note: this doesn't check the expiration because we need to be able to update it
             // regardless so we can prevent rollback attacks againsts targets/delegations.
 
-            new_snapshot
+            (new_snapshot, integrity)
         };
 
+        self.record_rollback_state(&MetadataPath::snapshot(), verified.version())?;
+
         // FIXME(#297): purging targets is not part of the spec. Do we need to do it?
         if self
             .trusted_targets
@@ -602,9 +1332,12 @@ impl<D: Pouf> Database<D> {
                 .unwrap_or(0)
         {
             self.trusted_targets = None;
+            self.raw_targets = None;
         }
 
-        self.trusted_snapshot = Some(verified);
+        self.trusted_snapshot = Some(Arc::new(verified));
+        self.snapshot_integrity = Some(integrity);
+        self.raw_snapshot = Some(Arc::new(clone_raw(raw_snapshot)));
 
         // FIXME(#297): purging delegates is not part of the spec. Do we need to do it?
         self.purge_delegations();
@@ -635,11 +1368,25 @@ impl<D: Pouf> Database<D> {
         };
 
         for role in &purge {
-            let _ = self.trusted_delegations.remove(role);
+            let _ = Arc::make_mut(&mut self.trusted_delegations).remove(role);
+            let _ = Arc::make_mut(&mut self.raw_delegations).remove(role);
         }
     }
 
     /// Verify and update the targets metadata.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                role = "targets",
+                bytes = raw_targets.as_bytes().len(),
+                trusted_version = ?self.trusted_targets.as_ref().map(|t| t.version()),
+            ),
+            err,
+        )
+    )]
     pub fn update_targets(
         &mut self,
         start_time: &DateTime<Utc>,
@@ -661,64 +1408,324 @@ impl<D: Pouf> Database<D> {
             )?
         };
 
-        if let Some(verified) = verified {
-            self.trusted_targets = Some(verified);
+        if let Some((verified, integrity)) = verified {
+            self.record_rollback_state(&MetadataPath::targets(), verified.version())?;
+            self.trusted_targets = Some(Arc::new(verified));
+            self.raw_targets = Some(Arc::new(clone_raw(raw_targets)));
+            let _ = Arc::make_mut(&mut self.targets_integrity)
+                .insert(MetadataPath::targets(), integrity);
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
-    /// Verify and update a delegation metadata.
-    pub fn update_delegated_targets(
-        &mut self,
+    /// Verify the top-level targets metadata the same way as [Database::update_targets], but only
+    /// index its targets instead of parsing them, and return it instead of trusting it. See
+    /// [LazyTargetsMetadata] for details.
+    pub fn verify_targets_lazily(
+        &self,
         start_time: &DateTime<Utc>,
-        parent_role: &MetadataPath,
-        role: &MetadataPath,
-        raw_delegated_targets: &RawSignedMetadata<D, TargetsMetadata>,
-    ) -> Result<bool> {
-        let verified = {
-            // FIXME(https://github.com/theupdateframework/specification/issues/113) Checking if
-            // this metadata expired isn't part of the spec. Do we actually want to do this?
-            let _ = self.trusted_root_unexpired(start_time)?;
-            let _ = self.trusted_snapshot_unexpired(start_time)?;
-            let trusted_targets = self.trusted_targets_unexpired(start_time)?;
-
-            if trusted_targets.delegations().is_empty() {
-                return Err(Error::UnauthorizedDelegation {
-                    parent_role: parent_role.clone(),
-                    child_role: role.clone(),
-                });
-            };
+        raw_targets: &RawSignedMetadata<D, TargetsMetadata>,
+    ) -> Result<Verified<LazyTargetsMetadata>> {
+        let role = MetadataPath::targets();
+        let trusted_root = self.trusted_root_unexpired(start_time)?;
+        let trusted_targets_description = self.snapshot_description_unexpired(start_time, &role)?;
+
+        let _ = self.check_metadata_integrity(
+            &MetadataPath::snapshot(),
+            &role,
+            raw_targets,
+            trusted_targets_description,
+        )?;
 
-            let (threshold, keys) = self
-                .find_delegation_threshold_and_keys(parent_role, role)?
-                .ok_or_else(|| Error::UnauthorizedDelegation {
-                    parent_role: parent_role.clone(),
-                    child_role: role.clone(),
-                })?;
+        let new_targets = verify::verify_signatures_lazily(
+            &role,
+            raw_targets,
+            trusted_root.targets().threshold(),
+            trusted_root.targets_keys(),
+        )
+        .map_err(|err| {
+            err.with_trusted_version(self.trusted_targets.as_ref().map(|t| t.version()))
+        })?;
 
-            let trusted_delegated_targets_version =
-                self.trusted_delegations.get(role).map(|t| t.version());
+        self.check_spec_version(&role, new_targets.spec_version())?;
+        self.policy
+            .check_delegations(&role, new_targets.delegations())?;
 
-            self.verify_target_or_delegated_target(
-                start_time,
-                role,
-                raw_delegated_targets,
+        if new_targets.version() != trusted_targets_description.version() {
+            return Err(Error::WrongMetadataVersion {
+                parent_role: MetadataPath::snapshot(),
+                child_role: role,
+                expected_version: trusted_targets_description.version(),
+                new_version: new_targets.version(),
+            });
+        }
+
+        if let Some(trusted_targets) = &self.trusted_targets {
+            if new_targets.version() < trusted_targets.version() {
+                return Err(Error::AttemptedMetadataRollBack {
+                    role,
+                    trusted_version: trusted_targets.version(),
+                    new_version: new_targets.version(),
+                });
+            }
+        }
+
+        self.check_rollback_state(&role, new_targets.version())?;
+
+        if new_targets.expires() <= start_time {
+            return Err(Error::ExpiredMetadata {
+                path: role,
+                version: new_targets.version(),
+                trusted_version: self.trusted_targets.as_ref().map(|t| t.version()),
+                expiration: *new_targets.expires(),
+                now: *start_time,
+            });
+        }
+
+        Ok(new_targets)
+    }
+
+    /// Check the signatures of a new root metadata against the keys [Database::update_root] would
+    /// check them against. The first report is for the keys of the trusted root metadata, and the
+    /// second for the keys of the new root metadata itself. See [verify::check_signatures].
+    ///
+    /// **WARNING**: The new root metadata is deserialized to find its own keys before it has
+    /// been verified. This exposes us to potential parser exploits.
+    pub fn check_root_signatures(
+        &self,
+        raw_root: &RawSignedMetadata<D, RootMetadata>,
+    ) -> Result<(SignatureReport, SignatureReport)> {
+        let trusted_report = verify::check_signatures(
+            &MetadataPath::root(),
+            raw_root,
+            self.trusted_root.root().threshold(),
+            self.trusted_root.root_keys(),
+        )?;
+
+        let new_root = raw_root.parse_untrusted()?.assume_valid()?;
+        let new_report = verify::check_signatures(
+            &MetadataPath::root(),
+            raw_root,
+            new_root.root().threshold(),
+            new_root.root_keys(),
+        )?;
+
+        Ok((trusted_report, new_report))
+    }
+
+    /// Check the signatures of a timestamp metadata against the keys
+    /// [Database::update_timestamp] would check them against. See [verify::check_signatures].
+    pub fn check_timestamp_signatures(
+        &self,
+        raw_timestamp: &RawSignedMetadata<D, TimestampMetadata>,
+    ) -> Result<SignatureReport> {
+        verify::check_signatures(
+            &MetadataPath::timestamp(),
+            raw_timestamp,
+            self.trusted_root.timestamp().threshold(),
+            self.trusted_root.timestamp_keys(),
+        )
+    }
+
+    /// Check the signatures of a snapshot metadata against the keys [Database::update_snapshot]
+    /// would check them against. See [verify::check_signatures].
+    pub fn check_snapshot_signatures(
+        &self,
+        raw_snapshot: &RawSignedMetadata<D, SnapshotMetadata>,
+    ) -> Result<SignatureReport> {
+        verify::check_signatures(
+            &MetadataPath::snapshot(),
+            raw_snapshot,
+            self.trusted_root.snapshot().threshold(),
+            self.trusted_root.snapshot_keys(),
+        )
+    }
+
+    /// Check the signatures of a targets metadata against the keys [Database::update_targets]
+    /// would check them against. See [verify::check_signatures].
+    pub fn check_targets_signatures(
+        &self,
+        raw_targets: &RawSignedMetadata<D, TargetsMetadata>,
+    ) -> Result<SignatureReport> {
+        verify::check_signatures(
+            &MetadataPath::targets(),
+            raw_targets,
+            self.trusted_root.targets().threshold(),
+            self.trusted_root.targets_keys(),
+        )
+    }
+
+    /// Check the signatures of a delegated targets metadata against the keys
+    /// [Database::update_delegated_targets] would check them against. See
+    /// [verify::check_signatures].
+    pub fn check_delegated_targets_signatures(
+        &self,
+        parent_role: &MetadataPath,
+        role: &MetadataPath,
+        raw_delegated_targets: &RawSignedMetadata<D, TargetsMetadata>,
+    ) -> Result<SignatureReport> {
+        let (threshold, keys) = self
+            .find_delegation_threshold_and_keys(parent_role, role)?
+            .ok_or_else(|| Error::UnauthorizedDelegation {
+                parent_role: parent_role.clone(),
+                child_role: role.clone(),
+            })?;
+
+        verify::check_signatures(role, raw_delegated_targets, threshold, keys)
+    }
+
+    /// Verify and update a delegation metadata.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                parent_role = %parent_role,
+                role = %role,
+                bytes = raw_delegated_targets.as_bytes().len(),
+            ),
+            err,
+        )
+    )]
+    pub fn update_delegated_targets(
+        &mut self,
+        start_time: &DateTime<Utc>,
+        parent_role: &MetadataPath,
+        role: &MetadataPath,
+        raw_delegated_targets: &RawSignedMetadata<D, TargetsMetadata>,
+    ) -> Result<bool> {
+        let verified = {
+            // FIXME(https://github.com/theupdateframework/specification/issues/113) Checking if
+            // this metadata expired isn't part of the spec. Do we actually want to do this?
+            let _ = self.trusted_root_unexpired(start_time)?;
+            self.check_snapshot_unexpired(start_time)?;
+            let trusted_targets = self.trusted_targets_unexpired(start_time)?;
+
+            if trusted_targets.delegations().is_empty() {
+                return Err(Error::UnauthorizedDelegation {
+                    parent_role: parent_role.clone(),
+                    child_role: role.clone(),
+                });
+            };
+
+            let (threshold, keys) = self
+                .find_delegation_threshold_and_keys(parent_role, role)?
+                .ok_or_else(|| Error::UnauthorizedDelegation {
+                    parent_role: parent_role.clone(),
+                    child_role: role.clone(),
+                })?;
+
+            let trusted_delegated_targets_version =
+                self.trusted_delegations.get(role).map(|t| t.version());
+
+            self.verify_target_or_delegated_target(
+                start_time,
+                role,
+                raw_delegated_targets,
                 threshold,
                 keys.into_iter(),
                 trusted_delegated_targets_version,
             )?
         };
 
-        if let Some(verified) = verified {
-            let _ = self.trusted_delegations.insert(role.clone(), verified);
+        if let Some((verified, integrity)) = verified {
+            self.record_rollback_state(role, verified.version())?;
+            let _ = Arc::make_mut(&mut self.trusted_delegations)
+                .insert(role.clone(), Arc::new(verified));
+            let _ = Arc::make_mut(&mut self.targets_integrity).insert(role.clone(), integrity);
+            let _ = Arc::make_mut(&mut self.raw_delegations)
+                .insert(role.clone(), Arc::new(clone_raw(raw_delegated_targets)));
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
+    /// Verify and update the metadata of a custom top-level role. See [CustomMetadata] for
+    /// details.
+    ///
+    /// Custom roles are not listed in the snapshot metadata, so like the timestamp metadata, the
+    /// new metadata is only checked against the role's definition in the trusted root metadata
+    /// and against the trusted metadata of the role.
+    pub fn update_custom_role(
+        &mut self,
+        start_time: &DateTime<Utc>,
+        role: &MetadataPath,
+        raw_custom: &RawSignedMetadata<D, CustomMetadata>,
+    ) -> Result<bool> {
+        let verified = {
+            let trusted_root = self.trusted_root_unexpired(start_time)?;
+            let definition =
+                trusted_root
+                    .custom_role(role)
+                    .ok_or_else(|| Error::UnauthorizedDelegation {
+                        parent_role: MetadataPath::root(),
+                        child_role: role.clone(),
+                    })?;
+
+            let new_custom = verify::verify_signatures(
+                role,
+                raw_custom,
+                definition.threshold(),
+                trusted_root.custom_role_keys(role),
+            )
+            .map_err(|err| {
+                err.with_trusted_version(self.trusted_custom_roles.get(role).map(|c| c.version()))
+            })?;
+
+            if new_custom.role() != role {
+                return Err(Error::Encoding(format!(
+                    "Attempted to decode {} metadata labeled as {:?}",
+                    role,
+                    new_custom.role().as_str()
+                )));
+            }
+
+            self.check_spec_version(role, new_custom.spec_version())?;
+            self.policy
+                .check_custom_role(role, trusted_root.custom_role_keys(role))?;
+
+            if let Some(trusted_custom) = self.trusted_custom_roles.get(role) {
+                match new_custom.version().cmp(&trusted_custom.version()) {
+                    Ordering::Less => {
+                        return Err(Error::AttemptedMetadataRollBack {
+                            role: role.clone(),
+                            trusted_version: trusted_custom.version(),
+                            new_version: new_custom.version(),
+                        });
+                    }
+                    Ordering::Equal => {
+                        return Ok(false);
+                    }
+                    Ordering::Greater => {}
+                }
+            }
+
+            self.check_rollback_state(role, new_custom.version())?;
+
+            if new_custom.expires() <= start_time {
+                return Err(Error::ExpiredMetadata {
+                    path: role.clone(),
+                    version: new_custom.version(),
+                    trusted_version: self.trusted_custom_roles.get(role).map(|c| c.version()),
+                    expiration: *new_custom.expires(),
+                    now: *start_time,
+                });
+            }
+
+            new_custom
+        };
+
+        self.record_rollback_state(role, verified.version())?;
+        let _ =
+            Arc::make_mut(&mut self.trusted_custom_roles).insert(role.clone(), Arc::new(verified));
+
+        Ok(true)
+    }
+
     fn verify_target_or_delegated_target<'a>(
         &self,
         start_time: &DateTime<Utc>,
@@ -727,19 +1734,10 @@ impl<D: Pouf> Database<D> {
         trusted_targets_threshold: u32,
         trusted_targets_keys: impl Iterator<Item = &'a PublicKey>,
         trusted_targets_version: Option<u32>,
-    ) -> Result<Option<Verified<TargetsMetadata>>> {
+    ) -> Result<Option<(Verified<TargetsMetadata>, MetadataIntegrity)>> {
         // FIXME(https://github.com/theupdateframework/specification/issues/113) Checking if
         // this metadata expired isn't part of the spec. Do we actually want to do this?
-        let trusted_snapshot = self.trusted_snapshot_unexpired(start_time)?;
-
-        let trusted_targets_description =
-            trusted_snapshot
-                .meta()
-                .get(role)
-                .ok_or_else(|| Error::MissingMetadataDescription {
-                    parent_role: MetadataPath::snapshot(),
-                    child_role: role.clone(),
-                })?;
+        let trusted_targets_description = self.snapshot_description_unexpired(start_time, role)?;
 
         /////////////////////////////////////////
         // TUF-1.0.5 §5.4.1:
@@ -750,9 +1748,14 @@ impl<D: Pouf> Database<D> {
         //     mix-and-match attack by man-in-the-middle attackers. If the new targets metadata
         //     file does not match, discard it, abort the update cycle, and report the failure.
 
-        // FIXME: rust-tuf checks the hash during download, but it would be better if we
-        // checked the hash here to make it easier to validate we've correctly implemented the
-        // spec.
+        // NOTE: rust-tuf also checks the hashes during download, so that a mismatched file is not
+        // downloaded in full.
+        let integrity = self.check_metadata_integrity(
+            &MetadataPath::snapshot(),
+            role,
+            raw_targets,
+            trusted_targets_description,
+        )?;
 
         // NOTE(https://github.com/theupdateframework/specification/pull/112): Technically
         // we're supposed to check the version before checking the signature, but we do it
@@ -772,7 +1775,11 @@ impl<D: Pouf> Database<D> {
             raw_targets,
             trusted_targets_threshold,
             trusted_targets_keys,
-        )?;
+        )
+        .map_err(|err| err.with_trusted_version(trusted_targets_version))?;
+
+        self.check_spec_version(role, new_targets.spec_version())?;
+        self.policy.check_targets(role, &new_targets)?;
 
         /////////////////////////////////////////
         // FIXME(https://github.com/theupdateframework/specification/pull/112): Actually check
@@ -805,6 +1812,8 @@ impl<D: Pouf> Database<D> {
             }
         }
 
+        self.check_rollback_state(role, new_targets.version())?;
+
         /////////////////////////////////////////
         // TUF-1.0.5 §5.4.3:
         //
@@ -817,12 +1826,81 @@ impl<D: Pouf> Database<D> {
         if new_targets.expires() <= start_time {
             return Err(Error::ExpiredMetadata {
                 path: role.clone(),
+                version: new_targets.version(),
+                trusted_version: trusted_targets_version,
                 expiration: *new_targets.expires(),
                 now: *start_time,
             });
         }
 
-        Ok(Some(new_targets))
+        Ok(Some((new_targets, integrity)))
+    }
+
+    /// Check `raw` against the length and hashes that its description in the trusted metadata of
+    /// `parent_role` declares, according to the [MetadataIntegrityPolicy].
+    fn check_metadata_integrity<M: Metadata>(
+        &self,
+        parent_role: &MetadataPath,
+        role: &MetadataPath,
+        raw: &RawSignedMetadata<D, M>,
+        description: &MetadataDescription<M>,
+    ) -> Result<MetadataIntegrity> {
+        let bytes = raw.as_bytes();
+
+        let length_checked = match description.length() {
+            Some(length) if length != bytes.len() => {
+                return Err(Error::MetadataIntegrityMismatch {
+                    role: role.clone(),
+                    reason: format!(
+                        "{} declares length {}, but found {}",
+                        parent_role,
+                        length,
+                        bytes.len()
+                    ),
+                });
+            }
+            Some(_) => true,
+            None => false,
+        };
+
+        let hashes = crypto::retain_supported_hashes(description.hashes());
+        let hashes_checked = if hashes.is_empty() {
+            false
+        } else {
+            let hash_algs = hashes
+                .iter()
+                .map(|(alg, _)| (*alg).clone())
+                .collect::<Vec<_>>();
+            let actual_hashes = crypto::calculate_hashes_from_slice(bytes, &hash_algs)?;
+
+            for (alg, expected) in hashes {
+                if actual_hashes.get(alg) != Some(&expected) {
+                    return Err(Error::MetadataIntegrityMismatch {
+                        role: role.clone(),
+                        reason: format!("{} hash declared by {} does not match", alg, parent_role),
+                    });
+                }
+            }
+
+            true
+        };
+
+        if self.metadata_integrity_policy == MetadataIntegrityPolicy::Strict
+            && !(length_checked && hashes_checked)
+        {
+            return Err(Error::MetadataIntegrityMismatch {
+                role: role.clone(),
+                reason: format!(
+                    "{} does not declare both a length and a supported hash",
+                    parent_role
+                ),
+            });
+        }
+
+        Ok(MetadataIntegrity {
+            length_checked,
+            hashes_checked,
+        })
     }
 
     /// Find the signing keys and metadata for the delegation given by `role`, as seen from the
@@ -842,7 +1920,7 @@ impl<D: Pouf> Database<D> {
                     version: MetadataVersion::None,
                 });
             }
-        } else if let Some(trusted_parent) = self.trusted_delegations.get(parent_role) {
+        } else if let Some(trusted_parent) = self.trusted_delegation(parent_role) {
             trusted_parent
         } else {
             return Err(Error::MetadataNotFound {
@@ -854,28 +1932,25 @@ impl<D: Pouf> Database<D> {
         // Only consider targets metadata that define delegations.
         let trusted_delegations = trusted_parent.delegations();
 
-        for trusted_delegation in trusted_delegations.roles() {
-            if trusted_delegation.name() != role {
-                continue;
-            }
-
-            // Filter the delegations keys to just the ones for this delegation.
-            let authorized_keys = trusted_delegations
-                .keys()
-                .iter()
-                .filter_map(|(k, v)| {
-                    if trusted_delegation.key_ids().contains(k) {
-                        Some(v)
-                    } else {
-                        None
-                    }
-                })
-                .collect();
+        let trusted_delegation = match trusted_delegations.delegation(role) {
+            Some(trusted_delegation) => trusted_delegation,
+            None => return Ok(None),
+        };
 
-            return Ok(Some((trusted_delegation.threshold(), authorized_keys)));
-        }
+        // Filter the delegations keys to just the ones for this delegation.
+        let authorized_keys = trusted_delegations
+            .keys()
+            .iter()
+            .filter_map(|(k, v)| {
+                if trusted_delegation.key_ids().contains(k) {
+                    Some(v)
+                } else {
+                    None
+                }
+            })
+            .collect();
 
-        Ok(None)
+        Ok(Some((trusted_delegation.threshold(), authorized_keys)))
     }
 
     /// Get a reference to the description needed to verify the target defined by the given
@@ -883,7 +1958,7 @@ impl<D: Pouf> Database<D> {
     /// metadata. This may mean the target exists somewhere in the metadata, but the chain of trust
     /// to that target may be invalid or incomplete.
     pub fn target_description(&self, target_path: &TargetPath) -> Result<TargetDescription> {
-        self.target_description_with_start_time(&Utc::now(), target_path)
+        self.target_description_with_start_time(&self.clock.now(), target_path)
     }
 
     /// Get a reference to the description needed to verify the target defined by the given
@@ -895,12 +1970,25 @@ impl<D: Pouf> Database<D> {
         start_time: &DateTime<Utc>,
         target_path: &TargetPath,
     ) -> Result<TargetDescription> {
+        self.resolve_target_with_start_time(start_time, target_path)
+            .map(|(description, _)| description)
+    }
+
+    /// Look up the trusted description of `target_path` like
+    /// [Database::target_description_with_start_time], along with the role that signed it. For a
+    /// target trusted through a multi-role delegation, this is the first of the roles that agree
+    /// on its description.
+    pub(crate) fn resolve_target_with_start_time(
+        &self,
+        start_time: &DateTime<Utc>,
+        target_path: &TargetPath,
+    ) -> Result<(TargetDescription, MetadataPath)> {
         let _ = self.trusted_root_unexpired(start_time)?;
-        let _ = self.trusted_snapshot_unexpired(start_time)?;
+        self.check_snapshot_unexpired(start_time)?;
         let targets = self.trusted_targets_unexpired(start_time)?;
 
         if let Some(d) = targets.targets().get(target_path) {
-            return Ok(d.clone());
+            return Ok((d.clone(), MetadataPath::targets()));
         }
 
         fn lookup<'a, D: Pouf>(
@@ -911,62 +1999,167 @@ impl<D: Pouf> Database<D> {
             target_path: &TargetPath,
             delegations: &'a Delegations,
             parents: &[HashSet<TargetPath>],
-            visited: &mut HashSet<&'a MetadataPath>,
-        ) -> (bool, Option<TargetDescription>) {
-            for delegation in delegations.roles() {
-                if visited.contains(delegation.name()) {
-                    return (delegation.terminating(), None);
+            visited: &mut HashSet<MetadataPath>,
+        ) -> (bool, Option<(TargetDescription, MetadataPath)>) {
+            // The delegations are consulted in the order they are listed.
+            for entry in delegations.entries_for_target(target_path).iter() {
+                let res = match entry {
+                    DelegationEntry::Single(delegation) => lookup_delegation(
+                        start_time,
+                        tuf,
+                        current_depth,
+                        target_path,
+                        delegation,
+                        parents,
+                        visited,
+                    ),
+                    DelegationEntry::Multi(multi_role_delegation) => lookup_multi_role_delegation(
+                        start_time,
+                        tuf,
+                        current_depth,
+                        target_path,
+                        multi_role_delegation,
+                        parents,
+                        visited,
+                    ),
+                };
+
+                if let Some(res) = res {
+                    return res;
                 }
-                let _ = visited.insert(delegation.name());
+            }
+
+            (default_terminate, None)
+        }
 
-                let mut new_parents = parents.to_owned();
-                new_parents.push(delegation.paths().clone());
+        // Look up `target_path` in the roles of `multi_role_delegation`. Every role is consulted on
+        // its own, and the target is only trusted if enough of them agree on its description.
+        // Returns `None` if the search should continue with the next delegation.
+        fn lookup_multi_role_delegation<D: Pouf>(
+            start_time: &DateTime<Utc>,
+            tuf: &Database<D>,
+            current_depth: u32,
+            target_path: &TargetPath,
+            multi_role_delegation: &MultiRoleDelegation,
+            parents: &[HashSet<TargetPath>],
+            visited: &mut HashSet<MetadataPath>,
+        ) -> Option<(bool, Option<(TargetDescription, MetadataPath)>)> {
+            if !multi_role_delegation.matches_target_with_policy(target_path, tuf.path_matching) {
+                return None;
+            }
 
-                if current_depth > 0 && !target_path.matches_chain(parents) {
-                    return (delegation.terminating(), None);
+            let mut descriptions = vec![];
+            for delegation in multi_role_delegation.roles() {
+                if let Some((_, Some(resolved))) = lookup_delegation(
+                    start_time,
+                    tuf,
+                    current_depth,
+                    target_path,
+                    delegation,
+                    parents,
+                    visited,
+                ) {
+                    descriptions.push(resolved);
                 }
+            }
 
-                let trusted_delegation = match tuf.trusted_delegations.get(delegation.name()) {
-                    Some(trusted_delegation) => trusted_delegation,
-                    None => return (delegation.terminating(), None),
-                };
+            if let Some(description) = multi_role_delegation
+                .agreed_description(descriptions.iter().map(|(description, _)| description))
+            {
+                let resolved = descriptions.into_iter().find(|(d, _)| d == &description);
+                return Some((multi_role_delegation.terminating(), resolved));
+            }
 
-                if trusted_delegation.expires() <= start_time {
-                    return (delegation.terminating(), None);
-                }
+            if multi_role_delegation.terminating() {
+                return Some((true, None));
+            }
 
-                if let Some(target) = trusted_delegation.targets().get(target_path) {
-                    return (delegation.terminating(), Some(target.clone()));
-                }
+            None
+        }
 
-                let trusted_child_delegations = trusted_delegation.delegations();
+        // Look up `target_path` in the targets delegated to `delegation`. Returns `None` if the
+        // search should continue with the next delegation.
+        fn lookup_delegation<D: Pouf>(
+            start_time: &DateTime<Utc>,
+            tuf: &Database<D>,
+            current_depth: u32,
+            target_path: &TargetPath,
+            delegation: &Delegation,
+            parents: &[HashSet<TargetPath>],
+            visited: &mut HashSet<MetadataPath>,
+        ) -> Option<(bool, Option<(TargetDescription, MetadataPath)>)> {
+            // A delegation that isn't trusted with `target_path`, by itself or by any of its
+            // parents, is skipped even if it is terminating.
+            if !delegation.matches_target_with_policy(target_path, tuf.path_matching)
+                || (current_depth > 0 && !tuf.path_matching.matches_chain(target_path, parents))
+            {
+                return None;
+            }
 
-                // We only need to check the child delegations if it delegates to any child roles.
-                if !trusted_child_delegations.roles().is_empty() {
-                    let mut new_parents = parents.to_vec();
-                    new_parents.push(delegation.paths().clone());
-                    let (term, res) = lookup(
-                        start_time,
-                        tuf,
-                        delegation.terminating(),
-                        current_depth + 1,
-                        target_path,
-                        trusted_child_delegations,
-                        &new_parents,
-                        visited,
-                    );
-                    if term {
-                        return (true, res);
-                    } else if res.is_some() {
-                        return (term, res);
-                    }
+            // Otherwise the search only ends here if the delegation is terminating.
+            let unresolved = if delegation.terminating() {
+                Some((true, None))
+            } else {
+                None
+            };
+
+            if visited.contains(delegation.name()) {
+                return unresolved;
+            }
+            let _ = visited.insert(delegation.name().clone());
+
+            let trusted_delegation = match tuf.trusted_delegation(delegation.name()) {
+                Some(trusted_delegation) => trusted_delegation,
+                None => return unresolved,
+            };
+
+            if trusted_delegation.expires() <= start_time {
+                return unresolved;
+            }
+
+            if let Some(target) = trusted_delegation.targets().get(target_path) {
+                return Some((
+                    delegation.terminating(),
+                    Some((target.clone(), delegation.name().clone())),
+                ));
+            }
+
+            let trusted_child_delegations = trusted_delegation.delegations();
+
+            // We only need to check the child delegations if it delegates to any child roles.
+            if trusted_child_delegations.has_roles() {
+                // A delegation by path hash prefix covers at most `target_path` itself.
+                let paths = if delegation.path_hash_prefixes().is_empty() {
+                    delegation.paths().clone()
+                } else if delegation.matches_target_with_policy(target_path, tuf.path_matching) {
+                    HashSet::from([target_path.clone()])
+                } else {
+                    HashSet::new()
+                };
+                let mut new_parents = parents.to_vec();
+                new_parents.push(paths);
+                let (term, res) = lookup(
+                    start_time,
+                    tuf,
+                    delegation.terminating(),
+                    current_depth + 1,
+                    target_path,
+                    trusted_child_delegations,
+                    &new_parents,
+                    visited,
+                );
+                if term {
+                    return Some((true, res));
+                } else if res.is_some() {
+                    return Some((term, res));
                 }
             }
-            (default_terminate, None)
+
+            unresolved
         }
 
         let delegations = targets.delegations();
-        if delegations.roles().is_empty() {
+        if !delegations.has_roles() {
             Err(Error::TargetNotFound(target_path.clone()))
         } else {
             let mut visited = HashSet::new();
@@ -985,38 +2178,178 @@ impl<D: Pouf> Database<D> {
         }
     }
 
-    fn purge_metadata(&mut self) {
-        self.trusted_snapshot = None;
-        self.trusted_targets = None;
-        self.trusted_timestamp = None;
-        self.trusted_delegations.clear();
-    }
-
-    fn trusted_root_unexpired(&self, start_time: &DateTime<Utc>) -> Result<&RootMetadata> {
-        let trusted_root = &self.trusted_root;
-        if trusted_root.expires() <= start_time {
-            return Err(Error::ExpiredMetadata {
-                path: MetadataPath::root(),
-                expiration: *trusted_root.expires(),
-                now: *start_time,
-            });
-        }
-        Ok(trusted_root)
+    /// Verify that the target read from `read` matches the trusted description of `target_path`,
+    /// without fetching any metadata. This can be used to check the integrity of targets that
+    /// were previously fetched and installed. Returns the trusted [TargetDescription] if the
+    /// length and hashes of the target match.
+    ///
+    /// Fails with [Error::UnverifiableTarget] if the trusted description of the target is missing
+    /// its length or hashes.
+    pub async fn verify_target<R>(
+        &self,
+        target_path: &TargetPath,
+        read: R,
+    ) -> Result<TargetDescription>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let start_time = self.clock.now();
+        self.verify_target_with_start_time(&start_time, target_path, read)
+            .await
     }
 
-    fn trusted_timestamp_unexpired(
+    /// Verify that the target read from `read` matches the trusted description of `target_path`,
+    /// without fetching any metadata.
+    ///
+    /// See [Database::verify_target] for more details.
+    pub async fn verify_target_with_start_time<R>(
+        &self,
+        start_time: &DateTime<Utc>,
+        target_path: &TargetPath,
+        read: R,
+    ) -> Result<TargetDescription>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let target_description =
+            self.target_description_with_start_time(start_time, target_path)?;
+
+        let expected_length = match target_description.length() {
+            Some(length) if !target_description.hashes().is_empty() => length,
+            _ => return Err(Error::UnverifiableTarget(target_path.clone())),
+        };
+
+        let hashes = crypto::retain_supported_hashes(target_description.hashes());
+        if hashes.is_empty() {
+            return Err(Error::NoSupportedHashAlgorithm);
+        }
+
+        let mut read = read.check_length_and_hash(expected_length, hashes)?;
+        let length = copy(&mut read, &mut sink()).await?;
+
+        // The reader only enforces an upper bound on the length.
+        if length != expected_length {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Target length did not match the trusted length.",
+            )));
+        }
+
+        Ok(target_description)
+    }
+
+    fn purge_metadata(&mut self) {
+        self.trusted_snapshot = None;
+        self.snapshot_integrity = None;
+        self.trusted_targets = None;
+        self.trusted_timestamp = None;
+        self.trusted_delegations = Arc::default();
+        self.trusted_custom_roles = Arc::default();
+        self.targets_integrity = Arc::default();
+        self.raw_timestamp = None;
+        self.raw_snapshot = None;
+        self.raw_targets = None;
+        self.raw_delegations = Arc::default();
+        self.snapshot_merkle_entries = Arc::default();
+    }
+
+    /// Check that `version` of the metadata for `role` is not older than the version recorded in
+    /// the [RollbackState].
+    fn check_rollback_state(&self, role: &MetadataPath, version: u32) -> Result<()> {
+        let rollback_state = match &self.rollback_state {
+            Some(rollback_state) => rollback_state,
+            None => return Ok(()),
+        };
+
+        match rollback_state.highest_version(role)? {
+            Some(highest_version) if version < highest_version => {
+                Err(Error::AttemptedMetadataRollBack {
+                    role: role.clone(),
+                    trusted_version: highest_version,
+                    new_version: version,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Record `version` of the metadata for `role` in the [RollbackState]. This is done before the
+    /// metadata is trusted, so the database is left unchanged if the version cannot be recorded.
+    fn record_rollback_state(&self, role: &MetadataPath, version: u32) -> Result<()> {
+        match &self.rollback_state {
+            Some(rollback_state) => rollback_state.record_version(role, version),
+            None => Ok(()),
+        }
+    }
+
+    fn check_spec_version(&self, path: &MetadataPath, spec_version: &SpecVersion) -> Result<()> {
+        if spec_version.major() == self.spec_major_version {
+            Ok(())
+        } else {
+            Err(Error::UnsupportedSpecVersion {
+                path: path.clone(),
+                spec_version: *spec_version,
+                supported_major_version: self.spec_major_version,
+            })
+        }
+    }
+
+    fn check_expiration_with_grace_period(
+        &self,
+        path: &MetadataPath,
+        version: u32,
+        trusted_version: Option<u32>,
+        expiration: &DateTime<Utc>,
+        start_time: &DateTime<Utc>,
+    ) -> Result<()> {
+        if expiration > start_time {
+            return Ok(());
+        }
+
+        if *expiration + self.expiration_grace_period > *start_time {
+            warn!(
+                "metadata {} expired at {}, but is within the grace period of {} at {}",
+                path, expiration, self.expiration_grace_period, start_time
+            );
+            return Ok(());
+        }
+
+        Err(Error::ExpiredMetadata {
+            path: path.clone(),
+            version,
+            trusted_version,
+            expiration: *expiration,
+            now: *start_time,
+        })
+    }
+
+    fn trusted_root_unexpired(&self, start_time: &DateTime<Utc>) -> Result<&RootMetadata> {
+        let trusted_root = &self.trusted_root;
+        if trusted_root.expires() <= start_time {
+            return Err(Error::ExpiredMetadata {
+                path: MetadataPath::root(),
+                version: trusted_root.version(),
+                trusted_version: Some(trusted_root.version()),
+                expiration: *trusted_root.expires(),
+                now: *start_time,
+            });
+        }
+        Ok(trusted_root)
+    }
+
+    fn trusted_timestamp_unexpired(
         &self,
         start_time: &DateTime<Utc>,
     ) -> Result<&TimestampMetadata> {
         match self.trusted_timestamp {
             Some(ref trusted_timestamp) => {
-                if trusted_timestamp.expires() <= start_time {
-                    return Err(Error::ExpiredMetadata {
-                        path: MetadataPath::timestamp(),
-                        expiration: *trusted_timestamp.expires(),
-                        now: *start_time,
-                    });
-                }
+                self.check_expiration_with_grace_period(
+                    &MetadataPath::timestamp(),
+                    trusted_timestamp.version(),
+                    Some(trusted_timestamp.version()),
+                    trusted_timestamp.expires(),
+                    start_time,
+                )?;
                 Ok(trusted_timestamp)
             }
             None => Err(Error::MetadataNotFound {
@@ -1029,13 +2362,13 @@ impl<D: Pouf> Database<D> {
     fn trusted_snapshot_unexpired(&self, start_time: &DateTime<Utc>) -> Result<&SnapshotMetadata> {
         match self.trusted_snapshot {
             Some(ref trusted_snapshot) => {
-                if trusted_snapshot.expires() <= start_time {
-                    return Err(Error::ExpiredMetadata {
-                        path: MetadataPath::snapshot(),
-                        expiration: *trusted_snapshot.expires(),
-                        now: *start_time,
-                    });
-                }
+                self.check_expiration_with_grace_period(
+                    &MetadataPath::snapshot(),
+                    trusted_snapshot.version(),
+                    Some(trusted_snapshot.version()),
+                    trusted_snapshot.expires(),
+                    start_time,
+                )?;
                 Ok(trusted_snapshot)
             }
             None => Err(Error::MetadataNotFound {
@@ -1045,12 +2378,45 @@ impl<D: Pouf> Database<D> {
         }
     }
 
+    /// Check that the snapshot entries are trusted and unexpired: either the trusted snapshot
+    /// metadata, or, for a repository that publishes a snapshot Merkle tree, the trusted timestamp
+    /// metadata that signs the Merkle root.
+    fn check_snapshot_unexpired(&self, start_time: &DateTime<Utc>) -> Result<()> {
+        if self.trusted_snapshot.is_none() && self.trusted_merkle_root().is_some() {
+            let _ = self.trusted_timestamp_unexpired(start_time)?;
+        } else {
+            let _ = self.trusted_snapshot_unexpired(start_time)?;
+        }
+        Ok(())
+    }
+
+    fn snapshot_description_unexpired(
+        &self,
+        start_time: &DateTime<Utc>,
+        role: &MetadataPath,
+    ) -> Result<&MetadataDescription<TargetsMetadata>> {
+        if let Some(description) = self.snapshot_merkle_entries.get(role) {
+            let _ = self.trusted_timestamp_unexpired(start_time)?;
+            return Ok(&**description);
+        }
+
+        self.trusted_snapshot_unexpired(start_time)?
+            .meta()
+            .get(role)
+            .ok_or_else(|| Error::MissingMetadataDescription {
+                parent_role: MetadataPath::snapshot(),
+                child_role: role.clone(),
+            })
+    }
+
     fn trusted_targets_unexpired(&self, start_time: &DateTime<Utc>) -> Result<&TargetsMetadata> {
         match self.trusted_targets {
             Some(ref trusted_targets) => {
                 if trusted_targets.expires() <= start_time {
                     return Err(Error::ExpiredMetadata {
                         path: MetadataPath::targets(),
+                        version: trusted_targets.version(),
+                        trusted_version: Some(trusted_targets.version()),
                         expiration: *trusted_targets.expires(),
                         now: *start_time,
                     });
@@ -1073,6 +2439,22 @@ impl<D: Pouf> Clone for Database<D> {
             trusted_snapshot: self.trusted_snapshot.clone(),
             trusted_timestamp: self.trusted_timestamp.clone(),
             trusted_delegations: self.trusted_delegations.clone(),
+            trusted_custom_roles: self.trusted_custom_roles.clone(),
+            raw_root_history: Arc::clone(&self.raw_root_history),
+            raw_timestamp: self.raw_timestamp.clone(),
+            raw_snapshot: self.raw_snapshot.clone(),
+            raw_targets: self.raw_targets.clone(),
+            raw_delegations: Arc::clone(&self.raw_delegations),
+            snapshot_merkle_entries: self.snapshot_merkle_entries.clone(),
+            clock: Arc::clone(&self.clock),
+            rollback_state: self.rollback_state.clone(),
+            expiration_grace_period: self.expiration_grace_period,
+            spec_major_version: self.spec_major_version,
+            path_matching: self.path_matching,
+            metadata_integrity_policy: self.metadata_integrity_policy,
+            policy: Arc::clone(&self.policy),
+            snapshot_integrity: self.snapshot_integrity,
+            targets_integrity: self.targets_integrity.clone(),
             pouf: PhantomData,
         }
     }
@@ -1081,13 +2463,17 @@ impl<D: Pouf> Clone for Database<D> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::crypto::{Ed25519PrivateKey, HashAlgorithm, PrivateKey};
+    use crate::crypto::{Ed25519PrivateKey, HashAlgorithm, PrivateKey, SignatureScheme};
+    use crate::error::ErrorKind;
     use crate::metadata::{
-        RawSignedMetadataSetBuilder, RootMetadataBuilder, SnapshotMetadataBuilder,
-        TargetsMetadataBuilder, TimestampMetadataBuilder,
+        CustomMetadataBuilder, RawSignedMetadataSetBuilder, RootMetadataBuilder,
+        SignedMetadataBuilder, SnapshotMetadataBuilder, TargetsMetadataBuilder,
+        TimestampMetadataBuilder,
     };
     use crate::pouf::Pouf1;
     use assert_matches::assert_matches;
+    use chrono::offset::TimeZone;
+    use futures_executor::block_on;
     use lazy_static::lazy_static;
     use std::iter::once;
 
@@ -1139,6 +2525,7 @@ mod test {
             Database::from_root_with_trusted_keys(&raw_root, 1, once(KEYS[1].public())),
             Err(Error::MetadataMissingSignatures {
                 role,
+                trusted_version: None,
                 number_of_valid_signatures: 0,
                 threshold: 1,
             })
@@ -1181,6 +2568,7 @@ mod test {
             Database::from_trusted_metadata(&metadata),
             Err(Error::MetadataMissingSignatures {
                 role,
+                trusted_version: None,
                 number_of_valid_signatures: 0,
                 threshold: 1,
             })
@@ -1226,6 +2614,7 @@ mod test {
             Database::from_metadata_with_trusted_keys(&metadata, 1, once(KEYS[1].public())),
             Err(Error::MetadataMissingSignatures {
                 role,
+                trusted_version: None,
                 number_of_valid_signatures: 0,
                 threshold: 1,
             })
@@ -1270,6 +2659,75 @@ mod test {
         );
     }
 
+    #[test]
+    fn check_signatures_reports_per_key_results() {
+        let mut root = RootMetadataBuilder::new()
+            .root_key(KEYS[0].public().clone())
+            .root_key(KEYS[1].public().clone())
+            .root_threshold(2)
+            .snapshot_key(KEYS[0].public().clone())
+            .targets_key(KEYS[0].public().clone())
+            .timestamp_key(KEYS[0].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap();
+        root.add_signature(&KEYS[1]).unwrap();
+        let raw_root = root.to_raw().unwrap();
+
+        let tuf = Database::from_trusted_root(&raw_root).unwrap();
+
+        // The new root is signed by one of the old root keys, by a key that is not trusted, and
+        // not by the new root key.
+        let mut root = RootMetadataBuilder::new()
+            .version(2)
+            .root_key(KEYS[2].public().clone())
+            .snapshot_key(KEYS[2].public().clone())
+            .targets_key(KEYS[2].public().clone())
+            .timestamp_key(KEYS[2].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap();
+        root.add_signature(&KEYS[3]).unwrap();
+        let raw_root = root.to_raw().unwrap();
+
+        let (trusted_report, new_report) = tuf.check_root_signatures(&raw_root).unwrap();
+        assert_eq!(trusted_report.threshold(), 2);
+        assert_eq!(
+            trusted_report.valid_key_ids(),
+            &HashSet::from([KEYS[0].public().key_id().clone()])
+        );
+        assert!(trusted_report.invalid_key_ids().is_empty());
+        assert_eq!(
+            trusted_report.unknown_key_ids(),
+            &HashSet::from([KEYS[3].public().key_id().clone()])
+        );
+        assert_eq!(
+            trusted_report.missing_key_ids(),
+            &HashSet::from([KEYS[1].public().key_id().clone()])
+        );
+        assert!(!trusted_report.is_threshold_met());
+
+        assert!(new_report.valid_key_ids().is_empty());
+        assert_eq!(
+            new_report.missing_key_ids(),
+            &HashSet::from([KEYS[2].public().key_id().clone()])
+        );
+        assert!(!new_report.is_threshold_met());
+
+        let raw_timestamp = TimestampMetadataBuilder::from_snapshot(
+            &SnapshotMetadataBuilder::new()
+                .signed::<Pouf1>(&KEYS[0])
+                .unwrap(),
+            &[HashAlgorithm::Sha256],
+        )
+        .unwrap()
+        .signed::<Pouf1>(&KEYS[0])
+        .unwrap()
+        .to_raw()
+        .unwrap();
+        let report = tuf.check_timestamp_signatures(&raw_timestamp).unwrap();
+        assert_eq!(report.role(), &MetadataPath::timestamp());
+        assert!(report.is_threshold_met());
+    }
+
     #[test]
     fn no_cross_sign_root_rotation() {
         let raw_root = RootMetadataBuilder::new()
@@ -1333,6 +2791,117 @@ mod test {
         assert_matches!(tuf.update_timestamp(&now, &raw_timestamp), Ok(None))
     }
 
+    #[test]
+    fn timestamp_update_spec_major_version() {
+        let now = Utc::now();
+
+        let raw_root = RootMetadataBuilder::new()
+            .root_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[1].public().clone())
+            .targets_key(KEYS[1].public().clone())
+            .timestamp_key(KEYS[1].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+
+        let mut tuf = Database::from_trusted_root(&raw_root).unwrap();
+        assert_eq!(tuf.spec_major_version(), 1);
+
+        let snapshot = SnapshotMetadataBuilder::new()
+            .signed::<Pouf1>(&KEYS[1])
+            .unwrap();
+
+        let make_timestamp = |version, spec_version| {
+            let timestamp =
+                TimestampMetadataBuilder::from_snapshot(&snapshot, &[HashAlgorithm::Sha256])
+                    .unwrap()
+                    .version(version)
+                    .build()
+                    .unwrap()
+                    .with_spec_version(spec_version);
+
+            SignedMetadataBuilder::<Pouf1, _>::from_metadata(&timestamp)
+                .unwrap()
+                .sign(&KEYS[1])
+                .unwrap()
+                .build()
+                .to_raw()
+                .unwrap()
+        };
+
+        // A newer minor version of the spec is accepted, and its version is exposed.
+        let raw_timestamp = make_timestamp(1, SpecVersion::new(1, 1, 0));
+        assert_matches!(tuf.update_timestamp(&now, &raw_timestamp), Ok(Some(_)));
+        assert_eq!(
+            tuf.trusted_timestamp().unwrap().spec_version(),
+            &SpecVersion::new(1, 1, 0)
+        );
+
+        // A different major version is rejected.
+        let raw_timestamp = make_timestamp(2, SpecVersion::new(2, 0, 0));
+        assert_matches!(
+            tuf.update_timestamp(&now, &raw_timestamp),
+            Err(Error::UnsupportedSpecVersion { path, spec_version, supported_major_version: 1 })
+            if path == MetadataPath::timestamp() && spec_version == SpecVersion::new(2, 0, 0)
+        );
+
+        // Unless the database is configured to expect it.
+        tuf.set_spec_major_version(2);
+        assert_matches!(tuf.update_timestamp(&now, &raw_timestamp), Ok(Some(_)));
+    }
+
+    #[test]
+    fn trusted_metadata_expirations() {
+        let now = Utc::now();
+        let root_expires = Utc.with_ymd_and_hms(2038, 1, 1, 0, 0, 0).unwrap();
+        let timestamp_expires = Utc.with_ymd_and_hms(2037, 1, 1, 0, 0, 0).unwrap();
+
+        let raw_root = RootMetadataBuilder::new()
+            .version(3)
+            .expires(root_expires)
+            .root_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[1].public().clone())
+            .targets_key(KEYS[1].public().clone())
+            .timestamp_key(KEYS[1].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+
+        let mut tuf = Database::from_trusted_root(&raw_root).unwrap();
+        assert_eq!(
+            tuf.trusted_metadata_expirations(),
+            vec![MetadataExpiration {
+                path: MetadataPath::root(),
+                version: 3,
+                expires: root_expires,
+            }]
+        );
+
+        let snapshot = SnapshotMetadataBuilder::new()
+            .signed::<Pouf1>(&KEYS[1])
+            .unwrap();
+
+        let raw_timestamp =
+            TimestampMetadataBuilder::from_snapshot(&snapshot, &[HashAlgorithm::Sha256])
+                .unwrap()
+                .version(2)
+                .expires(timestamp_expires)
+                .signed::<Pouf1>(&KEYS[1])
+                .unwrap()
+                .to_raw()
+                .unwrap();
+
+        tuf.update_timestamp(&now, &raw_timestamp).unwrap();
+
+        let expirations = tuf.trusted_metadata_expirations();
+        assert_eq!(expirations.len(), 2);
+        assert_eq!(expirations[1].path(), &MetadataPath::timestamp());
+        assert_eq!(expirations[1].version(), 2);
+        assert_eq!(expirations[1].expires(), &timestamp_expires);
+    }
+
     #[test]
     fn bad_timestamp_update_wrong_key() {
         let now = Utc::now();
@@ -1365,6 +2934,154 @@ mod test {
         assert!(tuf.update_timestamp(&now, &raw_timestamp).is_err())
     }
 
+    #[test]
+    fn policy_rejects_disallowed_algorithms() {
+        let now = Utc::now();
+
+        let raw_root = |version| {
+            RootMetadataBuilder::new()
+                .version(version)
+                .root_key(KEYS[0].public().clone())
+                .snapshot_key(KEYS[1].public().clone())
+                .targets_key(KEYS[1].public().clone())
+                .timestamp_key(KEYS[1].public().clone())
+                .signed::<Pouf1>(&KEYS[0])
+                .unwrap()
+                .to_raw()
+                .unwrap()
+        };
+
+        let mut tuf = Database::from_trusted_root(&raw_root(1)).unwrap();
+        tuf.set_policy(
+            Policy::new()
+                .with_allowed_hash_algorithms(vec![HashAlgorithm::Sha256, HashAlgorithm::Sha512])
+                .with_required_hash_algorithm(HashAlgorithm::Sha512),
+        );
+
+        let snapshot = SnapshotMetadataBuilder::new()
+            .signed::<Pouf1>(&KEYS[1])
+            .unwrap();
+        let timestamp = |hash_algs: &[HashAlgorithm]| {
+            TimestampMetadataBuilder::from_snapshot(&snapshot, hash_algs)
+                .unwrap()
+                .signed::<Pouf1>(&KEYS[1])
+                .unwrap()
+                .to_raw()
+                .unwrap()
+        };
+
+        assert_matches!(
+            tuf.update_timestamp(&now, &timestamp(&[HashAlgorithm::Sha256])),
+            Err(Error::MissingRequiredHashAlgorithm { role, algorithm: HashAlgorithm::Sha512 })
+            if role == MetadataPath::timestamp()
+        );
+        assert_matches!(
+            tuf.update_timestamp(&now, &timestamp(&[HashAlgorithm::Sha384, HashAlgorithm::Sha512])),
+            Err(Error::DisallowedHashAlgorithm { role, algorithm: HashAlgorithm::Sha384 })
+            if role == MetadataPath::timestamp()
+        );
+        assert!(tuf.trusted_timestamp().is_none());
+
+        tuf.update_timestamp(
+            &now,
+            &timestamp(&[HashAlgorithm::Sha256, HashAlgorithm::Sha512]),
+        )
+        .unwrap();
+
+        tuf.set_policy(Policy::new().with_allowed_signature_schemes(vec![]));
+        assert_matches!(
+            tuf.update_root(&raw_root(2)),
+            Err(Error::DisallowedSignatureScheme { role, scheme: SignatureScheme::Ed25519 })
+            if role == MetadataPath::root()
+        );
+        assert_eq!(tuf.trusted_root().version(), 1);
+
+        // The root metadata trusts KEYS[1] for the timestamp, snapshot, and targets roles.
+        tuf.set_policy(Policy::new().with_forbid_key_reuse(true));
+        assert_matches!(
+            tuf.update_root(&raw_root(2)),
+            Err(Error::KeyReuse { key_id, roles })
+            if &key_id == KEYS[1].public().key_id()
+                && roles
+                    == vec![
+                        MetadataPath::timestamp(),
+                        MetadataPath::snapshot(),
+                        MetadataPath::targets(),
+                    ]
+        );
+    }
+
+    #[test]
+    fn timestamp_update_errors_carry_versions() {
+        let now = Utc::now();
+
+        let raw_root = RootMetadataBuilder::new()
+            .root_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[1].public().clone())
+            .targets_key(KEYS[1].public().clone())
+            .timestamp_key(KEYS[1].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+
+        let mut tuf = Database::from_trusted_root(&raw_root).unwrap();
+
+        let snapshot = SnapshotMetadataBuilder::new()
+            .signed::<Pouf1>(&KEYS[1])
+            .unwrap();
+        let timestamp = |version: u32, key: &Ed25519PrivateKey| {
+            TimestampMetadataBuilder::from_snapshot(&snapshot, &[HashAlgorithm::Sha256])
+                .unwrap()
+                .version(version)
+                .expires(now + Duration::days(1))
+                .signed::<Pouf1>(key)
+                .unwrap()
+                .to_raw()
+                .unwrap()
+        };
+
+        tuf.update_timestamp(&now, &timestamp(1, &KEYS[1])).unwrap();
+
+        let err = tuf
+            .update_timestamp(&now, &timestamp(2, &KEYS[0]))
+            .unwrap_err();
+        assert_matches!(
+            err,
+            Error::MetadataMissingSignatures {
+                ref role,
+                trusted_version: Some(1),
+                number_of_valid_signatures: 0,
+                threshold: 1,
+            }
+            if role == &MetadataPath::timestamp()
+        );
+        assert_eq!(err.kind(), ErrorKind::InvalidSignatures);
+
+        let err = tuf
+            .update_timestamp(&(now + Duration::days(2)), &timestamp(2, &KEYS[1]))
+            .unwrap_err();
+        assert_matches!(
+            err,
+            Error::ExpiredMetadata {
+                ref path,
+                version: 2,
+                trusted_version: Some(1),
+                ..
+            }
+            if path == &MetadataPath::timestamp()
+        );
+        assert_eq!(err.kind(), ErrorKind::Freeze);
+        assert_eq!(err.kind().as_str(), "freeze");
+        assert!(err.is_attack());
+
+        assert_eq!(
+            Error::TargetNotFound(TargetPath::new("foo").unwrap()).kind(),
+            ErrorKind::NotFound
+        );
+        assert!(!Error::Cancelled.is_attack());
+    }
+
     #[test]
     fn good_snapshot_update() {
         let now = Utc::now();
@@ -1521,6 +3238,549 @@ mod test {
         assert_matches!(tuf.update_targets(&now, &raw_targets), Ok(false));
     }
 
+    #[test]
+    fn custom_role_update() {
+        let now = Utc::now();
+        let mirrors = MetadataPath::new("mirrors").unwrap();
+
+        let raw_root = RootMetadataBuilder::new()
+            .root_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[1].public().clone())
+            .targets_key(KEYS[2].public().clone())
+            .timestamp_key(KEYS[3].public().clone())
+            .custom_role_key(mirrors.clone(), KEYS[4].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+
+        let mut tuf = Database::from_trusted_root(&raw_root).unwrap();
+
+        let raw_mirrors = |version, key: &Ed25519PrivateKey| {
+            CustomMetadataBuilder::new(mirrors.clone())
+                .version(version)
+                .signed::<Pouf1>(key)
+                .unwrap()
+                .to_raw()
+                .unwrap()
+        };
+
+        assert_matches!(
+            tuf.update_custom_role(&now, &mirrors, &raw_mirrors(2, &KEYS[3])),
+            Err(Error::MetadataMissingSignatures { .. })
+        );
+
+        // The policy applies to the keys of custom roles too.
+        tuf.set_policy(Policy::new().with_allowed_signature_schemes(vec![]));
+        assert_matches!(
+            tuf.update_custom_role(&now, &mirrors, &raw_mirrors(2, &KEYS[4])),
+            Err(Error::DisallowedSignatureScheme { role, scheme: SignatureScheme::Ed25519 })
+            if role == mirrors
+        );
+        assert_eq!(tuf.trusted_custom_role(&mirrors), None);
+        tuf.set_policy(Policy::new());
+
+        assert_matches!(
+            tuf.update_custom_role(&now, &mirrors, &raw_mirrors(2, &KEYS[4])),
+            Ok(true)
+        );
+        assert_eq!(tuf.trusted_custom_role(&mirrors).unwrap().version(), 2);
+
+        // second update should do nothing
+        assert_matches!(
+            tuf.update_custom_role(&now, &mirrors, &raw_mirrors(2, &KEYS[4])),
+            Ok(false)
+        );
+        assert_matches!(
+            tuf.update_custom_role(&now, &mirrors, &raw_mirrors(1, &KEYS[4])),
+            Err(Error::AttemptedMetadataRollBack { .. })
+        );
+
+        let undefined = MetadataPath::new("undefined").unwrap();
+        let raw_undefined = CustomMetadataBuilder::new(undefined.clone())
+            .signed::<Pouf1>(&KEYS[4])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+        assert_matches!(
+            tuf.update_custom_role(&now, &undefined, &raw_undefined),
+            Err(Error::UnauthorizedDelegation { .. })
+        );
+    }
+
+    #[test]
+    fn targets_integrity() {
+        let now = Utc::now();
+
+        let raw_root = RootMetadataBuilder::new()
+            .root_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[1].public().clone())
+            .targets_key(KEYS[2].public().clone())
+            .timestamp_key(KEYS[3].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+
+        let raw_targets = TargetsMetadataBuilder::new()
+            .signed::<Pouf1>(&KEYS[2])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+        let length = raw_targets.as_bytes().len();
+        let hashes =
+            crypto::calculate_hashes_from_slice(raw_targets.as_bytes(), &[HashAlgorithm::Sha256])
+                .unwrap();
+
+        let update = |policy, length, hashes| {
+            let mut tuf = Database::<Pouf1>::from_trusted_root(&raw_root).unwrap();
+            tuf.set_metadata_integrity_policy(policy);
+
+            let snapshot = SnapshotMetadataBuilder::new()
+                .insert_metadata_description(
+                    MetadataPath::targets(),
+                    MetadataDescription::new(1, length, hashes).unwrap(),
+                )
+                .signed::<Pouf1>(&KEYS[1])
+                .unwrap();
+            let raw_timestamp =
+                TimestampMetadataBuilder::from_snapshot(&snapshot, &[HashAlgorithm::Sha256])
+                    .unwrap()
+                    .signed::<Pouf1>(&KEYS[3])
+                    .unwrap()
+                    .to_raw()
+                    .unwrap();
+
+            tuf.update_timestamp(&now, &raw_timestamp).unwrap();
+            tuf.update_snapshot(&now, &snapshot.to_raw().unwrap())
+                .unwrap();
+            tuf.update_targets(&now, &raw_targets)
+                .map(|_| tuf.targets_integrity(&MetadataPath::targets()).unwrap())
+        };
+
+        let integrity = update(
+            MetadataIntegrityPolicy::Strict,
+            Some(length),
+            hashes.clone(),
+        )
+        .unwrap();
+        assert!(integrity.length_checked());
+        assert!(integrity.hashes_checked());
+
+        let integrity = update(MetadataIntegrityPolicy::Lenient, None, HashMap::new()).unwrap();
+        assert!(!integrity.length_checked());
+        assert!(!integrity.hashes_checked());
+
+        assert_matches!(
+            update(MetadataIntegrityPolicy::Strict, None, hashes.clone()),
+            Err(Error::MetadataIntegrityMismatch { role, .. })
+            if role == MetadataPath::targets()
+        );
+        assert_matches!(
+            update(MetadataIntegrityPolicy::Lenient, Some(length + 1), hashes),
+            Err(Error::MetadataIntegrityMismatch { .. })
+        );
+
+        let mut bad_hashes = HashMap::new();
+        let _ = bad_hashes.insert(HashAlgorithm::Sha256, HashValue::new(vec![0; 32]));
+        assert_matches!(
+            update(MetadataIntegrityPolicy::Lenient, Some(length), bad_hashes),
+            Err(Error::MetadataIntegrityMismatch { .. })
+        );
+    }
+
+    #[test]
+    fn snapshot_integrity() {
+        let now = Utc::now();
+
+        let raw_root = RootMetadataBuilder::new()
+            .root_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[1].public().clone())
+            .targets_key(KEYS[2].public().clone())
+            .timestamp_key(KEYS[3].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+
+        let raw_snapshot = SnapshotMetadataBuilder::new()
+            .signed::<Pouf1>(&KEYS[1])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+        let length = raw_snapshot.as_bytes().len();
+        let hashes =
+            crypto::calculate_hashes_from_slice(raw_snapshot.as_bytes(), &[HashAlgorithm::Sha256])
+                .unwrap();
+
+        let update = |policy, length, hashes| {
+            let mut tuf = Database::<Pouf1>::from_trusted_root(&raw_root).unwrap();
+            tuf.set_metadata_integrity_policy(policy);
+
+            let raw_timestamp = TimestampMetadataBuilder::from_metadata_description(
+                MetadataDescription::new(1, length, hashes).unwrap(),
+            )
+            .signed::<Pouf1>(&KEYS[3])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+
+            tuf.update_timestamp(&now, &raw_timestamp).unwrap();
+            assert_eq!(tuf.snapshot_integrity(), None);
+            tuf.update_snapshot(&now, &raw_snapshot)
+                .map(|_| tuf.snapshot_integrity().unwrap())
+        };
+
+        let integrity = update(
+            MetadataIntegrityPolicy::Strict,
+            Some(length),
+            hashes.clone(),
+        )
+        .unwrap();
+        assert!(integrity.length_checked());
+        assert!(integrity.hashes_checked());
+
+        let integrity = update(MetadataIntegrityPolicy::Lenient, None, HashMap::new()).unwrap();
+        assert!(!integrity.length_checked());
+        assert!(!integrity.hashes_checked());
+
+        assert_matches!(
+            update(MetadataIntegrityPolicy::Strict, Some(length), HashMap::new()),
+            Err(Error::MetadataIntegrityMismatch { role, .. })
+            if role == MetadataPath::snapshot()
+        );
+        assert_matches!(
+            update(MetadataIntegrityPolicy::Lenient, Some(length - 1), hashes),
+            Err(Error::MetadataIntegrityMismatch { .. })
+        );
+
+        let mut bad_hashes = HashMap::new();
+        let _ = bad_hashes.insert(HashAlgorithm::Sha256, HashValue::new(vec![0; 32]));
+        assert_matches!(
+            update(MetadataIntegrityPolicy::Lenient, None, bad_hashes),
+            Err(Error::MetadataIntegrityMismatch { role, .. })
+            if role == MetadataPath::snapshot()
+        );
+    }
+
+    #[test]
+    fn raw_metadata_set_round_trip() {
+        let now = Utc::now();
+
+        let raw_root = RootMetadataBuilder::new()
+            .root_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[1].public().clone())
+            .targets_key(KEYS[2].public().clone())
+            .timestamp_key(KEYS[3].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+
+        let mut tuf = Database::from_trusted_root(&raw_root).unwrap();
+
+        let raw_root = RootMetadataBuilder::new()
+            .version(2)
+            .root_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[1].public().clone())
+            .targets_key(KEYS[2].public().clone())
+            .timestamp_key(KEYS[3].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+        tuf.update_root(&raw_root).unwrap();
+
+        let delegation_path = MetadataPath::new("delegation").unwrap();
+        let signed_delegation = TargetsMetadataBuilder::new()
+            .insert_target_from_slice(
+                TargetPath::new("foo/bar").unwrap(),
+                b"bar",
+                &[HashAlgorithm::Sha256],
+            )
+            .unwrap()
+            .signed::<Pouf1>(&KEYS[4])
+            .unwrap();
+
+        let signed_targets = TargetsMetadataBuilder::new()
+            .delegations(
+                Delegations::builder()
+                    .key(KEYS[4].public().clone())
+                    .role(
+                        Delegation::builder(delegation_path.clone())
+                            .key(KEYS[4].public())
+                            .delegate_path(TargetPath::new("foo/").unwrap())
+                            .build()
+                            .unwrap(),
+                    )
+                    .build()
+                    .unwrap(),
+            )
+            .signed::<Pouf1>(&KEYS[2])
+            .unwrap();
+
+        let snapshot = SnapshotMetadataBuilder::new()
+            .insert_metadata(&signed_targets, &[HashAlgorithm::Sha256])
+            .unwrap()
+            .insert_metadata_with_path("delegation", &signed_delegation, &[HashAlgorithm::Sha256])
+            .unwrap()
+            .signed::<Pouf1>(&KEYS[1])
+            .unwrap();
+
+        let raw_timestamp =
+            TimestampMetadataBuilder::from_snapshot(&snapshot, &[HashAlgorithm::Sha256])
+                .unwrap()
+                .signed::<Pouf1>(&KEYS[3])
+                .unwrap()
+                .to_raw()
+                .unwrap();
+
+        tuf.update_timestamp(&now, &raw_timestamp).unwrap();
+        tuf.update_snapshot(&now, &snapshot.to_raw().unwrap())
+            .unwrap();
+        tuf.update_targets(&now, &signed_targets.to_raw().unwrap())
+            .unwrap();
+        tuf.update_delegated_targets(
+            &now,
+            &MetadataPath::targets(),
+            &delegation_path,
+            &signed_delegation.to_raw().unwrap(),
+        )
+        .unwrap();
+
+        let delegation = tuf.trusted_delegation(&delegation_path).unwrap();
+        assert_eq!(delegation.version(), 1);
+        assert_eq!(
+            tuf.trusted_delegation_expirations().collect::<Vec<_>>(),
+            vec![MetadataExpiration::new(
+                delegation_path.clone(),
+                &**delegation
+            )]
+        );
+        assert_eq!(
+            tuf.trusted_delegation(&MetadataPath::new("missing").unwrap()),
+            None
+        );
+
+        let metadata_set = tuf.to_raw_metadata_set();
+        assert_eq!(metadata_set.root_history().len(), 2);
+        assert_eq!(metadata_set.root(), &raw_root);
+        assert_eq!(metadata_set.timestamp(), Some(&raw_timestamp));
+        assert_eq!(metadata_set.delegations().len(), 1);
+
+        let bytes = metadata_set.to_vec().unwrap();
+        let decoded = RawTrustedMetadataSet::<Pouf1>::from_slice(&bytes).unwrap();
+        assert_eq!(decoded, metadata_set);
+
+        let restored = Database::from_raw_metadata_set(&decoded).unwrap();
+        assert_eq!(restored.trusted_root(), tuf.trusted_root());
+        assert_eq!(restored.trusted_timestamp(), tuf.trusted_timestamp());
+        assert_eq!(restored.trusted_snapshot(), tuf.trusted_snapshot());
+        assert_eq!(restored.trusted_targets(), tuf.trusted_targets());
+        assert_eq!(restored.trusted_delegations(), tuf.trusted_delegations());
+        assert_eq!(restored.to_raw_metadata_set(), metadata_set);
+
+        // Restoring verifies the metadata again.
+        assert_matches!(
+            Database::from_raw_metadata_set_with_start_time(&decoded, &(now + Duration::days(365))),
+            Err(Error::ExpiredMetadata { .. })
+        );
+        assert_matches!(
+            RawTrustedMetadataSet::<Pouf1>::from_slice(br#"{"root_history": []}"#),
+            Err(Error::MetadataNotFound { .. })
+        );
+    }
+
+    /// A delegated role along with the targets it describes, as paths and contents.
+    type DelegatedRole<'a> = (&'a MetadataPath, &'a [(&'a str, &'a [u8])]);
+
+    /// A database whose top-level targets delegates to `delegations` in order, and which trusts
+    /// the delegated targets metadata in `roles`. Every delegated role is signed by `KEYS[4]`.
+    fn database_with_delegations(
+        now: &DateTime<Utc>,
+        delegations: Vec<Delegation>,
+        roles: &[DelegatedRole<'_>],
+    ) -> Database<Pouf1> {
+        let raw_root = RootMetadataBuilder::new()
+            .root_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[1].public().clone())
+            .targets_key(KEYS[2].public().clone())
+            .timestamp_key(KEYS[3].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+        let mut tuf = Database::from_trusted_root(&raw_root).unwrap();
+
+        let signed_delegations = roles
+            .iter()
+            .map(|(role, targets)| {
+                let mut builder = TargetsMetadataBuilder::new();
+                for (path, contents) in targets.iter() {
+                    builder = builder
+                        .insert_target_from_slice(
+                            TargetPath::new(path.to_string()).unwrap(),
+                            contents,
+                            &[HashAlgorithm::Sha256],
+                        )
+                        .unwrap();
+                }
+                (*role, builder.signed::<Pouf1>(&KEYS[4]).unwrap())
+            })
+            .collect::<Vec<_>>();
+
+        let mut delegations_builder = Delegations::builder().key(KEYS[4].public().clone());
+        for delegation in delegations {
+            delegations_builder = delegations_builder.role(delegation);
+        }
+        let signed_targets = TargetsMetadataBuilder::new()
+            .delegations(delegations_builder.build().unwrap())
+            .signed::<Pouf1>(&KEYS[2])
+            .unwrap();
+
+        let mut snapshot = SnapshotMetadataBuilder::new()
+            .insert_metadata(&signed_targets, &[HashAlgorithm::Sha256])
+            .unwrap();
+        for (role, signed_delegation) in &signed_delegations {
+            snapshot = snapshot
+                .insert_metadata_with_path(
+                    role.as_str().to_string(),
+                    signed_delegation,
+                    &[HashAlgorithm::Sha256],
+                )
+                .unwrap();
+        }
+        let snapshot = snapshot.signed::<Pouf1>(&KEYS[1]).unwrap();
+
+        let raw_timestamp =
+            TimestampMetadataBuilder::from_snapshot(&snapshot, &[HashAlgorithm::Sha256])
+                .unwrap()
+                .signed::<Pouf1>(&KEYS[3])
+                .unwrap()
+                .to_raw()
+                .unwrap();
+
+        tuf.update_timestamp(now, &raw_timestamp).unwrap();
+        tuf.update_snapshot(now, &snapshot.to_raw().unwrap())
+            .unwrap();
+        tuf.update_targets(now, &signed_targets.to_raw().unwrap())
+            .unwrap();
+        for (role, signed_delegation) in &signed_delegations {
+            tuf.update_delegated_targets(
+                now,
+                &MetadataPath::targets(),
+                role,
+                &signed_delegation.to_raw().unwrap(),
+            )
+            .unwrap();
+        }
+
+        tuf
+    }
+
+    #[test]
+    fn target_description_checks_delegated_paths() {
+        let now = Utc::now();
+        let delegation = |name: &MetadataPath, terminating, path: &str| {
+            Delegation::new(
+                name.clone(),
+                terminating,
+                1,
+                HashSet::from([KEYS[4].public().key_id().clone()]),
+                HashSet::from([TargetPath::new(path.to_string()).unwrap()]),
+            )
+            .unwrap()
+        };
+
+        let unmatched = MetadataPath::new("unmatched").unwrap();
+        let untrusted = MetadataPath::new("untrusted").unwrap();
+        let trusted = MetadataPath::new("trusted").unwrap();
+        let tuf = database_with_delegations(
+            &now,
+            vec![
+                delegation(&unmatched, true, "other/"),
+                delegation(&untrusted, false, "foo/"),
+                delegation(&trusted, false, "foo/"),
+            ],
+            &[
+                (&unmatched, &[("foo/bar", b"unmatched")]),
+                (
+                    &trusted,
+                    &[("foo/bar", b"trusted"), ("other/baz", b"trusted")],
+                ),
+            ],
+        );
+
+        // A terminating delegation that doesn't match the target doesn't end the search, and
+        // neither does a non-terminating delegation whose metadata isn't trusted.
+        let foo_bar = TargetPath::new("foo/bar").unwrap();
+        assert_eq!(
+            tuf.resolve_target_with_start_time(&now, &foo_bar).unwrap(),
+            (
+                TargetDescription::from_slice(b"trusted", &[HashAlgorithm::Sha256]).unwrap(),
+                trusted.clone()
+            )
+        );
+
+        // A role can't describe a target outside of the paths delegated to it.
+        let other_baz = TargetPath::new("other/baz").unwrap();
+        assert_matches!(
+            tuf.target_description_with_start_time(&now, &other_baz),
+            Err(Error::TargetNotFound(p)) if p == other_baz
+        );
+
+        // A matching terminating delegation ends the search, even if it lacks the target.
+        let tuf = database_with_delegations(
+            &now,
+            vec![
+                delegation(&untrusted, true, "foo/"),
+                delegation(&trusted, false, "foo/"),
+            ],
+            &[(&trusted, &[("foo/bar", b"trusted")])],
+        );
+        assert_matches!(
+            tuf.target_description_with_start_time(&now, &foo_bar),
+            Err(Error::TargetNotFound(p)) if p == foo_bar
+        );
+    }
+
+    #[test]
+    fn verify_target_rejects_unauthorized_delegated_target() {
+        block_on(async {
+            let now = Utc::now();
+            let role = MetadataPath::new("delegation").unwrap();
+            let delegation = Delegation::builder(role.clone())
+                .key(KEYS[4].public())
+                .delegate_path(TargetPath::new("foo/").unwrap())
+                .build()
+                .unwrap();
+            let tuf = database_with_delegations(
+                &now,
+                vec![delegation],
+                &[(&role, &[("foo/bar", b"bar"), ("other/baz", b"baz")])],
+            );
+
+            assert_eq!(
+                tuf.verify_target_with_start_time(
+                    &now,
+                    &TargetPath::new("foo/bar").unwrap(),
+                    &b"bar"[..]
+                )
+                .await
+                .unwrap(),
+                TargetDescription::from_slice(b"bar", &[HashAlgorithm::Sha256]).unwrap()
+            );
+
+            // The role lists a target outside of the paths delegated to it.
+            let other_baz = TargetPath::new("other/baz").unwrap();
+            assert_matches!(
+                tuf.verify_target_with_start_time(&now, &other_baz, &b"baz"[..])
+                    .await,
+                Err(Error::TargetNotFound(p)) if p == other_baz
+            );
+        })
+    }
+
     #[test]
     fn bad_targets_update_wrong_key() {
         let now = Utc::now();
@@ -1784,6 +4044,7 @@ mod test {
             tuf.update_metadata(&metadata2),
             Err(Error::MetadataMissingSignatures {
                 role,
+                trusted_version: Some(1),
                 number_of_valid_signatures: 0,
                 threshold: 1,
             })
@@ -0,0 +1,443 @@
+//! A binary cache of the state of a [Database].
+
+use chrono::Duration;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use super::{Database, MetadataIntegrity, MetadataIntegrityPolicy};
+use crate::clock::SystemClock;
+use crate::crypto::{self, HashAlgorithm, HashValue};
+use crate::error::Error;
+use crate::metadata::{
+    CustomMetadata, Metadata, MetadataDescription, MetadataPath, PathMatching, RawSignedMetadata,
+    RootMetadata, SnapshotMetadata, SpecVersion, TargetsMetadata, TimestampMetadata,
+};
+use crate::pouf::{Der, Pouf};
+use crate::verify::Verified;
+use crate::Result;
+
+/// The bytes every cache starts with.
+const MAGIC: &[u8] = b"TUFCACHE";
+
+/// The version of the cache format, which is bumped whenever the format changes.
+const FORMAT_VERSION: u32 = 1;
+
+/// The length of the SHA-256 checksum at the end of the cache.
+const CHECKSUM_LEN: usize = 32;
+
+/// The verified metadata of a [Database]. The raw metadata follows it in the cache, in the order
+/// of `raw`.
+#[derive(Serialize, Deserialize)]
+struct CacheState {
+    root: RootMetadata,
+    timestamp: Option<TimestampMetadata>,
+    snapshot: Option<SnapshotMetadata>,
+    targets: Option<TargetsMetadata>,
+    delegations: BTreeMap<MetadataPath, TargetsMetadata>,
+    custom_roles: BTreeMap<MetadataPath, CustomMetadata>,
+    snapshot_merkle_entries: BTreeMap<MetadataPath, MetadataDescription<TargetsMetadata>>,
+    snapshot_integrity: Option<CachedIntegrity>,
+    targets_integrity: BTreeMap<MetadataPath, CachedIntegrity>,
+    raw: Vec<RawSection>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedIntegrity {
+    length_checked: bool,
+    hashes_checked: bool,
+}
+
+/// Which raw metadata a section of the cache holds.
+#[derive(Serialize, Deserialize)]
+enum RawSection {
+    Root,
+    Timestamp,
+    Snapshot,
+    Targets,
+    Delegation(MetadataPath),
+}
+
+impl<D: Pouf> Database<D> {
+    /// Serialize the trusted state of this database into a compact binary cache, which
+    /// [Database::from_cache] restores without parsing the raw metadata or verifying its
+    /// signatures again. This is meant to cut the startup time of clients on slow devices.
+    ///
+    /// The cache starts with a format version and ends with a SHA-256 checksum, and it is
+    /// rejected if either doesn't match. The checksum only detects corruption: anyone who can
+    /// write the cache can make the restored database trust any metadata, so it must be stored
+    /// where only the client can write, and [Database::from_raw_metadata_set] should be used
+    /// whenever the cache is rejected.
+    ///
+    /// The verified metadata is encoded with the [Der] pouf rather than a format like bincode,
+    /// since metadata can carry arbitrary JSON values that such formats cannot represent. The
+    /// clock, rollback state, policies and other settings of the database are not cached.
+    pub fn to_cache(&self) -> Result<Vec<u8>> {
+        let integrity = |integrity: &MetadataIntegrity| CachedIntegrity {
+            length_checked: integrity.length_checked,
+            hashes_checked: integrity.hashes_checked,
+        };
+
+        let mut raw: Vec<(RawSection, &[u8])> = self
+            .raw_root_history
+            .iter()
+            .map(|root| (RawSection::Root, root.as_bytes()))
+            .collect();
+        if let Some(timestamp) = &self.raw_timestamp {
+            raw.push((RawSection::Timestamp, timestamp.as_bytes()));
+        }
+        if let Some(snapshot) = &self.raw_snapshot {
+            raw.push((RawSection::Snapshot, snapshot.as_bytes()));
+        }
+        if let Some(targets) = &self.raw_targets {
+            raw.push((RawSection::Targets, targets.as_bytes()));
+        }
+        let mut delegations = self.raw_delegations.iter().collect::<Vec<_>>();
+        delegations.sort_by_key(|(role, _)| *role);
+        for (role, delegation) in delegations {
+            raw.push((RawSection::Delegation(role.clone()), delegation.as_bytes()));
+        }
+
+        let (sections, raw): (Vec<_>, Vec<_>) = raw.into_iter().unzip();
+        let state = CacheState {
+            root: (**self.trusted_root).clone(),
+            timestamp: self.trusted_timestamp.as_ref().map(|m| (***m).clone()),
+            snapshot: self.trusted_snapshot.as_ref().map(|m| (***m).clone()),
+            targets: self.trusted_targets.as_ref().map(|m| (***m).clone()),
+            delegations: self
+                .trusted_delegations
+                .iter()
+                .map(|(role, m)| (role.clone(), (***m).clone()))
+                .collect(),
+            custom_roles: self
+                .trusted_custom_roles
+                .iter()
+                .map(|(role, m)| (role.clone(), (***m).clone()))
+                .collect(),
+            snapshot_merkle_entries: self
+                .snapshot_merkle_entries
+                .iter()
+                .map(|(role, description)| (role.clone(), (**description).clone()))
+                .collect(),
+            snapshot_integrity: self.snapshot_integrity.as_ref().map(integrity),
+            targets_integrity: self
+                .targets_integrity
+                .iter()
+                .map(|(role, i)| (role.clone(), integrity(i)))
+                .collect(),
+            raw: sections,
+        };
+        let state = Der::canonicalize(&Der::serialize(&state)?)?;
+
+        let mut buf = MAGIC.to_vec();
+        buf.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+        for section in std::iter::once(&state[..]).chain(raw) {
+            let len = u32::try_from(section.len())
+                .map_err(|_| Error::Encoding("database cache section is too long".into()))?;
+            buf.extend_from_slice(&len.to_be_bytes());
+            buf.extend_from_slice(section);
+        }
+        let checksum = checksum(&buf)?;
+        buf.extend_from_slice(checksum.value());
+
+        Ok(buf)
+    }
+
+    /// Restore a database from a cache written by [Database::to_cache], trusting the metadata it
+    /// contains without verifying it again. See [Database::to_cache] for when this is safe.
+    ///
+    /// Returns [Error::Encoding] if the cache was written with another version of the format, or
+    /// if it is corrupt.
+    pub fn from_cache(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < MAGIC.len() + 4 + CHECKSUM_LEN || !bytes.starts_with(MAGIC) {
+            return Err(Error::Encoding("not a database cache".into()));
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+        if self::checksum(body)?.value() != checksum {
+            return Err(Error::Encoding("database cache checksum mismatch".into()));
+        }
+
+        let mut sections = Sections(&body[MAGIC.len()..]);
+        let version = u32::from_be_bytes(sections.take(4)?.try_into().unwrap());
+        if version != FORMAT_VERSION {
+            return Err(Error::Encoding(format!(
+                "unsupported database cache version {}",
+                version
+            )));
+        }
+
+        let state: CacheState = Der::from_slice(sections.section()?)?;
+        let integrity = |integrity: CachedIntegrity| MetadataIntegrity {
+            length_checked: integrity.length_checked,
+            hashes_checked: integrity.hashes_checked,
+        };
+
+        let mut db = Database {
+            trusted_root: Arc::new(Verified::from_trusted_cache(state.root)),
+            trusted_targets: state
+                .targets
+                .map(|m| Arc::new(Verified::from_trusted_cache(m))),
+            trusted_snapshot: state
+                .snapshot
+                .map(|m| Arc::new(Verified::from_trusted_cache(m))),
+            trusted_timestamp: state
+                .timestamp
+                .map(|m| Arc::new(Verified::from_trusted_cache(m))),
+            trusted_delegations: Arc::new(
+                state
+                    .delegations
+                    .into_iter()
+                    .map(|(role, m)| (role, Arc::new(Verified::from_trusted_cache(m))))
+                    .collect(),
+            ),
+            trusted_custom_roles: Arc::new(
+                state
+                    .custom_roles
+                    .into_iter()
+                    .map(|(role, m)| (role, Arc::new(Verified::from_trusted_cache(m))))
+                    .collect(),
+            ),
+            raw_root_history: Arc::default(),
+            raw_timestamp: None,
+            raw_snapshot: None,
+            raw_targets: None,
+            raw_delegations: Arc::default(),
+            snapshot_merkle_entries: Arc::new(
+                state
+                    .snapshot_merkle_entries
+                    .into_iter()
+                    .map(|(role, description)| (role, Arc::new(description)))
+                    .collect(),
+            ),
+            clock: Arc::new(SystemClock),
+            rollback_state: None,
+            expiration_grace_period: Duration::zero(),
+            spec_major_version: SpecVersion::SUPPORTED_MAJOR_VERSION,
+            path_matching: PathMatching::default(),
+            metadata_integrity_policy: MetadataIntegrityPolicy::default(),
+            policy: Arc::default(),
+            snapshot_integrity: state.snapshot_integrity.map(integrity),
+            targets_integrity: Arc::new(
+                state
+                    .targets_integrity
+                    .into_iter()
+                    .map(|(role, i)| (role, integrity(i)))
+                    .collect(),
+            ),
+            pouf: PhantomData,
+        };
+
+        let mut root_history = vec![];
+        let mut raw_delegations = HashMap::new();
+        for section in state.raw {
+            let raw = sections.section()?;
+            match section {
+                RawSection::Root => root_history.push(Arc::new(raw_metadata(raw))),
+                RawSection::Timestamp => db.raw_timestamp = Some(Arc::new(raw_metadata(raw))),
+                RawSection::Snapshot => db.raw_snapshot = Some(Arc::new(raw_metadata(raw))),
+                RawSection::Targets => db.raw_targets = Some(Arc::new(raw_metadata(raw))),
+                RawSection::Delegation(role) => {
+                    let _ = raw_delegations.insert(role, Arc::new(raw_metadata(raw)));
+                }
+            }
+        }
+        if !sections.0.is_empty() {
+            return Err(Error::Encoding("trailing data in database cache".into()));
+        }
+        if root_history.is_empty() {
+            return Err(Error::Encoding(
+                "database cache has no root metadata".into(),
+            ));
+        }
+        db.raw_root_history = Arc::new(root_history);
+        db.raw_delegations = Arc::new(raw_delegations);
+
+        Ok(db)
+    }
+}
+
+fn raw_metadata<D: Pouf, M: Metadata>(raw: &[u8]) -> RawSignedMetadata<D, M> {
+    RawSignedMetadata::new(raw.to_vec())
+}
+
+/// The SHA-256 digest that ends a cache, over everything before it.
+fn checksum(data: &[u8]) -> Result<HashValue> {
+    let mut hashes = crypto::calculate_hashes_from_slice(data, &[HashAlgorithm::Sha256])?;
+    Ok(hashes.remove(&HashAlgorithm::Sha256).unwrap())
+}
+
+/// The length-prefixed sections of a cache.
+struct Sections<'a>(&'a [u8]);
+
+impl<'a> Sections<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(Error::Encoding("truncated database cache".into()));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn section(&mut self) -> Result<&'a [u8]> {
+        let len = u32::from_be_bytes(self.take(4)?.try_into().unwrap());
+        self.take(len as usize)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{Ed25519PrivateKey, PrivateKey};
+    use crate::metadata::{
+        Delegation, Delegations, RootMetadataBuilder, SnapshotMetadataBuilder, TargetPath,
+        TargetsMetadataBuilder, TimestampMetadataBuilder,
+    };
+    use crate::pouf::Pouf1;
+    use assert_matches::assert_matches;
+    use chrono::Utc;
+    use lazy_static::lazy_static;
+    use pretty_assertions::assert_eq;
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[
+                include_bytes!("../../tests/ed25519/ed25519-1.pk8.der"),
+                include_bytes!("../../tests/ed25519/ed25519-2.pk8.der"),
+            ];
+            keys.iter()
+                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+                .collect()
+        };
+    }
+
+    fn database() -> Database<Pouf1> {
+        let now = Utc::now();
+        let delegated = MetadataPath::new("delegated").unwrap();
+
+        let root = |version| {
+            RootMetadataBuilder::new()
+                .version(version)
+                .root_key(KEYS[0].public().clone())
+                .snapshot_key(KEYS[0].public().clone())
+                .targets_key(KEYS[0].public().clone())
+                .timestamp_key(KEYS[0].public().clone())
+                .signed::<Pouf1>(&KEYS[0])
+                .unwrap()
+                .to_raw()
+                .unwrap()
+        };
+        let delegated_targets = TargetsMetadataBuilder::new()
+            .insert_target_from_slice(
+                TargetPath::new("delegated/foo").unwrap(),
+                b"foo",
+                &[HashAlgorithm::Sha256],
+            )
+            .unwrap()
+            .signed::<Pouf1>(&KEYS[1])
+            .unwrap();
+        let targets = TargetsMetadataBuilder::new()
+            .delegations(
+                Delegations::builder()
+                    .key(KEYS[1].public().clone())
+                    .role(
+                        Delegation::builder(delegated.clone())
+                            .key(KEYS[1].public())
+                            .delegate_path(TargetPath::new("delegated/").unwrap())
+                            .build()
+                            .unwrap(),
+                    )
+                    .build()
+                    .unwrap(),
+            )
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap();
+        let snapshot = SnapshotMetadataBuilder::new()
+            .insert_metadata(&targets, &[HashAlgorithm::Sha256])
+            .unwrap()
+            .insert_metadata_with_path("delegated", &delegated_targets, &[HashAlgorithm::Sha256])
+            .unwrap()
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap();
+        let timestamp =
+            TimestampMetadataBuilder::from_snapshot(&snapshot, &[HashAlgorithm::Sha256])
+                .unwrap()
+                .signed::<Pouf1>(&KEYS[0])
+                .unwrap();
+
+        let mut db = Database::from_trusted_root(&root(1)).unwrap();
+        db.update_root(&root(2)).unwrap();
+        db.update_timestamp(&now, &timestamp.to_raw().unwrap())
+            .unwrap();
+        db.update_snapshot(&now, &snapshot.to_raw().unwrap())
+            .unwrap();
+        db.update_targets(&now, &targets.to_raw().unwrap()).unwrap();
+        db.update_delegated_targets(
+            &now,
+            &MetadataPath::targets(),
+            &delegated,
+            &delegated_targets.to_raw().unwrap(),
+        )
+        .unwrap();
+        db
+    }
+
+    #[test]
+    fn cache_round_trip() {
+        let db = database();
+        let restored = Database::<Pouf1>::from_cache(&db.to_cache().unwrap()).unwrap();
+
+        assert_eq!(restored.trusted_root(), db.trusted_root());
+        assert_eq!(restored.trusted_timestamp(), db.trusted_timestamp());
+        assert_eq!(restored.trusted_snapshot(), db.trusted_snapshot());
+        assert_eq!(restored.trusted_targets(), db.trusted_targets());
+        assert_eq!(restored.trusted_delegations(), db.trusted_delegations());
+        assert_eq!(restored.snapshot_integrity(), db.snapshot_integrity());
+        assert_eq!(
+            restored.targets_integrity(&MetadataPath::targets()),
+            db.targets_integrity(&MetadataPath::targets())
+        );
+        assert_eq!(restored.to_raw_metadata_set(), db.to_raw_metadata_set());
+        assert_eq!(restored.to_raw_metadata_set().root_history().len(), 2);
+
+        // The restored database can be restored from its raw metadata as well.
+        let reverified = Database::from_raw_metadata_set(&restored.to_raw_metadata_set()).unwrap();
+        assert_eq!(reverified.trusted_delegations(), db.trusted_delegations());
+    }
+
+    #[test]
+    fn cache_rejects_corruption() {
+        let cache = database().to_cache().unwrap();
+
+        let mut corrupt = cache.clone();
+        corrupt[MAGIC.len() + 20] ^= 1;
+        assert_matches!(
+            Database::<Pouf1>::from_cache(&corrupt),
+            Err(Error::Encoding(msg)) if msg.contains("checksum")
+        );
+
+        assert_matches!(
+            Database::<Pouf1>::from_cache(&cache[..cache.len() - 1]),
+            Err(Error::Encoding(_))
+        );
+        assert_matches!(
+            Database::<Pouf1>::from_cache(b"TUFCACHE"),
+            Err(Error::Encoding(_))
+        );
+    }
+
+    #[test]
+    fn cache_rejects_other_versions() {
+        let mut cache = database().to_cache().unwrap();
+        cache.truncate(cache.len() - CHECKSUM_LEN);
+        cache[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&2u32.to_be_bytes());
+        let checksum = crypto::calculate_hash(&cache, &HashAlgorithm::Sha256);
+        cache.extend_from_slice(checksum.value());
+
+        assert_matches!(
+            Database::<Pouf1>::from_cache(&cache),
+            Err(Error::Encoding(msg)) if msg.contains("version 2")
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Sharing a [Database] between many tasks.
+
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+
+use crate::database::Database;
+use crate::pouf::Pouf;
+
+/// A [Database] that can be read concurrently from many tasks while it is being updated, such as
+/// by a server that verifies requests from many clients against the same trusted metadata.
+///
+/// Readers get an immutable snapshot of the database with [SharedDatabase::read], which they can
+/// keep for as long as they need without blocking updates. The database can only be changed
+/// through a [DatabaseUpdate] from [SharedDatabase::begin_update], which works on a copy of the
+/// database, and publishes it to new readers once it is committed. Since the trusted metadata is
+/// shared between copies of a [Database], neither reading nor updating copies the metadata that
+/// the update does not replace.
+///
+/// ```
+/// # use tuf::crypto::{Ed25519PrivateKey, PrivateKey};
+/// # use tuf::database::{Database, SharedDatabase};
+/// # use tuf::metadata::{Metadata, RootMetadataBuilder};
+/// # use tuf::pouf::Pouf1;
+/// # let key = Ed25519PrivateKey::from_pkcs8(
+/// #     &Ed25519PrivateKey::pkcs8().unwrap(),
+/// # ).unwrap();
+/// # let raw_root = |version| RootMetadataBuilder::new()
+/// #     .version(version)
+/// #     .root_key(key.public().clone())
+/// #     .snapshot_key(key.public().clone())
+/// #     .targets_key(key.public().clone())
+/// #     .timestamp_key(key.public().clone())
+/// #     .signed::<Pouf1>(&key)
+/// #     .unwrap()
+/// #     .to_raw()
+/// #     .unwrap();
+/// let shared = SharedDatabase::new(Database::<Pouf1>::from_trusted_root(&raw_root(1)).unwrap());
+/// let snapshot = shared.read();
+///
+/// let mut update = shared.begin_update();
+/// update.update_root(&raw_root(2)).unwrap();
+/// update.commit();
+///
+/// assert_eq!(snapshot.trusted_root().version(), 1);
+/// assert_eq!(shared.read().trusted_root().version(), 2);
+/// ```
+pub struct SharedDatabase<D: Pouf> {
+    inner: Arc<Inner<D>>,
+}
+
+struct Inner<D: Pouf> {
+    current: RwLock<Arc<Database<D>>>,
+    // Held by the update in progress, so that concurrent updates do not overwrite each other.
+    update: Mutex<()>,
+}
+
+impl<D: Pouf> SharedDatabase<D> {
+    /// Share `database` between tasks.
+    pub fn new(database: Database<D>) -> Self {
+        SharedDatabase {
+            inner: Arc::new(Inner {
+                current: RwLock::new(Arc::new(database)),
+                update: Mutex::new(()),
+            }),
+        }
+    }
+
+    /// An immutable snapshot of the most recently committed database. The snapshot does not
+    /// observe updates that are committed after it is taken.
+    pub fn read(&self) -> Arc<Database<D>> {
+        Arc::clone(&self.inner.current.read().unwrap())
+    }
+
+    /// Start updating the database. Only one update can be in progress at a time, so this blocks
+    /// until any other update in progress is committed or dropped.
+    pub fn begin_update(&self) -> DatabaseUpdate<'_, D> {
+        let guard = self.inner.update.lock().unwrap();
+        let database = Database::clone(&self.read());
+
+        DatabaseUpdate {
+            current: &self.inner.current,
+            _guard: guard,
+            database,
+        }
+    }
+}
+
+impl<D: Pouf> Clone for SharedDatabase<D> {
+    fn clone(&self) -> Self {
+        SharedDatabase {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<D: Pouf> From<Database<D>> for SharedDatabase<D> {
+    fn from(database: Database<D>) -> Self {
+        SharedDatabase::new(database)
+    }
+}
+
+impl<D: Pouf> fmt::Debug for SharedDatabase<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedDatabase")
+            .field("current", &self.read())
+            .finish_non_exhaustive()
+    }
+}
+
+/// An update in progress of a [SharedDatabase]. It dereferences to a copy of the database that
+/// can be updated with the usual [Database] methods, and [DatabaseUpdate::commit] publishes the
+/// copy to new readers. Dropping the update without committing it discards the copy.
+pub struct DatabaseUpdate<'a, D: Pouf> {
+    current: &'a RwLock<Arc<Database<D>>>,
+    _guard: MutexGuard<'a, ()>,
+    database: Database<D>,
+}
+
+impl<'a, D: Pouf> DatabaseUpdate<'a, D> {
+    /// Publish the updated database, so that it is returned by later calls to
+    /// [SharedDatabase::read].
+    pub fn commit(self) {
+        let DatabaseUpdate {
+            current,
+            _guard,
+            database,
+        } = self;
+
+        *current.write().unwrap() = Arc::new(database);
+    }
+}
+
+impl<'a, D: Pouf> Deref for DatabaseUpdate<'a, D> {
+    type Target = Database<D>;
+
+    fn deref(&self) -> &Database<D> {
+        &self.database
+    }
+}
+
+impl<'a, D: Pouf> DerefMut for DatabaseUpdate<'a, D> {
+    fn deref_mut(&mut self) -> &mut Database<D> {
+        &mut self.database
+    }
+}
+
+impl<'a, D: Pouf> fmt::Debug for DatabaseUpdate<'a, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DatabaseUpdate")
+            .field("database", &self.database)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{Ed25519PrivateKey, PrivateKey};
+    use crate::metadata::{Metadata, RawSignedMetadata, RootMetadata, RootMetadataBuilder};
+    use crate::pouf::Pouf1;
+    use std::thread;
+
+    fn raw_root(key: &Ed25519PrivateKey, version: u32) -> RawSignedMetadata<Pouf1, RootMetadata> {
+        RootMetadataBuilder::new()
+            .version(version)
+            .root_key(key.public().clone())
+            .snapshot_key(key.public().clone())
+            .targets_key(key.public().clone())
+            .timestamp_key(key.public().clone())
+            .signed::<Pouf1>(key)
+            .unwrap()
+            .to_raw()
+            .unwrap()
+    }
+
+    #[test]
+    fn shared_database_updates() {
+        let key = Ed25519PrivateKey::from_pkcs8(&Ed25519PrivateKey::pkcs8().unwrap()).unwrap();
+        let shared =
+            SharedDatabase::new(Database::<Pouf1>::from_trusted_root(&raw_root(&key, 1)).unwrap());
+        let snapshot = shared.read();
+
+        // A dropped update is discarded.
+        {
+            let mut update = shared.begin_update();
+            update.update_root(&raw_root(&key, 2)).unwrap();
+            assert_eq!(update.trusted_root().version(), 2);
+        }
+        assert_eq!(shared.read().trusted_root().version(), 1);
+
+        let mut update = shared.begin_update();
+        update.update_root(&raw_root(&key, 2)).unwrap();
+
+        // Readers do not observe the update until it is committed.
+        let reader = shared.clone();
+        thread::spawn(move || assert_eq!(reader.read().trusted_root().version(), 1))
+            .join()
+            .unwrap();
+
+        update.commit();
+
+        let reader = shared.clone();
+        thread::spawn(move || assert_eq!(reader.read().trusted_root().version(), 2))
+            .join()
+            .unwrap();
+        assert_eq!(snapshot.trusted_root().version(), 1);
+    }
+}
@@ -0,0 +1,571 @@
+//! Exporting the trusted delegation structure of a repository.
+//!
+//! A [DelegationGraph] lists every targets role that a [Database] knows of, and every delegation
+//! between them, along with the keys, threshold and target paths of each delegation. It can be
+//! serialized to hand it to other tooling, or rendered with [DelegationGraph::to_dot] to draw it
+//! with [Graphviz](https://graphviz.org/), so operators can see who can sign what.
+//!
+//! Only delegations in trusted metadata are included. A role whose metadata has not been fetched
+//! yet is still a node of the graph, but its own delegations are unknown.
+
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::crypto::KeyId;
+use crate::database::Database;
+use crate::metadata::{
+    Delegation, DelegationEntry, Delegations, Metadata, MetadataPath, TargetPath,
+};
+use crate::pouf::Pouf;
+
+/// The delegations between the targets roles of a repository.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DelegationGraph {
+    roles: BTreeMap<MetadataPath, RoleNode>,
+    delegations: Vec<DelegationEdge>,
+    succinct_delegations: Vec<SuccinctDelegationEdge>,
+}
+
+impl DelegationGraph {
+    /// Build the graph from the trusted metadata in `db`, starting with the delegation of the
+    /// top-level targets role by the root.
+    pub fn from_database<D: Pouf>(db: &Database<D>) -> Self {
+        let mut graph = DelegationGraph::default();
+
+        let root = db.trusted_root();
+        graph.add_role(MetadataPath::root(), Some(root.version()));
+        graph.add_role(
+            MetadataPath::targets(),
+            db.trusted_targets().map(|targets| targets.version()),
+        );
+        graph.delegations.push(DelegationEdge {
+            delegator: MetadataPath::root(),
+            role: MetadataPath::targets(),
+            threshold: root.targets().threshold(),
+            key_ids: root.targets().key_ids().iter().cloned().collect(),
+            paths: BTreeSet::new(),
+            path_hash_prefixes: BTreeSet::new(),
+            terminating: false,
+            min_roles_in_agreement: None,
+        });
+
+        if let Some(targets) = db.trusted_targets() {
+            graph.add_delegations(db, &MetadataPath::targets(), targets.delegations());
+        }
+
+        let mut delegators = db.trusted_delegations().iter().collect::<Vec<_>>();
+        delegators.sort_by_key(|(delegator, _)| *delegator);
+        for (delegator, targets) in delegators {
+            graph.add_role(delegator.clone(), Some(targets.version()));
+            graph.add_delegations(db, delegator, targets.delegations());
+        }
+
+        graph
+    }
+
+    /// Every role in the graph, sorted by path.
+    pub fn roles(&self) -> impl Iterator<Item = &RoleNode> {
+        self.roles.values()
+    }
+
+    /// The role at `path`, if it is in the graph.
+    pub fn role(&self, path: &MetadataPath) -> Option<&RoleNode> {
+        self.roles.get(path)
+    }
+
+    /// Every delegation in the graph, in the order they are listed by their delegators.
+    pub fn delegations(&self) -> &[DelegationEdge] {
+        &self.delegations
+    }
+
+    /// The delegations from `delegator`.
+    pub fn delegations_from<'a>(
+        &'a self,
+        delegator: &'a MetadataPath,
+    ) -> impl Iterator<Item = &'a DelegationEdge> + 'a {
+        self.delegations
+            .iter()
+            .filter(move |edge| &edge.delegator == delegator)
+    }
+
+    /// Every delegation of hash bins with succinct roles in the graph. The bins are not listed
+    /// as roles, since there can be billions of them.
+    pub fn succinct_delegations(&self) -> &[SuccinctDelegationEdge] {
+        &self.succinct_delegations
+    }
+
+    /// Render the graph in the DOT language of [Graphviz](https://graphviz.org/). Roles whose
+    /// metadata is trusted are drawn solid and the other roles dashed. Terminating delegations
+    /// are drawn bold.
+    ///
+    /// ```
+    /// # use tuf::crypto::{Ed25519PrivateKey, PrivateKey};
+    /// # use tuf::database::Database;
+    /// # use tuf::delegation_graph::DelegationGraph;
+    /// # use tuf::metadata::RootMetadataBuilder;
+    /// # use tuf::pouf::Pouf1;
+    /// #
+    /// # let key: &[u8] = include_bytes!("../tests/ed25519/ed25519-1.pk8.der");
+    /// # let key = Ed25519PrivateKey::from_pkcs8(&key).unwrap();
+    /// let raw_root = RootMetadataBuilder::new()
+    ///     .root_key(key.public().clone())
+    ///     .snapshot_key(key.public().clone())
+    ///     .targets_key(key.public().clone())
+    ///     .timestamp_key(key.public().clone())
+    ///     .signed::<Pouf1>(&key)
+    ///     .unwrap()
+    ///     .to_raw()
+    ///     .unwrap();
+    /// let db = Database::from_trusted_root(&raw_root).unwrap();
+    ///
+    /// let dot = DelegationGraph::from_database(&db).to_dot();
+    /// assert!(dot.contains("\"root\" -> \"targets\" [label=\"1 of 1 keys\"];"));
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph delegations {\n");
+
+        for node in self.roles.values() {
+            let (label, style) = match node.version {
+                Some(version) => (format!("{}\\nversion {}", node.role, version), "solid"),
+                None => (node.role.to_string(), "dashed"),
+            };
+            let _ = writeln!(
+                dot,
+                "    {} [label={}, style={}];",
+                dot_id(node.role.as_str()),
+                dot_id(&label),
+                style
+            );
+        }
+
+        for edge in &self.delegations {
+            let mut label = format!("{} of {} keys", edge.threshold, edge.key_ids.len());
+            if let Some(min_roles) = edge.min_roles_in_agreement {
+                let _ = write!(label, "\\n{} roles must agree", min_roles);
+            }
+            for path in &edge.paths {
+                let _ = write!(label, "\\n{}", path);
+            }
+            for prefix in &edge.path_hash_prefixes {
+                let _ = write!(label, "\\nhash prefix {}", prefix);
+            }
+
+            let _ = writeln!(
+                dot,
+                "    {} -> {} [label={}{}];",
+                dot_id(edge.delegator.as_str()),
+                dot_id(edge.role.as_str()),
+                dot_id(&label),
+                if edge.terminating { ", style=bold" } else { "" }
+            );
+        }
+
+        for edge in &self.succinct_delegations {
+            let bins = format!("{}-*", edge.name_prefix);
+            let _ = writeln!(
+                dot,
+                "    {} [label={}, shape=box3d];",
+                dot_id(&bins),
+                dot_id(&format!("{}\\n{} bins", bins, 1u64 << edge.bit_length))
+            );
+            let _ = writeln!(
+                dot,
+                "    {} -> {} [label={}];",
+                dot_id(edge.delegator.as_str()),
+                dot_id(&bins),
+                dot_id(&format!(
+                    "{} of {} keys",
+                    edge.threshold,
+                    edge.key_ids.len()
+                ))
+            );
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn add_role(&mut self, role: MetadataPath, version: Option<u32>) {
+        let node = self.roles.entry(role.clone()).or_insert(RoleNode {
+            role,
+            version: None,
+        });
+        if version.is_some() {
+            node.version = version;
+        }
+    }
+
+    fn add_delegations<D: Pouf>(
+        &mut self,
+        db: &Database<D>,
+        delegator: &MetadataPath,
+        delegations: &Delegations,
+    ) {
+        for entry in delegations.entries() {
+            match entry {
+                DelegationEntry::Single(delegation) => {
+                    self.add_delegation(db, delegator, delegation, None);
+                }
+                DelegationEntry::Multi(multi_role) => {
+                    for delegation in multi_role.roles() {
+                        self.add_delegation(
+                            db,
+                            delegator,
+                            delegation,
+                            Some(multi_role.min_roles_in_agreement()),
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(succinct_roles) = delegations.succinct_roles() {
+            self.succinct_delegations.push(SuccinctDelegationEdge {
+                delegator: delegator.clone(),
+                name_prefix: succinct_roles.name_prefix().to_owned(),
+                bit_length: succinct_roles.bit_length(),
+                threshold: succinct_roles.threshold(),
+                key_ids: succinct_roles.key_ids().iter().cloned().collect(),
+            });
+        }
+    }
+
+    fn add_delegation<D: Pouf>(
+        &mut self,
+        db: &Database<D>,
+        delegator: &MetadataPath,
+        delegation: &Delegation,
+        min_roles_in_agreement: Option<u32>,
+    ) {
+        self.add_role(
+            delegation.name().clone(),
+            db.trusted_delegation(delegation.name())
+                .map(|targets| targets.version()),
+        );
+        self.delegations.push(DelegationEdge {
+            delegator: delegator.clone(),
+            role: delegation.name().clone(),
+            threshold: delegation.threshold(),
+            key_ids: delegation.key_ids().iter().cloned().collect(),
+            paths: delegation.paths().iter().cloned().collect(),
+            path_hash_prefixes: delegation.path_hash_prefixes().iter().cloned().collect(),
+            terminating: delegation.terminating(),
+            min_roles_in_agreement,
+        });
+    }
+}
+
+/// A targets role in a [DelegationGraph].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RoleNode {
+    role: MetadataPath,
+    version: Option<u32>,
+}
+
+impl RoleNode {
+    /// The path of the role.
+    pub fn role(&self) -> &MetadataPath {
+        &self.role
+    }
+
+    /// The version of the trusted metadata of the role, or `None` if it has not been fetched.
+    pub fn version(&self) -> Option<u32> {
+        self.version
+    }
+}
+
+/// A delegation from one role to another in a [DelegationGraph].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DelegationEdge {
+    delegator: MetadataPath,
+    role: MetadataPath,
+    threshold: u32,
+    key_ids: BTreeSet<KeyId>,
+    paths: BTreeSet<TargetPath>,
+    path_hash_prefixes: BTreeSet<String>,
+    terminating: bool,
+    min_roles_in_agreement: Option<u32>,
+}
+
+impl DelegationEdge {
+    /// The role that delegates, which is the root for the top-level targets role.
+    pub fn delegator(&self) -> &MetadataPath {
+        &self.delegator
+    }
+
+    /// The role that is delegated to.
+    pub fn role(&self) -> &MetadataPath {
+        &self.role
+    }
+
+    /// The number of signatures the delegated metadata needs.
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    /// The keys that may sign the delegated metadata.
+    pub fn key_ids(&self) -> &BTreeSet<KeyId> {
+        &self.key_ids
+    }
+
+    /// The target path patterns that are delegated.
+    pub fn paths(&self) -> &BTreeSet<TargetPath> {
+        &self.paths
+    }
+
+    /// The prefixes of target path hashes that are delegated.
+    pub fn path_hash_prefixes(&self) -> &BTreeSet<String> {
+        &self.path_hash_prefixes
+    }
+
+    /// Whether the delegation is terminating.
+    pub fn terminating(&self) -> bool {
+        self.terminating
+    }
+
+    /// Whether every target is delegated, which is only the case for the delegation of the
+    /// top-level targets role by the root.
+    pub fn delegates_all_targets(&self) -> bool {
+        self.delegator == MetadataPath::root()
+    }
+
+    /// How many of the roles of a multi-role delegation must agree on a target, or `None` if the
+    /// delegation is not part of a multi-role delegation.
+    pub fn min_roles_in_agreement(&self) -> Option<u32> {
+        self.min_roles_in_agreement
+    }
+}
+
+/// A delegation of hash bins with succinct roles in a [DelegationGraph].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SuccinctDelegationEdge {
+    delegator: MetadataPath,
+    name_prefix: String,
+    bit_length: u8,
+    threshold: u32,
+    key_ids: BTreeSet<KeyId>,
+}
+
+impl SuccinctDelegationEdge {
+    /// The role that delegates.
+    pub fn delegator(&self) -> &MetadataPath {
+        &self.delegator
+    }
+
+    /// The prefix of the names of the bins.
+    pub fn name_prefix(&self) -> &str {
+        &self.name_prefix
+    }
+
+    /// The number of bits of the target path hashes that select the bin.
+    pub fn bit_length(&self) -> u8 {
+        self.bit_length
+    }
+
+    /// The number of signatures the metadata of every bin needs.
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    /// The keys that may sign the metadata of every bin.
+    pub fn key_ids(&self) -> &BTreeSet<KeyId> {
+        &self.key_ids
+    }
+}
+
+/// Quote `id` as a DOT identifier.
+fn dot_id(id: &str) -> String {
+    format!("\"{}\"", id.replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{Ed25519PrivateKey, HashAlgorithm, PrivateKey};
+    use crate::metadata::{
+        MultiRoleDelegation, RootMetadataBuilder, SnapshotMetadataBuilder, SuccinctRoles,
+        TargetsMetadataBuilder, TimestampMetadataBuilder,
+    };
+    use crate::pouf::Pouf1;
+    use chrono::prelude::*;
+    use lazy_static::lazy_static;
+    use maplit::btreeset;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashSet;
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[
+                include_bytes!("../tests/ed25519/ed25519-1.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-2.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-3.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-4.pk8.der"),
+            ];
+            keys.iter()
+                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+                .collect()
+        };
+    }
+
+    fn delegation(name: &str, key: &Ed25519PrivateKey, path: &str) -> Delegation {
+        Delegation::builder(MetadataPath::new(name.to_owned()).unwrap())
+            .key(key.public())
+            .delegate_path(TargetPath::new(path).unwrap())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn delegation_graph_from_database() {
+        let now = Utc::now();
+        let raw_root = RootMetadataBuilder::new()
+            .root_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[0].public().clone())
+            .targets_key(KEYS[0].public().clone())
+            .timestamp_key(KEYS[0].public().clone())
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+        let mut db = Database::from_trusted_root(&raw_root).unwrap();
+
+        let bins = SuccinctRoles::new(
+            HashSet::from([KEYS[3].public().key_id().clone()]),
+            1,
+            2,
+            "bins".into(),
+        )
+        .unwrap();
+        let signed_delegation = TargetsMetadataBuilder::new()
+            .version(4)
+            .delegations(
+                Delegations::builder()
+                    .key(KEYS[3].public().clone())
+                    .succinct_roles(bins)
+                    .build()
+                    .unwrap(),
+            )
+            .signed::<Pouf1>(&KEYS[1])
+            .unwrap();
+
+        let signed_targets = TargetsMetadataBuilder::new()
+            .delegations(
+                Delegations::builder()
+                    .key(KEYS[1].public().clone())
+                    .key(KEYS[2].public().clone())
+                    .role(delegation("delegation", &KEYS[1], "foo/"))
+                    .multi_role(
+                        MultiRoleDelegation::new(
+                            2,
+                            vec![
+                                delegation("qa", &KEYS[1], "bar/"),
+                                delegation("release", &KEYS[2], "bar/"),
+                            ],
+                        )
+                        .unwrap(),
+                    )
+                    .build()
+                    .unwrap(),
+            )
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap();
+
+        let snapshot = SnapshotMetadataBuilder::new()
+            .insert_metadata(&signed_targets, &[HashAlgorithm::Sha256])
+            .unwrap()
+            .insert_metadata_with_path("delegation", &signed_delegation, &[HashAlgorithm::Sha256])
+            .unwrap()
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap();
+        let raw_timestamp =
+            TimestampMetadataBuilder::from_snapshot(&snapshot, &[HashAlgorithm::Sha256])
+                .unwrap()
+                .signed::<Pouf1>(&KEYS[0])
+                .unwrap()
+                .to_raw()
+                .unwrap();
+
+        db.update_timestamp(&now, &raw_timestamp).unwrap();
+        db.update_snapshot(&now, &snapshot.to_raw().unwrap())
+            .unwrap();
+        db.update_targets(&now, &signed_targets.to_raw().unwrap())
+            .unwrap();
+        db.update_delegated_targets(
+            &now,
+            &MetadataPath::targets(),
+            &MetadataPath::new("delegation").unwrap(),
+            &signed_delegation.to_raw().unwrap(),
+        )
+        .unwrap();
+
+        let graph = DelegationGraph::from_database(&db);
+        assert_eq!(
+            graph
+                .roles()
+                .map(|node| (node.role().as_str(), node.version()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("delegation", Some(4)),
+                ("qa", None),
+                ("release", None),
+                ("root", Some(1)),
+                ("targets", Some(1)),
+            ]
+        );
+
+        let edges = graph
+            .delegations()
+            .iter()
+            .map(|edge| {
+                (
+                    edge.delegator().as_str(),
+                    edge.role().as_str(),
+                    edge.min_roles_in_agreement(),
+                    edge.delegates_all_targets(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            edges,
+            vec![
+                ("root", "targets", None, true),
+                ("targets", "delegation", None, false),
+                ("targets", "qa", Some(2), false),
+                ("targets", "release", Some(2), false),
+            ]
+        );
+
+        let targets = MetadataPath::targets();
+        let edge = graph.delegations_from(&targets).next().unwrap();
+        assert_eq!(edge.threshold(), 1);
+        assert_eq!(
+            edge.key_ids(),
+            &btreeset![KEYS[1].public().key_id().clone()]
+        );
+        assert_eq!(edge.paths(), &btreeset![TargetPath::new("foo/").unwrap()]);
+        assert!(!edge.terminating());
+
+        let bins = &graph.succinct_delegations()[0];
+        assert_eq!(bins.delegator().as_str(), "delegation");
+        assert_eq!(bins.name_prefix(), "bins");
+        assert_eq!(bins.bit_length(), 2);
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph delegations {\n"));
+        assert!(
+            dot.contains("    \"delegation\" [label=\"delegation\\nversion 4\", style=solid];\n")
+        );
+        assert!(dot.contains("    \"qa\" [label=\"qa\", style=dashed];\n"));
+        assert!(dot.contains(
+            "    \"targets\" -> \"qa\" [label=\"1 of 1 keys\\n2 roles must agree\\nbar/\"];\n"
+        ));
+        assert!(dot.contains("    \"delegation\" -> \"bins-*\" [label=\"1 of 1 keys\"];\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn dot_ids_are_quoted() {
+        assert_eq!(dot_id("a\"b"), "\"a\\\"b\"");
+    }
+}
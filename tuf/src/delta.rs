@@ -0,0 +1,193 @@
+//! Delta updates for targets.
+//!
+//! Large targets often change very little between releases. Instead of downloading the full
+//! target, a [Client](crate::client::Client) that already has a previous release of the target can
+//! download a much smaller binary diff, and apply it to the previous release to reconstruct the new
+//! one.
+//!
+//! Deltas are advertised by listing them in the `deltas` custom field of the
+//! [TargetDescription] of the new target. The field is an object that maps the lowercase hex
+//! encoded SHA-256 of a source file to the path of a patch that turns that source file into the
+//! target:
+//!
+//! ```json
+//! "custom": {
+//!   "deltas": {
+//!     "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03": "app-2.patch-from-1"
+//!   }
+//! }
+//! ```
+//!
+//! Each patch must itself be listed as a target in the trusted targets metadata, so it is verified
+//! before it is applied, and the reconstructed file is verified against the full target
+//! description before it is installed. The format of the patch is up to the repository, and
+//! clients apply it with a [DeltaPatcher], for example one that is backed by bsdiff.
+
+use data_encoding::HEXLOWER_PERMISSIVE;
+use std::collections::HashMap;
+
+use crate::crypto::{HashAlgorithm, HashValue};
+use crate::error::{Error, Result};
+use crate::metadata::{TargetDescription, TargetPath};
+
+/// The name of the custom field of a [TargetDescription] that lists the deltas of a target.
+pub const DELTAS_CUSTOM_FIELD: &str = "deltas";
+
+/// Applies a binary diff to a source file to reconstruct a target.
+///
+/// This is implemented for closures with the same signature as [DeltaPatcher::apply_patch].
+pub trait DeltaPatcher: Send + Sync {
+    /// Apply `patch` to `source`, returning the reconstructed target. The output does not need to
+    /// be checked, since it is verified against the trusted target description before it is used.
+    fn apply_patch(&self, source: &[u8], patch: &[u8]) -> Result<Vec<u8>>;
+}
+
+impl<F> DeltaPatcher for F
+where
+    F: Fn(&[u8], &[u8]) -> Result<Vec<u8>> + Send + Sync,
+{
+    fn apply_patch(&self, source: &[u8], patch: &[u8]) -> Result<Vec<u8>> {
+        self(source, patch)
+    }
+}
+
+/// A patch that reconstructs a target from the source file with the given SHA-256.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delta {
+    source_sha256: HashValue,
+    patch: TargetPath,
+}
+
+impl Delta {
+    /// Create a new delta that turns the source file with the SHA-256 `source_sha256` into a
+    /// target by applying the patch target `patch`.
+    pub fn new(source_sha256: HashValue, patch: TargetPath) -> Self {
+        Delta {
+            source_sha256,
+            patch,
+        }
+    }
+
+    /// The SHA-256 of the source file the patch applies to.
+    pub fn source_sha256(&self) -> &HashValue {
+        &self.source_sha256
+    }
+
+    /// The path of the target that contains the patch.
+    pub fn patch(&self) -> &TargetPath {
+        &self.patch
+    }
+}
+
+/// Parse the deltas listed in the custom metadata of `description`. Returns an empty list if the
+/// target does not list any deltas.
+pub fn deltas(description: &TargetDescription) -> Result<Vec<Delta>> {
+    let deltas = match description.custom().get(DELTAS_CUSTOM_FIELD) {
+        Some(deltas) => deltas,
+        None => return Ok(vec![]),
+    };
+
+    let deltas = deltas.as_object().ok_or_else(|| {
+        Error::Encoding(format!(
+            "custom field {:?} must be an object",
+            DELTAS_CUSTOM_FIELD
+        ))
+    })?;
+
+    deltas
+        .iter()
+        .map(|(source, patch)| {
+            let source_sha256 = HEXLOWER_PERMISSIVE
+                .decode(source.as_bytes())
+                .map_err(|err| Error::Encoding(format!("invalid delta source hash: {}", err)))?;
+            let patch = patch.as_str().ok_or_else(|| {
+                Error::Encoding(format!("delta patch for {} must be a string", source))
+            })?;
+
+            Ok(Delta::new(
+                HashValue::new(source_sha256),
+                TargetPath::new(patch)?,
+            ))
+        })
+        .collect()
+}
+
+/// Build custom metadata for a target that lists `deltas`, for use with
+/// [TargetDescription::from_reader_with_custom].
+pub fn deltas_custom(deltas: &[Delta]) -> HashMap<String, serde_json::Value> {
+    let deltas = deltas
+        .iter()
+        .map(|delta| {
+            (
+                delta.source_sha256.to_string(),
+                serde_json::Value::String(delta.patch.as_str().into()),
+            )
+        })
+        .collect::<serde_json::Map<_, _>>();
+
+    let mut custom = HashMap::new();
+    custom.insert(DELTAS_CUSTOM_FIELD.into(), deltas.into());
+    custom
+}
+
+/// Find the delta in `description` that applies to `source`, if any.
+pub(crate) fn find_delta(description: &TargetDescription, source: &[u8]) -> Result<Option<Delta>> {
+    let deltas = deltas(description)?;
+    if deltas.is_empty() {
+        return Ok(None);
+    }
+
+    let mut context = HashAlgorithm::Sha256.digest_context()?;
+    context.update(source);
+    let source_sha256 = HashValue::new(context.finish().as_ref().to_vec());
+
+    Ok(deltas
+        .into_iter()
+        .find(|delta| delta.source_sha256 == source_sha256))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::calculate_hash;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn deltas_round_trip_through_custom_metadata() {
+        let source = b"version 1";
+        let delta = Delta::new(
+            calculate_hash(source, &HashAlgorithm::Sha256),
+            TargetPath::new("app-2.patch-from-1").unwrap(),
+        );
+
+        let description = TargetDescription::from_slice_with_custom(
+            b"version 2",
+            &[HashAlgorithm::Sha256],
+            deltas_custom(std::slice::from_ref(&delta)),
+        )
+        .unwrap();
+
+        assert_eq!(deltas(&description).unwrap(), vec![delta.clone()]);
+        assert_eq!(find_delta(&description, source).unwrap(), Some(delta));
+        assert_eq!(find_delta(&description, b"version 0").unwrap(), None);
+    }
+
+    #[test]
+    fn deltas_rejects_malformed_custom_metadata() {
+        let description = TargetDescription::from_slice(b"foo", &[HashAlgorithm::Sha256]).unwrap();
+        assert_eq!(deltas(&description).unwrap(), vec![]);
+
+        for value in [
+            serde_json::json!("not an object"),
+            serde_json::json!({ "not hex": "patch" }),
+            serde_json::json!({ "abcd": 5 }),
+        ] {
+            let mut custom = HashMap::new();
+            custom.insert(DELTAS_CUSTOM_FIELD.to_string(), value);
+            let description =
+                TargetDescription::from_slice_with_custom(b"foo", &[HashAlgorithm::Sha256], custom)
+                    .unwrap();
+            assert_matches!(deltas(&description), Err(Error::Encoding(_)));
+        }
+    }
+}
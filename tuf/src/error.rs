@@ -2,11 +2,11 @@
 
 use {
     crate::{
-        crypto::KeyId,
-        metadata::{MetadataPath, MetadataVersion, TargetPath},
+        crypto::{HashAlgorithm, KeyId, SignatureScheme},
+        metadata::{MetadataPath, MetadataVersion, SpecVersion, TargetPath},
     },
     chrono::{offset::Utc, DateTime},
-    std::io,
+    std::{fmt, io},
     thiserror::Error,
 };
 
@@ -25,11 +25,16 @@ pub enum Error {
     #[error("encoding: {0}")]
     Encoding(String),
 
-    /// Metadata was expired.
-    #[error("metadata {path} expired at {expiration}, it is now {now}")]
+    /// Metadata was expired, which may indicate a freeze attack.
+    #[error("metadata {path} version {version} expired at {expiration}, it is now {now}")]
     ExpiredMetadata {
         /// The metadata that expired.
         path: MetadataPath,
+        /// The version of the metadata that expired.
+        version: u32,
+        /// The version of the metadata that was trusted before, if any. This is `version` if the
+        /// trusted metadata itself expired.
+        trusted_version: Option<u32>,
         /// When the metadata expired.
         expiration: DateTime<Utc>,
         /// The latest known time.
@@ -96,6 +101,10 @@ pub enum Error {
     #[error("no supported hash algorithm")]
     NoSupportedHashAlgorithm,
 
+    /// The target cannot be verified, because its description is missing its length or hashes.
+    #[error("target {0} has no trusted length or hashes to verify it with")]
+    UnverifiableTarget(TargetPath),
+
     /// The metadata was not found.
     #[error("metadata {path} at version {version} not found")]
     MetadataNotFound {
@@ -123,6 +132,20 @@ pub enum Error {
     #[error("unknown signature scheme: {0}")]
     UnknownSignatureScheme(String),
 
+    /// The metadata follows a major version of the specification that is not supported.
+    #[error(
+        "metadata {path} follows spec version {spec_version}, \
+         but only spec version {supported_major_version}.x is supported"
+    )]
+    UnsupportedSpecVersion {
+        /// The metadata.
+        path: MetadataPath,
+        /// The spec version of the metadata.
+        spec_version: SpecVersion,
+        /// The supported major version of the specification.
+        supported_major_version: u32,
+    },
+
     /// The metadata's version must be greater than 0.
     #[error("metadata {0} version should be greater than zero")]
     MetadataVersionMustBeGreaterThanZero(MetadataPath),
@@ -156,19 +179,24 @@ pub enum Error {
     },
 
     /// The metadata was not signed with enough valid signatures.
+    ///
+    /// This does not carry the version of the rejected metadata, since metadata is not parsed
+    /// until its signatures are verified.
     #[error(
         "metadata {role} signature threshold not met: {number_of_valid_signatures}/{threshold}"
     )]
     MetadataMissingSignatures {
         /// The signed metadata.
         role: MetadataPath,
+        /// The version of the metadata that was trusted for the role, if any.
+        trusted_version: Option<u32>,
         /// The number of signatures which are valid.
         number_of_valid_signatures: u32,
         /// The minimum number of valid signatures.
         threshold: u32,
     },
 
-    /// Attempted to update metadata with an older version.
+    /// Attempted to update metadata with an older version, which may indicate a rollback attack.
     #[error(
         "attempted to roll back metadata {role} from version {trusted_version} to {new_version}"
     )]
@@ -182,7 +210,7 @@ pub enum Error {
     },
 
     /// The parent metadata expected the child metadata to be at one version, but was found to be at
-    /// another version.
+    /// another version, which may indicate a mix-and-match attack.
     #[error("metadata {parent_role} expected metadata {child_role} version {expected_version}, but found {new_version}")]
     WrongMetadataVersion {
         /// The parent metadata that contains the child metadata's version.
@@ -195,6 +223,21 @@ pub enum Error {
         new_version: u32,
     },
 
+    /// The metadata does not match the length or hashes its parent metadata declares for it, or
+    /// the parent metadata does not declare them as strictly as required.
+    #[error("metadata {role} does not match its description: {reason}")]
+    MetadataIntegrityMismatch {
+        /// The metadata that failed the check.
+        role: MetadataPath,
+        /// Why the check failed.
+        reason: String,
+    },
+
+    /// The root metadata fetched from the remote repository does not match the trusted root
+    /// metadata of the same version.
+    #[error("root metadata version {0} does not match the trusted root metadata")]
+    RootChainMismatch(u32),
+
     /// The parent metadata does not contain a description of the child metadata.
     #[error("metadata {parent_role} missing description of {child_role}")]
     MissingMetadataDescription {
@@ -219,4 +262,270 @@ pub enum Error {
         /// The metadata to be signed.
         role: MetadataPath,
     },
+
+    /// Walking the delegation graph would have exceeded [Config::max_delegation_depth].
+    ///
+    /// [Config::max_delegation_depth]: crate::client::Config::max_delegation_depth
+    #[error("walking the delegation graph exceeded the maximum depth of {0}")]
+    MaxDelegationDepthExceeded(u32),
+
+    /// Walking the delegation graph would have exceeded [Config::max_visited_roles].
+    ///
+    /// [Config::max_visited_roles]: crate::client::Config::max_visited_roles
+    #[error("walking the delegation graph exceeded the maximum of {0} visited roles")]
+    MaxVisitedRolesExceeded(u32),
+
+    /// The operation was cancelled with a
+    /// [CancellationToken](crate::cancel::CancellationToken).
+    #[error("operation was cancelled")]
+    Cancelled,
+
+    /// The attestation of a target did not satisfy the
+    /// [AttestationPolicy](crate::attestation::AttestationPolicy).
+    #[error("attestation for target {target} failed verification: {reason}")]
+    AttestationVerificationFailed {
+        /// The target the attestation is for.
+        target: TargetPath,
+        /// Why the attestation was rejected.
+        reason: String,
+    },
+
+    /// A [SnapshotMerkleProof](crate::merkle::SnapshotMerkleProof) did not prove the snapshot
+    /// entry of a role against the Merkle root in the trusted timestamp metadata.
+    #[error("invalid snapshot merkle proof for {role}: {reason}")]
+    InvalidSnapshotMerkleProof {
+        /// The role the proof is for.
+        role: MetadataPath,
+        /// Why the proof was rejected.
+        reason: String,
+    },
+
+    /// The metadata uses a hash algorithm that the [Policy](crate::policy::Policy) does not
+    /// allow.
+    #[error("metadata {role} uses hash algorithm {algorithm}, which is not allowed by policy")]
+    DisallowedHashAlgorithm {
+        /// The metadata that uses the hash algorithm.
+        role: MetadataPath,
+        /// The disallowed hash algorithm.
+        algorithm: HashAlgorithm,
+    },
+
+    /// The metadata declares hashes without a hash algorithm that the
+    /// [Policy](crate::policy::Policy) requires.
+    #[error("metadata {role} declares hashes without required hash algorithm {algorithm}")]
+    MissingRequiredHashAlgorithm {
+        /// The metadata that declares the hashes.
+        role: MetadataPath,
+        /// The missing hash algorithm.
+        algorithm: HashAlgorithm,
+    },
+
+    /// The metadata authorizes a key with a signature scheme that the
+    /// [Policy](crate::policy::Policy) does not allow.
+    #[error("metadata {role} uses signature scheme {scheme}, which is not allowed by policy")]
+    DisallowedSignatureScheme {
+        /// The metadata that authorizes the key.
+        role: MetadataPath,
+        /// The disallowed signature scheme.
+        scheme: SignatureScheme,
+    },
+
+    /// The root metadata trusts a key for more than one top-level role, which was forbidden when
+    /// building it with a [RootMetadataBuilder](crate::metadata::RootMetadataBuilder), or by the
+    /// [Policy](crate::policy::Policy).
+    #[error("key {key_id} is trusted by more than one top-level role: {}", join_paths(.roles))]
+    KeyReuse {
+        /// The reused key.
+        key_id: KeyId,
+        /// The top-level roles that trust the key.
+        roles: Vec<MetadataPath>,
+    },
+
+    /// The custom metadata of a target could not be converted to or from the requested type.
+    #[error("invalid custom target metadata: {0}")]
+    InvalidCustomMetadata(String),
+
+    /// The director and image repositories of an
+    /// [UptaneClient](crate::uptane::UptaneClient) disagree on the description of a target.
+    #[cfg(feature = "uptane")]
+    #[error("director and image repositories disagree on target {target}: {reason}")]
+    UptaneTargetMismatch {
+        /// The target the repositories disagree on.
+        target: TargetPath,
+        /// How the descriptions differ.
+        reason: String,
+    },
+}
+
+impl Error {
+    /// The [ErrorKind] of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::AttemptedMetadataRollBack { .. } => ErrorKind::Rollback,
+            Error::ExpiredMetadata { .. } => ErrorKind::Freeze,
+            Error::WrongMetadataVersion { .. } | Error::RootChainMismatch(_) => {
+                ErrorKind::MixAndMatch
+            }
+            Error::BadSignature(_) | Error::MetadataMissingSignatures { .. } => {
+                ErrorKind::InvalidSignatures
+            }
+            Error::UnauthorizedDelegation { .. } => ErrorKind::UnauthorizedDelegation,
+            Error::MetadataIntegrityMismatch { .. } | Error::InvalidSnapshotMerkleProof { .. } => {
+                ErrorKind::IntegrityMismatch
+            }
+            #[cfg(feature = "uptane")]
+            Error::UptaneTargetMismatch { .. } => ErrorKind::IntegrityMismatch,
+            Error::AttestationVerificationFailed { .. } => ErrorKind::AttestationFailed,
+            Error::DisallowedHashAlgorithm { .. }
+            | Error::MissingRequiredHashAlgorithm { .. }
+            | Error::DisallowedSignatureScheme { .. }
+            | Error::KeyReuse { .. } => ErrorKind::PolicyViolation,
+            Error::Encoding(_) | Error::Json(_) | Error::InvalidCustomMetadata(_) => {
+                ErrorKind::Encoding
+            }
+            Error::UnsupportedSpecVersion { .. }
+            | Error::NoSupportedHashAlgorithm
+            | Error::UnknownKeyType(_)
+            | Error::UnknownSignatureScheme(_) => ErrorKind::Unsupported,
+            Error::MetadataVersionMustBeGreaterThanZero(_)
+            | Error::MetadataVersionMustBeSmallerThanMaxU32(_)
+            | Error::MetadataThresholdMustBeGreaterThanZero(_)
+            | Error::MetadataRoleHasDuplicateKeyId { .. }
+            | Error::MetadataRoleDoesNotHaveEnoughKeyIds { .. }
+            | Error::MissingMetadataDescription { .. }
+            | Error::UnverifiableTarget(_) => ErrorKind::InvalidMetadata,
+            Error::MetadataNotFound { .. } | Error::TargetNotFound(_) => ErrorKind::NotFound,
+            Error::Http { .. } | Error::BadHttpStatus { .. } => ErrorKind::Transport,
+            #[cfg(feature = "hyper")]
+            Error::Hyper { .. } => ErrorKind::Transport,
+            Error::Io(_) | Error::IoPath { .. } => ErrorKind::Io,
+            Error::MaxDelegationDepthExceeded(_) | Error::MaxVisitedRolesExceeded(_) => {
+                ErrorKind::LimitExceeded
+            }
+            Error::IllegalArgument(_) | Error::MissingPrivateKey { .. } => {
+                ErrorKind::IllegalArgument
+            }
+            Error::Cancelled => ErrorKind::Cancelled,
+            Error::Opaque(_) => ErrorKind::Other,
+        }
+    }
+
+    /// Whether this error may indicate an attack by the repository or a man-in-the-middle. See
+    /// [ErrorKind::is_attack].
+    pub fn is_attack(&self) -> bool {
+        self.kind().is_attack()
+    }
+
+    /// Record the version of the metadata that was trusted for the role, in errors that carry it
+    /// but were raised where it is not known.
+    pub(crate) fn with_trusted_version(mut self, version: Option<u32>) -> Self {
+        if let Error::MetadataMissingSignatures {
+            ref mut trusted_version,
+            ..
+        } = self
+        {
+            *trusted_version = version;
+        }
+        self
+    }
+}
+
+fn join_paths(paths: &[MetadataPath]) -> String {
+    paths
+        .iter()
+        .map(|path| path.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A stable classification of [Error]s, for handling errors programmatically and reporting them
+/// to telemetry. New [Error] variants are classified into the existing kinds where possible, so
+/// matching on the kind is less likely to change between releases than matching on the error.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ErrorKind {
+    /// Metadata older than the trusted metadata was served.
+    Rollback,
+    /// Expired metadata was served.
+    Freeze,
+    /// Metadata was served that is inconsistent with the versions in other trusted metadata.
+    MixAndMatch,
+    /// Metadata was served that is not signed by a threshold of trusted keys.
+    InvalidSignatures,
+    /// Metadata was served for a role that was not delegated to.
+    UnauthorizedDelegation,
+    /// Metadata or a target did not match the length or hashes trusted metadata declares for it.
+    IntegrityMismatch,
+    /// The attestation of a target was rejected.
+    AttestationFailed,
+    /// Metadata uses an algorithm or a key in a way that the [Policy](crate::policy::Policy) does
+    /// not allow.
+    PolicyViolation,
+    /// Data could not be encoded or decoded.
+    Encoding,
+    /// Metadata uses a spec version, hash algorithm, key type or signature scheme that is not
+    /// supported.
+    Unsupported,
+    /// Metadata is well-formed, but not valid.
+    InvalidMetadata,
+    /// Metadata or a target was not found.
+    NotFound,
+    /// Fetching from a remote repository failed.
+    Transport,
+    /// An IO error occurred.
+    Io,
+    /// A configured limit was exceeded.
+    LimitExceeded,
+    /// An illegal argument was passed into a function.
+    IllegalArgument,
+    /// The operation was cancelled.
+    Cancelled,
+    /// Any other error.
+    Other,
+}
+
+impl ErrorKind {
+    /// Whether errors of this kind may indicate an attack by the repository or a
+    /// man-in-the-middle, rather than an unavailable repository or a local problem.
+    pub fn is_attack(&self) -> bool {
+        matches!(
+            self,
+            ErrorKind::Rollback
+                | ErrorKind::Freeze
+                | ErrorKind::MixAndMatch
+                | ErrorKind::InvalidSignatures
+                | ErrorKind::UnauthorizedDelegation
+                | ErrorKind::IntegrityMismatch
+        )
+    }
+
+    /// A stable, lowercase name for the kind, suitable as a metric label.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::Rollback => "rollback",
+            ErrorKind::Freeze => "freeze",
+            ErrorKind::MixAndMatch => "mix_and_match",
+            ErrorKind::InvalidSignatures => "invalid_signatures",
+            ErrorKind::UnauthorizedDelegation => "unauthorized_delegation",
+            ErrorKind::IntegrityMismatch => "integrity_mismatch",
+            ErrorKind::AttestationFailed => "attestation_failed",
+            ErrorKind::PolicyViolation => "policy_violation",
+            ErrorKind::Encoding => "encoding",
+            ErrorKind::Unsupported => "unsupported",
+            ErrorKind::InvalidMetadata => "invalid_metadata",
+            ErrorKind::NotFound => "not_found",
+            ErrorKind::Transport => "transport",
+            ErrorKind::Io => "io",
+            ErrorKind::LimitExceeded => "limit_exceeded",
+            ErrorKind::IllegalArgument => "illegal_argument",
+            ErrorKind::Cancelled => "cancelled",
+            ErrorKind::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
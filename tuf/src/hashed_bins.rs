@@ -0,0 +1,284 @@
+//! Sharding a large number of targets into hashed bins.
+//!
+//! A repository that lists every target in its top-level targets metadata forces clients to
+//! download all of them to look up any one. With hashed bins, the top-level targets metadata
+//! instead delegates to `2^bit_length` bins, and every target is signed by the bin selected by
+//! the first `bit_length` bits of the SHA-256 hash of its path. A client then only needs the
+//! targets metadata of the bin a target hashes to.
+//!
+//! Bins are named after the range of hex hash prefixes they cover, and are delegated to with
+//! those `path_hash_prefixes`, as python-tuf does. With `bit_length` 3, the bins are named
+//! `bins-0-1`, `bins-2-3`, ..., `bins-e-f`, and with `bit_length` 4 they are named `bins-0`,
+//! `bins-1`, ..., `bins-f`.
+//!
+//! [RepoBuilder::hashed_bins](crate::repo_builder::RepoBuilder::hashed_bins) generates, signs and
+//! stages the bins for the targets added to a repository.
+
+use ring::digest::{self, SHA256};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::crypto::KeyId;
+use crate::error::{Error, Result};
+use crate::metadata::{Delegation, MetadataPath, TargetDescription, TargetPath};
+
+/// A description of how targets are sharded into hashed bins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashedBins {
+    name_prefix: String,
+    bit_length: u8,
+    threshold: u32,
+}
+
+impl HashedBins {
+    /// Shard targets into `2^bit_length` bins, whose names start with `name_prefix`. The bins
+    /// require a threshold of 1 signature, see [HashedBins::threshold].
+    ///
+    /// `bit_length` must be between 1 and 16.
+    pub fn new(name_prefix: impl Into<String>, bit_length: u8) -> Result<Self> {
+        if !(1..=16).contains(&bit_length) {
+            return Err(Error::IllegalArgument(
+                "Bit length must be between 1 and 16".into(),
+            ));
+        }
+
+        let bins = HashedBins {
+            name_prefix: name_prefix.into(),
+            bit_length,
+            threshold: 1,
+        };
+
+        // Every bin name only differs by its hex suffix, so checking the first is enough.
+        let _ = MetadataPath::new(bins.format_bin_name(0))?;
+
+        Ok(bins)
+    }
+
+    /// Set the threshold number of signatures required for each bin to be trusted.
+    pub fn threshold(mut self, threshold: u32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// The prefix of the name of every bin.
+    pub fn name_prefix(&self) -> &str {
+        &self.name_prefix
+    }
+
+    /// The number of bits of the target path hash used to select its bin.
+    pub fn bit_length(&self) -> u8 {
+        self.bit_length
+    }
+
+    /// The number of bins.
+    pub fn bin_count(&self) -> u32 {
+        1 << self.bit_length
+    }
+
+    /// The name of every bin, in order.
+    pub fn role_names(&self) -> impl Iterator<Item = MetadataPath> + '_ {
+        (0..self.bin_count()).map(move |bin| self.bin_name(bin))
+    }
+
+    /// The name of the bin that `target` is sharded into.
+    pub fn role_for_target(&self, target: &TargetPath) -> MetadataPath {
+        self.bin_name(self.bin_for_target(target))
+    }
+
+    /// Sort `targets` into the bins they are sharded into. Bins that no target hashes to are
+    /// omitted.
+    pub fn bin_targets<I>(
+        &self,
+        targets: I,
+    ) -> BTreeMap<MetadataPath, HashMap<TargetPath, TargetDescription>>
+    where
+        I: IntoIterator<Item = (TargetPath, TargetDescription)>,
+    {
+        let mut bins = BTreeMap::new();
+        for (path, description) in targets {
+            let _ = bins
+                .entry(self.role_for_target(&path))
+                .or_insert_with(HashMap::new)
+                .insert(path, description);
+        }
+        bins
+    }
+
+    /// Create the delegation to the bin `role`, signed by `key_ids`, that is authorized to sign
+    /// the targets whose path hash starts with one of the prefixes covered by the bin.
+    pub fn delegation<K>(&self, role: &MetadataPath, key_ids: K) -> Result<Delegation>
+    where
+        K: IntoIterator<Item = KeyId>,
+    {
+        let bin = self
+            .role_names()
+            .position(|name| &name == role)
+            .ok_or_else(|| {
+                Error::IllegalArgument(format!("{} is not one of the hashed bins", role))
+            })? as u32;
+
+        let width = self.prefix_len() as usize;
+        let (low, high) = self.prefix_range(bin);
+        let prefixes = (low..=high)
+            .map(|prefix| format!("{:0width$x}", prefix, width = width))
+            .collect();
+
+        Delegation::with_path_hash_prefixes(
+            role.clone(),
+            false,
+            self.threshold,
+            key_ids.into_iter().collect(),
+            prefixes,
+        )
+    }
+
+    fn bin_for_target(&self, target: &TargetPath) -> u32 {
+        let hash = digest::digest(&SHA256, target.as_str().as_bytes());
+        let mut prefix = [0; 4];
+        prefix.copy_from_slice(&hash.as_ref()[..4]);
+        u32::from_be_bytes(prefix) >> (32 - u32::from(self.bit_length))
+    }
+
+    /// The number of hex digits of the hash prefixes covered by each bin.
+    fn prefix_len(&self) -> u32 {
+        (u32::from(self.bit_length) + 3) / 4
+    }
+
+    /// The range of hash prefixes covered by `bin`.
+    fn prefix_range(&self, bin: u32) -> (u32, u32) {
+        let prefixes_per_bin = 1 << (4 * self.prefix_len() - u32::from(self.bit_length));
+        let low = bin * prefixes_per_bin;
+        (low, low + prefixes_per_bin - 1)
+    }
+
+    fn format_bin_name(&self, bin: u32) -> String {
+        let width = self.prefix_len() as usize;
+        match self.prefix_range(bin) {
+            (low, high) if low == high => {
+                format!("{}-{:0width$x}", self.name_prefix, low, width = width)
+            }
+            (low, high) => format!(
+                "{}-{:0width$x}-{:0width$x}",
+                self.name_prefix,
+                low,
+                high,
+                width = width
+            ),
+        }
+    }
+
+    fn bin_name(&self, bin: u32) -> MetadataPath {
+        MetadataPath::new(self.format_bin_name(bin))
+            .expect("bin names only differ by their hex suffix")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::HashAlgorithm;
+
+    #[test]
+    fn test_bin_names() {
+        let bins = HashedBins::new("bins", 3).unwrap();
+        assert_eq!(
+            bins.role_names()
+                .map(|role| role.as_str().to_owned())
+                .collect::<Vec<_>>(),
+            vec![
+                "bins-0-1", "bins-2-3", "bins-4-5", "bins-6-7", "bins-8-9", "bins-a-b", "bins-c-d",
+                "bins-e-f"
+            ]
+        );
+
+        let bins = HashedBins::new("bins", 8).unwrap();
+        assert_eq!(bins.role_names().count(), 256);
+        assert_eq!(
+            bins.role_names().last(),
+            Some(MetadataPath::new("bins-ff").unwrap())
+        );
+
+        // sha256("foo") starts with 0x2c26.
+        let foo = TargetPath::new("foo").unwrap();
+        assert_eq!(
+            HashedBins::new("bins", 3).unwrap().role_for_target(&foo),
+            MetadataPath::new("bins-2-3").unwrap()
+        );
+        assert_eq!(
+            bins.role_for_target(&foo),
+            MetadataPath::new("bins-2c").unwrap()
+        );
+        assert_eq!(
+            HashedBins::new("bins", 10).unwrap().role_for_target(&foo),
+            MetadataPath::new("bins-2c0-2c3").unwrap()
+        );
+
+        assert!(HashedBins::new("bins", 0).is_err());
+        assert!(HashedBins::new("bins", 17).is_err());
+        assert!(HashedBins::new("../bins", 4).is_err());
+    }
+
+    #[test]
+    fn test_bin_targets() {
+        let bins = HashedBins::new("bins", 1).unwrap();
+        let description = TargetDescription::from_slice(b"", &[HashAlgorithm::Sha256]).unwrap();
+
+        // sha256("foo") starts with 0x2c, sha256("bar") with 0xfc, and sha256("baz") with 0xba.
+        let targets = ["foo", "bar", "baz"]
+            .iter()
+            .map(|path| (TargetPath::new(*path).unwrap(), description.clone()));
+        let sharded = bins.bin_targets(targets);
+
+        assert_eq!(
+            sharded
+                .iter()
+                .map(|(role, targets)| {
+                    let mut paths = targets.keys().map(|p| p.as_str()).collect::<Vec<_>>();
+                    paths.sort_unstable();
+                    (role.as_str(), paths)
+                })
+                .collect::<Vec<_>>(),
+            vec![("bins-0-7", vec!["foo"]), ("bins-8-f", vec!["bar", "baz"])]
+        );
+    }
+
+    #[test]
+    fn test_bin_delegation() {
+        let key_id: KeyId = "4750eaf6878740780d6f97b12dbad079fb012bec88c78de2c380add56d3f51db"
+            .parse()
+            .unwrap();
+        let bins = HashedBins::new("bins", 3).unwrap();
+
+        let delegation = bins
+            .delegation(
+                &MetadataPath::new("bins-2-3").unwrap(),
+                vec![key_id.clone()],
+            )
+            .unwrap();
+        let mut prefixes = delegation
+            .path_hash_prefixes()
+            .iter()
+            .map(|prefix| prefix.as_str())
+            .collect::<Vec<_>>();
+        prefixes.sort_unstable();
+        assert_eq!(prefixes, vec!["2", "3"]);
+        assert!(delegation.paths().is_empty());
+
+        // sha256("foo") starts with 0x2c, and sha256("bar") with 0xfc.
+        assert!(delegation.matches_target(&TargetPath::new("foo").unwrap()));
+        assert!(!delegation.matches_target(&TargetPath::new("bar").unwrap()));
+
+        let delegation = HashedBins::new("bins", 10)
+            .unwrap()
+            .delegation(
+                &MetadataPath::new("bins-2c0-2c3").unwrap(),
+                vec![key_id.clone()],
+            )
+            .unwrap();
+        assert_eq!(delegation.path_hash_prefixes().len(), 4);
+        assert!(delegation.path_hash_prefixes().contains("2c2"));
+
+        assert!(bins
+            .delegation(&MetadataPath::new("bins-2").unwrap(), vec![key_id])
+            .is_err());
+    }
+}
@@ -0,0 +1,229 @@
+//! Looking inside metadata without trusting it.
+//!
+//! [inspect] parses raw metadata into an [UntrustedMetadata] view of its version, expiration, the
+//! keys it authorizes, and the keys that claim to have signed it. No signatures are verified and
+//! nothing is checked against trusted metadata, so the view is only fit for display, such as in
+//! dashboards and debugging tools. Use a [Database](crate::database::Database) or a
+//! [Client](crate::client::Client) to decide whether metadata can be trusted.
+//!
+//! ```
+//! # use tuf::crypto::{Ed25519PrivateKey, PrivateKey};
+//! # use tuf::inspect;
+//! # use tuf::metadata::{Role, RootMetadataBuilder};
+//! # use tuf::pouf::Pouf1;
+//! #
+//! # let key: &[u8] = include_bytes!("../tests/ed25519/ed25519-1.pk8.der");
+//! # let key = Ed25519PrivateKey::from_pkcs8(&key).unwrap();
+//! let raw_root = RootMetadataBuilder::new()
+//!     .version(3)
+//!     .root_key(key.public().clone())
+//!     .snapshot_key(key.public().clone())
+//!     .targets_key(key.public().clone())
+//!     .timestamp_key(key.public().clone())
+//!     .signed::<Pouf1>(&key)
+//!     .unwrap()
+//!     .to_raw()
+//!     .unwrap();
+//!
+//! let root = inspect::inspect(&raw_root).unwrap();
+//! assert_eq!(root.role(), Role::Root);
+//! assert_eq!(root.version(), 3);
+//! assert_eq!(root.signature_key_ids(), &[key.public().key_id().clone()]);
+//! ```
+
+use chrono::{DateTime, Utc};
+use std::collections::BTreeSet;
+
+use crate::crypto::KeyId;
+use crate::error::Result;
+use crate::metadata::{
+    Metadata, RawSignedMetadata, Role, RootMetadata, SignedMetadata, SpecVersion, TargetsMetadata,
+};
+use crate::pouf::Pouf;
+
+/// Parse `raw` metadata into a read-only view, without verifying its signatures.
+pub fn inspect<D, M>(raw: &RawSignedMetadata<D, M>) -> Result<UntrustedMetadata<M>>
+where
+    D: Pouf,
+    M: Metadata,
+{
+    inspect_signed(&raw.parse_untrusted()?)
+}
+
+/// Parse metadata serialized with the pouf `D` into a read-only view, without verifying its
+/// signatures.
+pub fn inspect_slice<D, M>(bytes: &[u8]) -> Result<UntrustedMetadata<M>>
+where
+    D: Pouf,
+    M: Metadata,
+{
+    inspect_signed(&D::from_slice::<SignedMetadata<D, M>>(bytes)?)
+}
+
+/// Create a read-only view of `signed` metadata, without verifying its signatures.
+pub fn inspect_signed<D, M>(signed: &SignedMetadata<D, M>) -> Result<UntrustedMetadata<M>>
+where
+    D: Pouf,
+    M: Metadata,
+{
+    Ok(UntrustedMetadata {
+        metadata: signed.assume_valid()?,
+        signature_key_ids: signed
+            .signatures()
+            .iter()
+            .map(|sig| sig.key_id().clone())
+            .collect(),
+    })
+}
+
+/// A read-only view of metadata whose signatures have **not** been verified.
+///
+/// Every value is as claimed by whoever produced the metadata. Nothing converts an
+/// `UntrustedMetadata` back into metadata that a [Database](crate::database::Database) accepts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UntrustedMetadata<M> {
+    metadata: M,
+    signature_key_ids: Vec<KeyId>,
+}
+
+impl<M: Metadata> UntrustedMetadata<M> {
+    /// The role of the metadata.
+    pub fn role(&self) -> Role {
+        M::ROLE
+    }
+
+    /// The version the metadata claims.
+    pub fn version(&self) -> u32 {
+        self.metadata.version()
+    }
+
+    /// The expiration the metadata claims.
+    pub fn expires(&self) -> &DateTime<Utc> {
+        self.metadata.expires()
+    }
+
+    /// The version of the specification the metadata claims to follow.
+    pub fn spec_version(&self) -> &SpecVersion {
+        self.metadata.spec_version()
+    }
+
+    /// The key IDs of the signatures, in the order they appear. The signatures have not been
+    /// checked, so they may be invalid, or made by keys that are not trusted for the role.
+    pub fn signature_key_ids(&self) -> &[KeyId] {
+        &self.signature_key_ids
+    }
+
+    /// The unverified metadata itself.
+    pub fn metadata_untrusted(&self) -> &M {
+        &self.metadata
+    }
+}
+
+impl UntrustedMetadata<RootMetadata> {
+    /// The IDs of every key the root metadata lists.
+    pub fn key_ids(&self) -> BTreeSet<&KeyId> {
+        self.metadata.keys().keys().collect()
+    }
+}
+
+impl UntrustedMetadata<TargetsMetadata> {
+    /// The IDs of every key the targets metadata delegates to.
+    pub fn key_ids(&self) -> BTreeSet<&KeyId> {
+        self.metadata.delegations().keys().keys().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{Ed25519PrivateKey, PrivateKey};
+    use crate::metadata::{
+        Delegation, Delegations, MetadataPath, RootMetadataBuilder, TargetPath,
+        TargetsMetadataBuilder,
+    };
+    use crate::pouf::Pouf1;
+    use lazy_static::lazy_static;
+    use maplit::btreeset;
+    use pretty_assertions::assert_eq;
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[
+                include_bytes!("../tests/ed25519/ed25519-1.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-2.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-3.pk8.der"),
+            ];
+            keys.iter()
+                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+                .collect()
+        };
+    }
+
+    #[test]
+    fn inspect_root_signed_by_untrusted_key() {
+        // The root is signed by a key it does not trust, which inspecting does not notice.
+        let raw_root = RootMetadataBuilder::new()
+            .version(2)
+            .root_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[1].public().clone())
+            .targets_key(KEYS[1].public().clone())
+            .timestamp_key(KEYS[1].public().clone())
+            .signed::<Pouf1>(&KEYS[2])
+            .unwrap()
+            .to_raw()
+            .unwrap();
+
+        let root = inspect(&raw_root).unwrap();
+        assert_eq!(root.role(), Role::Root);
+        assert_eq!(root.version(), 2);
+        assert_eq!(root.spec_version(), &SpecVersion::default());
+        assert_eq!(
+            root.signature_key_ids(),
+            &[KEYS[2].public().key_id().clone()]
+        );
+        assert_eq!(
+            root.key_ids(),
+            btreeset![KEYS[0].public().key_id(), KEYS[1].public().key_id()]
+        );
+
+        assert_eq!(
+            inspect_slice::<Pouf1, RootMetadata>(raw_root.as_bytes()).unwrap(),
+            root
+        );
+    }
+
+    #[test]
+    fn inspect_targets_delegations() {
+        let delegations = Delegations::builder()
+            .key(KEYS[1].public().clone())
+            .role(
+                Delegation::builder(MetadataPath::new("delegation").unwrap())
+                    .key(KEYS[1].public())
+                    .delegate_path(TargetPath::new("foo/").unwrap())
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+        let mut targets = TargetsMetadataBuilder::new()
+            .version(5)
+            .delegations(delegations)
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap();
+        targets.add_signature(&KEYS[2]).unwrap();
+
+        let targets = inspect_signed(&targets).unwrap();
+        assert_eq!(targets.role(), Role::Targets);
+        assert_eq!(targets.version(), 5);
+        assert_eq!(
+            targets.signature_key_ids().iter().collect::<BTreeSet<_>>(),
+            btreeset![KEYS[0].public().key_id(), KEYS[2].public().key_id()]
+        );
+        assert_eq!(targets.key_ids(), btreeset![KEYS[1].public().key_id()]);
+    }
+
+    #[test]
+    fn inspect_rejects_malformed_metadata() {
+        assert!(inspect_slice::<Pouf1, RootMetadata>(b"{\"signatures\": []}").is_err());
+    }
+}
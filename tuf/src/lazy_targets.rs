@@ -0,0 +1,471 @@
+//! Lazily indexed targets metadata.
+//!
+//! Parsing targets metadata into a [TargetsMetadata] allocates a [TargetDescription] for every
+//! target it lists, which for a repository with millions of targets can take far more memory than
+//! the metadata itself. A client that only needs to look up a handful of targets can instead
+//! verify the metadata into a [LazyTargetsMetadata], which keeps the canonical bytes of the
+//! metadata along with an index of where each target is described in them, and only parses the
+//! descriptions of the targets that are looked up.
+//!
+//! Note that verifying the signatures still requires parsing the metadata once to canonicalize
+//! it, so this bounds the memory retained after verification, not the peak memory usage.
+//!
+//! The index is built by scanning the canonical bytes as JSON, so this requires a [Pouf] whose
+//! canonical form is JSON, such as [Pouf1](crate::pouf::Pouf1).
+//!
+//! [Pouf]: crate::pouf::Pouf
+
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::ops::Range;
+use std::str;
+
+use crate::error::{Error, Result};
+use crate::metadata::{
+    Delegations, Metadata, SpecVersion, TargetDescription, TargetPath, TargetsMetadata,
+};
+
+/// Targets metadata whose targets are only parsed when they are looked up.
+///
+/// A [LazyTargetsMetadata] is only created by verifying its signatures, with
+/// [verify_signatures_lazily](crate::verify::verify_signatures_lazily).
+#[derive(Clone, PartialEq, Eq)]
+pub struct LazyTargetsMetadata {
+    /// The metadata without any targets.
+    header: TargetsMetadata,
+    /// The canonical bytes of the `signed` portion of the metadata.
+    bytes: Vec<u8>,
+    /// The location of the path and the description of every target in `bytes`, sorted by path.
+    index: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl LazyTargetsMetadata {
+    /// Index the canonical bytes of the `signed` portion of targets metadata.
+    pub(crate) fn from_canonical_bytes(bytes: Vec<u8>) -> Result<Self> {
+        let mut scanner = Scanner::new(&bytes);
+        let mut fields = vec![];
+        let mut targets = None;
+        scanner.object(|scanner, key| {
+            let value = scanner.value()?;
+            if &bytes[key.clone()] == b"\"targets\"" {
+                targets = Some(value);
+            } else {
+                fields.push(key.start..value.end);
+            }
+            Ok(())
+        })?;
+        scanner.end()?;
+
+        let targets =
+            targets.ok_or_else(|| Error::Encoding("Targets metadata has no targets".into()))?;
+
+        // Parse everything but the targets, so that the rest of the metadata is validated as usual.
+        let mut header = b"{".to_vec();
+        for field in fields {
+            header.extend_from_slice(&bytes[field]);
+            header.push(b',');
+        }
+        header.extend_from_slice(b"\"targets\":{}}");
+        let header: TargetsMetadata = serde_json::from_slice(&header)?;
+
+        // The paths are still validated up front. A valid path never needs to be escaped, so it
+        // can be compared without decoding it.
+        let mut index = vec![];
+        let mut scanner = Scanner::at(&bytes, targets.start);
+        scanner.object(|scanner, key| {
+            let path = key.start + 1..key.end - 1;
+            let _ = TargetPath::new(decode_path(&bytes[path.clone()])?)?;
+            index.push((path, scanner.value()?));
+            Ok(())
+        })?;
+
+        index.sort_by(|(a, _), (b, _)| bytes[a.clone()].cmp(&bytes[b.clone()]));
+        if index
+            .windows(2)
+            .any(|pair| bytes[pair[0].0.clone()] == bytes[pair[1].0.clone()])
+        {
+            return Err(Error::Encoding(
+                "Targets metadata has duplicate targets".into(),
+            ));
+        }
+
+        Ok(LazyTargetsMetadata {
+            header,
+            bytes,
+            index,
+        })
+    }
+
+    /// The version number.
+    pub fn version(&self) -> u32 {
+        self.header.version()
+    }
+
+    /// An immutable reference to the metadata's expiration `DateTime`.
+    pub fn expires(&self) -> &DateTime<Utc> {
+        self.header.expires()
+    }
+
+    /// The version of the specification the metadata claims to follow.
+    pub fn spec_version(&self) -> &SpecVersion {
+        self.header.spec_version()
+    }
+
+    /// An immutable reference to the delegations.
+    pub fn delegations(&self) -> &Delegations {
+        self.header.delegations()
+    }
+
+    /// The number of targets.
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Whether or not there are any targets.
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Whether or not `path` is one of the targets.
+    pub fn contains(&self, path: &TargetPath) -> bool {
+        self.find(path).is_some()
+    }
+
+    /// Parse the description of `path`, if it is one of the targets.
+    pub fn get(&self, path: &TargetPath) -> Result<Option<TargetDescription>> {
+        match self.find(path) {
+            Some(value) => Ok(Some(serde_json::from_slice(&self.bytes[value])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The path of every target, in order.
+    pub fn target_paths(&self) -> impl Iterator<Item = TargetPath> + '_ {
+        self.index.iter().map(move |(path, _)| {
+            let path = str::from_utf8(&self.bytes[path.clone()]).expect("paths are validated");
+            TargetPath::new(path).expect("paths are validated")
+        })
+    }
+
+    /// The location of the description of `path`.
+    fn find(&self, path: &TargetPath) -> Option<Range<usize>> {
+        self.index
+            .binary_search_by(|(key, _)| self.bytes[key.clone()].cmp(path.as_str().as_bytes()))
+            .ok()
+            .map(|i| self.index[i].1.clone())
+    }
+}
+
+impl fmt::Debug for LazyTargetsMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The canonical bytes may be very large, so only summarize them.
+        f.debug_struct("LazyTargetsMetadata")
+            .field("version", &self.version())
+            .field("expires", self.expires())
+            .field("delegations", self.delegations())
+            .field("targets", &self.len())
+            .finish()
+    }
+}
+
+fn decode_path(raw: &[u8]) -> Result<&str> {
+    if raw.contains(&b'\\') {
+        return Err(Error::Encoding(
+            "Target paths cannot contain escapes".into(),
+        ));
+    }
+    str::from_utf8(raw).map_err(|e| Error::Encoding(e.to_string()))
+}
+
+/// Finds the boundaries of JSON values without parsing them.
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self::at(bytes, 0)
+    }
+
+    fn at(bytes: &'a [u8], pos: usize) -> Self {
+        Scanner { bytes, pos }
+    }
+
+    fn error<T>(&self, msg: &str) -> Result<T> {
+        Err(Error::Encoding(format!(
+            "Invalid targets metadata at byte {}: {}",
+            self.pos, msg
+        )))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<()> {
+        if self.peek() != Some(byte) {
+            return self.error(&format!("expected {:?}", byte as char));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Check that there is nothing but whitespace left.
+    fn end(&mut self) -> Result<()> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => self.error("trailing characters"),
+        }
+    }
+
+    /// Scan an object, calling `f` with the location of every key, which must scan the value.
+    fn object<F>(&mut self, mut f: F) -> Result<()>
+    where
+        F: FnMut(&mut Self, Range<usize>) -> Result<()>,
+    {
+        self.expect(b'{')?;
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(());
+        }
+        loop {
+            let key = self.string()?;
+            self.expect(b':')?;
+            f(self, key)?;
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                _ => return self.error("expected ',' or '}'"),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<Range<usize>> {
+        if self.peek() != Some(b'"') {
+            return self.error("expected a string");
+        }
+        let start = self.pos;
+        self.pos += 1;
+        loop {
+            match self.bytes.get(self.pos) {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(start..self.pos);
+                }
+                Some(b'\\') => self.pos += 2,
+                Some(_) => self.pos += 1,
+                None => return self.error("unterminated string"),
+            }
+        }
+    }
+
+    /// Scan any value. Values are validated when they are parsed, so this only needs to find where
+    /// they end.
+    fn value(&mut self) -> Result<Range<usize>> {
+        let start = match self.peek() {
+            Some(_) => self.pos,
+            None => return self.error("expected a value"),
+        };
+        let mut depth = 0usize;
+        loop {
+            match self.bytes.get(self.pos) {
+                Some(b'"') => {
+                    let _ = self.string()?;
+                }
+                Some(b'{' | b'[') => {
+                    depth += 1;
+                    self.pos += 1;
+                }
+                Some(b'}' | b']') if depth > 0 => {
+                    depth -= 1;
+                    self.pos += 1;
+                }
+                Some(b',' | b'}' | b']') if depth == 0 => break,
+                Some(_) => self.pos += 1,
+                None if depth == 0 => break,
+                None => return self.error("unterminated value"),
+            }
+
+            // A string, object or array ends as soon as it is closed.
+            if depth == 0 && matches!(self.bytes[self.pos - 1], b'"' | b'}' | b']') {
+                break;
+            }
+        }
+
+        let mut end = self.pos;
+        while end > start && self.bytes[end - 1].is_ascii_whitespace() {
+            end -= 1;
+        }
+        Ok(start..end)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::{Client, Config};
+    use crate::crypto::{Ed25519PrivateKey, HashAlgorithm, PrivateKey};
+    use crate::metadata::{MetadataPath, RawSignedMetadata};
+    use crate::pouf::Pouf1;
+    use crate::repo_builder::RepoBuilder;
+    use crate::repository::EphemeralRepository;
+    use crate::verify::verify_signatures_lazily;
+    use assert_matches::assert_matches;
+    use futures_executor::block_on;
+    use futures_util::io::Cursor;
+    use lazy_static::lazy_static;
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[include_bytes!("../tests/ed25519/ed25519-1.pk8.der")];
+            keys.iter()
+                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+                .collect()
+        };
+    }
+
+    #[test]
+    fn test_lazy_targets() {
+        block_on(async {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            let files = [
+                ("foo", &b"foo file"[..]),
+                ("bar/baz", &b"baz file"[..]),
+                ("qux", &b"qux file"[..]),
+            ];
+
+            let mut builder = RepoBuilder::create(&mut repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap();
+            for (path, file) in &files {
+                builder = builder
+                    .add_target(TargetPath::new(*path).unwrap(), Cursor::new(*file))
+                    .await
+                    .unwrap();
+            }
+            let metadata = builder.commit().await.unwrap();
+
+            let raw_targets = metadata.targets().unwrap();
+            let targets = raw_targets
+                .parse_untrusted()
+                .unwrap()
+                .assume_valid()
+                .unwrap();
+            let lazy = verify_signatures_lazily(
+                &MetadataPath::targets(),
+                raw_targets,
+                1,
+                [KEYS[0].public()],
+            )
+            .unwrap();
+
+            assert_eq!(lazy.version(), targets.version());
+            assert_eq!(lazy.expires(), targets.expires());
+            assert_eq!(lazy.delegations(), targets.delegations());
+            assert_eq!(lazy.len(), 3);
+            assert_eq!(
+                lazy.target_paths()
+                    .map(|path| path.as_str().to_owned())
+                    .collect::<Vec<_>>(),
+                vec!["bar/baz", "foo", "qux"]
+            );
+            for (path, file) in &files {
+                let path = TargetPath::new(*path).unwrap();
+                assert_eq!(
+                    lazy.get(&path).unwrap(),
+                    Some(TargetDescription::from_slice(file, &[HashAlgorithm::Sha256]).unwrap())
+                );
+                assert_eq!(
+                    lazy.get(&path).unwrap().as_ref(),
+                    targets.targets().get(&path)
+                );
+            }
+            assert_eq!(lazy.get(&TargetPath::new("bar").unwrap()).unwrap(), None);
+            assert!(!lazy.contains(&TargetPath::new("zzz").unwrap()));
+
+            // A lookup-only client doesn't have to trust the top-level targets metadata.
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                repo,
+            )
+            .await
+            .unwrap();
+            let lazy = client.fetch_lazy_targets().await.unwrap();
+            assert!(client.database().trusted_targets().is_none());
+            assert!(lazy.contains(&TargetPath::new("qux").unwrap()));
+
+            // The signatures are still verified.
+            let bytes = String::from_utf8(raw_targets.as_bytes().to_vec())
+                .unwrap()
+                .replace("foo", "fop");
+            assert_matches!(
+                verify_signatures_lazily(
+                    &MetadataPath::targets(),
+                    &RawSignedMetadata::<Pouf1, _>::new(bytes.into_bytes()),
+                    1,
+                    [KEYS[0].public()],
+                ),
+                Err(Error::MetadataMissingSignatures { .. })
+            );
+        })
+    }
+
+    #[test]
+    fn test_index() {
+        let bytes = br#"{"_type":"targets","expires":"2038-01-01T00:00:00Z","spec_version":"1.0.0",
+            "targets":{"b":{"custom":{"x":[1,{"y":"}]\""}]},"hashes":{},"length":2} ,
+            "a":{"hashes":{},"length":1}},"version":1}"#;
+        let lazy = LazyTargetsMetadata::from_canonical_bytes(bytes.to_vec()).unwrap();
+        assert_eq!(lazy.version(), 1);
+        assert_eq!(
+            lazy.target_paths()
+                .map(|path| path.as_str().to_owned())
+                .collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            lazy.get(&TargetPath::new("b").unwrap())
+                .unwrap()
+                .unwrap()
+                .length(),
+            Some(2)
+        );
+
+        // Paths are still validated up front, but descriptions only when they are looked up.
+        let bytes = String::from_utf8(bytes.to_vec()).unwrap();
+        assert_matches!(
+            LazyTargetsMetadata::from_canonical_bytes(bytes.replace("\"a\"", "\"a\\\"\"").into()),
+            Err(Error::Encoding(_))
+        );
+        let lazy =
+            LazyTargetsMetadata::from_canonical_bytes(bytes.replace("\"length\":1", "").into())
+                .unwrap();
+        assert_matches!(lazy.get(&TargetPath::new("a").unwrap()), Err(_));
+
+        assert_matches!(
+            LazyTargetsMetadata::from_canonical_bytes(br#"{"version":1"#.to_vec()),
+            Err(Error::Encoding(_))
+        );
+        assert_matches!(
+            LazyTargetsMetadata::from_canonical_bytes(br#"{"version":1}"#.to_vec()),
+            Err(Error::Encoding(_))
+        );
+    }
+}
@@ -111,15 +111,44 @@
     clippy::too_many_arguments
 )]
 
+pub mod attestation;
+#[cfg(feature = "auto-update")]
+pub mod auto_update;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "bundle")]
+pub mod bundle;
+pub mod cancel;
 pub mod client;
+pub mod clock;
+#[cfg(feature = "compression")]
+pub mod compression;
 pub mod crypto;
 pub mod database;
+pub mod delegation_graph;
+pub mod delta;
 pub mod error;
+pub mod hashed_bins;
+pub mod inspect;
+pub mod lazy_targets;
+pub mod lint;
+pub mod merkle;
 pub mod metadata;
+pub mod multi_repo;
+pub mod policy;
 pub mod pouf;
+pub mod prune;
 pub mod repo_builder;
+#[cfg(feature = "repo-refresher")]
+pub mod repo_refresher;
 pub mod repository;
+pub mod rollback;
+pub mod root_ceremony;
+#[cfg(feature = "uptane")]
+pub mod uptane;
 pub mod verify;
+#[cfg(feature = "semver")]
+pub mod versions;
 
 mod format_hex;
 mod util;
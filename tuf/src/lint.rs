@@ -0,0 +1,970 @@
+//! Checking the metadata of a repository for problems before it is published.
+//!
+//! [check_repo] fetches the whole metadata set of a repository and reports every problem it finds
+//! as a [Finding]. Unlike a [Client](crate::client::Client), which stops at the first metadata
+//! it cannot trust, the linter keeps going so that a publish pipeline can report everything that
+//! needs fixing at once. The [LintReport] can be serialized to hand it to other tooling.
+//!
+//! [check_repo_and_targets] also fetches every target and checks it against its description,
+//! which is slower, but catches targets that were stored incorrectly or not at all.
+
+use chrono::{DateTime, Duration, Utc};
+use futures_util::io::AsyncReadExt as _;
+use serde_derive::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::crypto::{self, KeyId, PublicKey};
+use crate::metadata::{
+    Delegations, Metadata, MetadataDescription, MetadataPath, MetadataVersion, RawSignedMetadata,
+    RootMetadata, SnapshotMetadata, TargetsMetadata, TimestampMetadata,
+};
+use crate::pouf::Pouf;
+use crate::repository::{Repository, RepositoryProvider};
+use crate::verify::verify_signatures;
+
+/// Metadata that expires within this period of the time of the check is reported as
+/// [FindingKind::ExpiresSoon].
+pub const EXPIRY_WARNING_PERIOD: Duration = Duration::hours(12);
+
+/// How serious a [Finding] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The metadata works, but is likely to cause problems.
+    Warning,
+    /// Clients will reject the metadata, or fail to find some targets.
+    Error,
+}
+
+/// The kinds of problems that [check_repo] finds.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingKind {
+    /// The metadata could not be fetched.
+    Unavailable,
+    /// The metadata could not be parsed.
+    Malformed,
+    /// The metadata is not signed by a threshold of the keys trusted for it.
+    InvalidSignatures,
+    /// The metadata has expired.
+    Expired,
+    /// The metadata expires within [EXPIRY_WARNING_PERIOD].
+    ExpiresSoon,
+    /// A role requires more signatures than it has keys.
+    UnreachableThreshold,
+    /// A role trusts a key ID that is not listed with the keys.
+    UnknownKey,
+    /// A key is listed, but not trusted by any role.
+    UnusedKey,
+    /// A version of the root metadata is missing.
+    VersionGap,
+    /// The version of the metadata does not match the version it was fetched as, or the version
+    /// described by its parent metadata.
+    VersionMismatch,
+    /// The length or hashes of the metadata do not match its description.
+    DescriptionMismatch,
+    /// A role is delegated to, but its metadata is not listed in the snapshot.
+    DanglingDelegation,
+    /// The snapshot lists metadata that no role delegates to.
+    UnreferencedMetadata,
+    /// A target could not be fetched, or does not match the length or hashes it is described by.
+    TargetMismatch,
+}
+
+/// A problem with the metadata of a repository.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    severity: Severity,
+    kind: FindingKind,
+    role: MetadataPath,
+    message: String,
+}
+
+impl Finding {
+    /// How serious the problem is.
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// The kind of problem.
+    pub fn kind(&self) -> FindingKind {
+        self.kind
+    }
+
+    /// The role whose metadata has the problem.
+    pub fn role(&self) -> &MetadataPath {
+        &self.role
+    }
+
+    /// A human readable description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// The findings of [check_repo].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LintReport {
+    findings: Vec<Finding>,
+}
+
+impl LintReport {
+    /// Every finding, in the order the metadata was checked.
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// The findings of severity [Severity::Error].
+    pub fn errors(&self) -> impl Iterator<Item = &Finding> + '_ {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == Severity::Error)
+    }
+
+    /// Whether there are any findings of severity [Severity::Error].
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// Whether there are no findings at all.
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+/// Check the metadata of `repo` for problems. See the [module](self) documentation for details.
+///
+/// Metadata that cannot be fetched or parsed is reported in the [LintReport] too, along with
+/// anything that could not be checked because of it.
+pub async fn check_repo<D>(repo: &dyn RepositoryProvider<D>) -> LintReport
+where
+    D: Pouf,
+{
+    check_repo_with_start_time(repo, &Utc::now()).await
+}
+
+/// Check the metadata of `repo` for problems, as if the time were `start_time`. See
+/// [check_repo].
+pub async fn check_repo_with_start_time<D>(
+    repo: &dyn RepositoryProvider<D>,
+    start_time: &DateTime<Utc>,
+) -> LintReport
+where
+    D: Pouf,
+{
+    let mut linter = Linter {
+        repo: Repository::new(repo),
+        start_time: *start_time,
+        check_targets: false,
+        report: LintReport::default(),
+    };
+    linter.check().await;
+    linter.report
+}
+
+/// Check the metadata of `repo` for problems like [check_repo], and also fetch every target the
+/// metadata describes and check its length and hashes, like a client would.
+///
+/// Targets that are described without a length or hashes can't be verified, and are skipped.
+pub async fn check_repo_and_targets<D>(repo: &dyn RepositoryProvider<D>) -> LintReport
+where
+    D: Pouf,
+{
+    check_repo_and_targets_with_start_time(repo, &Utc::now()).await
+}
+
+/// Check the metadata and targets of `repo` for problems, as if the time were `start_time`. See
+/// [check_repo_and_targets].
+pub async fn check_repo_and_targets_with_start_time<D>(
+    repo: &dyn RepositoryProvider<D>,
+    start_time: &DateTime<Utc>,
+) -> LintReport
+where
+    D: Pouf,
+{
+    let mut linter = Linter {
+        repo: Repository::new(repo),
+        start_time: *start_time,
+        check_targets: true,
+        report: LintReport::default(),
+    };
+    linter.check().await;
+    linter.report
+}
+
+struct Linter<'a, D: Pouf> {
+    repo: Repository<&'a dyn RepositoryProvider<D>, D>,
+    start_time: DateTime<Utc>,
+    check_targets: bool,
+    report: LintReport,
+}
+
+impl<'a, D> Linter<'a, D>
+where
+    D: Pouf,
+{
+    fn find(
+        &mut self,
+        severity: Severity,
+        kind: FindingKind,
+        role: &MetadataPath,
+        message: String,
+    ) {
+        self.report.findings.push(Finding {
+            severity,
+            kind,
+            role: role.clone(),
+            message,
+        });
+    }
+
+    async fn check(&mut self) {
+        let root = match self.check_root().await {
+            Some(root) => root,
+            None => return,
+        };
+
+        let timestamp_path = MetadataPath::timestamp();
+        let timestamp = match self
+            .fetch::<TimestampMetadata>(&timestamp_path, MetadataVersion::None)
+            .await
+        {
+            Some((raw, timestamp)) => {
+                self.check_signatures(
+                    &timestamp_path,
+                    &raw,
+                    root.timestamp().threshold(),
+                    root.timestamp_keys(),
+                );
+                self.check_expiry(&timestamp_path, &timestamp);
+                timestamp
+            }
+            None => return,
+        };
+
+        let snapshot_path = MetadataPath::snapshot();
+        let snapshot = match self
+            .fetch_described::<SnapshotMetadata>(&root, &snapshot_path, timestamp.snapshot())
+            .await
+        {
+            Some((raw, snapshot)) => {
+                self.check_signatures(
+                    &snapshot_path,
+                    &raw,
+                    root.snapshot().threshold(),
+                    root.snapshot_keys(),
+                );
+                self.check_expiry(&snapshot_path, &snapshot);
+                snapshot
+            }
+            None => return,
+        };
+
+        let targets_path = MetadataPath::targets();
+        let targets = match snapshot.meta().get(&targets_path) {
+            Some(description) => match self
+                .fetch_described::<TargetsMetadata>(&root, &targets_path, description)
+                .await
+            {
+                Some((raw, targets)) => {
+                    self.check_signatures(
+                        &targets_path,
+                        &raw,
+                        root.targets().threshold(),
+                        root.targets_keys(),
+                    );
+                    self.check_expiry(&targets_path, &targets);
+                    targets
+                }
+                None => return,
+            },
+            None => {
+                self.find(
+                    Severity::Error,
+                    FindingKind::DanglingDelegation,
+                    &targets_path,
+                    "the snapshot does not list the targets metadata".into(),
+                );
+                return;
+            }
+        };
+
+        let mut visited = HashSet::from([targets_path.clone()]);
+        let mut queue = vec![(targets_path, targets)];
+        while let Some((parent, targets)) = queue.pop() {
+            if self.check_targets {
+                self.check_target_files(&root, &parent, &targets).await;
+            }
+
+            let delegations = targets.delegations();
+            self.check_delegation_keys(&parent, delegations);
+
+            // Only the bins of succinct hash bin delegations that are in the snapshot are checked,
+            // as there may be far too many to check all of them.
+            let mut children = delegations.role_names().cloned().collect::<Vec<_>>();
+            if let Some(succinct_roles) = delegations.succinct_roles() {
+                children.extend(
+                    snapshot
+                        .meta()
+                        .keys()
+                        .filter(|role| succinct_roles.is_delegated_role(role))
+                        .cloned(),
+                );
+            }
+            children.sort();
+
+            for child in children {
+                if !visited.insert(child.clone()) {
+                    continue;
+                }
+
+                let description = match snapshot.meta().get(&child) {
+                    Some(description) => description,
+                    None => {
+                        self.find(
+                            Severity::Error,
+                            FindingKind::DanglingDelegation,
+                            &child,
+                            format!(
+                                "{} delegates to {}, which is not listed in the snapshot",
+                                parent, child
+                            ),
+                        );
+                        continue;
+                    }
+                };
+
+                let delegation = match delegations.delegation(&child) {
+                    Some(delegation) => delegation.into_owned(),
+                    None => continue,
+                };
+
+                if let Some((raw, child_targets)) = self
+                    .fetch_described::<TargetsMetadata>(&root, &child, description)
+                    .await
+                {
+                    let keys = delegation
+                        .key_ids()
+                        .iter()
+                        .filter_map(|key_id| delegations.keys().get(key_id));
+                    self.check_signatures(&child, &raw, delegation.threshold(), keys);
+                    self.check_expiry(&child, &child_targets);
+                    queue.push((child, child_targets));
+                }
+            }
+        }
+
+        let mut unreferenced = snapshot
+            .meta()
+            .keys()
+            .filter(|role| !visited.contains(*role))
+            .collect::<Vec<_>>();
+        unreferenced.sort();
+        for role in unreferenced {
+            self.find(
+                Severity::Warning,
+                FindingKind::UnreferencedMetadata,
+                role,
+                format!("the snapshot lists {}, but no role delegates to it", role),
+            );
+        }
+    }
+
+    /// Fetch every verifiable target of the targets metadata `role`, and check it against its
+    /// description.
+    async fn check_target_files(
+        &mut self,
+        root: &RootMetadata,
+        role: &MetadataPath,
+        targets: &TargetsMetadata,
+    ) {
+        let mut target_paths = targets
+            .targets()
+            .iter()
+            .filter(|(_, description)| description.is_verifiable())
+            .collect::<Vec<_>>();
+        target_paths.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (target_path, description) in target_paths {
+            let result = match self
+                .repo
+                .fetch_target(root.consistent_snapshot(), target_path, description.clone())
+                .await
+            {
+                Ok(mut reader) => {
+                    // The reader fails if the target doesn't match its description.
+                    let mut buf = Vec::new();
+                    reader
+                        .read_to_end(&mut buf)
+                        .await
+                        .map(|_| ())
+                        .map_err(|err| err.to_string())
+                }
+                Err(err) => Err(err.to_string()),
+            };
+
+            if let Err(err) = result {
+                self.find(
+                    Severity::Error,
+                    FindingKind::TargetMismatch,
+                    role,
+                    format!("failed to verify the target {}: {}", target_path, err),
+                );
+            }
+        }
+    }
+
+    /// Check every version of the root metadata, and return the latest one.
+    async fn check_root(&mut self) -> Option<RootMetadata> {
+        let path = MetadataPath::root();
+        let (raw, root) = self
+            .fetch::<RootMetadata>(&path, MetadataVersion::None)
+            .await?;
+
+        // Every version must be signed by both the previous version and itself.
+        let mut previous: Option<RootMetadata> = None;
+        for version in 1..root.version() {
+            let (raw, versioned_root) = match self
+                .fetch_versioned::<RootMetadata>(&path, version, FindingKind::VersionGap)
+                .await
+            {
+                Some(fetched) => fetched,
+                None => {
+                    previous = None;
+                    continue;
+                }
+            };
+
+            self.check_root_signatures(&raw, previous.as_ref(), &versioned_root);
+            previous = Some(versioned_root);
+        }
+        self.check_root_signatures(&raw, previous.as_ref(), &root);
+
+        let roles = [
+            (
+                MetadataPath::root(),
+                root.root().threshold(),
+                root.root().key_ids(),
+            ),
+            (
+                MetadataPath::snapshot(),
+                root.snapshot().threshold(),
+                root.snapshot().key_ids(),
+            ),
+            (
+                MetadataPath::targets(),
+                root.targets().threshold(),
+                root.targets().key_ids(),
+            ),
+            (
+                MetadataPath::timestamp(),
+                root.timestamp().threshold(),
+                root.timestamp().key_ids(),
+            ),
+        ];
+        for (role, threshold, key_ids) in &roles {
+            self.check_threshold(role, *threshold, key_ids, root.keys());
+        }
+        self.check_unused_keys(
+            &path,
+            root.keys(),
+            roles.iter().flat_map(|(_, _, key_ids)| key_ids.iter()),
+        );
+        self.check_expiry(&path, &root);
+
+        Some(root)
+    }
+
+    fn check_root_signatures(
+        &mut self,
+        raw: &RawSignedMetadata<D, RootMetadata>,
+        previous: Option<&RootMetadata>,
+        root: &RootMetadata,
+    ) {
+        let path = MetadataPath::root();
+        if let Some(previous) = previous {
+            if verify_signatures(
+                &path,
+                raw,
+                previous.root().threshold(),
+                previous.root_keys(),
+            )
+            .is_err()
+            {
+                self.find(
+                    Severity::Error,
+                    FindingKind::InvalidSignatures,
+                    &path,
+                    format!(
+                        "version {} is not signed by a threshold of the root keys of version {}",
+                        root.version(),
+                        previous.version()
+                    ),
+                );
+            }
+        }
+
+        if verify_signatures(&path, raw, root.root().threshold(), root.root_keys()).is_err() {
+            self.find(
+                Severity::Error,
+                FindingKind::InvalidSignatures,
+                &path,
+                format!(
+                    "version {} is not signed by a threshold of its own root keys",
+                    root.version()
+                ),
+            );
+        }
+    }
+
+    fn check_delegation_keys(&mut self, role: &MetadataPath, delegations: &Delegations) {
+        let mut used_key_ids = vec![];
+        for child in delegations.role_names() {
+            if let Some(delegation) = delegations.delegation(child) {
+                self.check_threshold(
+                    child,
+                    delegation.threshold(),
+                    delegation.key_ids(),
+                    delegations.keys(),
+                );
+                used_key_ids.extend(delegation.key_ids().iter().cloned());
+            }
+        }
+        if let Some(succinct_roles) = delegations.succinct_roles() {
+            self.check_threshold(
+                role,
+                succinct_roles.threshold(),
+                succinct_roles.key_ids(),
+                delegations.keys(),
+            );
+            used_key_ids.extend(succinct_roles.key_ids().iter().cloned());
+        }
+
+        self.check_unused_keys(role, delegations.keys(), used_key_ids.iter());
+    }
+
+    fn check_threshold(
+        &mut self,
+        role: &MetadataPath,
+        threshold: u32,
+        key_ids: &HashSet<KeyId>,
+        keys: &HashMap<KeyId, PublicKey>,
+    ) {
+        let mut unknown_key_ids = key_ids
+            .iter()
+            .filter(|key_id| !keys.contains_key(*key_id))
+            .collect::<Vec<_>>();
+        unknown_key_ids.sort();
+        for key_id in &unknown_key_ids {
+            self.find(
+                Severity::Error,
+                FindingKind::UnknownKey,
+                role,
+                format!("{} trusts the unknown key {:?}", role, key_id),
+            );
+        }
+
+        let known_keys = key_ids.len() - unknown_key_ids.len();
+        if threshold as usize > known_keys {
+            self.find(
+                Severity::Error,
+                FindingKind::UnreachableThreshold,
+                role,
+                format!(
+                    "{} requires {} signatures, but only has {} keys",
+                    role, threshold, known_keys
+                ),
+            );
+        }
+    }
+
+    fn check_unused_keys<'b, I>(
+        &mut self,
+        role: &MetadataPath,
+        keys: &HashMap<KeyId, PublicKey>,
+        used_key_ids: I,
+    ) where
+        I: IntoIterator<Item = &'b KeyId>,
+    {
+        let used_key_ids = used_key_ids.into_iter().collect::<HashSet<_>>();
+        let mut unused_key_ids = keys
+            .keys()
+            .filter(|key_id| !used_key_ids.contains(key_id))
+            .collect::<Vec<_>>();
+        unused_key_ids.sort();
+        for key_id in unused_key_ids {
+            self.find(
+                Severity::Warning,
+                FindingKind::UnusedKey,
+                role,
+                format!("{} lists the key {:?}, but no role trusts it", role, key_id),
+            );
+        }
+    }
+
+    fn check_signatures<'b, M, I>(
+        &mut self,
+        role: &MetadataPath,
+        raw: &RawSignedMetadata<D, M>,
+        threshold: u32,
+        keys: I,
+    ) where
+        M: Metadata,
+        I: IntoIterator<Item = &'b PublicKey>,
+    {
+        if let Err(err) = verify_signatures(role, raw, threshold, keys) {
+            self.find(
+                Severity::Error,
+                FindingKind::InvalidSignatures,
+                role,
+                err.to_string(),
+            );
+        }
+    }
+
+    fn check_expiry<M: Metadata>(&mut self, role: &MetadataPath, metadata: &M) {
+        let expires = *metadata.expires();
+        if expires <= self.start_time {
+            self.find(
+                Severity::Error,
+                FindingKind::Expired,
+                role,
+                format!("{} expired at {}", role, expires),
+            );
+        } else if expires <= self.start_time + EXPIRY_WARNING_PERIOD {
+            self.find(
+                Severity::Warning,
+                FindingKind::ExpiresSoon,
+                role,
+                format!("{} expires at {}", role, expires),
+            );
+        }
+    }
+
+    /// Fetch the metadata `role` as described by `description`, and check that it matches the
+    /// description.
+    async fn fetch_described<M: Metadata>(
+        &mut self,
+        root: &RootMetadata,
+        role: &MetadataPath,
+        description: &MetadataDescription<M>,
+    ) -> Option<(RawSignedMetadata<D, M>, M)> {
+        let (raw, metadata) = if root.consistent_snapshot() {
+            self.fetch_versioned(role, description.version(), FindingKind::Unavailable)
+                .await?
+        } else {
+            let fetched = self.fetch::<M>(role, MetadataVersion::None).await?;
+            if fetched.1.version() != description.version() {
+                self.find(
+                    Severity::Error,
+                    FindingKind::VersionMismatch,
+                    role,
+                    format!(
+                        "{} has version {}, but is described as version {}",
+                        role,
+                        fetched.1.version(),
+                        description.version()
+                    ),
+                );
+            }
+            fetched
+        };
+
+        let bytes = raw.as_bytes();
+        let length_matches = description
+            .length()
+            .map_or(true, |length| length == bytes.len());
+        let hashes_match = description
+            .hashes()
+            .iter()
+            .filter(|(alg, _)| crypto::is_supported_hash_algorithm(alg))
+            .all(|(alg, value)| {
+                crypto::calculate_hashes_from_slice(bytes, std::slice::from_ref(alg))
+                    .map_or(false, |hashes| hashes.get(alg) == Some(value))
+            });
+        if !length_matches || !hashes_match {
+            self.find(
+                Severity::Error,
+                FindingKind::DescriptionMismatch,
+                role,
+                format!(
+                    "{} does not match the length or hashes it is described by",
+                    role
+                ),
+            );
+        }
+
+        Some((raw, metadata))
+    }
+
+    /// Fetch version `version` of the metadata `role`, reporting a finding of `missing` if it
+    /// cannot be fetched.
+    async fn fetch_versioned<M: Metadata>(
+        &mut self,
+        role: &MetadataPath,
+        version: u32,
+        missing: FindingKind,
+    ) -> Option<(RawSignedMetadata<D, M>, M)> {
+        let raw = match self
+            .repo
+            .fetch_metadata::<M>(role, MetadataVersion::Number(version), None, vec![])
+            .await
+        {
+            Ok(raw) => raw,
+            Err(err) => {
+                self.find(
+                    Severity::Error,
+                    missing,
+                    role,
+                    format!("failed to fetch version {} of {}: {}", version, role, err),
+                );
+                return None;
+            }
+        };
+
+        let metadata = self.parse(role, &raw)?;
+        if metadata.version() != version {
+            self.find(
+                Severity::Error,
+                FindingKind::VersionMismatch,
+                role,
+                format!(
+                    "version {} of {} claims to be version {}",
+                    version,
+                    role,
+                    metadata.version()
+                ),
+            );
+        }
+
+        Some((raw, metadata))
+    }
+
+    async fn fetch<M: Metadata>(
+        &mut self,
+        role: &MetadataPath,
+        version: MetadataVersion,
+    ) -> Option<(RawSignedMetadata<D, M>, M)> {
+        let raw = match self
+            .repo
+            .fetch_metadata::<M>(role, version, None, vec![])
+            .await
+        {
+            Ok(raw) => raw,
+            Err(err) => {
+                self.find(
+                    Severity::Error,
+                    FindingKind::Unavailable,
+                    role,
+                    format!("failed to fetch {}: {}", role, err),
+                );
+                return None;
+            }
+        };
+
+        let metadata = self.parse(role, &raw)?;
+        Some((raw, metadata))
+    }
+
+    fn parse<M: Metadata>(
+        &mut self,
+        role: &MetadataPath,
+        raw: &RawSignedMetadata<D, M>,
+    ) -> Option<M> {
+        match raw
+            .parse_untrusted()
+            .and_then(|signed| signed.assume_valid())
+        {
+            Ok(metadata) => Some(metadata),
+            Err(err) => {
+                self.find(
+                    Severity::Error,
+                    FindingKind::Malformed,
+                    role,
+                    format!("failed to parse {}: {}", role, err),
+                );
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{Ed25519PrivateKey, PrivateKey};
+    use crate::metadata::{Delegation, TargetPath};
+    use crate::pouf::Pouf1;
+    use crate::repo_builder::RepoBuilder;
+    use crate::repository::{EphemeralRepository, RepositoryStorage};
+    use futures_executor::block_on;
+    use futures_util::io::Cursor;
+    use lazy_static::lazy_static;
+    use pretty_assertions::assert_eq;
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[
+                include_bytes!("../tests/ed25519/ed25519-1.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-2.pk8.der"),
+            ];
+            keys.iter()
+                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+                .collect()
+        };
+    }
+
+    fn kinds(report: &LintReport) -> Vec<(Severity, FindingKind, &str)> {
+        report
+            .findings()
+            .iter()
+            .map(|finding| (finding.severity(), finding.kind(), finding.role().as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_check_repo() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            let _ = RepoBuilder::create(&repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .add_target(TargetPath::new("foo").unwrap(), Cursor::new(b"foo"))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let report = check_repo::<Pouf1>(&repo).await;
+            assert!(report.is_empty(), "{:?}", report);
+
+            // Almost a week later, the timestamp has expired and the snapshot is about to.
+            let report =
+                check_repo_with_start_time::<Pouf1>(&repo, &(Utc::now() + Duration::hours(162)))
+                    .await;
+            assert_eq!(
+                kinds(&report),
+                vec![
+                    (Severity::Error, FindingKind::Expired, "timestamp"),
+                    (Severity::Warning, FindingKind::ExpiresSoon, "snapshot"),
+                ]
+            );
+            assert!(report.has_errors());
+        })
+    }
+
+    #[test]
+    fn test_check_repo_and_targets() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            let target_path = TargetPath::new("foo").unwrap();
+            let metadata = RepoBuilder::create(&repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .add_target(target_path.clone(), Cursor::new(b"foo"))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let report = check_repo_and_targets::<Pouf1>(&repo).await;
+            assert!(report.is_empty(), "{:?}", report);
+
+            // Corrupt the stored target.
+            let targets = metadata.targets().unwrap().parse_untrusted().unwrap();
+            let targets = targets.assume_valid().unwrap();
+            for hash in targets.targets()[&target_path].hashes().values() {
+                repo.store_target(
+                    &target_path.with_hash_prefix(hash).unwrap(),
+                    &mut &b"bar"[..],
+                )
+                .await
+                .unwrap();
+            }
+
+            // Only checking the targets finds the problem.
+            assert!(check_repo::<Pouf1>(&repo).await.is_empty());
+
+            let report = check_repo_and_targets::<Pouf1>(&repo).await;
+            assert_eq!(
+                kinds(&report),
+                vec![(Severity::Error, FindingKind::TargetMismatch, "targets")]
+            );
+        })
+    }
+
+    #[test]
+    fn test_check_repo_delegations() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .add_delegation_key(KEYS[0].public().clone())
+                .add_delegation_key(KEYS[1].public().clone())
+                .add_delegation_role(
+                    Delegation::builder(MetadataPath::new("delegation").unwrap())
+                        .key(KEYS[0].public())
+                        .delegate_path(TargetPath::new("foo/").unwrap())
+                        .build()
+                        .unwrap(),
+                )
+                .commit()
+                .await
+                .unwrap();
+
+            let report = check_repo::<Pouf1>(&repo).await;
+            assert_eq!(
+                kinds(&report),
+                vec![
+                    (Severity::Warning, FindingKind::UnusedKey, "targets"),
+                    (
+                        Severity::Error,
+                        FindingKind::DanglingDelegation,
+                        "delegation"
+                    ),
+                ]
+            );
+            assert_eq!(
+                serde_json::to_value(&report.findings()[1]).unwrap(),
+                serde_json::json!({
+                    "severity": "error",
+                    "kind": "dangling_delegation",
+                    "role": "delegation",
+                    "message": "targets delegates to delegation, which is not listed in the \
+                        snapshot",
+                })
+            );
+
+            // Stripping the signatures of the root metadata makes it untrusted.
+            let mut root: serde_json::Value =
+                serde_json::from_slice(metadata.root().unwrap().as_bytes()).unwrap();
+            root["signatures"] = serde_json::json!([]);
+            let root = serde_json::to_vec(&root).unwrap();
+            repo.store_metadata(&MetadataPath::root(), MetadataVersion::None, &mut &*root)
+                .await
+                .unwrap();
+
+            let report = check_repo::<Pouf1>(&repo).await;
+            assert_eq!(
+                kinds(&report)[0],
+                (Severity::Error, FindingKind::InvalidSignatures, "root")
+            );
+        })
+    }
+}
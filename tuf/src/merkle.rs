@@ -0,0 +1,344 @@
+//! Snapshot Merkle trees, as described in [TAP 16].
+//!
+//! Repositories with a very large number of delegated roles have a correspondingly large snapshot
+//! metadata, which every client would otherwise have to download on every update. Instead, such a
+//! repository can build a Merkle tree over the entries of its snapshot metadata and sign the root
+//! of the tree in its timestamp metadata. For every targets role it then publishes a
+//! [SnapshotMerkleProof], which lets a client verify the version, length and hashes of that role
+//! against the Merkle root without downloading the snapshot. Proofs are stored apart from the
+//! metadata, see [RepositoryProvider::fetch_snapshot_merkle_proof].
+//!
+//! The tree is built over the snapshot entries sorted by role name. Each leaf is the SHA-256 of a
+//! `0x00` byte followed by the canonical JSON encoding of the entry, and each interior node is the
+//! SHA-256 of a `0x01` byte followed by the hashes of its two children. A node without a sibling
+//! is promoted to the next level unchanged. Leaves are always encoded with [Pouf1], whatever
+//! [Pouf] the repository uses for its metadata, so the Merkle root of a set of snapshot entries
+//! does not depend on how the metadata is encoded.
+//!
+//! [TAP 16]: https://github.com/theupdateframework/taps/blob/master/tap16.md
+//! [RepositoryProvider::fetch_snapshot_merkle_proof]: crate::repository::RepositoryProvider::fetch_snapshot_merkle_proof
+
+use ring::digest::{self, SHA256};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::crypto::{HashAlgorithm, HashValue};
+use crate::error::{Error, Result};
+use crate::metadata::{MetadataDescription, MetadataPath, SnapshotMetadata, TargetsMetadata};
+use crate::pouf::{Pouf, Pouf1};
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// The contents of a leaf of a snapshot Merkle tree: a role and its snapshot entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct SnapshotMerkleLeaf {
+    name: String,
+    version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    length: Option<usize>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    hashes: BTreeMap<HashAlgorithm, HashValue>,
+}
+
+impl SnapshotMerkleLeaf {
+    fn new(role: &MetadataPath, description: &MetadataDescription<TargetsMetadata>) -> Self {
+        SnapshotMerkleLeaf {
+            name: role.as_str().into(),
+            version: description.version(),
+            length: description.length(),
+            hashes: description
+                .hashes()
+                .iter()
+                .map(|(alg, value)| (alg.clone(), value.clone()))
+                .collect(),
+        }
+    }
+
+    /// The hash of this leaf. This is pinned to the canonical JSON of [Pouf1] rather than the
+    /// [Pouf] of the repository, see the [module](self) documentation.
+    fn hash(&self) -> Result<HashValue> {
+        let value = Pouf1::serialize(self)?;
+        let bytes = Pouf1::canonicalize(&value)?;
+
+        let mut context = digest::Context::new(&SHA256);
+        context.update(&[LEAF_PREFIX]);
+        context.update(&bytes);
+        Ok(HashValue::new(context.finish().as_ref().to_vec()))
+    }
+
+    fn description(&self) -> Result<MetadataDescription<TargetsMetadata>> {
+        MetadataDescription::new(
+            self.version,
+            self.length,
+            self.hashes
+                .iter()
+                .map(|(alg, value)| (alg.clone(), value.clone()))
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+}
+
+fn hash_node(left: &HashValue, right: &HashValue) -> HashValue {
+    let mut context = digest::Context::new(&SHA256);
+    context.update(&[NODE_PREFIX]);
+    context.update(left.value());
+    context.update(right.value());
+    HashValue::new(context.finish().as_ref().to_vec())
+}
+
+/// Which side of a node its sibling is on, in a [SnapshotMerkleProof].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PathDirection {
+    /// The sibling is the left child, so the node is hashed after it.
+    Left,
+    /// The sibling is the right child, so the node is hashed before it.
+    Right,
+}
+
+/// A Merkle tree over the entries of a snapshot metadata, used by a repository to compute the
+/// Merkle root for its timestamp metadata and the [SnapshotMerkleProof] of every role.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotMerkleTree {
+    leaves: Vec<SnapshotMerkleLeaf>,
+    // `levels[0]` are the leaf hashes, and the last level holds just the root.
+    levels: Vec<Vec<HashValue>>,
+}
+
+impl SnapshotMerkleTree {
+    /// Build the tree over the entries of `snapshot`.
+    pub fn from_snapshot(snapshot: &SnapshotMetadata) -> Result<Self> {
+        Self::new(snapshot.meta())
+    }
+
+    /// Build the tree over the snapshot entries `meta`.
+    pub fn new<'a, I>(meta: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (&'a MetadataPath, &'a MetadataDescription<TargetsMetadata>)>,
+    {
+        let mut leaves = meta
+            .into_iter()
+            .map(|(role, description)| SnapshotMerkleLeaf::new(role, description))
+            .collect::<Vec<_>>();
+        if leaves.is_empty() {
+            return Err(Error::IllegalArgument(
+                "Cannot build a snapshot Merkle tree without any roles".into(),
+            ));
+        }
+        leaves.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut levels = vec![leaves
+            .iter()
+            .map(|leaf| leaf.hash())
+            .collect::<Result<Vec<_>>>()?];
+
+        while levels[levels.len() - 1].len() > 1 {
+            let level = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_node(left, right),
+                    [node] => node.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(level);
+        }
+
+        Ok(SnapshotMerkleTree { leaves, levels })
+    }
+
+    /// The root of the tree, which is signed in the timestamp metadata.
+    pub fn root(&self) -> &HashValue {
+        &self.levels[self.levels.len() - 1][0]
+    }
+
+    /// The inclusion proof of the snapshot entry of `role`, or `None` if the role is not in the
+    /// tree.
+    pub fn proof(&self, role: &MetadataPath) -> Option<SnapshotMerkleProof> {
+        let mut index = self
+            .leaves
+            .binary_search_by(|leaf| leaf.name.as_str().cmp(role.as_str()))
+            .ok()?;
+        let leaf_contents = self.leaves[index].clone();
+
+        let mut merkle_path = vec![];
+        let mut path_directions = vec![];
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if let Some(hash) = level.get(sibling) {
+                merkle_path.push(hash.clone());
+                path_directions.push(if sibling < index {
+                    PathDirection::Left
+                } else {
+                    PathDirection::Right
+                });
+            }
+            index /= 2;
+        }
+
+        Some(SnapshotMerkleProof {
+            leaf_contents,
+            merkle_path,
+            path_directions,
+        })
+    }
+
+    /// The roles in the tree and their inclusion proofs, sorted by role name.
+    pub fn proofs(&self) -> Result<Vec<(MetadataPath, SnapshotMerkleProof)>> {
+        self.leaves
+            .iter()
+            .map(|leaf| {
+                let role = MetadataPath::new(leaf.name.clone())?;
+                let proof = self.proof(&role).expect("every leaf has a proof");
+                Ok((role, proof))
+            })
+            .collect()
+    }
+}
+
+/// A proof that a role's snapshot entry is included in a snapshot Merkle tree.
+///
+/// Proofs are not signed. Instead, [SnapshotMerkleProof::verify] recomputes the Merkle root from
+/// the entry and the sibling hashes along its path to the root, and compares it to the root
+/// signed in the trusted timestamp metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotMerkleProof {
+    leaf_contents: SnapshotMerkleLeaf,
+    merkle_path: Vec<HashValue>,
+    path_directions: Vec<PathDirection>,
+}
+
+impl SnapshotMerkleProof {
+    /// The role whose snapshot entry this proves.
+    pub fn role(&self) -> Result<MetadataPath> {
+        MetadataPath::new(self.leaf_contents.name.clone())
+    }
+
+    /// The hashes of the siblings of the nodes on the path from the leaf to the root.
+    pub fn merkle_path(&self) -> &[HashValue] {
+        &self.merkle_path
+    }
+
+    /// Which side each hash in [SnapshotMerkleProof::merkle_path] is on.
+    pub fn path_directions(&self) -> &[PathDirection] {
+        &self.path_directions
+    }
+
+    /// Verify that this proves the snapshot entry of `role` is included in the tree with the root
+    /// `merkle_root`, and return the entry.
+    pub fn verify(
+        &self,
+        role: &MetadataPath,
+        merkle_root: &HashValue,
+    ) -> Result<MetadataDescription<TargetsMetadata>> {
+        let invalid = |reason: &str| Error::InvalidSnapshotMerkleProof {
+            role: role.clone(),
+            reason: reason.into(),
+        };
+
+        if self.leaf_contents.name != role.as_str() {
+            return Err(invalid("proof is for a different role"));
+        }
+
+        if self.merkle_path.len() != self.path_directions.len() {
+            return Err(invalid("every hash in the path must have a direction"));
+        }
+
+        let mut node = self.leaf_contents.hash()?;
+        for (sibling, direction) in self.merkle_path.iter().zip(&self.path_directions) {
+            node = match direction {
+                PathDirection::Left => hash_node(sibling, &node),
+                PathDirection::Right => hash_node(&node, sibling),
+            };
+        }
+
+        if &node != merkle_root {
+            return Err(invalid("proof does not match the Merkle root"));
+        }
+
+        self.leaf_contents.description()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    fn meta(count: u32) -> HashMap<MetadataPath, MetadataDescription<TargetsMetadata>> {
+        (1..=count)
+            .map(|i| {
+                let role = MetadataPath::new(format!("role-{}", i)).unwrap();
+                let description = MetadataDescription::from_slice(
+                    format!("contents {}", i).as_bytes(),
+                    i,
+                    &[HashAlgorithm::Sha256],
+                )
+                .unwrap();
+                (role, description)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn snapshot_merkle_proofs_verify_against_the_root() {
+        for count in 1..=9 {
+            let meta = meta(count);
+            let tree = SnapshotMerkleTree::new(&meta).unwrap();
+            let proofs = tree.proofs().unwrap();
+            assert_eq!(proofs.len(), count as usize);
+
+            for (role, proof) in proofs {
+                assert_eq!(proof.role().unwrap(), role);
+                assert_eq!(&proof.verify(&role, tree.root()).unwrap(), &meta[&role]);
+
+                // Proofs survive a round trip through their serialized form.
+                let bytes = serde_json::to_vec(&proof).unwrap();
+                let decoded: SnapshotMerkleProof = Pouf1::from_slice(&bytes).unwrap();
+                assert_eq!(decoded, proof);
+            }
+        }
+    }
+
+    #[test]
+    fn snapshot_merkle_proofs_reject_tampering() {
+        let five = meta(5);
+        let tree = SnapshotMerkleTree::new(&five).unwrap();
+        let role = MetadataPath::new("role-3").unwrap();
+        let proof = tree.proof(&role).unwrap();
+        assert_eq!(tree.proof(&MetadataPath::new("role-6").unwrap()), None);
+
+        // A proof for one role can't be used for another.
+        assert_matches!(
+            proof.verify(&MetadataPath::new("role-4").unwrap(), tree.root()),
+            Err(Error::InvalidSnapshotMerkleProof { .. })
+        );
+
+        // The entry can't be changed.
+        let mut tampered = proof.clone();
+        tampered.leaf_contents.version += 1;
+        assert_matches!(
+            tampered.verify(&role, tree.root()),
+            Err(Error::InvalidSnapshotMerkleProof { .. })
+        );
+
+        // Nor can the path.
+        let mut tampered = proof.clone();
+        tampered.path_directions[0] = match tampered.path_directions[0] {
+            PathDirection::Left => PathDirection::Right,
+            PathDirection::Right => PathDirection::Left,
+        };
+        assert_matches!(
+            tampered.verify(&role, tree.root()),
+            Err(Error::InvalidSnapshotMerkleProof { .. })
+        );
+
+        // And the proof only verifies against the root of its own tree.
+        let other = SnapshotMerkleTree::new(&meta(6)).unwrap();
+        assert_matches!(
+            proof.verify(&role, other.root()),
+            Err(Error::InvalidSnapshotMerkleProof { .. })
+        );
+    }
+}
@@ -7,17 +7,31 @@ use serde::de::{Deserialize, DeserializeOwned, Deserializer, Error as Deserializ
 use serde::ser::{Error as SerializeError, Serialize, Serializer};
 use serde_derive::{Deserialize, Serialize};
 use std::borrow::{Borrow, Cow};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::{self, Debug, Display};
 use std::marker::PhantomData;
 use std::str;
 
-use crate::crypto::{self, HashAlgorithm, HashValue, KeyId, PrivateKey, PublicKey, Signature};
+use crate::crypto::{
+    self, HashAlgorithm, HashValue, KeyId, PrivateKey, PublicKey, Signature, SignatureValue,
+};
 use crate::error::Error;
 use crate::pouf::pouf1::shims;
 use crate::pouf::Pouf;
+use crate::verify::{self, SignatureReport};
 use crate::Result;
 
+mod diff;
+pub use self::diff::{
+    diff, diff_root, diff_snapshot, Change, RoleDiff, RootDiff, SnapshotDiff, TargetsDiff,
+};
+
+mod expiration;
+pub use self::expiration::{ExpirationPolicy, FirstOfMonth};
+
+/// The custom metadata field that marks a target as yanked. See [TargetDescription::is_yanked].
+pub(crate) const YANKED_CUSTOM_KEY: &str = "yanked";
+
 #[rustfmt::skip]
 static PATH_ILLEGAL_COMPONENTS: &[&str] = &[
     ".", // current dir
@@ -162,6 +176,11 @@ pub enum Role {
     /// The timestamp role.
     #[serde(rename = "timestamp")]
     Timestamp,
+
+    /// A custom top-level role defined in the root metadata, such as the `mirrors` role of TAP 5.
+    /// The metadata of a custom role is labeled with the name of the role instead.
+    #[serde(skip)]
+    Custom,
 }
 
 impl Role {
@@ -177,6 +196,9 @@ impl Role {
     ///
     /// assert!(!Role::Root.fuzzy_matches_path(&MetadataPath::snapshot()));
     /// assert!(!Role::Root.fuzzy_matches_path(&MetadataPath::new("wat").unwrap()));
+    ///
+    /// assert!(Role::Custom.fuzzy_matches_path(&MetadataPath::new("mirrors").unwrap()));
+    /// assert!(!Role::Custom.fuzzy_matches_path(&MetadataPath::timestamp()));
     /// ```
     pub fn fuzzy_matches_path(&self, path: &MetadataPath) -> bool {
         match *self {
@@ -185,6 +207,9 @@ impl Role {
             Role::Timestamp if &path.0 == "timestamp" => true,
             Role::Targets if &path.0 == "targets" => true,
             Role::Targets if !&["root", "snapshot", "targets"].contains(&path.0.as_ref()) => true,
+            Role::Custom => {
+                !["root", "snapshot", "targets", "timestamp"].contains(&path.0.as_ref())
+            }
             _ => false,
         }
     }
@@ -196,6 +221,7 @@ impl Role {
             Role::Snapshot => "snapshot",
             Role::Targets => "targets",
             Role::Timestamp => "timestamp",
+            Role::Custom => "custom",
         }
     }
 }
@@ -234,6 +260,117 @@ impl MetadataVersion {
     }
 }
 
+/// The version of the TUF specification that metadata claims to follow.
+///
+/// The specification uses semantic versioning, so metadata with the same major version as the one
+/// this crate implements can be parsed, and any fields it adds are preserved as unrecognized
+/// fields. Old roots may use the two component version "1.0", which is accepted and preserved.
+///
+/// ```
+/// use tuf::metadata::SpecVersion;
+///
+/// let version: SpecVersion = "1.1.0".parse().unwrap();
+/// assert_eq!(version.major(), 1);
+/// assert_eq!(version.minor(), 1);
+/// assert_eq!(version.patch(), 0);
+/// assert_eq!(version.to_string(), "1.1.0");
+///
+/// let version: SpecVersion = "1.0".parse().unwrap();
+/// assert_eq!(version.patch(), 0);
+/// assert_eq!(version.to_string(), "1.0");
+///
+/// assert!("1".parse::<SpecVersion>().is_err());
+/// assert!("1.0.0-rc.1".parse::<SpecVersion>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpecVersion {
+    major: u32,
+    minor: u32,
+    patch: Option<u32>,
+}
+
+impl SpecVersion {
+    /// The major version of the specification implemented by this crate.
+    pub const SUPPORTED_MAJOR_VERSION: u32 = 1;
+
+    /// Create a new `SpecVersion`.
+    pub fn new(major: u32, minor: u32, patch: u32) -> Self {
+        SpecVersion {
+            major,
+            minor,
+            patch: Some(patch),
+        }
+    }
+
+    /// The major version.
+    pub fn major(&self) -> u32 {
+        self.major
+    }
+
+    /// The minor version.
+    pub fn minor(&self) -> u32 {
+        self.minor
+    }
+
+    /// The patch version, which is 0 for the two component versions used by old roots.
+    pub fn patch(&self) -> u32 {
+        self.patch.unwrap_or(0)
+    }
+}
+
+/// The version returned by [SpecVersion::default].
+const DEFAULT_SPEC_VERSION: SpecVersion = SpecVersion {
+    major: 1,
+    minor: 0,
+    patch: None,
+};
+
+impl Default for SpecVersion {
+    /// The version written into new metadata, which is the literal "1.0" that older clients of
+    /// this crate require.
+    fn default() -> Self {
+        DEFAULT_SPEC_VERSION
+    }
+}
+
+impl str::FromStr for SpecVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parse = |component: &str| {
+            if component.is_empty() || !component.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(Error::Encoding(format!("Invalid spec version {:?}", s)));
+            }
+            component
+                .parse::<u32>()
+                .map_err(|_| Error::Encoding(format!("Invalid spec version {:?}", s)))
+        };
+
+        match s.split('.').collect::<Vec<_>>()[..] {
+            [major, minor] => Ok(SpecVersion {
+                major: parse(major)?,
+                minor: parse(minor)?,
+                patch: None,
+            }),
+            [major, minor, patch] => Ok(SpecVersion {
+                major: parse(major)?,
+                minor: parse(minor)?,
+                patch: Some(parse(patch)?),
+            }),
+            _ => Err(Error::Encoding(format!("Invalid spec version {:?}", s))),
+        }
+    }
+}
+
+impl Display for SpecVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.patch {
+            Some(patch) => write!(f, "{}.{}.{}", self.major, self.minor, patch),
+            None => write!(f, "{}.{}", self.major, self.minor),
+        }
+    }
+}
+
 /// Top level trait used for role metadata.
 pub trait Metadata: Debug + PartialEq + Serialize + DeserializeOwned {
     /// The role associated with the metadata.
@@ -244,6 +381,12 @@ pub trait Metadata: Debug + PartialEq + Serialize + DeserializeOwned {
 
     /// An immutable reference to the metadata's expiration `DateTime`.
     fn expires(&self) -> &DateTime<Utc>;
+
+    /// The version of the specification the metadata claims to follow. Metadata that doesn't
+    /// record a version is assumed to follow the [SpecVersion::default] version.
+    fn spec_version(&self) -> &SpecVersion {
+        &DEFAULT_SPEC_VERSION
+    }
 }
 
 /// Unverified raw metadata with attached signatures and type information identifying the
@@ -313,6 +456,35 @@ impl<D> RawSignedMetadataSet<D> {
     }
 }
 
+impl<D> RawSignedMetadataSet<D>
+where
+    D: Pouf,
+{
+    /// Returns a SHA-256 digest over every metadata in this set, which changes if any metadata is
+    /// added, removed, or differs by a single byte. Two sets built from the same inputs with
+    /// [RepoBuilder::deterministic](crate::repo_builder::RepoBuilder::deterministic) have the
+    /// same digest.
+    pub fn digest(&self) -> Result<HashValue> {
+        let mut context = HashAlgorithm::Sha256.digest_context()?;
+        let roles = [
+            ("root", self.root.as_ref().map(|m| m.as_bytes())),
+            ("targets", self.targets.as_ref().map(|m| m.as_bytes())),
+            ("snapshot", self.snapshot.as_ref().map(|m| m.as_bytes())),
+            ("timestamp", self.timestamp.as_ref().map(|m| m.as_bytes())),
+        ];
+        for (role, bytes) in roles {
+            if let Some(bytes) = bytes {
+                // Prefix each metadata with its role and length, so that bytes can't move between
+                // roles without changing the digest.
+                context.update(role.as_bytes());
+                context.update(&(bytes.len() as u64).to_be_bytes());
+                context.update(bytes);
+            }
+        }
+        Ok(HashValue::new(context.finish().as_ref().to_vec()))
+    }
+}
+
 /// Builder for [RawSignedMetadataSet].
 #[derive(Default)]
 pub struct RawSignedMetadataSetBuilder<D>
@@ -368,6 +540,39 @@ where
     }
 }
 
+/// Returns the canonical bytes of `metadata` in the [Pouf] `D`. These are the exact bytes that
+/// must be signed for a signature over the metadata to be valid, so they can be handed to signers
+/// outside of this crate, such as air-gapped signers or key management services. The resulting
+/// signatures can be attached with [SignedMetadataBuilder::add_signature].
+///
+/// ```
+/// # use tuf::crypto::{Ed25519PrivateKey, PrivateKey};
+/// # use tuf::pouf::Pouf1;
+/// # use tuf::metadata::{canonical_bytes, SignedMetadataBuilder, SnapshotMetadataBuilder};
+/// #
+/// # let key: &[u8] = include_bytes!("../tests/ed25519/ed25519-1.pk8.der");
+/// # let key = Ed25519PrivateKey::from_pkcs8(&key).unwrap();
+/// let snapshot = SnapshotMetadataBuilder::new().build().unwrap();
+/// let payload = canonical_bytes::<Pouf1, _>(&snapshot).unwrap();
+///
+/// // Sign the payload elsewhere.
+/// let sig = key.sign(&payload).unwrap();
+///
+/// let signed = SignedMetadataBuilder::<Pouf1, _>::from_metadata(&snapshot)
+///     .unwrap()
+///     .add_signature(key.public(), sig.value().as_bytes())
+///     .unwrap()
+///     .build();
+/// assert_eq!(signed.signatures().len(), 1);
+/// ```
+pub fn canonical_bytes<D, M>(metadata: &M) -> Result<Vec<u8>>
+where
+    D: Pouf,
+    M: Metadata,
+{
+    D::canonicalize(&D::serialize(metadata)?)
+}
+
 /// Helper to construct `SignedMetadata`.
 #[derive(Debug, Clone)]
 pub struct SignedMetadataBuilder<D, M>
@@ -404,6 +609,13 @@ where
         })
     }
 
+    /// The canonical bytes of the metadata, which are signed by [SignedMetadataBuilder::sign] and
+    /// must be signed by signatures attached with [SignedMetadataBuilder::add_signature]. See
+    /// [canonical_bytes].
+    pub fn canonical_bytes(&self) -> &[u8] {
+        &self.metadata_bytes
+    }
+
     /// Sign the metadata using the given `private_key`, replacing any existing signatures with the
     /// same `KeyId`.
     ///
@@ -417,6 +629,23 @@ where
         Ok(self)
     }
 
+    /// Attach a signature that was produced outside of this crate, such as by an air-gapped HSM
+    /// or another tool, replacing any existing signature with the same `KeyId`.
+    ///
+    /// `sig_bytes` must be the signature of `public_key` over the canonical bytes of the
+    /// metadata. The signature is recorded under the `KeyId` of `public_key`, and is verified
+    /// before it is attached, so an [Error::BadSignature] is returned rather than producing
+    /// metadata that clients would reject.
+    pub fn add_signature(mut self, public_key: &PublicKey, sig_bytes: &[u8]) -> Result<Self> {
+        let sig = Signature::new(
+            public_key.key_id().clone(),
+            SignatureValue::new(sig_bytes.to_vec()),
+        );
+        public_key.verify(&M::ROLE.into(), &self.metadata_bytes, &sig)?;
+        let _ = self.signatures.insert(sig.key_id().clone(), sig);
+        Ok(self)
+    }
+
     /// Construct a new `SignedMetadata` using the included signatures, sorting the signatures by
     /// `KeyId`.
     pub fn build(self) -> SignedMetadata<D, M> {
@@ -492,6 +721,30 @@ where
         Ok(RawSignedMetadata::new(bytes))
     }
 
+    /// Serialize this metadata to bytes that are easier for people to read, such as indented
+    /// JSON, with [Pouf::pretty_print]. The signatures still cover the canonical form, which
+    /// clients recompute when they parse the metadata, so the returned bytes can be published as
+    /// they are. The same caveats as for [SignedMetadata::to_raw] apply.
+    ///
+    /// ```
+    /// # use tuf::crypto::{Ed25519PrivateKey, PrivateKey};
+    /// # use tuf::pouf::Pouf1;
+    /// # use tuf::metadata::{SignedMetadata, SnapshotMetadataBuilder};
+    /// #
+    /// # let key: &[u8] = include_bytes!("../tests/ed25519/ed25519-1.pk8.der");
+    /// # let key = Ed25519PrivateKey::from_pkcs8(&key).unwrap();
+    /// let snapshot = SnapshotMetadataBuilder::new().build().unwrap();
+    /// let signed = SignedMetadata::<Pouf1, _>::new(&snapshot, &key).unwrap();
+    ///
+    /// let raw = signed.to_raw_pretty().unwrap();
+    /// assert!(raw.as_bytes().starts_with(b"{\n  \"signatures\": ["));
+    /// assert_eq!(raw.parse_untrusted().unwrap(), signed);
+    /// ```
+    pub fn to_raw_pretty(&self) -> Result<RawSignedMetadata<D, M>> {
+        let bytes = D::pretty_print(&D::serialize(self)?)?;
+        Ok(RawSignedMetadata::new(bytes))
+    }
+
     /// Append a signature to this signed metadata. Will overwrite signature by keys with the same
     /// ID.
     ///
@@ -533,9 +786,32 @@ where
         Ok(())
     }
 
-    /// Merge the singatures from `other` into `self` if and only if
-    /// `self.as_ref() == other.as_ref()`. If `self` and `other` contain signatures from the same
-    /// key ID, then the signatures from `self` will replace the signatures from `other`.
+    /// Merge the signatures from `other` into `self` if and only if both carry the same signed
+    /// payload. This supports multi-party signing, where every custodian signs their own copy of
+    /// the metadata and a coordinator merges the copies.
+    ///
+    /// Every key ID is only kept once. If `self` and `other` contain signatures from the same key
+    /// ID, then the signatures from `self` will replace the signatures from `other`. The
+    /// signatures are not verified.
+    ///
+    /// ```
+    /// # use tuf::crypto::{Ed25519PrivateKey, PrivateKey};
+    /// # use tuf::pouf::Pouf1;
+    /// # use tuf::metadata::{SignedMetadata, SnapshotMetadataBuilder};
+    /// #
+    /// # let key_1: &[u8] = include_bytes!("../tests/ed25519/ed25519-1.pk8.der");
+    /// # let key_1 = Ed25519PrivateKey::from_pkcs8(&key_1).unwrap();
+    /// # let key_2: &[u8] = include_bytes!("../tests/ed25519/ed25519-2.pk8.der");
+    /// # let key_2 = Ed25519PrivateKey::from_pkcs8(&key_2).unwrap();
+    /// let snapshot = SnapshotMetadataBuilder::new().build().unwrap();
+    ///
+    /// // Each custodian signs their own copy.
+    /// let mut signed = SignedMetadata::<Pouf1, _>::new(&snapshot, &key_1).unwrap();
+    /// let other = SignedMetadata::<Pouf1, _>::new(&snapshot, &key_2).unwrap();
+    ///
+    /// signed.merge_signatures(&other).unwrap();
+    /// assert_eq!(signed.signatures().len(), 2);
+    /// ```
     pub fn merge_signatures(&mut self, other: &Self) -> Result<()> {
         if self.metadata != other.metadata {
             return Err(Error::IllegalArgument(
@@ -543,20 +819,15 @@ where
             ));
         }
 
-        let key_ids = self
-            .signatures
-            .iter()
-            .map(|s| s.key_id().clone())
-            .collect::<HashSet<KeyId>>();
-
-        self.signatures.extend(
-            other
-                .signatures
-                .iter()
-                .filter(|s| !key_ids.contains(s.key_id()))
-                .cloned(),
-        );
-        self.signatures.sort();
+        let mut key_ids = HashSet::new();
+        let mut signatures = Vec::with_capacity(self.signatures.len() + other.signatures.len());
+        for sig in self.signatures.iter().chain(other.signatures.iter()) {
+            if key_ids.insert(sig.key_id().clone()) {
+                signatures.push(sig.clone());
+            }
+        }
+        signatures.sort();
+        self.signatures = signatures;
 
         Ok(())
     }
@@ -566,6 +837,57 @@ where
         &self.signatures
     }
 
+    /// Check the signatures against the keys authorized by `role_definition`, looking the keys
+    /// up in `keys`, such as the keys of a [RootMetadata]. The report tells how many of the
+    /// threshold signatures are present and valid, and which authorized keys have not signed yet,
+    /// so it can be used to track the progress of a signing ceremony. See
+    /// [verify::check_signatures].
+    ///
+    /// ```
+    /// # use tuf::crypto::{Ed25519PrivateKey, PrivateKey};
+    /// # use tuf::pouf::Pouf1;
+    /// # use tuf::metadata::{RootMetadataBuilder, SignedMetadata};
+    /// #
+    /// # let key_1: &[u8] = include_bytes!("../tests/ed25519/ed25519-1.pk8.der");
+    /// # let key_1 = Ed25519PrivateKey::from_pkcs8(&key_1).unwrap();
+    /// # let key_2: &[u8] = include_bytes!("../tests/ed25519/ed25519-2.pk8.der");
+    /// # let key_2 = Ed25519PrivateKey::from_pkcs8(&key_2).unwrap();
+    /// let root = RootMetadataBuilder::new()
+    ///     .root_key(key_1.public().clone())
+    ///     .root_key(key_2.public().clone())
+    ///     .root_threshold(2)
+    ///     .snapshot_key(key_1.public().clone())
+    ///     .targets_key(key_1.public().clone())
+    ///     .timestamp_key(key_1.public().clone())
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let signed = SignedMetadata::<Pouf1, _>::new(&root, &key_1).unwrap();
+    /// let report = signed.signature_report(root.root(), root.keys()).unwrap();
+    ///
+    /// // 1 of 2 signatures collected.
+    /// assert_eq!(report.valid_key_ids().len(), 1);
+    /// assert_eq!(report.signatures_needed(), 1);
+    /// assert!(report.missing_key_ids().contains(key_2.public().key_id()));
+    /// ```
+    pub fn signature_report(
+        &self,
+        role_definition: &RoleDefinition<M>,
+        keys: &HashMap<KeyId, PublicKey>,
+    ) -> Result<SignatureReport> {
+        let authorized_keys = role_definition
+            .key_ids()
+            .iter()
+            .filter_map(|key_id| keys.get(key_id));
+
+        verify::check_signatures(
+            &M::ROLE.into(),
+            &self.to_raw()?,
+            role_definition.threshold(),
+            authorized_keys,
+        )
+    }
+
     /// Parse the version number of this metadata without verifying signatures.
     ///
     /// This operation is generally unsafe to do with metadata obtained from an untrusted source,
@@ -603,6 +925,8 @@ pub struct RootMetadataBuilder {
     targets_key_ids: HashSet<KeyId>,
     timestamp_threshold: u32,
     timestamp_key_ids: HashSet<KeyId>,
+    custom_roles: HashMap<MetadataPath, (u32, HashSet<KeyId>)>,
+    forbid_key_reuse: bool,
 }
 
 impl RootMetadataBuilder {
@@ -626,6 +950,8 @@ impl RootMetadataBuilder {
             targets_key_ids: HashSet::new(),
             timestamp_threshold: 1,
             timestamp_key_ids: HashSet::new(),
+            custom_roles: HashMap::new(),
+            forbid_key_reuse: false,
         }
     }
 
@@ -641,6 +967,17 @@ impl RootMetadataBuilder {
         self
     }
 
+    /// Set this metadata to expire `duration` from the current time.
+    pub fn expires_in(self, duration: Duration) -> Self {
+        self.expires_with(&duration)
+    }
+
+    /// Set the time this metadata expires with an [ExpirationPolicy], starting from the current
+    /// time.
+    pub fn expires_with<P: ExpirationPolicy + ?Sized>(self, policy: &P) -> Self {
+        self.expires(policy.expires(Utc::now()))
+    }
+
     /// Set this metadata to have a consistent snapshot.
     pub fn consistent_snapshot(mut self, consistent_snapshot: bool) -> Self {
         self.consistent_snapshot = consistent_snapshot;
@@ -703,9 +1040,72 @@ impl RootMetadataBuilder {
         self
     }
 
+    /// Set the threshold of the custom top-level `role`, which defaults to 1.
+    pub fn custom_role_threshold(mut self, role: MetadataPath, threshold: u32) -> Self {
+        self.custom_roles
+            .entry(role)
+            .or_insert_with(|| (1, HashSet::new()))
+            .0 = threshold;
+        self
+    }
+
+    /// Add a public key of the custom top-level `role`. See [CustomMetadata] for details.
+    pub fn custom_role_key(mut self, role: MetadataPath, public_key: PublicKey) -> Self {
+        let key_id = public_key.key_id().clone();
+        self.keys.insert(key_id.clone(), public_key);
+        self.custom_roles
+            .entry(role)
+            .or_insert_with(|| (1, HashSet::new()))
+            .1
+            .insert(key_id);
+        self
+    }
+
+    /// Remove every public key of the top-level `role`, but keep its threshold.
+    pub(crate) fn clear_role_keys(mut self, role: &Role) -> Self {
+        match role {
+            Role::Root => self.root_key_ids.clear(),
+            Role::Snapshot => self.snapshot_key_ids.clear(),
+            Role::Targets => self.targets_key_ids.clear(),
+            Role::Timestamp => self.timestamp_key_ids.clear(),
+            Role::Custom => {}
+        }
+
+        self.keys.retain(|key_id, _| {
+            self.root_key_ids.contains(key_id)
+                || self.snapshot_key_ids.contains(key_id)
+                || self.targets_key_ids.contains(key_id)
+                || self.timestamp_key_ids.contains(key_id)
+                || self
+                    .custom_roles
+                    .values()
+                    .any(|(_, key_ids)| key_ids.contains(key_id))
+        });
+        self
+    }
+
+    /// Reject building root metadata that trusts a key for more than one top-level role with
+    /// [Error::KeyReuse]. See [RootMetadata::reused_key_ids].
+    pub fn forbid_key_reuse(mut self, forbid: bool) -> Self {
+        self.forbid_key_reuse = forbid;
+        self
+    }
+
     /// Construct a new `RootMetadata`.
     pub fn build(self) -> Result<RootMetadata> {
-        RootMetadata::new(
+        let mut custom_roles = HashMap::new();
+        for (role, (threshold, key_ids)) in self.custom_roles {
+            if !Role::Custom.fuzzy_matches_path(&role) {
+                return Err(Error::IllegalArgument(format!(
+                    "{} is not a custom role",
+                    role
+                )));
+            }
+
+            let _ = custom_roles.insert(role, RoleDefinition::new(threshold, key_ids)?);
+        }
+
+        let root = RootMetadata::new(
             self.version,
             self.expires,
             self.consistent_snapshot,
@@ -714,7 +1114,16 @@ impl RootMetadataBuilder {
             RoleDefinition::new(self.snapshot_threshold, self.snapshot_key_ids)?,
             RoleDefinition::new(self.targets_threshold, self.targets_key_ids)?,
             RoleDefinition::new(self.timestamp_threshold, self.timestamp_key_ids)?,
-        )
+        )?
+        .with_custom_roles(custom_roles);
+
+        if self.forbid_key_reuse {
+            if let Some((key_id, roles)) = root.reused_key_ids().into_iter().next() {
+                return Err(Error::KeyReuse { key_id, roles });
+            }
+        }
+
+        Ok(root)
     }
 
     /// Construct a new `SignedMetadata<D, RootMetadata>`.
@@ -747,6 +1156,12 @@ impl From<RootMetadata> for RootMetadataBuilder {
             targets_key_ids: metadata.targets.key_ids,
             timestamp_threshold: metadata.timestamp.threshold,
             timestamp_key_ids: metadata.timestamp.key_ids,
+            custom_roles: metadata
+                .custom_roles
+                .into_iter()
+                .map(|(role, definition)| (role, (definition.threshold, definition.key_ids)))
+                .collect(),
+            forbid_key_reuse: false,
         }
     }
 }
@@ -754,6 +1169,7 @@ impl From<RootMetadata> for RootMetadataBuilder {
 /// Metadata for the root role.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RootMetadata {
+    spec_version: SpecVersion,
     version: u32,
     expires: DateTime<Utc>,
     consistent_snapshot: bool,
@@ -762,6 +1178,8 @@ pub struct RootMetadata {
     snapshot: RoleDefinition<SnapshotMetadata>,
     targets: RoleDefinition<TargetsMetadata>,
     timestamp: RoleDefinition<TimestampMetadata>,
+    custom_roles: HashMap<MetadataPath, RoleDefinition<CustomMetadata>>,
+    unrecognized_fields: HashMap<String, serde_json::Value>,
 }
 
 impl RootMetadata {
@@ -783,6 +1201,7 @@ impl RootMetadata {
         }
 
         Ok(RootMetadata {
+            spec_version: SpecVersion::default(),
             version,
             expires,
             consistent_snapshot,
@@ -791,9 +1210,41 @@ impl RootMetadata {
             snapshot,
             targets,
             timestamp,
+            custom_roles: HashMap::new(),
+            unrecognized_fields: HashMap::new(),
         })
     }
 
+    /// Set the version of the specification the metadata claims to follow.
+    pub fn with_spec_version(mut self, spec_version: SpecVersion) -> Self {
+        self.spec_version = spec_version;
+        self
+    }
+
+    /// Set the definitions of the custom top-level roles. See [CustomMetadata] for details.
+    pub fn with_custom_roles(
+        mut self,
+        custom_roles: HashMap<MetadataPath, RoleDefinition<CustomMetadata>>,
+    ) -> Self {
+        self.custom_roles = custom_roles;
+        self
+    }
+
+    /// Set the fields of the metadata that this crate does not recognize, which are preserved
+    /// when the metadata is serialized again.
+    pub fn with_unrecognized_fields(
+        mut self,
+        unrecognized_fields: HashMap<String, serde_json::Value>,
+    ) -> Self {
+        self.unrecognized_fields = unrecognized_fields;
+        self
+    }
+
+    /// An immutable reference to the fields of the metadata that this crate does not recognize.
+    pub fn unrecognized_fields(&self) -> &HashMap<String, serde_json::Value> {
+        &self.unrecognized_fields
+    }
+
     /// Whether or not this repository is currently implementing that TUF consistent snapshot
     /// feature.
     pub fn consistent_snapshot(&self) -> bool {
@@ -856,6 +1307,68 @@ impl RootMetadata {
     pub fn timestamp(&self) -> &RoleDefinition<TimestampMetadata> {
         &self.timestamp
     }
+
+    /// An immutable reference to the definitions of the custom top-level roles.
+    pub fn custom_roles(&self) -> &HashMap<MetadataPath, RoleDefinition<CustomMetadata>> {
+        &self.custom_roles
+    }
+
+    /// An immutable reference to the definition of the custom top-level `role`, if it is
+    /// defined.
+    pub fn custom_role(&self, role: &MetadataPath) -> Option<&RoleDefinition<CustomMetadata>> {
+        self.custom_roles.get(role)
+    }
+
+    /// An iterator over all the trusted public keys of the custom top-level `role`, which is empty
+    /// if the role is not defined.
+    pub fn custom_role_keys<'a>(
+        &'a self,
+        role: &MetadataPath,
+    ) -> impl Iterator<Item = &'a PublicKey> + 'a {
+        self.custom_roles
+            .get(role)
+            .into_iter()
+            .flat_map(move |definition| {
+                definition
+                    .key_ids()
+                    .iter()
+                    .filter_map(move |key_id| self.keys.get(key_id))
+            })
+    }
+
+    /// The key IDs that are trusted for more than one top-level role, including custom roles,
+    /// with the roles that trust them. TUF recommends that each top-level role uses separate keys,
+    /// so that compromising the keys of one role does not compromise the others.
+    pub fn reused_key_ids(&self) -> BTreeMap<KeyId, Vec<MetadataPath>> {
+        let mut custom_roles = self.custom_roles.iter().collect::<Vec<_>>();
+        custom_roles.sort_by(|a, b| a.0.cmp(b.0));
+
+        let roles = [
+            (MetadataPath::root(), self.root.key_ids()),
+            (MetadataPath::timestamp(), self.timestamp.key_ids()),
+            (MetadataPath::snapshot(), self.snapshot.key_ids()),
+            (MetadataPath::targets(), self.targets.key_ids()),
+        ]
+        .into_iter()
+        .chain(
+            custom_roles
+                .into_iter()
+                .map(|(role, definition)| (role.clone(), definition.key_ids())),
+        );
+
+        let mut trusted_by = BTreeMap::<KeyId, Vec<MetadataPath>>::new();
+        for (role, key_ids) in roles {
+            for key_id in key_ids {
+                trusted_by
+                    .entry(key_id.clone())
+                    .or_default()
+                    .push(role.clone());
+            }
+        }
+
+        trusted_by.retain(|_, roles| roles.len() > 1);
+        trusted_by
+    }
 }
 
 impl Metadata for RootMetadata {
@@ -868,6 +1381,10 @@ impl Metadata for RootMetadata {
     fn expires(&self) -> &DateTime<Utc> {
         &self.expires
     }
+
+    fn spec_version(&self) -> &SpecVersion {
+        &self.spec_version
+    }
 }
 
 impl Serialize for RootMetadata {
@@ -1039,6 +1556,48 @@ impl MetadataPath {
         buf[len - 1] = format!("{}{}.{}", version.prefix(), buf[len - 1], D::extension());
         buf
     }
+
+    /// The string value of the path.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The path with `%` and `/` percent-encoded, so that the name of a namespaced delegated role
+    /// can be used as a single file name. See
+    /// [RoleNameMapping](crate::repository::RoleNameMapping).
+    ///
+    /// ```
+    /// # use tuf::metadata::MetadataPath;
+    /// let path = MetadataPath::new("projects/foo/bin-07").unwrap();
+    /// assert_eq!(path.escaped(), "projects%2Ffoo%2Fbin-07");
+    /// assert_eq!(MetadataPath::from_escaped(&path.escaped()).unwrap(), path);
+    /// ```
+    pub fn escaped(&self) -> String {
+        self.0.replace('%', "%25").replace('/', "%2F")
+    }
+
+    /// Parse a path that was escaped with [MetadataPath::escaped].
+    pub fn from_escaped(escaped: &str) -> Result<Self> {
+        let mut path = String::with_capacity(escaped.len());
+        let mut rest = escaped;
+        while let Some(idx) = rest.find('%') {
+            path.push_str(&rest[..idx]);
+            match rest.get(idx..idx + 3) {
+                Some("%25") => path.push('%'),
+                Some("%2F") | Some("%2f") => path.push('/'),
+                _ => {
+                    return Err(Error::IllegalArgument(format!(
+                        "Invalid escape sequence in metadata path {:?}",
+                        escaped
+                    )))
+                }
+            }
+            rest = &rest[idx + 3..];
+        }
+        path.push_str(rest);
+
+        MetadataPath::new(path)
+    }
 }
 
 impl From<Role> for MetadataPath {
@@ -1048,6 +1607,7 @@ impl From<Role> for MetadataPath {
             Role::Timestamp => MetadataPath::timestamp(),
             Role::Snapshot => MetadataPath::snapshot(),
             Role::Targets => MetadataPath::targets(),
+            Role::Custom => MetadataPath(Role::Custom.name().into()),
         }
     }
 }
@@ -1070,6 +1630,7 @@ pub struct TimestampMetadataBuilder {
     version: u32,
     expires: DateTime<Utc>,
     snapshot: MetadataDescription<SnapshotMetadata>,
+    merkle_root: Option<HashValue>,
 }
 
 impl TimestampMetadataBuilder {
@@ -1104,6 +1665,7 @@ impl TimestampMetadataBuilder {
             version: 1,
             expires: Utc::now() + Duration::days(1),
             snapshot: description,
+            merkle_root: None,
         }
     }
 
@@ -1119,9 +1681,31 @@ impl TimestampMetadataBuilder {
         self
     }
 
+    /// Set this metadata to expire `duration` from the current time.
+    pub fn expires_in(self, duration: Duration) -> Self {
+        self.expires_with(&duration)
+    }
+
+    /// Set the time this metadata expires with an [ExpirationPolicy], starting from the current
+    /// time.
+    pub fn expires_with<P: ExpirationPolicy + ?Sized>(self, policy: &P) -> Self {
+        self.expires(policy.expires(Utc::now()))
+    }
+
+    /// Set the root of the snapshot Merkle tree. See the [merkle](crate::merkle) module for
+    /// details.
+    pub fn merkle_root(mut self, merkle_root: HashValue) -> Self {
+        self.merkle_root = Some(merkle_root);
+        self
+    }
+
     /// Construct a new `TimestampMetadata`.
     pub fn build(self) -> Result<TimestampMetadata> {
-        TimestampMetadata::new(self.version, self.expires, self.snapshot)
+        let timestamp = TimestampMetadata::new(self.version, self.expires, self.snapshot)?;
+        Ok(match self.merkle_root {
+            Some(merkle_root) => timestamp.with_merkle_root(merkle_root),
+            None => timestamp,
+        })
     }
 
     /// Construct a new `SignedMetadata<D, TimestampMetadata>`.
@@ -1139,9 +1723,12 @@ impl TimestampMetadataBuilder {
 /// Metadata for the timestamp role.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TimestampMetadata {
+    spec_version: SpecVersion,
     version: u32,
     expires: DateTime<Utc>,
     snapshot: MetadataDescription<SnapshotMetadata>,
+    merkle_root: Option<HashValue>,
+    unrecognized_fields: HashMap<String, serde_json::Value>,
 }
 
 impl TimestampMetadata {
@@ -1158,16 +1745,52 @@ impl TimestampMetadata {
         }
 
         Ok(TimestampMetadata {
+            spec_version: SpecVersion::default(),
             version,
             expires,
             snapshot,
+            merkle_root: None,
+            unrecognized_fields: HashMap::new(),
         })
     }
 
+    /// Set the root of the snapshot Merkle tree that clients use to verify the snapshot entries
+    /// of roles without downloading the snapshot metadata.
+    pub fn with_merkle_root(mut self, merkle_root: HashValue) -> Self {
+        self.merkle_root = Some(merkle_root);
+        self
+    }
+
+    /// Set the version of the specification the metadata claims to follow.
+    pub fn with_spec_version(mut self, spec_version: SpecVersion) -> Self {
+        self.spec_version = spec_version;
+        self
+    }
+
+    /// Set the fields of the metadata that this crate does not recognize, which are preserved
+    /// when the metadata is serialized again.
+    pub fn with_unrecognized_fields(
+        mut self,
+        unrecognized_fields: HashMap<String, serde_json::Value>,
+    ) -> Self {
+        self.unrecognized_fields = unrecognized_fields;
+        self
+    }
+
     /// An immutable reference to the snapshot description.
     pub fn snapshot(&self) -> &MetadataDescription<SnapshotMetadata> {
         &self.snapshot
     }
+
+    /// The root of the snapshot Merkle tree, if the repository publishes one.
+    pub fn merkle_root(&self) -> Option<&HashValue> {
+        self.merkle_root.as_ref()
+    }
+
+    /// An immutable reference to the fields of the metadata that this crate does not recognize.
+    pub fn unrecognized_fields(&self) -> &HashMap<String, serde_json::Value> {
+        &self.unrecognized_fields
+    }
 }
 
 impl Metadata for TimestampMetadata {
@@ -1180,6 +1803,10 @@ impl Metadata for TimestampMetadata {
     fn expires(&self) -> &DateTime<Utc> {
         &self.expires
     }
+
+    fn spec_version(&self) -> &SpecVersion {
+        &self.spec_version
+    }
 }
 
 impl Serialize for TimestampMetadata {
@@ -1202,29 +1829,193 @@ impl<'de> Deserialize<'de> for TimestampMetadata {
     }
 }
 
-/// Description of a piece of metadata, used in verification.
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct MetadataDescription<M: Metadata> {
+/// Helper to construct `CustomMetadata`.
+pub struct CustomMetadataBuilder {
+    role: MetadataPath,
     version: u32,
-    length: Option<usize>,
-    hashes: HashMap<HashAlgorithm, HashValue>,
-    _metadata: PhantomData<M>,
+    expires: DateTime<Utc>,
+    fields: HashMap<String, serde_json::Value>,
 }
 
-impl<M: Metadata> MetadataDescription<M> {
-    /// Create a `MetadataDescription` from a slice. Size and hashes will be calculated.
-    pub fn from_slice(buf: &[u8], version: u32, hash_algs: &[HashAlgorithm]) -> Result<Self> {
-        if version < 1 {
-            return Err(Error::IllegalArgument(
-                "Version must be greater than zero".into(),
-            ));
+impl CustomMetadataBuilder {
+    /// Create a new `CustomMetadataBuilder` for the custom top-level `role`. It defaults to:
+    ///
+    /// * version: 1
+    /// * expires: 365 days from the current time.
+    pub fn new(role: MetadataPath) -> Self {
+        CustomMetadataBuilder {
+            role,
+            version: 1,
+            expires: Utc::now() + Duration::days(365),
+            fields: HashMap::new(),
         }
+    }
 
-        let hashes = if hash_algs.is_empty() {
-            HashMap::new()
-        } else {
-            crypto::calculate_hashes_from_slice(buf, hash_algs)?
-        };
+    /// Set the version number for this metadata.
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Set the time this metadata expires.
+    pub fn expires(mut self, expires: DateTime<Utc>) -> Self {
+        self.expires = expires;
+        self
+    }
+
+    /// Set this metadata to expire `duration` from the current time.
+    pub fn expires_in(self, duration: Duration) -> Self {
+        self.expires_with(&duration)
+    }
+
+    /// Set the time this metadata expires with an [ExpirationPolicy], starting from the current
+    /// time.
+    pub fn expires_with<P: ExpirationPolicy + ?Sized>(self, policy: &P) -> Self {
+        self.expires(policy.expires(Utc::now()))
+    }
+
+    /// Set a role specific field of the metadata.
+    pub fn field<K: Into<String>>(mut self, name: K, value: serde_json::Value) -> Self {
+        let _ = self.fields.insert(name.into(), value);
+        self
+    }
+
+    /// Construct a new `CustomMetadata`.
+    pub fn build(self) -> Result<CustomMetadata> {
+        Ok(CustomMetadata::new(&self.role, self.version, self.expires)?.with_fields(self.fields))
+    }
+
+    /// Construct a new `SignedMetadata<D, CustomMetadata>`.
+    pub fn signed<D>(
+        self,
+        private_key: &dyn PrivateKey,
+    ) -> Result<SignedMetadata<D, CustomMetadata>>
+    where
+        D: Pouf,
+    {
+        SignedMetadata::new(&self.build()?, private_key)
+    }
+}
+
+/// Metadata for a custom top-level role, such as the `mirrors` role of TAP 5.
+///
+/// Custom roles are defined next to the standard roles in the root metadata, which lists their
+/// keys and thresholds, and their metadata is labeled with the name of the role. Apart from the
+/// fields that are common to all metadata, this crate does not interpret their contents, which are
+/// available as [CustomMetadata::fields] to the extension that defines the role.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomMetadata {
+    role: MetadataPath,
+    spec_version: SpecVersion,
+    version: u32,
+    expires: DateTime<Utc>,
+    fields: HashMap<String, serde_json::Value>,
+}
+
+impl CustomMetadata {
+    /// Create new `CustomMetadata` for the custom top-level `role`.
+    pub fn new(role: &MetadataPath, version: u32, expires: DateTime<Utc>) -> Result<Self> {
+        if !Role::Custom.fuzzy_matches_path(role) {
+            return Err(Error::IllegalArgument(format!(
+                "{} is not a custom role",
+                role
+            )));
+        }
+
+        if version < 1 {
+            return Err(Error::MetadataVersionMustBeGreaterThanZero(role.clone()));
+        }
+
+        Ok(CustomMetadata {
+            role: role.clone(),
+            spec_version: SpecVersion::default(),
+            version,
+            expires,
+            fields: HashMap::new(),
+        })
+    }
+
+    /// Set the version of the specification the metadata claims to follow.
+    pub fn with_spec_version(mut self, spec_version: SpecVersion) -> Self {
+        self.spec_version = spec_version;
+        self
+    }
+
+    /// Set the role specific fields of the metadata.
+    pub fn with_fields(mut self, fields: HashMap<String, serde_json::Value>) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// The custom role the metadata is labeled with.
+    pub fn role(&self) -> &MetadataPath {
+        &self.role
+    }
+
+    /// An immutable reference to the role specific fields of the metadata, which is every field
+    /// other than `_type`, `spec_version`, `version`, and `expires`.
+    pub fn fields(&self) -> &HashMap<String, serde_json::Value> {
+        &self.fields
+    }
+}
+
+impl Metadata for CustomMetadata {
+    const ROLE: Role = Role::Custom;
+
+    fn version(&self) -> u32 {
+        self.version
+    }
+
+    fn expires(&self) -> &DateTime<Utc> {
+        &self.expires
+    }
+
+    fn spec_version(&self) -> &SpecVersion {
+        &self.spec_version
+    }
+}
+
+impl Serialize for CustomMetadata {
+    fn serialize<S>(&self, ser: S) -> ::std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        shims::CustomMetadata::from(self).serialize(ser)
+    }
+}
+
+impl<'de> Deserialize<'de> for CustomMetadata {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> ::std::result::Result<Self, D::Error> {
+        let intermediate: shims::CustomMetadata = Deserialize::deserialize(de)?;
+        intermediate
+            .try_into()
+            .map_err(|e| DeserializeError::custom(format!("{:?}", e)))
+    }
+}
+
+/// Description of a piece of metadata, used in verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataDescription<M: Metadata> {
+    version: u32,
+    length: Option<usize>,
+    hashes: HashMap<HashAlgorithm, HashValue>,
+    _metadata: PhantomData<M>,
+}
+
+impl<M: Metadata> MetadataDescription<M> {
+    /// Create a `MetadataDescription` from a slice. Size and hashes will be calculated.
+    pub fn from_slice(buf: &[u8], version: u32, hash_algs: &[HashAlgorithm]) -> Result<Self> {
+        if version < 1 {
+            return Err(Error::IllegalArgument(
+                "Version must be greater than zero".into(),
+            ));
+        }
+
+        let hashes = if hash_algs.is_empty() {
+            HashMap::new()
+        } else {
+            crypto::calculate_hashes_from_slice(buf, hash_algs)?
+        };
 
         Ok(MetadataDescription {
             version,
@@ -1332,6 +2123,17 @@ impl SnapshotMetadataBuilder {
         self
     }
 
+    /// Set this metadata to expire `duration` from the current time.
+    pub fn expires_in(self, duration: Duration) -> Self {
+        self.expires_with(&duration)
+    }
+
+    /// Set the time this metadata expires with an [ExpirationPolicy], starting from the current
+    /// time.
+    pub fn expires_with<P: ExpirationPolicy + ?Sized>(self, policy: &P) -> Self {
+        self.expires(policy.expires(Utc::now()))
+    }
+
     /// Add metadata to this snapshot metadata using the default path.
     pub fn insert_metadata<D, M>(
         self,
@@ -1413,9 +2215,11 @@ impl From<SnapshotMetadata> for SnapshotMetadataBuilder {
 /// Metadata for the snapshot role.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SnapshotMetadata {
+    spec_version: SpecVersion,
     version: u32,
     expires: DateTime<Utc>,
     meta: HashMap<MetadataPath, MetadataDescription<TargetsMetadata>>,
+    unrecognized_fields: HashMap<String, serde_json::Value>,
 }
 
 impl SnapshotMetadata {
@@ -1432,16 +2236,39 @@ impl SnapshotMetadata {
         }
 
         Ok(SnapshotMetadata {
+            spec_version: SpecVersion::default(),
             version,
             expires,
             meta,
+            unrecognized_fields: HashMap::new(),
         })
     }
 
+    /// Set the version of the specification the metadata claims to follow.
+    pub fn with_spec_version(mut self, spec_version: SpecVersion) -> Self {
+        self.spec_version = spec_version;
+        self
+    }
+
+    /// Set the fields of the metadata that this crate does not recognize, which are preserved
+    /// when the metadata is serialized again.
+    pub fn with_unrecognized_fields(
+        mut self,
+        unrecognized_fields: HashMap<String, serde_json::Value>,
+    ) -> Self {
+        self.unrecognized_fields = unrecognized_fields;
+        self
+    }
+
     /// An immutable reference to the metadata paths and descriptions.
     pub fn meta(&self) -> &HashMap<MetadataPath, MetadataDescription<TargetsMetadata>> {
         &self.meta
     }
+
+    /// An immutable reference to the fields of the metadata that this crate does not recognize.
+    pub fn unrecognized_fields(&self) -> &HashMap<String, serde_json::Value> {
+        &self.unrecognized_fields
+    }
 }
 
 impl Metadata for SnapshotMetadata {
@@ -1454,6 +2281,10 @@ impl Metadata for SnapshotMetadata {
     fn expires(&self) -> &DateTime<Utc> {
         &self.expires
     }
+
+    fn spec_version(&self) -> &SpecVersion {
+        &self.spec_version
+    }
 }
 
 impl Serialize for SnapshotMetadata {
@@ -1606,10 +2437,208 @@ impl Borrow<str> for TargetPath {
     }
 }
 
+/// The rules used to match a target path against the `paths` of a [Delegation].
+///
+/// The TUF specification describes delegated paths as shell-style patterns, but implementations
+/// disagree on the details, so the rules are chosen by the client rather than by the metadata.
+/// [PathMatching::Glob] is compatible with repositories produced by python-tuf and go-tuf.
+///
+/// ```
+/// # use tuf::metadata::{PathMatching, TargetPath};
+/// let pattern = TargetPath::new("images/*.img").unwrap();
+/// let target = TargetPath::new("images/foo.img").unwrap();
+/// let nested = TargetPath::new("images/v1/foo.img").unwrap();
+///
+/// let glob = PathMatching::Glob { case_sensitive: true };
+/// assert!(glob.matches(&pattern, &target));
+/// assert!(!glob.matches(&pattern, &nested));
+///
+/// let pattern = TargetPath::new("images/**/*.img").unwrap();
+/// let globstar = PathMatching::GlobStar { case_sensitive: true };
+/// assert!(globstar.matches(&pattern, &target));
+/// assert!(globstar.matches(&pattern, &nested));
+///
+/// let pattern = TargetPath::new("images/").unwrap();
+/// assert!(PathMatching::default().matches(&pattern, &nested));
+/// assert!(!PathMatching::default().matches(&pattern, &TargetPath::new("IMAGES/foo").unwrap()));
+///
+/// let prefix = PathMatching::Prefix { case_sensitive: false };
+/// assert!(prefix.matches(&pattern, &TargetPath::new("IMAGES/foo").unwrap()));
+/// ```
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathMatching {
+    /// A target matches a path if it is equal to the path, or if the path ends with `/` and the
+    /// target is inside of that directory. This is the default.
+    Prefix {
+        /// Whether or not letters must have the same case.
+        case_sensitive: bool,
+    },
+
+    /// A target matches a pattern if they have the same number of `/` separated components, and
+    /// every component of the target matches the corresponding component of the pattern. In a
+    /// component, `*` matches any sequence of characters, and `[...]` matches one character in a
+    /// set or range of characters, or one character not in it with `[!...]` or `[^...]`.
+    Glob {
+        /// Whether or not letters must have the same case.
+        case_sensitive: bool,
+    },
+
+    /// Like [PathMatching::Glob], but a component that is exactly `**` matches any number of
+    /// components, including none.
+    GlobStar {
+        /// Whether or not letters must have the same case.
+        case_sensitive: bool,
+    },
+}
+
+impl Default for PathMatching {
+    fn default() -> Self {
+        PathMatching::Prefix {
+            case_sensitive: true,
+        }
+    }
+}
+
+impl PathMatching {
+    /// Whether or not `target` matches the delegated path `pattern`.
+    pub fn matches(&self, pattern: &TargetPath, target: &TargetPath) -> bool {
+        let (pattern, target) = if self.case_sensitive() {
+            (
+                Cow::Borrowed(pattern.as_str()),
+                Cow::Borrowed(target.as_str()),
+            )
+        } else {
+            (
+                Cow::Owned(pattern.as_str().to_lowercase()),
+                Cow::Owned(target.as_str().to_lowercase()),
+            )
+        };
+
+        if pattern == target {
+            return true;
+        }
+
+        match self {
+            PathMatching::Prefix { .. } => pattern.ends_with('/') && target.starts_with(&*pattern),
+            PathMatching::Glob { .. } | PathMatching::GlobStar { .. } => {
+                let pattern = pattern.split('/').collect::<Vec<_>>();
+                let target = target.split('/').collect::<Vec<_>>();
+                glob_components(
+                    &pattern,
+                    &target,
+                    matches!(self, PathMatching::GlobStar { .. }),
+                )
+            }
+        }
+    }
+
+    /// Whether or not `target` is matched by a path of every group in `parents`, which are the
+    /// paths of every delegation on the way to a role.
+    pub(crate) fn matches_chain(
+        &self,
+        target: &TargetPath,
+        parents: &[HashSet<TargetPath>],
+    ) -> bool {
+        if *self == PathMatching::default() {
+            return target.matches_chain(parents);
+        }
+
+        !parents.is_empty()
+            && parents
+                .iter()
+                .all(|group| group.iter().any(|pattern| self.matches(pattern, target)))
+    }
+
+    fn case_sensitive(&self) -> bool {
+        match *self {
+            PathMatching::Prefix { case_sensitive }
+            | PathMatching::Glob { case_sensitive }
+            | PathMatching::GlobStar { case_sensitive } => case_sensitive,
+        }
+    }
+}
+
+// Match the `/` separated components of a target against those of a pattern.
+fn glob_components(pattern: &[&str], target: &[&str], globstar: bool) -> bool {
+    match pattern.split_first() {
+        None => target.is_empty(),
+        Some((&"**", rest)) if globstar => {
+            (0..=target.len()).any(|i| glob_components(rest, &target[i..], globstar))
+        }
+        Some((component, rest)) => match target.split_first() {
+            Some((target_component, target_rest)) => {
+                let component = component.chars().collect::<Vec<_>>();
+                let target_component = target_component.chars().collect::<Vec<_>>();
+                glob_component(&component, &target_component)
+                    && glob_components(rest, target_rest, globstar)
+            }
+            None => false,
+        },
+    }
+}
+
+// Match a single component of a target against a single component of a pattern.
+fn glob_component(pattern: &[char], target: &[char]) -> bool {
+    match pattern.split_first() {
+        None => target.is_empty(),
+        Some(('*', rest)) => (0..=target.len()).any(|i| glob_component(rest, &target[i..])),
+        Some(('[', rest)) => match (target.split_first(), glob_class(rest)) {
+            (Some((c, target_rest)), Some((class, rest))) => {
+                class(*c) && glob_component(rest, target_rest)
+            }
+            // An unterminated set is matched literally.
+            (Some(('[', target_rest)), None) => glob_component(rest, target_rest),
+            _ => false,
+        },
+        Some((c, rest)) => match target.split_first() {
+            Some((target_c, target_rest)) => c == target_c && glob_component(rest, target_rest),
+            None => false,
+        },
+    }
+}
+
+// Parse the set of characters after a `[` in a pattern. Returns whether or not a character is in
+// the set, and the rest of the pattern after the closing `]`, or `None` if the set is not closed.
+fn glob_class(pattern: &[char]) -> Option<(impl Fn(char) -> bool + '_, &[char])> {
+    let (negated, pattern) = match pattern.split_first() {
+        Some(('!', rest)) | Some(('^', rest)) => (true, rest),
+        _ => (false, pattern),
+    };
+
+    // A `]` at the start of a set is part of the set.
+    let end = pattern
+        .iter()
+        .skip(1)
+        .position(|c| *c == ']')
+        .map(|i| i + 1)?;
+    let (class, rest) = (&pattern[..end], &pattern[end + 1..]);
+
+    let contains = move |c: char| {
+        let mut i = 0;
+        while i < class.len() {
+            if i + 2 < class.len() && class[i + 1] == '-' {
+                if class[i] <= c && c <= class[i + 2] {
+                    return true;
+                }
+                i += 3;
+            } else {
+                if class[i] == c {
+                    return true;
+                }
+                i += 1;
+            }
+        }
+        false
+    };
+
+    Some((move |c| contains(c) != negated, rest))
+}
+
 /// Description of a target, used in verification.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TargetDescription {
-    length: u64,
+    length: Option<u64>,
     hashes: HashMap<HashAlgorithm, HashValue>,
     custom: HashMap<String, serde_json::Value>,
 }
@@ -1619,17 +2648,15 @@ impl TargetDescription {
     ///
     /// Note: Creating this manually could lead to errors, and the `from_reader` method is
     /// preferred.
+    ///
+    /// The length and hashes may be omitted for targets that are not meant to be fetched through a
+    /// client, but such a target is only fetched by a client that allows unverifiable targets. See
+    /// [TargetDescription::is_verifiable].
     pub fn new(
-        length: u64,
+        length: Option<u64>,
         hashes: HashMap<HashAlgorithm, HashValue>,
         custom: HashMap<String, serde_json::Value>,
     ) -> Result<Self> {
-        if hashes.is_empty() {
-            return Err(Error::IllegalArgument(
-                "Cannot have empty set of hashes".into(),
-            ));
-        }
-
         Ok(TargetDescription {
             length,
             hashes,
@@ -1658,7 +2685,7 @@ impl TargetDescription {
     ///     BU1zKP8GShoJuXEtCf5NkDTCEJgQ==";
     /// let sha512 = HashValue::new(BASE64URL.decode(s.as_bytes()).unwrap());
     ///
-    /// assert_eq!(target_description.length(), bytes.len() as u64);
+    /// assert_eq!(target_description.length(), Some(bytes.len() as u64));
     /// assert_eq!(target_description.hashes().get(&HashAlgorithm::Sha256), Some(&sha256));
     /// assert_eq!(target_description.hashes().get(&HashAlgorithm::Sha512), Some(&sha512));
     /// ```
@@ -1694,7 +2721,7 @@ impl TargetDescription {
     ///     BU1zKP8GShoJuXEtCf5NkDTCEJgQ==";
     /// let sha512 = HashValue::new(BASE64URL.decode(s.as_bytes()).unwrap());
     ///
-    /// assert_eq!(target_description.length(), bytes.len() as u64);
+    /// assert_eq!(target_description.length(), Some(bytes.len() as u64));
     /// assert_eq!(target_description.hashes().get(&HashAlgorithm::Sha256), Some(&sha256));
     /// assert_eq!(target_description.hashes().get(&HashAlgorithm::Sha512), Some(&sha512));
     /// assert_eq!(target_description.custom().get("Hello"), Some(&"World".into()));
@@ -1706,13 +2733,14 @@ impl TargetDescription {
     ) -> Result<Self> {
         let hashes = crypto::calculate_hashes_from_slice(buf, hash_algs)?;
         Ok(TargetDescription {
-            length: buf.len() as u64,
+            length: Some(buf.len() as u64),
             hashes,
             custom,
         })
     }
 
-    /// Read the from the given reader and calculate the length and hash values.
+    /// Read the from the given reader and calculate the length and hash values. The reader is
+    /// only read once, and the hash values of all of `hash_algs` are calculated as it is read.
     ///
     /// ```
     /// # use data_encoding::BASE64URL;
@@ -1735,7 +2763,7 @@ impl TargetDescription {
     ///     BU1zKP8GShoJuXEtCf5NkDTCEJgQ==";
     /// let sha512 = HashValue::new(BASE64URL.decode(s.as_bytes()).unwrap());
     ///
-    /// assert_eq!(target_description.length(), bytes.len() as u64);
+    /// assert_eq!(target_description.length(), Some(bytes.len() as u64));
     /// assert_eq!(target_description.hashes().get(&HashAlgorithm::Sha256), Some(&sha256));
     /// assert_eq!(target_description.hashes().get(&HashAlgorithm::Sha512), Some(&sha512));
     /// # })
@@ -1777,7 +2805,7 @@ impl TargetDescription {
     ///     BU1zKP8GShoJuXEtCf5NkDTCEJgQ==";
     /// let sha512 = HashValue::new(BASE64URL.decode(s.as_bytes()).unwrap());
     ///
-    /// assert_eq!(target_description.length(), bytes.len() as u64);
+    /// assert_eq!(target_description.length(), Some(bytes.len() as u64));
     /// assert_eq!(target_description.hashes().get(&HashAlgorithm::Sha256), Some(&sha256));
     /// assert_eq!(target_description.hashes().get(&HashAlgorithm::Sha512), Some(&sha512));
     /// assert_eq!(target_description.custom().get("Hello"), Some(&"World".into()));
@@ -1793,26 +2821,145 @@ impl TargetDescription {
     {
         let (length, hashes) = crypto::calculate_hashes_from_reader(read, hash_algs).await?;
         Ok(TargetDescription {
-            length,
+            length: Some(length),
             hashes,
             custom,
         })
     }
 
-    /// The maximum length of the target.
-    pub fn length(&self) -> u64 {
+    /// The maximum length of the target, if it is known.
+    pub fn length(&self) -> Option<u64> {
         self.length
     }
 
-    /// An immutable reference to the list of calculated hashes.
+    /// An immutable reference to the list of calculated hashes, which is empty if the hashes of
+    /// the target are not known.
     pub fn hashes(&self) -> &HashMap<HashAlgorithm, HashValue> {
         &self.hashes
     }
 
+    /// Whether or not the target can be verified when it is fetched, which requires both its
+    /// length and at least one hash.
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use tuf::crypto::HashAlgorithm;
+    /// # use tuf::metadata::TargetDescription;
+    /// #
+    /// let description = TargetDescription::from_slice(b"foo", &[HashAlgorithm::Sha256]).unwrap();
+    /// assert!(description.is_verifiable());
+    ///
+    /// let description = TargetDescription::new(None, HashMap::new(), HashMap::new()).unwrap();
+    /// assert!(!description.is_verifiable());
+    /// ```
+    pub fn is_verifiable(&self) -> bool {
+        self.length.is_some() && !self.hashes.is_empty()
+    }
+
     /// An immutable reference to the custom metadata.
     pub fn custom(&self) -> &HashMap<String, serde_json::Value> {
         &self.custom
     }
+
+    /// Whether the target was yanked, which by convention is marked by a `"yanked": true` field
+    /// in its custom metadata. A yanked target, such as a release that was pulled, can still be
+    /// fetched, but shouldn't be picked for new installs. See
+    /// [RepoBuilder::yank_target](crate::repo_builder::RepoBuilder::yank_target).
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use tuf::metadata::TargetDescription;
+    /// #
+    /// let description = TargetDescription::new(None, HashMap::new(), HashMap::new()).unwrap();
+    /// assert!(!description.is_yanked());
+    ///
+    /// let custom = HashMap::from([("yanked".into(), true.into())]);
+    /// let description = TargetDescription::new(None, HashMap::new(), custom).unwrap();
+    /// assert!(description.is_yanked());
+    /// ```
+    pub fn is_yanked(&self) -> bool {
+        self.custom.get(YANKED_CUSTOM_KEY) == Some(&serde_json::Value::Bool(true))
+    }
+
+    /// Deserialize the custom metadata into a `T`, such as a struct with a field for each of the
+    /// custom fields an installer needs.
+    ///
+    /// ```
+    /// # use serde_derive::Deserialize;
+    /// # use serde_json::json;
+    /// # use std::collections::HashMap;
+    /// # use tuf::crypto::HashAlgorithm;
+    /// # use tuf::metadata::TargetDescription;
+    /// #
+    /// #[derive(Deserialize)]
+    /// struct Custom {
+    ///     version: String,
+    ///     #[serde(rename = "install-path")]
+    ///     install_path: String,
+    /// }
+    ///
+    /// let custom = HashMap::from([
+    ///     ("version".into(), json!("1.2.3")),
+    ///     ("install-path".into(), json!("/usr/bin/app")),
+    /// ]);
+    /// let description =
+    ///     TargetDescription::from_slice_with_custom(b"app", &[HashAlgorithm::Sha256], custom)
+    ///         .unwrap();
+    ///
+    /// let custom: Custom = description.custom_as().unwrap();
+    /// assert_eq!(custom.version, "1.2.3");
+    /// assert_eq!(custom.install_path, "/usr/bin/app");
+    /// ```
+    pub fn custom_as<T>(&self) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let custom = self
+            .custom
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect::<serde_json::Map<_, _>>();
+        serde_json::from_value(serde_json::Value::Object(custom))
+            .map_err(|e| Error::InvalidCustomMetadata(e.to_string()))
+    }
+
+    /// Deserialize the custom metadata `field` into a `T`, or return `None` if there is no such
+    /// field.
+    pub fn custom_field_as<T>(&self, field: &str) -> Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        match self.custom.get(field) {
+            Some(value) => serde_json::from_value(value.clone())
+                .map(Some)
+                .map_err(|e| Error::InvalidCustomMetadata(format!("field {:?}: {}", field, e))),
+            None => Ok(None),
+        }
+    }
+
+    /// Serialize `custom` into the custom metadata, replacing any fields that are already set.
+    /// `custom` must serialize to a map, such as a struct.
+    pub fn with_typed_custom<T>(mut self, custom: &T) -> Result<Self>
+    where
+        T: Serialize,
+    {
+        self.custom.extend(typed_custom(custom)?);
+        Ok(self)
+    }
+}
+
+/// Serialize `custom` into custom target metadata.
+pub(crate) fn typed_custom<T>(custom: &T) -> Result<HashMap<String, serde_json::Value>>
+where
+    T: Serialize,
+{
+    match serde_json::to_value(custom) {
+        Ok(serde_json::Value::Object(custom)) => Ok(custom.into_iter().collect()),
+        Ok(_) => Err(Error::InvalidCustomMetadata(
+            "custom metadata must serialize to a map".into(),
+        )),
+        Err(e) => Err(Error::InvalidCustomMetadata(e.to_string())),
+    }
 }
 
 impl Serialize for TargetDescription {
@@ -1836,10 +2983,12 @@ impl<'de> Deserialize<'de> for TargetDescription {
 /// Metadata for the targets role.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TargetsMetadata {
+    spec_version: SpecVersion,
     version: u32,
     expires: DateTime<Utc>,
     targets: HashMap<TargetPath, TargetDescription>,
     delegations: Delegations,
+    unrecognized_fields: HashMap<String, serde_json::Value>,
 }
 
 impl TargetsMetadata {
@@ -1857,13 +3006,31 @@ impl TargetsMetadata {
         }
 
         Ok(TargetsMetadata {
+            spec_version: SpecVersion::default(),
             version,
             expires,
             targets,
             delegations,
+            unrecognized_fields: HashMap::new(),
         })
     }
 
+    /// Set the version of the specification the metadata claims to follow.
+    pub fn with_spec_version(mut self, spec_version: SpecVersion) -> Self {
+        self.spec_version = spec_version;
+        self
+    }
+
+    /// Set the fields of the metadata that this crate does not recognize, which are preserved
+    /// when the metadata is serialized again.
+    pub fn with_unrecognized_fields(
+        mut self,
+        unrecognized_fields: HashMap<String, serde_json::Value>,
+    ) -> Self {
+        self.unrecognized_fields = unrecognized_fields;
+        self
+    }
+
     /// An immutable reference to the descriptions of targets.
     pub fn targets(&self) -> &HashMap<TargetPath, TargetDescription> {
         &self.targets
@@ -1873,6 +3040,11 @@ impl TargetsMetadata {
     pub fn delegations(&self) -> &Delegations {
         &self.delegations
     }
+
+    /// An immutable reference to the fields of the metadata that this crate does not recognize.
+    pub fn unrecognized_fields(&self) -> &HashMap<String, serde_json::Value> {
+        &self.unrecognized_fields
+    }
 }
 
 impl Metadata for TargetsMetadata {
@@ -1885,6 +3057,10 @@ impl Metadata for TargetsMetadata {
     fn expires(&self) -> &DateTime<Utc> {
         &self.expires
     }
+
+    fn spec_version(&self) -> &SpecVersion {
+        &self.spec_version
+    }
 }
 
 impl Serialize for TargetsMetadata {
@@ -1941,6 +3117,17 @@ impl TargetsMetadataBuilder {
         self
     }
 
+    /// Set this metadata to expire `duration` from the current time.
+    pub fn expires_in(self, duration: Duration) -> Self {
+        self.expires_with(&duration)
+    }
+
+    /// Set the time this metadata expires with an [ExpirationPolicy], starting from the current
+    /// time.
+    pub fn expires_with<P: ExpirationPolicy + ?Sized>(self, policy: &P) -> Self {
+        self.expires(policy.expires(Utc::now()))
+    }
+
     /// Add target to the target metadata.
     pub fn insert_target_from_slice(
         self,
@@ -1976,7 +3163,25 @@ impl TargetsMetadataBuilder {
         self
     }
 
+    /// Serialize `custom` into the custom metadata of the target at `path`, which must already
+    /// have been added. See [TargetDescription::with_typed_custom].
+    pub fn insert_typed_custom<T>(mut self, path: &TargetPath, custom: &T) -> Result<Self>
+    where
+        T: Serialize,
+    {
+        let description = self
+            .targets
+            .get_mut(path)
+            .ok_or_else(|| Error::TargetNotFound(path.clone()))?;
+        description.custom.extend(typed_custom(custom)?);
+        Ok(self)
+    }
+
     /// Add `Delegations` to this target metadata.
+    ///
+    /// To delegate to TAP 15 succinct hash bins, build the delegations with
+    /// [DelegationsBuilder::succinct_roles], and use [SuccinctRoles::bin_targets] to create the
+    /// targets metadata of each bin.
     pub fn delegations(mut self, delegations: Delegations) -> Self {
         self.delegations = Some(delegations);
         self
@@ -2014,7 +3219,9 @@ impl Default for TargetsMetadataBuilder {
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct Delegations {
     keys: HashMap<KeyId, PublicKey>,
+    entries: Vec<DelegationEntry>,
     roles: Vec<Delegation>,
+    succinct_roles: Option<SuccinctRoles>,
 }
 
 impl Delegations {
@@ -2027,24 +3234,93 @@ impl Delegations {
     // TODO check all roles have their ID in the set of keys
     /// Create a new `Delegations` wrapper from the given set of trusted keys and roles.
     pub fn new(keys: HashMap<KeyId, PublicKey>, roles: Vec<Delegation>) -> Result<Self> {
-        if roles.len()
-            != roles
-                .iter()
-                .map(|r| &r.name)
-                .collect::<HashSet<&MetadataPath>>()
-                .len()
-        {
-            return Err(Error::IllegalArgument(
-                "Cannot have duplicated roles in delegations.".into(),
+        Self::with_entries(
+            keys,
+            roles.into_iter().map(DelegationEntry::Single).collect(),
+        )
+    }
+
+    /// Create a new `Delegations` wrapper that also delegates to roles that must agree on a
+    /// target, as specified by [TAP 3].
+    ///
+    /// Multi-role delegations are consulted after all of the single role delegations. Use
+    /// [Delegations::with_entries] to interleave them.
+    ///
+    /// [TAP 3]: https://github.com/theupdateframework/taps/blob/master/tap3.md
+    pub fn with_multi_role_delegations(
+        keys: HashMap<KeyId, PublicKey>,
+        roles: Vec<Delegation>,
+        multi_role_delegations: Vec<MultiRoleDelegation>,
+    ) -> Result<Self> {
+        let entries = roles
+            .into_iter()
+            .map(DelegationEntry::Single)
+            .chain(
+                multi_role_delegations
+                    .into_iter()
+                    .map(DelegationEntry::Multi),
+            )
+            .collect();
+        Self::with_entries(keys, entries)
+    }
+
+    /// Create a new `Delegations` wrapper from an ordered list of single role and multi-role
+    /// delegations. Targets are looked up in the entries in this order.
+    pub fn with_entries(
+        keys: HashMap<KeyId, PublicKey>,
+        entries: Vec<DelegationEntry>,
+    ) -> Result<Self> {
+        let names = entries
+            .iter()
+            .flat_map(DelegationEntry::roles)
+            .map(|r| &r.name)
+            .collect::<Vec<&MetadataPath>>();
+        if names.len() != names.iter().collect::<HashSet<_>>().len() {
+            return Err(Error::IllegalArgument(
+                "Cannot have duplicated roles in delegations.".into(),
             ));
         }
 
-        Ok(Delegations { keys, roles })
+        let roles = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                DelegationEntry::Single(delegation) => Some(delegation.clone()),
+                DelegationEntry::Multi(_) => None,
+            })
+            .collect();
+
+        Ok(Delegations {
+            keys,
+            entries,
+            roles,
+            succinct_roles: None,
+        })
+    }
+
+    /// Create a new `Delegations` wrapper that delegates to the hash bins described by
+    /// `succinct_roles`, as specified by [TAP 15].
+    ///
+    /// [TAP 15]: https://github.com/theupdateframework/taps/blob/master/tap15.md
+    pub fn new_succinct(
+        keys: HashMap<KeyId, PublicKey>,
+        succinct_roles: SuccinctRoles,
+    ) -> Result<Self> {
+        Ok(Delegations {
+            keys,
+            entries: vec![],
+            roles: vec![],
+            succinct_roles: Some(succinct_roles),
+        })
     }
 
     /// Return if this delegation is empty.
     pub fn is_empty(&self) -> bool {
-        self.keys.is_empty() && self.roles.is_empty()
+        self.keys.is_empty() && !self.has_roles()
+    }
+
+    /// Return if this delegates to any roles, either explicitly or through succinct hash bins.
+    pub fn has_roles(&self) -> bool {
+        !self.entries.is_empty() || self.succinct_roles.is_some()
     }
 
     /// An immutable reference to the keys used for this set of delegations.
@@ -2052,10 +3328,85 @@ impl Delegations {
         &self.keys
     }
 
-    /// An immutable reference to the delegated roles.
+    /// An immutable reference to the single role delegations, in order.
     pub fn roles(&self) -> &Vec<Delegation> {
         &self.roles
     }
+
+    /// The delegations to roles that must agree on a target, in order.
+    pub fn multi_role_delegations(&self) -> impl Iterator<Item = &MultiRoleDelegation> + '_ {
+        self.entries.iter().filter_map(|entry| match entry {
+            DelegationEntry::Single(_) => None,
+            DelegationEntry::Multi(delegation) => Some(delegation),
+        })
+    }
+
+    /// An immutable reference to the single role and multi-role delegations, in the order they
+    /// are consulted.
+    pub fn entries(&self) -> &[DelegationEntry] {
+        &self.entries
+    }
+
+    /// An immutable reference to the succinct hash bin delegations, if any.
+    pub fn succinct_roles(&self) -> Option<&SuccinctRoles> {
+        self.succinct_roles.as_ref()
+    }
+
+    /// The delegations to consult, in order, when looking up `target`. With succinct hash bin
+    /// delegations this is only the bin that `target` hashes to.
+    pub(crate) fn entries_for_target(&self, target: &TargetPath) -> Cow<'_, [DelegationEntry]> {
+        match &self.succinct_roles {
+            Some(succinct_roles) => Cow::Owned(vec![DelegationEntry::Single(
+                succinct_roles.delegation_for_target(target),
+            )]),
+            None => Cow::Borrowed(&self.entries),
+        }
+    }
+
+    /// Return the delegation of `role`, if this delegates to it.
+    pub(crate) fn delegation(&self, role: &MetadataPath) -> Option<Cow<'_, Delegation>> {
+        if let Some(succinct_roles) = &self.succinct_roles {
+            return if succinct_roles.is_delegated_role(role) {
+                Some(Cow::Owned(succinct_roles.delegation(role.clone())))
+            } else {
+                None
+            };
+        }
+
+        self.entries
+            .iter()
+            .flat_map(DelegationEntry::roles)
+            .find(|delegation| delegation.name() == role)
+            .map(Cow::Borrowed)
+    }
+
+    /// The name of every explicitly delegated role, including the roles of multi-role
+    /// delegations.
+    pub(crate) fn role_names(&self) -> impl Iterator<Item = &MetadataPath> + '_ {
+        self.entries
+            .iter()
+            .flat_map(DelegationEntry::roles)
+            .map(|delegation| delegation.name())
+    }
+}
+
+/// An entry of the ordered list of delegations in [Delegations].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DelegationEntry {
+    /// A delegation to a single role.
+    Single(Delegation),
+    /// A delegation to roles that must agree on a target.
+    Multi(MultiRoleDelegation),
+}
+
+impl DelegationEntry {
+    /// The delegated roles of this entry, in order.
+    pub fn roles(&self) -> &[Delegation] {
+        match self {
+            DelegationEntry::Single(delegation) => std::slice::from_ref(delegation),
+            DelegationEntry::Multi(delegation) => delegation.roles(),
+        }
+    }
 }
 
 impl Serialize for Delegations {
@@ -2080,8 +3431,9 @@ impl<'de> Deserialize<'de> for Delegations {
 #[derive(Default)]
 pub struct DelegationsBuilder {
     keys: HashMap<KeyId, PublicKey>,
-    roles: Vec<Delegation>,
+    entries: Vec<DelegationEntry>,
     role_index: HashMap<MetadataPath, usize>,
+    succinct_roles: Option<SuccinctRoles>,
 }
 
 impl DelegationsBuilder {
@@ -2089,8 +3441,9 @@ impl DelegationsBuilder {
     pub fn new() -> Self {
         Self {
             keys: HashMap::new(),
-            roles: vec![],
+            entries: vec![],
             role_index: HashMap::new(),
+            succinct_roles: None,
         }
     }
 
@@ -2105,24 +3458,67 @@ impl DelegationsBuilder {
         // The delegation list is ordered and unique by role name, so check if we should overwrite
         // the old delegation.
         if let Some(idx) = self.role_index.get(&delegation.name) {
-            self.roles[*idx] = delegation;
+            self.entries[*idx] = DelegationEntry::Single(delegation);
         } else {
             self.role_index
-                .insert(delegation.name.clone(), self.roles.len());
+                .insert(delegation.name.clone(), self.entries.len());
+
+            self.entries.push(DelegationEntry::Single(delegation));
+        }
+
+        self
+    }
 
-            self.roles.push(delegation);
+    /// Add a [MultiRoleDelegation]. It is consulted after the delegations that were added before
+    /// it, and replaces any multi-role delegation with the same roles.
+    pub fn multi_role(mut self, delegation: MultiRoleDelegation) -> Self {
+        match self.entries.iter_mut().find(|entry| match entry {
+            DelegationEntry::Multi(old) => old.role_names().eq(delegation.role_names()),
+            DelegationEntry::Single(_) => false,
+        }) {
+            Some(old) => *old = DelegationEntry::Multi(delegation),
+            None => self.entries.push(DelegationEntry::Multi(delegation)),
         }
+        self
+    }
 
+    /// Delegate to succinct hash bins instead of to explicit roles.
+    pub fn succinct_roles(mut self, succinct_roles: SuccinctRoles) -> Self {
+        self.succinct_roles = Some(succinct_roles);
         self
     }
 
     /// Construct a new [Delegations].
     pub fn build(self) -> Result<Delegations> {
-        Delegations::new(self.keys, self.roles)
+        match self.succinct_roles {
+            Some(_) if !self.entries.is_empty() => Err(Error::IllegalArgument(
+                "Cannot have both roles and succinct roles in delegations.".into(),
+            )),
+            Some(succinct_roles) => {
+                // Every bin shares the same keys, so a missing key would make every bin
+                // unverifiable.
+                if let Some(key_id) = succinct_roles
+                    .key_ids()
+                    .iter()
+                    .find(|key_id| !self.keys.contains_key(key_id))
+                {
+                    return Err(Error::IllegalArgument(format!(
+                        "Succinct roles key {:?} is not in the delegation keys",
+                        key_id
+                    )));
+                }
+
+                Delegations::new_succinct(self.keys, succinct_roles)
+            }
+            None => Delegations::with_entries(self.keys, self.entries),
+        }
     }
 }
 
 /// A delegated targets role.
+///
+/// A delegation is either authorized to sign the targets under a set of `paths`, or the targets
+/// whose SHA-256 path hash, in lowercase hex, starts with one of a set of `path_hash_prefixes`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Delegation {
     name: MetadataPath,
@@ -2130,6 +3526,7 @@ pub struct Delegation {
     threshold: u32,
     key_ids: HashSet<KeyId>,
     paths: HashSet<TargetPath>,
+    path_hash_prefixes: HashSet<String>,
 }
 
 impl Delegation {
@@ -2146,33 +3543,99 @@ impl Delegation {
         key_ids: HashSet<KeyId>,
         paths: HashSet<TargetPath>,
     ) -> Result<Self> {
-        if key_ids.is_empty() {
-            return Err(Error::IllegalArgument("Cannot have empty key IDs".into()));
-        }
-
         if paths.is_empty() {
             return Err(Error::IllegalArgument("Cannot have empty paths".into()));
         }
 
-        if threshold < 1 {
-            return Err(Error::IllegalArgument("Cannot have threshold < 1".into()));
-        }
+        Self::check_keys(threshold, &key_ids)?;
 
-        if (key_ids.len() as u64) < u64::from(threshold) {
+        Ok(Delegation {
+            name,
+            terminating,
+            threshold,
+            key_ids,
+            paths,
+            path_hash_prefixes: HashSet::new(),
+        })
+    }
+
+    /// Create a new delegation of the targets whose path hash starts with one of
+    /// `path_hash_prefixes`.
+    ///
+    /// ```
+    /// # use std::collections::HashSet;
+    /// # use tuf::crypto::KeyId;
+    /// # use tuf::metadata::{Delegation, MetadataPath, TargetPath};
+    /// let key_id: KeyId = "4750eaf6878740780d6f97b12dbad079fb012bec88c78de2c380add56d3f51db"
+    ///     .parse()
+    ///     .unwrap();
+    /// let delegation = Delegation::with_path_hash_prefixes(
+    ///     MetadataPath::new("bins-2").unwrap(),
+    ///     false,
+    ///     1,
+    ///     HashSet::from([key_id]),
+    ///     HashSet::from(["2".into()]),
+    /// )
+    /// .unwrap();
+    ///
+    /// // sha256("foo") is 2c26b46b...
+    /// assert!(delegation.matches_target(&TargetPath::new("foo").unwrap()));
+    /// ```
+    pub fn with_path_hash_prefixes(
+        name: MetadataPath,
+        terminating: bool,
+        threshold: u32,
+        key_ids: HashSet<KeyId>,
+        path_hash_prefixes: HashSet<String>,
+    ) -> Result<Self> {
+        if path_hash_prefixes.is_empty() {
             return Err(Error::IllegalArgument(
-                "Cannot have threshold less than number of keys".into(),
+                "Cannot have empty path hash prefixes".into(),
             ));
         }
 
+        if let Some(prefix) = path_hash_prefixes.iter().find(|prefix| {
+            prefix.is_empty()
+                || !prefix
+                    .bytes()
+                    .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        }) {
+            return Err(Error::IllegalArgument(format!(
+                "Path hash prefix {:?} must be a non-empty lowercase hex string",
+                prefix
+            )));
+        }
+
+        Self::check_keys(threshold, &key_ids)?;
+
         Ok(Delegation {
             name,
             terminating,
             threshold,
             key_ids,
-            paths,
+            paths: HashSet::new(),
+            path_hash_prefixes,
         })
     }
 
+    fn check_keys(threshold: u32, key_ids: &HashSet<KeyId>) -> Result<()> {
+        if key_ids.is_empty() {
+            return Err(Error::IllegalArgument("Cannot have empty key IDs".into()));
+        }
+
+        if threshold < 1 {
+            return Err(Error::IllegalArgument("Cannot have threshold < 1".into()));
+        }
+
+        if (key_ids.len() as u64) < u64::from(threshold) {
+            return Err(Error::IllegalArgument(
+                "Cannot have threshold less than number of keys".into(),
+            ));
+        }
+
+        Ok(())
+    }
+
     /// An immutable reference to the delegations's metadata path (role).
     pub fn name(&self) -> &MetadataPath {
         &self.name
@@ -2193,10 +3656,44 @@ impl Delegation {
         self.threshold
     }
 
-    /// An immutable reference to the delegation's authorized paths.
+    /// An immutable reference to the delegation's authorized paths. This is empty if the
+    /// delegation uses path hash prefixes.
     pub fn paths(&self) -> &HashSet<TargetPath> {
         &self.paths
     }
+
+    /// An immutable reference to the delegation's authorized path hash prefixes. This is empty if
+    /// the delegation uses paths.
+    pub fn path_hash_prefixes(&self) -> &HashSet<String> {
+        &self.path_hash_prefixes
+    }
+
+    /// Whether or not this delegation is authorized to sign `target`, matching its paths with
+    /// the default [PathMatching].
+    pub fn matches_target(&self, target: &TargetPath) -> bool {
+        self.matches_target_with_policy(target, PathMatching::default())
+    }
+
+    /// Whether or not this delegation is authorized to sign `target`, matching its paths with
+    /// `path_matching`.
+    pub fn matches_target_with_policy(
+        &self,
+        target: &TargetPath,
+        path_matching: PathMatching,
+    ) -> bool {
+        if self.path_hash_prefixes.is_empty() {
+            return self
+                .paths
+                .iter()
+                .any(|path| path_matching.matches(path, target));
+        }
+
+        let hash = ring::digest::digest(&ring::digest::SHA256, target.as_str().as_bytes());
+        let hash = data_encoding::HEXLOWER.encode(hash.as_ref());
+        self.path_hash_prefixes
+            .iter()
+            .any(|prefix| hash.starts_with(prefix.as_str()))
+    }
 }
 
 impl Serialize for Delegation {
@@ -2224,6 +3721,7 @@ pub struct DelegationBuilder {
     threshold: u32,
     key_ids: HashSet<KeyId>,
     paths: HashSet<TargetPath>,
+    path_hash_prefixes: HashSet<String>,
 }
 
 impl DelegationBuilder {
@@ -2235,6 +3733,7 @@ impl DelegationBuilder {
             threshold: 1,
             key_ids: HashSet::new(),
             paths: HashSet::new(),
+            path_hash_prefixes: HashSet::new(),
         }
     }
 
@@ -2262,14 +3761,445 @@ impl DelegationBuilder {
         self
     }
 
+    /// Delegate the targets whose path hash starts with `prefix` to this delegation. This can't
+    /// be combined with [DelegationBuilder::delegate_path].
+    pub fn delegate_path_hash_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.path_hash_prefixes.insert(prefix.into());
+        self
+    }
+
     /// Construct the [Delegation].
     pub fn build(self) -> Result<Delegation> {
-        Delegation::new(
+        if self.path_hash_prefixes.is_empty() {
+            return Delegation::new(
+                self.role,
+                self.terminating,
+                self.threshold,
+                self.key_ids,
+                self.paths,
+            );
+        }
+
+        if !self.paths.is_empty() {
+            return Err(Error::IllegalArgument(
+                "Cannot have both paths and path hash prefixes".into(),
+            ));
+        }
+
+        Delegation::with_path_hash_prefixes(
             self.role,
             self.terminating,
             self.threshold,
             self.key_ids,
-            self.paths,
+            self.path_hash_prefixes,
+        )
+    }
+}
+
+/// A delegation to several roles, of which at least `min_roles_in_agreement` must describe a
+/// target identically for it to be trusted, as specified by [TAP 3].
+///
+/// Every role has its own keys and threshold, but all of them share the same `terminating` flag
+/// and `paths` or `path_hash_prefixes`. This can be used to require that, for example, both a
+/// development and a release team sign production artifacts.
+///
+/// [TAP 3]: https://github.com/theupdateframework/taps/blob/master/tap3.md
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiRoleDelegation {
+    roles: Vec<Delegation>,
+    min_roles_in_agreement: u32,
+}
+
+impl MultiRoleDelegation {
+    /// Create a new multi-role delegation to `roles`, which must all have the same `terminating`
+    /// flag and paths or path hash prefixes.
+    ///
+    /// ```
+    /// # use std::collections::HashSet;
+    /// # use tuf::crypto::KeyId;
+    /// # use tuf::metadata::{Delegation, MetadataPath, MultiRoleDelegation, TargetPath};
+    /// let key_id: KeyId = "4750eaf6878740780d6f97b12dbad079fb012bec88c78de2c380add56d3f51db"
+    ///     .parse()
+    ///     .unwrap();
+    /// let role = |name| {
+    ///     Delegation::builder(MetadataPath::new(name).unwrap())
+    ///         .key_id(key_id.clone())
+    ///         .delegate_path(TargetPath::new("prod/").unwrap())
+    ///         .build()
+    ///         .unwrap()
+    /// };
+    ///
+    /// let delegation = MultiRoleDelegation::new(2, vec![role("dev"), role("release")]).unwrap();
+    /// assert_eq!(delegation.min_roles_in_agreement(), 2);
+    /// ```
+    pub fn new(min_roles_in_agreement: u32, roles: Vec<Delegation>) -> Result<Self> {
+        if min_roles_in_agreement < 1 {
+            return Err(Error::IllegalArgument(
+                "Cannot have min roles in agreement < 1".into(),
+            ));
+        }
+
+        if (roles.len() as u64) < u64::from(min_roles_in_agreement) {
+            return Err(Error::IllegalArgument(
+                "Cannot have fewer roles than min roles in agreement".into(),
+            ));
+        }
+
+        let first = &roles[0];
+        if roles.iter().any(|role| {
+            role.terminating != first.terminating
+                || role.paths != first.paths
+                || role.path_hash_prefixes != first.path_hash_prefixes
+        }) {
+            return Err(Error::IllegalArgument(
+                "Roles of a multi-role delegation must share terminating and paths".into(),
+            ));
+        }
+
+        if roles.len() != roles.iter().map(|r| &r.name).collect::<HashSet<_>>().len() {
+            return Err(Error::IllegalArgument(
+                "Cannot have duplicated roles in a multi-role delegation".into(),
+            ));
+        }
+
+        Ok(MultiRoleDelegation {
+            roles,
+            min_roles_in_agreement,
+        })
+    }
+
+    /// The delegated roles, in order.
+    pub fn roles(&self) -> &[Delegation] {
+        &self.roles
+    }
+
+    /// The number of roles that must describe a target identically for it to be trusted.
+    pub fn min_roles_in_agreement(&self) -> u32 {
+        self.min_roles_in_agreement
+    }
+
+    /// Whether or not the delegation is terminating.
+    pub fn terminating(&self) -> bool {
+        self.roles[0].terminating
+    }
+
+    /// The paths the roles are authorized to sign. This is empty if the roles use path hash
+    /// prefixes.
+    pub fn paths(&self) -> &HashSet<TargetPath> {
+        &self.roles[0].paths
+    }
+
+    /// The path hash prefixes the roles are authorized to sign. This is empty if the roles use
+    /// paths.
+    pub fn path_hash_prefixes(&self) -> &HashSet<String> {
+        &self.roles[0].path_hash_prefixes
+    }
+
+    /// Whether or not the roles are authorized to sign `target`, matching their paths with the
+    /// default [PathMatching].
+    pub fn matches_target(&self, target: &TargetPath) -> bool {
+        self.roles[0].matches_target(target)
+    }
+
+    /// Whether or not the roles are authorized to sign `target`, matching their paths with
+    /// `path_matching`.
+    pub fn matches_target_with_policy(
+        &self,
+        target: &TargetPath,
+        path_matching: PathMatching,
+    ) -> bool {
+        self.roles[0].matches_target_with_policy(target, path_matching)
+    }
+
+    /// The description that at least `min_roles_in_agreement` of `descriptions` agree on, if any.
+    pub(crate) fn agreed_description<'a, I>(&self, descriptions: I) -> Option<TargetDescription>
+    where
+        I: IntoIterator<Item = &'a TargetDescription>,
+    {
+        let mut counts: Vec<(&TargetDescription, u32)> = vec![];
+        for description in descriptions {
+            match counts.iter_mut().find(|(d, _)| *d == description) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((description, 1)),
+            }
+        }
+
+        counts
+            .into_iter()
+            .find(|(_, count)| *count >= self.min_roles_in_agreement)
+            .map(|(description, _)| description.clone())
+    }
+
+    fn role_names(&self) -> impl Iterator<Item = &MetadataPath> + '_ {
+        self.roles.iter().map(|role| role.name())
+    }
+}
+
+/// Succinct hash bin delegations, as specified by [TAP 15].
+///
+/// Instead of listing every bin, the delegating role describes `2^bit_length` bins that share the
+/// same keys and threshold. Bin `n` is named `{name_prefix}-{n}`, with `n` formatted as lowercase
+/// hex and zero-padded to `ceil(bit_length / 4)` digits. A target is delegated to the bin given by
+/// the first `bit_length` bits of the SHA-256 hash of its path, so a client only needs to fetch a
+/// single bin to look it up.
+///
+/// [TAP 15]: https://github.com/theupdateframework/taps/blob/master/tap15.md
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuccinctRoles {
+    key_ids: HashSet<KeyId>,
+    threshold: u32,
+    bit_length: u8,
+    name_prefix: String,
+}
+
+impl SuccinctRoles {
+    /// Create a new [SuccinctRolesBuilder] for `2^bit_length` bins named with `name_prefix`.
+    pub fn builder(name_prefix: impl Into<String>, bit_length: u8) -> SuccinctRolesBuilder {
+        SuccinctRolesBuilder::new(name_prefix, bit_length)
+    }
+
+    /// Create a new description of succinct hash bins.
+    pub fn new(
+        key_ids: HashSet<KeyId>,
+        threshold: u32,
+        bit_length: u8,
+        name_prefix: String,
+    ) -> Result<Self> {
+        if key_ids.is_empty() {
+            return Err(Error::IllegalArgument("Cannot have empty key IDs".into()));
+        }
+
+        if threshold < 1 {
+            return Err(Error::IllegalArgument("Cannot have threshold < 1".into()));
+        }
+
+        if (key_ids.len() as u64) < u64::from(threshold) {
+            return Err(Error::IllegalArgument(
+                "Cannot have threshold less than number of keys".into(),
+            ));
+        }
+
+        if !(1..=32).contains(&bit_length) {
+            return Err(Error::IllegalArgument(
+                "Bit length must be between 1 and 32".into(),
+            ));
+        }
+
+        let succinct_roles = SuccinctRoles {
+            key_ids,
+            threshold,
+            bit_length,
+            name_prefix,
+        };
+
+        // Every bin name only differs by its hex suffix, so checking the first is enough.
+        let _ = MetadataPath::new(succinct_roles.format_bin_name(0))?;
+
+        Ok(succinct_roles)
+    }
+
+    /// An immutable reference to the key IDs trusted to sign every bin.
+    pub fn key_ids(&self) -> &HashSet<KeyId> {
+        &self.key_ids
+    }
+
+    /// The threshold of every bin.
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    /// The number of bits of the target path hash used to select its bin.
+    pub fn bit_length(&self) -> u8 {
+        self.bit_length
+    }
+
+    /// The prefix of the name of every bin.
+    pub fn name_prefix(&self) -> &str {
+        &self.name_prefix
+    }
+
+    /// The number of bins.
+    pub fn bin_count(&self) -> u64 {
+        1 << self.bit_length
+    }
+
+    /// The name of every bin, in order.
+    pub fn role_names(&self) -> impl Iterator<Item = MetadataPath> + '_ {
+        (0..self.bin_count()).map(move |bin| self.bin_name(bin as u32))
+    }
+
+    /// The name of the bin that `target` is delegated to.
+    ///
+    /// ```
+    /// # use std::collections::HashSet;
+    /// # use tuf::crypto::KeyId;
+    /// # use tuf::metadata::{MetadataPath, SuccinctRoles, TargetPath};
+    /// let key_id: KeyId = "4750eaf6878740780d6f97b12dbad079fb012bec88c78de2c380add56d3f51db"
+    ///     .parse()
+    ///     .unwrap();
+    /// let bins = SuccinctRoles::new(HashSet::from([key_id]), 1, 8, "bins".into()).unwrap();
+    ///
+    /// let role = bins.role_for_target(&TargetPath::new("foo").unwrap());
+    /// assert_eq!(role, MetadataPath::new("bins-2c").unwrap());
+    /// ```
+    pub fn role_for_target(&self, target: &TargetPath) -> MetadataPath {
+        let hash = ring::digest::digest(&ring::digest::SHA256, target.as_str().as_bytes());
+        let mut prefix = [0; 4];
+        prefix.copy_from_slice(&hash.as_ref()[..4]);
+
+        // The bit length is at most 32, so the bin always fits in the first four bytes.
+        let bin = u64::from(u32::from_be_bytes(prefix)) >> (32 - u32::from(self.bit_length));
+
+        self.bin_name(bin as u32)
+    }
+
+    /// Sort `targets` into the bins they are delegated to. Returns a [TargetsMetadataBuilder] for
+    /// every bin, including the bins that no target hashes to, so that each can be signed with the
+    /// keys of the bins and published alongside the delegating targets metadata.
+    pub fn bin_targets<I>(&self, targets: I) -> HashMap<MetadataPath, TargetsMetadataBuilder>
+    where
+        I: IntoIterator<Item = (TargetPath, TargetDescription)>,
+    {
+        let mut bins = self
+            .role_names()
+            .map(|role| (role, TargetsMetadataBuilder::new()))
+            .collect::<HashMap<_, _>>();
+
+        for (path, description) in targets {
+            let role = self.role_for_target(&path);
+            if let Some(builder) = bins.remove(&role) {
+                let _ = bins.insert(role, builder.insert_target_description(path, description));
+            }
+        }
+
+        bins
+    }
+
+    /// Return if `role` is the name of one of the bins.
+    pub fn is_delegated_role(&self, role: &MetadataPath) -> bool {
+        let suffix = match role
+            .as_str()
+            .strip_prefix(self.name_prefix.as_str())
+            .and_then(|rest| rest.strip_prefix('-'))
+        {
+            Some(suffix) => suffix,
+            None => return false,
+        };
+
+        if suffix.len() != self.suffix_len()
+            || !suffix
+                .bytes()
+                .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        {
+            return false;
+        }
+
+        match u64::from_str_radix(suffix, 16) {
+            Ok(bin) => bin < self.bin_count(),
+            Err(_) => false,
+        }
+    }
+
+    /// The delegation to the bin that `target` hashes to. The delegation is only authorized to
+    /// sign `target`.
+    pub(crate) fn delegation_for_target(&self, target: &TargetPath) -> Delegation {
+        let mut delegation = self.delegation(self.role_for_target(target));
+        let _ = delegation.paths.insert(target.clone());
+        delegation
+    }
+
+    /// The delegation to the bin named `role`. Bins are always terminating.
+    fn delegation(&self, role: MetadataPath) -> Delegation {
+        Delegation {
+            name: role,
+            terminating: true,
+            threshold: self.threshold,
+            key_ids: self.key_ids.clone(),
+            paths: HashSet::new(),
+            path_hash_prefixes: HashSet::new(),
+        }
+    }
+
+    fn suffix_len(&self) -> usize {
+        (usize::from(self.bit_length) + 3) / 4
+    }
+
+    fn format_bin_name(&self, bin: u32) -> String {
+        format!(
+            "{}-{:0width$x}",
+            self.name_prefix,
+            bin,
+            width = self.suffix_len()
+        )
+    }
+
+    fn bin_name(&self, bin: u32) -> MetadataPath {
+        MetadataPath(self.format_bin_name(bin).into())
+    }
+}
+
+impl Serialize for SuccinctRoles {
+    fn serialize<S>(&self, ser: S) -> ::std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        shims::SuccinctRoles::from(self).serialize(ser)
+    }
+}
+
+impl<'de> Deserialize<'de> for SuccinctRoles {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> ::std::result::Result<Self, D::Error> {
+        let intermediate: shims::SuccinctRoles = Deserialize::deserialize(de)?;
+        intermediate
+            .try_into()
+            .map_err(|e| DeserializeError::custom(format!("{:?}", e)))
+    }
+}
+
+/// A builder for [SuccinctRoles].
+pub struct SuccinctRolesBuilder {
+    name_prefix: String,
+    bit_length: u8,
+    threshold: u32,
+    key_ids: HashSet<KeyId>,
+}
+
+impl SuccinctRolesBuilder {
+    /// Create a new [SuccinctRolesBuilder] for `2^bit_length` bins named with `name_prefix`.
+    pub fn new(name_prefix: impl Into<String>, bit_length: u8) -> Self {
+        Self {
+            name_prefix: name_prefix.into(),
+            bit_length,
+            threshold: 1,
+            key_ids: HashSet::new(),
+        }
+    }
+
+    /// The threshold number of signatures required for each bin to be trusted.
+    pub fn threshold(mut self, threshold: u32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Every bin can be signed by this [PublicKey].
+    pub fn key(mut self, key: &PublicKey) -> Self {
+        self.key_ids.insert(key.key_id().clone());
+        self
+    }
+
+    /// Every bin can be signed by this [KeyId].
+    pub fn key_id(mut self, key_id: KeyId) -> Self {
+        self.key_ids.insert(key_id);
+        self
+    }
+
+    /// Construct the [SuccinctRoles].
+    pub fn build(self) -> Result<SuccinctRoles> {
+        SuccinctRoles::new(
+            self.key_ids,
+            self.threshold,
+            self.bit_length,
+            self.name_prefix,
         )
     }
 }
@@ -2283,7 +4213,7 @@ mod test {
     use assert_matches::assert_matches;
     use chrono::prelude::*;
     use futures_executor::block_on;
-    use maplit::{hashmap, hashset};
+    use maplit::{btreemap, hashmap, hashset};
     use pretty_assertions::assert_eq;
     use serde_json::json;
     use std::str::FromStr;
@@ -2328,6 +4258,77 @@ mod test {
         }
     }
 
+    #[test]
+    fn path_matching() {
+        let prefix = PathMatching::default();
+        let prefix_insensitive = PathMatching::Prefix {
+            case_sensitive: false,
+        };
+        let glob = PathMatching::Glob {
+            case_sensitive: true,
+        };
+        let glob_insensitive = PathMatching::Glob {
+            case_sensitive: false,
+        };
+        let globstar = PathMatching::GlobStar {
+            case_sensitive: true,
+        };
+
+        let test_cases: &[(PathMatching, &str, &str, bool)] = &[
+            (prefix, "foo", "foo", true),
+            (prefix, "foo/", "foo/bar/baz", true),
+            (prefix, "foo", "foo/bar", false),
+            (prefix, "foo/*", "foo/bar", false),
+            (prefix, "FOO/", "foo/bar", false),
+            (prefix_insensitive, "FOO/", "foo/bar", true),
+            (glob, "foo", "foo", true),
+            (glob, "*", "foo", true),
+            (glob, "*", "foo/bar", false),
+            (glob, "foo/*", "foo/bar", true),
+            (glob, "foo/*", "foo/bar/baz", false),
+            (glob, "*/bar", "foo/bar", true),
+            (glob, "foo/*.tar.gz", "foo/bar.tar.gz", true),
+            (glob, "foo/*.tar.gz", "foo/bar.tgz", false),
+            (glob, "foo-[0-9]", "foo-7", true),
+            (glob, "foo-[0-9]", "foo-x", false),
+            (glob, "foo-[!0-9]", "foo-x", true),
+            (glob, "foo-[^0-9]", "foo-7", false),
+            (glob, "foo-[]]", "foo-]", true),
+            (glob, "foo-[", "foo-[", true),
+            (glob, "foo/", "foo/bar", false),
+            (glob, "foo/**", "foo/bar/baz", false),
+            (glob, "FOO/*", "foo/bar", false),
+            (glob_insensitive, "FOO/*", "foo/BAR", true),
+            (globstar, "foo/**", "foo/bar/baz", true),
+            (globstar, "foo/**/baz", "foo/baz", true),
+            (globstar, "foo/**/baz", "foo/bar/quux/baz", true),
+            (globstar, "foo/**/baz", "foo/bar/quux", false),
+            (globstar, "**/*.img", "foo/bar.img", true),
+        ];
+
+        for (path_matching, pattern, target, expected) in test_cases {
+            let pattern = TargetPath::new(*pattern).unwrap();
+            let target = TargetPath::new(*target).unwrap();
+            assert_eq!(
+                path_matching.matches(&pattern, &target),
+                *expected,
+                "{:?} matching {:?} against {:?}",
+                path_matching,
+                target,
+                pattern
+            );
+        }
+
+        // With any other rules, every delegation on the way to a role must match the target.
+        let parents = [
+            hashset!(TargetPath::new("foo/*").unwrap()),
+            hashset!(TargetPath::new("*/bar").unwrap()),
+        ];
+        assert!(glob.matches_chain(&TargetPath::new("foo/bar").unwrap(), &parents));
+        assert!(!glob.matches_chain(&TargetPath::new("foo/baz").unwrap(), &parents));
+        assert!(!glob.matches_chain(&TargetPath::new("foo/bar").unwrap(), &[]));
+    }
+
     #[test]
     fn path_matches_chain() {
         let test_cases: &[(bool, &str, &[&[&str]])] = &[
@@ -2411,6 +4412,139 @@ mod test {
         assert_eq!(parsed_str, parsed_jsn);
     }
 
+    #[test]
+    fn target_description_from_reader_with_hash_algorithms() {
+        let s: &[u8] = b"from water does all life begin";
+        let hash_algs = [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha384,
+            HashAlgorithm::Sha512,
+        ];
+
+        let description = block_on(TargetDescription::from_reader(s, &hash_algs)).unwrap();
+        assert_eq!(description.length(), Some(s.len() as u64));
+        assert_eq!(description.hashes().len(), hash_algs.len());
+        for alg in &hash_algs {
+            assert_eq!(
+                description.hashes().get(alg),
+                Some(&crypto::calculate_hash(s, alg))
+            );
+        }
+        assert_eq!(
+            description,
+            TargetDescription::from_slice(s, &hash_algs).unwrap()
+        );
+
+        let encoded = serde_json::to_value(&description).unwrap();
+        assert_eq!(encoded["hashes"].as_object().unwrap().len(), 3);
+        assert!(encoded["hashes"].get("sha384").is_some());
+
+        assert_matches!(
+            block_on(TargetDescription::from_reader(
+                s,
+                &[HashAlgorithm::Unknown("md5".into())]
+            )),
+            Err(Error::IllegalArgument(_))
+        );
+    }
+
+    #[test]
+    fn serde_target_description_without_length_and_hashes() {
+        let description = TargetDescription::new(
+            None,
+            HashMap::new(),
+            hashmap! { "foo".into() => json!("bar") },
+        )
+        .unwrap();
+        assert!(!description.is_verifiable());
+
+        let jsn = json!({
+            "custom": {
+                "foo": "bar",
+            },
+        });
+
+        let encoded = serde_json::to_value(&description).unwrap();
+        assert_eq!(encoded, jsn);
+        let decoded: TargetDescription = serde_json::from_value(encoded).unwrap();
+        assert_eq!(decoded, description);
+
+        let description = TargetDescription::new(Some(30), HashMap::new(), HashMap::new()).unwrap();
+        assert!(!description.is_verifiable());
+        let encoded = serde_json::to_value(&description).unwrap();
+        assert_eq!(encoded, json!({ "length": 30 }));
+    }
+
+    #[test]
+    fn typed_custom_target_description() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Custom {
+            version: String,
+            arch: Option<String>,
+            #[serde(rename = "install-path")]
+            install_path: String,
+        }
+
+        let custom = Custom {
+            version: "1.0.0".into(),
+            arch: Some("x86_64".into()),
+            install_path: "/opt/app".into(),
+        };
+        let description = TargetDescription::from_slice(b"app", &[HashAlgorithm::Sha256])
+            .unwrap()
+            .with_typed_custom(&custom)
+            .unwrap();
+        assert_eq!(description.custom()["install-path"], json!("/opt/app"));
+        assert_eq!(description.custom_as::<Custom>().unwrap(), custom);
+        assert_eq!(
+            description.custom_field_as::<String>("arch").unwrap(),
+            Some("x86_64".into())
+        );
+        assert_eq!(
+            description.custom_field_as::<String>("missing").unwrap(),
+            None
+        );
+
+        // Type mismatches are reported with the offending field.
+        assert_matches!(
+            description.custom_field_as::<u32>("version"),
+            Err(Error::InvalidCustomMetadata(msg)) if msg.contains("version")
+        );
+
+        #[derive(Debug, Deserialize)]
+        struct Required {
+            #[allow(dead_code)]
+            hardware_id: String,
+        }
+        assert_matches!(
+            description.custom_as::<Required>(),
+            Err(Error::InvalidCustomMetadata(msg)) if msg.contains("hardware_id")
+        );
+
+        // Custom metadata must be a map.
+        assert_matches!(
+            description.clone().with_typed_custom(&"not a map"),
+            Err(Error::InvalidCustomMetadata(_))
+        );
+
+        let path = TargetPath::new("app").unwrap();
+        let targets = TargetsMetadataBuilder::new()
+            .insert_target_from_slice(path.clone(), b"app", &[HashAlgorithm::Sha256])
+            .unwrap()
+            .insert_typed_custom(&path, &custom)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(targets.targets()[&path], description);
+
+        assert_matches!(
+            TargetsMetadataBuilder::new()
+                .insert_typed_custom(&path, &custom)
+                .map(|_| ()),
+            Err(Error::TargetNotFound(_))
+        );
+    }
+
     #[test]
     fn serde_role_definition() {
         // keyid ordering must be preserved.
@@ -2458,6 +4592,38 @@ mod test {
         assert!(serde_json::from_value::<RoleDefinition<RootMetadata>>(jsn).is_err());
     }
 
+    #[test]
+    fn builder_expiration_policies() {
+        let before = Utc::now();
+        let snapshot = SnapshotMetadataBuilder::new()
+            .expires_in(Duration::days(3))
+            .build()
+            .unwrap();
+        let after = Utc::now();
+        assert!(*snapshot.expires() >= before + Duration::days(3));
+        assert!(*snapshot.expires() <= after + Duration::days(3));
+
+        let targets = TargetsMetadataBuilder::new()
+            .expires_with(&FirstOfMonth::next())
+            .build()
+            .unwrap();
+        assert!(*targets.expires() > before);
+        assert_eq!(targets.expires().day(), 1);
+        assert_eq!(targets.expires().num_seconds_from_midnight(), 0);
+
+        let key = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
+        let expires = Utc.with_ymd_and_hms(2038, 1, 1, 0, 0, 0).unwrap();
+        let root = RootMetadataBuilder::new()
+            .expires_with(&|_: DateTime<Utc>| expires)
+            .root_key(key.public().clone())
+            .snapshot_key(key.public().clone())
+            .targets_key(key.public().clone())
+            .timestamp_key(key.public().clone())
+            .build()
+            .unwrap();
+        assert_eq!(root.expires(), &expires);
+    }
+
     #[test]
     fn serde_root_metadata() {
         let root_key = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
@@ -2689,6 +4855,7 @@ mod test {
             verify_signatures(&MetadataPath::root(), &raw_root, 2, &[root_key.public().clone()]),
             Err(Error::MetadataMissingSignatures {
                 role,
+                trusted_version: None,
                 number_of_valid_signatures: 1,
                 threshold: 2,
             })
@@ -2705,6 +4872,94 @@ mod test {
         );
     }
 
+    #[test]
+    fn signed_metadata_builder_add_signature() {
+        let key_1 = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
+        let key_2 = Ed25519PrivateKey::from_pkcs8(ED25519_2_PK8).unwrap();
+
+        let snapshot = SnapshotMetadataBuilder::new().build().unwrap();
+        let builder = SignedMetadataBuilder::<Pouf1, _>::from_metadata(&snapshot).unwrap();
+        let bytes = canonical_bytes::<Pouf1, _>(&snapshot).unwrap();
+        assert_eq!(builder.canonical_bytes(), &bytes[..]);
+
+        // A signature made elsewhere over the canonical bytes is attached.
+        let sig = key_2.sign(&bytes).unwrap();
+        let raw_snapshot = builder
+            .sign(&key_1)
+            .unwrap()
+            .add_signature(key_2.public(), sig.value().as_bytes())
+            .unwrap()
+            .build()
+            .to_raw()
+            .unwrap();
+        assert_matches!(
+            verify_signatures(
+                &MetadataPath::snapshot(),
+                &raw_snapshot,
+                2,
+                vec![key_1.public(), key_2.public()]
+            ),
+            Ok(_)
+        );
+
+        // A signature from another key, or over other bytes, is rejected.
+        assert_matches!(
+            SignedMetadataBuilder::<Pouf1, _>::from_metadata(&snapshot)
+                .unwrap()
+                .add_signature(key_1.public(), sig.value().as_bytes()),
+            Err(Error::BadSignature(role)) if role == MetadataPath::snapshot()
+        );
+        let other_sig = key_2.sign(b"not the metadata").unwrap();
+        assert_matches!(
+            SignedMetadataBuilder::<Pouf1, _>::from_metadata(&snapshot)
+                .unwrap()
+                .add_signature(key_2.public(), other_sig.value().as_bytes()),
+            Err(Error::BadSignature(_))
+        );
+    }
+
+    #[test]
+    fn signed_metadata_merge_signatures() {
+        let key_1 = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
+        let key_2 = Ed25519PrivateKey::from_pkcs8(ED25519_2_PK8).unwrap();
+        let key_3 = Ed25519PrivateKey::from_pkcs8(ED25519_3_PK8).unwrap();
+
+        let snapshot = SnapshotMetadataBuilder::new().build().unwrap();
+
+        let mut signed = SignedMetadata::<Pouf1, _>::new(&snapshot, &key_1).unwrap();
+        let mut other = SignedMetadata::<Pouf1, _>::new(&snapshot, &key_2).unwrap();
+        other.add_signature(&key_3).unwrap();
+
+        // A signature from the same key is only kept once.
+        let mut duplicate = SignedMetadata::<Pouf1, _>::new(&snapshot, &key_2).unwrap();
+        duplicate.signatures.push(duplicate.signatures[0].clone());
+
+        signed.merge_signatures(&duplicate).unwrap();
+        assert_eq!(signed.signatures().len(), 2);
+
+        signed.merge_signatures(&other).unwrap();
+        assert_eq!(signed.signatures().len(), 3);
+
+        assert_matches!(
+            verify_signatures(
+                &MetadataPath::snapshot(),
+                &signed.to_raw().unwrap(),
+                3,
+                vec![key_1.public(), key_2.public(), key_3.public()]
+            ),
+            Ok(_)
+        );
+
+        // Copies with different payloads cannot be merged.
+        let other_snapshot = SnapshotMetadataBuilder::new().version(2).build().unwrap();
+        let other = SignedMetadata::<Pouf1, _>::new(&other_snapshot, &key_2).unwrap();
+        assert_matches!(
+            signed.merge_signatures(&other),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_eq!(signed.signatures().len(), 3);
+    }
+
     fn verify_signature_with_unknown_fields<M>(mut metadata: serde_json::Value)
     where
         M: Metadata,
@@ -2769,7 +5024,12 @@ mod test {
                 1,
                 std::iter::once(key.public())
             ),
-            Err(Error::MetadataMissingSignatures { role, number_of_valid_signatures: 0, threshold: 1 })
+            Err(Error::MetadataMissingSignatures {
+                role,
+                trusted_version: None,
+                number_of_valid_signatures: 0,
+                threshold: 1,
+            })
             if role == M::ROLE.into()
         );
         assert_matches!(
@@ -2779,7 +5039,12 @@ mod test {
                 1,
                 std::iter::once(key.public())
             ),
-            Err(Error::MetadataMissingSignatures { role, number_of_valid_signatures: 0, threshold: 1 })
+            Err(Error::MetadataMissingSignatures {
+                role,
+                trusted_version: None,
+                number_of_valid_signatures: 0,
+                threshold: 1,
+            })
             if role == M::ROLE.into()
         );
     }
@@ -2842,6 +5107,113 @@ mod test {
         assert_eq!(decoded, timestamp);
     }
 
+    #[test]
+    fn serde_custom_role() {
+        let mirrors = MetadataPath::new("mirrors").unwrap();
+        let mirrors_key = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
+
+        let custom = CustomMetadataBuilder::new(mirrors.clone())
+            .expires(Utc.with_ymd_and_hms(2017, 1, 1, 0, 0, 0).unwrap())
+            .field("mirrors", json!([{ "urlbase": "https://example.com" }]))
+            .build()
+            .unwrap();
+
+        let jsn = json!({
+            "_type": "mirrors",
+            "spec_version": "1.0",
+            "version": 1,
+            "expires": "2017-01-01T00:00:00Z",
+            "mirrors": [{ "urlbase": "https://example.com" }],
+        });
+
+        let encoded = serde_json::to_value(&custom).unwrap();
+        assert_eq!(encoded, jsn);
+        let decoded: CustomMetadata = serde_json::from_value(encoded).unwrap();
+        assert_eq!(decoded, custom);
+
+        let mut jsn = jsn;
+        jsn["_type"] = json!("timestamp");
+        assert!(serde_json::from_value::<CustomMetadata>(jsn).is_err());
+
+        let root = RootMetadataBuilder::new()
+            .expires(Utc.with_ymd_and_hms(2017, 1, 1, 0, 0, 0).unwrap())
+            .root_key(mirrors_key.public().clone())
+            .snapshot_key(mirrors_key.public().clone())
+            .targets_key(mirrors_key.public().clone())
+            .timestamp_key(mirrors_key.public().clone())
+            .custom_role_key(mirrors.clone(), mirrors_key.public().clone())
+            .build()
+            .unwrap();
+
+        let encoded = serde_json::to_value(&root).unwrap();
+        assert_eq!(
+            encoded["roles"]["mirrors"],
+            json!({
+                "threshold": 1,
+                "keyids": [mirrors_key.public().key_id()],
+            })
+        );
+        let decoded: RootMetadata = serde_json::from_value(encoded).unwrap();
+        assert_eq!(decoded, root);
+        assert_eq!(
+            decoded.custom_role_keys(&mirrors).collect::<Vec<_>>(),
+            vec![mirrors_key.public()]
+        );
+
+        assert_matches!(
+            RootMetadataBuilder::new()
+                .custom_role_key(MetadataPath::targets(), mirrors_key.public().clone())
+                .build(),
+            Err(Error::IllegalArgument(_))
+        );
+    }
+
+    #[test]
+    fn root_reused_key_ids() {
+        let key_1 = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
+        let key_2 = Ed25519PrivateKey::from_pkcs8(ED25519_2_PK8).unwrap();
+        let key_3 = Ed25519PrivateKey::from_pkcs8(ED25519_3_PK8).unwrap();
+
+        let builder = || {
+            RootMetadataBuilder::new()
+                .root_key(key_1.public().clone())
+                .timestamp_key(key_2.public().clone())
+                .snapshot_key(key_2.public().clone())
+                .targets_key(key_3.public().clone())
+        };
+
+        let root = builder().build().unwrap();
+        assert_eq!(
+            root.reused_key_ids(),
+            btreemap! {
+                key_2.public().key_id().clone() =>
+                    vec![MetadataPath::timestamp(), MetadataPath::snapshot()],
+            }
+        );
+
+        assert_matches!(
+            builder().forbid_key_reuse(true).build(),
+            Err(Error::KeyReuse { key_id, roles })
+            if &key_id == key_2.public().key_id()
+                && roles == vec![MetadataPath::timestamp(), MetadataPath::snapshot()]
+        );
+
+        let root = RootMetadataBuilder::new()
+            .root_key(key_1.public().clone())
+            .timestamp_key(key_2.public().clone())
+            .snapshot_key(key_3.public().clone())
+            .targets_key(
+                Ed25519PrivateKey::from_pkcs8(ED25519_4_PK8)
+                    .unwrap()
+                    .public()
+                    .clone(),
+            )
+            .forbid_key_reuse(true)
+            .build()
+            .unwrap();
+        assert!(root.reused_key_ids().is_empty());
+    }
+
     // Deserialize timestamp metadata with optional length and hashes
     #[test]
     fn serde_timestamp_metadata_without_length_and_hashes() {
@@ -2956,6 +5328,35 @@ mod test {
         assert_eq!(decoded, snapshot);
     }
 
+    #[test]
+    fn serde_snapshot_metadata_namespaced_role() {
+        let role = MetadataPath::new("projects/foo/bin-07").unwrap();
+        let snapshot = SnapshotMetadataBuilder::new()
+            .expires(Utc.with_ymd_and_hms(2017, 1, 1, 0, 0, 0).unwrap())
+            .insert_metadata_description(
+                role.clone(),
+                MetadataDescription::new(3, None, HashMap::new()).unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        let encoded = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(
+            encoded["meta"],
+            json!({ "projects/foo/bin-07.json": { "version": 3 } })
+        );
+        let decoded: SnapshotMetadata = serde_json::from_value(encoded).unwrap();
+        assert_eq!(decoded, snapshot);
+        assert_eq!(decoded.meta()[&role].version(), 3);
+
+        assert_eq!(
+            MetadataPath::from_escaped("projects%2Ffoo%25%2fbin-07").unwrap(),
+            MetadataPath::new("projects/foo%/bin-07").unwrap()
+        );
+        assert!(MetadataPath::from_escaped("projects%2").is_err());
+        assert!(MetadataPath::from_escaped("projects%2F..%2Fbin").is_err());
+    }
+
     // Deserialize snapshot metadata with optional length and hashes
     #[test]
     fn serde_snapshot_optional_length_and_hashes() {
@@ -2986,6 +5387,56 @@ mod test {
         assert_eq!(decoded, snapshot);
     }
 
+    // Fields this crate does not recognize are preserved across a round-trip.
+    #[test]
+    fn serde_unrecognized_fields() {
+        fn add_unrecognized_fields(mut jsn: serde_json::Value) -> serde_json::Value {
+            let obj = jsn.as_object_mut().unwrap();
+            let _ = obj.insert("x-future-field".into(), json!({ "foo": [1, 2, 3] }));
+            let _ = obj.insert("x-other-field".into(), json!("bar"));
+            jsn
+        }
+
+        fn check<M: Metadata>(jsn: serde_json::Value) {
+            let jsn = add_unrecognized_fields(jsn);
+            let decoded: M = serde_json::from_value(jsn.clone()).unwrap();
+            assert_eq!(serde_json::to_value(&decoded).unwrap(), jsn);
+        }
+
+        check::<RootMetadata>(make_root());
+        check::<SnapshotMetadata>(make_snapshot());
+        check::<TimestampMetadata>(make_timestamp());
+        check::<TargetsMetadata>(make_targets());
+
+        let root: RootMetadata =
+            serde_json::from_value(add_unrecognized_fields(make_root())).unwrap();
+        assert_eq!(
+            root.unrecognized_fields(),
+            &hashmap! {
+                "x-future-field".into() => json!({ "foo": [1, 2, 3] }),
+                "x-other-field".into() => json!("bar"),
+            }
+        );
+
+        let targets: TargetsMetadata = serde_json::from_value(make_targets()).unwrap();
+        assert!(targets.unrecognized_fields().is_empty());
+    }
+
+    // Any minor version of the spec is parsed, and is preserved across a round-trip.
+    #[test]
+    fn serde_spec_version() {
+        let mut jsn = make_targets();
+        assert_eq!(jsn["spec_version"], json!("1.0"));
+        let _ = jsn
+            .as_object_mut()
+            .unwrap()
+            .insert("spec_version".into(), json!("1.1.0"));
+
+        let targets: TargetsMetadata = serde_json::from_value(jsn.clone()).unwrap();
+        assert_eq!(targets.spec_version(), &SpecVersion::new(1, 1, 0));
+        assert_eq!(serde_json::to_value(&targets).unwrap(), jsn);
+    }
+
     #[test]
     fn serde_targets_metadata() {
         block_on(async {
@@ -3135,6 +5586,350 @@ mod test {
         assert_eq!(decoded, targets);
     }
 
+    #[test]
+    fn serde_succinct_delegations() {
+        let key = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
+        let delegations = Delegations::builder()
+            .key(key.public().clone())
+            .succinct_roles(
+                SuccinctRoles::new(hashset!(key.public().key_id().clone()), 1, 8, "bins".into())
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        let jsn = json!({
+            "keys": {
+                "a9f3ebc9b138762563a9c27b6edd439959e559709babd123e8d449ba2c18c61a": {
+                    "keytype": "ed25519",
+                    "scheme": "ed25519",
+                    "keyid_hash_algorithms": ["sha256", "sha512"],
+                    "keyval": {
+                        "public": "eb8ac26b5c9ef0279e3be3e82262a93bce16fe58\
+                            ee422500d38caf461c65a3b6",
+                    }
+                },
+            },
+            "succinct_roles": {
+                "keyids": ["a9f3ebc9b138762563a9c27b6edd439959e559709babd123e8d449ba2c18c61a"],
+                "threshold": 1,
+                "bit_length": 8,
+                "name_prefix": "bins",
+            },
+        });
+
+        let encoded = serde_json::to_value(&delegations).unwrap();
+        assert_eq!(encoded, jsn);
+        let decoded: Delegations = serde_json::from_value(encoded).unwrap();
+        assert_eq!(decoded, delegations);
+
+        // Delegations can't mix explicit roles with succinct roles.
+        let mut jsn = jsn;
+        jsn.as_object_mut()
+            .unwrap()
+            .insert("roles".into(), json!([]));
+        assert!(serde_json::from_value::<Delegations>(jsn).is_err());
+    }
+
+    #[test]
+    fn serde_path_hash_prefix_delegation() {
+        let key = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
+        let delegation = Delegation::builder(MetadataPath::new("bins-0-7").unwrap())
+            .key(key.public())
+            .delegate_path_hash_prefix("2")
+            .delegate_path_hash_prefix("0")
+            .build()
+            .unwrap();
+
+        let jsn = json!({
+            "name": "bins-0-7",
+            "terminating": false,
+            "threshold": 1,
+            "keyids": ["a9f3ebc9b138762563a9c27b6edd439959e559709babd123e8d449ba2c18c61a"],
+            "path_hash_prefixes": ["0", "2"],
+        });
+
+        let encoded = serde_json::to_value(&delegation).unwrap();
+        assert_eq!(encoded, jsn);
+        let decoded: Delegation = serde_json::from_value(encoded).unwrap();
+        assert_eq!(decoded, delegation);
+
+        // sha256("bar") starts with 0xfc, and sha256("qux") with 0x21.
+        assert!(!delegation.matches_target(&TargetPath::new("bar").unwrap()));
+        assert!(delegation.matches_target(&TargetPath::new("qux").unwrap()));
+
+        // Delegations can't have both paths and path hash prefixes.
+        let mut both = jsn.clone();
+        both.as_object_mut()
+            .unwrap()
+            .insert("paths".into(), json!(["foo"]));
+        assert!(serde_json::from_value::<Delegation>(both).is_err());
+
+        let mut neither = jsn;
+        neither
+            .as_object_mut()
+            .unwrap()
+            .remove("path_hash_prefixes");
+        assert!(serde_json::from_value::<Delegation>(neither).is_err());
+
+        assert_matches!(
+            Delegation::builder(MetadataPath::new("bins").unwrap())
+                .key(key.public())
+                .delegate_path(TargetPath::new("foo").unwrap())
+                .delegate_path_hash_prefix("0")
+                .build(),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            Delegation::builder(MetadataPath::new("bins").unwrap())
+                .key(key.public())
+                .delegate_path_hash_prefix("0A")
+                .build(),
+            Err(Error::IllegalArgument(_))
+        );
+    }
+
+    #[test]
+    fn serde_multi_role_delegations() {
+        let key = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
+        let role = |name| {
+            Delegation::builder(MetadataPath::new(name).unwrap())
+                .key(key.public())
+                .delegate_path(TargetPath::new("prod/").unwrap())
+                .build()
+                .unwrap()
+        };
+        let multi_role = MultiRoleDelegation::new(2, vec![role("dev"), role("release")]).unwrap();
+        let delegations = Delegations::builder()
+            .key(key.public().clone())
+            .role(role("qa"))
+            .multi_role(multi_role.clone())
+            .build()
+            .unwrap();
+
+        let encoded = serde_json::to_value(&delegations).unwrap();
+        assert_eq!(
+            encoded["roles"],
+            json!([
+                {
+                    "name": "qa",
+                    "terminating": false,
+                    "threshold": 1,
+                    "keyids": ["a9f3ebc9b138762563a9c27b6edd439959e559709babd123e8d449ba2c18c61a"],
+                    "paths": ["prod/"],
+                },
+                {
+                    "min_roles_in_agreement": 2,
+                    "terminating": false,
+                    "paths": ["prod/"],
+                    "roles": [
+                        {
+                            "name": "dev",
+                            "keyids": [
+                                "a9f3ebc9b138762563a9c27b6edd439959e559709babd123e8d449ba2c18c61a"
+                            ],
+                            "threshold": 1,
+                        },
+                        {
+                            "name": "release",
+                            "keyids": [
+                                "a9f3ebc9b138762563a9c27b6edd439959e559709babd123e8d449ba2c18c61a"
+                            ],
+                            "threshold": 1,
+                        },
+                    ],
+                },
+            ])
+        );
+        let decoded: Delegations = serde_json::from_value(encoded).unwrap();
+        assert_eq!(decoded, delegations);
+        assert_eq!(
+            decoded.multi_role_delegations().collect::<Vec<_>>(),
+            vec![&multi_role]
+        );
+        assert_eq!(
+            decoded
+                .delegation(&MetadataPath::new("release").unwrap())
+                .map(Cow::into_owned),
+            Some(role("release"))
+        );
+
+        assert_matches!(
+            MultiRoleDelegation::new(0, vec![role("dev")]),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            MultiRoleDelegation::new(2, vec![role("dev")]),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            MultiRoleDelegation::new(2, vec![role("dev"), role("dev")]),
+            Err(Error::IllegalArgument(_))
+        );
+
+        // Every role must be authorized to sign the same targets.
+        let other = Delegation::builder(MetadataPath::new("release").unwrap())
+            .key(key.public())
+            .delegate_path(TargetPath::new("test/").unwrap())
+            .build()
+            .unwrap();
+        assert_matches!(
+            MultiRoleDelegation::new(2, vec![role("dev"), other]),
+            Err(Error::IllegalArgument(_))
+        );
+
+        // Role names must be unique across all delegations.
+        assert_matches!(
+            Delegations::builder()
+                .key(key.public().clone())
+                .role(role("dev"))
+                .multi_role(MultiRoleDelegation::new(1, vec![role("dev")]).unwrap())
+                .build(),
+            Err(Error::IllegalArgument(_))
+        );
+    }
+
+    #[test]
+    fn serde_delegations_keep_order() {
+        let key = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
+        let role = |name| {
+            Delegation::builder(MetadataPath::new(name).unwrap())
+                .key(key.public())
+                .delegate_path(TargetPath::new("prod/").unwrap())
+                .build()
+                .unwrap()
+        };
+        let multi_role = MultiRoleDelegation::new(2, vec![role("dev"), role("release")]).unwrap();
+        let delegations = Delegations::builder()
+            .key(key.public().clone())
+            .role(role("qa"))
+            .multi_role(multi_role.clone())
+            .role(role("archive"))
+            .build()
+            .unwrap();
+
+        let expected = [
+            DelegationEntry::Single(role("qa")),
+            DelegationEntry::Multi(multi_role),
+            DelegationEntry::Single(role("archive")),
+        ];
+        assert_eq!(delegations.entries(), &expected[..]);
+
+        // The multi-role delegation stays between the single roles it was listed between.
+        let encoded = serde_json::to_value(&delegations).unwrap();
+        let names = encoded["roles"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|role| role.get("name").and_then(|name| name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![Some("qa"), None, Some("archive")]);
+
+        let decoded: Delegations = serde_json::from_value(encoded).unwrap();
+        assert_eq!(decoded.entries(), &expected[..]);
+    }
+
+    #[test]
+    fn build_succinct_delegations() {
+        let key = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
+        let succinct_roles = SuccinctRoles::builder("bins", 2)
+            .key(key.public())
+            .build()
+            .unwrap();
+
+        // The keys of the bins must be included in the delegations.
+        assert_matches!(
+            Delegations::builder()
+                .succinct_roles(succinct_roles.clone())
+                .build(),
+            Err(Error::IllegalArgument(_))
+        );
+
+        // Succinct roles can't be mixed with explicit roles.
+        assert_matches!(
+            Delegations::builder()
+                .key(key.public().clone())
+                .role(
+                    Delegation::builder(MetadataPath::new("foo").unwrap())
+                        .key(key.public())
+                        .delegate_path(TargetPath::new("foo/").unwrap())
+                        .build()
+                        .unwrap()
+                )
+                .succinct_roles(succinct_roles.clone())
+                .build(),
+            Err(Error::IllegalArgument(_))
+        );
+
+        let targets = TargetsMetadataBuilder::new()
+            .delegations(
+                Delegations::builder()
+                    .key(key.public().clone())
+                    .succinct_roles(succinct_roles.clone())
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+        assert_eq!(
+            targets.delegations().succinct_roles(),
+            Some(&succinct_roles)
+        );
+
+        let foo = TargetPath::new("foo").unwrap();
+        let bar = TargetPath::new("bar").unwrap();
+        let description = TargetDescription::from_slice(b"", &[HashAlgorithm::Sha256]).unwrap();
+        let bins = succinct_roles.bin_targets(vec![
+            (foo.clone(), description.clone()),
+            (bar.clone(), description),
+        ]);
+        assert_eq!(bins.len(), 4);
+
+        for (role, builder) in bins {
+            let bin = builder.build().unwrap();
+            let expected = [&foo, &bar]
+                .into_iter()
+                .filter(|path| succinct_roles.role_for_target(path) == role)
+                .collect::<HashSet<_>>();
+            assert_eq!(bin.targets().keys().collect::<HashSet<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn succinct_roles_bins() {
+        let key = Ed25519PrivateKey::from_pkcs8(ED25519_1_PK8).unwrap();
+        let key_ids = hashset!(key.public().key_id().clone());
+        let target = TargetPath::new("foo").unwrap();
+
+        // sha256("foo") starts with 0x2c26.
+        for (bit_length, bin) in [
+            (1, "bins-0"),
+            (4, "bins-2"),
+            (8, "bins-2c"),
+            (10, "bins-0b0"),
+        ] {
+            let bins = SuccinctRoles::new(key_ids.clone(), 1, bit_length, "bins".into()).unwrap();
+            let role = bins.role_for_target(&target);
+            assert_eq!(role, MetadataPath::new(bin).unwrap());
+            assert!(bins.is_delegated_role(&role));
+            assert_eq!(bins.role_names().count() as u64, bins.bin_count());
+        }
+
+        let bins = SuccinctRoles::new(key_ids.clone(), 1, 4, "bins".into()).unwrap();
+        assert_eq!(
+            bins.role_names().collect::<Vec<_>>().last(),
+            Some(&MetadataPath::new("bins-f").unwrap())
+        );
+        for role in ["bins-10", "bins-F", "bins", "bins-", "other-1"] {
+            assert!(!bins.is_delegated_role(&MetadataPath::new(role).unwrap()));
+        }
+
+        assert!(SuccinctRoles::new(key_ids.clone(), 1, 0, "bins".into()).is_err());
+        assert!(SuccinctRoles::new(key_ids.clone(), 1, 33, "bins".into()).is_err());
+        assert!(SuccinctRoles::new(key_ids.clone(), 2, 8, "bins".into()).is_err());
+        assert!(SuccinctRoles::new(key_ids, 1, 8, "../bins".into()).is_err());
+    }
+
     #[test]
     fn serde_signed_metadata() {
         let snapshot = SnapshotMetadataBuilder::new()
@@ -0,0 +1,657 @@
+//! Comparing two versions of the same metadata.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::crypto::{KeyId, PublicKey};
+use crate::metadata::{
+    Delegation, Metadata, MetadataDescription, MetadataPath, RoleDefinition, RootMetadata,
+    SnapshotMetadata, TargetDescription, TargetPath, TargetsMetadata,
+};
+
+/// A value that differs between two versions of a metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change<T> {
+    before: T,
+    after: T,
+}
+
+impl<T> Change<T> {
+    /// The value in the old metadata.
+    pub fn before(&self) -> &T {
+        &self.before
+    }
+
+    /// The value in the new metadata.
+    pub fn after(&self) -> &T {
+        &self.after
+    }
+}
+
+impl Change<Delegation> {
+    /// The changes to the keys and threshold of the delegated role.
+    pub fn role_diff(&self) -> RoleDiff {
+        RoleDiff::new(
+            self.before.threshold(),
+            self.before.key_ids(),
+            self.after.threshold(),
+            self.after.key_ids(),
+        )
+    }
+}
+
+fn change<T: PartialEq>(before: T, after: T) -> Option<Change<T>> {
+    if before == after {
+        None
+    } else {
+        Some(Change { before, after })
+    }
+}
+
+/// The entries added to, removed from, and changed in a map.
+struct MapDiff<K, V> {
+    added: HashMap<K, V>,
+    removed: HashMap<K, V>,
+    changed: HashMap<K, Change<V>>,
+}
+
+impl<K, V> MapDiff<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone + PartialEq,
+{
+    fn new(before: &HashMap<K, V>, after: &HashMap<K, V>) -> Self {
+        let removed = before
+            .iter()
+            .filter(|(key, _)| !after.contains_key(*key))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        let mut added = HashMap::new();
+        let mut changed = HashMap::new();
+        for (key, value) in after {
+            match before.get(key) {
+                None => {
+                    let _ = added.insert(key.clone(), value.clone());
+                }
+                Some(old) => {
+                    if let Some(c) = change(old.clone(), value.clone()) {
+                        let _ = changed.insert(key.clone(), c);
+                    }
+                }
+            }
+        }
+
+        MapDiff {
+            added,
+            removed,
+            changed,
+        }
+    }
+}
+
+/// The changes to the keys and threshold of a role.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleDiff {
+    threshold: Option<Change<u32>>,
+    added_key_ids: HashSet<KeyId>,
+    removed_key_ids: HashSet<KeyId>,
+}
+
+impl RoleDiff {
+    fn new(
+        threshold_before: u32,
+        key_ids_before: &HashSet<KeyId>,
+        threshold_after: u32,
+        key_ids_after: &HashSet<KeyId>,
+    ) -> Self {
+        RoleDiff {
+            threshold: change(threshold_before, threshold_after),
+            added_key_ids: key_ids_after.difference(key_ids_before).cloned().collect(),
+            removed_key_ids: key_ids_before.difference(key_ids_after).cloned().collect(),
+        }
+    }
+
+    fn from_definitions<M: Metadata>(
+        before: &RoleDefinition<M>,
+        after: &RoleDefinition<M>,
+    ) -> Self {
+        RoleDiff::new(
+            before.threshold(),
+            before.key_ids(),
+            after.threshold(),
+            after.key_ids(),
+        )
+    }
+
+    /// The change of the threshold, if it changed.
+    pub fn threshold(&self) -> Option<&Change<u32>> {
+        self.threshold.as_ref()
+    }
+
+    /// The IDs of the keys that are now trusted for the role.
+    pub fn added_key_ids(&self) -> &HashSet<KeyId> {
+        &self.added_key_ids
+    }
+
+    /// The IDs of the keys that are no longer trusted for the role.
+    pub fn removed_key_ids(&self) -> &HashSet<KeyId> {
+        &self.removed_key_ids
+    }
+
+    /// Whether the keys and threshold of the role are unchanged.
+    pub fn is_empty(&self) -> bool {
+        self.threshold.is_none() && self.added_key_ids.is_empty() && self.removed_key_ids.is_empty()
+    }
+}
+
+/// The changes between two versions of a [RootMetadata]. See [diff_root].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootDiff {
+    version: Change<u32>,
+    consistent_snapshot: Option<Change<bool>>,
+    added_keys: HashMap<KeyId, PublicKey>,
+    removed_keys: HashMap<KeyId, PublicKey>,
+    root: RoleDiff,
+    snapshot: RoleDiff,
+    targets: RoleDiff,
+    timestamp: RoleDiff,
+}
+
+impl RootDiff {
+    /// The versions of the two metadata.
+    pub fn version(&self) -> &Change<u32> {
+        &self.version
+    }
+
+    /// The change of the consistent snapshot flag, if it changed.
+    pub fn consistent_snapshot(&self) -> Option<&Change<bool>> {
+        self.consistent_snapshot.as_ref()
+    }
+
+    /// The keys that were added.
+    pub fn added_keys(&self) -> &HashMap<KeyId, PublicKey> {
+        &self.added_keys
+    }
+
+    /// The keys that were removed.
+    pub fn removed_keys(&self) -> &HashMap<KeyId, PublicKey> {
+        &self.removed_keys
+    }
+
+    /// The changes to the root role.
+    pub fn root(&self) -> &RoleDiff {
+        &self.root
+    }
+
+    /// The changes to the snapshot role.
+    pub fn snapshot(&self) -> &RoleDiff {
+        &self.snapshot
+    }
+
+    /// The changes to the targets role.
+    pub fn targets(&self) -> &RoleDiff {
+        &self.targets
+    }
+
+    /// The changes to the timestamp role.
+    pub fn timestamp(&self) -> &RoleDiff {
+        &self.timestamp
+    }
+
+    /// Whether nothing but the version and expiration changed.
+    pub fn is_empty(&self) -> bool {
+        self.consistent_snapshot.is_none()
+            && self.added_keys.is_empty()
+            && self.removed_keys.is_empty()
+            && self.root.is_empty()
+            && self.snapshot.is_empty()
+            && self.targets.is_empty()
+            && self.timestamp.is_empty()
+    }
+}
+
+/// The changes between two versions of a [SnapshotMetadata]. See [diff_snapshot].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    version: Change<u32>,
+    added_meta: HashMap<MetadataPath, MetadataDescription<TargetsMetadata>>,
+    removed_meta: HashMap<MetadataPath, MetadataDescription<TargetsMetadata>>,
+    changed_meta: HashMap<MetadataPath, Change<MetadataDescription<TargetsMetadata>>>,
+}
+
+impl SnapshotDiff {
+    /// The versions of the two metadata.
+    pub fn version(&self) -> &Change<u32> {
+        &self.version
+    }
+
+    /// The targets metadata that were added.
+    pub fn added_meta(&self) -> &HashMap<MetadataPath, MetadataDescription<TargetsMetadata>> {
+        &self.added_meta
+    }
+
+    /// The targets metadata that were removed.
+    pub fn removed_meta(&self) -> &HashMap<MetadataPath, MetadataDescription<TargetsMetadata>> {
+        &self.removed_meta
+    }
+
+    /// The targets metadata whose description changed, such as by being given a new version.
+    pub fn changed_meta(
+        &self,
+    ) -> &HashMap<MetadataPath, Change<MetadataDescription<TargetsMetadata>>> {
+        &self.changed_meta
+    }
+
+    /// Whether nothing but the version and expiration changed.
+    pub fn is_empty(&self) -> bool {
+        self.added_meta.is_empty() && self.removed_meta.is_empty() && self.changed_meta.is_empty()
+    }
+}
+
+/// The changes between two versions of a [TargetsMetadata]. See [diff].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetsDiff {
+    version: Change<u32>,
+    added_targets: HashMap<TargetPath, TargetDescription>,
+    removed_targets: HashMap<TargetPath, TargetDescription>,
+    changed_targets: HashMap<TargetPath, Change<TargetDescription>>,
+    added_keys: HashMap<KeyId, PublicKey>,
+    removed_keys: HashMap<KeyId, PublicKey>,
+    added_roles: HashMap<MetadataPath, Delegation>,
+    removed_roles: HashMap<MetadataPath, Delegation>,
+    changed_roles: HashMap<MetadataPath, Change<Delegation>>,
+    succinct_roles_changed: bool,
+}
+
+impl TargetsDiff {
+    /// The versions of the two metadata.
+    pub fn version(&self) -> &Change<u32> {
+        &self.version
+    }
+
+    /// The targets that were added.
+    pub fn added_targets(&self) -> &HashMap<TargetPath, TargetDescription> {
+        &self.added_targets
+    }
+
+    /// The targets that were removed.
+    pub fn removed_targets(&self) -> &HashMap<TargetPath, TargetDescription> {
+        &self.removed_targets
+    }
+
+    /// The targets whose description changed.
+    pub fn changed_targets(&self) -> &HashMap<TargetPath, Change<TargetDescription>> {
+        &self.changed_targets
+    }
+
+    /// The delegation keys that were added.
+    pub fn added_keys(&self) -> &HashMap<KeyId, PublicKey> {
+        &self.added_keys
+    }
+
+    /// The delegation keys that were removed.
+    pub fn removed_keys(&self) -> &HashMap<KeyId, PublicKey> {
+        &self.removed_keys
+    }
+
+    /// The roles that are newly delegated to, including the roles of multi-role delegations.
+    pub fn added_roles(&self) -> &HashMap<MetadataPath, Delegation> {
+        &self.added_roles
+    }
+
+    /// The roles that are no longer delegated to, including the roles of multi-role delegations.
+    pub fn removed_roles(&self) -> &HashMap<MetadataPath, Delegation> {
+        &self.removed_roles
+    }
+
+    /// The roles whose delegation changed, such as by trusting other keys or targets. See
+    /// [Change::role_diff].
+    pub fn changed_roles(&self) -> &HashMap<MetadataPath, Change<Delegation>> {
+        &self.changed_roles
+    }
+
+    /// Whether the succinct hash bin delegations changed. The bins themselves are not listed in
+    /// [TargetsDiff::added_roles] and [TargetsDiff::removed_roles].
+    pub fn succinct_roles_changed(&self) -> bool {
+        self.succinct_roles_changed
+    }
+
+    /// Whether nothing but the version and expiration changed.
+    pub fn is_empty(&self) -> bool {
+        self.added_targets.is_empty()
+            && self.removed_targets.is_empty()
+            && self.changed_targets.is_empty()
+            && self.added_keys.is_empty()
+            && self.removed_keys.is_empty()
+            && self.added_roles.is_empty()
+            && self.removed_roles.is_empty()
+            && self.changed_roles.is_empty()
+            && !self.succinct_roles_changed
+    }
+}
+
+/// Enumerate the targets and delegations that changed between the `old` and `new` versions of a
+/// targets metadata.
+///
+/// This can be used to act on exactly what changed after
+/// [Client::update](crate::client::Client::update), by comparing the trusted targets metadata
+/// before and after the update.
+///
+/// ```
+/// # use tuf::crypto::HashAlgorithm;
+/// # use tuf::metadata::{self, TargetPath, TargetsMetadataBuilder};
+/// let foo = TargetPath::new("foo").unwrap();
+/// let old = TargetsMetadataBuilder::new().version(1).build().unwrap();
+/// let new = TargetsMetadataBuilder::new()
+///     .version(2)
+///     .insert_target_from_slice(foo.clone(), b"foo", &[HashAlgorithm::Sha256])
+///     .unwrap()
+///     .build()
+///     .unwrap();
+///
+/// let diff = metadata::diff(&old, &new);
+/// assert_eq!(*diff.version().after(), 2);
+/// assert!(diff.added_targets().contains_key(&foo));
+/// assert!(diff.removed_targets().is_empty());
+/// ```
+pub fn diff(old: &TargetsMetadata, new: &TargetsMetadata) -> TargetsDiff {
+    let targets = MapDiff::new(old.targets(), new.targets());
+    let keys = MapDiff::new(old.delegations().keys(), new.delegations().keys());
+
+    let delegated_roles = |targets: &TargetsMetadata| {
+        targets
+            .delegations()
+            .role_names()
+            .filter_map(|role| {
+                targets
+                    .delegations()
+                    .delegation(role)
+                    .map(|delegation| (role.clone(), delegation.into_owned()))
+            })
+            .collect::<HashMap<_, _>>()
+    };
+    let roles = MapDiff::new(&delegated_roles(old), &delegated_roles(new));
+
+    TargetsDiff {
+        version: Change {
+            before: old.version(),
+            after: new.version(),
+        },
+        added_targets: targets.added,
+        removed_targets: targets.removed,
+        changed_targets: targets.changed,
+        added_keys: keys.added,
+        removed_keys: keys.removed,
+        added_roles: roles.added,
+        removed_roles: roles.removed,
+        changed_roles: roles.changed,
+        succinct_roles_changed: old.delegations().succinct_roles()
+            != new.delegations().succinct_roles(),
+    }
+}
+
+/// Enumerate the keys and roles that changed between the `old` and `new` versions of a root
+/// metadata.
+pub fn diff_root(old: &RootMetadata, new: &RootMetadata) -> RootDiff {
+    let keys = MapDiff::new(old.keys(), new.keys());
+
+    RootDiff {
+        version: Change {
+            before: old.version(),
+            after: new.version(),
+        },
+        consistent_snapshot: change(old.consistent_snapshot(), new.consistent_snapshot()),
+        added_keys: keys.added,
+        removed_keys: keys.removed,
+        root: RoleDiff::from_definitions(old.root(), new.root()),
+        snapshot: RoleDiff::from_definitions(old.snapshot(), new.snapshot()),
+        targets: RoleDiff::from_definitions(old.targets(), new.targets()),
+        timestamp: RoleDiff::from_definitions(old.timestamp(), new.timestamp()),
+    }
+}
+
+/// Enumerate the targets metadata that changed between the `old` and `new` versions of a
+/// snapshot metadata.
+pub fn diff_snapshot(old: &SnapshotMetadata, new: &SnapshotMetadata) -> SnapshotDiff {
+    let meta = MapDiff::new(old.meta(), new.meta());
+
+    SnapshotDiff {
+        version: Change {
+            before: old.version(),
+            after: new.version(),
+        },
+        added_meta: meta.added,
+        removed_meta: meta.removed,
+        changed_meta: meta.changed,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{Ed25519PrivateKey, HashAlgorithm, PrivateKey};
+    use crate::metadata::{
+        Delegations, RootMetadataBuilder, SnapshotMetadataBuilder, TargetsMetadataBuilder,
+    };
+    use lazy_static::lazy_static;
+    use maplit::{hashmap, hashset};
+    use pretty_assertions::assert_eq;
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[
+                include_bytes!("../../tests/ed25519/ed25519-1.pk8.der"),
+                include_bytes!("../../tests/ed25519/ed25519-2.pk8.der"),
+                include_bytes!("../../tests/ed25519/ed25519-3.pk8.der"),
+            ];
+            keys.iter()
+                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+                .collect()
+        };
+    }
+
+    #[test]
+    fn test_diff_targets() {
+        let foo = TargetPath::new("foo").unwrap();
+        let bar = TargetPath::new("bar").unwrap();
+        let baz = TargetPath::new("baz").unwrap();
+        let dev = MetadataPath::new("dev").unwrap();
+        let release = MetadataPath::new("release").unwrap();
+        let qa = MetadataPath::new("qa").unwrap();
+
+        let description =
+            |contents: &[u8]| TargetDescription::from_slice(contents, &[HashAlgorithm::Sha256]);
+        let role = |name: &MetadataPath, key: &Ed25519PrivateKey, threshold| {
+            Delegation::builder(name.clone())
+                .key(key.public())
+                .threshold(threshold)
+                .delegate_path(TargetPath::new("prod/").unwrap())
+                .build()
+                .unwrap()
+        };
+
+        let old = TargetsMetadataBuilder::new()
+            .version(1)
+            .insert_target_from_slice(foo.clone(), b"foo", &[HashAlgorithm::Sha256])
+            .unwrap()
+            .insert_target_from_slice(bar.clone(), b"bar", &[HashAlgorithm::Sha256])
+            .unwrap()
+            .delegations(
+                Delegations::builder()
+                    .key(KEYS[0].public().clone())
+                    .role(role(&dev, &KEYS[0], 1))
+                    .role(role(&release, &KEYS[0], 1))
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+        let new = TargetsMetadataBuilder::new()
+            .version(2)
+            .insert_target_from_slice(foo.clone(), b"new foo", &[HashAlgorithm::Sha256])
+            .unwrap()
+            .insert_target_from_slice(baz.clone(), b"baz", &[HashAlgorithm::Sha256])
+            .unwrap()
+            .delegations(
+                Delegations::builder()
+                    .key(KEYS[0].public().clone())
+                    .key(KEYS[1].public().clone())
+                    .role(role(&dev, &KEYS[0], 1))
+                    .role(role(&release, &KEYS[1], 1))
+                    .role(role(&qa, &KEYS[1], 1))
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        let diff = diff(&old, &new);
+        assert_eq!(
+            diff.version(),
+            &Change {
+                before: 1,
+                after: 2
+            }
+        );
+        assert_eq!(
+            diff.added_targets(),
+            &hashmap! { baz => description(b"baz").unwrap() }
+        );
+        assert_eq!(
+            diff.removed_targets(),
+            &hashmap! { bar => description(b"bar").unwrap() }
+        );
+        assert_eq!(
+            diff.changed_targets(),
+            &hashmap! {
+                foo => Change {
+                    before: description(b"foo").unwrap(),
+                    after: description(b"new foo").unwrap(),
+                },
+            }
+        );
+        assert_eq!(
+            diff.added_keys(),
+            &hashmap! { KEYS[1].public().key_id().clone() => KEYS[1].public().clone() }
+        );
+        assert!(diff.removed_keys().is_empty());
+        assert_eq!(
+            diff.added_roles(),
+            &hashmap! { qa.clone() => role(&qa, &KEYS[1], 1) }
+        );
+        assert!(diff.removed_roles().is_empty());
+        assert_eq!(diff.changed_roles().len(), 1);
+
+        let role_diff = diff.changed_roles()[&release].role_diff();
+        assert_eq!(role_diff.threshold(), None);
+        assert_eq!(
+            role_diff.added_key_ids(),
+            &hashset! { KEYS[1].public().key_id().clone() }
+        );
+        assert_eq!(
+            role_diff.removed_key_ids(),
+            &hashset! { KEYS[0].public().key_id().clone() }
+        );
+        assert!(!diff.succinct_roles_changed());
+        assert!(!diff.is_empty());
+
+        assert!(super::diff(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_diff_root() {
+        let old = RootMetadataBuilder::new()
+            .version(1)
+            .root_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[0].public().clone())
+            .targets_key(KEYS[0].public().clone())
+            .timestamp_key(KEYS[0].public().clone())
+            .build()
+            .unwrap();
+        let new = RootMetadataBuilder::new()
+            .version(2)
+            .root_key(KEYS[0].public().clone())
+            .root_key(KEYS[1].public().clone())
+            .root_threshold(2)
+            .snapshot_key(KEYS[0].public().clone())
+            .targets_key(KEYS[0].public().clone())
+            .timestamp_key(KEYS[2].public().clone())
+            .build()
+            .unwrap();
+
+        let diff = diff_root(&old, &new);
+        assert_eq!(*diff.version().after(), 2);
+        assert_eq!(diff.consistent_snapshot(), None);
+        assert_eq!(
+            diff.added_keys(),
+            &hashmap! {
+                KEYS[1].public().key_id().clone() => KEYS[1].public().clone(),
+                KEYS[2].public().key_id().clone() => KEYS[2].public().clone(),
+            }
+        );
+        assert!(diff.removed_keys().is_empty());
+        assert_eq!(
+            diff.root().threshold(),
+            Some(&Change {
+                before: 1,
+                after: 2
+            })
+        );
+        assert_eq!(
+            diff.root().added_key_ids(),
+            &hashset! { KEYS[1].public().key_id().clone() }
+        );
+        assert!(diff.snapshot().is_empty());
+        assert!(diff.targets().is_empty());
+        assert_eq!(
+            diff.timestamp().added_key_ids(),
+            &hashset! { KEYS[2].public().key_id().clone() }
+        );
+        assert_eq!(
+            diff.timestamp().removed_key_ids(),
+            &hashset! { KEYS[0].public().key_id().clone() }
+        );
+        assert!(!diff.is_empty());
+
+        assert!(diff_root(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_diff_snapshot() {
+        let dev = MetadataPath::new("dev").unwrap();
+        let release = MetadataPath::new("release").unwrap();
+        let description = |version| {
+            MetadataDescription::from_slice(b"targets", version, &[HashAlgorithm::Sha256]).unwrap()
+        };
+
+        let old = SnapshotMetadataBuilder::new()
+            .version(1)
+            .insert_metadata_description(MetadataPath::targets(), description(1))
+            .insert_metadata_description(dev.clone(), description(1))
+            .build()
+            .unwrap();
+        let new = SnapshotMetadataBuilder::new()
+            .version(2)
+            .insert_metadata_description(MetadataPath::targets(), description(2))
+            .insert_metadata_description(release.clone(), description(1))
+            .build()
+            .unwrap();
+
+        let diff = diff_snapshot(&old, &new);
+        assert_eq!(*diff.version().before(), 1);
+        assert_eq!(diff.added_meta(), &hashmap! { release => description(1) });
+        assert_eq!(diff.removed_meta(), &hashmap! { dev => description(1) });
+        assert_eq!(
+            diff.changed_meta(),
+            &hashmap! {
+                MetadataPath::targets() => Change {
+                    before: description(1),
+                    after: description(2),
+                },
+            }
+        );
+
+        assert!(diff_snapshot(&new, &new).is_empty());
+    }
+}
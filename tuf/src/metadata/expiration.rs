@@ -0,0 +1,115 @@
+//! Policies that compute when newly built metadata expires.
+
+use chrono::offset::{TimeZone, Utc};
+use chrono::{DateTime, Datelike, Duration};
+
+/// A policy that computes the expiration time of metadata built at a given time. See
+/// [RootMetadataBuilder::expires_with](crate::metadata::RootMetadataBuilder::expires_with) and the
+/// equivalent methods of the other metadata builders.
+///
+/// A [Duration] expires the metadata that long after it is built, and any closure of the form
+/// `Fn(DateTime<Utc>) -> DateTime<Utc>` implements this trait.
+pub trait ExpirationPolicy {
+    /// Returns the expiration time of metadata built at `now`.
+    fn expires(&self, now: DateTime<Utc>) -> DateTime<Utc>;
+}
+
+impl<F> ExpirationPolicy for F
+where
+    F: Fn(DateTime<Utc>) -> DateTime<Utc>,
+{
+    fn expires(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        (self)(now)
+    }
+}
+
+impl ExpirationPolicy for Duration {
+    fn expires(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now + *self
+    }
+}
+
+/// An [ExpirationPolicy] that expires metadata at midnight UTC on the first day of a month, so
+/// that metadata can be refreshed on a monthly schedule.
+///
+/// ```
+/// # use chrono::prelude::*;
+/// # use tuf::metadata::{ExpirationPolicy, FirstOfMonth};
+/// let now = Utc.with_ymd_and_hms(2024, 12, 15, 8, 30, 0).unwrap();
+///
+/// assert_eq!(
+///     FirstOfMonth::next().expires(now),
+///     Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+/// );
+/// assert_eq!(
+///     FirstOfMonth::months_ahead(3).expires(now),
+///     Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap(),
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirstOfMonth {
+    months_ahead: u32,
+}
+
+impl FirstOfMonth {
+    /// Expire metadata on the first day of the month after the one it is built in.
+    pub fn next() -> Self {
+        Self::months_ahead(1)
+    }
+
+    /// Expire metadata on the first day of the month `months_ahead` months after the one it is
+    /// built in. A `months_ahead` of 0 is treated as 1, since the first day of the current month
+    /// has already passed.
+    pub fn months_ahead(months_ahead: u32) -> Self {
+        FirstOfMonth {
+            months_ahead: months_ahead.max(1),
+        }
+    }
+}
+
+impl ExpirationPolicy for FirstOfMonth {
+    fn expires(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        // Count months from year 0 to avoid special casing the end of the year.
+        let months = now.year() as i64 * 12 + now.month0() as i64 + self.months_ahead as i64;
+        let year = (months / 12) as i32;
+        let month = (months % 12) as u32 + 1;
+
+        // Midnight on the first day of a month always exists in UTC.
+        Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn expiration_policies() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 31, 23, 59, 59).unwrap();
+
+        assert_eq!(
+            Duration::days(7).expires(now),
+            Utc.with_ymd_and_hms(2024, 2, 7, 23, 59, 59).unwrap()
+        );
+        assert_eq!(
+            (|now: DateTime<Utc>| now + Duration::hours(1)).expires(now),
+            Utc.with_ymd_and_hms(2024, 2, 1, 0, 59, 59).unwrap()
+        );
+        assert_eq!(
+            FirstOfMonth::next().expires(now),
+            Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            FirstOfMonth::months_ahead(0).expires(now),
+            Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            FirstOfMonth::months_ahead(23).expires(now),
+            Utc.with_ymd_and_hms(2025, 12, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            FirstOfMonth::months_ahead(24).expires(now),
+            Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap()
+        );
+    }
+}
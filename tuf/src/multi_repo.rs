@@ -0,0 +1,410 @@
+//! Types for the map file of
+//! [TAP 4](https://github.com/theupdateframework/taps/blob/master/tap4.md), which tells a client
+//! how to look up targets across multiple repositories.
+//!
+//! A map file names the repositories a client uses, along with the URLs of their mirrors, and
+//! lists mappings from target path patterns to the repositories that must agree on those targets.
+//! Mappings are consulted in order. A target is only trusted if a threshold of the repositories of
+//! the first mapping that matches it sign the same length and hashes for it. If they do not, and
+//! the mapping is not terminating, the next matching mapping is consulted instead.
+//!
+//! ```
+//! # use tuf::metadata::TargetPath;
+//! # use tuf::multi_repo::RepositoryMap;
+//! let map = RepositoryMap::from_slice(br#"{
+//!     "repositories": {
+//!         "Django": ["https://djangoproject.com/"],
+//!         "PyPI": ["https://pypi.python.org/"]
+//!     },
+//!     "mapping": [
+//!         {
+//!             "paths": ["*django*"],
+//!             "repositories": ["Django", "PyPI"],
+//!             "terminating": true,
+//!             "threshold": 2
+//!         },
+//!         {
+//!             "paths": ["*"],
+//!             "repositories": ["PyPI"],
+//!             "terminating": true,
+//!             "threshold": 1
+//!         }
+//!     ]
+//! }"#).unwrap();
+//!
+//! let target = TargetPath::new("django-4.2.tar.gz").unwrap();
+//! let mappings = map.mappings_for(&target);
+//! assert_eq!(mappings.len(), 1);
+//! assert_eq!(mappings[0].repositories(), &["Django", "PyPI"]);
+//! assert_eq!(mappings[0].threshold(), 2);
+//! ```
+
+use serde::de::{Deserialize, Deserializer, Error as DeserializeError};
+use serde::ser::{Serialize, Serializer};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::error::{Error, Result};
+use crate::metadata::{PathMatching, TargetPath};
+
+/// The TAP 4 map file, which names the repositories a client uses and maps targets to them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepositoryMap {
+    repositories: BTreeMap<String, Vec<String>>,
+    mappings: Vec<Mapping>,
+}
+
+impl RepositoryMap {
+    /// Create a new `RepositoryMap` from the URLs of the mirrors of each named repository, and
+    /// the mappings of targets to those repositories.
+    ///
+    /// Every repository must have at least one mirror, and every mapping may only name
+    /// repositories in `repositories`.
+    pub fn new(
+        repositories: BTreeMap<String, Vec<String>>,
+        mappings: Vec<Mapping>,
+    ) -> Result<Self> {
+        if repositories.is_empty() {
+            return Err(Error::IllegalArgument(
+                "Map file must name at least one repository".into(),
+            ));
+        }
+
+        for (name, mirrors) in &repositories {
+            if mirrors.is_empty() {
+                return Err(Error::IllegalArgument(format!(
+                    "Repository {:?} must have at least one mirror",
+                    name
+                )));
+            }
+        }
+
+        for mapping in &mappings {
+            for name in &mapping.repositories {
+                if !repositories.contains_key(name) {
+                    return Err(Error::IllegalArgument(format!(
+                        "Mapping refers to unknown repository {:?}",
+                        name
+                    )));
+                }
+            }
+        }
+
+        Ok(RepositoryMap {
+            repositories,
+            mappings,
+        })
+    }
+
+    /// Parse a `RepositoryMap` from the contents of a JSON map file.
+    pub fn from_slice(buf: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(buf)?)
+    }
+
+    /// Serialize the `RepositoryMap` as the contents of a JSON map file.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+
+    /// An immutable reference to the URLs of the mirrors of every repository, by name.
+    pub fn repositories(&self) -> &BTreeMap<String, Vec<String>> {
+        &self.repositories
+    }
+
+    /// The URLs of the mirrors of the repository called `name`, if there is one.
+    pub fn mirrors(&self, name: &str) -> Option<&[String]> {
+        self.repositories.get(name).map(|mirrors| &mirrors[..])
+    }
+
+    /// An immutable reference to the mappings, in the order they are consulted.
+    pub fn mappings(&self) -> &[Mapping] {
+        &self.mappings
+    }
+
+    /// The mappings that should be consulted for `target`, in order. These are the mappings that
+    /// match `target`, up to and including the first matching mapping that is terminating.
+    pub fn mappings_for(&self, target: &TargetPath) -> Vec<&Mapping> {
+        let mut mappings = vec![];
+        for mapping in &self.mappings {
+            if mapping.matches_target(target) {
+                mappings.push(mapping);
+
+                if mapping.terminating {
+                    break;
+                }
+            }
+        }
+
+        mappings
+    }
+}
+
+impl Serialize for RepositoryMap {
+    fn serialize<S>(&self, ser: S) -> ::std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        RawRepositoryMap {
+            repositories: self.repositories.clone(),
+            mapping: self.mappings.clone(),
+        }
+        .serialize(ser)
+    }
+}
+
+impl<'de> Deserialize<'de> for RepositoryMap {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> ::std::result::Result<Self, D::Error> {
+        let raw: RawRepositoryMap = Deserialize::deserialize(de)?;
+        RepositoryMap::new(raw.repositories, raw.mapping)
+            .map_err(|e| DeserializeError::custom(format!("{:?}", e)))
+    }
+}
+
+/// A mapping of targets to the repositories that must agree on them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    paths: Vec<TargetPath>,
+    repositories: Vec<String>,
+    threshold: u32,
+    terminating: bool,
+}
+
+impl Mapping {
+    /// Create a new `Mapping` of the targets that match any of the glob patterns in `paths` to
+    /// `repositories`, a `threshold` of which must sign the same length and hashes for a target.
+    ///
+    /// `paths` and `repositories` must not be empty, `repositories` must not contain duplicates,
+    /// and `threshold` must be between 1 and the number of repositories.
+    pub fn new(
+        paths: Vec<TargetPath>,
+        repositories: Vec<String>,
+        threshold: u32,
+        terminating: bool,
+    ) -> Result<Self> {
+        if paths.is_empty() {
+            return Err(Error::IllegalArgument(
+                "Mapping must have at least one path".into(),
+            ));
+        }
+
+        if repositories.is_empty() {
+            return Err(Error::IllegalArgument(
+                "Mapping must have at least one repository".into(),
+            ));
+        }
+
+        let mut names = HashSet::new();
+        for name in &repositories {
+            if !names.insert(name) {
+                return Err(Error::IllegalArgument(format!(
+                    "Mapping has duplicate repository {:?}",
+                    name
+                )));
+            }
+        }
+
+        if threshold < 1 || threshold as usize > repositories.len() {
+            return Err(Error::IllegalArgument(format!(
+                "Mapping threshold must be between 1 and {}, but was {}",
+                repositories.len(),
+                threshold
+            )));
+        }
+
+        Ok(Mapping {
+            paths,
+            repositories,
+            threshold,
+            terminating,
+        })
+    }
+
+    /// An immutable reference to the glob patterns of the targets this mapping applies to.
+    pub fn paths(&self) -> &[TargetPath] {
+        &self.paths
+    }
+
+    /// An immutable reference to the names of the repositories that must agree on the targets.
+    pub fn repositories(&self) -> &[String] {
+        &self.repositories
+    }
+
+    /// The number of repositories that must sign the same length and hashes for a target.
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    /// Whether or not mappings after this one are consulted when this mapping matches a target,
+    /// but its repositories do not agree on it.
+    pub fn terminating(&self) -> bool {
+        self.terminating
+    }
+
+    /// Whether or not this mapping applies to `target`. TAP 4 specifies the paths as glob
+    /// patterns, which are matched with [PathMatching::GlobStar].
+    pub fn matches_target(&self, target: &TargetPath) -> bool {
+        let path_matching = PathMatching::GlobStar {
+            case_sensitive: true,
+        };
+        self.paths
+            .iter()
+            .any(|path| path_matching.matches(path, target))
+    }
+}
+
+impl Serialize for Mapping {
+    fn serialize<S>(&self, ser: S) -> ::std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        RawMapping {
+            paths: self.paths.clone(),
+            repositories: self.repositories.clone(),
+            terminating: self.terminating,
+            threshold: self.threshold,
+        }
+        .serialize(ser)
+    }
+}
+
+impl<'de> Deserialize<'de> for Mapping {
+    fn deserialize<D: Deserializer<'de>>(de: D) -> ::std::result::Result<Self, D::Error> {
+        let raw: RawMapping = Deserialize::deserialize(de)?;
+        Mapping::new(raw.paths, raw.repositories, raw.threshold, raw.terminating)
+            .map_err(|e| DeserializeError::custom(format!("{:?}", e)))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawRepositoryMap {
+    repositories: BTreeMap<String, Vec<String>>,
+    mapping: Vec<Mapping>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawMapping {
+    paths: Vec<TargetPath>,
+    repositories: Vec<String>,
+    #[serde(default)]
+    terminating: bool,
+    #[serde(default = "default_threshold")]
+    threshold: u32,
+}
+
+fn default_threshold() -> u32 {
+    1
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert_matches::assert_matches;
+    use maplit::btreemap;
+
+    fn mapping(
+        paths: &[&str],
+        repositories: &[&str],
+        threshold: u32,
+        terminating: bool,
+    ) -> Mapping {
+        Mapping::new(
+            paths.iter().map(|p| TargetPath::new(*p).unwrap()).collect(),
+            repositories.iter().map(|r| r.to_string()).collect(),
+            threshold,
+            terminating,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn mapping_validation() {
+        let paths = vec![TargetPath::new("*").unwrap()];
+        let repositories = vec!["a".to_string(), "b".to_string()];
+
+        assert_matches!(
+            Mapping::new(vec![], repositories.clone(), 1, false),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            Mapping::new(paths.clone(), vec![], 1, false),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            Mapping::new(paths.clone(), repositories.clone(), 0, false),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            Mapping::new(paths.clone(), repositories.clone(), 3, false),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            Mapping::new(paths.clone(), vec!["a".into(), "a".into()], 1, false),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(Mapping::new(paths, repositories, 2, false), Ok(_));
+    }
+
+    #[test]
+    fn repository_map_validation() {
+        let mappings = vec![mapping(&["*"], &["a"], 1, true)];
+
+        assert_matches!(
+            RepositoryMap::new(BTreeMap::new(), vec![]),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            RepositoryMap::new(btreemap! { "a".to_string() => vec![] }, mappings.clone()),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            RepositoryMap::new(
+                btreemap! { "b".to_string() => vec!["https://b.example.com".to_string()] },
+                mappings.clone(),
+            ),
+            Err(Error::IllegalArgument(_))
+        );
+
+        // Invalid map files are rejected when parsed.
+        assert_matches!(
+            RepositoryMap::from_slice(
+                br#"{"repositories": {"a": ["https://a.example.com"]},
+                     "mapping": [{"paths": ["*"], "repositories": ["a"], "threshold": 2}]}"#
+            ),
+            Err(Error::Json(_))
+        );
+    }
+
+    #[test]
+    fn repository_map_mappings_for() {
+        let map = RepositoryMap::new(
+            btreemap! {
+                "a".to_string() => vec!["https://a.example.com".to_string()],
+                "b".to_string() => vec!["https://b.example.com".to_string()],
+            },
+            vec![
+                mapping(&["foo/*"], &["a", "b"], 2, false),
+                mapping(&["foo/bar"], &["b"], 1, true),
+                mapping(&["**"], &["a"], 1, true),
+            ],
+        )
+        .unwrap();
+
+        let mappings = map.mappings_for(&TargetPath::new("foo/bar").unwrap());
+        assert_eq!(mappings, vec![&map.mappings()[0], &map.mappings()[1]]);
+
+        let mappings = map.mappings_for(&TargetPath::new("foo/baz").unwrap());
+        assert_eq!(mappings, vec![&map.mappings()[0], &map.mappings()[2]]);
+
+        let mappings = map.mappings_for(&TargetPath::new("baz/qux").unwrap());
+        assert_eq!(mappings, vec![&map.mappings()[2]]);
+
+        assert_eq!(
+            map.mirrors("a"),
+            Some(&["https://a.example.com".to_string()][..])
+        );
+        assert_eq!(map.mirrors("c"), None);
+
+        let decoded = RepositoryMap::from_slice(&map.to_vec().unwrap()).unwrap();
+        assert_eq!(decoded, map);
+    }
+}
@@ -0,0 +1,219 @@
+//! Restrictions on the cryptographic algorithms that trusted metadata may use.
+//!
+//! A [Policy] is configured on a [Database](crate::database::Database) with
+//! [Database::set_policy](crate::database::Database::set_policy), or on a
+//! [Client](crate::client::Client) with
+//! [ConfigBuilder::policy](crate::client::ConfigBuilder::policy). Metadata that does not satisfy
+//! the policy is rejected when it is updated, with an error naming the role and the offending
+//! algorithm or key.
+
+use std::collections::HashSet;
+
+use crate::crypto::{HashAlgorithm, PublicKey, SignatureScheme};
+use crate::error::{Error, Result};
+use crate::metadata::{
+    Delegations, MetadataPath, RootMetadata, SnapshotMetadata, TargetsMetadata, TimestampMetadata,
+};
+
+/// Allow-lists of the hash algorithms and signature schemes that trusted metadata may use, such
+/// as to forbid legacy algorithms, or to require that every set of hashes includes SHA-512.
+///
+/// The default policy allows every algorithm and scheme, requires none, and allows keys to be
+/// reused across top-level roles.
+///
+/// ```
+/// # use tuf::crypto::{HashAlgorithm, SignatureScheme};
+/// # use tuf::metadata::MetadataPath;
+/// # use tuf::policy::Policy;
+/// # use tuf::Error;
+/// # use std::iter::once;
+/// let policy = Policy::new()
+///     .with_allowed_hash_algorithms(vec![HashAlgorithm::Sha256, HashAlgorithm::Sha512])
+///     .with_required_hash_algorithm(HashAlgorithm::Sha512)
+///     .with_allowed_signature_schemes(once(SignatureScheme::Ed25519));
+///
+/// let role = MetadataPath::snapshot();
+/// assert!(policy.check_hashes(&role, &[HashAlgorithm::Sha512]).is_ok());
+/// assert!(matches!(
+///     policy.check_hashes(&role, &[HashAlgorithm::Sha256]),
+///     Err(Error::MissingRequiredHashAlgorithm { .. })
+/// ));
+/// assert!(matches!(
+///     policy.check_hashes(&role, &[HashAlgorithm::Sha384, HashAlgorithm::Sha512]),
+///     Err(Error::DisallowedHashAlgorithm { .. })
+/// ));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Policy {
+    allowed_hash_algorithms: Option<HashSet<HashAlgorithm>>,
+    required_hash_algorithms: HashSet<HashAlgorithm>,
+    allowed_signature_schemes: Option<HashSet<SignatureScheme>>,
+    forbid_key_reuse: bool,
+}
+
+impl Policy {
+    /// Create a policy that allows every hash algorithm and signature scheme.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only allow metadata to declare hashes with the `algorithms`.
+    pub fn with_allowed_hash_algorithms<I>(mut self, algorithms: I) -> Self
+    where
+        I: IntoIterator<Item = HashAlgorithm>,
+    {
+        self.allowed_hash_algorithms = Some(algorithms.into_iter().collect());
+        self
+    }
+
+    /// Require every set of hashes that metadata declares to include a hash with the `algorithm`.
+    /// Descriptions that declare no hashes at all are left to the
+    /// [MetadataIntegrityPolicy](crate::database::MetadataIntegrityPolicy).
+    pub fn with_required_hash_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        let _ = self.required_hash_algorithms.insert(algorithm);
+        self
+    }
+
+    /// Only allow root metadata and delegations to authorize keys with the `schemes`.
+    pub fn with_allowed_signature_schemes<I>(mut self, schemes: I) -> Self
+    where
+        I: IntoIterator<Item = SignatureScheme>,
+    {
+        self.allowed_signature_schemes = Some(schemes.into_iter().collect());
+        self
+    }
+
+    /// Reject root metadata that trusts a key for more than one top-level role. See
+    /// [RootMetadata::reused_key_ids].
+    pub fn with_forbid_key_reuse(mut self, forbid: bool) -> Self {
+        self.forbid_key_reuse = forbid;
+        self
+    }
+
+    /// The hash algorithms metadata may use, or `None` if every algorithm is allowed.
+    pub fn allowed_hash_algorithms(&self) -> Option<&HashSet<HashAlgorithm>> {
+        self.allowed_hash_algorithms.as_ref()
+    }
+
+    /// The hash algorithms every set of hashes must include.
+    pub fn required_hash_algorithms(&self) -> &HashSet<HashAlgorithm> {
+        &self.required_hash_algorithms
+    }
+
+    /// The signature schemes keys may use, or `None` if every scheme is allowed.
+    pub fn allowed_signature_schemes(&self) -> Option<&HashSet<SignatureScheme>> {
+        self.allowed_signature_schemes.as_ref()
+    }
+
+    /// Whether root metadata that trusts a key for more than one top-level role is rejected.
+    pub fn forbids_key_reuse(&self) -> bool {
+        self.forbid_key_reuse
+    }
+
+    /// Check one set of hashes that the metadata for `role` declares.
+    pub fn check_hashes<'a, I>(&self, role: &MetadataPath, algorithms: I) -> Result<()>
+    where
+        I: IntoIterator<Item = &'a HashAlgorithm>,
+    {
+        let algorithms = algorithms.into_iter().collect::<HashSet<_>>();
+        if algorithms.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(allowed) = &self.allowed_hash_algorithms {
+            if let Some(algorithm) = algorithms.iter().find(|alg| !allowed.contains(**alg)) {
+                return Err(Error::DisallowedHashAlgorithm {
+                    role: role.clone(),
+                    algorithm: (*algorithm).clone(),
+                });
+            }
+        }
+
+        if let Some(algorithm) = self
+            .required_hash_algorithms
+            .iter()
+            .find(|alg| !algorithms.contains(alg))
+        {
+            return Err(Error::MissingRequiredHashAlgorithm {
+                role: role.clone(),
+                algorithm: algorithm.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Check a key that the metadata for `role` authorizes.
+    pub fn check_key(&self, role: &MetadataPath, key: &PublicKey) -> Result<()> {
+        match &self.allowed_signature_schemes {
+            Some(allowed) if !allowed.contains(key.scheme()) => {
+                Err(Error::DisallowedSignatureScheme {
+                    role: role.clone(),
+                    scheme: key.scheme().clone(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the keys of root metadata.
+    pub fn check_root(&self, root: &RootMetadata) -> Result<()> {
+        let role = MetadataPath::root();
+        for key in root.keys().values() {
+            self.check_key(&role, key)?;
+        }
+
+        if self.forbid_key_reuse {
+            if let Some((key_id, roles)) = root.reused_key_ids().into_iter().next() {
+                return Err(Error::KeyReuse { key_id, roles });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the keys that root metadata authorizes for the custom top-level `role`.
+    pub fn check_custom_role<'a, I>(&self, role: &MetadataPath, keys: I) -> Result<()>
+    where
+        I: IntoIterator<Item = &'a PublicKey>,
+    {
+        for key in keys {
+            self.check_key(role, key)?;
+        }
+        Ok(())
+    }
+
+    /// Check the hashes of the snapshot metadata that timestamp metadata describes.
+    pub fn check_timestamp(&self, timestamp: &TimestampMetadata) -> Result<()> {
+        self.check_hashes(
+            &MetadataPath::timestamp(),
+            timestamp.snapshot().hashes().keys(),
+        )
+    }
+
+    /// Check the hashes of the targets metadata that snapshot metadata describes.
+    pub fn check_snapshot(&self, snapshot: &SnapshotMetadata) -> Result<()> {
+        let role = MetadataPath::snapshot();
+        for description in snapshot.meta().values() {
+            self.check_hashes(&role, description.hashes().keys())?;
+        }
+        Ok(())
+    }
+
+    /// Check the hashes of the targets, and the delegated keys, of the targets metadata for
+    /// `role`.
+    pub fn check_targets(&self, role: &MetadataPath, targets: &TargetsMetadata) -> Result<()> {
+        for description in targets.targets().values() {
+            self.check_hashes(role, description.hashes().keys())?;
+        }
+        self.check_delegations(role, targets.delegations())
+    }
+
+    /// Check the delegated keys of the targets metadata for `role`.
+    pub fn check_delegations(&self, role: &MetadataPath, delegations: &Delegations) -> Result<()> {
+        for key in delegations.keys().values() {
+            self.check_key(role, key)?;
+        }
+        Ok(())
+    }
+}
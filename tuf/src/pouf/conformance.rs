@@ -0,0 +1,225 @@
+use chrono::Utc;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::crypto::{Ed25519PrivateKey, HashAlgorithm, PrivateKey};
+use crate::database::Database;
+use crate::error::Error;
+use crate::metadata::{
+    Delegation, Delegations, Metadata, MetadataPath, RootMetadataBuilder, SignedMetadata,
+    SnapshotMetadataBuilder, TargetPath, TargetsMetadataBuilder, TimestampMetadataBuilder,
+};
+use crate::pouf::Pouf;
+use crate::Result;
+
+/// Check that the pouf `D` behaves the way this crate expects, such as in the tests of a pouf
+/// that is implemented outside of this crate. Returns an [Error::Encoding] that describes the
+/// first check that failed, or the error of the pouf itself.
+///
+/// The checks sign every kind of top-level metadata, along with delegated targets metadata, with a
+/// freshly generated key, and then check that:
+///
+/// * [Pouf::extension] can be used in file names.
+/// * [Pouf::canonicalize] does not depend on the order in which maps are serialized.
+/// * The metadata survives a round trip through its raw bytes, through [Pouf::from_slice] and
+///   [Pouf::from_reader], and through [Pouf::pretty_print].
+/// * Canonicalizing parsed metadata gives back the exact bytes that were parsed.
+/// * A [Database] trusts the metadata, which checks every signature.
+///
+/// ```
+/// # use tuf::pouf::{self, Pouf1};
+/// pouf::verify_impl::<Pouf1>().unwrap();
+/// ```
+pub fn verify_impl<D>() -> Result<()>
+where
+    D: Pouf,
+{
+    let extension = D::extension();
+    if extension.is_empty() || !extension.bytes().all(|b| b.is_ascii_alphanumeric()) {
+        return Err(Error::Encoding(format!(
+            "extension {:?} must be a non-empty ASCII alphanumeric string",
+            extension
+        )));
+    }
+
+    let hash_map = (0..64)
+        .map(|i| (i.to_string(), i))
+        .collect::<HashMap<_, _>>();
+    let btree_map = hash_map.clone().into_iter().collect::<BTreeMap<_, _>>();
+    check(
+        D::canonicalize(&D::serialize(&hash_map)?)? == D::canonicalize(&D::serialize(&btree_map)?)?,
+        "canonicalize depends on the order in which map entries are serialized",
+    )?;
+
+    let key = Ed25519PrivateKey::from_pkcs8(&Ed25519PrivateKey::pkcs8()?)?;
+    let now = Utc::now();
+    let delegated_path = MetadataPath::new("delegated")?;
+
+    let root = RootMetadataBuilder::new()
+        .root_key(key.public().clone())
+        .snapshot_key(key.public().clone())
+        .targets_key(key.public().clone())
+        .timestamp_key(key.public().clone())
+        .signed::<D>(&key)?;
+    let delegated_targets = TargetsMetadataBuilder::new()
+        .insert_target_from_slice(
+            TargetPath::new("delegated/target")?,
+            b"delegated target",
+            &[HashAlgorithm::Sha256],
+        )?
+        .signed::<D>(&key)?;
+    let delegation = Delegation::builder(delegated_path.clone())
+        .key(key.public())
+        .delegate_path(TargetPath::new("delegated/")?)
+        .build()?;
+    let targets = TargetsMetadataBuilder::new()
+        .insert_target_from_slice(
+            TargetPath::new("target")?,
+            b"target",
+            &[HashAlgorithm::Sha256],
+        )?
+        .delegations(
+            Delegations::builder()
+                .key(key.public().clone())
+                .role(delegation)
+                .build()?,
+        )
+        .signed::<D>(&key)?;
+    let snapshot = SnapshotMetadataBuilder::new()
+        .insert_metadata(&targets, &[HashAlgorithm::Sha256])?
+        .insert_metadata_with_path("delegated", &delegated_targets, &[HashAlgorithm::Sha256])?
+        .signed::<D>(&key)?;
+    let timestamp = TimestampMetadataBuilder::from_snapshot(&snapshot, &[HashAlgorithm::Sha256])?
+        .signed::<D>(&key)?;
+
+    check_round_trip(&root)?;
+    check_round_trip(&timestamp)?;
+    check_round_trip(&snapshot)?;
+    check_round_trip(&targets)?;
+    check_round_trip(&delegated_targets)?;
+
+    let mut db = Database::<D>::from_trusted_root(&root.to_raw()?)?;
+    let _ = db.update_timestamp(&now, &timestamp.to_raw()?)?;
+    let _ = db.update_snapshot(&now, &snapshot.to_raw()?)?;
+    let _ = db.update_targets(&now, &targets.to_raw()?)?;
+    let _ = db.update_delegated_targets(
+        &now,
+        &MetadataPath::targets(),
+        &delegated_path,
+        &delegated_targets.to_raw()?,
+    )?;
+
+    check(
+        db.trusted_delegations().contains_key(&delegated_path),
+        "the database did not trust the delegated targets metadata",
+    )
+}
+
+fn check_round_trip<D, M>(signed: &SignedMetadata<D, M>) -> Result<()>
+where
+    D: Pouf,
+    M: Metadata,
+{
+    let role = M::ROLE;
+    let metadata = signed.assume_valid()?;
+
+    let raw = signed.to_raw()?;
+    let parsed = raw.parse_untrusted()?;
+    check(
+        parsed.signatures() == signed.signatures() && parsed.assume_valid()? == metadata,
+        &format!(
+            "{} metadata changed in a round trip through from_slice",
+            role
+        ),
+    )?;
+    check(
+        parsed.to_raw()?.as_bytes() == raw.as_bytes(),
+        &format!("canonicalizing parsed {} metadata changed its bytes", role),
+    )?;
+
+    let read: SignedMetadata<D, M> = D::from_reader(raw.as_bytes())?;
+    check(
+        read.signatures() == signed.signatures() && read.assume_valid()? == metadata,
+        &format!(
+            "{} metadata changed in a round trip through from_reader",
+            role
+        ),
+    )?;
+
+    let pretty = signed.to_raw_pretty()?.parse_untrusted()?;
+    check(
+        pretty.signatures() == signed.signatures() && pretty.assume_valid()? == metadata,
+        &format!(
+            "{} metadata changed in a round trip through pretty_print",
+            role
+        ),
+    )
+}
+
+fn check(ok: bool, msg: &str) -> Result<()> {
+    if ok {
+        Ok(())
+    } else {
+        Err(Error::Encoding(msg.into()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pouf::{Der, Pouf1, Protobuf};
+
+    #[test]
+    fn builtin_poufs_conform() {
+        verify_impl::<Pouf1>().unwrap();
+        verify_impl::<Der>().unwrap();
+        verify_impl::<Protobuf>().unwrap();
+        #[cfg(feature = "cbor")]
+        verify_impl::<crate::pouf::Cbor>().unwrap();
+        #[cfg(feature = "msgpack")]
+        verify_impl::<crate::pouf::MessagePack>().unwrap();
+    }
+
+    #[test]
+    fn verify_impl_rejects_non_deterministic_canonicalization() {
+        // Canonicalizes by serializing maps in the order they were given.
+        struct Unsorted;
+
+        impl Pouf for Unsorted {
+            type RawData = Vec<(String, u32)>;
+
+            fn extension() -> &'static str {
+                "unsorted"
+            }
+
+            fn canonicalize(raw_data: &Self::RawData) -> Result<Vec<u8>> {
+                Ok(serde_json::to_vec(raw_data)?)
+            }
+
+            fn deserialize<T>(raw_data: &Self::RawData) -> Result<T>
+            where
+                T: serde::de::DeserializeOwned,
+            {
+                Pouf1::deserialize(&serde_json::to_value(raw_data)?)
+            }
+
+            fn serialize<T>(data: &T) -> Result<Self::RawData>
+            where
+                T: serde::ser::Serialize,
+            {
+                let value = serde_json::to_value(data)?;
+                Ok(serde_json::from_value::<HashMap<String, u32>>(value)
+                    .map(|map| map.into_iter().collect())
+                    .unwrap_or_default())
+            }
+
+            fn from_slice<T>(slice: &[u8]) -> Result<T>
+            where
+                T: serde::de::DeserializeOwned,
+            {
+                Pouf1::from_slice(slice)
+            }
+        }
+
+        assert!(verify_impl::<Unsorted>().is_err());
+    }
+}
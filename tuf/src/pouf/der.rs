@@ -0,0 +1,409 @@
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::{Map, Number, Value};
+
+use crate::error::Error;
+use crate::pouf::Pouf;
+use crate::Result;
+
+const TAG_BOOLEAN: u8 = 0x01;
+const TAG_INTEGER: u8 = 0x02;
+const TAG_NULL: u8 = 0x05;
+const TAG_UTF8_STRING: u8 = 0x0c;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+
+/// The deepest nesting of values that is decoded, to bound the recursion of the decoder.
+const MAX_DEPTH: usize = 128;
+
+/// TUF metadata encoded with the
+/// [Distinguished Encoding Rules](https://www.itu.int/rec/T-REC-X.690) of ASN.1.
+///
+/// The schema is the same as the one of [Pouf1](crate::pouf::Pouf1), with every JSON value
+/// encoded as the following ASN.1 type:
+///
+/// ```text
+/// Value ::= CHOICE {
+///     null     NULL,
+///     boolean  BOOLEAN,
+///     integer  INTEGER,
+///     string   UTF8String,
+///     array    SEQUENCE OF Value,
+///     object   SET OF Member
+/// }
+///
+/// Member ::= SEQUENCE {
+///     key    UTF8String,
+///     value  Value
+/// }
+/// ```
+///
+/// DER only allows one encoding of every value, and sorts the members of every object by their
+/// encoding, so the encoding is canonical by construction and metadata is signed over it as is.
+/// Decoding is strict: encodings that are not DER, floating point numbers, objects with
+/// duplicate keys, and trailing bytes are rejected. This lets devices that already ship an
+/// ASN.1 DER parser, such as secure elements, check metadata without a JSON parser.
+///
+/// Metadata can be converted to and from other poufs with [convert](crate::pouf::convert).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Der;
+
+impl Pouf for Der {
+    type RawData = Value;
+
+    /// ```
+    /// # use tuf::pouf::{Der, Pouf};
+    /// assert_eq!(Der::extension(), "der");
+    /// ```
+    fn extension() -> &'static str {
+        "der"
+    }
+
+    /// ```
+    /// # use serde_json::json;
+    /// # use tuf::pouf::{Der, Pouf};
+    /// let out = Der::canonicalize(&json!({"foo": true, "a": 1})).unwrap();
+    /// assert_eq!(
+    ///     out,
+    ///     b"\x31\x12\x30\x06\x0c\x01a\x02\x01\x01\x30\x08\x0c\x03foo\x01\x01\xff",
+    /// );
+    /// ```
+    fn canonicalize(raw_data: &Self::RawData) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        encode(raw_data, &mut buf)?;
+        Ok(buf)
+    }
+
+    fn deserialize<T>(raw_data: &Self::RawData) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(serde_json::from_value(raw_data.clone())?)
+    }
+
+    fn serialize<T>(data: &T) -> Result<Self::RawData>
+    where
+        T: Serialize,
+    {
+        Ok(serde_json::to_value(data)?)
+    }
+
+    /// ```
+    /// # use tuf::pouf::{Der, Pouf};
+    /// # use std::collections::HashMap;
+    /// let der: &[u8] = b"\x31\x0c\x30\x0a\x0c\x03foo\x0c\x03bar";
+    /// let map: HashMap<String, String> = Der::from_slice(der).unwrap();
+    /// assert_eq!(map["foo"], "bar");
+    /// ```
+    fn from_slice<T>(slice: &[u8]) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let mut input = slice;
+        let value = decode(&mut input, 0)?;
+        if !input.is_empty() {
+            return Err(Error::Encoding("trailing bytes after DER value".into()));
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+fn encode(value: &Value, buf: &mut Vec<u8>) -> Result<()> {
+    match value {
+        Value::Null => write_tlv(buf, TAG_NULL, &[]),
+        Value::Bool(b) => write_tlv(buf, TAG_BOOLEAN, &[if *b { 0xff } else { 0x00 }]),
+        Value::Number(n) => {
+            let n = n
+                .as_i64()
+                .map(i128::from)
+                .or_else(|| n.as_u64().map(i128::from))
+                .ok_or_else(|| Error::Encoding("only i64 and u64 are supported".into()))?;
+            write_tlv(buf, TAG_INTEGER, &encode_integer(n))
+        }
+        Value::String(s) => write_tlv(buf, TAG_UTF8_STRING, s.as_bytes()),
+        Value::Array(items) => {
+            let mut content = Vec::new();
+            for item in items {
+                encode(item, &mut content)?;
+            }
+            write_tlv(buf, TAG_SEQUENCE, &content)
+        }
+        Value::Object(members) => {
+            let mut encoded = Vec::with_capacity(members.len());
+            for (key, value) in members {
+                let mut member = Vec::new();
+                write_tlv(&mut member, TAG_UTF8_STRING, key.as_bytes());
+                encode(value, &mut member)?;
+
+                let mut tlv = Vec::new();
+                write_tlv(&mut tlv, TAG_SEQUENCE, &member);
+                encoded.push(tlv);
+            }
+
+            // The elements of a DER `SET OF` are sorted by their encoding.
+            encoded.sort();
+            write_tlv(buf, TAG_SET, &encoded.concat())
+        }
+    }
+    Ok(())
+}
+
+fn write_tlv(buf: &mut Vec<u8>, tag: u8, content: &[u8]) {
+    buf.push(tag);
+    if content.len() < 0x80 {
+        buf.push(content.len() as u8);
+    } else {
+        let len = (content.len() as u64).to_be_bytes();
+        let skip = len.iter().take_while(|b| **b == 0).count();
+        buf.push(0x80 | (len.len() - skip) as u8);
+        buf.extend(&len[skip..]);
+    }
+    buf.extend(content);
+}
+
+/// The shortest two's complement big-endian encoding of `n`.
+fn encode_integer(n: i128) -> Vec<u8> {
+    let bytes = n.to_be_bytes();
+    let mut start = 0;
+    while start < bytes.len() - 1 {
+        let redundant = (bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0);
+        if !redundant {
+            break;
+        }
+        start += 1;
+    }
+    bytes[start..].to_vec()
+}
+
+fn decode(input: &mut &[u8], depth: usize) -> Result<Value> {
+    if depth > MAX_DEPTH {
+        return Err(Error::Encoding("DER value is nested too deeply".into()));
+    }
+
+    let (tag, content) = read_tlv(input)?;
+    match tag {
+        TAG_NULL if content.is_empty() => Ok(Value::Null),
+        TAG_BOOLEAN => match content {
+            [0x00] => Ok(Value::Bool(false)),
+            [0xff] => Ok(Value::Bool(true)),
+            _ => Err(Error::Encoding("invalid DER boolean".into())),
+        },
+        TAG_INTEGER => decode_integer(content),
+        TAG_UTF8_STRING => Ok(Value::String(decode_string(content)?)),
+        TAG_SEQUENCE => {
+            let mut content = content;
+            let mut items = Vec::new();
+            while !content.is_empty() {
+                items.push(decode(&mut content, depth + 1)?);
+            }
+            Ok(Value::Array(items))
+        }
+        TAG_SET => {
+            let mut content = content;
+            let mut members = Map::new();
+            let mut previous: Option<&[u8]> = None;
+            while !content.is_empty() {
+                let before = content;
+                let (tag, mut member) = read_tlv(&mut content)?;
+                let encoded = &before[..before.len() - content.len()];
+                if previous.map_or(false, |previous| previous >= encoded) {
+                    return Err(Error::Encoding("DER set is not sorted".into()));
+                }
+                previous = Some(encoded);
+
+                if tag != TAG_SEQUENCE {
+                    return Err(Error::Encoding(
+                        "DER object member is not a sequence".into(),
+                    ));
+                }
+                let (key_tag, key) = read_tlv(&mut member)?;
+                if key_tag != TAG_UTF8_STRING {
+                    return Err(Error::Encoding("DER object key is not a string".into()));
+                }
+                let key = decode_string(key)?;
+                let value = decode(&mut member, depth + 1)?;
+                if !member.is_empty() {
+                    return Err(Error::Encoding("DER object member is too long".into()));
+                }
+                if members.insert(key, value).is_some() {
+                    return Err(Error::Encoding("DER object has duplicate keys".into()));
+                }
+            }
+            Ok(Value::Object(members))
+        }
+        _ => Err(Error::Encoding(format!("unsupported DER tag {:#04x}", tag))),
+    }
+}
+
+fn read_tlv<'a>(input: &mut &'a [u8]) -> Result<(u8, &'a [u8])> {
+    let truncated = || Error::Encoding("truncated DER value".into());
+
+    let (&tag, rest) = input.split_first().ok_or_else(truncated)?;
+    let (&first, mut rest) = rest.split_first().ok_or_else(truncated)?;
+
+    let len = if first < 0x80 {
+        usize::from(first)
+    } else {
+        let count = usize::from(first & 0x7f);
+        if count == 0 || count > std::mem::size_of::<usize>() || rest.len() < count {
+            return Err(Error::Encoding("invalid DER length".into()));
+        }
+        let (len_bytes, tail) = rest.split_at(count);
+        rest = tail;
+        if len_bytes[0] == 0 {
+            return Err(Error::Encoding("DER length is not minimal".into()));
+        }
+        let len = len_bytes
+            .iter()
+            .fold(0usize, |len, b| (len << 8) | usize::from(*b));
+        if len < 0x80 {
+            return Err(Error::Encoding("DER length is not minimal".into()));
+        }
+        len
+    };
+
+    if rest.len() < len {
+        return Err(truncated());
+    }
+    let (content, rest) = rest.split_at(len);
+    *input = rest;
+    Ok((tag, content))
+}
+
+fn decode_integer(content: &[u8]) -> Result<Value> {
+    if content.is_empty() || content.len() > 16 || encode_integer(sign_extend(content)) != content {
+        return Err(Error::Encoding("invalid DER integer".into()));
+    }
+
+    let n = sign_extend(content);
+    if let Ok(n) = i64::try_from(n) {
+        Ok(Value::Number(Number::from(n)))
+    } else if let Ok(n) = u64::try_from(n) {
+        Ok(Value::Number(Number::from(n)))
+    } else {
+        Err(Error::Encoding("DER integer is out of range".into()))
+    }
+}
+
+fn sign_extend(content: &[u8]) -> i128 {
+    let fill = if content[0] & 0x80 != 0 { 0xff } else { 0x00 };
+    let mut bytes = [fill; 16];
+    bytes[16 - content.len()..].copy_from_slice(content);
+    i128::from_be_bytes(bytes)
+}
+
+fn decode_string(content: &[u8]) -> Result<String> {
+    String::from_utf8(content.to_vec())
+        .map_err(|_| Error::Encoding("DER string is not UTF-8".into()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{Ed25519PrivateKey, PrivateKey};
+    use crate::database::Database;
+    use crate::metadata::{RootMetadata, RootMetadataBuilder};
+    use crate::pouf::{convert, Pouf1};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn round_trip(value: Value) {
+        let bytes = Der::canonicalize(&value).unwrap();
+        assert_eq!(Der::from_slice::<Value>(&bytes).unwrap(), value);
+    }
+
+    #[test]
+    fn encode_integers() {
+        let cases: &[(i128, &[u8])] = &[
+            (0, &[0x00]),
+            (127, &[0x7f]),
+            (128, &[0x00, 0x80]),
+            (256, &[0x01, 0x00]),
+            (-1, &[0xff]),
+            (-128, &[0x80]),
+            (-129, &[0xff, 0x7f]),
+        ];
+        for (n, expected) in cases {
+            assert_eq!(&encode_integer(*n), expected, "{}", n);
+        }
+
+        round_trip(json!(u64::MAX));
+        round_trip(json!(i64::MIN));
+    }
+
+    #[test]
+    fn encode_long_lengths() {
+        let long = "x".repeat(300);
+        let bytes = Der::canonicalize(&json!(long)).unwrap();
+        assert_eq!(&bytes[..4], &[TAG_UTF8_STRING, 0x82, 0x01, 0x2c]);
+        round_trip(json!(long));
+    }
+
+    #[test]
+    fn round_trip_nested_values() {
+        round_trip(json!({
+            "signed": {
+                "_type": "targets",
+                "version": 3,
+                "targets": {},
+                "custom": [null, false, "ü", -5],
+            },
+            "signatures": [],
+        }));
+    }
+
+    #[test]
+    fn reject_non_der_encodings() {
+        let cases: &[&[u8]] = &[
+            // A boolean that is neither 0x00 nor 0xff.
+            b"\x01\x01\x01",
+            // An integer with a redundant leading byte.
+            b"\x02\x02\x00\x01",
+            // A length in the long form that fits the short form.
+            b"\x0c\x81\x01a",
+            // Members of an object that are not sorted.
+            b"\x31\x12\x30\x08\x0c\x03foo\x01\x01\xff\x30\x06\x0c\x01a\x02\x01\x01",
+            // Duplicate keys.
+            b"\x31\x10\x30\x06\x0c\x01a\x02\x01\x01\x30\x06\x0c\x01a\x02\x01\x02",
+            // Trailing bytes.
+            b"\x05\x00\x05\x00",
+            // A truncated value.
+            b"\x0c\x05abc",
+        ];
+        for case in cases {
+            assert!(Der::from_slice::<Value>(case).is_err(), "{:x?}", case);
+        }
+
+        assert!(Der::canonicalize(&json!(1.5)).is_err());
+    }
+
+    #[test]
+    fn convert_root_between_json_and_der() {
+        let key: &[u8] = include_bytes!("../../tests/ed25519/ed25519-1.pk8.der");
+        let key = Ed25519PrivateKey::from_pkcs8(key).unwrap();
+        let json_root = RootMetadataBuilder::new()
+            .version(2)
+            .root_key(key.public().clone())
+            .snapshot_key(key.public().clone())
+            .targets_key(key.public().clone())
+            .timestamp_key(key.public().clone())
+            .signed::<Pouf1>(&key)
+            .unwrap()
+            .to_raw()
+            .unwrap();
+
+        let der_root = convert::<Pouf1, Der, RootMetadata>(&json_root, &[&key]).unwrap();
+
+        let db = Database::<Der>::from_trusted_root(&der_root).unwrap();
+        assert_eq!(
+            &**db.trusted_root(),
+            &json_root.parse_untrusted().unwrap().assume_valid().unwrap()
+        );
+
+        let back = convert::<Der, Pouf1, RootMetadata>(&der_root, &[&key]).unwrap();
+        assert_eq!(back, json_root);
+
+        assert!(convert::<Pouf1, Der, RootMetadata>(&json_root, &[]).is_err());
+    }
+}
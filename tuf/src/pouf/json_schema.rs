@@ -0,0 +1,511 @@
+//! [JSON Schemas](https://json-schema.org/) of signed metadata in the [Pouf1] format.
+//!
+//! The schemas describe the shape of `root.json`, `timestamp.json`, `snapshot.json` and
+//! `targets.json`, including delegated targets metadata, so that services such as upload gateways
+//! can reject malformed metadata before it reaches signing infrastructure. Passing validation does
+//! not mean that metadata is trusted: the schemas cannot check signatures, thresholds or versions
+//! against other metadata.
+//!
+//! Unrecognized fields of the `signed` portion of metadata are allowed, since they are preserved
+//! and signed over by this crate. Hash values, key IDs and signatures are lowercase or uppercase
+//! hex strings.
+//!
+//! ```
+//! # use tuf::pouf::json_schema;
+//! let schema = json_schema::root();
+//! assert_eq!(schema["title"], "root.json");
+//! ```
+//!
+//! [Pouf1]: crate::pouf::Pouf1
+
+use serde_json::{json, Value};
+
+const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// The JSON Schema of signed root metadata.
+pub fn root() -> Value {
+    signed_metadata(
+        "root.json",
+        json!({
+            "type": "object",
+            "required": [
+                "_type", "spec_version", "version", "expires", "consistent_snapshot", "keys",
+                "roles",
+            ],
+            "properties": {
+                "_type": { "const": "root" },
+                "spec_version": { "$ref": "#/$defs/spec_version" },
+                "version": { "$ref": "#/$defs/natural_number" },
+                "expires": { "$ref": "#/$defs/expires" },
+                "consistent_snapshot": { "type": "boolean" },
+                "keys": { "$ref": "#/$defs/keys" },
+                "roles": {
+                    "type": "object",
+                    "required": ["root", "snapshot", "targets", "timestamp"],
+                    "additionalProperties": { "$ref": "#/$defs/role_definition" },
+                },
+            },
+        }),
+    )
+}
+
+/// The JSON Schema of signed timestamp metadata.
+pub fn timestamp() -> Value {
+    signed_metadata(
+        "timestamp.json",
+        json!({
+            "type": "object",
+            "required": ["_type", "spec_version", "version", "expires", "meta"],
+            "properties": {
+                "_type": { "const": "timestamp" },
+                "spec_version": { "$ref": "#/$defs/spec_version" },
+                "version": { "$ref": "#/$defs/natural_number" },
+                "expires": { "$ref": "#/$defs/expires" },
+                "meta": {
+                    "type": "object",
+                    "required": ["snapshot.json"],
+                    "properties": {
+                        "snapshot.json": { "$ref": "#/$defs/metadata_description" },
+                    },
+                    "additionalProperties": false,
+                },
+                "merkle_root": { "$ref": "#/$defs/hex" },
+            },
+        }),
+    )
+}
+
+/// The JSON Schema of signed snapshot metadata.
+pub fn snapshot() -> Value {
+    signed_metadata(
+        "snapshot.json",
+        json!({
+            "type": "object",
+            "required": ["_type", "spec_version", "version", "expires", "meta"],
+            "properties": {
+                "_type": { "const": "snapshot" },
+                "spec_version": { "$ref": "#/$defs/spec_version" },
+                "version": { "$ref": "#/$defs/natural_number" },
+                "expires": { "$ref": "#/$defs/expires" },
+                "meta": {
+                    "type": "object",
+                    "additionalProperties": { "$ref": "#/$defs/metadata_description" },
+                },
+            },
+        }),
+    )
+}
+
+/// The JSON Schema of signed targets metadata, both top-level and delegated.
+pub fn targets() -> Value {
+    signed_metadata(
+        "targets.json",
+        json!({
+            "type": "object",
+            "required": ["_type", "spec_version", "version", "expires", "targets"],
+            "properties": {
+                "_type": { "const": "targets" },
+                "spec_version": { "$ref": "#/$defs/spec_version" },
+                "version": { "$ref": "#/$defs/natural_number" },
+                "expires": { "$ref": "#/$defs/expires" },
+                "targets": {
+                    "type": "object",
+                    "additionalProperties": { "$ref": "#/$defs/target_description" },
+                },
+                "delegations": { "$ref": "#/$defs/delegations" },
+            },
+        }),
+    )
+}
+
+/// Wrap the schema of the `signed` portion of metadata in the schema of its signatures.
+fn signed_metadata(title: &str, signed: Value) -> Value {
+    json!({
+        "$schema": SCHEMA_DIALECT,
+        "title": title,
+        "type": "object",
+        "required": ["signatures", "signed"],
+        "properties": {
+            "signatures": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["keyid", "sig"],
+                    "properties": {
+                        "keyid": { "$ref": "#/$defs/key_id" },
+                        "sig": { "$ref": "#/$defs/hex" },
+                    },
+                    "additionalProperties": false,
+                },
+            },
+            "signed": signed,
+        },
+        "additionalProperties": false,
+        "$defs": definitions(),
+    })
+}
+
+/// The definitions shared by every schema.
+fn definitions() -> Value {
+    json!({
+        "natural_number": {
+            "type": "integer",
+            "minimum": 1,
+            "maximum": u32::MAX,
+        },
+        "expires": {
+            "type": "string",
+            "format": "date-time",
+        },
+        "spec_version": {
+            "type": "string",
+            "pattern": "^[0-9]+\\.[0-9]+(\\.[0-9]+)?$",
+        },
+        "hex": {
+            "type": "string",
+            "pattern": "^[0-9a-fA-F]*$",
+        },
+        "key_id": {
+            "type": "string",
+            "pattern": "^[0-9a-fA-F]{64}$",
+        },
+        "hashes": {
+            "type": "object",
+            "additionalProperties": { "$ref": "#/$defs/hex" },
+        },
+        "public_key": {
+            "type": "object",
+            "required": ["keytype", "scheme", "keyval"],
+            "properties": {
+                "keytype": { "type": "string" },
+                "scheme": { "type": "string" },
+                "keyid_hash_algorithms": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                },
+                "keyval": {
+                    "type": "object",
+                    "required": ["public"],
+                    "properties": {
+                        "public": { "type": "string" },
+                    },
+                },
+            },
+        },
+        "keys": {
+            "type": "object",
+            "propertyNames": { "$ref": "#/$defs/key_id" },
+            "additionalProperties": { "$ref": "#/$defs/public_key" },
+        },
+        "key_ids": {
+            "type": "array",
+            "items": { "$ref": "#/$defs/key_id" },
+            "uniqueItems": true,
+        },
+        "role_definition": {
+            "type": "object",
+            "required": ["threshold", "keyids"],
+            "properties": {
+                "threshold": { "$ref": "#/$defs/natural_number" },
+                "keyids": { "$ref": "#/$defs/key_ids" },
+            },
+        },
+        "metadata_description": {
+            "type": "object",
+            "required": ["version"],
+            "properties": {
+                "version": { "$ref": "#/$defs/natural_number" },
+                "length": { "type": "integer", "minimum": 0 },
+                "hashes": { "$ref": "#/$defs/hashes" },
+            },
+        },
+        "target_description": {
+            "type": "object",
+            "properties": {
+                "length": { "type": "integer", "minimum": 0 },
+                "hashes": { "$ref": "#/$defs/hashes" },
+                "custom": { "type": "object" },
+            },
+        },
+        "path_hash_prefixes": {
+            "type": "array",
+            "items": { "$ref": "#/$defs/hex" },
+        },
+        "delegation": {
+            "type": "object",
+            "required": ["name", "terminating", "threshold", "keyids"],
+            "properties": {
+                "name": { "type": "string", "minLength": 1 },
+                "terminating": { "type": "boolean" },
+                "threshold": { "$ref": "#/$defs/natural_number" },
+                "keyids": { "$ref": "#/$defs/key_ids" },
+                "paths": { "type": "array", "items": { "type": "string" } },
+                "path_hash_prefixes": { "$ref": "#/$defs/path_hash_prefixes" },
+            },
+        },
+        "multi_role_delegation": {
+            "type": "object",
+            "required": ["min_roles_in_agreement", "terminating", "roles"],
+            "properties": {
+                "min_roles_in_agreement": { "$ref": "#/$defs/natural_number" },
+                "terminating": { "type": "boolean" },
+                "paths": { "type": "array", "items": { "type": "string" } },
+                "path_hash_prefixes": { "$ref": "#/$defs/path_hash_prefixes" },
+                "roles": {
+                    "type": "array",
+                    "minItems": 1,
+                    "items": {
+                        "type": "object",
+                        "required": ["name", "keyids", "threshold"],
+                        "properties": {
+                            "name": { "type": "string", "minLength": 1 },
+                            "keyids": { "$ref": "#/$defs/key_ids" },
+                            "threshold": { "$ref": "#/$defs/natural_number" },
+                        },
+                    },
+                },
+            },
+        },
+        "succinct_roles": {
+            "type": "object",
+            "required": ["keyids", "threshold", "bit_length", "name_prefix"],
+            "properties": {
+                "keyids": { "$ref": "#/$defs/key_ids" },
+                "threshold": { "$ref": "#/$defs/natural_number" },
+                "bit_length": { "type": "integer", "minimum": 1, "maximum": 32 },
+                "name_prefix": { "type": "string" },
+            },
+        },
+        "delegations": {
+            "type": "object",
+            "required": ["keys"],
+            "properties": {
+                "keys": { "$ref": "#/$defs/keys" },
+                "roles": {
+                    "type": "array",
+                    "items": {
+                        "anyOf": [
+                            { "$ref": "#/$defs/delegation" },
+                            { "$ref": "#/$defs/multi_role_delegation" },
+                        ],
+                    },
+                },
+                "succinct_roles": { "$ref": "#/$defs/succinct_roles" },
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{Ed25519PrivateKey, HashAlgorithm, PrivateKey};
+    use crate::metadata::{
+        Delegation, Delegations, MetadataPath, RootMetadataBuilder, SnapshotMetadataBuilder,
+        TargetPath, TargetsMetadataBuilder, TimestampMetadataBuilder,
+    };
+    use crate::pouf::Pouf1;
+    use lazy_static::lazy_static;
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[
+                include_bytes!("../../tests/ed25519/ed25519-1.pk8.der"),
+                include_bytes!("../../tests/ed25519/ed25519-2.pk8.der"),
+            ];
+            keys.iter()
+                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+                .collect()
+        };
+    }
+
+    /// Check `value` against `schema`, supporting the keywords used by the schemas above except
+    /// for `pattern` and `format`. Returns the JSON pointer of the first value that fails.
+    fn validate(root: &Value, schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+        let fail = || Err(at.to_string());
+
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/$defs/");
+            return validate(root, &root["$defs"][name], value, at);
+        }
+        if let Some(any_of) = schema["anyOf"].as_array() {
+            if !any_of.iter().any(|s| validate(root, s, value, at).is_ok()) {
+                return fail();
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if value != expected {
+                return fail();
+            }
+        }
+        let type_matches = match schema["type"].as_str() {
+            None => true,
+            Some("object") => value.is_object(),
+            Some("array") => value.is_array(),
+            Some("string") => value.is_string(),
+            Some("integer") => value.is_u64() || value.is_i64(),
+            Some("boolean") => value.is_boolean(),
+            Some(other) => panic!("unsupported type {}", other),
+        };
+        if !type_matches {
+            return fail();
+        }
+        if let Some(min) = schema["minimum"].as_i64() {
+            if value.as_i64().map_or(true, |v| v < min) {
+                return fail();
+            }
+        }
+        if let Some(max) = schema["maximum"].as_u64() {
+            if value.as_u64().map_or(true, |v| v > max) {
+                return fail();
+            }
+        }
+        if let Some(min) = schema["minLength"].as_u64() {
+            if (value.as_str().unwrap().chars().count() as u64) < min {
+                return fail();
+            }
+        }
+
+        if let Some(items) = value.as_array() {
+            if let Some(min) = schema["minItems"].as_u64() {
+                if (items.len() as u64) < min {
+                    return fail();
+                }
+            }
+            for (i, item) in items.iter().enumerate() {
+                validate(root, &schema["items"], item, &format!("{}/{}", at, i))?;
+            }
+        }
+
+        if let Some(object) = value.as_object() {
+            for required in schema["required"].as_array().into_iter().flatten() {
+                if !object.contains_key(required.as_str().unwrap()) {
+                    return fail();
+                }
+            }
+            for (key, field) in object {
+                let at = format!("{}/{}", at, key);
+                match schema["properties"].get(key) {
+                    Some(property) => validate(root, property, field, &at)?,
+                    None => match &schema["additionalProperties"] {
+                        Value::Bool(false) => return Err(at),
+                        Value::Null => {}
+                        additional => validate(root, additional, field, &at)?,
+                    },
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check(schema: &Value, value: &Value) -> Result<(), String> {
+        validate(schema, schema, value, "")
+    }
+
+    fn to_json<M: crate::metadata::Metadata>(
+        signed: &crate::metadata::SignedMetadata<Pouf1, M>,
+    ) -> Value {
+        serde_json::from_slice(signed.to_raw().unwrap().as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn schemas_accept_metadata() {
+        let signed_root = RootMetadataBuilder::new()
+            .root_key(KEYS[0].public().clone())
+            .snapshot_key(KEYS[0].public().clone())
+            .targets_key(KEYS[0].public().clone())
+            .timestamp_key(KEYS[0].public().clone())
+            .custom_role_key(
+                MetadataPath::new("mirrors").unwrap(),
+                KEYS[1].public().clone(),
+            )
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap();
+        assert_eq!(check(&root(), &to_json(&signed_root)), Ok(()));
+
+        let delegation = Delegation::builder(MetadataPath::new("delegated").unwrap())
+            .key(KEYS[1].public())
+            .delegate_path(TargetPath::new("delegated/").unwrap())
+            .build()
+            .unwrap();
+        let signed_targets = TargetsMetadataBuilder::new()
+            .insert_target_from_slice(
+                TargetPath::new("foo").unwrap(),
+                b"foo",
+                &[HashAlgorithm::Sha256],
+            )
+            .unwrap()
+            .delegations(
+                Delegations::builder()
+                    .key(KEYS[1].public().clone())
+                    .role(delegation)
+                    .build()
+                    .unwrap(),
+            )
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap();
+        assert_eq!(check(&targets(), &to_json(&signed_targets)), Ok(()));
+
+        let signed_snapshot = SnapshotMetadataBuilder::new()
+            .insert_metadata(&signed_targets, &[HashAlgorithm::Sha256])
+            .unwrap()
+            .signed::<Pouf1>(&KEYS[0])
+            .unwrap();
+        assert_eq!(check(&snapshot(), &to_json(&signed_snapshot)), Ok(()));
+
+        let signed_timestamp =
+            TimestampMetadataBuilder::from_snapshot(&signed_snapshot, &[HashAlgorithm::Sha256])
+                .unwrap()
+                .signed::<Pouf1>(&KEYS[0])
+                .unwrap();
+        assert_eq!(check(&timestamp(), &to_json(&signed_timestamp)), Ok(()));
+
+        // Each schema only accepts its own role.
+        assert_eq!(
+            check(&snapshot(), &to_json(&signed_timestamp)),
+            Err("/signed/_type".into())
+        );
+    }
+
+    #[test]
+    fn schemas_reject_malformed_metadata() {
+        let signed_timestamp = TimestampMetadataBuilder::from_snapshot(
+            &SnapshotMetadataBuilder::new()
+                .signed::<Pouf1>(&KEYS[0])
+                .unwrap(),
+            &[HashAlgorithm::Sha256],
+        )
+        .unwrap()
+        .signed::<Pouf1>(&KEYS[0])
+        .unwrap();
+        let json = to_json(&signed_timestamp);
+
+        let mut missing_version = json.clone();
+        missing_version["signed"]
+            .as_object_mut()
+            .unwrap()
+            .remove("version");
+        assert_eq!(check(&timestamp(), &missing_version), Err("/signed".into()));
+
+        let mut zero_version = json.clone();
+        zero_version["signed"]["version"] = json!(0);
+        assert_eq!(
+            check(&timestamp(), &zero_version),
+            Err("/signed/version".into())
+        );
+
+        let mut extra_meta = json.clone();
+        extra_meta["signed"]["meta"]["targets.json"] = json!({ "version": 1 });
+        assert_eq!(
+            check(&timestamp(), &extra_meta),
+            Err("/signed/meta/targets.json".into())
+        );
+
+        let mut extra_field = json;
+        extra_field["signed"]["custom"] = json!("allowed");
+        assert_eq!(check(&timestamp(), &extra_field), Ok(()));
+        extra_field["extra"] = json!("not allowed");
+        assert_eq!(check(&timestamp(), &extra_field), Err("/extra".into()));
+    }
+}
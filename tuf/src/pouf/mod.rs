@@ -1,14 +1,47 @@
 //! Structures and functions to aid in various TUF data pouf formats.
 
+#[cfg(feature = "cbor")]
+mod cbor;
+mod conformance;
+mod der;
+#[cfg(feature = "json-schema")]
+pub mod json_schema;
+#[cfg(feature = "msgpack")]
+mod msgpack;
 pub(crate) mod pouf1;
+mod protobuf;
+#[cfg(feature = "cbor")]
+pub use cbor::Cbor;
+pub use conformance::verify_impl;
+pub use der::Der;
+#[cfg(feature = "msgpack")]
+pub use msgpack::MessagePack;
 pub use pouf1::Pouf1;
+pub use protobuf::Protobuf;
 
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
+use std::io::Read;
 
+use crate::crypto::PrivateKey;
+use crate::error::Error;
+use crate::metadata::{Metadata, RawSignedMetadata, SignedMetadata};
 use crate::Result;
 
 /// The format used for data interchange, serialization, and deserialization.
+///
+/// # Implementing a pouf
+///
+/// A pouf can be implemented outside of this crate. The metadata types serialize themselves
+/// through `serde` into the same shapes as in [Pouf1], so a pouf only decides how those shapes are
+/// encoded. [Pouf::RawData] holds the `signed` portion of metadata while its signatures are
+/// checked; a pouf that has no value type of its own can use [serde_json::Value], as [Der] and
+/// [Protobuf] do.
+///
+/// Signatures are made over [Pouf::canonicalize], so it must give the same bytes for the same
+/// data no matter how that data was produced or parsed, and [Pouf::from_slice] must parse those
+/// bytes back into the same data. Use [verify_impl] in the tests of a pouf to check these
+/// requirements against every kind of metadata.
 pub trait Pouf: Sync {
     /// The type of data that is contained in the `signed` portion of metadata.
     type RawData: Serialize + DeserializeOwned + PartialEq;
@@ -33,4 +66,94 @@ pub trait Pouf: Sync {
     fn from_slice<T>(slice: &[u8]) -> Result<T>
     where
         T: DeserializeOwned;
+
+    /// Read a struct from `reader`. Formats that can parse incrementally should override this, so
+    /// that the whole stream is never buffered.
+    /// Defaults to reading the stream into memory and parsing it with [Pouf::from_slice].
+    fn from_reader<T, R>(mut reader: R) -> Result<T>
+    where
+        T: DeserializeOwned,
+        R: Read,
+    {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        Self::from_slice(&buf)
+    }
+
+    /// Serialize `raw_data` in a form that is easier for people to read, such as indented JSON,
+    /// for metadata that is stored for review. Signatures are still made over the bytes of
+    /// [Pouf::canonicalize]. Defaults to the canonical form.
+    fn pretty_print(raw_data: &Self::RawData) -> Result<Vec<u8>> {
+        Self::canonicalize(raw_data)
+    }
+
+    /// Pad serialized metadata to a multiple of `size` bytes, in a way that [Pouf::from_slice]
+    /// ignores, so that the size of the metadata reveals less about its contents. Metadata that is
+    /// larger than `size` is rounded up to the next multiple of it, rather than rejected.
+    ///
+    /// Signatures are made over [Pouf::canonicalize] of the signed portion, so they do not cover
+    /// the padding, but the hashes and lengths that describe the metadata in snapshot and timestamp
+    /// metadata do.
+    ///
+    /// By default, returns [Error::IllegalArgument] since padding is not supported.
+    fn pad(bytes: Vec<u8>, size: usize) -> Result<Vec<u8>> {
+        let _ = (bytes, size);
+        Err(Error::IllegalArgument(format!(
+            "the {} pouf does not support padding",
+            Self::extension()
+        )))
+    }
+}
+
+/// Convert `raw` metadata from the pouf `F` to the pouf `T`, such as from [Pouf1] to [Der], and
+/// sign it with every key in `keys`.
+///
+/// Signatures are made over the canonical encoding of a pouf, so the signatures of `raw` are not
+/// valid for the converted metadata and are dropped. The signatures of `raw` are not verified
+/// either, so only convert metadata that is trusted. Since the encoding changes, so do the lengths
+/// and hashes of the converted metadata: convert the targets metadata of a repository first, and
+/// then build new snapshot and timestamp metadata that describe the converted metadata, such as
+/// with a [RepoBuilder](crate::repo_builder::RepoBuilder).
+///
+/// ```
+/// # use tuf::crypto::{Ed25519PrivateKey, PrivateKey};
+/// # use tuf::metadata::RootMetadataBuilder;
+/// # use tuf::pouf::{self, Der, Pouf1};
+/// # use tuf::Database;
+/// #
+/// # let key: &[u8] = include_bytes!("../../tests/ed25519/ed25519-1.pk8.der");
+/// # let key = Ed25519PrivateKey::from_pkcs8(&key).unwrap();
+/// let json_root = RootMetadataBuilder::new()
+///     .root_key(key.public().clone())
+///     .snapshot_key(key.public().clone())
+///     .targets_key(key.public().clone())
+///     .timestamp_key(key.public().clone())
+///     .signed::<Pouf1>(&key)
+///     .unwrap()
+///     .to_raw()
+///     .unwrap();
+///
+/// let der_root = pouf::convert::<Pouf1, Der, _>(&json_root, &[&key]).unwrap();
+/// assert!(Database::<Der>::from_trusted_root(&der_root).is_ok());
+/// ```
+pub fn convert<F, T, M>(
+    raw: &RawSignedMetadata<F, M>,
+    keys: &[&dyn PrivateKey],
+) -> Result<RawSignedMetadata<T, M>>
+where
+    F: Pouf,
+    T: Pouf,
+    M: Metadata,
+{
+    let metadata = raw.parse_untrusted()?.assume_valid()?;
+
+    let (first, rest) = keys.split_first().ok_or_else(|| {
+        Error::IllegalArgument("converted metadata must be signed by at least one key".into())
+    })?;
+    let mut signed = SignedMetadata::<T, M>::new(&metadata, *first)?;
+    for key in rest {
+        signed.add_signature(*key)?;
+    }
+
+    signed.to_raw()
 }
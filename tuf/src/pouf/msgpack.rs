@@ -0,0 +1,193 @@
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::Value;
+use std::fmt::Debug;
+
+use crate::error::Error;
+use crate::pouf::Pouf;
+use crate::Result;
+
+/// TUF metadata encoded with [MessagePack](https://msgpack.org/) instead of JSON.
+///
+/// The schema is the same as the one of [Pouf1](crate::pouf::Pouf1), with every JSON object,
+/// array, string, number and boolean replaced by the matching MessagePack map, array, str, int
+/// and bool. Metadata is signed over its canonical encoding, which follows these rules:
+///
+/// * Every int, str, array and map is encoded with the shortest format that can hold it.
+///   Non-negative integers always use a positive fixint or uint format, and negative integers a
+///   negative fixint or int format.
+/// * The entries of every map are sorted by the UTF-8 bytes of their keys.
+/// * Floating point numbers, binary data and extension types are not used by TUF metadata, and
+///   cannot be canonicalized.
+///
+/// Parsing accepts any MessagePack encoding of the schema, in the same way that
+/// [Pouf1](crate::pouf::Pouf1) accepts JSON with any whitespace, since signatures are checked
+/// against the canonical encoding of the parsed metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessagePack;
+
+impl Pouf for MessagePack {
+    type RawData = Value;
+
+    /// ```
+    /// # use tuf::pouf::{MessagePack, Pouf};
+    /// assert_eq!(MessagePack::extension(), "msgpack");
+    /// ```
+    fn extension() -> &'static str {
+        "msgpack"
+    }
+
+    /// ```
+    /// # use serde_json::json;
+    /// # use tuf::pouf::{MessagePack, Pouf};
+    /// let out = MessagePack::canonicalize(&json!({"foo": "bar", "baz": 300})).unwrap();
+    /// assert_eq!(out, b"\x82\xa3baz\xcd\x01\x2c\xa3foo\xa3bar");
+    /// ```
+    fn canonicalize(raw_data: &Self::RawData) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        encode(raw_data, &mut buf)?;
+        Ok(buf)
+    }
+
+    fn deserialize<T>(raw_data: &Self::RawData) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(serde_json::from_value(raw_data.clone())?)
+    }
+
+    fn serialize<T>(data: &T) -> Result<Self::RawData>
+    where
+        T: Serialize,
+    {
+        Ok(serde_json::to_value(data)?)
+    }
+
+    /// ```
+    /// # use tuf::pouf::{MessagePack, Pouf};
+    /// # use std::collections::HashMap;
+    /// let msgpack: &[u8] = b"\x81\xa3foo\xa3bar";
+    /// let map: HashMap<String, String> = MessagePack::from_slice(msgpack).unwrap();
+    /// assert_eq!(map["foo"], "bar");
+    /// ```
+    fn from_slice<T>(slice: &[u8]) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        rmp_serde::from_slice(slice).map_err(encoding_error)
+    }
+}
+
+fn encode(value: &Value, buf: &mut Vec<u8>) -> Result<()> {
+    match value {
+        Value::Null => rmp::encode::write_nil(buf).map_err(encoding_error)?,
+        Value::Bool(b) => rmp::encode::write_bool(buf, *b).map_err(encoding_error)?,
+        Value::Number(n) => {
+            if let Some(n) = n.as_u64() {
+                let _ = rmp::encode::write_uint(buf, n).map_err(encoding_error)?;
+            } else if let Some(n) = n.as_i64() {
+                let _ = rmp::encode::write_sint(buf, n).map_err(encoding_error)?;
+            } else {
+                return Err(Error::Encoding("only i64 and u64 are supported".into()));
+            }
+        }
+        Value::String(s) => rmp::encode::write_str(buf, s).map_err(encoding_error)?,
+        Value::Array(items) => {
+            let _ =
+                rmp::encode::write_array_len(buf, len_u32(items.len())?).map_err(encoding_error)?;
+            for item in items {
+                encode(item, buf)?;
+            }
+        }
+        Value::Object(entries) => {
+            let mut entries = entries.iter().collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+
+            let _ =
+                rmp::encode::write_map_len(buf, len_u32(entries.len())?).map_err(encoding_error)?;
+            for (key, value) in entries {
+                rmp::encode::write_str(buf, key).map_err(encoding_error)?;
+                encode(value, buf)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn len_u32(len: usize) -> Result<u32> {
+    u32::try_from(len).map_err(|_| Error::Encoding("MessagePack container is too long".into()))
+}
+
+fn encoding_error<E: Debug>(err: E) -> Error {
+    Error::Encoding(format!("{:?}", err))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{Ed25519PrivateKey, PrivateKey};
+    use crate::database::Database;
+    use crate::metadata::{Metadata, RootMetadataBuilder};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn canonicalize_uses_shortest_formats() {
+        let cases: &[(Value, &[u8])] = &[
+            (json!(0), b"\x00"),
+            (json!(127), b"\x7f"),
+            (json!(128), b"\xcc\x80"),
+            (json!(65536), b"\xce\x00\x01\x00\x00"),
+            (json!(-1), b"\xff"),
+            (json!(-33), b"\xd0\xdf"),
+            (json!(u64::MAX), b"\xcf\xff\xff\xff\xff\xff\xff\xff\xff"),
+            (json!("a"), b"\xa1a"),
+            (json!([null, true]), b"\x92\xc0\xc3"),
+            (json!({"b": 1, "a": 2}), b"\x82\xa1a\x02\xa1b\x01"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(
+                &MessagePack::canonicalize(value).unwrap(),
+                expected,
+                "{}",
+                value
+            );
+        }
+
+        assert_eq!(
+            &MessagePack::canonicalize(&json!("x".repeat(32))).unwrap()[..2],
+            b"\xd9\x20"
+        );
+        assert!(MessagePack::canonicalize(&json!(1.5)).is_err());
+    }
+
+    #[test]
+    fn from_slice_accepts_non_canonical_encodings() {
+        // A map with unsorted keys and an integer in a longer format than needed.
+        let value: Value = MessagePack::from_slice(b"\x82\xa1b\xcd\x00\x01\xa1a\x02").unwrap();
+        assert_eq!(value, json!({"a": 2, "b": 1}));
+        assert_eq!(
+            MessagePack::canonicalize(&value).unwrap(),
+            b"\x82\xa1a\x02\xa1b\x01"
+        );
+    }
+
+    #[test]
+    fn msgpack_root_round_trip() {
+        let key: &[u8] = include_bytes!("../../tests/ed25519/ed25519-1.pk8.der");
+        let key = Ed25519PrivateKey::from_pkcs8(key).unwrap();
+        let signed = RootMetadataBuilder::new()
+            .version(3)
+            .root_key(key.public().clone())
+            .snapshot_key(key.public().clone())
+            .targets_key(key.public().clone())
+            .timestamp_key(key.public().clone())
+            .signed::<MessagePack>(&key)
+            .unwrap();
+        let raw_root = signed.to_raw().unwrap();
+
+        let db = Database::<MessagePack>::from_trusted_root(&raw_root).unwrap();
+        assert_eq!(db.trusted_root().version(), 3);
+        assert_eq!(&**db.trusted_root(), &signed.assume_valid().unwrap());
+    }
+}
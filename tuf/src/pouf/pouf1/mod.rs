@@ -261,6 +261,61 @@ impl Pouf for Pouf1 {
     {
         Ok(serde_json::from_slice(slice)?)
     }
+
+    /// Pads with trailing spaces, which are ignored by JSON parsers.
+    ///
+    /// ```
+    /// # use tuf::pouf::{Pouf, Pouf1};
+    /// let padded = Pouf1::pad(b"{}".to_vec(), 8).unwrap();
+    /// assert_eq!(padded, b"{}      ");
+    /// let _: serde_json::Value = Pouf1::from_slice(&padded).unwrap();
+    ///
+    /// // Metadata larger than the size is padded to the next multiple of it.
+    /// assert_eq!(Pouf1::pad(vec![b'1'; 9], 8).unwrap().len(), 16);
+    /// ```
+    fn pad(mut bytes: Vec<u8>, size: usize) -> Result<Vec<u8>> {
+        if size == 0 {
+            return Err(Error::IllegalArgument(
+                "metadata cannot be padded to zero bytes".into(),
+            ));
+        }
+
+        let len = match bytes.len() % size {
+            0 if !bytes.is_empty() => bytes.len(),
+            rem => bytes.len() + size - rem,
+        };
+        bytes.resize(len, b' ');
+        Ok(bytes)
+    }
+
+    /// Parses the JSON as it is read, without buffering the whole stream.
+    ///
+    /// ```
+    /// # use tuf::pouf::{Pouf, Pouf1};
+    /// # use std::collections::HashMap;
+    /// let jsn: &[u8] = br#"{"foo": "bar", "baz": "quux"}"#;
+    /// let map: HashMap<String, String> = Pouf1::from_reader(jsn).unwrap();
+    /// assert_eq!(map["foo"], "bar");
+    /// ```
+    fn from_reader<T, R>(reader: R) -> Result<T>
+    where
+        T: DeserializeOwned,
+        R: std::io::Read,
+    {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// ```
+    /// # use serde_json::json;
+    /// # use tuf::pouf::{Pouf, Pouf1};
+    /// let out = Pouf1::pretty_print(&json!({"foo": "bar", "baz": [1]})).unwrap();
+    /// assert_eq!(out, b"{\n  \"baz\": [\n    1\n  ],\n  \"foo\": \"bar\"\n}\n");
+    /// ```
+    fn pretty_print(raw_data: &Self::RawData) -> Result<Vec<u8>> {
+        let mut buf = serde_json::to_vec_pretty(raw_data)?;
+        buf.push(b'\n');
+        Ok(buf)
+    }
 }
 
 fn canonicalize(jsn: &serde_json::Value) -> std::result::Result<Vec<u8>, String> {
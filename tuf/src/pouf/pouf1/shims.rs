@@ -8,19 +8,20 @@ use {
     chrono::{offset::Utc, prelude::*},
     serde_derive::{Deserialize, Serialize},
     std::{
-        collections::{BTreeMap, HashSet},
+        collections::{BTreeMap, HashMap, HashSet},
         marker::PhantomData,
     },
 };
 
-const SPEC_VERSION: &str = "1.0";
-
-// Ensure the given spec version matches our spec version.
+// Parse the spec version of the metadata. Any well-formed version is accepted here, since whether
+// the major version is supported is up to the policy of the database that verifies the metadata.
 //
 // We also need to handle the literal "1.0" here, despite that fact that it is not a valid version
 // according to the SemVer spec, because it is already baked into some of the old roots.
-fn valid_spec_version(other: &str) -> bool {
-    matches!(other, "1.0" | "1.0.0")
+fn parse_spec_version(spec_version: &str) -> Result<metadata::SpecVersion> {
+    spec_version
+        .parse()
+        .map_err(|_| Error::Encoding(format!("Unknown spec version {}", spec_version)))
 }
 
 fn parse_datetime(ts: &str) -> Result<DateTime<Utc>> {
@@ -29,6 +30,13 @@ fn parse_datetime(ts: &str) -> Result<DateTime<Utc>> {
         .map_err(|e| Error::Encoding(format!("Can't parse DateTime: {:?}", e)))
 }
 
+// Collect the unrecognized fields of the metadata into a map with a stable order.
+fn unrecognized_fields_from(
+    fields: &HashMap<String, serde_json::Value>,
+) -> BTreeMap<String, serde_json::Value> {
+    fields.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+}
+
 fn format_datetime(ts: &DateTime<Utc>) -> String {
     format!(
         "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
@@ -52,13 +60,15 @@ pub struct RootMetadata {
     #[serde(deserialize_with = "deserialize_reject_duplicates::deserialize")]
     keys: BTreeMap<crypto::KeyId, crypto::PublicKey>,
     roles: RoleDefinitions,
+    #[serde(flatten)]
+    unrecognized_fields: BTreeMap<String, serde_json::Value>,
 }
 
 impl RootMetadata {
     pub fn from(meta: &metadata::RootMetadata) -> Result<Self> {
         Ok(RootMetadata {
             typ: metadata::Role::Root,
-            spec_version: SPEC_VERSION.to_string(),
+            spec_version: meta.spec_version().to_string(),
             version: meta.version(),
             expires: format_datetime(meta.expires()),
             consistent_snapshot: meta.consistent_snapshot(),
@@ -72,7 +82,13 @@ impl RootMetadata {
                 snapshot: meta.snapshot().clone(),
                 targets: meta.targets().clone(),
                 timestamp: meta.timestamp().clone(),
+                custom: meta
+                    .custom_roles()
+                    .iter()
+                    .map(|(role, definition)| (role.clone(), definition.clone()))
+                    .collect(),
             },
+            unrecognized_fields: unrecognized_fields_from(meta.unrecognized_fields()),
         })
     }
 
@@ -84,12 +100,7 @@ impl RootMetadata {
             )));
         }
 
-        if !valid_spec_version(&self.spec_version) {
-            return Err(Error::Encoding(format!(
-                "Unknown spec version {}",
-                self.spec_version
-            )));
-        }
+        let spec_version = parse_spec_version(&self.spec_version)?;
 
         // Ignore all keys with incorrect key IDs. We should give an error if the key ID is not
         // correct according to TUF spec. However, due to backward compatibility, we may receive
@@ -100,7 +111,7 @@ impl RootMetadata {
             .filter(|(key_id, pkey)| key_id == pkey.key_id())
             .collect();
 
-        metadata::RootMetadata::new(
+        let root = metadata::RootMetadata::new(
             self.version,
             parse_datetime(&self.expires)?,
             self.consistent_snapshot,
@@ -109,7 +120,12 @@ impl RootMetadata {
             self.roles.snapshot,
             self.roles.targets,
             self.roles.timestamp,
-        )
+        )?;
+
+        Ok(root
+            .with_spec_version(spec_version)
+            .with_custom_roles(self.roles.custom.into_iter().collect())
+            .with_unrecognized_fields(self.unrecognized_fields.into_iter().collect()))
     }
 }
 
@@ -119,6 +135,49 @@ struct RoleDefinitions {
     snapshot: metadata::RoleDefinition<metadata::SnapshotMetadata>,
     targets: metadata::RoleDefinition<metadata::TargetsMetadata>,
     timestamp: metadata::RoleDefinition<metadata::TimestampMetadata>,
+    #[serde(flatten)]
+    custom: BTreeMap<metadata::MetadataPath, metadata::RoleDefinition<metadata::CustomMetadata>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CustomMetadata {
+    #[serde(rename = "_type")]
+    typ: String,
+    spec_version: String,
+    version: u32,
+    expires: String,
+    #[serde(flatten)]
+    fields: BTreeMap<String, serde_json::Value>,
+}
+
+impl CustomMetadata {
+    pub fn from(metadata: &metadata::CustomMetadata) -> Self {
+        CustomMetadata {
+            typ: metadata.role().as_str().into(),
+            spec_version: metadata.spec_version().to_string(),
+            version: metadata.version(),
+            expires: format_datetime(metadata.expires()),
+            fields: unrecognized_fields_from(metadata.fields()),
+        }
+    }
+
+    pub fn try_into(self) -> Result<metadata::CustomMetadata> {
+        let role = metadata::MetadataPath::new(self.typ)?;
+        if !metadata::Role::Custom.fuzzy_matches_path(&role) {
+            return Err(Error::Encoding(format!(
+                "Attempted to decode custom metadata labeled as {:?}",
+                role.as_str()
+            )));
+        }
+
+        let spec_version = parse_spec_version(&self.spec_version)?;
+
+        Ok(
+            metadata::CustomMetadata::new(&role, self.version, parse_datetime(&self.expires)?)?
+                .with_spec_version(spec_version)
+                .with_fields(self.fields.into_iter().collect()),
+        )
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -172,6 +231,10 @@ pub struct TimestampMetadata {
     version: u32,
     expires: String,
     meta: TimestampMeta,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    merkle_root: Option<crypto::HashValue>,
+    #[serde(flatten)]
+    unrecognized_fields: BTreeMap<String, serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -185,12 +248,14 @@ impl TimestampMetadata {
     pub fn from(metadata: &metadata::TimestampMetadata) -> Result<Self> {
         Ok(TimestampMetadata {
             typ: metadata::Role::Timestamp,
-            spec_version: SPEC_VERSION.to_string(),
+            spec_version: metadata.spec_version().to_string(),
             version: metadata.version(),
             expires: format_datetime(metadata.expires()),
             meta: TimestampMeta {
                 snapshot: metadata.snapshot().clone(),
             },
+            merkle_root: metadata.merkle_root().cloned(),
+            unrecognized_fields: unrecognized_fields_from(metadata.unrecognized_fields()),
         })
     }
 
@@ -202,18 +267,20 @@ impl TimestampMetadata {
             )));
         }
 
-        if !valid_spec_version(&self.spec_version) {
-            return Err(Error::Encoding(format!(
-                "Unknown spec version {}",
-                self.spec_version
-            )));
-        }
+        let spec_version = parse_spec_version(&self.spec_version)?;
 
-        metadata::TimestampMetadata::new(
+        let timestamp = metadata::TimestampMetadata::new(
             self.version,
             parse_datetime(&self.expires)?,
             self.meta.snapshot,
-        )
+        )?
+        .with_spec_version(spec_version)
+        .with_unrecognized_fields(self.unrecognized_fields.into_iter().collect());
+
+        Ok(match self.merkle_root {
+            Some(merkle_root) => timestamp.with_merkle_root(merkle_root),
+            None => timestamp,
+        })
     }
 }
 
@@ -226,13 +293,15 @@ pub struct SnapshotMetadata {
     expires: String,
     #[serde(deserialize_with = "deserialize_reject_duplicates::deserialize")]
     meta: BTreeMap<String, metadata::MetadataDescription<metadata::TargetsMetadata>>,
+    #[serde(flatten)]
+    unrecognized_fields: BTreeMap<String, serde_json::Value>,
 }
 
 impl SnapshotMetadata {
     pub fn from(metadata: &metadata::SnapshotMetadata) -> Result<Self> {
         Ok(SnapshotMetadata {
             typ: metadata::Role::Snapshot,
-            spec_version: SPEC_VERSION.to_string(),
+            spec_version: metadata.spec_version().to_string(),
             version: metadata.version(),
             expires: format_datetime(metadata.expires()),
             meta: metadata
@@ -240,6 +309,7 @@ impl SnapshotMetadata {
                 .iter()
                 .map(|(p, d)| (format!("{}.json", p), d.clone()))
                 .collect(),
+            unrecognized_fields: unrecognized_fields_from(metadata.unrecognized_fields()),
         })
     }
 
@@ -251,14 +321,9 @@ impl SnapshotMetadata {
             )));
         }
 
-        if !valid_spec_version(&self.spec_version) {
-            return Err(Error::Encoding(format!(
-                "Unknown spec version {}",
-                self.spec_version
-            )));
-        }
+        let spec_version = parse_spec_version(&self.spec_version)?;
 
-        metadata::SnapshotMetadata::new(
+        let snapshot = metadata::SnapshotMetadata::new(
             self.version,
             parse_datetime(&self.expires)?,
             self.meta
@@ -277,7 +342,11 @@ impl SnapshotMetadata {
                     Ok((p, d))
                 })
                 .collect::<Result<_>>()?,
-        )
+        )?;
+
+        Ok(snapshot
+            .with_spec_version(spec_version)
+            .with_unrecognized_fields(self.unrecognized_fields.into_iter().collect()))
     }
 }
 
@@ -291,13 +360,15 @@ pub struct TargetsMetadata {
     targets: BTreeMap<metadata::TargetPath, metadata::TargetDescription>,
     #[serde(default, skip_serializing_if = "metadata::Delegations::is_empty")]
     delegations: metadata::Delegations,
+    #[serde(flatten)]
+    unrecognized_fields: BTreeMap<String, serde_json::Value>,
 }
 
 impl TargetsMetadata {
     pub fn from(metadata: &metadata::TargetsMetadata) -> Result<Self> {
         Ok(TargetsMetadata {
             typ: metadata::Role::Targets,
-            spec_version: SPEC_VERSION.to_string(),
+            spec_version: metadata.spec_version().to_string(),
             version: metadata.version(),
             expires: format_datetime(metadata.expires()),
             targets: metadata
@@ -306,6 +377,7 @@ impl TargetsMetadata {
                 .map(|(p, d)| (p.clone(), d.clone()))
                 .collect(),
             delegations: metadata.delegations().clone(),
+            unrecognized_fields: unrecognized_fields_from(metadata.unrecognized_fields()),
         })
     }
 
@@ -317,19 +389,18 @@ impl TargetsMetadata {
             )));
         }
 
-        if !valid_spec_version(&self.spec_version) {
-            return Err(Error::Encoding(format!(
-                "Unknown spec version {}",
-                self.spec_version
-            )));
-        }
+        let spec_version = parse_spec_version(&self.spec_version)?;
 
-        metadata::TargetsMetadata::new(
+        let targets = metadata::TargetsMetadata::new(
             self.version,
             parse_datetime(&self.expires)?,
             self.targets.into_iter().collect(),
             self.delegations,
-        )
+        )?;
+
+        Ok(targets
+            .with_spec_version(spec_version)
+            .with_unrecognized_fields(self.unrecognized_fields.into_iter().collect()))
     }
 }
 
@@ -386,7 +457,10 @@ pub struct Delegation {
     threshold: u32,
     #[serde(rename = "keyids")]
     key_ids: Vec<crypto::KeyId>,
-    paths: Vec<metadata::TargetPath>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    paths: Option<Vec<metadata::TargetPath>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path_hash_prefixes: Option<Vec<String>>,
 }
 
 impl From<&metadata::Delegation> for Delegation {
@@ -405,12 +479,26 @@ impl From<&metadata::Delegation> for Delegation {
             .collect::<Vec<crypto::KeyId>>();
         key_ids.sort();
 
+        // A delegation has either paths or path hash prefixes, never both.
+        let (paths, path_hash_prefixes) = if delegation.path_hash_prefixes().is_empty() {
+            (Some(paths), None)
+        } else {
+            let mut prefixes = delegation
+                .path_hash_prefixes()
+                .iter()
+                .cloned()
+                .collect::<Vec<String>>();
+            prefixes.sort();
+            (None, Some(prefixes))
+        };
+
         Delegation {
             name: delegation.name().clone(),
             terminating: delegation.terminating(),
             threshold: delegation.threshold(),
             key_ids,
             paths,
+            path_hash_prefixes,
         }
     }
 }
@@ -426,19 +514,94 @@ impl TryFrom<Delegation> for metadata::Delegation {
             return Err(Error::Encoding("Non-unique delegation key IDs.".into()));
         }
 
-        let delegation_paths_len = delegation.paths.len();
-        let paths = delegation.paths.into_iter().collect::<HashSet<_>>();
+        match (delegation.paths, delegation.path_hash_prefixes) {
+            (Some(paths), None) => {
+                let delegation_paths_len = paths.len();
+                let paths = paths.into_iter().collect::<HashSet<_>>();
+
+                if paths.len() != delegation_paths_len {
+                    return Err(Error::Encoding("Non-unique delegation paths.".into()));
+                }
+
+                metadata::Delegation::new(
+                    delegation.name,
+                    delegation.terminating,
+                    delegation.threshold,
+                    key_ids,
+                    paths,
+                )
+            }
+            (None, Some(prefixes)) => {
+                let delegation_prefixes_len = prefixes.len();
+                let prefixes = prefixes.into_iter().collect::<HashSet<_>>();
+
+                if prefixes.len() != delegation_prefixes_len {
+                    return Err(Error::Encoding(
+                        "Non-unique delegation path hash prefixes.".into(),
+                    ));
+                }
 
-        if paths.len() != delegation_paths_len {
-            return Err(Error::Encoding("Non-unique delegation paths.".into()));
+                metadata::Delegation::with_path_hash_prefixes(
+                    delegation.name,
+                    delegation.terminating,
+                    delegation.threshold,
+                    key_ids,
+                    prefixes,
+                )
+            }
+            (Some(_), Some(_)) => Err(Error::Encoding(
+                "Delegation cannot have both paths and path_hash_prefixes.".into(),
+            )),
+            (None, None) => Err(Error::Encoding(
+                "Delegation must have either paths or path_hash_prefixes.".into(),
+            )),
         }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SuccinctRoles {
+    #[serde(rename = "keyids")]
+    key_ids: Vec<crypto::KeyId>,
+    threshold: u32,
+    bit_length: u8,
+    name_prefix: String,
+}
 
-        metadata::Delegation::new(
-            delegation.name,
-            delegation.terminating,
-            delegation.threshold,
+impl From<&metadata::SuccinctRoles> for SuccinctRoles {
+    fn from(succinct_roles: &metadata::SuccinctRoles) -> Self {
+        let mut key_ids = succinct_roles
+            .key_ids()
+            .iter()
+            .cloned()
+            .collect::<Vec<crypto::KeyId>>();
+        key_ids.sort();
+
+        SuccinctRoles {
             key_ids,
-            paths,
+            threshold: succinct_roles.threshold(),
+            bit_length: succinct_roles.bit_length(),
+            name_prefix: succinct_roles.name_prefix().into(),
+        }
+    }
+}
+
+impl TryFrom<SuccinctRoles> for metadata::SuccinctRoles {
+    type Error = Error;
+
+    fn try_from(succinct_roles: SuccinctRoles) -> Result<Self> {
+        let succinct_roles_key_ids_len = succinct_roles.key_ids.len();
+        let key_ids = succinct_roles.key_ids.into_iter().collect::<HashSet<_>>();
+
+        if key_ids.len() != succinct_roles_key_ids_len {
+            return Err(Error::Encoding("Non-unique succinct roles key IDs.".into()));
+        }
+
+        metadata::SuccinctRoles::new(
+            key_ids,
+            succinct_roles.threshold,
+            succinct_roles.bit_length,
+            succinct_roles.name_prefix,
         )
     }
 }
@@ -447,19 +610,115 @@ impl TryFrom<Delegation> for metadata::Delegation {
 pub struct Delegations {
     #[serde(deserialize_with = "deserialize_reject_duplicates::deserialize")]
     keys: BTreeMap<crypto::KeyId, crypto::PublicKey>,
-    roles: Vec<Delegation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    roles: Option<Vec<DelegationEntry>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    succinct_roles: Option<SuccinctRoles>,
+}
+
+/// An entry in the `roles` of delegations, which is either a single role or a TAP 3 multi-role
+/// delegation.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DelegationEntry {
+    Single(Delegation),
+    Multi(MultiRoleDelegation),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MultiRoleDelegation {
+    min_roles_in_agreement: u32,
+    terminating: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    paths: Option<Vec<metadata::TargetPath>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path_hash_prefixes: Option<Vec<String>>,
+    roles: Vec<MultiRoleDelegationRole>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MultiRoleDelegationRole {
+    name: metadata::MetadataPath,
+    #[serde(rename = "keyids")]
+    key_ids: Vec<crypto::KeyId>,
+    threshold: u32,
+}
+
+impl From<&metadata::MultiRoleDelegation> for MultiRoleDelegation {
+    fn from(delegation: &metadata::MultiRoleDelegation) -> Self {
+        // The roles share everything but their names, keys and thresholds, so reuse the
+        // serialization of the first role for the shared fields.
+        let first = Delegation::from(&delegation.roles()[0]);
+
+        MultiRoleDelegation {
+            min_roles_in_agreement: delegation.min_roles_in_agreement(),
+            terminating: first.terminating,
+            paths: first.paths,
+            path_hash_prefixes: first.path_hash_prefixes,
+            roles: delegation
+                .roles()
+                .iter()
+                .map(|role| {
+                    let role = Delegation::from(role);
+                    MultiRoleDelegationRole {
+                        name: role.name,
+                        key_ids: role.key_ids,
+                        threshold: role.threshold,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<MultiRoleDelegation> for metadata::MultiRoleDelegation {
+    type Error = Error;
+
+    fn try_from(delegation: MultiRoleDelegation) -> Result<Self> {
+        let roles = delegation
+            .roles
+            .into_iter()
+            .map(|role| {
+                Delegation {
+                    name: role.name,
+                    terminating: delegation.terminating,
+                    threshold: role.threshold,
+                    key_ids: role.key_ids,
+                    paths: delegation.paths.clone(),
+                    path_hash_prefixes: delegation.path_hash_prefixes.clone(),
+                }
+                .try_into()
+            })
+            .collect::<Result<Vec<metadata::Delegation>>>()?;
+
+        metadata::MultiRoleDelegation::new(delegation.min_roles_in_agreement, roles)
+    }
 }
 
 impl From<&metadata::Delegations> for Delegations {
     fn from(delegations: &metadata::Delegations) -> Delegations {
-        let mut roles = delegations
-            .roles()
+        // The roles are written in the order they are consulted, which is what the signature
+        // covers.
+        let roles = delegations
+            .entries()
             .iter()
-            .map(Delegation::from)
-            .collect::<Vec<Delegation>>();
+            .map(|entry| match entry {
+                metadata::DelegationEntry::Single(delegation) => {
+                    DelegationEntry::Single(delegation.into())
+                }
+                metadata::DelegationEntry::Multi(delegation) => {
+                    DelegationEntry::Multi(delegation.into())
+                }
+            })
+            .collect::<Vec<DelegationEntry>>();
 
-        // We want our roles in a consistent order.
-        roles.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+        // TAP 15 requires `roles` to be omitted when delegating to succinct hash bins.
+        let succinct_roles = delegations.succinct_roles().map(SuccinctRoles::from);
+        let roles = if succinct_roles.is_some() && roles.is_empty() {
+            None
+        } else {
+            Some(roles)
+        };
 
         Delegations {
             keys: delegations
@@ -468,6 +727,7 @@ impl From<&metadata::Delegations> for Delegations {
                 .map(|(id, key)| (id.clone(), key.clone()))
                 .collect(),
             roles,
+            succinct_roles,
         }
     }
 }
@@ -476,20 +736,41 @@ impl TryFrom<Delegations> for metadata::Delegations {
     type Error = Error;
 
     fn try_from(delegations: Delegations) -> Result<metadata::Delegations> {
-        metadata::Delegations::new(
-            delegations.keys.into_iter().collect(),
-            delegations
-                .roles
-                .into_iter()
-                .map(|delegation| delegation.try_into())
-                .collect::<Result<Vec<_>>>()?,
-        )
+        let keys = delegations.keys.into_iter().collect();
+
+        match (delegations.roles, delegations.succinct_roles) {
+            (Some(_), Some(_)) => Err(Error::Encoding(
+                "Delegations cannot have both roles and succinct roles.".into(),
+            )),
+            (None, Some(succinct_roles)) => {
+                metadata::Delegations::new_succinct(keys, succinct_roles.try_into()?)
+            }
+            (roles, None) => {
+                let entries = roles
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|entry| {
+                        Ok(match entry {
+                            DelegationEntry::Single(delegation) => {
+                                metadata::DelegationEntry::Single(delegation.try_into()?)
+                            }
+                            DelegationEntry::Multi(delegation) => {
+                                metadata::DelegationEntry::Multi(delegation.try_into()?)
+                            }
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                metadata::Delegations::with_entries(keys, entries)
+            }
+        }
     }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct TargetDescription {
-    length: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    length: Option<u64>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     hashes: BTreeMap<crypto::HashAlgorithm, crypto::HashValue>,
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     custom: BTreeMap<String, serde_json::Value>,
@@ -618,17 +899,22 @@ mod test {
 
     #[test]
     fn spec_version_validation() {
-        let valid_spec_versions = ["1.0.0", "1.0"];
+        let valid_spec_versions = ["1.0.0", "1.0", "1.0.1", "1.1.0", "2.0.0", "3.0"];
 
         for version in valid_spec_versions {
-            assert!(valid_spec_version(version), "{:?} should be valid", version);
+            assert_eq!(
+                parse_spec_version(version).map(|v| v.to_string()).ok(),
+                Some(version.to_string()),
+                "{:?} should be valid",
+                version
+            );
         }
 
-        let invalid_spec_versions = ["1.0.1", "1.1.0", "2.0.0", "3.0"];
+        let invalid_spec_versions = ["", "0", "1", "1.", "1.0.0.0", "1.x", "1.0.0-rc.1", "+1.0"];
 
         for version in invalid_spec_versions {
             assert!(
-                !valid_spec_version(version),
+                parse_spec_version(version).is_err(),
                 "{:?} should be invalid",
                 version
             );
@@ -0,0 +1,1210 @@
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::{Map, Number, Value};
+
+use crate::error::Error;
+use crate::pouf::Pouf;
+use crate::Result;
+
+const WIRE_VARINT: u8 = 0;
+const WIRE_LEN: u8 = 2;
+
+// The fields of the `Value` message.
+const VALUE_NULL: u32 = 1;
+const VALUE_BOOL: u32 = 2;
+const VALUE_INT: u32 = 3;
+const VALUE_UINT: u32 = 4;
+const VALUE_STRING: u32 = 5;
+const VALUE_LIST: u32 = 6;
+const VALUE_STRUCT: u32 = 7;
+
+// The fields of the `ListValue`, `Struct` and `Field` messages.
+const LIST_VALUES: u32 = 1;
+const STRUCT_FIELDS: u32 = 1;
+const FIELD_KEY: u32 = 1;
+const FIELD_VALUE: u32 = 2;
+
+/// The field of the role messages that holds the members the other fields don't describe.
+const UNRECOGNIZED_FIELDS: u32 = 15;
+
+/// The deepest nesting of values that is decoded, to bound the recursion of the decoder.
+const MAX_DEPTH: usize = 128;
+
+/// The type of a field of the schema in `proto/tuf.proto`.
+#[derive(Clone, Copy)]
+enum Type {
+    String,
+    Uint,
+    Bool,
+    /// A `tuf.Value`, which holds any JSON value.
+    Value,
+    Message(&'static Message),
+    /// A message with the single field `repeated values = 1`, so that a missing list can be told
+    /// apart from an empty one.
+    List(&'static Type),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Label {
+    Singular,
+    Repeated,
+    Map,
+}
+
+/// A field of a message, which holds the JSON member `name`.
+struct FieldDef {
+    number: u32,
+    name: &'static str,
+    ty: Type,
+    label: Label,
+    /// Whether the JSON member may be missing. A scalar without explicit presence is omitted when
+    /// it has its default value, and a repeated field or map that may be missing is missing rather
+    /// than empty.
+    optional: bool,
+}
+
+const fn field(number: u32, name: &'static str, ty: Type) -> FieldDef {
+    FieldDef {
+        number,
+        name,
+        ty,
+        label: Label::Singular,
+        optional: false,
+    }
+}
+
+const fn optional(number: u32, name: &'static str, ty: Type) -> FieldDef {
+    FieldDef {
+        optional: true,
+        ..field(number, name, ty)
+    }
+}
+
+const fn repeated(number: u32, name: &'static str, ty: Type) -> FieldDef {
+    FieldDef {
+        label: Label::Repeated,
+        ..field(number, name, ty)
+    }
+}
+
+const fn map(number: u32, name: &'static str, ty: Type) -> FieldDef {
+    FieldDef {
+        label: Label::Map,
+        ..field(number, name, ty)
+    }
+}
+
+const fn optional_map(number: u32, name: &'static str, ty: Type) -> FieldDef {
+    FieldDef {
+        optional: true,
+        ..map(number, name, ty)
+    }
+}
+
+/// A message of the schema in `proto/tuf.proto`.
+struct Message {
+    name: &'static str,
+    kind: MessageKind,
+}
+
+enum MessageKind {
+    /// A JSON object with the members described by `fields`. The `_type` of role metadata is
+    /// implied by the message, and the other members are only allowed if the message has a field
+    /// for unrecognized fields.
+    Object {
+        role: Option<&'static str>,
+        fields: &'static [FieldDef],
+        unrecognized_fields: bool,
+    },
+    /// Exactly one of `fields` is set, and holds the JSON value itself.
+    OneOf { fields: &'static [FieldDef] },
+}
+
+static DOCUMENT: Message = Message {
+    name: "Document",
+    kind: MessageKind::OneOf {
+        fields: &[
+            field(2, "signed_metadata", Type::Message(&SIGNED_METADATA)),
+            field(3, "root", Type::Message(&ROOT_METADATA)),
+            field(4, "timestamp", Type::Message(&TIMESTAMP_METADATA)),
+            field(5, "snapshot", Type::Message(&SNAPSHOT_METADATA)),
+            field(6, "targets", Type::Message(&TARGETS_METADATA)),
+            field(1, "value", Type::Value),
+        ],
+    },
+};
+
+static SIGNED_METADATA: Message = Message {
+    name: "SignedMetadata",
+    kind: MessageKind::Object {
+        role: None,
+        fields: &[
+            repeated(1, "signatures", Type::Message(&SIGNATURE)),
+            field(2, "signed", Type::Message(&SIGNED)),
+        ],
+        unrecognized_fields: false,
+    },
+};
+
+static SIGNED: Message = Message {
+    name: "Signed",
+    kind: MessageKind::OneOf {
+        fields: &[
+            field(3, "root", Type::Message(&ROOT_METADATA)),
+            field(4, "timestamp", Type::Message(&TIMESTAMP_METADATA)),
+            field(5, "snapshot", Type::Message(&SNAPSHOT_METADATA)),
+            field(6, "targets", Type::Message(&TARGETS_METADATA)),
+            field(1, "value", Type::Value),
+        ],
+    },
+};
+
+static SIGNATURE: Message = Message {
+    name: "Signature",
+    kind: MessageKind::Object {
+        role: None,
+        fields: &[
+            field(1, "keyid", Type::String),
+            field(2, "sig", Type::String),
+        ],
+        unrecognized_fields: false,
+    },
+};
+
+static ROOT_METADATA: Message = Message {
+    name: "RootMetadata",
+    kind: MessageKind::Object {
+        role: Some("root"),
+        fields: &[
+            field(1, "spec_version", Type::String),
+            field(2, "version", Type::Uint),
+            field(3, "consistent_snapshot", Type::Bool),
+            field(4, "expires", Type::String),
+            map(5, "keys", Type::Message(&PUBLIC_KEY)),
+            map(6, "roles", Type::Message(&ROLE_DEFINITION)),
+        ],
+        unrecognized_fields: true,
+    },
+};
+
+static ROLE_DEFINITION: Message = Message {
+    name: "RoleDefinition",
+    kind: MessageKind::Object {
+        role: None,
+        fields: &[
+            field(1, "threshold", Type::Uint),
+            repeated(2, "keyids", Type::String),
+        ],
+        unrecognized_fields: false,
+    },
+};
+
+static PUBLIC_KEY: Message = Message {
+    name: "PublicKey",
+    kind: MessageKind::Object {
+        role: None,
+        fields: &[
+            field(1, "keytype", Type::String),
+            field(2, "scheme", Type::String),
+            optional(3, "keyid_hash_algorithms", STRING_LIST),
+            field(4, "keyval", Type::Message(&PUBLIC_KEY_VALUE)),
+        ],
+        unrecognized_fields: false,
+    },
+};
+
+static PUBLIC_KEY_VALUE: Message = Message {
+    name: "PublicKeyValue",
+    kind: MessageKind::Object {
+        role: None,
+        fields: &[field(1, "public", Type::String)],
+        unrecognized_fields: false,
+    },
+};
+
+static TIMESTAMP_METADATA: Message = Message {
+    name: "TimestampMetadata",
+    kind: MessageKind::Object {
+        role: Some("timestamp"),
+        fields: &[
+            field(1, "spec_version", Type::String),
+            field(2, "version", Type::Uint),
+            field(3, "expires", Type::String),
+            map(4, "meta", Type::Message(&METADATA_DESCRIPTION)),
+            optional(5, "merkle_root", Type::String),
+        ],
+        unrecognized_fields: true,
+    },
+};
+
+static SNAPSHOT_METADATA: Message = Message {
+    name: "SnapshotMetadata",
+    kind: MessageKind::Object {
+        role: Some("snapshot"),
+        fields: &[
+            field(1, "spec_version", Type::String),
+            field(2, "version", Type::Uint),
+            field(3, "expires", Type::String),
+            map(4, "meta", Type::Message(&METADATA_DESCRIPTION)),
+        ],
+        unrecognized_fields: true,
+    },
+};
+
+static METADATA_DESCRIPTION: Message = Message {
+    name: "MetadataDescription",
+    kind: MessageKind::Object {
+        role: None,
+        fields: &[
+            field(1, "version", Type::Uint),
+            optional(2, "length", Type::Uint),
+            optional_map(3, "hashes", Type::String),
+        ],
+        unrecognized_fields: false,
+    },
+};
+
+static TARGETS_METADATA: Message = Message {
+    name: "TargetsMetadata",
+    kind: MessageKind::Object {
+        role: Some("targets"),
+        fields: &[
+            field(1, "spec_version", Type::String),
+            field(2, "version", Type::Uint),
+            field(3, "expires", Type::String),
+            map(4, "targets", Type::Message(&TARGET_DESCRIPTION)),
+            optional(5, "delegations", Type::Message(&DELEGATIONS)),
+        ],
+        unrecognized_fields: true,
+    },
+};
+
+static TARGET_DESCRIPTION: Message = Message {
+    name: "TargetDescription",
+    kind: MessageKind::Object {
+        role: None,
+        fields: &[
+            optional(1, "length", Type::Uint),
+            optional_map(2, "hashes", Type::String),
+            optional_map(3, "custom", Type::Value),
+        ],
+        unrecognized_fields: false,
+    },
+};
+
+static DELEGATIONS: Message = Message {
+    name: "Delegations",
+    kind: MessageKind::Object {
+        role: None,
+        fields: &[
+            map(1, "keys", Type::Message(&PUBLIC_KEY)),
+            optional(2, "roles", Type::List(&DELEGATION_ENTRY_TYPE)),
+            optional(3, "succinct_roles", Type::Message(&SUCCINCT_ROLES)),
+        ],
+        unrecognized_fields: false,
+    },
+};
+
+static DELEGATION_ENTRY_TYPE: Type = Type::Message(&DELEGATION_ENTRY);
+
+static DELEGATION_ENTRY: Message = Message {
+    name: "DelegationEntry",
+    kind: MessageKind::OneOf {
+        fields: &[
+            field(1, "role", Type::Message(&DELEGATION)),
+            field(2, "multi_role", Type::Message(&MULTI_ROLE_DELEGATION)),
+        ],
+    },
+};
+
+static DELEGATION: Message = Message {
+    name: "Delegation",
+    kind: MessageKind::Object {
+        role: None,
+        fields: &[
+            field(1, "name", Type::String),
+            field(2, "terminating", Type::Bool),
+            field(3, "threshold", Type::Uint),
+            repeated(4, "keyids", Type::String),
+            optional(5, "paths", STRING_LIST),
+            optional(6, "path_hash_prefixes", STRING_LIST),
+        ],
+        unrecognized_fields: false,
+    },
+};
+
+static MULTI_ROLE_DELEGATION: Message = Message {
+    name: "MultiRoleDelegation",
+    kind: MessageKind::Object {
+        role: None,
+        fields: &[
+            field(1, "min_roles_in_agreement", Type::Uint),
+            field(2, "terminating", Type::Bool),
+            optional(3, "paths", STRING_LIST),
+            optional(4, "path_hash_prefixes", STRING_LIST),
+            repeated(5, "roles", Type::Message(&MULTI_ROLE_DELEGATION_ROLE)),
+        ],
+        unrecognized_fields: false,
+    },
+};
+
+static MULTI_ROLE_DELEGATION_ROLE: Message = Message {
+    name: "MultiRoleDelegationRole",
+    kind: MessageKind::Object {
+        role: None,
+        fields: &[
+            field(1, "name", Type::String),
+            repeated(2, "keyids", Type::String),
+            field(3, "threshold", Type::Uint),
+        ],
+        unrecognized_fields: false,
+    },
+};
+
+static SUCCINCT_ROLES: Message = Message {
+    name: "SuccinctRoles",
+    kind: MessageKind::Object {
+        role: None,
+        fields: &[
+            repeated(1, "keyids", Type::String),
+            field(2, "threshold", Type::Uint),
+            field(3, "bit_length", Type::Uint),
+            field(4, "name_prefix", Type::String),
+        ],
+        unrecognized_fields: false,
+    },
+};
+
+const STRING_LIST: Type = Type::List(&Type::String);
+
+/// TUF metadata encoded with [Protocol Buffers](https://protobuf.dev/), so that services that
+/// distribute metadata over gRPC can use a single wire format.
+///
+/// Every file is one `tuf.Document` message of the schema in `proto/tuf.proto`, which has a
+/// message for each of the top-level roles whose fields are named after the members of the JSON
+/// of [Pouf1](crate::pouf::Pouf1):
+///
+/// ```text
+/// message Document {
+///   oneof kind {
+///     Value value = 1;
+///     SignedMetadata signed_metadata = 2;
+///     RootMetadata root = 3;
+///     TimestampMetadata timestamp = 4;
+///     SnapshotMetadata snapshot = 5;
+///     TargetsMetadata targets = 6;
+///   }
+/// }
+/// message SignedMetadata { repeated Signature signatures = 1; Signed signed = 2; }
+/// ```
+///
+/// The `_type` of role metadata is implied by its message. Members of the metadata that the
+/// schema doesn't describe are kept in the `unrecognized_fields` of the role message, and custom
+/// roles, along with the `custom` members of target descriptions, are encoded as a `tuf.Value`
+/// with the structure of their JSON.
+///
+/// The encoding of a protobuf message is not unique, so metadata is signed over its canonical
+/// encoding, which follows these rules:
+///
+/// * Fields are written in the order of their field numbers, and varints in their shortest form.
+/// * Scalars without explicit presence are omitted when they have their default value, as proto3
+///   does. Every other field that is set is written, including the field of a `oneof`.
+/// * The entries of a `map` and the fields of a `Struct` are sorted by the UTF-8 bytes of their
+///   keys.
+/// * Metadata is encoded with the message of its role, and only as a `tuf.Value` if that message
+///   can't describe all of its members. Integers in a `tuf.Value` that are not negative are
+///   written as `uint_value`, and negative ones as `int_value`. Floating point numbers cannot be
+///   canonicalized.
+///
+/// Parsing accepts any protobuf encoding of the schema, in the same way that
+/// [Pouf1](crate::pouf::Pouf1) accepts JSON with any whitespace, since signatures are checked
+/// against the canonical encoding of the parsed metadata. Unknown fields and duplicate keys are
+/// rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Protobuf;
+
+impl Pouf for Protobuf {
+    type RawData = Value;
+
+    /// ```
+    /// # use tuf::pouf::{Pouf, Protobuf};
+    /// assert_eq!(Protobuf::extension(), "pb");
+    /// ```
+    fn extension() -> &'static str {
+        "pb"
+    }
+
+    /// ```
+    /// # use serde_json::json;
+    /// # use tuf::pouf::{Pouf, Protobuf};
+    /// let out = Protobuf::canonicalize(&json!({"a": true})).unwrap();
+    /// assert_eq!(out, b"\x0a\x0b\x3a\x09\x0a\x07\x0a\x01a\x12\x02\x10\x01");
+    /// ```
+    fn canonicalize(raw_data: &Self::RawData) -> Result<Vec<u8>> {
+        // Every value fits a `tuf.Value`, unless it holds a number that cannot be canonicalized.
+        encode_body(Type::Message(&DOCUMENT), raw_data)
+            .ok_or_else(|| Error::Encoding("only i64 and u64 are supported".into()))
+    }
+
+    fn deserialize<T>(raw_data: &Self::RawData) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(serde_json::from_value(raw_data.clone())?)
+    }
+
+    fn serialize<T>(data: &T) -> Result<Self::RawData>
+    where
+        T: Serialize,
+    {
+        Ok(serde_json::to_value(data)?)
+    }
+
+    /// ```
+    /// # use tuf::pouf::{Pouf, Protobuf};
+    /// # use std::collections::HashMap;
+    /// let pb: &[u8] = b"\x0a\x10\x3a\x0e\x0a\x0c\x0a\x03foo\x12\x05\x2a\x03bar";
+    /// let map: HashMap<String, String> = Protobuf::from_slice(pb).unwrap();
+    /// assert_eq!(map["foo"], "bar");
+    /// ```
+    fn from_slice<T>(slice: &[u8]) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(serde_json::from_value(decode_message(
+            &DOCUMENT, slice, 0,
+        )?)?)
+    }
+}
+
+/// Encode `value` as the content of a field of type `ty`, or return `None` if the type does not
+/// describe the value.
+fn encode_body(ty: Type, value: &Value) -> Option<Vec<u8>> {
+    let mut buf = Vec::new();
+    match ty {
+        Type::Message(message) => encode_message(message, value, &mut buf)?,
+        Type::List(item) => {
+            for item_value in value.as_array()? {
+                encode_field_value(&mut buf, 1, *item, item_value, true)?;
+            }
+        }
+        Type::Value => encode_value(value, &mut buf).ok()?,
+        Type::String | Type::Uint | Type::Bool => return None,
+    }
+    Some(buf)
+}
+
+fn encode_message(message: &Message, value: &Value, buf: &mut Vec<u8>) -> Option<()> {
+    let (role, fields, unrecognized_fields) = match &message.kind {
+        MessageKind::Object {
+            role,
+            fields,
+            unrecognized_fields,
+        } => (role, fields, unrecognized_fields),
+        MessageKind::OneOf { fields } => {
+            // The first message that describes the value is used.
+            return fields.iter().find_map(|field| {
+                let content = encode_body(field.ty, value)?;
+                write_len_field(buf, field.number, &content);
+                Some(())
+            });
+        }
+    };
+
+    let members = value.as_object()?;
+    if let Some(role) = role {
+        if members.get("_type")?.as_str()? != *role {
+            return None;
+        }
+    }
+
+    let mut fields_by_number = fields.iter().collect::<Vec<_>>();
+    fields_by_number.sort_by_key(|field| field.number);
+    for field in fields_by_number {
+        encode_field(buf, field, members.get(field.name))?;
+    }
+
+    let unrecognized = members
+        .iter()
+        .filter(|(key, _)| {
+            !(fields.iter().any(|field| field.name == *key) || role.is_some() && *key == "_type")
+        })
+        .collect::<Vec<_>>();
+    if !unrecognized.is_empty() {
+        if !unrecognized_fields {
+            return None;
+        }
+        let content = encode_struct(unrecognized).ok()?;
+        write_len_field(buf, UNRECOGNIZED_FIELDS, &content);
+    }
+
+    Some(())
+}
+
+/// Encode the JSON member `value` of `field`, or return `None` if the field does not describe it.
+fn encode_field(buf: &mut Vec<u8>, field: &FieldDef, value: Option<&Value>) -> Option<()> {
+    let value = match value {
+        Some(value) => value,
+        None if field.optional => return Some(()),
+        None => return None,
+    };
+
+    match field.label {
+        Label::Singular => encode_field_value(buf, field.number, field.ty, value, field.optional),
+        Label::Repeated => {
+            let items = value.as_array()?;
+            // A missing member is encoded like an empty one, so it can't also be empty.
+            if field.optional && items.is_empty() {
+                return None;
+            }
+            for item in items {
+                encode_field_value(buf, field.number, field.ty, item, true)?;
+            }
+            Some(())
+        }
+        Label::Map => {
+            let entries = value.as_object()?;
+            if field.optional && entries.is_empty() {
+                return None;
+            }
+            let mut entries = entries.iter().collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+            for (key, value) in entries {
+                let mut entry = Vec::new();
+                write_len_field(&mut entry, 1, key.as_bytes());
+                encode_field_value(&mut entry, 2, field.ty, value, true)?;
+                write_len_field(buf, field.number, &entry);
+            }
+            Some(())
+        }
+    }
+}
+
+/// Encode a single `value` of type `ty` as the field `number`. Scalars with their default value
+/// are only written if `write_default` is set.
+fn encode_field_value(
+    buf: &mut Vec<u8>,
+    number: u32,
+    ty: Type,
+    value: &Value,
+    write_default: bool,
+) -> Option<()> {
+    match ty {
+        Type::String => {
+            let s = value.as_str()?;
+            if write_default || !s.is_empty() {
+                write_len_field(buf, number, s.as_bytes());
+            }
+        }
+        Type::Uint => {
+            let n = value.as_u64()?;
+            if write_default || n != 0 {
+                write_varint_field(buf, number, n);
+            }
+        }
+        Type::Bool => {
+            let b = value.as_bool()?;
+            if write_default || b {
+                write_varint_field(buf, number, u64::from(b));
+            }
+        }
+        Type::Value | Type::Message(_) | Type::List(_) => {
+            let content = encode_body(ty, value)?;
+            write_len_field(buf, number, &content);
+        }
+    }
+    Some(())
+}
+
+fn encode_value(value: &Value, buf: &mut Vec<u8>) -> Result<()> {
+    match value {
+        Value::Null => write_varint_field(buf, VALUE_NULL, 0),
+        Value::Bool(b) => write_varint_field(buf, VALUE_BOOL, u64::from(*b)),
+        Value::Number(n) => {
+            if let Some(n) = n.as_u64() {
+                write_varint_field(buf, VALUE_UINT, n);
+            } else if let Some(n) = n.as_i64() {
+                // sint64 uses the zigzag encoding.
+                write_varint_field(buf, VALUE_INT, ((n << 1) ^ (n >> 63)) as u64);
+            } else {
+                return Err(Error::Encoding("only i64 and u64 are supported".into()));
+            }
+        }
+        Value::String(s) => write_len_field(buf, VALUE_STRING, s.as_bytes()),
+        Value::Array(items) => {
+            let mut list = Vec::new();
+            for item in items {
+                let mut encoded = Vec::new();
+                encode_value(item, &mut encoded)?;
+                write_len_field(&mut list, LIST_VALUES, &encoded);
+            }
+            write_len_field(buf, VALUE_LIST, &list);
+        }
+        Value::Object(members) => {
+            let fields = encode_struct(members.iter().collect())?;
+            write_len_field(buf, VALUE_STRUCT, &fields);
+        }
+    }
+    Ok(())
+}
+
+/// Encode `members` as the content of a `Struct`.
+fn encode_struct(mut members: Vec<(&String, &Value)>) -> Result<Vec<u8>> {
+    members.sort_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+
+    let mut fields = Vec::new();
+    for (key, value) in members {
+        let mut field = Vec::new();
+        if !key.is_empty() {
+            write_len_field(&mut field, FIELD_KEY, key.as_bytes());
+        }
+        let mut encoded = Vec::new();
+        encode_value(value, &mut encoded)?;
+        write_len_field(&mut field, FIELD_VALUE, &encoded);
+
+        write_len_field(&mut fields, STRUCT_FIELDS, &field);
+    }
+    Ok(fields)
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        buf.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+fn write_varint_field(buf: &mut Vec<u8>, field: u32, n: u64) {
+    write_varint(buf, (u64::from(field) << 3) | u64::from(WIRE_VARINT));
+    write_varint(buf, n);
+}
+
+fn write_len_field(buf: &mut Vec<u8>, field: u32, content: &[u8]) {
+    write_varint(buf, (u64::from(field) << 3) | u64::from(WIRE_LEN));
+    write_varint(buf, content.len() as u64);
+    buf.extend(content);
+}
+
+/// A field of a message, with the value of a varint or the content of a length-delimited field.
+enum Field<'a> {
+    Varint(u64),
+    Len(&'a [u8]),
+}
+
+/// Read the fields of the message in `input`, in the order they are encoded.
+fn read_fields(mut input: &[u8]) -> Result<Vec<(u32, Field<'_>)>> {
+    let mut fields = Vec::new();
+    while !input.is_empty() {
+        let key = read_varint(&mut input)?;
+        let number = u32::try_from(key >> 3)
+            .ok()
+            .filter(|number| *number != 0)
+            .ok_or_else(|| Error::Encoding("invalid protobuf field number".into()))?;
+
+        let field = match (key & 0x7) as u8 {
+            WIRE_VARINT => Field::Varint(read_varint(&mut input)?),
+            WIRE_LEN => {
+                let len = usize::try_from(read_varint(&mut input)?)
+                    .ok()
+                    .filter(|len| *len <= input.len())
+                    .ok_or_else(|| Error::Encoding("truncated protobuf field".into()))?;
+                let (content, rest) = input.split_at(len);
+                input = rest;
+                Field::Len(content)
+            }
+            wire_type => {
+                return Err(Error::Encoding(format!(
+                    "unsupported protobuf wire type {}",
+                    wire_type
+                )))
+            }
+        };
+        fields.push((number, field));
+    }
+    Ok(fields)
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<u64> {
+    let mut n = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = input
+            .split_first()
+            .ok_or_else(|| Error::Encoding("truncated protobuf varint".into()))?;
+        *input = rest;
+        n |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(Error::Encoding("protobuf varint is too long".into()))
+}
+
+/// Decode the content of `message` into the JSON value it describes.
+fn decode_message(message: &Message, input: &[u8], depth: usize) -> Result<Value> {
+    if depth > MAX_DEPTH {
+        return Err(Error::Encoding(
+            "protobuf message is nested too deeply".into(),
+        ));
+    }
+
+    let (role, fields, unrecognized_fields) = match &message.kind {
+        MessageKind::Object {
+            role,
+            fields,
+            unrecognized_fields,
+        } => (role, fields, unrecognized_fields),
+        MessageKind::OneOf { fields } => {
+            // Like any protobuf field that is not repeated, the last field that is encoded wins.
+            let mut value = None;
+            for (number, field) in read_fields(input)? {
+                let def = fields
+                    .iter()
+                    .find(|def| def.number == number)
+                    .ok_or_else(|| unknown_field(message.name, number))?;
+                value = Some(decode_field_value(message, def.ty, number, field, depth)?);
+            }
+            return value
+                .ok_or_else(|| Error::Encoding(format!("protobuf {} has no kind", message.name)));
+        }
+    };
+
+    let mut members = Map::new();
+    let mut unrecognized = Map::new();
+    for (number, field) in read_fields(input)? {
+        if *unrecognized_fields && number == UNRECOGNIZED_FIELDS {
+            let content = match field {
+                Field::Len(content) => content,
+                Field::Varint(_) => return Err(unknown_field(message.name, number)),
+            };
+            for (key, value) in decode_struct(content, depth)? {
+                if unrecognized.insert(key, value).is_some() {
+                    return Err(Error::Encoding("protobuf struct has duplicate keys".into()));
+                }
+            }
+            continue;
+        }
+
+        let def = fields
+            .iter()
+            .find(|def| def.number == number)
+            .ok_or_else(|| unknown_field(message.name, number))?;
+        match def.label {
+            Label::Singular => {
+                let value = decode_field_value(message, def.ty, number, field, depth)?;
+                let _ = members.insert(def.name.into(), value);
+            }
+            Label::Repeated => {
+                let value = decode_field_value(message, def.ty, number, field, depth)?;
+                if let Value::Array(items) = members
+                    .entry(def.name)
+                    .or_insert_with(|| Value::Array(Vec::new()))
+                {
+                    items.push(value);
+                }
+            }
+            Label::Map => {
+                let (key, value) = match field {
+                    Field::Len(entry) => decode_map_entry(message, def, entry, depth)?,
+                    Field::Varint(_) => return Err(unknown_field(message.name, number)),
+                };
+                if let Value::Object(entries) = members
+                    .entry(def.name)
+                    .or_insert_with(|| Value::Object(Map::new()))
+                {
+                    if entries.insert(key, value).is_some() {
+                        return Err(Error::Encoding(format!(
+                            "protobuf map {} of {} has duplicate keys",
+                            def.name, message.name
+                        )));
+                    }
+                }
+            }
+        }
+    }
+
+    // Fields without explicit presence are missing when they have their default value.
+    for def in fields.iter().filter(|def| !def.optional) {
+        if !members.contains_key(def.name) {
+            let value = match def.label {
+                Label::Singular => default_value(def.ty, depth)?,
+                Label::Repeated => Value::Array(Vec::new()),
+                Label::Map => Value::Object(Map::new()),
+            };
+            let _ = members.insert(def.name.into(), value);
+        }
+    }
+
+    if let Some(role) = role {
+        let _ = members.insert("_type".into(), Value::String((*role).into()));
+    }
+
+    for (key, value) in unrecognized {
+        if members.insert(key, value).is_some() {
+            return Err(Error::Encoding(format!(
+                "protobuf {} has unrecognized fields that it describes",
+                message.name
+            )));
+        }
+    }
+
+    Ok(Value::Object(members))
+}
+
+/// Decode the `field` numbered `number` of `message`, which has type `ty`.
+fn decode_field_value(
+    message: &Message,
+    ty: Type,
+    number: u32,
+    field: Field<'_>,
+    depth: usize,
+) -> Result<Value> {
+    Ok(match (ty, field) {
+        (Type::String, Field::Len(s)) => Value::String(decode_string(s)?),
+        (Type::Uint, Field::Varint(n)) => Value::Number(Number::from(n)),
+        (Type::Bool, Field::Varint(b)) => Value::Bool(b != 0),
+        (Type::Value, Field::Len(value)) => decode_value(value, depth + 1)?,
+        (Type::Message(inner), Field::Len(content)) => decode_message(inner, content, depth + 1)?,
+        (Type::List(item), Field::Len(content)) => {
+            let mut items = Vec::new();
+            for (number, field) in read_fields(content)? {
+                if number != 1 {
+                    return Err(unknown_field(message.name, number));
+                }
+                items.push(decode_field_value(
+                    message,
+                    *item,
+                    number,
+                    field,
+                    depth + 1,
+                )?);
+            }
+            Value::Array(items)
+        }
+        _ => return Err(unknown_field(message.name, number)),
+    })
+}
+
+fn decode_map_entry(
+    message: &Message,
+    def: &FieldDef,
+    input: &[u8],
+    depth: usize,
+) -> Result<(String, Value)> {
+    let mut key = String::new();
+    let mut value = None;
+    for (number, field) in read_fields(input)? {
+        match (number, field) {
+            (1, Field::Len(s)) => key = decode_string(s)?,
+            (2, field) => value = Some(decode_field_value(message, def.ty, number, field, depth)?),
+            _ => return Err(unknown_field(message.name, number)),
+        }
+    }
+
+    let value = match value {
+        Some(value) => value,
+        None => default_value(def.ty, depth)?,
+    };
+    Ok((key, value))
+}
+
+/// The value of a field of type `ty` that is missing from a message at `depth`.
+fn default_value(ty: Type, depth: usize) -> Result<Value> {
+    match ty {
+        Type::String => Ok(Value::String(String::new())),
+        Type::Uint => Ok(Value::Number(Number::from(0u64))),
+        Type::Bool => Ok(Value::Bool(false)),
+        Type::List(_) => Ok(Value::Array(Vec::new())),
+        Type::Message(message) => decode_message(message, &[], depth + 1),
+        Type::Value => Err(Error::Encoding("protobuf value has no kind".into())),
+    }
+}
+
+fn decode_value(input: &[u8], depth: usize) -> Result<Value> {
+    if depth > MAX_DEPTH {
+        return Err(Error::Encoding(
+            "protobuf value is nested too deeply".into(),
+        ));
+    }
+
+    // Like any protobuf field that is not repeated, the last `kind` that is encoded wins.
+    let mut value = None;
+    for (number, field) in read_fields(input)? {
+        value = Some(match (number, field) {
+            (VALUE_NULL, Field::Varint(_)) => Value::Null,
+            (VALUE_BOOL, Field::Varint(b)) => Value::Bool(b != 0),
+            (VALUE_INT, Field::Varint(n)) => {
+                Value::Number(Number::from(((n >> 1) as i64) ^ -((n & 1) as i64)))
+            }
+            (VALUE_UINT, Field::Varint(n)) => Value::Number(Number::from(n)),
+            (VALUE_STRING, Field::Len(s)) => Value::String(decode_string(s)?),
+            (VALUE_LIST, Field::Len(list)) => {
+                let mut items = Vec::new();
+                for (number, field) in read_fields(list)? {
+                    match (number, field) {
+                        (LIST_VALUES, Field::Len(item)) => {
+                            items.push(decode_value(item, depth + 1)?)
+                        }
+                        _ => return Err(unknown_field("ListValue", number)),
+                    }
+                }
+                Value::Array(items)
+            }
+            (VALUE_STRUCT, Field::Len(fields)) => Value::Object(decode_struct(fields, depth)?),
+            _ => return Err(unknown_field("Value", number)),
+        });
+    }
+
+    value.ok_or_else(|| Error::Encoding("protobuf value has no kind".into()))
+}
+
+/// Decode the content of a `Struct` that is nested in a value at `depth`.
+fn decode_struct(input: &[u8], depth: usize) -> Result<Map<String, Value>> {
+    let mut members = Map::new();
+    for (number, field) in read_fields(input)? {
+        let (key, value) = match (number, field) {
+            (STRUCT_FIELDS, Field::Len(field)) => decode_field(field, depth + 1)?,
+            _ => return Err(unknown_field("Struct", number)),
+        };
+        if members.insert(key, value).is_some() {
+            return Err(Error::Encoding("protobuf struct has duplicate keys".into()));
+        }
+    }
+    Ok(members)
+}
+
+fn decode_field(input: &[u8], depth: usize) -> Result<(String, Value)> {
+    let mut key = String::new();
+    let mut value = None;
+    for (number, field) in read_fields(input)? {
+        match (number, field) {
+            (FIELD_KEY, Field::Len(s)) => key = decode_string(s)?,
+            (FIELD_VALUE, Field::Len(v)) => value = Some(decode_value(v, depth)?),
+            _ => return Err(unknown_field("Field", number)),
+        }
+    }
+
+    let value = value.ok_or_else(|| Error::Encoding("protobuf field has no value".into()))?;
+    Ok((key, value))
+}
+
+fn decode_string(content: &[u8]) -> Result<String> {
+    String::from_utf8(content.to_vec())
+        .map_err(|_| Error::Encoding("protobuf string is not UTF-8".into()))
+}
+
+fn unknown_field(message: &str, number: u32) -> Error {
+    Error::Encoding(format!(
+        "unknown field {} or wire type of protobuf message {}",
+        number, message
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{Ed25519PrivateKey, PrivateKey};
+    use crate::database::Database;
+    use crate::metadata::{Metadata, RootMetadataBuilder};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn round_trip(value: Value) {
+        let bytes = Protobuf::canonicalize(&value).unwrap();
+        assert_eq!(Protobuf::from_slice::<Value>(&bytes).unwrap(), value);
+    }
+
+    #[test]
+    fn canonicalize_scalars() {
+        let cases: &[(Value, &[u8])] = &[
+            (json!(null), b"\x08\x00"),
+            (json!(false), b"\x10\x00"),
+            (json!(0), b"\x20\x00"),
+            (json!(300), b"\x20\xac\x02"),
+            (json!(-1), b"\x18\x01"),
+            (json!(-2), b"\x18\x03"),
+            (json!(""), b"\x2a\x00"),
+            (json!([]), b"\x32\x00"),
+            (json!({"": 1}), b"\x3a\x06\x0a\x04\x12\x02\x20\x01"),
+        ];
+        for (value, expected) in cases {
+            let mut document = Vec::new();
+            write_len_field(&mut document, 1, expected);
+            assert_eq!(
+                Protobuf::canonicalize(value).unwrap(),
+                document,
+                "{}",
+                value
+            );
+            round_trip(value.clone());
+        }
+
+        round_trip(json!(u64::MAX));
+        round_trip(json!(i64::MIN));
+        assert!(Protobuf::canonicalize(&json!(1.5)).is_err());
+    }
+
+    #[test]
+    fn canonicalize_sorts_struct_fields() {
+        let value = json!({"b": [1, "x"], "a": {"c": null}});
+        let bytes = Protobuf::canonicalize(&value).unwrap();
+        let key_a = bytes.windows(3).position(|w| w == b"\x0a\x01a").unwrap();
+        let key_b = bytes.windows(3).position(|w| w == b"\x0a\x01b").unwrap();
+        assert!(key_a < key_b);
+        round_trip(value);
+    }
+
+    #[test]
+    fn from_slice_rejects_invalid_values() {
+        let cases: &[&[u8]] = &[
+            // No kind.
+            b"",
+            // An unknown field.
+            b"\x40\x01",
+            // A wire type that does not match the field.
+            b"\x12\x00",
+            b"\x28\x00",
+            // A truncated string.
+            b"\x2a\x05abc",
+            // Duplicate keys.
+            b"\x3a\x12\x0a\x07\x0a\x01a\x12\x02\x08\x00\x0a\x07\x0a\x01a\x12\x02\x08\x00",
+            // A field without a value.
+            b"\x3a\x05\x0a\x03\x0a\x01a",
+        ];
+        for case in cases {
+            let mut document = Vec::new();
+            write_len_field(&mut document, 1, case);
+            assert!(
+                Protobuf::from_slice::<Value>(&document).is_err(),
+                "{:x?}",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn from_slice_rejects_invalid_documents() {
+        let cases: &[&[u8]] = &[
+            // No kind.
+            b"",
+            // An unknown field of the document and of a role.
+            b"\x40\x00",
+            b"\x1a\x02\x38\x01",
+            // A wire type that does not match the field.
+            b"\x1a\x02\x12\x00",
+            // Duplicate map keys.
+            b"\x1a\x0a\x32\x03\x0a\x01a\x32\x03\x0a\x01a",
+            // An unrecognized field that the role message describes.
+            b"\x1a\x10\x7a\x0e\x0a\x0c\x0a\x05_type\x12\x03\x2a\x01x",
+            // Signed metadata without a kind.
+            b"\x12\x00",
+        ];
+        for case in cases {
+            assert!(Protobuf::from_slice::<Value>(case).is_err(), "{:x?}", case);
+        }
+    }
+
+    #[test]
+    fn canonicalize_uses_role_messages() {
+        let key: &[u8] = include_bytes!("../../tests/ed25519/ed25519-1.pk8.der");
+        let key = Ed25519PrivateKey::from_pkcs8(key).unwrap();
+        let root = RootMetadataBuilder::new()
+            .root_key(key.public().clone())
+            .snapshot_key(key.public().clone())
+            .targets_key(key.public().clone())
+            .timestamp_key(key.public().clone())
+            .build()
+            .unwrap();
+        let raw_root = Protobuf::serialize(&root).unwrap();
+
+        let bytes = Protobuf::canonicalize(&raw_root).unwrap();
+        assert_eq!(bytes[0], 0x1a);
+        assert!(!bytes.windows(5).any(|w| w == b"_type"));
+        round_trip(raw_root);
+    }
+
+    #[test]
+    fn canonicalize_targets_message() {
+        let targets = json!({
+            "_type": "targets",
+            "spec_version": "1.0.0",
+            "version": 1,
+            "expires": "2038-01-19T03:14:08Z",
+            "targets": {
+                "foo": {"length": 0, "hashes": {"sha256": "abcd"}, "custom": {"x": [1, null]}},
+                "bar": {},
+            },
+            "delegations": {
+                "keys": {},
+                "roles": [
+                    {
+                        "name": "a",
+                        "terminating": false,
+                        "threshold": 1,
+                        "keyids": [],
+                        "paths": [],
+                    },
+                    {
+                        "min_roles_in_agreement": 2,
+                        "terminating": true,
+                        "path_hash_prefixes": ["ab"],
+                        "roles": [{"name": "b", "keyids": ["k"], "threshold": 1}],
+                    },
+                ],
+                "succinct_roles": {
+                    "keyids": ["k"],
+                    "threshold": 1,
+                    "bit_length": 8,
+                    "name_prefix": "bin",
+                },
+            },
+            "x-unrecognized": {"y": true},
+        });
+
+        let bytes = Protobuf::canonicalize(&targets).unwrap();
+        assert_eq!(bytes[0], 0x32);
+        round_trip(targets);
+    }
+
+    #[test]
+    fn canonicalize_falls_back_to_value() {
+        let cases = [
+            // A custom role.
+            json!({"_type": "custom", "spec_version": "1.0.0", "version": 1, "expires": ""}),
+            // Metadata whose members don't fit the message of its role.
+            json!({"_type": "snapshot", "spec_version": "1.0.0", "version": -1, "expires": ""}),
+            json!({"_type": "snapshot", "version": 1, "expires": "", "meta": {}}),
+            json!({
+                "_type": "snapshot",
+                "spec_version": "1.0.0",
+                "version": 1,
+                "expires": "",
+                "meta": {"targets.json": {"version": 1, "hashes": {}}},
+            }),
+        ];
+        for value in cases {
+            let bytes = Protobuf::canonicalize(&value).unwrap();
+            assert_eq!(bytes[0], 0x0a, "{}", value);
+            round_trip(value);
+        }
+    }
+
+    #[test]
+    fn protobuf_root_round_trip() {
+        let key: &[u8] = include_bytes!("../../tests/ed25519/ed25519-1.pk8.der");
+        let key = Ed25519PrivateKey::from_pkcs8(key).unwrap();
+        let signed = RootMetadataBuilder::new()
+            .version(2)
+            .root_key(key.public().clone())
+            .snapshot_key(key.public().clone())
+            .targets_key(key.public().clone())
+            .timestamp_key(key.public().clone())
+            .signed::<Protobuf>(&key)
+            .unwrap();
+        let raw_root = signed.to_raw().unwrap();
+
+        let db = Database::<Protobuf>::from_trusted_root(&raw_root).unwrap();
+        assert_eq!(db.trusted_root().version(), 2);
+        assert_eq!(&**db.trusted_root(), &signed.assume_valid().unwrap());
+    }
+}
@@ -0,0 +1,420 @@
+//! Removing targets from the storage of a repository that no metadata refers to anymore.
+//!
+//! [RepoBuilder::remove_target](crate::repo_builder::RepoBuilder::remove_target) removes the
+//! stored file of a target that is removed from the targets metadata, but targets can still pile
+//! up in storage: with consistent snapshots every new version of a target is stored under a new
+//! `$HASH.FILENAME.EXT` path, and a publish that fails part way can leave targets behind that no
+//! metadata was written for.
+//!
+//! [prune_targets] lists the stored targets with [RepositoryStorage::list_targets], and compares
+//! them against every target referenced by the trusted targets metadata, the metadata of every
+//! reachable delegated role, and the previous `keep_versions` versions of each of these roles, so
+//! that clients that haven't updated yet can still fetch the targets of the metadata they have.
+//! The stored targets that aren't referenced are orphans, which are removed unless it's a dry
+//! run. Either way the [PruneReport] lists them.
+//!
+//! Previous versions of metadata are only stored when consistent snapshots are enabled. They are
+//! parsed without verifying their signatures, since they were signed with keys that may have been
+//! rotated since: this is safe because they can only cause targets to be kept.
+//!
+//! Targets are stored before the metadata that refers to them is written, so pruning must not
+//! run at the same time as a repository is being published, or the new targets could be removed.
+
+use serde_derive::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::client::{Client, Config};
+use crate::error::{Error, Result};
+use crate::metadata::{
+    Metadata as _, MetadataPath, MetadataVersion, RawSignedMetadata, RootMetadata,
+    TargetDescription, TargetPath, TargetsMetadata,
+};
+use crate::pouf::Pouf;
+use crate::repository::{EphemeralRepository, Repository, RepositoryProvider, RepositoryStorage};
+
+/// The result of [prune_targets].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PruneReport {
+    dry_run: bool,
+    stored: usize,
+    referenced: usize,
+    orphans: Vec<TargetPath>,
+}
+
+impl PruneReport {
+    /// Whether this was a dry run, in which case the orphans were not removed.
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// The number of targets stored in the repository, including the orphans.
+    pub fn stored_targets(&self) -> usize {
+        self.stored
+    }
+
+    /// The number of stored paths referenced by the kept metadata. A target is stored under one
+    /// path for each of its hashes when consistent snapshots are enabled.
+    pub fn referenced_targets(&self) -> usize {
+        self.referenced
+    }
+
+    /// The stored targets that aren't referenced by the kept metadata, sorted by path.
+    pub fn orphans(&self) -> &[TargetPath] {
+        &self.orphans
+    }
+}
+
+impl fmt::Display for PruneReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = if self.dry_run {
+            "would remove"
+        } else {
+            "removed"
+        };
+
+        for orphan in &self.orphans {
+            writeln!(f, "{} {}", action, orphan)?;
+        }
+
+        write!(
+            f,
+            "{} {} of {} stored targets ({} referenced)",
+            action,
+            self.orphans.len(),
+            self.stored,
+            self.referenced
+        )
+    }
+}
+
+/// Find the targets stored in `repo` that aren't referenced by the metadata trusted from `root`,
+/// or by the previous `keep_versions` versions of the targets metadata of every role, and remove
+/// them unless `dry_run` is set. See the [module](self) documentation for details.
+///
+/// This fails without removing anything if `repo` can't list its targets, if the latest metadata
+/// can't be trusted, or if the metadata of any reachable delegated role can't be fetched or
+/// verified, since the targets of that role would otherwise look like orphans. It also fails if
+/// the delegation graph is larger than the limits of the default [Config], in which case some
+/// roles could not be visited.
+pub async fn prune_targets<D, R>(
+    repo: &R,
+    root: &RawSignedMetadata<D, RootMetadata>,
+    keep_versions: u32,
+    dry_run: bool,
+) -> Result<PruneReport>
+where
+    D: Pouf,
+    R: RepositoryProvider<D> + RepositoryStorage<D>,
+{
+    let referenced = referenced_targets(repo, root, keep_versions).await?;
+
+    let stored = repo.list_targets().await?;
+    let mut orphans = stored
+        .iter()
+        .filter(|path| !referenced.contains(*path))
+        .cloned()
+        .collect::<Vec<_>>();
+    orphans.sort();
+
+    if !dry_run {
+        for orphan in &orphans {
+            repo.remove_target(orphan).await?;
+        }
+    }
+
+    Ok(PruneReport {
+        dry_run,
+        stored: stored.len(),
+        referenced: stored.len() - orphans.len(),
+        orphans,
+    })
+}
+
+/// Collect the stored paths of every target referenced by the metadata that is kept.
+async fn referenced_targets<D, R>(
+    repo: &R,
+    root: &RawSignedMetadata<D, RootMetadata>,
+    keep_versions: u32,
+) -> Result<HashSet<TargetPath>>
+where
+    D: Pouf,
+    R: RepositoryProvider<D>,
+{
+    let mut client =
+        Client::with_trusted_root(Config::default(), root, EphemeralRepository::new(), repo)
+            .await?;
+    client.update().await?;
+
+    // Listing the targets fetches the metadata of every reachable delegated role, and fails if
+    // any of them can't be trusted or a delegation limit is hit.
+    let _ = client.trusted_targets_iter_strict().await?;
+
+    let parts = client.into_parts();
+    let db = parts.database;
+    let repo = Repository::<_, D>::new(parts.remote);
+    let consistent_snapshot = db.trusted_root().consistent_snapshot();
+
+    let mut roles = vec![];
+    if let Some(targets) = db.trusted_targets() {
+        roles.push((MetadataPath::targets(), &**targets));
+    }
+    for (role, targets) in db.trusted_delegations() {
+        roles.push((role.clone(), &***targets));
+    }
+
+    let mut referenced = HashSet::new();
+    for (role, targets) in roles {
+        add_referenced_targets(&mut referenced, targets, consistent_snapshot)?;
+
+        // Walk back through the previous versions, stopping at the first one that is missing,
+        // since the versions before it have most likely been removed as well.
+        let latest = targets.version();
+        let oldest = latest.saturating_sub(keep_versions).max(1);
+        for version in (oldest..latest).rev() {
+            let raw = match repo
+                .fetch_metadata::<TargetsMetadata>(
+                    &role,
+                    MetadataVersion::Number(version),
+                    None,
+                    vec![],
+                )
+                .await
+            {
+                Ok(raw) => raw,
+                Err(Error::MetadataNotFound { .. }) => break,
+                Err(err) => return Err(err),
+            };
+            let previous = raw.parse_untrusted()?.assume_valid()?;
+            add_referenced_targets(&mut referenced, &previous, consistent_snapshot)?;
+        }
+    }
+
+    Ok(referenced)
+}
+
+/// Add the paths that the targets of `targets` are stored at. With consistent snapshots a target
+/// is stored under a hash prefixed path for each of its hashes.
+fn add_referenced_targets(
+    referenced: &mut HashSet<TargetPath>,
+    targets: &TargetsMetadata,
+    consistent_snapshot: bool,
+) -> Result<()> {
+    for (path, description) in targets.targets() {
+        add_referenced_target(referenced, path, description, consistent_snapshot)?;
+    }
+
+    Ok(())
+}
+
+fn add_referenced_target(
+    referenced: &mut HashSet<TargetPath>,
+    path: &TargetPath,
+    description: &TargetDescription,
+    consistent_snapshot: bool,
+) -> Result<()> {
+    if consistent_snapshot {
+        for hash in description.hashes().values() {
+            referenced.insert(path.with_hash_prefix(hash)?);
+        }
+    } else {
+        referenced.insert(path.clone());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{Ed25519PrivateKey, HashAlgorithm, PrivateKey as _};
+    use crate::metadata::Delegation;
+    use crate::pouf::Pouf1;
+    use crate::repo_builder::RepoBuilder;
+    use futures_executor::block_on;
+    use futures_util::io::Cursor;
+    use lazy_static::lazy_static;
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[include_bytes!("../tests/ed25519/ed25519-1.pk8.der")];
+            keys.iter()
+                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+                .collect()
+        };
+    }
+
+    fn hash_prefixed(path: &str, content: &[u8]) -> TargetPath {
+        let description = TargetDescription::from_slice(content, &[HashAlgorithm::Sha256]).unwrap();
+        let hash = description.hashes().values().next().unwrap();
+        TargetPath::new(path)
+            .unwrap()
+            .with_hash_prefix(hash)
+            .unwrap()
+    }
+
+    async fn stored_targets(repo: &EphemeralRepository<Pouf1>) -> Vec<TargetPath> {
+        let mut targets = repo.list_targets().await.unwrap();
+        targets.sort();
+        targets
+    }
+
+    #[test]
+    fn prune_targets_removes_orphans() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            let foo = TargetPath::new("foo").unwrap();
+            let bar = TargetPath::new("bar").unwrap();
+
+            let metadata = RepoBuilder::create(&repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target(foo.clone(), Cursor::new(b"foo v1"))
+                .await
+                .unwrap()
+                .add_target(bar.clone(), Cursor::new(b"bar"))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+            let root = metadata.root().unwrap().clone();
+
+            // Publish a new version of `foo`, which leaves the first one in storage, and store a
+            // target that no metadata refers to.
+            RepoBuilder::from_repo(&repo, &root)
+                .await
+                .unwrap()
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .skip_root()
+                .add_target(foo.clone(), Cursor::new(b"foo v2"))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+            let stray = TargetPath::new("stray").unwrap();
+            repo.store_target(&stray, &mut &b"stray"[..]).await.unwrap();
+
+            let stored = stored_targets(&repo).await;
+            assert_eq!(stored.len(), 4);
+
+            // The first version of `foo` is kept along with the previous targets metadata.
+            let report = prune_targets(&repo, &root, 1, true).await.unwrap();
+            assert!(report.is_dry_run());
+            assert_eq!(report.stored_targets(), 4);
+            assert_eq!(report.referenced_targets(), 3);
+            assert_eq!(report.orphans(), std::slice::from_ref(&stray));
+
+            let report = prune_targets(&repo, &root, 0, true).await.unwrap();
+            assert_eq!(
+                report.orphans(),
+                &[hash_prefixed("foo", b"foo v1"), stray.clone()]
+            );
+            assert_eq!(
+                report.to_string(),
+                format!(
+                    "would remove {}\nwould remove stray\nwould remove 2 of 4 stored targets \
+                     (2 referenced)",
+                    hash_prefixed("foo", b"foo v1")
+                )
+            );
+
+            // A dry run leaves the storage alone.
+            assert_eq!(stored_targets(&repo).await, stored);
+
+            let report = prune_targets(&repo, &root, 0, false).await.unwrap();
+            assert!(!report.is_dry_run());
+            assert_eq!(report.orphans().len(), 2);
+
+            let mut expected = vec![
+                hash_prefixed("bar", b"bar"),
+                hash_prefixed("foo", b"foo v2"),
+            ];
+            expected.sort();
+            assert_eq!(stored_targets(&repo).await, expected);
+
+            // Pruning again finds nothing left to remove.
+            let report = prune_targets(&repo, &root, 0, false).await.unwrap();
+            assert!(report.orphans().is_empty());
+        })
+    }
+
+    #[test]
+    fn prune_targets_requires_trusted_metadata() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+            let root = metadata.root().unwrap().clone();
+
+            // Nothing is removed from a repository whose metadata can't be fetched.
+            let empty = EphemeralRepository::<Pouf1>::new();
+            let stray = TargetPath::new("stray").unwrap();
+            empty
+                .store_target(&stray, &mut &b"stray"[..])
+                .await
+                .unwrap();
+
+            assert!(prune_targets(&empty, &root, 0, false).await.is_err());
+            assert_eq!(stored_targets(&empty).await, vec![stray]);
+        })
+    }
+
+    #[test]
+    fn prune_targets_requires_delegated_metadata() {
+        block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            let role = MetadataPath::new("delegated").unwrap();
+            let delegation = Delegation::builder(role.clone())
+                .key(KEYS[0].public())
+                .delegate_path(TargetPath::new("delegated/").unwrap())
+                .build()
+                .unwrap();
+
+            let metadata = RepoBuilder::create(&repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .add_delegated_role(delegation, &[&KEYS[0]])
+                .add_delegated_target(
+                    &role,
+                    TargetPath::new("delegated/foo").unwrap(),
+                    Cursor::new(b"foo"),
+                )
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+            let root = metadata.root().unwrap().clone();
+            let stray = TargetPath::new("stray").unwrap();
+            repo.store_target(&stray, &mut &b"stray"[..]).await.unwrap();
+            let stored = stored_targets(&repo).await;
+
+            // The targets of a delegated role that can't be fetched would look like orphans, so
+            // nothing is removed.
+            for version in [MetadataVersion::Number(1), MetadataVersion::None] {
+                repo.store_metadata(&role, version, &mut &b"corrupt"[..])
+                    .await
+                    .unwrap();
+            }
+
+            assert!(prune_targets(&repo, &root, 0, false).await.is_err());
+            assert_eq!(stored_targets(&repo).await, stored);
+        })
+    }
+}
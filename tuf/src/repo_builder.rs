@@ -2,24 +2,40 @@
 
 use {
     crate::{
-        crypto::{self, HashAlgorithm, PrivateKey, PublicKey},
+        client::{Client, Config},
+        crypto::{
+            self, HashAlgorithm, HashValue, PrivateKey, PublicKey, Signature, SignatureValue,
+        },
         database::Database,
         error::{Error, Result},
+        hashed_bins::HashedBins,
+        lint::{self, LintReport},
+        merkle::SnapshotMerkleTree,
         metadata::{
-            Delegation, DelegationsBuilder, Metadata, MetadataDescription, MetadataPath,
-            MetadataVersion, RawSignedMetadata, RawSignedMetadataSet, RawSignedMetadataSetBuilder,
-            RootMetadata, RootMetadataBuilder, SignedMetadataBuilder, SnapshotMetadata,
-            SnapshotMetadataBuilder, TargetDescription, TargetPath, TargetsMetadata,
-            TargetsMetadataBuilder, TimestampMetadata, TimestampMetadataBuilder,
+            self, Delegation, DelegationEntry, DelegationsBuilder, Metadata, MetadataDescription,
+            MetadataPath, MetadataVersion, MultiRoleDelegation, RawSignedMetadata,
+            RawSignedMetadataSet, RawSignedMetadataSetBuilder, Role, RootMetadata,
+            RootMetadataBuilder, SignedMetadataBuilder, SnapshotMetadata, SnapshotMetadataBuilder,
+            SuccinctRoles, TargetDescription, TargetPath, TargetsMetadata, TargetsMetadataBuilder,
+            TimestampMetadata, TimestampMetadataBuilder,
         },
         pouf::Pouf,
-        repository::RepositoryStorage,
-        verify::Verified,
+        repository::{EphemeralRepository, RepositoryProvider, RepositoryStorage},
+        verify::{self, SignatureReport, Verified},
     },
     chrono::{DateTime, Duration, Utc},
     futures_io::{AsyncRead, AsyncSeek},
-    futures_util::AsyncSeekExt as _,
-    std::{collections::HashMap, io::SeekFrom, marker::PhantomData},
+    futures_util::{io::AllowStdIo, AsyncReadExt as _, AsyncSeekExt as _},
+    serde_derive::{Deserialize, Serialize},
+    std::{
+        borrow::Cow,
+        collections::{BTreeMap, HashMap, HashSet},
+        fs::{self, File},
+        io::{self, SeekFrom},
+        marker::PhantomData,
+        path::{Path, PathBuf},
+        thread,
+    },
 };
 
 mod private {
@@ -39,8 +55,78 @@ mod private {
 
 const DEFAULT_ROOT_EXPIRATION: Duration = Duration::days(365);
 const DEFAULT_TARGETS_EXPIRATION: Duration = Duration::days(90);
-const DEFAULT_SNAPSHOT_EXPIRATION: Duration = Duration::days(7);
-const DEFAULT_TIMESTAMP_EXPIRATION: Duration = Duration::days(1);
+pub(crate) const DEFAULT_SNAPSHOT_EXPIRATION: Duration = Duration::days(7);
+pub(crate) const DEFAULT_TIMESTAMP_EXPIRATION: Duration = Duration::days(1);
+
+/// How long the metadata of each top-level role is valid for when it is staged, which can be
+/// shared between the builders of a repository with [RepoBuilder::expiration_durations].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpirationDurations {
+    root: Duration,
+    targets: Duration,
+    snapshot: Duration,
+    timestamp: Duration,
+}
+
+impl ExpirationDurations {
+    /// Create a new `ExpirationDurations` with the durations of every top-level role.
+    pub fn new(root: Duration, targets: Duration, snapshot: Duration, timestamp: Duration) -> Self {
+        ExpirationDurations {
+            root,
+            targets,
+            snapshot,
+            timestamp,
+        }
+    }
+
+    /// How long the root metadata is valid for.
+    pub fn root(&self) -> Duration {
+        self.root
+    }
+
+    /// How long the targets metadata is valid for.
+    pub fn targets(&self) -> Duration {
+        self.targets
+    }
+
+    /// How long the snapshot metadata is valid for.
+    pub fn snapshot(&self) -> Duration {
+        self.snapshot
+    }
+
+    /// How long the timestamp metadata is valid for.
+    pub fn timestamp(&self) -> Duration {
+        self.timestamp
+    }
+
+    /// Check that the timestamp metadata expires no later than the snapshot metadata, which
+    /// expires no later than the targets metadata, so that the metadata that is refreshed most
+    /// often is the first to expire.
+    pub fn check_ordering(&self) -> Result<()> {
+        if self.timestamp > self.snapshot || self.snapshot > self.targets {
+            return Err(Error::IllegalArgument(format!(
+                "expected the timestamp expiration ({}) to be at most the snapshot expiration \
+                 ({}), which is at most the targets expiration ({})",
+                self.timestamp, self.snapshot, self.targets
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for ExpirationDurations {
+    /// 365 days for the root metadata, 90 days for the targets metadata, 7 days for the snapshot
+    /// metadata, and 1 day for the timestamp metadata.
+    fn default() -> Self {
+        ExpirationDurations::new(
+            DEFAULT_ROOT_EXPIRATION,
+            DEFAULT_TARGETS_EXPIRATION,
+            DEFAULT_SNAPSHOT_EXPIRATION,
+            DEFAULT_TIMESTAMP_EXPIRATION,
+        )
+    }
+}
 
 /// Trait to track each of the [RepoBuilder] building states.
 ///
@@ -54,6 +140,7 @@ pub trait State: private::Sealed {}
 #[doc(hidden)]
 pub struct Root {
     builder: RootMetadataBuilder,
+    rotating_keys: bool,
 }
 
 impl State for Root {}
@@ -64,9 +151,20 @@ pub struct Targets<D: Pouf> {
     staged_root: Option<Staged<D, RootMetadata>>,
     targets: HashMap<TargetPath, TargetDescription>,
     delegation_keys: Vec<PublicKey>,
-    delegation_roles: Vec<Delegation>,
+    delegation_entries: Vec<DelegationEntry>,
+    succinct_delegation_roles: Option<SuccinctRoles>,
+    hashed_bins: Option<HashedBins>,
+    delegated_roles: BTreeMap<MetadataPath, DelegatedRole>,
     file_hash_algorithms: Vec<HashAlgorithm>,
     inherit_from_trusted_targets: bool,
+    removed_targets: HashSet<TargetPath>,
+    yanked_targets: HashSet<TargetPath>,
+}
+
+/// A delegated targets role whose targets metadata is staged along with the targets metadata.
+struct DelegatedRole {
+    delegation: Delegation,
+    targets: HashMap<TargetPath, TargetDescription>,
 }
 
 impl<D: Pouf> Targets<D> {
@@ -75,9 +173,14 @@ impl<D: Pouf> Targets<D> {
             staged_root,
             targets: HashMap::new(),
             delegation_keys: vec![],
-            delegation_roles: vec![],
+            delegation_entries: vec![],
+            succinct_delegation_roles: None,
+            hashed_bins: None,
+            delegated_roles: BTreeMap::new(),
             file_hash_algorithms: vec![HashAlgorithm::Sha256],
             inherit_from_trusted_targets: true,
+            removed_targets: HashSet::new(),
+            yanked_targets: HashSet::new(),
         }
     }
 }
@@ -89,9 +192,11 @@ impl<D: Pouf> State for Targets<D> {}
 pub struct Snapshot<D: Pouf> {
     staged_root: Option<Staged<D, RootMetadata>>,
     staged_targets: Option<Staged<D, TargetsMetadata>>,
+    staged_delegated_targets: StagedDelegatedTargets<D>,
     include_targets_length: bool,
     targets_hash_algorithms: Vec<HashAlgorithm>,
     inherit_from_trusted_snapshot: bool,
+    removed_targets: RemovedTargets,
 }
 
 impl<D: Pouf> State for Snapshot<D> {}
@@ -100,51 +205,60 @@ impl<D: Pouf> Snapshot<D> {
     fn new(
         staged_root: Option<Staged<D, RootMetadata>>,
         staged_targets: Option<Staged<D, TargetsMetadata>>,
+        staged_delegated_targets: StagedDelegatedTargets<D>,
     ) -> Self {
         Self {
             staged_root,
             staged_targets,
+            staged_delegated_targets,
             include_targets_length: false,
             targets_hash_algorithms: vec![],
             inherit_from_trusted_snapshot: true,
+            removed_targets: vec![],
         }
     }
 
     fn targets_description(&self) -> Result<Option<MetadataDescription<TargetsMetadata>>> {
         if let Some(ref targets) = self.staged_targets {
-            let length = if self.include_targets_length {
-                Some(targets.raw.as_bytes().len())
-            } else {
-                None
-            };
-
-            let hashes = if self.targets_hash_algorithms.is_empty() {
-                HashMap::new()
-            } else {
-                crypto::calculate_hashes_from_slice(
-                    targets.raw.as_bytes(),
-                    &self.targets_hash_algorithms,
-                )?
-            };
-
-            Ok(Some(MetadataDescription::new(
-                targets.metadata.version(),
-                length,
-                hashes,
-            )?))
+            Ok(Some(self.describe_targets(targets)?))
         } else {
             Ok(None)
         }
     }
+
+    fn describe_targets(
+        &self,
+        targets: &Staged<D, TargetsMetadata>,
+    ) -> Result<MetadataDescription<TargetsMetadata>> {
+        let length = if self.include_targets_length {
+            Some(targets.raw.as_bytes().len())
+        } else {
+            None
+        };
+
+        let hashes = if self.targets_hash_algorithms.is_empty() {
+            HashMap::new()
+        } else {
+            crypto::calculate_hashes_from_slice(
+                targets.raw.as_bytes(),
+                &self.targets_hash_algorithms,
+            )?
+        };
+
+        MetadataDescription::new(targets.metadata.version(), length, hashes)
+    }
 }
 
 /// State to stage a timestamp metadata.
 pub struct Timestamp<D: Pouf> {
     staged_root: Option<Staged<D, RootMetadata>>,
     staged_targets: Option<Staged<D, TargetsMetadata>>,
+    staged_delegated_targets: StagedDelegatedTargets<D>,
     staged_snapshot: Option<Staged<D, SnapshotMetadata>>,
     include_snapshot_length: bool,
     snapshot_hash_algorithms: Vec<HashAlgorithm>,
+    snapshot_merkle_tree: bool,
+    removed_targets: RemovedTargets,
 }
 
 impl<D: Pouf> Timestamp<D> {
@@ -152,9 +266,12 @@ impl<D: Pouf> Timestamp<D> {
         Self {
             staged_root: state.staged_root,
             staged_targets: state.staged_targets,
+            staged_delegated_targets: state.staged_delegated_targets,
             staged_snapshot,
             include_snapshot_length: false,
             snapshot_hash_algorithms: vec![],
+            snapshot_merkle_tree: false,
+            removed_targets: state.removed_targets,
         }
     }
 
@@ -192,25 +309,334 @@ impl<D: Pouf> State for Timestamp<D> {}
 pub struct Done<D: Pouf> {
     staged_root: Option<Staged<D, RootMetadata>>,
     staged_targets: Option<Staged<D, TargetsMetadata>>,
+    staged_delegated_targets: StagedDelegatedTargets<D>,
     staged_snapshot: Option<Staged<D, SnapshotMetadata>>,
     staged_timestamp: Option<Staged<D, TimestampMetadata>>,
+    snapshot_merkle_tree: Option<SnapshotMerkleTree>,
+    removed_targets: RemovedTargets,
 }
 
 impl<D: Pouf> State for Done<D> {}
 
+impl<D: Pouf> Done<D> {
+    fn into_metadata_set(self) -> RawSignedMetadataSet<D> {
+        let mut builder = RawSignedMetadataSetBuilder::new();
+
+        if let Some(root) = self.staged_root {
+            builder = builder.root(root.raw);
+        }
+
+        if let Some(targets) = self.staged_targets {
+            builder = builder.targets(targets.raw);
+        }
+
+        if let Some(snapshot) = self.staged_snapshot {
+            builder = builder.snapshot(snapshot.raw);
+        }
+
+        if let Some(timestamp) = self.staged_timestamp {
+            builder = builder.timestamp(timestamp.raw);
+        }
+
+        builder.build()
+    }
+}
+
 struct Staged<D: Pouf, M: Metadata> {
     metadata: M,
     raw: RawSignedMetadata<D, M>,
 }
 
+impl<D: Pouf, M: Metadata> Staged<D, M> {
+    /// Attach the `signatures` that were made over the canonical bytes of the metadata, and
+    /// serialize the metadata again.
+    fn attach_signatures(
+        &self,
+        signatures: &[(&PublicKey, &[u8])],
+        pretty_print: bool,
+    ) -> Result<RawSignedMetadata<D, M>> {
+        let mut imported = SignedMetadataBuilder::<D, M>::from_metadata(&self.metadata)?;
+        for (public_key, sig_bytes) in signatures {
+            imported = imported.add_signature(public_key, sig_bytes)?;
+        }
+
+        let mut signed = self.raw.parse_untrusted()?;
+        signed.merge_signatures(&imported.build())?;
+
+        if pretty_print {
+            signed.to_raw_pretty()
+        } else {
+            signed.to_raw()
+        }
+    }
+}
+
+/// The canonical bytes of staged metadata that are signed outside of this crate. See
+/// [RepoBuilder::export_unsigned].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsignedPayload {
+    role: MetadataPath,
+    version: u32,
+    payload: Vec<u8>,
+    digest: HashValue,
+}
+
+impl UnsignedPayload {
+    fn new<D, M>(role: MetadataPath, metadata: &M) -> Result<Self>
+    where
+        D: Pouf,
+        M: Metadata,
+    {
+        let payload = metadata::canonical_bytes::<D, M>(metadata)?;
+        Self::from_payload(role, metadata.version(), payload)
+    }
+
+    fn from_payload(role: MetadataPath, version: u32, payload: Vec<u8>) -> Result<Self> {
+        let digest = crypto::calculate_hashes_from_slice(&payload, &[HashAlgorithm::Sha256])?
+            .remove(&HashAlgorithm::Sha256)
+            .expect("the payload was hashed with SHA-256");
+
+        Ok(UnsignedPayload {
+            role,
+            version,
+            payload,
+            digest,
+        })
+    }
+
+    /// The role of the metadata.
+    pub fn role(&self) -> &MetadataPath {
+        &self.role
+    }
+
+    /// The version of the metadata.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The canonical bytes of the metadata, which are what every signature is made over.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// The SHA-256 digest of [UnsignedPayload::payload], so that the signer can check that it is
+    /// signing the payload it was meant to.
+    pub fn digest(&self) -> &HashValue {
+        &self.digest
+    }
+}
+
+/// The staged metadata and the signatures collected for it while it is signed by several
+/// custodians, possibly over several days. The session can be saved with [SigningSession::to_vec],
+/// shipped to the next custodian, and resumed with [RepoBuilder::resume_signing_session]. See
+/// [RepoBuilder::signing_session].
+#[derive(Debug, Clone)]
+pub struct SigningSession {
+    entries: Vec<SigningSessionEntry>,
+}
+
+#[derive(Debug, Clone)]
+struct SigningSessionEntry {
+    payload: UnsignedPayload,
+    raw: Vec<u8>,
+    signatures: Vec<(PublicKey, Vec<u8>)>,
+}
+
+impl SigningSession {
+    /// The payloads that are signed in this session, in the order they were staged.
+    pub fn payloads(&self) -> impl Iterator<Item = &UnsignedPayload> {
+        self.entries.iter().map(|entry| &entry.payload)
+    }
+
+    /// The keys that signed the metadata of `role` so far.
+    pub fn signers(&self, role: &MetadataPath) -> Vec<&PublicKey> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.payload.role() == role)
+            .flat_map(|entry| entry.signatures.iter().map(|(public_key, _)| public_key))
+            .collect()
+    }
+
+    /// Add a signature by `public_key` over the [UnsignedPayload::payload] of `role`, replacing
+    /// any earlier signature by the same key. The signature is verified before it is added.
+    pub fn add_signature(
+        &mut self,
+        role: &MetadataPath,
+        public_key: &PublicKey,
+        sig_bytes: &[u8],
+    ) -> Result<()> {
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.payload.role() == role)
+            .ok_or_else(|| {
+                Error::IllegalArgument(format!("no {} metadata in the signing session", role))
+            })?;
+
+        let sig = Signature::new(
+            public_key.key_id().clone(),
+            SignatureValue::new(sig_bytes.to_vec()),
+        );
+        public_key.verify(role, entry.payload.payload(), &sig)?;
+
+        entry
+            .signatures
+            .retain(|(key, _)| key.key_id() != public_key.key_id());
+        entry
+            .signatures
+            .push((public_key.clone(), sig_bytes.to_vec()));
+
+        Ok(())
+    }
+
+    /// Serialize the session, such as to save it to a file. The metadata and signatures are
+    /// base64 encoded, so that they are preserved byte for byte.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let encode = |bytes: &[u8]| data_encoding::BASE64.encode(bytes);
+        let shim = SigningSessionShim {
+            entries: self
+                .entries
+                .iter()
+                .map(|entry| SigningSessionEntryShim {
+                    role: entry.payload.role().clone(),
+                    version: entry.payload.version(),
+                    payload: encode(entry.payload.payload()),
+                    raw: encode(&entry.raw),
+                    signatures: entry
+                        .signatures
+                        .iter()
+                        .map(|(public_key, sig_bytes)| SigningSessionSignatureShim {
+                            public_key: public_key.clone(),
+                            signature: encode(sig_bytes),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        };
+
+        Ok(serde_json::to_vec(&shim)?)
+    }
+
+    /// Deserialize a session serialized with [SigningSession::to_vec]. The signatures are verified
+    /// again when the session is resumed.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        fn decode(encoded: &str) -> Result<Vec<u8>> {
+            data_encoding::BASE64
+                .decode(encoded.as_bytes())
+                .map_err(|e| Error::Encoding(format!("invalid base64 in signing session: {}", e)))
+        }
+
+        let shim: SigningSessionShim = serde_json::from_slice(bytes)?;
+        let entries = shim
+            .entries
+            .into_iter()
+            .map(|entry| {
+                Ok(SigningSessionEntry {
+                    payload: UnsignedPayload::from_payload(
+                        entry.role,
+                        entry.version,
+                        decode(&entry.payload)?,
+                    )?,
+                    raw: decode(&entry.raw)?,
+                    signatures: entry
+                        .signatures
+                        .into_iter()
+                        .map(|sig| Ok((sig.public_key, decode(&sig.signature)?)))
+                        .collect::<Result<_>>()?,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(SigningSession { entries })
+    }
+}
+
+/// The outcome of [RepoBuilder::commit_or_stage].
+#[derive(Debug)]
+pub enum CommitOutcome<D> {
+    /// Every threshold was met, so the metadata was committed and written to the repository.
+    Published(RawSignedMetadataSet<D>),
+
+    /// Some threshold was not met, so nothing was written to the repository.
+    Unpublished(UnpublishedMetadata),
+}
+
+/// Staged metadata that is signed by fewer keys than its thresholds require, so it must not be
+/// published yet. The remaining signatures can be collected with its [SigningSession], which is
+/// resumed with [RepoBuilder::resume_signing_session] once they are.
+#[derive(Debug, Clone)]
+pub struct UnpublishedMetadata {
+    session: SigningSession,
+    reports: Vec<SignatureReport>,
+}
+
+impl UnpublishedMetadata {
+    /// The signatures of every staged metadata, see [RepoBuilder::signature_reports].
+    pub fn signature_reports(&self) -> &[SignatureReport] {
+        &self.reports
+    }
+
+    /// The reports of the staged metadata that still needs signatures to meet its threshold.
+    pub fn missing_signatures(&self) -> impl Iterator<Item = &SignatureReport> {
+        self.reports
+            .iter()
+            .filter(|report| !report.is_threshold_met())
+    }
+
+    /// The session to collect the missing signatures with.
+    pub fn signing_session(&self) -> &SigningSession {
+        &self.session
+    }
+
+    /// Consume this metadata, and return the session to collect the missing signatures with.
+    pub fn into_signing_session(self) -> SigningSession {
+        self.session
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SigningSessionShim {
+    entries: Vec<SigningSessionEntryShim>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SigningSessionEntryShim {
+    role: MetadataPath,
+    version: u32,
+    payload: String,
+    raw: String,
+    #[serde(default)]
+    signatures: Vec<SigningSessionSignatureShim>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SigningSessionSignatureShim {
+    public_key: PublicKey,
+    signature: String,
+}
+
+/// Targets that were removed from the targets metadata, along with their trusted description, so
+/// that their stored files can be removed once the metadata is written.
+type RemovedTargets = Vec<(TargetPath, TargetDescription)>;
+
+/// Staged delegated targets metadata, along with the name of their role.
+type StagedDelegatedTargets<D> = Vec<(MetadataPath, Staged<D, TargetsMetadata>)>;
+
+/// Supplies the custom metadata of a target, see [RepoBuilder::target_custom]. This is `'static`
+/// so that the builder's drop doesn't require its borrows to outlive it.
+type TargetCustomFn =
+    Box<dyn Fn(&TargetPath) -> HashMap<String, serde_json::Value> + Send + Sync + 'static>;
+
 struct RepoContext<'a, D, R>
 where
     D: Pouf,
     R: RepositoryStorage<D>,
 {
     repo: R,
-    db: Option<&'a Database<D>>,
+    db: Option<Cow<'a, Database<D>>>,
     current_time: DateTime<Utc>,
+    current_time_set: bool,
+    deterministic: bool,
     signing_root_keys: Vec<&'a dyn PrivateKey>,
     signing_targets_keys: Vec<&'a dyn PrivateKey>,
     signing_snapshot_keys: Vec<&'a dyn PrivateKey>,
@@ -219,11 +645,18 @@ where
     trusted_targets_keys: Vec<&'a dyn PrivateKey>,
     trusted_snapshot_keys: Vec<&'a dyn PrivateKey>,
     trusted_timestamp_keys: Vec<&'a dyn PrivateKey>,
+    hashed_bins_keys: Vec<&'a dyn PrivateKey>,
+    delegated_role_keys: HashMap<MetadataPath, Vec<&'a dyn PrivateKey>>,
+    target_custom: Option<TargetCustomFn>,
     time_version: Option<u32>,
+    pretty_print: bool,
+    offline_signing: bool,
+    metadata_padding: Option<usize>,
     root_expiration_duration: Duration,
     targets_expiration_duration: Duration,
     snapshot_expiration_duration: Duration,
     timestamp_expiration_duration: Duration,
+    require_expiration_ordering: bool,
     _pouf: PhantomData<D>,
 }
 
@@ -291,6 +724,30 @@ where
         false
     }
 
+    /// Check that metadata can be staged reproducibly, if deterministic builds were requested.
+    fn check_deterministic(&self) -> Result<()> {
+        if self.deterministic && !self.current_time_set {
+            return Err(Error::IllegalArgument(
+                "deterministic builds require an explicit current time".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Pad snapshot or timestamp metadata, if padding is enabled.
+    fn pad<M>(&self, raw: RawSignedMetadata<D, M>) -> Result<RawSignedMetadata<D, M>>
+    where
+        M: Metadata,
+    {
+        match self.metadata_padding {
+            Some(size) => Ok(RawSignedMetadata::new(D::pad(
+                raw.as_bytes().to_vec(),
+                size,
+            )?)),
+            None => Ok(raw),
+        }
+    }
+
     /// The initial version number for non-root metadata.
     fn non_root_initial_version(&self) -> u32 {
         if let Some(time_version) = self.time_version {
@@ -315,11 +772,10 @@ where
     }
 
     /// The next version number for non-root metadata.
-    fn non_root_next_version(
-        &self,
-        current_version: u32,
-        path: fn() -> MetadataPath,
-    ) -> Result<u32> {
+    fn non_root_next_version<F>(&self, current_version: u32, path: F) -> Result<u32>
+    where
+        F: FnOnce() -> MetadataPath,
+    {
         if let Some(time_version) = self.time_version {
             // We can only use the time version if it's larger than our current version. If not,
             // then fall back to the next version.
@@ -332,9 +788,144 @@ where
             .checked_add(1)
             .ok_or_else(|| Error::MetadataVersionMustBeSmallerThanMaxU32(path()))
     }
+
+    /// Shard `targets` into `bins`, and sign the targets metadata of every bin that has a target.
+    /// Returns the delegations to the bins along with the staged bins.
+    fn stage_hashed_bins(
+        &self,
+        bins: &HashedBins,
+        targets: HashMap<TargetPath, TargetDescription>,
+        inherit_from_trusted_targets: bool,
+    ) -> Result<(Vec<Delegation>, StagedDelegatedTargets<D>)> {
+        let key_ids = self
+            .hashed_bins_keys
+            .iter()
+            .map(|key| key.public().key_id().clone())
+            .collect::<Vec<_>>();
+
+        let mut bin_targets = bins.bin_targets(targets);
+        let mut delegations = vec![];
+        let mut staged = vec![];
+        for role in bins.role_names() {
+            if self.hashed_bins_keys.is_empty() {
+                return Err(Error::MissingPrivateKey { role });
+            }
+
+            // Every bin is delegated to by its hash prefixes, whether or not it has any targets.
+            delegations.push(bins.delegation(&role, key_ids.iter().cloned())?);
+
+            // Bins that are already published and have no new targets are left alone.
+            let new_targets = bin_targets.remove(&role);
+            if inherit_from_trusted_targets
+                && new_targets.is_none()
+                && self.trusted_delegated_version(&role).is_some()
+            {
+                continue;
+            }
+
+            let bin = self.stage_delegated_targets(
+                &role,
+                &self.hashed_bins_keys,
+                new_targets.unwrap_or_default(),
+                inherit_from_trusted_targets,
+            )?;
+            staged.push((role, bin));
+        }
+
+        Ok((delegations, staged))
+    }
+
+    /// The version of the delegated targets metadata of `role` known to the database. A role that
+    /// the database hasn't fetched may still be listed in the trusted snapshot, and its version
+    /// must keep increasing.
+    fn trusted_delegated_version(&self, role: &MetadataPath) -> Option<u32> {
+        self.db
+            .as_deref()
+            .and_then(|db| db.trusted_delegations().get(role))
+            .map(|targets| targets.version())
+            .or_else(|| {
+                self.db
+                    .as_deref()
+                    .and_then(|db| db.trusted_snapshot())
+                    .and_then(|snapshot| snapshot.meta().get(role))
+                    .map(|description| description.version())
+            })
+    }
+
+    /// Build the targets metadata of the delegated `role` with `targets`, on top of the targets of
+    /// its trusted metadata if `inherit_from_trusted_targets` is set, and sign it with `keys`.
+    /// The custom metadata of `target_path`, with the entries of `custom` taking precedence over
+    /// those supplied by [RepoBuilder::target_custom].
+    fn target_custom(
+        &self,
+        target_path: &TargetPath,
+        custom: HashMap<String, serde_json::Value>,
+    ) -> HashMap<String, serde_json::Value> {
+        match self.target_custom {
+            Some(ref target_custom) => {
+                let mut merged = target_custom(target_path);
+                merged.extend(custom);
+                merged
+            }
+            None => custom,
+        }
+    }
+
+    fn stage_delegated_targets(
+        &self,
+        role: &MetadataPath,
+        keys: &[&'a dyn PrivateKey],
+        targets: HashMap<TargetPath, TargetDescription>,
+        inherit_from_trusted_targets: bool,
+    ) -> Result<Staged<D, TargetsMetadata>> {
+        if keys.is_empty() {
+            return Err(Error::MissingPrivateKey { role: role.clone() });
+        }
+
+        let mut builder = TargetsMetadataBuilder::new()
+            .expires(self.current_time + self.targets_expiration_duration);
+
+        builder = match self.trusted_delegated_version(role) {
+            Some(version) => builder.version(self.non_root_next_version(version, || role.clone())?),
+            None => builder.version(self.non_root_initial_version()),
+        };
+
+        if inherit_from_trusted_targets {
+            if let Some(trusted) = self
+                .db
+                .as_deref()
+                .and_then(|db| db.trusted_delegations().get(role))
+            {
+                for (target_path, target_description) in trusted.targets() {
+                    builder = builder
+                        .insert_target_description(target_path.clone(), target_description.clone());
+                }
+            }
+        }
+
+        for (target_path, target_description) in targets {
+            builder = builder.insert_target_description(target_path, target_description);
+        }
+
+        let metadata = builder.build()?;
+        self.check_deterministic()?;
+        let raw = sign(
+            &metadata,
+            keys.iter(),
+            self.pretty_print,
+            self.offline_signing,
+        )?;
+
+        Ok(Staged { metadata, raw })
+    }
 }
 
-fn sign<'a, D, I, M>(meta: &M, keys: I) -> Result<RawSignedMetadata<D, M>>
+fn sign<'a, D, I, M>(
+    meta: &M,
+    keys: I,
+    pretty_print: bool,
+    offline_signing: bool,
+) -> Result<RawSignedMetadata<D, M>>
 where
     D: Pouf,
     M: Metadata,
@@ -348,14 +939,115 @@ where
         signed_builder = signed_builder.sign(*key)?;
     }
 
-    // We need at least one private key to sign the metadata.
-    if !has_key {
+    // We need at least one private key to sign the metadata, unless it will be signed offline.
+    if !has_key && !offline_signing {
         return Err(Error::MissingPrivateKey {
             role: M::ROLE.into(),
         });
     }
 
-    signed_builder.build().to_raw()
+    if pretty_print {
+        signed_builder.build().to_raw_pretty()
+    } else {
+        signed_builder.build().to_raw()
+    }
+}
+
+/// Stage the metadata of a [SigningSession] entry again, checking that it is the metadata the
+/// payload was made from.
+fn resume_staged<D, M>(entry: &SigningSessionEntry) -> Result<Staged<D, M>>
+where
+    D: Pouf,
+    M: Metadata,
+{
+    let raw = RawSignedMetadata::<D, M>::new(entry.raw.clone());
+    let metadata = raw.parse_untrusted()?.assume_valid()?;
+
+    if UnsignedPayload::new::<D, M>(entry.payload.role().clone(), &metadata)? != entry.payload {
+        return Err(Error::IllegalArgument(format!(
+            "the staged {} metadata doesn't match its payload in the signing session",
+            entry.payload.role()
+        )));
+    }
+
+    Ok(Staged { metadata, raw })
+}
+
+fn not_staged(role: &MetadataPath) -> Error {
+    Error::IllegalArgument(format!("no {} metadata was staged", role))
+}
+
+/// Signatures can't be attached to the metadata of `role` if `description` includes its length or
+/// hashes, which would no longer match.
+fn check_not_described_by_hash<M: Metadata>(
+    role: &MetadataPath,
+    description: &MetadataDescription<M>,
+) -> Result<()> {
+    if description.length().is_some() || !description.hashes().is_empty() {
+        return Err(Error::IllegalArgument(format!(
+            "the {} metadata is described by its length or hashes, so it cannot be signed after \
+             it is staged",
+            role
+        )));
+    }
+
+    Ok(())
+}
+
+/// Collect the target paths of every file under `dir`, along with their path on disk.
+fn walk_target_dir(dir: &Path, prefix: &str, files: &mut Vec<(TargetPath, PathBuf)>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry.file_name().into_string().map_err(|name| {
+            Error::IllegalArgument(format!("file name {:?} is not valid UTF-8", name))
+        })?;
+        let path = entry.path();
+
+        if fs::metadata(&path)?.is_dir() {
+            walk_target_dir(&path, &format!("{}{}/", prefix, name), files)?;
+        } else {
+            files.push((TargetPath::new(format!("{}{}", prefix, name))?, path));
+        }
+    }
+
+    Ok(())
+}
+
+/// Hash the `files` on as many threads as there is available parallelism, and return their lengths
+/// and hashes.
+fn hash_target_files(
+    files: &[(TargetPath, PathBuf)],
+    hash_algorithms: &[HashAlgorithm],
+) -> Result<Vec<(u64, HashMap<HashAlgorithm, crypto::HashValue>)>> {
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let chunk_size = files.len() / threads + 1;
+
+    thread::scope(|scope| {
+        let handles = files
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|(_, path)| {
+                            crypto::calculate_hashes_from_std_reader(
+                                File::open(path)?,
+                                hash_algorithms,
+                            )
+                        })
+                        .collect::<Result<Vec<_>>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut hashed_files = Vec::with_capacity(files.len());
+        for handle in handles {
+            hashed_files.extend(handle.join().expect("hashing thread panicked")?);
+        }
+        Ok(hashed_files)
+    })
 }
 
 /// This helper builder simplifies the process of creating new metadata.
@@ -372,30 +1064,193 @@ where
 impl<'a, D, R> RepoBuilder<'a, D, R, Root>
 where
     D: Pouf,
-    R: RepositoryStorage<D>,
+    R: RepositoryStorage<D> + RepositoryProvider<D>,
 {
-    /// Create a [RepoBuilder] for creating metadata for a new repository.
+    /// Create a [RepoBuilder] for making incremental changes to an existing repository, by reading
+    /// its metadata with a [Client] that starts from the trusted `root`. Every metadata the
+    /// repository trusts is read and verified, including the targets metadata of every delegated
+    /// role, so the new metadata continues from the current versions and inherits the targets and
+    /// delegations of the repository like with [RepoBuilder::from_database].
     ///
-    /// # Examples
+    /// Returns an error if the metadata of the repository can't be verified.
+    pub async fn from_repo(repo: R, root: &RawSignedMetadata<D, RootMetadata>) -> Result<Self> {
+        let mut client =
+            Client::with_trusted_root(Config::default(), root, EphemeralRepository::new(), repo)
+                .await?;
+        client.update().await?;
+
+        // Listing the targets fetches the metadata of every reachable delegated role.
+        let _ = client.trusted_targets_iter().await?;
+
+        let parts = client.into_parts();
+        Ok(Self::with_database(
+            parts.remote,
+            Cow::Owned(parts.database),
+        ))
+    }
+
+    /// Convert a repository that does not use consistent snapshots into one that does. Changing
+    /// the `consistent_snapshot` flag of the root metadata alone breaks clients, which then look
+    /// for files the repository doesn't have. Instead this:
     ///
-    /// ```rust
-    /// # use {
-    /// #     futures_executor::block_on,
-    /// #     tuf::{
-    /// #         pouf::Pouf1,
-    /// #         crypto::Ed25519PrivateKey,
-    /// #         repo_builder::RepoBuilder,
-    /// #         repository::EphemeralRepository,
-    /// #     },
-    /// # };
-    /// #
-    /// # let key = Ed25519PrivateKey::from_pkcs8(
-    /// #     include_bytes!("../tests/ed25519/ed25519-1.pk8.der")
-    /// # ).unwrap();
-    /// #
-    /// # block_on(async {
-    /// let mut repo = EphemeralRepository::<Pouf1>::new();
-    /// let _metadata = RepoBuilder::create(&mut repo)
+    /// * Copies every trusted target to its hash prefixed path, after checking it against its
+    ///   trusted description.
+    /// * Copies the trusted snapshot, targets, and delegated targets metadata to their versioned
+    ///   paths.
+    /// * Commits a new root metadata with `consistent_snapshot` enabled, which is staged like with
+    ///   [RepoBuilder::stage_root], so the keys of every top-level role must be trusted.
+    ///
+    /// The existing unprefixed targets and unversioned metadata are left in place, so clients that
+    /// haven't updated to the new root continue to work.
+    ///
+    /// Every delegated role listed in the trusted snapshot must be trusted by the database, such
+    /// as one from [RepoBuilder::from_repo]. Returns an error if the repository has no database,
+    /// already uses consistent snapshots, or a target doesn't match its trusted description.
+    pub async fn migrate_to_consistent_snapshot(self) -> Result<RawSignedMetadataSet<D>> {
+        let db = self.ctx.db.as_deref().ok_or_else(|| {
+            Error::IllegalArgument("migrating a repository requires a database".into())
+        })?;
+
+        if db.trusted_root().consistent_snapshot() {
+            return Err(Error::IllegalArgument(
+                "repository already uses consistent snapshots".into(),
+            ));
+        }
+
+        let snapshot = db
+            .trusted_snapshot()
+            .ok_or_else(|| Error::MetadataNotFound {
+                path: MetadataPath::snapshot(),
+                version: MetadataVersion::None,
+            })?;
+        let targets = db
+            .trusted_targets()
+            .ok_or_else(|| Error::MetadataNotFound {
+                path: MetadataPath::targets(),
+                version: MetadataVersion::None,
+            })?;
+
+        for (path, description) in snapshot.meta() {
+            if path != &MetadataPath::targets() && !db.trusted_delegations().contains_key(path) {
+                return Err(Error::MetadataNotFound {
+                    path: path.clone(),
+                    version: MetadataVersion::Number(description.version()),
+                });
+            }
+        }
+
+        let target_descriptions = targets.targets().iter().chain(
+            db.trusted_delegations()
+                .values()
+                .flat_map(|delegated| delegated.targets().iter()),
+        );
+        for (target_path, target_description) in target_descriptions {
+            if target_description.hashes().is_empty() {
+                return Err(Error::UnverifiableTarget(target_path.clone()));
+            }
+
+            let mut buf = Vec::new();
+            self.ctx
+                .repo
+                .fetch_target(target_path)
+                .await?
+                .read_to_end(&mut buf)
+                .await?;
+
+            let algorithms = target_description
+                .hashes()
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>();
+            let hashes = crypto::calculate_hashes_from_slice(&buf, &algorithms)?;
+            if target_description
+                .length()
+                .map_or(false, |len| len != buf.len() as u64)
+                || &hashes != target_description.hashes()
+            {
+                return Err(Error::IllegalArgument(format!(
+                    "target {} does not match its trusted description",
+                    target_path
+                )));
+            }
+
+            for digest in target_description.hashes().values() {
+                self.ctx
+                    .repo
+                    .store_target(&target_path.with_hash_prefix(digest)?, &mut buf.as_slice())
+                    .await?;
+            }
+        }
+
+        let raw = db.to_raw_metadata_set();
+        if let Some(raw_snapshot) = raw.snapshot() {
+            self.ctx
+                .repo
+                .store_metadata(
+                    &MetadataPath::snapshot(),
+                    MetadataVersion::Number(snapshot.version()),
+                    &mut raw_snapshot.as_bytes(),
+                )
+                .await?;
+        }
+        if let Some(raw_targets) = raw.targets() {
+            self.ctx
+                .repo
+                .store_metadata(
+                    &MetadataPath::targets(),
+                    MetadataVersion::Number(targets.version()),
+                    &mut raw_targets.as_bytes(),
+                )
+                .await?;
+        }
+        for (path, raw_delegated) in raw.delegations() {
+            let version = db.trusted_delegations()[path].version();
+            self.ctx
+                .repo
+                .store_metadata(
+                    path,
+                    MetadataVersion::Number(version),
+                    &mut raw_delegated.as_bytes(),
+                )
+                .await?;
+        }
+
+        self.stage_root_with_builder(|builder| builder.consistent_snapshot(true))?
+            .skip_targets()
+            .skip_snapshot()
+            .skip_timestamp()
+            .commit()
+            .await
+    }
+}
+
+impl<'a, D, R> RepoBuilder<'a, D, R, Root>
+where
+    D: Pouf,
+    R: RepositoryStorage<D>,
+{
+    /// Create a [RepoBuilder] for creating metadata for a new repository.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use {
+    /// #     futures_executor::block_on,
+    /// #     tuf::{
+    /// #         pouf::Pouf1,
+    /// #         crypto::Ed25519PrivateKey,
+    /// #         repo_builder::RepoBuilder,
+    /// #         repository::EphemeralRepository,
+    /// #     },
+    /// # };
+    /// #
+    /// # let key = Ed25519PrivateKey::from_pkcs8(
+    /// #     include_bytes!("../tests/ed25519/ed25519-1.pk8.der")
+    /// # ).unwrap();
+    /// #
+    /// # block_on(async {
+    /// let mut repo = EphemeralRepository::<Pouf1>::new();
+    /// let _metadata = RepoBuilder::create(&mut repo)
     ///     .trusted_root_keys(&[&key])
     ///     .trusted_targets_keys(&[&key])
     ///     .trusted_snapshot_keys(&[&key])
@@ -411,6 +1266,8 @@ where
                 repo,
                 db: None,
                 current_time: Utc::now(),
+                current_time_set: false,
+                deterministic: false,
                 signing_root_keys: vec![],
                 signing_targets_keys: vec![],
                 signing_snapshot_keys: vec![],
@@ -419,11 +1276,18 @@ where
                 trusted_targets_keys: vec![],
                 trusted_snapshot_keys: vec![],
                 trusted_timestamp_keys: vec![],
+                hashed_bins_keys: vec![],
+                delegated_role_keys: HashMap::new(),
+                target_custom: None,
                 time_version: None,
+                pretty_print: false,
+                offline_signing: false,
+                metadata_padding: None,
                 root_expiration_duration: DEFAULT_ROOT_EXPIRATION,
                 targets_expiration_duration: DEFAULT_TARGETS_EXPIRATION,
                 snapshot_expiration_duration: DEFAULT_SNAPSHOT_EXPIRATION,
                 timestamp_expiration_duration: DEFAULT_TIMESTAMP_EXPIRATION,
+                require_expiration_ordering: false,
                 _pouf: PhantomData,
             },
             state: Root {
@@ -433,6 +1297,7 @@ where
                     .targets_threshold(1)
                     .snapshot_threshold(1)
                     .timestamp_threshold(1),
+                rotating_keys: false,
             },
         }
     }
@@ -484,6 +1349,10 @@ where
     /// # });
     /// ```
     pub fn from_database(repo: R, db: &'a Database<D>) -> Self {
+        Self::with_database(repo, Cow::Borrowed(db))
+    }
+
+    fn with_database(repo: R, db: Cow<'a, Database<D>>) -> Self {
         let builder = {
             let trusted_root = db.trusted_root();
 
@@ -500,6 +1369,8 @@ where
                 repo,
                 db: Some(db),
                 current_time: Utc::now(),
+                current_time_set: false,
+                deterministic: false,
                 signing_root_keys: vec![],
                 signing_targets_keys: vec![],
                 signing_snapshot_keys: vec![],
@@ -508,14 +1379,24 @@ where
                 trusted_targets_keys: vec![],
                 trusted_snapshot_keys: vec![],
                 trusted_timestamp_keys: vec![],
+                hashed_bins_keys: vec![],
+                delegated_role_keys: HashMap::new(),
+                target_custom: None,
                 time_version: None,
+                pretty_print: false,
+                offline_signing: false,
+                metadata_padding: None,
                 root_expiration_duration: DEFAULT_ROOT_EXPIRATION,
                 targets_expiration_duration: DEFAULT_TARGETS_EXPIRATION,
                 snapshot_expiration_duration: DEFAULT_SNAPSHOT_EXPIRATION,
                 timestamp_expiration_duration: DEFAULT_TIMESTAMP_EXPIRATION,
+                require_expiration_ordering: false,
                 _pouf: PhantomData,
             },
-            state: Root { builder },
+            state: Root {
+                builder,
+                rotating_keys: false,
+            },
         }
     }
 
@@ -525,6 +1406,7 @@ where
     /// Default is the current wall clock time in UTC.
     pub fn current_time(mut self, current_time: DateTime<Utc>) -> Self {
         self.ctx.current_time = current_time;
+        self.ctx.current_time_set = true;
 
         // Update our time version if enabled.
         if self.ctx.time_version.is_some() {
@@ -545,6 +1427,56 @@ where
         self
     }
 
+    /// Require that identical inputs produce byte-identical metadata, so that builds can be
+    /// reproduced and compared with [RawSignedMetadataSet::digest]. Keys, signatures and targets
+    /// are always written in a stable order, but expirations and time versions are derived from
+    /// the current time, so staging any metadata fails unless [RepoBuilder::current_time] was
+    /// called.
+    ///
+    /// Default is `false`, which uses the current wall clock time if no time was set.
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.ctx.deterministic = deterministic;
+        self
+    }
+
+    /// Store metadata as indented JSON, or in the equivalent form of another [Pouf], so that
+    /// changes to it are easier to review. Signatures are still made over the canonical form, and
+    /// the snapshot and timestamp metadata describe the bytes as they are stored.
+    ///
+    /// Default is `false`, which stores the canonical form.
+    pub fn pretty_print(mut self, pretty_print: bool) -> Self {
+        self.ctx.pretty_print = pretty_print;
+        self
+    }
+
+    /// Pad the snapshot and timestamp metadata to a multiple of `size` bytes with [Pouf::pad], so
+    /// that observers of the repository can't tell how much changed from the size of the metadata
+    /// alone. Metadata larger than `size` is padded to the next multiple of it, so choose a `size`
+    /// that fits the usual metadata, and that clients accept as the maximum length of the
+    /// timestamp metadata. Returns an error when the metadata is staged if the pouf does not
+    /// support padding.
+    ///
+    /// Default is `None`, which does not pad the metadata.
+    pub fn metadata_padding(mut self, size: Option<usize>) -> Self {
+        self.ctx.metadata_padding = size;
+        self
+    }
+
+    /// Allow staging metadata that is signed by fewer keys than its threshold, or by no keys at
+    /// all, so that it can be signed outside of this crate, such as in an air-gapped root signing
+    /// ceremony. The payloads to sign are exported with [RepoBuilder::export_unsigned], and the
+    /// signatures are attached with [RepoBuilder::import_signatures] before committing, which
+    /// still checks that every threshold is met.
+    ///
+    /// When rotating keys with [RepoBuilder::rotate_keys], the rotation is only checked when the
+    /// metadata is committed.
+    ///
+    /// Default is `false`, which requires at least one key to sign every staged metadata.
+    pub fn offline_signing(mut self, offline_signing: bool) -> Self {
+        self.ctx.offline_signing = offline_signing;
+        self
+    }
+
     /// Sets that the root metadata will expire after this duration past the current time.
     ///
     /// Defaults to 365 days.
@@ -594,6 +1526,26 @@ where
         self
     }
 
+    /// Sets how long the metadata of every top-level role is valid for, like calling the
+    /// `*_expiration_duration` method of every role. An expiration passed to a
+    /// `stage_*_with_builder` method still takes precedence.
+    pub fn expiration_durations(mut self, durations: ExpirationDurations) -> Self {
+        self.ctx.root_expiration_duration = durations.root();
+        self.ctx.targets_expiration_duration = durations.targets();
+        self.ctx.snapshot_expiration_duration = durations.snapshot();
+        self.ctx.timestamp_expiration_duration = durations.timestamp();
+        self
+    }
+
+    /// Whether to fail to commit if the expiration durations are not ordered as described by
+    /// [ExpirationDurations::check_ordering].
+    ///
+    /// Default is `false`.
+    pub fn require_expiration_ordering(mut self, require: bool) -> Self {
+        self.ctx.require_expiration_ordering = require;
+        self
+    }
+
     /// Sign the root metadata with `keys`, but do not include the keys as trusted root keys in the
     /// root metadata. This is typically used to support root key rotation.
     pub fn signing_root_keys(mut self, keys: &[&'a dyn PrivateKey]) -> Self {
@@ -670,6 +1622,122 @@ where
         self
     }
 
+    /// Rotate the keys of the top-level `role` to `new_keys`, of which `new_threshold` must sign
+    /// the metadata of the role. This can only be used with a builder from
+    /// [RepoBuilder::from_database].
+    ///
+    /// The keys that were passed to the `trusted_*_keys` method of the role are the outgoing keys.
+    /// They are removed from the staged root metadata, but still sign the metadata of the role,
+    /// like keys passed to the `signing_*_keys` method. When rotating the root keys, the outgoing
+    /// keys must meet the threshold of the trusted root metadata, since the next root metadata is
+    /// signed by both the outgoing and the incoming root keys.
+    ///
+    /// Staging the root metadata fails if the trusted [Database] would not update to it.
+    pub fn rotate_keys(
+        mut self,
+        role: Role,
+        new_keys: &[&'a dyn PrivateKey],
+        new_threshold: u32,
+    ) -> Result<Self> {
+        let db = self.ctx.db.as_deref().ok_or_else(|| {
+            Error::IllegalArgument(
+                "keys can only be rotated in a repository with a trusted root".into(),
+            )
+        })?;
+
+        if new_threshold == 0 || new_keys.len() < new_threshold as usize {
+            return Err(Error::IllegalArgument(format!(
+                "{} new {:?} keys cannot meet a threshold of {}",
+                new_keys.len(),
+                role,
+                new_threshold
+            )));
+        }
+
+        let trusted_root = db.trusted_root();
+        let (trusted_keys, signing_keys, (trusted_threshold, trusted_key_ids)) = match role {
+            Role::Root => (
+                &mut self.ctx.trusted_root_keys,
+                &mut self.ctx.signing_root_keys,
+                (
+                    trusted_root.root().threshold(),
+                    trusted_root.root().key_ids(),
+                ),
+            ),
+            Role::Targets => (
+                &mut self.ctx.trusted_targets_keys,
+                &mut self.ctx.signing_targets_keys,
+                (
+                    trusted_root.targets().threshold(),
+                    trusted_root.targets().key_ids(),
+                ),
+            ),
+            Role::Snapshot => (
+                &mut self.ctx.trusted_snapshot_keys,
+                &mut self.ctx.signing_snapshot_keys,
+                (
+                    trusted_root.snapshot().threshold(),
+                    trusted_root.snapshot().key_ids(),
+                ),
+            ),
+            Role::Timestamp => (
+                &mut self.ctx.trusted_timestamp_keys,
+                &mut self.ctx.signing_timestamp_keys,
+                (
+                    trusted_root.timestamp().threshold(),
+                    trusted_root.timestamp().key_ids(),
+                ),
+            ),
+            Role::Custom => {
+                return Err(Error::IllegalArgument(
+                    "only the keys of the root, targets, snapshot and timestamp roles can be \
+                     rotated"
+                        .into(),
+                ))
+            }
+        };
+
+        signing_keys.append(trusted_keys);
+
+        if role == Role::Root {
+            let outgoing_key_ids = signing_keys
+                .iter()
+                .map(|key| key.public().key_id())
+                .filter(|key_id| trusted_key_ids.contains(key_id))
+                .collect::<HashSet<_>>();
+            if outgoing_key_ids.len() < trusted_threshold as usize {
+                return Err(Error::IllegalArgument(format!(
+                    "{} outgoing root keys cannot meet the trusted threshold of {}",
+                    outgoing_key_ids.len(),
+                    trusted_threshold
+                )));
+            }
+        }
+
+        let mut builder = self.state.builder.clear_role_keys(&role);
+        for key in new_keys {
+            trusted_keys.push(*key);
+            let public = key.public().clone();
+            builder = match role {
+                Role::Root => builder.root_key(public),
+                Role::Targets => builder.targets_key(public),
+                Role::Snapshot => builder.snapshot_key(public),
+                Role::Timestamp => builder.timestamp_key(public),
+                Role::Custom => unreachable!("custom roles were rejected above"),
+            };
+        }
+        self.state.builder = match role {
+            Role::Root => builder.root_threshold(new_threshold),
+            Role::Targets => builder.targets_threshold(new_threshold),
+            Role::Snapshot => builder.snapshot_threshold(new_threshold),
+            Role::Timestamp => builder.timestamp_threshold(new_threshold),
+            Role::Custom => unreachable!("custom roles were rejected above"),
+        };
+        self.state.rotating_keys = true;
+
+        Ok(self)
+    }
+
     /// Stage a root metadata.
     ///
     /// If this is a new repository, the root will be staged with:
@@ -744,7 +1812,7 @@ where
     where
         F: FnOnce(RootMetadataBuilder) -> RootMetadataBuilder,
     {
-        let next_version = if let Some(db) = self.ctx.db {
+        let next_version = if let Some(db) = self.ctx.db.as_deref() {
             db.trusted_root().version().checked_add(1).ok_or_else(|| {
                 Error::MetadataVersionMustBeSmallerThanMaxU32(MetadataPath::root())
             })?
@@ -759,14 +1827,25 @@ where
             .expires(self.ctx.current_time + self.ctx.root_expiration_duration);
         let root = f(root_builder).build()?;
 
+        self.ctx.check_deterministic()?;
         let raw_root = sign(
             &root,
             self.ctx
                 .signing_root_keys
                 .iter()
                 .chain(&self.ctx.trusted_root_keys),
+            self.ctx.pretty_print,
+            self.ctx.offline_signing,
         )?;
 
+        // Refuse to stage a key rotation that the trusted database would reject. Metadata that is
+        // signed offline is only checked when it is committed.
+        if self.state.rotating_keys && !self.ctx.offline_signing {
+            if let Some(db) = self.ctx.db.as_deref() {
+                db.clone().update_root(&raw_root)?;
+            }
+        }
+
         Ok(RepoBuilder {
             ctx: self.ctx,
             state: Targets::new(Some(Staged {
@@ -795,6 +1874,50 @@ where
             .await
     }
 
+    /// Add a target from a description that was computed elsewhere, without reading or storing
+    /// the target file, and may stage a root metadata if necessary.
+    ///
+    /// See `RepoBuilder<Targets>::add_target_description` for more details.
+    pub fn add_target_description(
+        self,
+        target_path: TargetPath,
+        target_description: TargetDescription,
+    ) -> Result<RepoBuilder<'a, D, R, Targets<D>>> {
+        Ok(self
+            .stage_root_if_necessary()?
+            .add_target_description(target_path, target_description))
+    }
+
+    /// Add several targets from descriptions that were computed elsewhere, and may stage a root
+    /// metadata if necessary.
+    ///
+    /// See `RepoBuilder<Targets>::add_target_descriptions` for more details.
+    pub fn add_target_descriptions<I>(self, targets: I) -> Result<RepoBuilder<'a, D, R, Targets<D>>>
+    where
+        I: IntoIterator<Item = (TargetPath, TargetDescription)>,
+    {
+        Ok(self
+            .stage_root_if_necessary()?
+            .add_target_descriptions(targets))
+    }
+
+    /// Remove a target from the targets metadata, and may stage a root metadata if necessary.
+    ///
+    /// See `RepoBuilder<Targets>::remove_target` for more details.
+    pub fn remove_target(
+        self,
+        target_path: TargetPath,
+    ) -> Result<RepoBuilder<'a, D, R, Targets<D>>> {
+        Ok(self.stage_root_if_necessary()?.remove_target(target_path))
+    }
+
+    /// Mark a target as yanked, and may stage a root metadata if necessary.
+    ///
+    /// See `RepoBuilder<Targets>::yank_target` for more details.
+    pub fn yank_target(self, target_path: TargetPath) -> Result<RepoBuilder<'a, D, R, Targets<D>>> {
+        Ok(self.stage_root_if_necessary()?.yank_target(target_path))
+    }
+
     /// Validate and write the metadata to the repository.
     ///
     /// This may stage a root, targets, snapshot, and timestamp metadata if necessary.
@@ -804,10 +1927,148 @@ where
         self.stage_root_if_necessary()?.commit().await
     }
 
+    /// Resume a [SigningSession] that was started with [RepoBuilder::signing_session], by staging
+    /// its metadata again and attaching the signatures collected so far. The resulting builder
+    /// can collect more signatures with [RepoBuilder::import_signatures], or commit the metadata.
+    ///
+    /// This builder should be configured like the one that started the session, with the same
+    /// repository, trusted database, and pretty printing and padding settings.
+    ///
+    /// Returns an error if the staged metadata of the session doesn't match its payloads, or if a
+    /// signature is invalid.
+    pub fn resume_signing_session(
+        self,
+        session: &SigningSession,
+    ) -> Result<RepoBuilder<'a, D, R, Done<D>>> {
+        let mut state = Done {
+            staged_root: None,
+            staged_targets: None,
+            staged_delegated_targets: vec![],
+            staged_snapshot: None,
+            staged_timestamp: None,
+            snapshot_merkle_tree: None,
+            removed_targets: vec![],
+        };
+
+        for entry in &session.entries {
+            let role = entry.payload.role();
+            if *role == MetadataPath::root() {
+                state.staged_root = Some(resume_staged(entry)?);
+            } else if *role == MetadataPath::targets() {
+                state.staged_targets = Some(resume_staged(entry)?);
+            } else if *role == MetadataPath::snapshot() {
+                state.staged_snapshot = Some(resume_staged(entry)?);
+            } else if *role == MetadataPath::timestamp() {
+                state.staged_timestamp = Some(resume_staged(entry)?);
+            } else {
+                state
+                    .staged_delegated_targets
+                    .push((role.clone(), resume_staged(entry)?));
+            }
+        }
+
+        // The Merkle tree isn't part of the session, so build it again from the snapshot.
+        if let Some(merkle_root) = state
+            .staged_timestamp
+            .as_ref()
+            .and_then(|timestamp| timestamp.metadata.merkle_root())
+        {
+            let tree = if let Some(ref snapshot) = state.staged_snapshot {
+                SnapshotMerkleTree::from_snapshot(&snapshot.metadata)?
+            } else {
+                let snapshot = self
+                    .ctx
+                    .db
+                    .as_deref()
+                    .and_then(|db| db.trusted_snapshot())
+                    .ok_or_else(|| Error::MetadataNotFound {
+                        path: MetadataPath::snapshot(),
+                        version: MetadataVersion::None,
+                    })?;
+                SnapshotMerkleTree::from_snapshot(snapshot)?
+            };
+
+            if tree.root() != merkle_root {
+                return Err(Error::IllegalArgument(
+                    "the snapshot doesn't match the Merkle root of the timestamp".into(),
+                ));
+            }
+            state.snapshot_merkle_tree = Some(tree);
+        }
+
+        let mut builder = RepoBuilder {
+            ctx: self.ctx,
+            state,
+        };
+
+        for entry in &session.entries {
+            if entry.signatures.is_empty() {
+                continue;
+            }
+
+            let signatures = entry
+                .signatures
+                .iter()
+                .map(|(public_key, sig_bytes)| (public_key, sig_bytes.as_slice()))
+                .collect::<Vec<_>>();
+            builder = builder.import_signatures(entry.payload.role(), &signatures)?;
+        }
+
+        Ok(builder)
+    }
+
+    /// Refresh the metadata of the top-level `roles` of an existing repository, and commit it.
+    /// This can only be used with a builder from [RepoBuilder::from_database].
+    ///
+    /// The metadata of every role in `roles` is staged with the next version and a new expiration,
+    /// and inherits everything it describes from the trusted metadata, so no targets change. The
+    /// snapshot and timestamp metadata are also staged if they describe refreshed metadata or if
+    /// they expired. This is the everyday operation that keeps a repository from expiring, which
+    /// typically refreshes the snapshot and timestamp metadata with online keys.
+    pub async fn refresh(self, roles: &[Role]) -> Result<RawSignedMetadataSet<D>> {
+        if self.ctx.db.is_none() {
+            return Err(Error::IllegalArgument(
+                "metadata can only be refreshed in a repository with a trusted root".into(),
+            ));
+        }
+
+        if roles.contains(&Role::Custom) {
+            return Err(Error::IllegalArgument(
+                "only the root, targets, snapshot and timestamp metadata can be refreshed".into(),
+            ));
+        }
+
+        let builder = if roles.contains(&Role::Root) {
+            self.stage_root()?
+        } else {
+            self.skip_root()
+        };
+
+        let builder = if roles.contains(&Role::Targets) {
+            builder.stage_targets()?
+        } else {
+            builder.skip_targets()
+        };
+
+        let builder = if roles.contains(&Role::Snapshot) {
+            builder.stage_snapshot()?
+        } else {
+            builder.stage_snapshot_if_necessary()?
+        };
+
+        let builder = if roles.contains(&Role::Timestamp) {
+            builder.stage_timestamp()?
+        } else {
+            builder.stage_timestamp_if_necessary()?
+        };
+
+        builder.commit().await
+    }
+
     /// Check if we need a new root database.
     fn need_new_root(&self) -> bool {
         // We need a new root metadata if we don't have a database yet.
-        let trusted_root = if let Some(db) = self.ctx.db {
+        let trusted_root = if let Some(db) = self.ctx.db.as_deref() {
             db.trusted_root()
         } else {
             return true;
@@ -854,6 +2115,67 @@ where
         self
     }
 
+    /// Supply the custom metadata of every target added to this builder, such as its version or
+    /// architecture, with `custom`. It is called with the path of every target, including those
+    /// added with [RepoBuilder::add_targets_from_dir] and [RepoBuilder::add_delegated_target].
+    ///
+    /// The custom metadata passed to [RepoBuilder::add_target_with_custom] takes precedence over
+    /// the entries returned by `custom`.
+    ///
+    /// ```rust
+    /// # use {
+    /// #     futures_executor::block_on,
+    /// #     futures_util::io::Cursor,
+    /// #     maplit::hashmap,
+    /// #     serde_json::json,
+    /// #     tuf::{
+    /// #         pouf::Pouf1,
+    /// #         crypto::Ed25519PrivateKey,
+    /// #         metadata::TargetPath,
+    /// #         repo_builder::RepoBuilder,
+    /// #         repository::EphemeralRepository,
+    /// #     },
+    /// # };
+    /// #
+    /// # let key = Ed25519PrivateKey::from_pkcs8(
+    /// #     include_bytes!("../tests/ed25519/ed25519-1.pk8.der")
+    /// # ).unwrap();
+    /// #
+    /// # block_on(async {
+    /// let versions = hashmap! {
+    ///     TargetPath::new("app.tar").unwrap() => json!("1.2.0"),
+    /// };
+    ///
+    /// let mut repo = EphemeralRepository::<Pouf1>::new();
+    /// let _metadata = RepoBuilder::create(&mut repo)
+    ///     .trusted_root_keys(&[&key])
+    ///     .trusted_targets_keys(&[&key])
+    ///     .trusted_snapshot_keys(&[&key])
+    ///     .trusted_timestamp_keys(&[&key])
+    ///     .stage_root()
+    ///     .unwrap()
+    ///     .target_custom(move |target_path| {
+    ///         versions
+    ///             .get(target_path)
+    ///             .map(|version| hashmap! { "version".into() => version.clone() })
+    ///             .unwrap_or_default()
+    ///     })
+    ///     .add_target(TargetPath::new("app.tar").unwrap(), Cursor::new(b"app"))
+    ///     .await
+    ///     .unwrap()
+    ///     .commit()
+    ///     .await
+    ///     .unwrap();
+    /// # });
+    /// ```
+    pub fn target_custom<F>(mut self, custom: F) -> Self
+    where
+        F: Fn(&TargetPath) -> HashMap<String, serde_json::Value> + Send + Sync + 'static,
+    {
+        self.ctx.target_custom = Some(Box::new(custom));
+        self
+    }
+
     /// Stage a targets metadata using the default settings.
     pub fn stage_targets(self) -> Result<RepoBuilder<'a, D, R, Snapshot<D>>> {
         self.stage_targets_with_builder(|builder| builder)
@@ -875,7 +2197,7 @@ where
     pub fn skip_targets(self) -> RepoBuilder<'a, D, R, Snapshot<D>> {
         RepoBuilder {
             ctx: self.ctx,
-            state: Snapshot::new(self.state.staged_root, None),
+            state: Snapshot::new(self.state.staged_root, None, vec![]),
         }
     }
 
@@ -895,30 +2217,188 @@ where
             .await
     }
 
-    /// Add a target that's loaded in from the reader. This will store the target in the repository.
+    /// Add a target that's loaded in from the reader, with custom metadata serialized from
+    /// `custom`. See [TargetDescription::with_typed_custom].
     ///
     /// This will hash the file with the hash specified in [RepoBuilder::target_hash_algorithms]. If
     /// none was specified, the file will be hashed with [HashAlgorithm::Sha256].
-    pub async fn add_target_with_custom<Rd>(
-        mut self,
+    pub async fn add_target_with_typed_custom<Rd, T>(
+        self,
         target_path: TargetPath,
-        mut reader: Rd,
-        custom: HashMap<String, serde_json::Value>,
+        reader: Rd,
+        custom: &T,
     ) -> Result<RepoBuilder<'a, D, R, Targets<D>>>
     where
         Rd: AsyncRead + AsyncSeek + Unpin + Send,
+        T: serde::Serialize,
     {
-        let consistent_snapshot = if let Some(ref staged_root) = self.state.staged_root {
-            staged_root.metadata.consistent_snapshot()
-        } else if let Some(db) = self.ctx.db {
-            db.trusted_root().consistent_snapshot()
-        } else {
-            return Err(Error::MetadataNotFound {
-                path: MetadataPath::root(),
-                version: MetadataVersion::None,
-            });
-        };
+        let custom = metadata::typed_custom(custom)?;
+        self.add_target_with_custom(target_path, reader, custom)
+            .await
+    }
+
+    /// Add a target that's loaded in from the reader. This will store the target in the repository.
+    ///
+    /// This will hash the file with the hash specified in [RepoBuilder::target_hash_algorithms]. If
+    /// none was specified, the file will be hashed with [HashAlgorithm::Sha256].
+    pub async fn add_target_with_custom<Rd>(
+        mut self,
+        target_path: TargetPath,
+        reader: Rd,
+        custom: HashMap<String, serde_json::Value>,
+    ) -> Result<RepoBuilder<'a, D, R, Targets<D>>>
+    where
+        Rd: AsyncRead + AsyncSeek + Unpin + Send,
+    {
+        let target_description = self.store_target(&target_path, reader, custom).await?;
+        self.state.targets.insert(target_path, target_description);
+
+        Ok(self)
+    }
+
+    /// Add a target from a description that was computed elsewhere, such as by a build system
+    /// that already hashed its artifacts. The target file is neither read nor stored in the
+    /// repository, so it's up to the caller to make it available to clients. When consistent
+    /// snapshots are enabled it must be stored under every `$HASH.FILENAME.EXT` path of the
+    /// description.
+    ///
+    /// The description is added as is: its hashes aren't checked against
+    /// [RepoBuilder::target_hash_algorithms], and the default custom metadata isn't merged in.
+    pub fn add_target_description(
+        mut self,
+        target_path: TargetPath,
+        target_description: TargetDescription,
+    ) -> Self {
+        self.state.targets.insert(target_path, target_description);
+        self
+    }
+
+    /// Add several targets from descriptions that were computed elsewhere. See
+    /// [RepoBuilder::add_target_description].
+    pub fn add_target_descriptions<I>(mut self, targets: I) -> Self
+    where
+        I: IntoIterator<Item = (TargetPath, TargetDescription)>,
+    {
+        self.state.targets.extend(targets);
+        self
+    }
+
+    /// Remove a target from the targets metadata, so it can no longer be fetched by clients. The
+    /// stored target file is removed from the repository after the new metadata is written, with
+    /// [RepositoryStorage::remove_target].
+    ///
+    /// Only targets of the top-level targets metadata can be removed. Staging the targets metadata
+    /// fails if the target isn't in the trusted targets metadata, or if the targets are sharded
+    /// into hashed bins.
+    pub fn remove_target(mut self, target_path: TargetPath) -> Self {
+        self.state.targets.remove(&target_path);
+        self.state.yanked_targets.remove(&target_path);
+        self.state.removed_targets.insert(target_path);
+        self
+    }
+
+    /// Mark a target as yanked, such as a release that was pulled. A yanked target is still listed
+    /// in the targets metadata, so clients that depend on it can still fetch it, but its custom
+    /// metadata has a `"yanked": true` field that clients check with
+    /// [TargetDescription::is_yanked] to avoid picking it for new installs. The target can be
+    /// removed later with [RepoBuilder::remove_target].
+    ///
+    /// Staging the targets metadata fails if the target isn't in the trusted targets metadata or
+    /// added by this builder, or if the targets are sharded into hashed bins.
+    pub fn yank_target(mut self, target_path: TargetPath) -> Self {
+        self.state.removed_targets.remove(&target_path);
+        self.state.yanked_targets.insert(target_path);
+        self
+    }
+
+    /// Add every file under the directory `path` as a target, and store them in the repository.
+    ///
+    /// The target path of a file is its path relative to `path` with `/` separators, prefixed
+    /// with `prefix`. For example, with the prefix `"packages/"` the file `path/a/b.tar` is added
+    /// as the target `packages/a/b.tar`. Symbolic links are followed.
+    ///
+    /// The files are hashed with `hash_algorithms` on several threads at once, and are then stored
+    /// one at a time in the order of their paths.
+    pub async fn add_targets_from_dir<P>(
+        mut self,
+        path: P,
+        prefix: &str,
+        hash_algorithms: &[HashAlgorithm],
+    ) -> Result<RepoBuilder<'a, D, R, Targets<D>>>
+    where
+        P: AsRef<Path>,
+    {
+        let mut files = vec![];
+        walk_target_dir(path.as_ref(), prefix, &mut files)?;
+        let hashed_files = hash_target_files(&files, hash_algorithms)?;
+
+        for ((target_path, file_path), (length, hashes)) in files.into_iter().zip(hashed_files) {
+            let custom = self.ctx.target_custom(&target_path, HashMap::new());
+            let target_description = TargetDescription::new(Some(length), hashes, custom)?;
+            let reader = AllowStdIo::new(File::open(&file_path)?);
+            self.store_described_target(&target_path, &target_description, reader)
+                .await?;
+            self.state.targets.insert(target_path, target_description);
+        }
+
+        Ok(self)
+    }
+
+    /// Add a target to the targets metadata of the delegated `role`, which must have been added
+    /// with [RepoBuilder::add_delegated_role] and must delegate `target_path`. This will store the
+    /// target in the repository.
+    ///
+    /// This will hash the file with the hash specified in [RepoBuilder::target_hash_algorithms]. If
+    /// none was specified, the file will be hashed with [HashAlgorithm::Sha256].
+    pub async fn add_delegated_target<Rd>(
+        mut self,
+        role: &MetadataPath,
+        target_path: TargetPath,
+        reader: Rd,
+    ) -> Result<RepoBuilder<'a, D, R, Targets<D>>>
+    where
+        Rd: AsyncRead + AsyncSeek + Unpin + Send,
+    {
+        match self.state.delegated_roles.get(role) {
+            Some(delegated) if delegated.delegation.matches_target(&target_path) => {}
+            Some(_) => {
+                return Err(Error::IllegalArgument(format!(
+                    "target {} is not delegated to role {}",
+                    target_path, role
+                )))
+            }
+            None => {
+                return Err(Error::IllegalArgument(format!(
+                    "role {} was not added to the builder",
+                    role
+                )))
+            }
+        }
+
+        let target_description = self
+            .store_target(&target_path, reader, HashMap::new())
+            .await?;
+        self.state
+            .delegated_roles
+            .get_mut(role)
+            .expect("role was checked above")
+            .targets
+            .insert(target_path, target_description);
+
+        Ok(self)
+    }
 
+    /// Hash the target that's loaded in from the reader, and store it in the repository.
+    async fn store_target<Rd>(
+        &mut self,
+        target_path: &TargetPath,
+        mut reader: Rd,
+        custom: HashMap<String, serde_json::Value>,
+    ) -> Result<TargetDescription>
+    where
+        Rd: AsyncRead + AsyncSeek + Unpin + Send,
+    {
+        let custom = self.ctx.target_custom(target_path, custom);
         let target_description = TargetDescription::from_reader_with_custom(
             &mut reader,
             &self.state.file_hash_algorithms,
@@ -926,6 +2406,34 @@ where
         )
         .await?;
 
+        self.store_described_target(target_path, &target_description, reader)
+            .await?;
+
+        Ok(target_description)
+    }
+
+    /// Store the target that's loaded in from the reader, and which was hashed into
+    /// `target_description`, in the repository.
+    async fn store_described_target<Rd>(
+        &mut self,
+        target_path: &TargetPath,
+        target_description: &TargetDescription,
+        mut reader: Rd,
+    ) -> Result<()>
+    where
+        Rd: AsyncRead + AsyncSeek + Unpin + Send,
+    {
+        let consistent_snapshot = if let Some(ref staged_root) = self.state.staged_root {
+            staged_root.metadata.consistent_snapshot()
+        } else if let Some(db) = self.ctx.db.as_deref() {
+            db.trusted_root().consistent_snapshot()
+        } else {
+            return Err(Error::MetadataNotFound {
+                path: MetadataPath::root(),
+                version: MetadataVersion::None,
+            });
+        };
+
         // According to TUF section 5.5.2, when consistent snapshot is enabled, target files should be
         // stored at `$HASH.FILENAME.EXT`. Otherwise it is stored at `FILENAME.EXT`.
         if consistent_snapshot {
@@ -942,15 +2450,10 @@ where
         } else {
             reader.seek(SeekFrom::Start(0)).await?;
 
-            self.ctx
-                .repo
-                .store_target(&target_path, &mut reader)
-                .await?;
+            self.ctx.repo.store_target(target_path, &mut reader).await?;
         }
 
-        self.state.targets.insert(target_path, target_description);
-
-        Ok(self)
+        Ok(())
     }
 
     /// Add a target delegation key.
@@ -961,10 +2464,100 @@ where
 
     /// Add a target delegation role.
     pub fn add_delegation_role(mut self, delegation: Delegation) -> Self {
-        self.state.delegation_roles.push(delegation);
+        self.state
+            .delegation_entries
+            .push(DelegationEntry::Single(delegation));
+        self
+    }
+
+    /// Add a delegation to several roles that must agree on a target for it to be trusted.
+    pub fn add_multi_role_delegation(mut self, delegation: MultiRoleDelegation) -> Self {
+        self.state
+            .delegation_entries
+            .push(DelegationEntry::Multi(delegation));
+        self
+    }
+
+    /// Delegate to a role described by `delegation`, and stage new targets metadata for the role
+    /// that is signed with `keys`. The snapshot metadata will describe the staged metadata.
+    ///
+    /// The public keys of `keys` are added to the delegation keys, and must be among the key IDs
+    /// of `delegation`. Use [RepoBuilder::add_delegation_key] to add keys that are authorized to
+    /// sign for the role but are not used by this builder. Targets are added to the role with
+    /// [RepoBuilder::add_delegated_target]. If the targets metadata inherits from the trusted
+    /// targets metadata, the staged metadata of the role inherits the targets of its trusted
+    /// metadata in the database.
+    pub fn add_delegated_role(
+        mut self,
+        delegation: Delegation,
+        keys: &[&'a dyn PrivateKey],
+    ) -> Self {
+        let _ = self
+            .ctx
+            .delegated_role_keys
+            .insert(delegation.name().clone(), keys.to_vec());
+        let _ = self.state.delegated_roles.insert(
+            delegation.name().clone(),
+            DelegatedRole {
+                delegation,
+                targets: HashMap::new(),
+            },
+        );
+        self
+    }
+
+    /// Delegate to succinct hash bins instead of to explicit delegation roles.
+    pub fn succinct_delegation_roles(mut self, succinct_roles: SuccinctRoles) -> Self {
+        self.state.succinct_delegation_roles = Some(succinct_roles);
+        self
+    }
+
+    /// Shard the targets added to this builder into `bins`, instead of listing them in the
+    /// targets metadata. See the [hashed_bins](crate::hashed_bins) module for details.
+    ///
+    /// The targets metadata delegates to every bin by its path hash prefixes. The targets metadata
+    /// of every bin is signed with `keys` and staged along with it. If the targets metadata
+    /// inherits from the trusted targets metadata, only the bins with new targets or that are not
+    /// yet in the trusted snapshot are staged, and they inherit the targets of any bins in the
+    /// database.
+    pub fn hashed_bins(mut self, bins: HashedBins, keys: &[&'a dyn PrivateKey]) -> Self {
+        self.state.hashed_bins = Some(bins);
+        self.ctx.hashed_bins_keys = keys.to_vec();
         self
     }
 
+    /// Add the targets loaded in from the readers of `targets`, sharded into `bin_count` hashed
+    /// bins named `bins-*` that are signed with `bin_signers`. This is a shorthand for
+    /// [RepoBuilder::hashed_bins] followed by [RepoBuilder::add_target] for every target, which
+    /// suits repositories with too many targets to list in the targets metadata.
+    ///
+    /// `bin_count` must be a power of two between 2 and 65536.
+    pub async fn add_targets_with_bins<I, Rd>(
+        self,
+        targets: I,
+        bin_count: u32,
+        bin_signers: &[&'a dyn PrivateKey],
+    ) -> Result<RepoBuilder<'a, D, R, Targets<D>>>
+    where
+        I: IntoIterator<Item = (TargetPath, Rd)>,
+        Rd: AsyncRead + AsyncSeek + Unpin + Send,
+    {
+        if !bin_count.is_power_of_two() {
+            return Err(Error::IllegalArgument(format!(
+                "bin count {} must be a power of two",
+                bin_count
+            )));
+        }
+        let bins = HashedBins::new("bins", bin_count.trailing_zeros() as u8)?;
+
+        let mut builder = self.hashed_bins(bins, bin_signers);
+        for (target_path, reader) in targets {
+            builder = builder.add_target(target_path, reader).await?;
+        }
+
+        Ok(builder)
+    }
+
     /// Initialize a [TargetsMetadataBuilder] and pass it to the closure for further configuration.
     /// This builder will then be used to generate and stage a new [TargetsMetadata] for eventual
     /// commitment to the repository.
@@ -982,7 +2575,9 @@ where
 
         let mut delegations_builder = DelegationsBuilder::new();
 
-        if let Some(trusted_targets) = self.ctx.db.and_then(|db| db.trusted_targets()) {
+        let (removed_targets, yanked_targets) = self.removed_and_yanked_targets()?;
+
+        if let Some(trusted_targets) = self.ctx.db.as_deref().and_then(|db| db.trusted_targets()) {
             let next_version = self
                 .ctx
                 .non_root_next_version(trusted_targets.version(), MetadataPath::targets)?;
@@ -992,6 +2587,10 @@ where
             // Insert all the metadata from the trusted snapshot.
             if self.state.inherit_from_trusted_targets {
                 for (target_path, target_description) in trusted_targets.targets() {
+                    if self.state.removed_targets.contains(target_path) {
+                        continue;
+                    }
+
                     targets_builder = targets_builder
                         .insert_target_description(target_path.clone(), target_description.clone());
                 }
@@ -1000,18 +2599,80 @@ where
                     delegations_builder = delegations_builder.key(key.clone());
                 }
 
-                for role in trusted_targets.delegations().roles() {
-                    delegations_builder = delegations_builder.role(role.clone());
+                for entry in trusted_targets.delegations().entries() {
+                    delegations_builder = match entry {
+                        DelegationEntry::Single(role) => delegations_builder.role(role.clone()),
+                        DelegationEntry::Multi(multi_role) => {
+                            delegations_builder.multi_role(multi_role.clone())
+                        }
+                    };
+                }
+
+                if let Some(succinct_roles) = trusted_targets.delegations().succinct_roles() {
+                    delegations_builder =
+                        delegations_builder.succinct_roles(succinct_roles.clone());
                 }
             }
         } else {
             targets_builder = targets_builder.version(self.ctx.non_root_initial_version());
         }
 
-        // Overwrite any of the old targets with the new ones.
-        for (target_path, target_description) in self.state.targets {
-            targets_builder = targets_builder
-                .insert_target_description(target_path.clone(), target_description.clone());
+        // Shard the new targets into the hashed bins, or else overwrite any of the old targets
+        // with the new ones.
+        let mut staged_delegated_targets = vec![];
+        if let Some(ref bins) = self.state.hashed_bins {
+            let (bin_delegations, staged_bins) = self.ctx.stage_hashed_bins(
+                bins,
+                self.state.targets,
+                self.state.inherit_from_trusted_targets,
+            )?;
+
+            for key in &self.ctx.hashed_bins_keys {
+                delegations_builder = delegations_builder.key(key.public().clone());
+            }
+
+            for delegation in bin_delegations {
+                delegations_builder = delegations_builder.role(delegation);
+            }
+
+            staged_delegated_targets = staged_bins;
+        } else {
+            for (target_path, target_description) in self.state.targets {
+                targets_builder = targets_builder
+                    .insert_target_description(target_path.clone(), target_description.clone());
+            }
+
+            for (target_path, target_description) in yanked_targets {
+                targets_builder =
+                    targets_builder.insert_target_description(target_path, target_description);
+            }
+        }
+
+        for (role, delegated) in self.state.delegated_roles {
+            let keys = &self.ctx.delegated_role_keys[&role];
+            for key in keys {
+                if !delegated
+                    .delegation
+                    .key_ids()
+                    .contains(key.public().key_id())
+                {
+                    return Err(Error::IllegalArgument(format!(
+                        "key {:?} is not authorized to sign for role {}",
+                        key.public().key_id(),
+                        role
+                    )));
+                }
+                delegations_builder = delegations_builder.key(key.public().clone());
+            }
+
+            let staged = self.ctx.stage_delegated_targets(
+                &role,
+                keys,
+                delegated.targets,
+                self.state.inherit_from_trusted_targets,
+            )?;
+            delegations_builder = delegations_builder.role(delegated.delegation);
+            staged_delegated_targets.push((role, staged));
         }
 
         // Overwrite the old delegation keys.
@@ -1019,9 +2680,16 @@ where
             delegations_builder = delegations_builder.key(key);
         }
 
-        // Overwrite the old delegation roles.
-        for role in self.state.delegation_roles {
-            delegations_builder = delegations_builder.role(role);
+        // Overwrite the old delegation roles, keeping the order they were added in.
+        for entry in self.state.delegation_entries {
+            delegations_builder = match entry {
+                DelegationEntry::Single(role) => delegations_builder.role(role),
+                DelegationEntry::Multi(multi_role) => delegations_builder.multi_role(multi_role),
+            };
+        }
+
+        if let Some(succinct_roles) = self.state.succinct_delegation_roles {
+            delegations_builder = delegations_builder.succinct_roles(succinct_roles);
         }
 
         targets_builder = targets_builder.delegations(delegations_builder.build()?);
@@ -1029,26 +2697,97 @@ where
         let targets = f(targets_builder).build()?;
 
         // Sign the targets metadata.
+        self.ctx.check_deterministic()?;
         let raw_targets = sign(
             &targets,
             self.ctx
                 .signing_targets_keys
                 .iter()
                 .chain(&self.ctx.trusted_targets_keys),
+            self.ctx.pretty_print,
+            self.ctx.offline_signing,
         )?;
 
+        let mut state = Snapshot::new(
+            self.state.staged_root,
+            Some(Staged {
+                metadata: targets,
+                raw: raw_targets,
+            }),
+            staged_delegated_targets,
+        );
+        state.removed_targets = removed_targets;
+
         Ok(RepoBuilder {
             ctx: self.ctx,
-            state: Snapshot::new(
-                self.state.staged_root,
-                Some(Staged {
-                    metadata: targets,
-                    raw: raw_targets,
-                }),
-            ),
+            state,
         })
     }
 
+    /// The trusted descriptions of the removed targets, and the new descriptions of the yanked
+    /// targets.
+    fn removed_and_yanked_targets(&self) -> Result<(RemovedTargets, RemovedTargets)> {
+        if self.state.removed_targets.is_empty() && self.state.yanked_targets.is_empty() {
+            return Ok((vec![], vec![]));
+        }
+
+        if self.state.hashed_bins.is_some() {
+            return Err(Error::IllegalArgument(
+                "targets can only be removed or yanked from the top-level targets metadata".into(),
+            ));
+        }
+
+        let trusted_targets = if self.state.inherit_from_trusted_targets {
+            self.ctx
+                .db
+                .as_deref()
+                .and_then(|db| db.trusted_targets())
+                .map(|targets| targets.targets())
+        } else {
+            None
+        };
+        let not_found = |target_path: &TargetPath| {
+            Error::IllegalArgument(format!(
+                "target {} is not in the targets metadata",
+                target_path
+            ))
+        };
+
+        let mut removed = vec![];
+        for target_path in &self.state.removed_targets {
+            // The target was added again after it was removed.
+            if self.state.targets.contains_key(target_path) {
+                continue;
+            }
+
+            let description = trusted_targets
+                .and_then(|targets| targets.get(target_path))
+                .ok_or_else(|| not_found(target_path))?;
+            removed.push((target_path.clone(), description.clone()));
+        }
+
+        let mut yanked = vec![];
+        for target_path in &self.state.yanked_targets {
+            let description = self
+                .state
+                .targets
+                .get(target_path)
+                .or_else(|| trusted_targets.and_then(|targets| targets.get(target_path)))
+                .ok_or_else(|| not_found(target_path))?;
+
+            let mut custom = description.custom().clone();
+            custom.insert(
+                metadata::YANKED_CUSTOM_KEY.into(),
+                serde_json::Value::Bool(true),
+            );
+            let description =
+                TargetDescription::new(description.length(), description.hashes().clone(), custom)?;
+            yanked.push((target_path.clone(), description));
+        }
+
+        Ok((removed, yanked))
+    }
+
     /// Validate and write the metadata to the repository.
     ///
     /// This may stage a targets, snapshot, and timestamp metadata if necessary.
@@ -1059,8 +2798,13 @@ where
     }
 
     fn need_new_targets(&self) -> bool {
-        // We need a new targets metadata if we added any targets.
-        if !self.state.targets.is_empty() {
+        // We need a new targets metadata if we added any targets or delegated roles.
+        if !self.state.targets.is_empty() || !self.state.delegated_roles.is_empty() {
+            return true;
+        }
+
+        // We need a new targets metadata if we removed or yanked any targets.
+        if !self.state.removed_targets.is_empty() || !self.state.yanked_targets.is_empty() {
             return true;
         }
 
@@ -1070,7 +2814,7 @@ where
         }
 
         // We need a new targets metadata if we don't have a database yet.
-        let db = if let Some(ref db) = self.ctx.db {
+        let db = if let Some(db) = self.ctx.db.as_deref() {
             db
         } else {
             return true;
@@ -1164,7 +2908,8 @@ where
         let mut snapshot_builder = SnapshotMetadataBuilder::new()
             .expires(self.ctx.current_time + self.ctx.snapshot_expiration_duration);
 
-        if let Some(trusted_snapshot) = self.ctx.db.and_then(|db| db.trusted_snapshot()) {
+        if let Some(trusted_snapshot) = self.ctx.db.as_deref().and_then(|db| db.trusted_snapshot())
+        {
             let next_version = self
                 .ctx
                 .non_root_next_version(trusted_snapshot.version(), MetadataPath::snapshot)?;
@@ -1188,14 +2933,25 @@ where
                 .insert_metadata_description(MetadataPath::targets(), targets_description);
         };
 
+        for (role, delegated_targets) in &self.state.staged_delegated_targets {
+            snapshot_builder = snapshot_builder.insert_metadata_description(
+                role.clone(),
+                self.state.describe_targets(delegated_targets)?,
+            );
+        }
+
         let snapshot = f(snapshot_builder).build()?;
+        self.ctx.check_deterministic()?;
         let raw_snapshot = sign(
             &snapshot,
             self.ctx
                 .signing_snapshot_keys
                 .iter()
                 .chain(&self.ctx.trusted_snapshot_keys),
+            self.ctx.pretty_print,
+            self.ctx.offline_signing,
         )?;
+        let raw_snapshot = self.ctx.pad(raw_snapshot)?;
 
         Ok(RepoBuilder {
             ctx: self.ctx,
@@ -1230,7 +2986,7 @@ where
         }
 
         // We need a new snapshot metadata if we don't have a database yet.
-        let db = if let Some(ref db) = self.ctx.db {
+        let db = if let Some(db) = self.ctx.db.as_deref() {
             db
         } else {
             return true;
@@ -1272,6 +3028,14 @@ where
         self
     }
 
+    /// Whether or not to publish a snapshot Merkle tree. If enabled, the root of the tree is
+    /// included in the new timestamp, and the inclusion proof of every role is written alongside
+    /// its metadata. See the [merkle](crate::merkle) module for details.
+    pub fn timestamp_includes_merkle_root(mut self, snapshot_merkle_tree: bool) -> Self {
+        self.state.snapshot_merkle_tree = snapshot_merkle_tree;
+        self
+    }
+
     /// Stage a timestamp metadata using the default settings.
     ///
     /// Note: This will also:
@@ -1301,8 +3065,11 @@ where
             state: Done {
                 staged_root: self.state.staged_root,
                 staged_targets: self.state.staged_targets,
+                staged_delegated_targets: self.state.staged_delegated_targets,
                 staged_snapshot: self.state.staged_snapshot,
                 staged_timestamp: None,
+                snapshot_merkle_tree: None,
+                removed_targets: self.state.removed_targets,
             },
         }
     }
@@ -1319,7 +3086,7 @@ where
     where
         F: FnOnce(TimestampMetadataBuilder) -> TimestampMetadataBuilder,
     {
-        let next_version = if let Some(db) = self.ctx.db {
+        let next_version = if let Some(db) = self.ctx.db.as_deref() {
             if let Some(trusted_timestamp) = db.trusted_timestamp() {
                 self.ctx
                     .non_root_next_version(trusted_timestamp.version(), MetadataPath::timestamp)?
@@ -1335,6 +3102,7 @@ where
         } else {
             self.ctx
                 .db
+                .as_deref()
                 .and_then(|db| db.trusted_timestamp())
                 .map(|timestamp| timestamp.snapshot().clone())
                 .ok_or_else(|| Error::MetadataNotFound {
@@ -1343,29 +3111,58 @@ where
                 })?
         };
 
-        let timestamp_builder = TimestampMetadataBuilder::from_metadata_description(description)
-            .version(next_version)
-            .expires(self.ctx.current_time + self.ctx.timestamp_expiration_duration);
+        let mut timestamp_builder =
+            TimestampMetadataBuilder::from_metadata_description(description)
+                .version(next_version)
+                .expires(self.ctx.current_time + self.ctx.timestamp_expiration_duration);
+
+        let snapshot_merkle_tree = if self.state.snapshot_merkle_tree {
+            let tree = if let Some(ref snapshot) = self.state.staged_snapshot {
+                SnapshotMerkleTree::from_snapshot(&snapshot.metadata)?
+            } else {
+                let snapshot = self
+                    .ctx
+                    .db
+                    .as_deref()
+                    .and_then(|db| db.trusted_snapshot())
+                    .ok_or_else(|| Error::MetadataNotFound {
+                        path: MetadataPath::snapshot(),
+                        version: MetadataVersion::None,
+                    })?;
+                SnapshotMerkleTree::from_snapshot(snapshot)?
+            };
+            timestamp_builder = timestamp_builder.merkle_root(tree.root().clone());
+            Some(tree)
+        } else {
+            None
+        };
 
         let timestamp = f(timestamp_builder).build()?;
+        self.ctx.check_deterministic()?;
         let raw_timestamp = sign(
             &timestamp,
             self.ctx
                 .signing_timestamp_keys
                 .iter()
                 .chain(&self.ctx.trusted_timestamp_keys),
+            self.ctx.pretty_print,
+            self.ctx.offline_signing,
         )?;
+        let raw_timestamp = self.ctx.pad(raw_timestamp)?;
 
         Ok(RepoBuilder {
             ctx: self.ctx,
             state: Done {
                 staged_root: self.state.staged_root,
                 staged_targets: self.state.staged_targets,
+                staged_delegated_targets: self.state.staged_delegated_targets,
                 staged_snapshot: self.state.staged_snapshot,
                 staged_timestamp: Some(Staged {
                     metadata: timestamp,
                     raw: raw_timestamp,
                 }),
+                snapshot_merkle_tree,
+                removed_targets: self.state.removed_targets,
             },
         })
     }
@@ -1387,7 +3184,7 @@ where
         }
 
         // We need a new timestamp metadata if we don't have a database yet.
-        let db = if let Some(ref db) = self.ctx.db {
+        let db = if let Some(db) = self.ctx.db.as_deref() {
             db
         } else {
             return true;
@@ -1415,50 +3212,192 @@ where
     D: Pouf,
     R: RepositoryStorage<D>,
 {
-    /// Commit the metadata for this repository, then write all metadata to the repository. Before
-    /// writing the metadata to `repo`, this will test that a client can update to this metadata to
-    /// make sure it is valid.
-    pub async fn commit(mut self) -> Result<RawSignedMetadataSet<D>> {
-        self.validate_built_metadata()?;
-        self.write_repo().await?;
+    /// Export the canonical bytes of every staged metadata, so that they can be signed outside of
+    /// this crate. See [RepoBuilder::offline_signing].
+    pub fn export_unsigned(&self) -> Result<Vec<UnsignedPayload>> {
+        let mut payloads = vec![];
+
+        if let Some(ref root) = self.state.staged_root {
+            payloads.push(UnsignedPayload::new::<D, _>(
+                MetadataPath::root(),
+                &root.metadata,
+            )?);
+        }
 
-        let mut builder = RawSignedMetadataSetBuilder::new();
+        if let Some(ref targets) = self.state.staged_targets {
+            payloads.push(UnsignedPayload::new::<D, _>(
+                MetadataPath::targets(),
+                &targets.metadata,
+            )?);
+        }
 
-        if let Some(root) = self.state.staged_root {
-            builder = builder.root(root.raw);
+        for (role, delegated_targets) in &self.state.staged_delegated_targets {
+            payloads.push(UnsignedPayload::new::<D, _>(
+                role.clone(),
+                &delegated_targets.metadata,
+            )?);
         }
 
-        if let Some(targets) = self.state.staged_targets {
-            builder = builder.targets(targets.raw);
+        if let Some(ref snapshot) = self.state.staged_snapshot {
+            payloads.push(UnsignedPayload::new::<D, _>(
+                MetadataPath::snapshot(),
+                &snapshot.metadata,
+            )?);
         }
 
-        if let Some(snapshot) = self.state.staged_snapshot {
-            builder = builder.snapshot(snapshot.raw);
+        if let Some(ref timestamp) = self.state.staged_timestamp {
+            payloads.push(UnsignedPayload::new::<D, _>(
+                MetadataPath::timestamp(),
+                &timestamp.metadata,
+            )?);
         }
 
-        if let Some(timestamp) = self.state.staged_timestamp {
-            builder = builder.timestamp(timestamp.raw);
+        Ok(payloads)
+    }
+
+    /// Attach `signatures` to the staged metadata of `role`. Every signature is a signature by the
+    /// public key over the [UnsignedPayload::payload] of the role, and is verified before it is
+    /// attached.
+    ///
+    /// Signatures can't be attached to metadata that is described by its length or hashes, since
+    /// that would change the description. Sign such metadata before the metadata that describes
+    /// it is staged, or only describe it by its version.
+    pub fn import_signatures(
+        mut self,
+        role: &MetadataPath,
+        signatures: &[(&PublicKey, &[u8])],
+    ) -> Result<Self> {
+        let pretty_print = self.ctx.pretty_print;
+
+        if *role == MetadataPath::root() {
+            let root = self
+                .state
+                .staged_root
+                .as_mut()
+                .ok_or_else(|| not_staged(role))?;
+            root.raw = root.attach_signatures(signatures, pretty_print)?;
+        } else if *role == MetadataPath::snapshot() {
+            if let Some(ref timestamp) = self.state.staged_timestamp {
+                check_not_described_by_hash(role, timestamp.metadata.snapshot())?;
+            }
+
+            let snapshot = self
+                .state
+                .staged_snapshot
+                .as_mut()
+                .ok_or_else(|| not_staged(role))?;
+            snapshot.raw = self
+                .ctx
+                .pad(snapshot.attach_signatures(signatures, pretty_print)?)?;
+        } else if *role == MetadataPath::timestamp() {
+            let timestamp = self
+                .state
+                .staged_timestamp
+                .as_mut()
+                .ok_or_else(|| not_staged(role))?;
+            timestamp.raw = self
+                .ctx
+                .pad(timestamp.attach_signatures(signatures, pretty_print)?)?;
+        } else {
+            if let Some(ref snapshot) = self.state.staged_snapshot {
+                if let Some(description) = snapshot.metadata.meta().get(role) {
+                    check_not_described_by_hash(role, description)?;
+                }
+            }
+
+            let targets = if *role == MetadataPath::targets() {
+                self.state.staged_targets.as_mut()
+            } else {
+                self.state
+                    .staged_delegated_targets
+                    .iter_mut()
+                    .find(|(path, _)| path == role)
+                    .map(|(_, delegated_targets)| delegated_targets)
+            };
+            let targets = targets.ok_or_else(|| not_staged(role))?;
+            targets.raw = targets.attach_signatures(signatures, pretty_print)?;
         }
 
-        Ok(builder.build())
+        Ok(self)
     }
 
-    /// Before we commit any metadata, make sure that we can update from our
-    /// current TUF database to the latest version.
-    fn validate_built_metadata(&self) -> Result<()> {
-        // Use a TUF database to make sure we can update to the metadata we just
-        // produced. If we were constructed with a database, create a copy of it
-        // and make sure we can install the update.
-        let mut db = if let Some(db) = self.ctx.db {
-            let mut db = db.clone();
+    /// Start a [SigningSession] for the staged metadata, so that it can be signed by several
+    /// custodians in turn. The session includes the signatures that were already made while the
+    /// metadata was staged, such as by online snapshot and timestamp keys.
+    pub fn signing_session(&self) -> Result<SigningSession> {
+        let entries = self
+            .export_unsigned()?
+            .into_iter()
+            .map(|payload| {
+                let raw = self
+                    .staged_raw_bytes(payload.role())
+                    .expect("every exported payload was staged")
+                    .to_vec();
+                SigningSessionEntry {
+                    payload,
+                    raw,
+                    signatures: vec![],
+                }
+            })
+            .collect();
 
-            if let Some(ref root) = self.state.staged_root {
-                db.update_root(&root.raw)?;
+        Ok(SigningSession { entries })
+    }
+
+    fn staged_raw_bytes(&self, role: &MetadataPath) -> Option<&[u8]> {
+        if *role == MetadataPath::root() {
+            self.state.staged_root.as_ref().map(|x| x.raw.as_bytes())
+        } else if *role == MetadataPath::targets() {
+            self.state.staged_targets.as_ref().map(|x| x.raw.as_bytes())
+        } else if *role == MetadataPath::snapshot() {
+            self.state
+                .staged_snapshot
+                .as_ref()
+                .map(|x| x.raw.as_bytes())
+        } else if *role == MetadataPath::timestamp() {
+            self.state
+                .staged_timestamp
+                .as_ref()
+                .map(|x| x.raw.as_bytes())
+        } else {
+            self.state
+                .staged_delegated_targets
+                .iter()
+                .find(|(path, _)| path == role)
+                .map(|(_, x)| x.raw.as_bytes())
+        }
+    }
+
+    /// Check the signatures of every staged metadata against the keys a client would check them
+    /// against, such as to find out whose signatures are still missing before the metadata can
+    /// be committed. Root metadata staged on top of a trusted root is reported twice, first
+    /// against the keys of the trusted root and then against its own keys, since a client
+    /// requires both thresholds to be met. See [verify::check_signatures].
+    pub fn signature_reports(&self) -> Result<Vec<SignatureReport>> {
+        let db = self.ctx.db.as_deref();
+        let mut reports = vec![];
+
+        let root: &RootMetadata = if let Some(ref root) = self.state.staged_root {
+            if let Some(db) = db {
+                let trusted_root = db.trusted_root();
+                reports.push(verify::check_signatures(
+                    &MetadataPath::root(),
+                    &root.raw,
+                    trusted_root.root().threshold(),
+                    trusted_root.root_keys(),
+                )?);
             }
 
-            db
-        } else if let Some(ref root) = self.state.staged_root {
-            Database::from_trusted_root(&root.raw)?
+            reports.push(verify::check_signatures(
+                &MetadataPath::root(),
+                &root.raw,
+                root.metadata.root().threshold(),
+                root.metadata.root_keys(),
+            )?);
+
+            &root.metadata
+        } else if let Some(db) = db {
+            db.trusted_root()
         } else {
             return Err(Error::MetadataNotFound {
                 path: MetadataPath::root(),
@@ -1466,18 +3405,154 @@ where
             });
         };
 
-        if let Some(ref timestamp) = self.state.staged_timestamp {
-            db.update_timestamp(&self.ctx.current_time, &timestamp.raw)?;
-        }
-
-        if let Some(ref snapshot) = self.state.staged_snapshot {
-            db.update_snapshot(&self.ctx.current_time, &snapshot.raw)?;
+        if let Some(ref targets) = self.state.staged_targets {
+            reports.push(verify::check_signatures(
+                &MetadataPath::targets(),
+                &targets.raw,
+                root.targets().threshold(),
+                root.targets_keys(),
+            )?);
+        }
+
+        if !self.state.staged_delegated_targets.is_empty() {
+            let parent: &TargetsMetadata = if let Some(ref targets) = self.state.staged_targets {
+                &targets.metadata
+            } else if let Some(targets) = db.and_then(|db| db.trusted_targets()) {
+                targets
+            } else {
+                return Err(Error::MetadataNotFound {
+                    path: MetadataPath::targets(),
+                    version: MetadataVersion::None,
+                });
+            };
+            let delegations = parent.delegations();
+
+            for (role, delegated_targets) in &self.state.staged_delegated_targets {
+                let delegation =
+                    delegations
+                        .delegation(role)
+                        .ok_or_else(|| Error::UnauthorizedDelegation {
+                            parent_role: MetadataPath::targets(),
+                            child_role: role.clone(),
+                        })?;
+                let keys = delegations
+                    .keys()
+                    .iter()
+                    .filter(|(key_id, _)| delegation.key_ids().contains(*key_id))
+                    .map(|(_, key)| key);
+
+                reports.push(verify::check_signatures(
+                    role,
+                    &delegated_targets.raw,
+                    delegation.threshold(),
+                    keys,
+                )?);
+            }
+        }
+
+        if let Some(ref snapshot) = self.state.staged_snapshot {
+            reports.push(verify::check_signatures(
+                &MetadataPath::snapshot(),
+                &snapshot.raw,
+                root.snapshot().threshold(),
+                root.snapshot_keys(),
+            )?);
+        }
+
+        if let Some(ref timestamp) = self.state.staged_timestamp {
+            reports.push(verify::check_signatures(
+                &MetadataPath::timestamp(),
+                &timestamp.raw,
+                root.timestamp().threshold(),
+                root.timestamp_keys(),
+            )?);
+        }
+
+        Ok(reports)
+    }
+
+    /// Commit the metadata like [RepoBuilder::commit] if every staged metadata meets its
+    /// threshold. Otherwise nothing is written to the repository, and the metadata is returned as
+    /// [UnpublishedMetadata] instead of failing, so that the missing signatures can be collected
+    /// asynchronously. Use [RepoBuilder::offline_signing] to also stage metadata that none of
+    /// the available keys can sign.
+    pub async fn commit_or_stage(self) -> Result<CommitOutcome<D>> {
+        let reports = self.signature_reports()?;
+
+        if reports.iter().all(|report| report.is_threshold_met()) {
+            Ok(CommitOutcome::Published(self.commit().await?))
+        } else {
+            Ok(CommitOutcome::Unpublished(UnpublishedMetadata {
+                session: self.signing_session()?,
+                reports,
+            }))
+        }
+    }
+
+    /// Commit the metadata for this repository, then write all metadata to the repository. Before
+    /// writing the metadata to `repo`, this will test that a client can update to this metadata to
+    /// make sure it is valid.
+    pub async fn commit(mut self) -> Result<RawSignedMetadataSet<D>> {
+        self.validate_built_metadata()?;
+        self.write_repo().await?;
+
+        Ok(self.state.into_metadata_set())
+    }
+
+    /// Before we commit any metadata, make sure that we can update from our
+    /// current TUF database to the latest version.
+    fn validate_built_metadata(&self) -> Result<()> {
+        if self.ctx.require_expiration_ordering {
+            ExpirationDurations::new(
+                self.ctx.root_expiration_duration,
+                self.ctx.targets_expiration_duration,
+                self.ctx.snapshot_expiration_duration,
+                self.ctx.timestamp_expiration_duration,
+            )
+            .check_ordering()?;
+        }
+
+        // Use a TUF database to make sure we can update to the metadata we just
+        // produced. If we were constructed with a database, create a copy of it
+        // and make sure we can install the update.
+        let mut db = if let Some(db) = self.ctx.db.as_deref() {
+            let mut db = db.clone();
+
+            if let Some(ref root) = self.state.staged_root {
+                db.update_root(&root.raw)?;
+            }
+
+            db
+        } else if let Some(ref root) = self.state.staged_root {
+            Database::from_trusted_root(&root.raw)?
+        } else {
+            return Err(Error::MetadataNotFound {
+                path: MetadataPath::root(),
+                version: MetadataVersion::None,
+            });
+        };
+
+        if let Some(ref timestamp) = self.state.staged_timestamp {
+            db.update_timestamp(&self.ctx.current_time, &timestamp.raw)?;
+        }
+
+        if let Some(ref snapshot) = self.state.staged_snapshot {
+            db.update_snapshot(&self.ctx.current_time, &snapshot.raw)?;
         }
 
         if let Some(ref targets) = self.state.staged_targets {
             db.update_targets(&self.ctx.current_time, &targets.raw)?;
         }
 
+        for (role, delegated_targets) in &self.state.staged_delegated_targets {
+            db.update_delegated_targets(
+                &self.ctx.current_time,
+                &MetadataPath::targets(),
+                role,
+                &delegated_targets.raw,
+            )?;
+        }
+
         Ok(())
     }
 
@@ -1502,7 +3577,7 @@ where
                 .await?;
 
             root.metadata.consistent_snapshot()
-        } else if let Some(db) = self.ctx.db {
+        } else if let Some(db) = self.ctx.db.as_deref() {
             db.trusted_root().consistent_snapshot()
         } else {
             return Err(Error::MetadataNotFound {
@@ -1534,6 +3609,28 @@ where
             }
         }
 
+        for (path, delegated_targets) in &self.state.staged_delegated_targets {
+            self.ctx
+                .repo
+                .store_metadata(
+                    path,
+                    MetadataVersion::None,
+                    &mut delegated_targets.raw.as_bytes(),
+                )
+                .await?;
+
+            if consistent_snapshot {
+                self.ctx
+                    .repo
+                    .store_metadata(
+                        path,
+                        MetadataVersion::Number(delegated_targets.metadata.version()),
+                        &mut delegated_targets.raw.as_bytes(),
+                    )
+                    .await?;
+            }
+        }
+
         if let Some(ref snapshot) = self.state.staged_snapshot {
             let path = MetadataPath::snapshot();
             self.ctx
@@ -1553,6 +3650,18 @@ where
             }
         }
 
+        // Write the proofs before the timestamp that signs their Merkle root, so clients never see
+        // a Merkle root without its proofs.
+        if let Some(ref tree) = self.state.snapshot_merkle_tree {
+            for (role, proof) in tree.proofs()? {
+                let buf = D::canonicalize(&D::serialize(&proof)?)?;
+                self.ctx
+                    .repo
+                    .store_snapshot_merkle_proof(&role, &mut buf.as_slice())
+                    .await?;
+            }
+        }
+
         if let Some(ref timestamp) = self.state.staged_timestamp {
             self.ctx
                 .repo
@@ -1564,20 +3673,56 @@ where
                 .await?;
         }
 
+        // Remove the files of removed targets last, once the new metadata no longer lists them.
+        for (target_path, target_description) in &self.state.removed_targets {
+            if consistent_snapshot {
+                for digest in target_description.hashes().values() {
+                    self.ctx
+                        .repo
+                        .remove_target(&target_path.with_hash_prefix(digest)?)
+                        .await?;
+                }
+            } else {
+                self.ctx.repo.remove_target(target_path).await?;
+            }
+        }
+
         Ok(())
     }
 }
 
+impl<'a, D, R> RepoBuilder<'a, D, R, Done<D>>
+where
+    D: Pouf,
+    R: RepositoryStorage<D> + RepositoryProvider<D>,
+{
+    /// Commit the metadata like `commit`, and then check the whole repository the way a client
+    /// would with [lint::check_repo_and_targets]: the root chain, the signature thresholds, that
+    /// the snapshot and timestamp describe the metadata that was written, and that every target
+    /// matches its length and hashes.
+    ///
+    /// A repository that was written, but is subtly broken, is reported in the [LintReport]
+    /// rather than as an error, so that it can be fixed before clients run into it.
+    pub async fn commit_and_verify(mut self) -> Result<(RawSignedMetadataSet<D>, LintReport)> {
+        self.validate_built_metadata()?;
+        self.write_repo().await?;
+
+        let report =
+            lint::check_repo_and_targets_with_start_time(&self.ctx.repo, &self.ctx.current_time)
+                .await;
+
+        Ok((self.state.into_metadata_set(), report))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use {
         super::*,
         crate::{
-            client::{Client, Config},
             crypto::Ed25519PrivateKey,
             metadata::SignedMetadata,
-            pouf::Pouf1,
-            repository::{EphemeralRepository, RepositoryProvider},
+            pouf::{Der, Pouf1},
         },
         assert_matches::assert_matches,
         chrono::{
@@ -1587,7 +3732,7 @@ mod tests {
         futures_executor::block_on,
         futures_util::io::{AsyncReadExt, Cursor},
         lazy_static::lazy_static,
-        maplit::hashmap,
+        maplit::{hashmap, hashset},
         pretty_assertions::assert_eq,
         std::collections::BTreeMap,
     };
@@ -2413,225 +4558,471 @@ mod tests {
     }
 
     #[test]
-    fn test_do_not_require_all_keys_to_be_online() {
-        block_on(async {
-            let mut remote = EphemeralRepository::<Pouf1>::new();
+    fn test_hashed_bins() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            let bins = HashedBins::new("bins", 1).unwrap();
+
+            // sha256("foo") starts with 0x2c, sha256("bar") with 0xfc, sha256("baz") with 0xba,
+            // and sha256("qux") with 0x21.
+            let files = [
+                ("foo", &b"foo file"[..]),
+                ("bar", &b"bar file"[..]),
+                ("baz", &b"baz file"[..]),
+                ("qux", &b"qux file"[..]),
+            ];
 
-            // First, write some metadata to the repo.
-            let expires1 = Utc.with_ymd_and_hms(2038, 1, 1, 0, 0, 0).unwrap();
-            let metadata1 = RepoBuilder::create(&mut remote)
+            let mut builder = RepoBuilder::create(&mut repo)
                 .trusted_root_keys(&[&KEYS[0]])
-                .trusted_targets_keys(&[&KEYS[1]])
-                .trusted_snapshot_keys(&[&KEYS[2]])
-                .trusted_timestamp_keys(&[&KEYS[3]])
-                .stage_root_with_builder(|builder| {
-                    builder.consistent_snapshot(true).expires(expires1)
-                })
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
                 .unwrap()
-                .stage_targets_with_builder(|builder| builder.expires(expires1))
+                .hashed_bins(bins.clone(), &[&KEYS[1]]);
+            for (path, file) in &files[..3] {
+                builder = builder
+                    .add_target(TargetPath::new(*path).unwrap(), Cursor::new(*file))
+                    .await
+                    .unwrap();
+            }
+            let metadata = builder.commit().await.unwrap();
+
+            // The targets are only listed by the bins.
+            let targets = metadata
+                .targets()
                 .unwrap()
-                .stage_snapshot_with_builder(|builder| builder.expires(expires1))
+                .parse_untrusted()
                 .unwrap()
-                .stage_timestamp_with_builder(|builder| builder.expires(expires1))
+                .assume_valid()
+                .unwrap();
+            assert!(targets.targets().is_empty());
+            assert_eq!(
+                targets
+                    .delegations()
+                    .roles()
+                    .iter()
+                    .map(|delegation| delegation.name().as_str())
+                    .collect::<Vec<_>>(),
+                vec!["bins-0-7", "bins-8-f"]
+            );
+            assert!(targets
+                .delegations()
+                .roles()
+                .iter()
+                .all(|delegation| delegation.path_hash_prefixes().len() == 8));
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                repo,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
+
+            for (path, file) in &files[..3] {
+                let mut buf = vec![];
+                client
+                    .fetch_target(&TargetPath::new(*path).unwrap())
+                    .await
+                    .unwrap()
+                    .read_to_end(&mut buf)
+                    .await
+                    .unwrap();
+                assert_eq!(&buf, file);
+            }
+
+            // Adding a target only regenerates its bin, which keeps the targets it already had.
+            let database = client.database().clone();
+            let metadata = RepoBuilder::from_database(client.remote_repo_mut(), &database)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .skip_root()
+                .hashed_bins(bins, &[&KEYS[1]])
+                .add_target(
+                    TargetPath::new(files[3].0).unwrap(),
+                    Cursor::new(files[3].1),
+                )
+                .await
                 .unwrap()
                 .commit()
                 .await
                 .unwrap();
 
-            // We wrote all the metadata.
-            assert!(metadata1.root().is_some());
-            assert!(metadata1.timestamp().is_some());
-            assert!(metadata1.snapshot().is_some());
-            assert!(metadata1.targets().is_some());
-
-            let mut expected_metadata: BTreeMap<_, _> = vec![
-                (
-                    (MetadataPath::root(), MetadataVersion::Number(1)),
-                    metadata1.root().unwrap().as_bytes(),
-                ),
-                (
-                    (MetadataPath::root(), MetadataVersion::None),
-                    metadata1.root().unwrap().as_bytes(),
-                ),
-                (
-                    (MetadataPath::targets(), MetadataVersion::Number(1)),
-                    metadata1.targets().unwrap().as_bytes(),
-                ),
-                (
-                    (MetadataPath::targets(), MetadataVersion::None),
-                    metadata1.targets().unwrap().as_bytes(),
-                ),
-                (
-                    (MetadataPath::snapshot(), MetadataVersion::Number(1)),
-                    metadata1.snapshot().unwrap().as_bytes(),
-                ),
-                (
-                    (MetadataPath::snapshot(), MetadataVersion::None),
-                    metadata1.snapshot().unwrap().as_bytes(),
-                ),
-                (
-                    (MetadataPath::timestamp(), MetadataVersion::None),
-                    metadata1.timestamp().unwrap().as_bytes(),
-                ),
-            ]
-            .into_iter()
-            .collect();
+            let snapshot = metadata
+                .snapshot()
+                .unwrap()
+                .parse_untrusted()
+                .unwrap()
+                .assume_valid()
+                .unwrap();
+            assert_eq!(
+                snapshot.meta()[&MetadataPath::new("bins-0-7").unwrap()].version(),
+                2
+            );
+            assert_eq!(
+                snapshot.meta()[&MetadataPath::new("bins-8-f").unwrap()].version(),
+                1
+            );
 
-            assert_repo(&remote, &expected_metadata);
+            client.update().await.unwrap();
+            for (path, file) in &files {
+                assert_eq!(
+                    client
+                        .fetch_target_description(&TargetPath::new(*path).unwrap())
+                        .await
+                        .unwrap(),
+                    TargetDescription::from_slice(file, &[HashAlgorithm::Sha256]).unwrap()
+                );
+            }
+        })
+    }
 
-            let mut db = Database::from_trusted_metadata(&metadata1).unwrap();
+    #[test]
+    fn test_add_targets_with_bins() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            let files = (0..64)
+                .map(|i| (format!("target-{}", i), format!("file {}", i).into_bytes()))
+                .collect::<Vec<_>>();
 
-            // Next, write another batch, but only have the timestamp, snapshot, and targets keys.
-            let expires2 = Utc.with_ymd_and_hms(2038, 1, 2, 0, 0, 0).unwrap();
-            let metadata2 = RepoBuilder::from_database(&mut remote, &db)
-                .trusted_targets_keys(&[&KEYS[1]])
-                .trusted_snapshot_keys(&[&KEYS[2]])
-                .trusted_timestamp_keys(&[&KEYS[3]])
-                .skip_root()
-                .stage_targets_with_builder(|builder| builder.expires(expires2))
-                .unwrap()
-                .stage_snapshot_with_builder(|builder| builder.expires(expires2))
+            let metadata = RepoBuilder::create(&mut repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
                 .unwrap()
-                .stage_timestamp_with_builder(|builder| builder.expires(expires2))
+                .add_targets_with_bins(
+                    files.iter().map(|(path, file)| {
+                        (TargetPath::new(path.as_str()).unwrap(), Cursor::new(file))
+                    }),
+                    16,
+                    &[&KEYS[1]],
+                )
+                .await
                 .unwrap()
                 .commit()
                 .await
                 .unwrap();
 
-            assert!(db.update_metadata(&metadata2).unwrap());
+            let targets = metadata
+                .targets()
+                .unwrap()
+                .parse_untrusted()
+                .unwrap()
+                .assume_valid()
+                .unwrap();
+            assert!(targets.targets().is_empty());
+            assert_eq!(targets.delegations().roles().len(), 16);
 
-            assert!(metadata2.root().is_none());
-            assert!(metadata2.targets().is_some());
-            assert!(metadata2.snapshot().is_some());
-            assert!(metadata2.timestamp().is_some());
+            // Every bin is described by the snapshot.
+            let snapshot = metadata
+                .snapshot()
+                .unwrap()
+                .parse_untrusted()
+                .unwrap()
+                .assume_valid()
+                .unwrap();
+            for delegation in targets.delegations().roles() {
+                assert_eq!(snapshot.meta()[delegation.name()].version(), 1);
+            }
 
-            expected_metadata.extend(vec![
-                (
-                    (MetadataPath::targets(), MetadataVersion::Number(2)),
-                    metadata2.targets().unwrap().as_bytes(),
-                ),
-                (
-                    (MetadataPath::targets(), MetadataVersion::None),
-                    metadata2.targets().unwrap().as_bytes(),
-                ),
-                (
-                    (MetadataPath::snapshot(), MetadataVersion::Number(2)),
-                    metadata2.snapshot().unwrap().as_bytes(),
-                ),
-                (
-                    (MetadataPath::snapshot(), MetadataVersion::None),
-                    metadata2.snapshot().unwrap().as_bytes(),
-                ),
-                (
-                    (MetadataPath::timestamp(), MetadataVersion::None),
-                    metadata2.timestamp().unwrap().as_bytes(),
-                ),
-            ]);
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                repo,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
 
-            assert_repo(&remote, &expected_metadata);
+            for (path, file) in &files {
+                assert_eq!(
+                    client
+                        .fetch_target_description(&TargetPath::new(path.as_str()).unwrap())
+                        .await
+                        .unwrap(),
+                    TargetDescription::from_slice(file, &[HashAlgorithm::Sha256]).unwrap()
+                );
+            }
+        })
+    }
 
-            // Now, only have the timestamp and snapshot keys online.
-            let expires3 = Utc.with_ymd_and_hms(2038, 1, 3, 0, 0, 0).unwrap();
-            let metadata3 = RepoBuilder::from_database(&mut remote, &db)
-                .trusted_snapshot_keys(&[&KEYS[2]])
-                .trusted_timestamp_keys(&[&KEYS[3]])
-                .skip_root()
-                .skip_targets()
-                .stage_snapshot_with_builder(|builder| builder.expires(expires3))
+    #[test]
+    fn test_add_targets_with_bins_requires_power_of_two() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            for bin_count in [0, 1, 3, 1 << 17] {
+                assert_matches!(
+                    RepoBuilder::create(&mut repo)
+                        .trusted_root_keys(&[&KEYS[0]])
+                        .trusted_targets_keys(&[&KEYS[0]])
+                        .trusted_snapshot_keys(&[&KEYS[0]])
+                        .trusted_timestamp_keys(&[&KEYS[0]])
+                        .stage_root()
+                        .unwrap()
+                        .add_targets_with_bins(
+                            Vec::<(TargetPath, Cursor<&[u8]>)>::new(),
+                            bin_count,
+                            &[&KEYS[1]],
+                        )
+                        .await
+                        .err(),
+                    Some(Error::IllegalArgument(_))
+                );
+            }
+        })
+    }
+
+    #[test]
+    fn test_add_targets_from_dir() {
+        block_on(async move {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            std::fs::create_dir_all(temp_dir.path().join("a/b")).unwrap();
+            std::fs::write(temp_dir.path().join("foo"), b"foo file").unwrap();
+            std::fs::write(temp_dir.path().join("a/bar"), b"bar file").unwrap();
+            std::fs::write(temp_dir.path().join("a/b/baz"), b"baz file").unwrap();
+
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            let hash_algs = &[HashAlgorithm::Sha256, HashAlgorithm::Sha512];
+            let metadata = RepoBuilder::create(&mut repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root_with_builder(|builder| builder.consistent_snapshot(true))
                 .unwrap()
-                .stage_timestamp_with_builder(|builder| builder.expires(expires3))
+                .add_targets_from_dir(temp_dir.path(), "packages/", hash_algs)
+                .await
                 .unwrap()
                 .commit()
                 .await
                 .unwrap();
 
-            assert!(db.update_metadata(&metadata3).unwrap());
+            let targets = metadata
+                .targets()
+                .unwrap()
+                .parse_untrusted()
+                .unwrap()
+                .assume_valid()
+                .unwrap();
+            let expected = hashmap! {
+                TargetPath::new("packages/foo").unwrap() => &b"foo file"[..],
+                TargetPath::new("packages/a/bar").unwrap() => &b"bar file"[..],
+                TargetPath::new("packages/a/b/baz").unwrap() => &b"baz file"[..],
+            };
+            assert_eq!(
+                targets.targets(),
+                &expected
+                    .iter()
+                    .map(|(path, file)| (
+                        path.clone(),
+                        TargetDescription::from_slice(file, hash_algs).unwrap()
+                    ))
+                    .collect::<HashMap<_, _>>()
+            );
 
-            // We only have timestamp and snapshot.
-            assert!(metadata3.root().is_none());
-            assert!(metadata3.targets().is_none());
-            assert!(metadata3.snapshot().is_some());
-            assert!(metadata3.timestamp().is_some());
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                repo,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
 
-            expected_metadata.extend(vec![
-                (
-                    (MetadataPath::snapshot(), MetadataVersion::Number(3)),
-                    metadata3.snapshot().unwrap().as_bytes(),
-                ),
-                (
-                    (MetadataPath::snapshot(), MetadataVersion::None),
-                    metadata3.snapshot().unwrap().as_bytes(),
-                ),
-                (
-                    (MetadataPath::timestamp(), MetadataVersion::None),
-                    metadata3.timestamp().unwrap().as_bytes(),
-                ),
-            ]);
+            for (path, file) in &expected {
+                let mut buf = vec![];
+                client
+                    .fetch_target(path)
+                    .await
+                    .unwrap()
+                    .read_to_end(&mut buf)
+                    .await
+                    .unwrap();
+                assert_eq!(&buf, file);
+            }
+        })
+    }
 
-            assert_repo(&remote, &expected_metadata);
+    #[test]
+    fn test_target_custom() {
+        block_on(async move {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            std::fs::write(temp_dir.path().join("baz"), b"baz file").unwrap();
 
-            // Finally, only have the timestamp keys online.
-            let expires4 = Utc.with_ymd_and_hms(2038, 1, 4, 0, 0, 0).unwrap();
-            let metadata4 = RepoBuilder::from_database(&mut remote, &db)
-                .trusted_timestamp_keys(&[&KEYS[3]])
-                .skip_root()
-                .skip_targets()
-                .skip_snapshot()
-                .stage_timestamp_with_builder(|builder| builder.expires(expires4))
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&mut repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .target_custom(|target_path| {
+                    hashmap! {
+                        "arch".into() => "x86_64".into(),
+                        "name".into() => target_path.as_str().into(),
+                    }
+                })
+                .add_target(TargetPath::new("foo").unwrap(), Cursor::new(b"foo file"))
+                .await
+                .unwrap()
+                .add_target_with_custom(
+                    TargetPath::new("bar").unwrap(),
+                    Cursor::new(b"bar file"),
+                    hashmap! { "arch".into() => "aarch64".into() },
+                )
+                .await
+                .unwrap()
+                .add_targets_from_dir(temp_dir.path(), "", &[HashAlgorithm::Sha256])
+                .await
                 .unwrap()
                 .commit()
                 .await
                 .unwrap();
 
-            assert!(db.update_metadata(&metadata4).unwrap());
-
-            // We only have timestamp and snapshot.
-            assert!(metadata4.root().is_none());
-            assert!(metadata4.targets().is_none());
-            assert!(metadata4.snapshot().is_none());
-            assert!(metadata4.timestamp().is_some());
-
-            expected_metadata.extend(vec![(
-                (MetadataPath::timestamp(), MetadataVersion::None),
-                metadata4.timestamp().unwrap().as_bytes(),
-            )]);
-
-            assert_repo(&remote, &expected_metadata);
+            let targets = metadata
+                .targets()
+                .unwrap()
+                .parse_untrusted()
+                .unwrap()
+                .assume_valid()
+                .unwrap();
+            let custom = |path: &str| {
+                targets.targets()[&TargetPath::new(path).unwrap()]
+                    .custom()
+                    .clone()
+            };
+            assert_eq!(
+                custom("foo"),
+                hashmap! { "arch".into() => "x86_64".into(), "name".into() => "foo".into() }
+            );
+            assert_eq!(
+                custom("bar"),
+                hashmap! { "arch".into() => "aarch64".into(), "name".into() => "bar".into() }
+            );
+            assert_eq!(
+                custom("baz"),
+                hashmap! { "arch".into() => "x86_64".into(), "name".into() => "baz".into() }
+            );
         })
     }
 
     #[test]
-    fn test_builder_inherits_from_trusted_targets() {
+    fn test_add_targets_from_missing_dir() {
         block_on(async move {
             let mut repo = EphemeralRepository::<Pouf1>::new();
+            assert_matches!(
+                RepoBuilder::create(&mut repo)
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .trusted_targets_keys(&[&KEYS[0]])
+                    .trusted_snapshot_keys(&[&KEYS[0]])
+                    .trusted_timestamp_keys(&[&KEYS[0]])
+                    .stage_root()
+                    .unwrap()
+                    .add_targets_from_dir("/does/not/exist", "", &[HashAlgorithm::Sha256])
+                    .await
+                    .err(),
+                Some(Error::Io(_))
+            );
+        })
+    }
 
-            let expires = Utc.with_ymd_and_hms(2038, 1, 4, 0, 0, 0).unwrap();
-            let hash_algs = &[HashAlgorithm::Sha256, HashAlgorithm::Sha512];
-            let delegation_key = &KEYS[0];
-            let delegation_path = MetadataPath::new("delegations").unwrap();
+    #[test]
+    fn test_delegated_role() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            let role = MetadataPath::new("delegated").unwrap();
+            let delegation = Delegation::builder(role.clone())
+                .key(KEYS[1].public())
+                .delegate_path(TargetPath::new("delegated/").unwrap())
+                .build()
+                .unwrap();
+            let target_path = TargetPath::new("delegated/foo").unwrap();
+            let target_file: &[u8] = b"foo file";
 
-            let target_path1 = TargetPath::new("target1").unwrap();
-            let target_file1: &[u8] = b"target1 file";
+            let metadata = RepoBuilder::create(&mut repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .add_delegated_role(delegation, &[&KEYS[1]])
+                .add_delegated_target(&role, target_path.clone(), Cursor::new(target_file))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
 
-            let delegated_target_path1 = TargetPath::new("delegations/delegation1").unwrap();
-            let delegated_target_file1: &[u8] = b"delegation1 file";
+            // The target is only listed by the delegated role.
+            let targets = metadata
+                .targets()
+                .unwrap()
+                .parse_untrusted()
+                .unwrap()
+                .assume_valid()
+                .unwrap();
+            assert!(targets.targets().is_empty());
+            assert_eq!(targets.delegations().roles().len(), 1);
+            assert_eq!(targets.delegations().roles()[0].name(), &role);
 
-            let delegation1 = Delegation::builder(delegation_path.clone())
-                .key(delegation_key.public())
-                .delegate_path(TargetPath::new("delegations/").unwrap())
-                .build()
+            let snapshot = metadata
+                .snapshot()
+                .unwrap()
+                .parse_untrusted()
+                .unwrap()
+                .assume_valid()
                 .unwrap();
+            assert_eq!(snapshot.meta()[&role].version(), 1);
 
-            let delegated_targets1 = TargetsMetadataBuilder::new()
-                .insert_target_from_slice(
-                    delegated_target_path1,
-                    delegated_target_file1,
-                    &[HashAlgorithm::Sha256],
-                )
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                repo,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
+
+            let mut buf = vec![];
+            client
+                .fetch_target(&target_path)
+                .await
                 .unwrap()
-                .signed::<Pouf1>(delegation_key)
+                .read_to_end(&mut buf)
+                .await
+                .unwrap();
+            assert_eq!(buf, target_file);
+        })
+    }
+
+    #[test]
+    fn test_from_repo() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            let role = MetadataPath::new("delegated").unwrap();
+            let delegation = Delegation::builder(role.clone())
+                .key(KEYS[1].public())
+                .delegate_path(TargetPath::new("delegated/").unwrap())
+                .build()
                 .unwrap();
-            let raw_delegated_targets = delegated_targets1.to_raw().unwrap();
 
             let metadata1 = RepoBuilder::create(&mut repo)
                 .trusted_root_keys(&[&KEYS[0]])
@@ -2640,114 +5031,1449 @@ mod tests {
                 .trusted_timestamp_keys(&[&KEYS[0]])
                 .stage_root()
                 .unwrap()
-                .target_hash_algorithms(hash_algs)
-                .add_target(target_path1.clone(), Cursor::new(target_file1))
+                .add_target(TargetPath::new("foo").unwrap(), Cursor::new(b"foo file"))
                 .await
                 .unwrap()
-                .add_delegation_key(delegation_key.public().clone())
-                .add_delegation_role(delegation1.clone())
-                .stage_targets()
-                .unwrap()
-                .stage_snapshot_with_builder(|builder| {
-                    builder.insert_metadata_description(
-                        delegation_path.clone(),
-                        MetadataDescription::from_slice(
-                            raw_delegated_targets.as_bytes(),
-                            1,
-                            &[HashAlgorithm::Sha256],
-                        )
-                        .unwrap(),
-                    )
-                })
+                .add_delegated_role(delegation.clone(), &[&KEYS[1]])
+                .add_delegated_target(
+                    &role,
+                    TargetPath::new("delegated/bar").unwrap(),
+                    Cursor::new(b"bar file"),
+                )
+                .await
                 .unwrap()
                 .commit()
                 .await
                 .unwrap();
+            let root = metadata1.root().unwrap().clone();
 
-            // Next, create a new commit where we add a new target and delegation. This should copy
-            // over the old targets and delegations.
-            let mut database = Database::from_trusted_metadata(&metadata1).unwrap();
-
-            let target_path2 = TargetPath::new("bar").unwrap();
-            let target_file2: &[u8] = b"bar file";
+            // Only the root metadata is needed to continue editing the repository.
+            let metadata2 = RepoBuilder::from_repo(&mut repo, &root)
+                .await
+                .unwrap()
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .skip_root()
+                .add_target(TargetPath::new("baz").unwrap(), Cursor::new(b"baz file"))
+                .await
+                .unwrap()
+                .add_delegated_role(delegation, &[&KEYS[1]])
+                .add_delegated_target(
+                    &role,
+                    TargetPath::new("delegated/qux").unwrap(),
+                    Cursor::new(b"qux file"),
+                )
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
 
-            let delegated_target_path2 = TargetPath::new("delegations/delegation2").unwrap();
-            let delegated_target_file2: &[u8] = b"delegation2 file";
+            assert!(metadata2.root().is_none());
 
-            let delegation2 = Delegation::builder(delegation_path.clone())
-                .key(delegation_key.public())
-                .delegate_path(TargetPath::new("delegations/").unwrap())
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                &root,
+                EphemeralRepository::new(),
+                repo,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
+
+            assert_eq!(client.database().trusted_targets().unwrap().version(), 2);
+            assert_eq!(client.database().trusted_snapshot().unwrap().version(), 2);
+            assert_eq!(client.database().trusted_timestamp().unwrap().version(), 2);
+
+            // The targets and delegated targets of the repository were inherited.
+            for (path, file) in [
+                ("foo", &b"foo file"[..]),
+                ("baz", &b"baz file"[..]),
+                ("delegated/bar", &b"bar file"[..]),
+                ("delegated/qux", &b"qux file"[..]),
+            ] {
+                assert_eq!(
+                    client
+                        .fetch_target_description(&TargetPath::new(path).unwrap())
+                        .await
+                        .unwrap(),
+                    TargetDescription::from_slice(file, &[HashAlgorithm::Sha256]).unwrap()
+                );
+            }
+        })
+    }
+
+    #[test]
+    fn test_migrate_to_consistent_snapshot() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            let role = MetadataPath::new("delegated").unwrap();
+            let delegation = Delegation::builder(role.clone())
+                .key(KEYS[1].public())
+                .delegate_path(TargetPath::new("delegated/").unwrap())
                 .build()
                 .unwrap();
 
-            let delegated_targets2 = TargetsMetadataBuilder::new()
-                .insert_target_from_slice(
-                    delegated_target_path2,
-                    delegated_target_file2,
-                    &[HashAlgorithm::Sha256],
+            let metadata1 = RepoBuilder::create(&mut repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root_with_builder(|builder| builder.consistent_snapshot(false))
+                .unwrap()
+                .add_target(TargetPath::new("foo").unwrap(), Cursor::new(b"foo file"))
+                .await
+                .unwrap()
+                .add_delegated_role(delegation, &[&KEYS[1]])
+                .add_delegated_target(
+                    &role,
+                    TargetPath::new("delegated/bar").unwrap(),
+                    Cursor::new(b"bar file"),
                 )
+                .await
                 .unwrap()
-                .signed::<Pouf1>(delegation_key)
+                .commit()
+                .await
                 .unwrap();
-            let raw_delegated_targets = delegated_targets2.to_raw().unwrap();
+            let root = metadata1.root().unwrap().clone();
+
+            let metadata2 = RepoBuilder::from_repo(&mut repo, &root)
+                .await
+                .unwrap()
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .migrate_to_consistent_snapshot()
+                .await
+                .unwrap();
+
+            let new_root = metadata2.root().unwrap().parse_untrusted().unwrap();
+            let new_root = new_root.assume_valid().unwrap();
+            assert_eq!(new_root.version(), 2);
+            assert!(new_root.consistent_snapshot());
+            assert!(metadata2.targets().is_none());
+            assert!(metadata2.snapshot().is_none());
+            assert!(metadata2.timestamp().is_none());
+
+            // A client that starts from the old root picks up the new one, and then fetches the
+            // versioned metadata and hash prefixed targets.
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                &root,
+                EphemeralRepository::new(),
+                &mut repo,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
+
+            assert_eq!(client.database().trusted_root().version(), 2);
+            assert!(client.database().trusted_root().consistent_snapshot());
+
+            for (path, file) in [
+                ("foo", &b"foo file"[..]),
+                ("delegated/bar", &b"bar file"[..]),
+            ] {
+                let mut buf = Vec::new();
+                client
+                    .fetch_target(&TargetPath::new(path).unwrap())
+                    .await
+                    .unwrap()
+                    .read_to_end(&mut buf)
+                    .await
+                    .unwrap();
+                assert_eq!(buf, file);
+            }
+
+            // The repository can't be migrated twice.
+            assert_matches!(
+                RepoBuilder::from_repo(&mut repo, &root)
+                    .await
+                    .unwrap()
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .trusted_targets_keys(&[&KEYS[0]])
+                    .trusted_snapshot_keys(&[&KEYS[0]])
+                    .trusted_timestamp_keys(&[&KEYS[0]])
+                    .migrate_to_consistent_snapshot()
+                    .await,
+                Err(Error::IllegalArgument(_))
+            );
+
+            // Migrating requires the metadata of the repository.
+            assert_matches!(
+                RepoBuilder::create(EphemeralRepository::<Pouf1>::new())
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .migrate_to_consistent_snapshot()
+                    .await,
+                Err(Error::IllegalArgument(_))
+            );
+        })
+    }
+
+    #[test]
+    fn test_remove_and_yank_target() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            let foo = TargetPath::new("foo").unwrap();
+            let bar = TargetPath::new("bar").unwrap();
+
+            let metadata1 = RepoBuilder::create(&mut repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target(foo.clone(), Cursor::new(b"foo file"))
+                .await
+                .unwrap()
+                .add_target(bar.clone(), Cursor::new(b"bar file"))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+            let root = metadata1.root().unwrap().clone();
+
+            let foo_description =
+                TargetDescription::from_slice(b"foo file", &[HashAlgorithm::Sha256]).unwrap();
+            let foo_stored = foo
+                .with_hash_prefix(&foo_description.hashes()[&HashAlgorithm::Sha256])
+                .unwrap();
+            repo.fetch_target(&foo_stored).await.unwrap();
+
+            RepoBuilder::from_repo(&mut repo, &root)
+                .await
+                .unwrap()
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .remove_target(foo.clone())
+                .unwrap()
+                .yank_target(bar.clone())
+                .commit()
+                .await
+                .unwrap();
+
+            // The stored file of the removed target was cleaned up.
+            assert_matches!(
+                repo.fetch_target(&foo_stored).await.err(),
+                Some(Error::TargetNotFound(_))
+            );
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                &root,
+                EphemeralRepository::new(),
+                &mut repo,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
+
+            let trusted_targets = client.database().trusted_targets().unwrap();
+            assert_eq!(trusted_targets.version(), 2);
+            assert!(!trusted_targets.targets().contains_key(&foo));
+
+            // The yanked target can still be fetched.
+            let bar_description = &trusted_targets.targets()[&bar];
+            assert!(bar_description.is_yanked());
+            assert_eq!(bar_description.length(), Some(b"bar file".len() as u64));
+
+            let mut buf = Vec::new();
+            client
+                .fetch_target(&bar)
+                .await
+                .unwrap()
+                .read_to_end(&mut buf)
+                .await
+                .unwrap();
+            assert_eq!(buf, b"bar file");
+        })
+    }
+
+    #[test]
+    fn test_add_target_descriptions() {
+        block_on(async move {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            let foo_path = TargetPath::new("foo").unwrap();
+            let bar_path = TargetPath::new("bar").unwrap();
+            let foo = TargetDescription::from_slice(b"foo file", &[HashAlgorithm::Sha256]).unwrap();
+            let bar = TargetDescription::from_slice(b"bar file", &[HashAlgorithm::Sha512]).unwrap();
+
+            let metadata = RepoBuilder::create(&repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target_descriptions(vec![
+                    (foo_path.clone(), foo.clone()),
+                    (bar_path.clone(), bar.clone()),
+                ])
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let targets = metadata.targets().unwrap().parse_untrusted().unwrap();
+            let targets = targets.assume_valid().unwrap();
+            assert_eq!(
+                targets.targets(),
+                &hashmap! {
+                    foo_path.clone() => foo,
+                    bar_path.clone() => bar,
+                }
+            );
+
+            // The target files aren't stored in the repository.
+            for path in [&foo_path, &bar_path] {
+                assert!(matches!(
+                    repo.fetch_target(path).await,
+                    Err(Error::TargetNotFound(_))
+                ));
+            }
+        })
+    }
+
+    #[test]
+    fn test_remove_target_errors() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&mut repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target(TargetPath::new("foo").unwrap(), Cursor::new(b"foo file"))
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+            let root = metadata.root().unwrap().clone();
+
+            // Only targets in the targets metadata can be removed or yanked.
+            for builder in [
+                RepoBuilder::from_repo(&repo, &root)
+                    .await
+                    .unwrap()
+                    .trusted_targets_keys(&[&KEYS[0]])
+                    .skip_root()
+                    .remove_target(TargetPath::new("bar").unwrap()),
+                RepoBuilder::from_repo(&repo, &root)
+                    .await
+                    .unwrap()
+                    .trusted_targets_keys(&[&KEYS[0]])
+                    .skip_root()
+                    .yank_target(TargetPath::new("bar").unwrap()),
+            ] {
+                assert_matches!(
+                    builder.stage_targets().map(|_| ()),
+                    Err(Error::IllegalArgument(_))
+                );
+            }
+
+            // A target that was removed and then added again is kept.
+            let metadata = RepoBuilder::from_repo(&mut repo, &root)
+                .await
+                .unwrap()
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .skip_root()
+                .remove_target(TargetPath::new("foo").unwrap())
+                .add_target(
+                    TargetPath::new("foo").unwrap(),
+                    Cursor::new(b"new foo file"),
+                )
+                .await
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let targets = metadata.targets().unwrap().parse_untrusted().unwrap();
+            let targets = targets.assume_valid().unwrap();
+            assert_eq!(
+                targets.targets()[&TargetPath::new("foo").unwrap()],
+                TargetDescription::from_slice(b"new foo file", &[HashAlgorithm::Sha256]).unwrap()
+            );
+        })
+    }
+
+    #[test]
+    fn test_commit_and_verify() {
+        block_on(async move {
+            let repo = EphemeralRepository::<Pouf1>::new();
+            let target_path = TargetPath::new("foo").unwrap();
+
+            let (metadata, report) = RepoBuilder::create(&repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target(target_path.clone(), Cursor::new(b"foo file"))
+                .await
+                .unwrap()
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot()
+                .unwrap()
+                .stage_timestamp()
+                .unwrap()
+                .commit_and_verify()
+                .await
+                .unwrap();
+            assert!(report.is_empty(), "{:?}", report);
+            let root = metadata.root().unwrap().clone();
+
+            // A target that was overwritten after it was published is reported.
+            let description =
+                TargetDescription::from_slice(b"foo file", &[HashAlgorithm::Sha256]).unwrap();
+            repo.store_target(
+                &target_path
+                    .with_hash_prefix(&description.hashes()[&HashAlgorithm::Sha256])
+                    .unwrap(),
+                &mut &b"bar file"[..],
+            )
+            .await
+            .unwrap();
+
+            let (_, report) = RepoBuilder::from_repo(&repo, &root)
+                .await
+                .unwrap()
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .skip_root()
+                .skip_targets()
+                .skip_snapshot()
+                .stage_timestamp()
+                .unwrap()
+                .commit_and_verify()
+                .await
+                .unwrap();
+            assert!(report.has_errors());
+            assert_eq!(
+                report
+                    .findings()
+                    .iter()
+                    .map(|finding| (finding.kind(), finding.role().as_str()))
+                    .collect::<Vec<_>>(),
+                vec![(lint::FindingKind::TargetMismatch, "targets")]
+            );
+        })
+    }
+
+    #[test]
+    fn test_deterministic() {
+        block_on(async move {
+            let current_time = Utc.timestamp_opt(1_000_000, 0).unwrap();
+
+            let mut digests = vec![];
+            let mut sets = vec![];
+            for _ in 0..2 {
+                let repo = EphemeralRepository::<Pouf1>::new();
+                let metadata = RepoBuilder::create(&repo)
+                    .deterministic(true)
+                    .current_time(current_time)
+                    .time_versioning(true)
+                    .pretty_print(true)
+                    .trusted_root_keys(&[&KEYS[0], &KEYS[1]])
+                    .trusted_targets_keys(&[&KEYS[1], &KEYS[0]])
+                    .trusted_snapshot_keys(&[&KEYS[2]])
+                    .trusted_timestamp_keys(&[&KEYS[3]])
+                    .add_target(TargetPath::new("foo").unwrap(), Cursor::new(b"foo file"))
+                    .await
+                    .unwrap()
+                    .add_target(TargetPath::new("bar").unwrap(), Cursor::new(b"bar file"))
+                    .await
+                    .unwrap()
+                    .commit()
+                    .await
+                    .unwrap();
+                digests.push(metadata.digest().unwrap());
+                sets.push(metadata);
+            }
+            assert_eq!(sets[0], sets[1]);
+            assert_eq!(digests[0], digests[1]);
+
+            // The digest covers every metadata in the set.
+            let partial = RawSignedMetadataSetBuilder::new()
+                .root(sets[0].root().unwrap().clone())
+                .build();
+            assert_ne!(partial.digest().unwrap(), digests[0]);
+
+            // Staging without an explicit time is refused.
+            let repo = EphemeralRepository::<Pouf1>::new();
+            assert_matches!(
+                RepoBuilder::create(&repo)
+                    .deterministic(true)
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .trusted_targets_keys(&[&KEYS[0]])
+                    .trusted_snapshot_keys(&[&KEYS[0]])
+                    .trusted_timestamp_keys(&[&KEYS[0]])
+                    .stage_root()
+                    .map(|_| ()),
+                Err(Error::IllegalArgument(_))
+            );
+        })
+    }
+
+    #[test]
+    fn test_delegated_role_errors() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            let role = MetadataPath::new("delegated").unwrap();
+            let delegation = Delegation::builder(role.clone())
+                .key(KEYS[1].public())
+                .delegate_path(TargetPath::new("delegated/").unwrap())
+                .build()
+                .unwrap();
+
+            // The role must have been added to the builder.
+            assert_matches!(
+                RepoBuilder::create(&mut repo)
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .trusted_targets_keys(&[&KEYS[0]])
+                    .trusted_snapshot_keys(&[&KEYS[0]])
+                    .trusted_timestamp_keys(&[&KEYS[0]])
+                    .stage_root()
+                    .unwrap()
+                    .add_delegated_target(
+                        &role,
+                        TargetPath::new("delegated/foo").unwrap(),
+                        Cursor::new(b"foo file"),
+                    )
+                    .await
+                    .err(),
+                Some(Error::IllegalArgument(_))
+            );
+
+            // The role must delegate the target.
+            assert_matches!(
+                RepoBuilder::create(&mut repo)
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .trusted_targets_keys(&[&KEYS[0]])
+                    .trusted_snapshot_keys(&[&KEYS[0]])
+                    .trusted_timestamp_keys(&[&KEYS[0]])
+                    .stage_root()
+                    .unwrap()
+                    .add_delegated_role(delegation.clone(), &[&KEYS[1]])
+                    .add_delegated_target(
+                        &role,
+                        TargetPath::new("foo").unwrap(),
+                        Cursor::new(b"foo file"),
+                    )
+                    .await
+                    .err(),
+                Some(Error::IllegalArgument(_))
+            );
+
+            // The role must be signed with keys the delegation authorizes.
+            assert_matches!(
+                RepoBuilder::create(&mut repo)
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .trusted_targets_keys(&[&KEYS[0]])
+                    .trusted_snapshot_keys(&[&KEYS[0]])
+                    .trusted_timestamp_keys(&[&KEYS[0]])
+                    .stage_root()
+                    .unwrap()
+                    .add_delegated_role(delegation.clone(), &[&KEYS[2]])
+                    .stage_targets()
+                    .err(),
+                Some(Error::IllegalArgument(_))
+            );
+
+            // The role must be signed with at least one key.
+            assert_matches!(
+                RepoBuilder::create(&mut repo)
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .trusted_targets_keys(&[&KEYS[0]])
+                    .trusted_snapshot_keys(&[&KEYS[0]])
+                    .trusted_timestamp_keys(&[&KEYS[0]])
+                    .stage_root()
+                    .unwrap()
+                    .add_delegated_role(delegation, &[])
+                    .stage_targets()
+                    .err(),
+                Some(Error::MissingPrivateKey { .. })
+            );
+        })
+    }
+
+    #[test]
+    fn test_do_not_require_all_keys_to_be_online() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+
+            // First, write some metadata to the repo.
+            let expires1 = Utc.with_ymd_and_hms(2038, 1, 1, 0, 0, 0).unwrap();
+            let metadata1 = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[1]])
+                .trusted_snapshot_keys(&[&KEYS[2]])
+                .trusted_timestamp_keys(&[&KEYS[3]])
+                .stage_root_with_builder(|builder| {
+                    builder.consistent_snapshot(true).expires(expires1)
+                })
+                .unwrap()
+                .stage_targets_with_builder(|builder| builder.expires(expires1))
+                .unwrap()
+                .stage_snapshot_with_builder(|builder| builder.expires(expires1))
+                .unwrap()
+                .stage_timestamp_with_builder(|builder| builder.expires(expires1))
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            // We wrote all the metadata.
+            assert!(metadata1.root().is_some());
+            assert!(metadata1.timestamp().is_some());
+            assert!(metadata1.snapshot().is_some());
+            assert!(metadata1.targets().is_some());
+
+            let mut expected_metadata: BTreeMap<_, _> = vec![
+                (
+                    (MetadataPath::root(), MetadataVersion::Number(1)),
+                    metadata1.root().unwrap().as_bytes(),
+                ),
+                (
+                    (MetadataPath::root(), MetadataVersion::None),
+                    metadata1.root().unwrap().as_bytes(),
+                ),
+                (
+                    (MetadataPath::targets(), MetadataVersion::Number(1)),
+                    metadata1.targets().unwrap().as_bytes(),
+                ),
+                (
+                    (MetadataPath::targets(), MetadataVersion::None),
+                    metadata1.targets().unwrap().as_bytes(),
+                ),
+                (
+                    (MetadataPath::snapshot(), MetadataVersion::Number(1)),
+                    metadata1.snapshot().unwrap().as_bytes(),
+                ),
+                (
+                    (MetadataPath::snapshot(), MetadataVersion::None),
+                    metadata1.snapshot().unwrap().as_bytes(),
+                ),
+                (
+                    (MetadataPath::timestamp(), MetadataVersion::None),
+                    metadata1.timestamp().unwrap().as_bytes(),
+                ),
+            ]
+            .into_iter()
+            .collect();
+
+            assert_repo(&remote, &expected_metadata);
+
+            let mut db = Database::from_trusted_metadata(&metadata1).unwrap();
+
+            // Next, write another batch, but only have the timestamp, snapshot, and targets keys.
+            let expires2 = Utc.with_ymd_and_hms(2038, 1, 2, 0, 0, 0).unwrap();
+            let metadata2 = RepoBuilder::from_database(&mut remote, &db)
+                .trusted_targets_keys(&[&KEYS[1]])
+                .trusted_snapshot_keys(&[&KEYS[2]])
+                .trusted_timestamp_keys(&[&KEYS[3]])
+                .skip_root()
+                .stage_targets_with_builder(|builder| builder.expires(expires2))
+                .unwrap()
+                .stage_snapshot_with_builder(|builder| builder.expires(expires2))
+                .unwrap()
+                .stage_timestamp_with_builder(|builder| builder.expires(expires2))
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            assert!(db.update_metadata(&metadata2).unwrap());
+
+            assert!(metadata2.root().is_none());
+            assert!(metadata2.targets().is_some());
+            assert!(metadata2.snapshot().is_some());
+            assert!(metadata2.timestamp().is_some());
+
+            expected_metadata.extend(vec![
+                (
+                    (MetadataPath::targets(), MetadataVersion::Number(2)),
+                    metadata2.targets().unwrap().as_bytes(),
+                ),
+                (
+                    (MetadataPath::targets(), MetadataVersion::None),
+                    metadata2.targets().unwrap().as_bytes(),
+                ),
+                (
+                    (MetadataPath::snapshot(), MetadataVersion::Number(2)),
+                    metadata2.snapshot().unwrap().as_bytes(),
+                ),
+                (
+                    (MetadataPath::snapshot(), MetadataVersion::None),
+                    metadata2.snapshot().unwrap().as_bytes(),
+                ),
+                (
+                    (MetadataPath::timestamp(), MetadataVersion::None),
+                    metadata2.timestamp().unwrap().as_bytes(),
+                ),
+            ]);
+
+            assert_repo(&remote, &expected_metadata);
+
+            // Now, only have the timestamp and snapshot keys online.
+            let expires3 = Utc.with_ymd_and_hms(2038, 1, 3, 0, 0, 0).unwrap();
+            let metadata3 = RepoBuilder::from_database(&mut remote, &db)
+                .trusted_snapshot_keys(&[&KEYS[2]])
+                .trusted_timestamp_keys(&[&KEYS[3]])
+                .skip_root()
+                .skip_targets()
+                .stage_snapshot_with_builder(|builder| builder.expires(expires3))
+                .unwrap()
+                .stage_timestamp_with_builder(|builder| builder.expires(expires3))
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            assert!(db.update_metadata(&metadata3).unwrap());
+
+            // We only have timestamp and snapshot.
+            assert!(metadata3.root().is_none());
+            assert!(metadata3.targets().is_none());
+            assert!(metadata3.snapshot().is_some());
+            assert!(metadata3.timestamp().is_some());
+
+            expected_metadata.extend(vec![
+                (
+                    (MetadataPath::snapshot(), MetadataVersion::Number(3)),
+                    metadata3.snapshot().unwrap().as_bytes(),
+                ),
+                (
+                    (MetadataPath::snapshot(), MetadataVersion::None),
+                    metadata3.snapshot().unwrap().as_bytes(),
+                ),
+                (
+                    (MetadataPath::timestamp(), MetadataVersion::None),
+                    metadata3.timestamp().unwrap().as_bytes(),
+                ),
+            ]);
+
+            assert_repo(&remote, &expected_metadata);
+
+            // Finally, only have the timestamp keys online.
+            let expires4 = Utc.with_ymd_and_hms(2038, 1, 4, 0, 0, 0).unwrap();
+            let metadata4 = RepoBuilder::from_database(&mut remote, &db)
+                .trusted_timestamp_keys(&[&KEYS[3]])
+                .skip_root()
+                .skip_targets()
+                .skip_snapshot()
+                .stage_timestamp_with_builder(|builder| builder.expires(expires4))
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            assert!(db.update_metadata(&metadata4).unwrap());
+
+            // We only have timestamp and snapshot.
+            assert!(metadata4.root().is_none());
+            assert!(metadata4.targets().is_none());
+            assert!(metadata4.snapshot().is_none());
+            assert!(metadata4.timestamp().is_some());
+
+            expected_metadata.extend(vec![(
+                (MetadataPath::timestamp(), MetadataVersion::None),
+                metadata4.timestamp().unwrap().as_bytes(),
+            )]);
+
+            assert_repo(&remote, &expected_metadata);
+        })
+    }
+
+    #[test]
+    fn test_builder_inherits_from_trusted_targets() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+
+            let expires = Utc.with_ymd_and_hms(2038, 1, 4, 0, 0, 0).unwrap();
+            let hash_algs = &[HashAlgorithm::Sha256, HashAlgorithm::Sha512];
+            let delegation_key = &KEYS[0];
+            let delegation_path = MetadataPath::new("delegations").unwrap();
+
+            let target_path1 = TargetPath::new("target1").unwrap();
+            let target_file1: &[u8] = b"target1 file";
+
+            let delegated_target_path1 = TargetPath::new("delegations/delegation1").unwrap();
+            let delegated_target_file1: &[u8] = b"delegation1 file";
+
+            let delegation1 = Delegation::builder(delegation_path.clone())
+                .key(delegation_key.public())
+                .delegate_path(TargetPath::new("delegations/").unwrap())
+                .build()
+                .unwrap();
+
+            let delegated_targets1 = TargetsMetadataBuilder::new()
+                .insert_target_from_slice(
+                    delegated_target_path1,
+                    delegated_target_file1,
+                    &[HashAlgorithm::Sha256],
+                )
+                .unwrap()
+                .signed::<Pouf1>(delegation_key)
+                .unwrap();
+            let raw_delegated_targets = delegated_targets1.to_raw().unwrap();
+
+            let metadata1 = RepoBuilder::create(&mut repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .target_hash_algorithms(hash_algs)
+                .add_target(target_path1.clone(), Cursor::new(target_file1))
+                .await
+                .unwrap()
+                .add_delegation_key(delegation_key.public().clone())
+                .add_delegation_role(delegation1.clone())
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot_with_builder(|builder| {
+                    builder.insert_metadata_description(
+                        delegation_path.clone(),
+                        MetadataDescription::from_slice(
+                            raw_delegated_targets.as_bytes(),
+                            1,
+                            &[HashAlgorithm::Sha256],
+                        )
+                        .unwrap(),
+                    )
+                })
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            // Next, create a new commit where we add a new target and delegation. This should copy
+            // over the old targets and delegations.
+            let mut database = Database::from_trusted_metadata(&metadata1).unwrap();
+
+            let target_path2 = TargetPath::new("bar").unwrap();
+            let target_file2: &[u8] = b"bar file";
+
+            let delegated_target_path2 = TargetPath::new("delegations/delegation2").unwrap();
+            let delegated_target_file2: &[u8] = b"delegation2 file";
+
+            let delegation2 = Delegation::builder(delegation_path.clone())
+                .key(delegation_key.public())
+                .delegate_path(TargetPath::new("delegations/").unwrap())
+                .build()
+                .unwrap();
+
+            let delegated_targets2 = TargetsMetadataBuilder::new()
+                .insert_target_from_slice(
+                    delegated_target_path2,
+                    delegated_target_file2,
+                    &[HashAlgorithm::Sha256],
+                )
+                .unwrap()
+                .signed::<Pouf1>(delegation_key)
+                .unwrap();
+            let raw_delegated_targets = delegated_targets2.to_raw().unwrap();
+
+            let metadata2 = RepoBuilder::from_database(&mut repo, &database)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .target_hash_algorithms(hash_algs)
+                .add_target(target_path2.clone(), Cursor::new(target_file2))
+                .await
+                .unwrap()
+                .add_delegation_role(delegation2.clone())
+                .stage_targets_with_builder(|b| b.expires(expires))
+                .unwrap()
+                .stage_snapshot_with_builder(|builder| {
+                    builder.insert_metadata_description(
+                        delegation_path.clone(),
+                        MetadataDescription::from_slice(
+                            raw_delegated_targets.as_bytes(),
+                            1,
+                            &[HashAlgorithm::Sha256],
+                        )
+                        .unwrap(),
+                    )
+                })
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            database.update_metadata(&metadata2).unwrap();
+
+            assert_eq!(
+                &**database.trusted_targets().unwrap(),
+                &TargetsMetadataBuilder::new()
+                    .version(2)
+                    .expires(expires)
+                    .insert_target_from_slice(target_path1.clone(), target_file1, hash_algs)
+                    .unwrap()
+                    .insert_target_from_slice(target_path2.clone(), target_file2, hash_algs)
+                    .unwrap()
+                    .delegations(
+                        DelegationsBuilder::new()
+                            .key(delegation_key.public().clone())
+                            .role(delegation1)
+                            .role(delegation2)
+                            .build()
+                            .unwrap()
+                    )
+                    .build()
+                    .unwrap()
+            )
+        })
+    }
+
+    #[test]
+    fn test_builder_rotating_keys_refreshes_metadata() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+
+            let metadata1 = RepoBuilder::create(&mut repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            let mut db = Database::from_trusted_metadata(&metadata1).unwrap();
+
+            // Because of [update-root], rotating any root keys should make a new timestamp and
+            // snapshot.
+            //
+            // FIXME(#297): This also purges targets, even though that's not conforming to the spec.
+            //
+            // [update-root]: https://theupdateframework.github.io/specification/v1.0.30/#update-root
+            let metadata2 = RepoBuilder::from_database(&mut repo, &db)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[1]])
+                .commit()
+                .await
+                .unwrap();
+
+            assert!(metadata2.root().is_some());
+            assert!(metadata2.targets().is_some());
+            assert!(metadata2.snapshot().is_some());
+            assert!(metadata2.timestamp().is_some());
+
+            db.update_metadata(&metadata2).unwrap();
+
+            assert_eq!(db.trusted_root().version(), 2);
+            assert_eq!(db.trusted_targets().unwrap().version(), 2);
+            assert_eq!(db.trusted_snapshot().unwrap().version(), 2);
+            assert_eq!(db.trusted_timestamp().unwrap().version(), 2);
+
+            // Note that rotating the timestamp keys purges all the metadata, so add it back in.
+
+            // Rotating the snapshot key should make new metadata.
+            let metadata3 = RepoBuilder::from_database(&mut repo, &db)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[1]])
+                .trusted_timestamp_keys(&[&KEYS[1]])
+                .commit()
+                .await
+                .unwrap();
+
+            assert!(metadata3.root().is_some());
+            assert!(metadata2.targets().is_some());
+            assert!(metadata2.snapshot().is_some());
+            assert!(metadata2.timestamp().is_some());
+
+            db.update_metadata(&metadata3).unwrap();
+
+            assert_eq!(db.trusted_root().version(), 3);
+            assert_eq!(db.trusted_targets().unwrap().version(), 3);
+            assert_eq!(db.trusted_snapshot().unwrap().version(), 3);
+            assert_eq!(db.trusted_timestamp().unwrap().version(), 3);
+
+            // Rotating the targets key should make a new targets, snapshot, and timestamp.
+            let metadata4 = RepoBuilder::from_database(&mut repo, &db)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[1]])
+                .trusted_snapshot_keys(&[&KEYS[1]])
+                .trusted_timestamp_keys(&[&KEYS[1]])
+                .commit()
+                .await
+                .unwrap();
+
+            assert!(metadata4.root().is_some());
+            assert!(metadata4.targets().is_some());
+            assert!(metadata4.snapshot().is_some());
+            assert!(metadata4.timestamp().is_some());
+
+            db.update_metadata(&metadata4).unwrap();
+
+            assert_eq!(db.trusted_root().version(), 4);
+            assert_eq!(db.trusted_targets().unwrap().version(), 4);
+            assert_eq!(db.trusted_snapshot().unwrap().version(), 4);
+            assert_eq!(db.trusted_timestamp().unwrap().version(), 4);
+
+            // Rotating the root key should make a new targets, snapshot, and timestamp.
+            let metadata5 = RepoBuilder::from_database(&mut repo, &db)
+                .signing_root_keys(&[&KEYS[0]])
+                .trusted_root_keys(&[&KEYS[1]])
+                .trusted_targets_keys(&[&KEYS[1]])
+                .trusted_snapshot_keys(&[&KEYS[1]])
+                .trusted_timestamp_keys(&[&KEYS[1]])
+                .commit()
+                .await
+                .unwrap();
+
+            assert!(metadata5.root().is_some());
+            assert!(metadata5.targets().is_some());
+            assert!(metadata5.snapshot().is_some());
+            assert!(metadata5.timestamp().is_some());
+
+            db.update_metadata(&metadata5).unwrap();
+
+            assert_eq!(db.trusted_root().version(), 5);
+            assert_eq!(db.trusted_targets().unwrap().version(), 5);
+            assert_eq!(db.trusted_snapshot().unwrap().version(), 5);
+            assert_eq!(db.trusted_timestamp().unwrap().version(), 5);
+        })
+    }
+
+    #[test]
+    fn test_rotate_keys() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+
+            let metadata1 = RepoBuilder::create(&mut repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            let mut db = Database::from_trusted_metadata(&metadata1).unwrap();
+
+            let metadata2 = RepoBuilder::from_database(&mut repo, &db)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .rotate_keys(Role::Root, &[&KEYS[1], &KEYS[2]], 2)
+                .unwrap()
+                .rotate_keys(Role::Timestamp, &[&KEYS[3]], 1)
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            // The next root is signed by both the outgoing and the incoming root keys.
+            let raw_root = metadata2.root().unwrap();
+            assert_eq!(raw_root.parse_untrusted().unwrap().signatures().len(), 3);
+
+            db.update_metadata(&metadata2).unwrap();
+
+            let root = db.trusted_root();
+            assert_eq!(root.version(), 2);
+            assert_eq!(root.root().threshold(), 2);
+            assert_eq!(
+                root.root().key_ids(),
+                &hashset! {
+                    KEYS[1].public().key_id().clone(),
+                    KEYS[2].public().key_id().clone(),
+                }
+            );
+            assert_eq!(
+                root.timestamp().key_ids(),
+                &hashset! { KEYS[3].public().key_id().clone() }
+            );
+        })
+    }
+
+    #[test]
+    fn test_rotate_keys_errors() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+
+            // Keys can only be rotated in a repository with a trusted root.
+            assert_matches!(
+                RepoBuilder::create(&mut repo)
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .rotate_keys(Role::Root, &[&KEYS[1]], 1)
+                    .err(),
+                Some(Error::IllegalArgument(_))
+            );
+
+            let metadata = RepoBuilder::create(&mut repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+            let db = Database::from_trusted_metadata(&metadata).unwrap();
+
+            // The new keys must meet the new threshold.
+            assert_matches!(
+                RepoBuilder::from_database(&mut repo, &db)
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .rotate_keys(Role::Root, &[&KEYS[1]], 2)
+                    .err(),
+                Some(Error::IllegalArgument(_))
+            );
+
+            // The outgoing root keys must meet the trusted threshold.
+            assert_matches!(
+                RepoBuilder::from_database(&mut repo, &db)
+                    .rotate_keys(Role::Root, &[&KEYS[1]], 1)
+                    .err(),
+                Some(Error::IllegalArgument(_))
+            );
+
+            // Custom roles cannot be rotated.
+            assert_matches!(
+                RepoBuilder::from_database(&mut repo, &db)
+                    .rotate_keys(Role::Custom, &[&KEYS[1]], 1)
+                    .err(),
+                Some(Error::IllegalArgument(_))
+            );
+        })
+    }
+
+    #[test]
+    fn test_offline_signing() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+
+            // Only the public root key is available when the metadata is staged.
+            let builder = RepoBuilder::create(&mut repo)
+                .offline_signing(true)
+                .trusted_targets_keys(&[&KEYS[1]])
+                .trusted_snapshot_keys(&[&KEYS[1]])
+                .trusted_timestamp_keys(&[&KEYS[1]])
+                .stage_root_with_builder(|builder| builder.root_key(KEYS[0].public().clone()))
+                .unwrap()
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot()
+                .unwrap()
+                .stage_timestamp()
+                .unwrap();
+
+            let payloads = builder.export_unsigned().unwrap();
+            assert_eq!(
+                payloads
+                    .iter()
+                    .map(|payload| payload.role().as_str())
+                    .collect::<Vec<_>>(),
+                vec!["root", "targets", "snapshot", "timestamp"]
+            );
+            let root_payload = &payloads[0];
+            assert_eq!(root_payload.version(), 1);
+            assert_eq!(
+                root_payload.digest(),
+                &crypto::calculate_hashes_from_slice(
+                    root_payload.payload(),
+                    &[HashAlgorithm::Sha256]
+                )
+                .unwrap()[&HashAlgorithm::Sha256]
+            );
+
+            // Sign the root payload elsewhere.
+            let sig = KEYS[0].sign(root_payload.payload()).unwrap();
+
+            // A signature over another payload is rejected.
+            let bad_sig = KEYS[0].sign(payloads[1].payload()).unwrap();
+            let builder = builder
+                .import_signatures(
+                    &MetadataPath::root(),
+                    &[(KEYS[0].public(), bad_sig.value().as_bytes())],
+                )
+                .err();
+            assert_matches!(builder, Some(Error::BadSignature(_)));
+
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&mut repo)
+                .offline_signing(true)
+                .trusted_targets_keys(&[&KEYS[1]])
+                .trusted_snapshot_keys(&[&KEYS[1]])
+                .trusted_timestamp_keys(&[&KEYS[1]])
+                .stage_root_with_builder(|builder| builder.root_key(KEYS[0].public().clone()))
+                .unwrap()
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot()
+                .unwrap()
+                .stage_timestamp()
+                .unwrap()
+                .import_signatures(
+                    &MetadataPath::root(),
+                    &[(KEYS[0].public(), sig.value().as_bytes())],
+                )
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let db = Database::from_trusted_metadata(&metadata).unwrap();
+            assert_eq!(db.trusted_root().version(), 1);
+        })
+    }
+
+    #[test]
+    fn test_signing_session() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+
+            // The root metadata needs the signatures of two custodians.
+            let session = RepoBuilder::create(&mut repo)
+                .offline_signing(true)
+                .trusted_targets_keys(&[&KEYS[1]])
+                .trusted_snapshot_keys(&[&KEYS[1]])
+                .trusted_timestamp_keys(&[&KEYS[1]])
+                .stage_root_with_builder(|builder| {
+                    builder
+                        .root_key(KEYS[0].public().clone())
+                        .root_key(KEYS[2].public().clone())
+                        .root_threshold(2)
+                })
+                .unwrap()
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot()
+                .unwrap()
+                .stage_timestamp()
+                .unwrap()
+                .signing_session()
+                .unwrap();
+            let mut saved = session.to_vec().unwrap();
+
+            // Each custodian restores the session, signs the root payload, and saves it again.
+            for key in [&KEYS[0], &KEYS[2]] {
+                let mut session = SigningSession::from_slice(&saved).unwrap();
+                let root_payload = session
+                    .payloads()
+                    .find(|payload| payload.role() == &MetadataPath::root())
+                    .unwrap()
+                    .clone();
+                let sig = key.sign(root_payload.payload()).unwrap();
+                session
+                    .add_signature(&MetadataPath::root(), key.public(), sig.value().as_bytes())
+                    .unwrap();
+                saved = session.to_vec().unwrap();
+            }
+
+            let session = SigningSession::from_slice(&saved).unwrap();
+            assert_eq!(
+                session.signers(&MetadataPath::root()),
+                vec![KEYS[0].public(), KEYS[2].public()]
+            );
+
+            // The coordinator resumes the session, and commits the metadata.
+            let metadata = RepoBuilder::create(&mut repo)
+                .resume_signing_session(&session)
+                .unwrap()
+                .commit()
+                .await
+                .unwrap();
+
+            let db = Database::from_trusted_metadata(&metadata).unwrap();
+            assert_eq!(db.trusted_root().version(), 1);
+            assert_eq!(db.trusted_timestamp().unwrap().version(), 1);
+        })
+    }
+
+    #[test]
+    fn test_commit_or_stage() {
+        block_on(async move {
+            let repo = EphemeralRepository::<Pouf1>::new();
+
+            // Only one of the two root keys is available.
+            let outcome = RepoBuilder::create(&repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[1]])
+                .trusted_snapshot_keys(&[&KEYS[1]])
+                .trusted_timestamp_keys(&[&KEYS[1]])
+                .stage_root_with_builder(|builder| {
+                    builder.root_key(KEYS[2].public().clone()).root_threshold(2)
+                })
+                .unwrap()
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot()
+                .unwrap()
+                .stage_timestamp()
+                .unwrap()
+                .commit_or_stage()
+                .await
+                .unwrap();
+            let unpublished = match outcome {
+                CommitOutcome::Unpublished(unpublished) => unpublished,
+                CommitOutcome::Published(_) => panic!("published below the root threshold"),
+            };
+            assert_eq!(unpublished.signature_reports().len(), 4);
+
+            let missing = unpublished.missing_signatures().collect::<Vec<_>>();
+            assert_eq!(missing.len(), 1);
+            assert_eq!(missing[0].role(), &MetadataPath::root());
+            assert_eq!(missing[0].signatures_needed(), 1);
+            assert_eq!(
+                missing[0].missing_key_ids(),
+                &hashset! {KEYS[2].public().key_id().clone()}
+            );
+
+            // Nothing was written to the repository.
+            assert!(repo
+                .fetch_metadata(&MetadataPath::root(), MetadataVersion::Number(1))
+                .await
+                .is_err());
+
+            // Collect the missing signature, and publish the metadata.
+            let mut session = unpublished.into_signing_session();
+            let root_payload = session
+                .payloads()
+                .find(|payload| payload.role() == &MetadataPath::root())
+                .unwrap()
+                .clone();
+            let sig = KEYS[2].sign(root_payload.payload()).unwrap();
+            session
+                .add_signature(
+                    &MetadataPath::root(),
+                    KEYS[2].public(),
+                    sig.value().as_bytes(),
+                )
+                .unwrap();
+
+            let outcome = RepoBuilder::create(&repo)
+                .resume_signing_session(&session)
+                .unwrap()
+                .commit_or_stage()
+                .await
+                .unwrap();
+            let metadata = match outcome {
+                CommitOutcome::Published(metadata) => metadata,
+                CommitOutcome::Unpublished(_) => panic!("every threshold was met"),
+            };
+
+            let db = Database::from_trusted_metadata(&metadata).unwrap();
+            assert_eq!(db.trusted_root().version(), 1);
+        })
+    }
+
+    #[test]
+    fn test_signing_session_errors() {
+        let mut repo = EphemeralRepository::<Pouf1>::new();
+        let mut session = RepoBuilder::create(&mut repo)
+            .offline_signing(true)
+            .trusted_targets_keys(&[&KEYS[1]])
+            .trusted_snapshot_keys(&[&KEYS[1]])
+            .trusted_timestamp_keys(&[&KEYS[1]])
+            .stage_root_with_builder(|builder| builder.root_key(KEYS[0].public().clone()))
+            .unwrap()
+            .stage_targets()
+            .unwrap()
+            .stage_snapshot()
+            .unwrap()
+            .stage_timestamp()
+            .unwrap()
+            .signing_session()
+            .unwrap();
+
+        let payloads = session.payloads().cloned().collect::<Vec<_>>();
+        assert_eq!(
+            payloads
+                .iter()
+                .map(|payload| payload.role().as_str())
+                .collect::<Vec<_>>(),
+            vec!["root", "targets", "snapshot", "timestamp"]
+        );
+
+        // A signature over another payload is rejected.
+        let bad_sig = KEYS[0].sign(payloads[1].payload()).unwrap();
+        assert_matches!(
+            session.add_signature(
+                &MetadataPath::root(),
+                KEYS[0].public(),
+                bad_sig.value().as_bytes()
+            ),
+            Err(Error::BadSignature(_))
+        );
+        assert!(session.signers(&MetadataPath::root()).is_empty());
+
+        // Only staged metadata can be signed.
+        let role = MetadataPath::new("delegated").unwrap();
+        let sig = KEYS[0].sign(payloads[0].payload()).unwrap();
+        assert_matches!(
+            session.add_signature(&role, KEYS[0].public(), sig.value().as_bytes()),
+            Err(Error::IllegalArgument(_))
+        );
+
+        // A session that isn't valid can't be restored.
+        assert_matches!(SigningSession::from_slice(b"{}"), Err(_));
+    }
+
+    #[test]
+    fn test_offline_signing_errors() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+
+            // Committing fails until the root metadata is signed.
+            assert!(RepoBuilder::create(&mut repo)
+                .offline_signing(true)
+                .trusted_targets_keys(&[&KEYS[1]])
+                .trusted_snapshot_keys(&[&KEYS[1]])
+                .trusted_timestamp_keys(&[&KEYS[1]])
+                .stage_root_with_builder(|builder| builder.root_key(KEYS[0].public().clone()))
+                .unwrap()
+                .commit()
+                .await
+                .is_err());
 
-            let metadata2 = RepoBuilder::from_database(&mut repo, &database)
+            // Signatures can't be attached to metadata that is described by its length.
+            let builder = RepoBuilder::create(&mut repo)
                 .trusted_root_keys(&[&KEYS[0]])
                 .trusted_targets_keys(&[&KEYS[0]])
                 .trusted_snapshot_keys(&[&KEYS[0]])
                 .trusted_timestamp_keys(&[&KEYS[0]])
                 .stage_root()
                 .unwrap()
-                .target_hash_algorithms(hash_algs)
-                .add_target(target_path2.clone(), Cursor::new(target_file2))
-                .await
-                .unwrap()
-                .add_delegation_role(delegation2.clone())
-                .stage_targets_with_builder(|b| b.expires(expires))
+                .stage_targets()
                 .unwrap()
-                .stage_snapshot_with_builder(|builder| {
-                    builder.insert_metadata_description(
-                        delegation_path.clone(),
-                        MetadataDescription::from_slice(
-                            raw_delegated_targets.as_bytes(),
-                            1,
-                            &[HashAlgorithm::Sha256],
-                        )
-                        .unwrap(),
-                    )
-                })
+                .stage_snapshot()
                 .unwrap()
-                .commit()
-                .await
+                .timestamp_includes_length(true)
+                .stage_timestamp()
                 .unwrap();
-
-            database.update_metadata(&metadata2).unwrap();
-
-            assert_eq!(
-                &**database.trusted_targets().unwrap(),
-                &TargetsMetadataBuilder::new()
-                    .version(2)
-                    .expires(expires)
-                    .insert_target_from_slice(target_path1.clone(), target_file1, hash_algs)
-                    .unwrap()
-                    .insert_target_from_slice(target_path2.clone(), target_file2, hash_algs)
-                    .unwrap()
-                    .delegations(
-                        DelegationsBuilder::new()
-                            .key(delegation_key.public().clone())
-                            .role(delegation1)
-                            .role(delegation2)
-                            .build()
-                            .unwrap()
+            let payloads = builder.export_unsigned().unwrap();
+            let sig = KEYS[1].sign(payloads[2].payload()).unwrap();
+            assert_matches!(
+                builder
+                    .import_signatures(
+                        &MetadataPath::snapshot(),
+                        &[(KEYS[1].public(), sig.value().as_bytes())],
                     )
-                    .build()
-                    .unwrap()
-            )
+                    .err(),
+                Some(Error::IllegalArgument(_))
+            );
         })
     }
 
     #[test]
-    fn test_builder_rotating_keys_refreshes_metadata() {
+    fn test_refresh() {
         block_on(async move {
             let mut repo = EphemeralRepository::<Pouf1>::new();
 
@@ -2756,107 +6482,73 @@ mod tests {
                 .trusted_targets_keys(&[&KEYS[0]])
                 .trusted_snapshot_keys(&[&KEYS[0]])
                 .trusted_timestamp_keys(&[&KEYS[0]])
+                .add_target(TargetPath::new("foo").unwrap(), Cursor::new(b"foo file"))
+                .await
+                .unwrap()
                 .commit()
                 .await
                 .unwrap();
 
             let mut db = Database::from_trusted_metadata(&metadata1).unwrap();
 
-            // Because of [update-root], rotating any root keys should make a new timestamp and
-            // snapshot.
-            //
-            // FIXME(#297): This also purges targets, even though that's not conforming to the spec.
-            //
-            // [update-root]: https://theupdateframework.github.io/specification/v1.0.30/#update-root
+            // Refreshing the timestamp only stages a new timestamp.
             let metadata2 = RepoBuilder::from_database(&mut repo, &db)
                 .trusted_root_keys(&[&KEYS[0]])
                 .trusted_targets_keys(&[&KEYS[0]])
                 .trusted_snapshot_keys(&[&KEYS[0]])
-                .trusted_timestamp_keys(&[&KEYS[1]])
-                .commit()
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .refresh(&[Role::Timestamp])
                 .await
                 .unwrap();
 
-            assert!(metadata2.root().is_some());
-            assert!(metadata2.targets().is_some());
-            assert!(metadata2.snapshot().is_some());
+            assert!(metadata2.root().is_none());
+            assert!(metadata2.targets().is_none());
+            assert!(metadata2.snapshot().is_none());
             assert!(metadata2.timestamp().is_some());
 
             db.update_metadata(&metadata2).unwrap();
-
-            assert_eq!(db.trusted_root().version(), 2);
-            assert_eq!(db.trusted_targets().unwrap().version(), 2);
-            assert_eq!(db.trusted_snapshot().unwrap().version(), 2);
+            assert_eq!(db.trusted_snapshot().unwrap().version(), 1);
             assert_eq!(db.trusted_timestamp().unwrap().version(), 2);
 
-            // Note that rotating the timestamp keys purges all the metadata, so add it back in.
-
-            // Rotating the snapshot key should make new metadata.
+            // Refreshing the targets also refreshes the metadata that describes it.
             let metadata3 = RepoBuilder::from_database(&mut repo, &db)
                 .trusted_root_keys(&[&KEYS[0]])
                 .trusted_targets_keys(&[&KEYS[0]])
-                .trusted_snapshot_keys(&[&KEYS[1]])
-                .trusted_timestamp_keys(&[&KEYS[1]])
-                .commit()
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .refresh(&[Role::Targets])
                 .await
                 .unwrap();
 
-            assert!(metadata3.root().is_some());
-            assert!(metadata2.targets().is_some());
-            assert!(metadata2.snapshot().is_some());
-            assert!(metadata2.timestamp().is_some());
+            assert!(metadata3.root().is_none());
+            assert!(metadata3.targets().is_some());
+            assert!(metadata3.snapshot().is_some());
+            assert!(metadata3.timestamp().is_some());
 
             db.update_metadata(&metadata3).unwrap();
-
-            assert_eq!(db.trusted_root().version(), 3);
-            assert_eq!(db.trusted_targets().unwrap().version(), 3);
-            assert_eq!(db.trusted_snapshot().unwrap().version(), 3);
+            assert_eq!(db.trusted_targets().unwrap().version(), 2);
+            assert_eq!(db.trusted_snapshot().unwrap().version(), 2);
             assert_eq!(db.trusted_timestamp().unwrap().version(), 3);
 
-            // Rotating the targets key should make a new targets, snapshot, and timestamp.
-            let metadata4 = RepoBuilder::from_database(&mut repo, &db)
-                .trusted_root_keys(&[&KEYS[0]])
-                .trusted_targets_keys(&[&KEYS[1]])
-                .trusted_snapshot_keys(&[&KEYS[1]])
-                .trusted_timestamp_keys(&[&KEYS[1]])
-                .commit()
-                .await
-                .unwrap();
-
-            assert!(metadata4.root().is_some());
-            assert!(metadata4.targets().is_some());
-            assert!(metadata4.snapshot().is_some());
-            assert!(metadata4.timestamp().is_some());
-
-            db.update_metadata(&metadata4).unwrap();
-
-            assert_eq!(db.trusted_root().version(), 4);
-            assert_eq!(db.trusted_targets().unwrap().version(), 4);
-            assert_eq!(db.trusted_snapshot().unwrap().version(), 4);
-            assert_eq!(db.trusted_timestamp().unwrap().version(), 4);
-
-            // Rotating the root key should make a new targets, snapshot, and timestamp.
-            let metadata5 = RepoBuilder::from_database(&mut repo, &db)
-                .signing_root_keys(&[&KEYS[0]])
-                .trusted_root_keys(&[&KEYS[1]])
-                .trusted_targets_keys(&[&KEYS[1]])
-                .trusted_snapshot_keys(&[&KEYS[1]])
-                .trusted_timestamp_keys(&[&KEYS[1]])
-                .commit()
-                .await
-                .unwrap();
-
-            assert!(metadata5.root().is_some());
-            assert!(metadata5.targets().is_some());
-            assert!(metadata5.snapshot().is_some());
-            assert!(metadata5.timestamp().is_some());
-
-            db.update_metadata(&metadata5).unwrap();
+            // The targets did not change.
+            assert_eq!(
+                db.trusted_targets().unwrap().targets(),
+                &hashmap! {
+                    TargetPath::new("foo").unwrap() =>
+                        TargetDescription::from_slice(b"foo file", &[HashAlgorithm::Sha256])
+                            .unwrap(),
+                }
+            );
 
-            assert_eq!(db.trusted_root().version(), 5);
-            assert_eq!(db.trusted_targets().unwrap().version(), 5);
-            assert_eq!(db.trusted_snapshot().unwrap().version(), 5);
-            assert_eq!(db.trusted_timestamp().unwrap().version(), 5);
+            // Metadata can only be refreshed in an existing repository.
+            assert_matches!(
+                RepoBuilder::create(&mut repo)
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .refresh(&[Role::Timestamp])
+                    .await
+                    .err(),
+                Some(Error::IllegalArgument(_))
+            );
         })
     }
 
@@ -3041,6 +6733,79 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_expiration_durations() {
+        block_on(async move {
+            let now = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+            let durations = ExpirationDurations::new(
+                Duration::days(100),
+                Duration::days(30),
+                Duration::days(3),
+                Duration::hours(6),
+            );
+
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            let metadata = RepoBuilder::create(&mut repo)
+                .current_time(now)
+                .expiration_durations(durations)
+                .require_expiration_ordering(true)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+
+            let db = Database::from_trusted_metadata_with_start_time(&metadata, &now).unwrap();
+            assert_eq!(db.trusted_root().expires(), &(now + Duration::days(100)));
+            assert_eq!(
+                db.trusted_targets().unwrap().expires(),
+                &(now + Duration::days(30))
+            );
+            assert_eq!(
+                db.trusted_snapshot().unwrap().expires(),
+                &(now + Duration::days(3))
+            );
+            assert_eq!(
+                db.trusted_timestamp().unwrap().expires(),
+                &(now + Duration::hours(6))
+            );
+
+            // A timestamp that outlives the snapshot is rejected if the ordering is required.
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+            assert_matches!(
+                RepoBuilder::create(&mut repo)
+                    .current_time(now)
+                    .timestamp_expiration_duration(Duration::days(30))
+                    .require_expiration_ordering(true)
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .trusted_targets_keys(&[&KEYS[0]])
+                    .trusted_snapshot_keys(&[&KEYS[0]])
+                    .trusted_timestamp_keys(&[&KEYS[0]])
+                    .commit()
+                    .await
+                    .err(),
+                Some(Error::IllegalArgument(_))
+            );
+        })
+    }
+
+    #[test]
+    fn test_expiration_durations_check_ordering() {
+        ExpirationDurations::default().check_ordering().unwrap();
+        assert_matches!(
+            ExpirationDurations::new(
+                Duration::days(365),
+                Duration::days(7),
+                Duration::days(90),
+                Duration::days(1),
+            )
+            .check_ordering(),
+            Err(Error::IllegalArgument(_))
+        );
+    }
+
     #[test]
     fn test_time_versioning() {
         block_on(async move {
@@ -3171,6 +6936,119 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_pretty_print() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+
+            let metadata = RepoBuilder::create(&mut repo)
+                .pretty_print(true)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .add_target(
+                    TargetPath::new("foo").unwrap(),
+                    Cursor::new(b"foo".to_vec()),
+                )
+                .await
+                .unwrap()
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot()
+                .unwrap()
+                .timestamp_includes_length(true)
+                .commit()
+                .await
+                .unwrap();
+
+            let raw_root = metadata.root().unwrap();
+            assert!(raw_root.as_bytes().starts_with(b"{\n  \"signatures\": [\n"));
+            assert_eq!(
+                raw_root.as_bytes(),
+                raw_root
+                    .parse_untrusted()
+                    .unwrap()
+                    .to_raw_pretty()
+                    .unwrap()
+                    .as_bytes()
+            );
+
+            // The signatures are over the canonical form, and the snapshot and timestamp describe
+            // the stored bytes.
+            let db = Database::from_trusted_metadata(&metadata).unwrap();
+            assert_eq!(
+                metadata.snapshot().unwrap().as_bytes().len(),
+                db.trusted_timestamp().unwrap().snapshot().length().unwrap()
+            );
+            assert!(db
+                .target_description(&TargetPath::new("foo").unwrap())
+                .is_ok());
+        })
+    }
+
+    #[test]
+    fn test_metadata_padding() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Pouf1>::new();
+
+            let metadata = RepoBuilder::create(&mut repo)
+                .metadata_padding(Some(4096))
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap()
+                .stage_targets()
+                .unwrap()
+                .stage_snapshot()
+                .unwrap()
+                .timestamp_includes_length(true)
+                .commit()
+                .await
+                .unwrap();
+
+            // Only the snapshot and timestamp metadata are padded.
+            assert_eq!(metadata.snapshot().unwrap().as_bytes().len(), 4096);
+            assert_eq!(metadata.timestamp().unwrap().as_bytes().len(), 4096);
+            assert!(metadata.root().unwrap().as_bytes().len() < 4096);
+            assert!(metadata.targets().unwrap().as_bytes().len() < 4096);
+
+            let db = Database::from_trusted_metadata(&metadata).unwrap();
+            assert_eq!(
+                db.trusted_timestamp().unwrap().snapshot().length(),
+                Some(4096)
+            );
+            assert_eq!(db.trusted_snapshot().unwrap().version(), 1);
+        })
+    }
+
+    #[test]
+    fn test_metadata_padding_requires_pouf_support() {
+        block_on(async move {
+            let mut repo = EphemeralRepository::<Der>::new();
+
+            assert_matches!(
+                RepoBuilder::create(&mut repo)
+                    .metadata_padding(Some(4096))
+                    .trusted_root_keys(&[&KEYS[0]])
+                    .trusted_targets_keys(&[&KEYS[0]])
+                    .trusted_snapshot_keys(&[&KEYS[0]])
+                    .trusted_timestamp_keys(&[&KEYS[0]])
+                    .stage_root()
+                    .unwrap()
+                    .stage_targets()
+                    .unwrap()
+                    .stage_snapshot()
+                    .err(),
+                Some(Error::IllegalArgument(_))
+            );
+        })
+    }
+
     #[test]
     fn test_builder_errs_if_no_keys() {
         block_on(async move {
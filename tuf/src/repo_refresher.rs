@@ -0,0 +1,399 @@
+//! Periodically refresh the snapshot and timestamp metadata of a repository in a background task.
+//!
+//! The snapshot and timestamp metadata expire quickly, so repositories typically keep their keys
+//! online and re-sign this metadata on a schedule. A [RepoRefresher] moves that loop into a task
+//! on the current [Tokio](tokio) runtime, which calls [RepoBuilder::refresh] on an interval and
+//! publishes the health of each refresh to a [RepoRefresherHandle].
+
+use chrono::{offset::Utc, DateTime, Duration};
+use futures_util::future::{self, Either};
+use ring::rand::SystemRandom;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+use tokio::runtime::Handle;
+use tokio::sync::{watch, Notify};
+use tokio::task::JoinHandle;
+
+use crate::crypto::PrivateKey;
+use crate::error::{Error, Result};
+use crate::metadata::{RawSignedMetadata, RawSignedMetadataSet, Role, RootMetadata};
+use crate::pouf::Pouf;
+use crate::repo_builder::{RepoBuilder, DEFAULT_SNAPSHOT_EXPIRATION, DEFAULT_TIMESTAMP_EXPIRATION};
+use crate::repository::{RepositoryProvider, RepositoryStorage};
+use crate::util::random_jitter;
+
+/// A private key that can be moved into the background task.
+pub type RefresherKey = Box<dyn PrivateKey + Send + Sync>;
+
+/// Re-signs the snapshot and timestamp metadata of a repository on a schedule.
+///
+/// Every refresh reads the repository with [RepoBuilder::from_repo], starting from the trusted
+/// root, and then calls [RepoBuilder::refresh] for the snapshot and timestamp roles, so any
+/// targets metadata that was published in the meantime is picked up by the new snapshot.
+pub struct RepoRefresher<D, R>
+where
+    D: Pouf,
+{
+    repo: R,
+    root: RawSignedMetadata<D, RootMetadata>,
+    snapshot_keys: Vec<RefresherKey>,
+    timestamp_keys: Vec<RefresherKey>,
+    snapshot_expiration_duration: Duration,
+    timestamp_expiration_duration: Duration,
+    interval: StdDuration,
+    jitter: StdDuration,
+}
+
+impl<D, R> RepoRefresher<D, R>
+where
+    D: Pouf + Send + Sync + 'static,
+    R: RepositoryStorage<D> + RepositoryProvider<D> + Send + Sync + 'static,
+{
+    /// Create a [RepoRefresher] that signs the snapshot and timestamp metadata of `repo` with the
+    /// `snapshot_keys` and `timestamp_keys`, which must be trusted by the root metadata.
+    ///
+    /// By default, the metadata is refreshed every hour plus a random delay of up to a minute,
+    /// and it expires with the defaults of [RepoBuilder].
+    pub fn new(
+        repo: R,
+        root: RawSignedMetadata<D, RootMetadata>,
+        snapshot_keys: Vec<RefresherKey>,
+        timestamp_keys: Vec<RefresherKey>,
+    ) -> Self {
+        Self {
+            repo,
+            root,
+            snapshot_keys,
+            timestamp_keys,
+            snapshot_expiration_duration: DEFAULT_SNAPSHOT_EXPIRATION,
+            timestamp_expiration_duration: DEFAULT_TIMESTAMP_EXPIRATION,
+            interval: StdDuration::from_secs(60 * 60),
+            jitter: StdDuration::from_secs(60),
+        }
+    }
+
+    /// Refresh the metadata every `interval`, plus a random delay of up to `jitter`. The jitter
+    /// spreads out the load when many repositories are refreshed by the same service.
+    ///
+    /// The interval should be well below the timestamp expiration duration, so that a few failed
+    /// refreshes in a row don't expire the repository.
+    pub fn interval(mut self, interval: StdDuration, jitter: StdDuration) -> Self {
+        self.interval = interval;
+        self.jitter = jitter;
+        self
+    }
+
+    /// Set how long the refreshed snapshot metadata is valid for.
+    ///
+    /// See [RepoBuilder::snapshot_expiration_duration].
+    pub fn snapshot_expiration_duration(mut self, duration: Duration) -> Self {
+        self.snapshot_expiration_duration = duration;
+        self
+    }
+
+    /// Set how long the refreshed timestamp metadata is valid for.
+    ///
+    /// See [RepoBuilder::timestamp_expiration_duration].
+    pub fn timestamp_expiration_duration(mut self, duration: Duration) -> Self {
+        self.timestamp_expiration_duration = duration;
+        self
+    }
+
+    /// Refresh the snapshot and timestamp metadata once, and return the metadata that was
+    /// written to the repository.
+    pub async fn refresh(&self) -> Result<RawSignedMetadataSet<D>> {
+        let snapshot_keys = self
+            .snapshot_keys
+            .iter()
+            .map(|key| &**key as &dyn PrivateKey)
+            .collect::<Vec<_>>();
+        let timestamp_keys = self
+            .timestamp_keys
+            .iter()
+            .map(|key| &**key as &dyn PrivateKey)
+            .collect::<Vec<_>>();
+
+        RepoBuilder::from_repo(&self.repo, &self.root)
+            .await?
+            .trusted_snapshot_keys(&snapshot_keys)
+            .trusted_timestamp_keys(&timestamp_keys)
+            .snapshot_expiration_duration(self.snapshot_expiration_duration)
+            .timestamp_expiration_duration(self.timestamp_expiration_duration)
+            .refresh(&[Role::Snapshot, Role::Timestamp])
+            .await
+    }
+
+    /// Move the refresher into a background task that refreshes the metadata immediately, and
+    /// then again on the configured interval. A failed refresh is retried on the same schedule.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of a Tokio runtime.
+    pub fn spawn(self) -> RepoRefresherHandle<D, R> {
+        let refresher = Arc::new(self);
+        let stop = Arc::new(Notify::new());
+        let (sender, receiver) = watch::channel(None);
+
+        let task = tokio::spawn(refresh_loop(
+            Arc::clone(&refresher),
+            Arc::clone(&stop),
+            sender,
+        ));
+
+        RepoRefresherHandle {
+            refresher,
+            stop,
+            receiver,
+            task,
+        }
+    }
+}
+
+async fn refresh_loop<D, R>(
+    refresher: Arc<RepoRefresher<D, R>>,
+    stop: Arc<Notify>,
+    sender: watch::Sender<Option<Arc<RefreshStatus>>>,
+) where
+    D: Pouf + Send + Sync + 'static,
+    R: RepositoryStorage<D> + RepositoryProvider<D> + Send + Sync + 'static,
+{
+    let rng = SystemRandom::new();
+    let mut last_success = None;
+    let mut consecutive_failures = 0;
+
+    loop {
+        // The builder borrows the signing keys as `&dyn PrivateKey`, so its future is not `Send`
+        // and is driven on the blocking pool instead of a worker thread.
+        let task_refresher = Arc::clone(&refresher);
+        let result = match tokio::task::spawn_blocking(move || {
+            Handle::current().block_on(task_refresher.refresh())
+        })
+        .await
+        {
+            Ok(result) => result.map(|_| ()),
+            Err(err) => Err(Error::Opaque(format!("refresh task failed: {}", err))),
+        };
+
+        let finished_at = Utc::now();
+        if result.is_ok() {
+            last_success = Some(finished_at);
+            consecutive_failures = 0;
+        } else {
+            consecutive_failures += 1;
+        }
+
+        // Stop once the handle and all of the subscribed receivers have been dropped.
+        if sender
+            .send(Some(Arc::new(RefreshStatus {
+                finished_at,
+                result,
+                last_success,
+                consecutive_failures,
+            })))
+            .is_err()
+        {
+            return;
+        }
+
+        let delay = refresher.interval + random_jitter(&rng, refresher.jitter);
+        let sleep = tokio::time::sleep(delay);
+        let stopped = stop.notified();
+        futures_util::pin_mut!(sleep, stopped);
+
+        if let Either::Right(_) = future::select(sleep, stopped).await {
+            return;
+        }
+    }
+}
+
+/// The health of a [RepoRefresher], as of its most recent refresh.
+#[derive(Debug)]
+pub struct RefreshStatus {
+    finished_at: DateTime<Utc>,
+    result: Result<()>,
+    last_success: Option<DateTime<Utc>>,
+    consecutive_failures: u32,
+}
+
+impl RefreshStatus {
+    /// When the most recent refresh finished.
+    pub fn finished_at(&self) -> &DateTime<Utc> {
+        &self.finished_at
+    }
+
+    /// The result of the most recent refresh.
+    pub fn result(&self) -> &Result<()> {
+        &self.result
+    }
+
+    /// When the metadata was last refreshed successfully, if ever.
+    pub fn last_success(&self) -> Option<&DateTime<Utc>> {
+        self.last_success.as_ref()
+    }
+
+    /// How many refreshes failed in a row, which is zero if the most recent refresh succeeded.
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    /// Whether the most recent refresh succeeded.
+    pub fn is_healthy(&self) -> bool {
+        self.consecutive_failures == 0
+    }
+}
+
+/// A handle to a [RepoRefresher] that is running in the background, from [RepoRefresher::spawn].
+///
+/// Dropping the handle, along with any receivers returned by [RepoRefresherHandle::subscribe],
+/// stops the background task after its next refresh.
+pub struct RepoRefresherHandle<D, R>
+where
+    D: Pouf,
+{
+    refresher: Arc<RepoRefresher<D, R>>,
+    stop: Arc<Notify>,
+    receiver: watch::Receiver<Option<Arc<RefreshStatus>>>,
+    task: JoinHandle<()>,
+}
+
+impl<D, R> RepoRefresherHandle<D, R>
+where
+    D: Pouf,
+{
+    /// The health as of the most recent refresh, or `None` if the first refresh has not finished
+    /// yet.
+    pub fn latest(&self) -> Option<Arc<RefreshStatus>> {
+        self.receiver.borrow().clone()
+    }
+
+    /// Subscribe to the health of every refresh. The receiver is notified each time a refresh
+    /// finishes.
+    pub fn subscribe(&self) -> watch::Receiver<Option<Arc<RefreshStatus>>> {
+        self.receiver.clone()
+    }
+
+    /// Stop the background task and return the refresher. This waits for the refresh in
+    /// progress, if any, to finish, so the metadata is never partially written.
+    pub async fn stop(self) -> RepoRefresher<D, R> {
+        self.stop.notify_one();
+        let _ = self.task.await;
+
+        match Arc::try_unwrap(self.refresher) {
+            Ok(refresher) => refresher,
+            Err(_) => unreachable!("the refresh task has exited"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::{Client, Config};
+    use crate::crypto::Ed25519PrivateKey;
+    use crate::metadata::Metadata;
+    use crate::pouf::Pouf1;
+    use crate::repository::EphemeralRepository;
+    use assert_matches::assert_matches;
+    use lazy_static::lazy_static;
+
+    const KEY_BYTES: &[&[u8]] = &[
+        include_bytes!("../tests/ed25519/ed25519-1.pk8.der"),
+        include_bytes!("../tests/ed25519/ed25519-2.pk8.der"),
+    ];
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = KEY_BYTES
+            .iter()
+            .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+            .collect();
+    }
+
+    fn key(index: usize) -> RefresherKey {
+        Box::new(Ed25519PrivateKey::from_pkcs8(KEY_BYTES[index]).unwrap())
+    }
+
+    #[test]
+    fn refresher_republishes_snapshot_and_timestamp() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let repo = Arc::new(EphemeralRepository::<Pouf1>::new());
+
+            let metadata = RepoBuilder::create(&*repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+            let root = metadata.root().unwrap().clone();
+
+            let handle =
+                RepoRefresher::new(Arc::clone(&repo), root.clone(), vec![key(0)], vec![key(0)])
+                    .interval(StdDuration::from_secs(3600), StdDuration::from_secs(60))
+                    .spawn();
+
+            let mut receiver = handle.subscribe();
+            receiver.changed().await.unwrap();
+            let latest = handle.latest().unwrap();
+            assert_matches!(latest.result(), Ok(()));
+            assert!(latest.is_healthy());
+            assert_eq!(latest.last_success(), Some(latest.finished_at()));
+
+            handle.stop().await;
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                &root,
+                EphemeralRepository::new(),
+                repo,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
+
+            assert_eq!(client.database().trusted_targets().unwrap().version(), 1);
+            assert_eq!(client.database().trusted_snapshot().unwrap().version(), 2);
+            assert_eq!(client.database().trusted_timestamp().unwrap().version(), 2);
+        });
+    }
+
+    #[test]
+    fn refresher_reports_failures() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let repo = EphemeralRepository::<Pouf1>::new();
+
+            let metadata = RepoBuilder::create(&repo)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .commit()
+                .await
+                .unwrap();
+            let root = metadata.root().unwrap().clone();
+
+            // The root doesn't trust this snapshot key.
+            let handle = RepoRefresher::new(repo, root, vec![key(1)], vec![key(0)]).spawn();
+
+            let mut receiver = handle.subscribe();
+            receiver.changed().await.unwrap();
+            let latest = handle.latest().unwrap();
+            assert_matches!(latest.result(), Err(_));
+            assert!(!latest.is_healthy());
+            assert_eq!(latest.consecutive_failures(), 1);
+            assert_eq!(latest.last_success(), None);
+
+            handle.stop().await;
+        });
+    }
+}
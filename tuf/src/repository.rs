@@ -1,6 +1,8 @@
 //! Interfaces for interacting with different types of TUF repositories.
 
+use crate::cancel::{CancellableRead, CancellationToken};
 use crate::crypto::{self, HashAlgorithm, HashValue};
+use crate::merkle::SnapshotMerkleProof;
 use crate::metadata::{
     Metadata, MetadataPath, MetadataVersion, RawSignedMetadata, TargetDescription, TargetPath,
 };
@@ -9,12 +11,14 @@ use crate::util::SafeAsyncRead;
 use crate::{Error, Result};
 
 use futures_io::AsyncRead;
-use futures_util::future::BoxFuture;
+use futures_util::future::{self, BoxFuture, FutureExt as _};
 use futures_util::io::AsyncReadExt;
+use log::warn;
 use std::marker::PhantomData;
 use std::sync::Arc;
 
 mod file_system;
+pub(crate) use self::file_system::create_temp_file;
 pub use self::file_system::{
     FileSystemBatchUpdate, FileSystemRepository, FileSystemRepositoryBuilder,
 };
@@ -23,11 +27,20 @@ pub use self::file_system::{
 mod http;
 
 #[cfg(feature = "hyper")]
-pub use self::http::{HttpRepository, HttpRepositoryBuilder};
+pub use self::http::{HttpRepository, HttpRepositoryBuilder, TargetUriSigner};
 
 mod ephemeral;
 pub use self::ephemeral::{EphemeralBatchUpdate, EphemeralRepository};
 
+mod layout;
+pub use self::layout::{RepositoryLayout, RoleNameMapping};
+
+mod metadata_cache;
+pub use self::metadata_cache::MetadataCacheRepository;
+
+mod mirrors;
+pub use self::mirrors::MirroredRepository;
+
 #[cfg(test)]
 mod error_repo;
 #[cfg(test)]
@@ -71,6 +84,38 @@ where
         &'a self,
         target_path: &TargetPath,
     ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>>;
+
+    /// Fetch the given target, which the trusted metadata describes as being `length` bytes long.
+    ///
+    /// This defaults to [`RepositoryProvider::fetch_target`]. Implementations that need the
+    /// expected length to issue the request (for example, to sign a URL for a private CDN) may
+    /// override it. As with `fetch_target`, [`Client`][Client] will verify the length itself.
+    ///
+    /// [Client]: crate::client::Client
+    fn fetch_target_with_length<'a>(
+        &'a self,
+        target_path: &TargetPath,
+        length: u64,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let _ = length;
+        self.fetch_target(target_path)
+    }
+
+    /// Fetch the TAP 16 snapshot Merkle proof of `role`. See the [merkle](crate::merkle) module
+    /// for details. Proofs are stored apart from metadata, so the proof of one role can't be
+    /// mistaken for the metadata of another.
+    ///
+    /// This defaults to an error, for repositories that don't publish snapshot Merkle proofs.
+    fn fetch_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let _ = role;
+        future::ready(Err(Error::Opaque(
+            "repository does not support snapshot merkle proofs".into(),
+        )))
+        .boxed()
+    }
 }
 
 /// Test helper to help read a metadata file from a repository into a string.
@@ -130,6 +175,46 @@ where
         target_path: &TargetPath,
         target: &'a mut (dyn AsyncRead + Send + Unpin),
     ) -> BoxFuture<'a, Result<()>>;
+
+    /// Remove the target at `target_path`, such as after it was removed from the targets
+    /// metadata. Removing a target that doesn't exist succeeds.
+    ///
+    /// This defaults to an error, for repositories that can't remove targets.
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+        let _ = target_path;
+        future::ready(Err(Error::Opaque(
+            "repository does not support removing targets".into(),
+        )))
+        .boxed()
+    }
+
+    /// List the paths of every target stored in the repository, as they were passed to
+    /// [RepositoryStorage::store_target]. This is used to find the targets that are no longer
+    /// referenced by any metadata, see [prune_targets](crate::prune::prune_targets).
+    ///
+    /// This defaults to an error, for repositories that can't list their targets.
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+        future::ready(Err(Error::Opaque(
+            "repository does not support listing targets".into(),
+        )))
+        .boxed()
+    }
+
+    /// Store the TAP 16 snapshot Merkle `proof` of `role`, overwriting any existing proof of that
+    /// role. See [RepositoryProvider::fetch_snapshot_merkle_proof].
+    ///
+    /// This defaults to an error, for repositories that can't store snapshot Merkle proofs.
+    fn store_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+        proof: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        let _ = (role, proof);
+        future::ready(Err(Error::Opaque(
+            "repository does not support snapshot merkle proofs".into(),
+        )))
+        .boxed()
+    }
 }
 
 /// A subtrait of both RepositoryStorage and RepositoryProvider. This is useful to create
@@ -166,6 +251,21 @@ macro_rules! impl_provider {
             ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
                 (**self).fetch_target(target_path)
             }
+
+            fn fetch_target_with_length<'a>(
+                &'a self,
+                target_path: &TargetPath,
+                length: u64,
+            ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+                (**self).fetch_target_with_length(target_path, length)
+            }
+
+            fn fetch_snapshot_merkle_proof<'a>(
+                &'a self,
+                role: &MetadataPath,
+            ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+                (**self).fetch_snapshot_merkle_proof(role)
+            }
         }
     };
 }
@@ -196,6 +296,22 @@ macro_rules! impl_storage {
             ) -> BoxFuture<'a, Result<()>> {
                 (**self).store_target(target_path, target)
             }
+
+            fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+                (**self).remove_target(target_path)
+            }
+
+            fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+                (**self).list_targets()
+            }
+
+            fn store_snapshot_merkle_proof<'a>(
+                &'a self,
+                role: &MetadataPath,
+                proof: &'a mut (dyn AsyncRead + Send + Unpin),
+            ) -> BoxFuture<'a, Result<()>> {
+                (**self).store_snapshot_merkle_proof(role, proof)
+            }
         }
     };
 }
@@ -210,6 +326,10 @@ impl_storage!(<D: Pouf, T: RepositoryStorage<D> + ?Sized> RepositoryStorage<D> f
 #[derive(Debug, Clone)]
 pub(crate) struct Repository<R, D> {
     repository: R,
+    cancel: Option<CancellationToken>,
+    max_retries: u32,
+    hash_algorithms: Option<Vec<HashAlgorithm>>,
+    allow_unverifiable_targets: bool,
     _pouf: PhantomData<D>,
 }
 
@@ -218,10 +338,36 @@ impl<R, D> Repository<R, D> {
     pub(crate) fn new(repository: R) -> Self {
         Self {
             repository,
+            cancel: None,
+            max_retries: 0,
+            hash_algorithms: None,
+            allow_unverifiable_targets: false,
             _pouf: PhantomData,
         }
     }
 
+    /// Abort fetches from this repository with [Error::Cancelled] once `cancel` is cancelled.
+    pub(crate) fn set_cancellation_token(&mut self, cancel: Option<CancellationToken>) {
+        self.cancel = cancel;
+    }
+
+    /// Retry fetches from this repository that fail with a transient error up to `max_retries`
+    /// times.
+    pub(crate) fn set_max_retries(&mut self, max_retries: u32) {
+        self.max_retries = max_retries;
+    }
+
+    /// Only verify targets fetched from this repository with hashes of the given algorithms.
+    pub(crate) fn set_hash_algorithms(&mut self, hash_algorithms: Vec<HashAlgorithm>) {
+        self.hash_algorithms = Some(hash_algorithms);
+    }
+
+    /// Allow fetching targets whose descriptions are missing their length or hashes, which are
+    /// then only verified against whatever the descriptions do specify.
+    pub(crate) fn set_allow_unverifiable_targets(&mut self, allow_unverifiable_targets: bool) {
+        self.allow_unverifiable_targets = allow_unverifiable_targets;
+    }
+
     /// Perform a sanity check that `M`, `Role`, and `MetadataPath` all describe the same entity.
     fn check<M>(meta_path: &MetadataPath) -> Result<()>
     where
@@ -262,7 +408,20 @@ where
     /// `max_length` bytes. If `hash_data` is provided, this method will return and error if the
     /// hashed bytes of the metadata do not match `hash_data`.
     ///
+    /// The whole metadata is buffered before it is parsed, since the
+    /// [Database](crate::database::Database) verifies signatures over the raw bytes and keeps them
+    /// once the metadata is trusted. `max_length` is enforced as the bytes arrive.
+    ///
     /// [extension]: crate::pouf::Pouf::extension
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(path = %meta_path, version = %version, max_length, bytes),
+            err,
+        )
+    )]
     pub(crate) async fn fetch_metadata<'a, M>(
         &'a self,
         meta_path: &'a MetadataPath,
@@ -275,19 +434,104 @@ where
     {
         Self::check::<M>(meta_path)?;
 
+        #[cfg(feature = "tracing")]
+        if let Some(max_length) = max_length {
+            tracing::Span::current().record("max_length", max_length);
+        }
+
+        let buf = self
+            .fetch_metadata_bytes(meta_path, version, max_length, hashes)
+            .await?;
+
+        Ok(RawSignedMetadata::new(buf))
+    }
+
+    /// Fetch the TAP 16 snapshot Merkle proof of `role`, reading at most `max_length` bytes. See
+    /// the [merkle](crate::merkle) module for details.
+    pub(crate) async fn fetch_snapshot_merkle_proof(
+        &self,
+        role: &MetadataPath,
+        max_length: Option<usize>,
+    ) -> Result<SnapshotMerkleProof> {
+        let buf = self
+            .fetch_bytes(role, max_length, vec![], || {
+                self.repository.fetch_snapshot_merkle_proof(role)
+            })
+            .await?;
+
+        D::from_slice(&buf)
+    }
+
+    async fn fetch_metadata_bytes(
+        &self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+        max_length: Option<usize>,
+        hashes: Vec<(&'static HashAlgorithm, HashValue)>,
+    ) -> Result<Vec<u8>> {
+        self.fetch_bytes(meta_path, max_length, hashes, || {
+            self.repository.fetch_metadata(meta_path, version)
+        })
+        .await
+    }
+
+    /// Read the metadata of `meta_path`, or its snapshot Merkle proof, from the reader returned
+    /// by `fetch`, retrying transient failures.
+    async fn fetch_bytes<'a, F>(
+        &'a self,
+        meta_path: &MetadataPath,
+        max_length: Option<usize>,
+        hashes: Vec<(&'static HashAlgorithm, HashValue)>,
+        fetch: F,
+    ) -> Result<Vec<u8>>
+    where
+        F: Fn() -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>>,
+    {
+        let fetch = async {
+            let mut retries = 0;
+            loop {
+                match self
+                    .fetch_bytes_once(max_length, hashes.clone(), &fetch)
+                    .await
+                {
+                    Err(err) if retries < self.max_retries && is_transient(&err) => {
+                        retries += 1;
+                        warn!("retrying fetch of metadata {}: {}", meta_path, err);
+                    }
+                    res => return res,
+                }
+            }
+        };
+
+        match &self.cancel {
+            Some(cancel) => cancel.run(fetch).await,
+            None => fetch.await,
+        }
+    }
+
+    async fn fetch_bytes_once<'a, F>(
+        &'a self,
+        max_length: Option<usize>,
+        hashes: Vec<(&'static HashAlgorithm, HashValue)>,
+        fetch: &F,
+    ) -> Result<Vec<u8>>
+    where
+        F: Fn() -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>>,
+    {
         // Fetch the metadata, verifying max_length and hashes (if provided), as
         // the repository implementation should only be trusted to use those as
         // hints to fail early.
-        let mut reader = self
-            .repository
-            .fetch_metadata(meta_path, version)
+        let mut reader = fetch()
             .await?
             .check_length_and_hash(max_length.unwrap_or(usize::MAX) as u64, hashes)?;
 
         let mut buf = Vec::new();
         reader.read_to_end(&mut buf).await?;
 
-        Ok(RawSignedMetadata::new(buf))
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("bytes", buf.len());
+
+        Ok(buf)
     }
 
     /// Fetch the target identified by `target_path` through the returned `AsyncRead`, verifying
@@ -297,6 +541,19 @@ where
     ///
     /// It is **critical** that none of the bytes from the returned `AsyncRead` are used until it
     /// has been fully consumed as the data is untrusted.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                target = %target_path,
+                length = target_description.length(),
+                consistent_snapshot,
+            ),
+            err,
+        )
+    )]
     pub(crate) async fn fetch_target(
         &self,
         consistent_snapshot: bool,
@@ -307,9 +564,20 @@ where
         //
         // [...] download the target (up to the number of bytes specified in the targets metadata),
         // and verify that its hashes match the targets metadata.
+        if let Some(cancel) = &self.cancel {
+            cancel.check()?;
+        }
+
+        if !target_description.is_verifiable() && !self.allow_unverifiable_targets {
+            return Err(Error::UnverifiableTarget(target_path.clone()));
+        }
+
         let length = target_description.length();
-        let hashes = crypto::retain_supported_hashes(target_description.hashes());
-        if hashes.is_empty() {
+        let hashes = match &self.hash_algorithms {
+            Some(allowed) => crypto::retain_allowed_hashes(target_description.hashes(), allowed),
+            None => crypto::retain_supported_hashes(target_description.hashes()),
+        };
+        if hashes.is_empty() && !target_description.hashes().is_empty() {
             return Err(Error::NoSupportedHashAlgorithm);
         }
 
@@ -318,12 +586,12 @@ where
         // [...] If consistent snapshots are not used (see § 6.2 Consistent snapshots), then the
         // filename used to download the target file is of the fixed form FILENAME.EXT (e.g.,
         // foobar.tar.gz). Otherwise, the filename is of the form HASH.FILENAME.EXT [...]
-        let target = if consistent_snapshot {
+        let target = if consistent_snapshot && !hashes.is_empty() {
             let mut hashes = hashes.iter();
             loop {
                 if let Some((_, hash)) = hashes.next() {
                     let target_path = target_path.with_hash_prefix(hash)?;
-                    match self.repository.fetch_target(&target_path).await {
+                    match self.fetch_target_with_retries(&target_path, length).await {
                         Ok(target) => break target,
                         Err(Error::TargetNotFound(_)) => {}
                         Err(err) => return Err(err),
@@ -333,10 +601,54 @@ where
                 }
             }
         } else {
-            self.repository.fetch_target(target_path).await?
+            self.fetch_target_with_retries(target_path, length).await?
+        };
+
+        // A target without a trusted length can only be bounded by the maximum length.
+        let target = match length {
+            Some(length) => target.check_exact_length_and_hash(length, hashes)?,
+            None => target.check_length_and_hash(u64::MAX, hashes)?,
         };
 
-        target.check_length_and_hash(length, hashes)
+        Ok(CancellableRead::new(target, self.cancel.clone()))
+    }
+
+    async fn fetch_target_with_retries(
+        &self,
+        target_path: &TargetPath,
+        length: Option<u64>,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin + '_>> {
+        let mut retries = 0;
+        loop {
+            let res = match length {
+                Some(length) => {
+                    self.repository
+                        .fetch_target_with_length(target_path, length)
+                        .await
+                }
+                None => self.repository.fetch_target(target_path).await,
+            };
+            match res {
+                Err(err) if retries < self.max_retries && is_transient(&err) => {
+                    retries += 1;
+                    warn!("retrying fetch of target {}: {}", target_path, err);
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+/// Returns `true` if `err` may succeed when the request is retried, such as a dropped connection
+/// or a server error. Errors that a repository reports deliberately, like missing metadata, are
+/// never considered transient.
+fn is_transient(err: &Error) -> bool {
+    match err {
+        Error::Io(_) => true,
+        #[cfg(feature = "hyper")]
+        Error::Hyper { .. } => true,
+        Error::BadHttpStatus { code, .. } => code.is_server_error(),
+        _ => false,
     }
 }
 
@@ -365,6 +677,20 @@ where
             .await
     }
 
+    /// Store the TAP 16 snapshot Merkle proof of `role`, so it can be fetched with
+    /// [Repository::fetch_snapshot_merkle_proof].
+    pub(crate) async fn store_snapshot_merkle_proof(
+        &mut self,
+        role: &MetadataPath,
+        proof: &SnapshotMerkleProof,
+    ) -> Result<()> {
+        let buf = D::canonicalize(&D::serialize(proof)?)?;
+
+        self.repository
+            .store_snapshot_merkle_proof(role, &mut buf.as_slice())
+            .await
+    }
+
     /// Store the provided `target` in a location identified by `target_path`.
     pub async fn store_target<'a>(
         &'a mut self,
@@ -383,6 +709,8 @@ mod test {
     use crate::repository::EphemeralRepository;
     use assert_matches::assert_matches;
     use futures_executor::block_on;
+    use std::collections::HashMap;
+    use std::io;
 
     #[test]
     fn repository_forwards_not_found_error() {
@@ -572,6 +900,36 @@ mod test {
         })
     }
 
+    #[test]
+    fn repository_rejects_truncated_targets() {
+        block_on(async {
+            let repo = EphemeralRepository::new();
+            let mut client = Repository::<_, Pouf1>::new(repo);
+            client.set_allow_unverifiable_targets(true);
+
+            // Without hashes, only the length can catch a target that was cut short.
+            let data: &[u8] = b"like tears in the rain";
+            let target_description =
+                TargetDescription::new(Some(data.len() as u64), HashMap::new(), HashMap::new())
+                    .unwrap();
+            let path = TargetPath::new("batty").unwrap();
+            client
+                .store_target(&path, &mut &data[..data.len() - 1])
+                .await
+                .unwrap();
+
+            let mut read = client
+                .fetch_target(false, &path, target_description)
+                .await
+                .unwrap();
+            let mut buf = Vec::new();
+            assert_matches!(
+                read.read_to_end(&mut buf).await,
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof
+            );
+        })
+    }
+
     #[test]
     fn repository_takes_trait_objects() {
         block_on(async {
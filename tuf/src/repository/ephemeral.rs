@@ -14,7 +14,7 @@ use {
         io::{AsyncReadExt, Cursor},
     },
     std::{
-        collections::HashMap,
+        collections::{HashMap, HashSet},
         marker::PhantomData,
         sync::{Arc, RwLock},
     },
@@ -29,12 +29,14 @@ pub struct EphemeralRepository<D> {
 
 type MetadataMap = HashMap<(MetadataPath, MetadataVersion), Arc<[u8]>>;
 type TargetsMap = HashMap<TargetPath, Arc<[u8]>>;
+type SnapshotMerkleProofsMap = HashMap<MetadataPath, Arc<[u8]>>;
 
 #[derive(Debug, Default)]
 struct Inner {
     version: u64,
     metadata: MetadataMap,
     targets: TargetsMap,
+    snapshot_merkle_proofs: SnapshotMerkleProofsMap,
 }
 
 impl<D> EphemeralRepository<D>
@@ -48,6 +50,7 @@ where
                 version: 0,
                 metadata: MetadataMap::new(),
                 targets: TargetsMap::new(),
+                snapshot_merkle_proofs: SnapshotMerkleProofsMap::new(),
             }),
             _pouf: PhantomData,
         }
@@ -63,7 +66,9 @@ where
                 version: 0,
                 metadata: MetadataMap::new(),
                 targets: TargetsMap::new(),
+                snapshot_merkle_proofs: SnapshotMerkleProofsMap::new(),
             }),
+            removed_targets: RwLock::new(HashSet::new()),
             _pouf: self._pouf,
         }
     }
@@ -109,6 +114,20 @@ where
         };
         bytes_to_reader(bytes).boxed()
     }
+
+    fn fetch_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let bytes = match self.inner.read().unwrap().snapshot_merkle_proofs.get(role) {
+            Some(bytes) => Ok(Arc::clone(bytes)),
+            None => Err(Error::MetadataNotFound {
+                path: role.clone(),
+                version: MetadataVersion::None,
+            }),
+        };
+        bytes_to_reader(bytes).boxed()
+    }
 }
 
 impl<D> RepositoryStorage<D> for EphemeralRepository<D>
@@ -131,6 +150,31 @@ where
     ) -> BoxFuture<'a, Result<()>> {
         store_target(&self.inner, target_path, read)
     }
+
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+        let mut inner = self.inner.write().unwrap();
+
+        if inner.targets.remove(target_path).is_some() {
+            // Increment the version since we changed.
+            inner.version += 1;
+        }
+
+        async { Ok(()) }.boxed()
+    }
+
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+        let targets = self.inner.read().unwrap().targets.keys().cloned().collect();
+
+        async { Ok(targets) }.boxed()
+    }
+
+    fn store_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+        proof: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        store_snapshot_merkle_proof(&self.inner, role, proof)
+    }
 }
 
 /// [EphemeralBatchUpdate] is a special repository that is designed to write the metadata and
@@ -143,6 +187,8 @@ pub struct EphemeralBatchUpdate<'a, D> {
     initial_parent_version: u64,
     parent_repo: &'a RwLock<Inner>,
     staging_repo: RwLock<Inner>,
+    /// The targets to remove from the parent repository when the batch is committed.
+    removed_targets: RwLock<HashSet<TargetPath>>,
     _pouf: PhantomData<D>,
 }
 
@@ -170,8 +216,14 @@ where
 
         // Since parent hasn't changed, merged everything we wrote into its tables.
         let staging_repo = self.staging_repo.into_inner().unwrap();
+        for target_path in self.removed_targets.into_inner().unwrap() {
+            parent_repo.targets.remove(&target_path);
+        }
         parent_repo.metadata.extend(staging_repo.metadata);
         parent_repo.targets.extend(staging_repo.targets);
+        parent_repo
+            .snapshot_merkle_proofs
+            .extend(staging_repo.snapshot_merkle_proofs);
 
         // Increment the version number because we modified the repository.
         parent_repo.version += 1;
@@ -214,6 +266,8 @@ where
         let bytes = if let Some(bytes) = self.staging_repo.read().unwrap().targets.get(target_path)
         {
             Ok(Arc::clone(bytes))
+        } else if self.removed_targets.read().unwrap().contains(target_path) {
+            Err(Error::TargetNotFound(target_path.clone()))
         } else {
             self.parent_repo
                 .read()
@@ -225,6 +279,33 @@ where
         };
         bytes_to_reader(bytes).boxed()
     }
+
+    fn fetch_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let bytes = if let Some(bytes) = self
+            .staging_repo
+            .read()
+            .unwrap()
+            .snapshot_merkle_proofs
+            .get(role)
+        {
+            Ok(Arc::clone(bytes))
+        } else {
+            self.parent_repo
+                .read()
+                .unwrap()
+                .snapshot_merkle_proofs
+                .get(role)
+                .cloned()
+                .ok_or_else(|| Error::MetadataNotFound {
+                    path: role.clone(),
+                    version: MetadataVersion::None,
+                })
+        };
+        bytes_to_reader(bytes).boxed()
+    }
 }
 
 impl<D> RepositoryStorage<D> for EphemeralBatchUpdate<'_, D>
@@ -245,8 +326,31 @@ where
         target_path: &TargetPath,
         read: &'a mut (dyn AsyncRead + Send + Unpin),
     ) -> BoxFuture<'a, Result<()>> {
+        self.removed_targets.write().unwrap().remove(target_path);
         store_target(&self.staging_repo, target_path, read)
     }
+
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+        self.staging_repo
+            .write()
+            .unwrap()
+            .targets
+            .remove(target_path);
+        self.removed_targets
+            .write()
+            .unwrap()
+            .insert(target_path.clone());
+
+        async { Ok(()) }.boxed()
+    }
+
+    fn store_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+        proof: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        store_snapshot_merkle_proof(&self.staging_repo, role, proof)
+    }
 }
 
 fn store_metadata<'a>(
@@ -296,6 +400,29 @@ fn store_target<'a>(
     .boxed()
 }
 
+fn store_snapshot_merkle_proof<'a>(
+    inner: &'a RwLock<Inner>,
+    role: &MetadataPath,
+    proof: &'a mut (dyn AsyncRead + Send + Unpin),
+) -> BoxFuture<'a, Result<()>> {
+    let role = role.clone();
+    async move {
+        let mut buf = Vec::new();
+        proof.read_to_end(&mut buf).await?;
+        buf.shrink_to_fit();
+
+        let mut inner = inner.write().unwrap();
+
+        inner.snapshot_merkle_proofs.insert(role, buf.into());
+
+        // Increment the version since we changed.
+        inner.version += 1;
+
+        Ok(())
+    }
+    .boxed()
+}
+
 #[allow(clippy::borrowed_box)]
 async fn bytes_to_reader<'a>(
     bytes: Result<Arc<[u8]>>,
@@ -342,6 +469,15 @@ mod test {
             buf.clear();
             read.read_to_end(&mut buf).await.unwrap();
             assert_eq!(buf.as_slice(), bad_data);
+            drop(read);
+
+            // Removing a target more than once succeeds.
+            repo.remove_target(&path).await.unwrap();
+            repo.remove_target(&path).await.unwrap();
+            assert_matches!(
+                repo.fetch_target(&path).await.err(),
+                Some(Error::TargetNotFound(p)) if p == path
+            );
         })
     }
 
@@ -443,6 +579,24 @@ mod test {
                 fetch_target_to_string(&repo, &target_path).await.unwrap(),
                 staged_target,
             );
+
+            // Removed targets are only removed from the repository once the batch is committed.
+            let batch = repo.batch_update();
+            batch.remove_target(&target_path).await.unwrap();
+            assert_matches!(
+                batch.fetch_target(&target_path).await.err(),
+                Some(Error::TargetNotFound(p)) if p == target_path
+            );
+            assert_eq!(
+                fetch_target_to_string(&repo, &target_path).await.unwrap(),
+                staged_target,
+            );
+
+            batch.commit().await.unwrap();
+            assert_matches!(
+                repo.fetch_target(&target_path).await.err(),
+                Some(Error::TargetNotFound(p)) if p == target_path
+            );
         })
     }
 
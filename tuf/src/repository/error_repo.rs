@@ -7,15 +7,19 @@ use {
     },
     futures_io::AsyncRead,
     futures_util::future::{BoxFuture, FutureExt},
-    std::sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
+    std::{
+        io,
+        sync::{
+            atomic::{AtomicBool, AtomicU32, Ordering},
+            Arc,
+        },
     },
 };
 
 pub(crate) struct ErrorRepository<R> {
     repo: R,
     fail_metadata_stores: Arc<AtomicBool>,
+    fail_metadata_fetches: Arc<AtomicU32>,
 }
 
 impl<R> ErrorRepository<R> {
@@ -23,6 +27,7 @@ impl<R> ErrorRepository<R> {
         Self {
             repo,
             fail_metadata_stores: Arc::new(AtomicBool::new(false)),
+            fail_metadata_fetches: Arc::new(AtomicU32::new(0)),
         }
     }
 
@@ -30,6 +35,10 @@ impl<R> ErrorRepository<R> {
         self.fail_metadata_stores
             .store(fail_metadata_stores, Ordering::SeqCst);
     }
+
+    pub(crate) fn fail_next_metadata_fetches(&self, count: u32) {
+        self.fail_metadata_fetches.store(count, Ordering::SeqCst);
+    }
 }
 
 impl<D, R> RepositoryProvider<D> for ErrorRepository<R>
@@ -42,7 +51,18 @@ where
         meta_path: &MetadataPath,
         version: MetadataVersion,
     ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
-        self.repo.fetch_metadata(meta_path, version)
+        let failed = self
+            .fail_metadata_fetches
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                count.checked_sub(1)
+            })
+            .is_ok();
+
+        if failed {
+            async { Err(Error::Io(io::Error::new(io::ErrorKind::Other, "failed"))) }.boxed()
+        } else {
+            self.repo.fetch_metadata(meta_path, version)
+        }
     }
 
     fn fetch_target<'a>(
@@ -51,6 +71,13 @@ where
     ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
         self.repo.fetch_target(target_path)
     }
+
+    fn fetch_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        self.repo.fetch_snapshot_merkle_proof(role)
+    }
 }
 
 impl<D, R> RepositoryStorage<D> for ErrorRepository<R>
@@ -78,4 +105,20 @@ where
     ) -> BoxFuture<'a, Result<()>> {
         self.repo.store_target(target_path, target)
     }
+
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+        self.repo.remove_target(target_path)
+    }
+
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+        self.repo.list_targets()
+    }
+
+    fn store_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+        proof: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        self.repo.store_snapshot_merkle_proof(role, proof)
+    }
 }
@@ -5,15 +5,15 @@ use {
         error::{Error, Result},
         metadata::{MetadataPath, MetadataVersion, TargetPath},
         pouf::Pouf,
-        repository::{RepositoryProvider, RepositoryStorage},
+        repository::{RepositoryLayout, RepositoryProvider, RepositoryStorage},
     },
     futures_io::AsyncRead,
     futures_util::future::{BoxFuture, FutureExt},
     futures_util::io::{copy, AllowStdIo},
     log::debug,
     std::{
-        collections::HashMap,
-        fs::{DirBuilder, File},
+        collections::{HashMap, HashSet},
+        fs::{self, DirBuilder, File},
         io,
         marker::PhantomData,
         path::{Path, PathBuf},
@@ -27,6 +27,7 @@ pub struct FileSystemRepositoryBuilder<D> {
     local_path: PathBuf,
     metadata_prefix: Option<PathBuf>,
     targets_prefix: Option<PathBuf>,
+    layout: RepositoryLayout,
     _pouf: PhantomData<D>,
 }
 
@@ -40,6 +41,7 @@ where
             local_path: local_path.into(),
             metadata_prefix: None,
             targets_prefix: None,
+            layout: RepositoryLayout::new(),
             _pouf: PhantomData,
         }
     }
@@ -64,6 +66,13 @@ where
         self
     }
 
+    /// Set the [RepositoryLayout] used to locate metadata and targets underneath the metadata and
+    /// targets prefixes. This defaults to the standard TUF layout.
+    pub fn layout(mut self, layout: RepositoryLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
     /// Build a `FileSystemRepository`.
     pub fn build(self) -> FileSystemRepository<D> {
         let metadata_path = if let Some(metadata_prefix) = self.metadata_prefix {
@@ -82,6 +91,7 @@ where
             version: RwLock::new(0),
             metadata_path,
             targets_path,
+            layout: self.layout,
             _pouf: PhantomData,
         }
     }
@@ -96,6 +106,7 @@ where
     version: RwLock<u64>,
     metadata_path: PathBuf,
     targets_path: PathBuf,
+    layout: RepositoryLayout,
     _pouf: PhantomData<D>,
 }
 
@@ -132,18 +143,25 @@ where
             parent_repo: self,
             metadata: RwLock::new(HashMap::new()),
             targets: RwLock::new(HashMap::new()),
+            removed_targets: RwLock::new(HashSet::new()),
         }
     }
 
     fn metadata_path(&self, meta_path: &MetadataPath, version: MetadataVersion) -> PathBuf {
         let mut path = self.metadata_path.clone();
-        path.extend(meta_path.components::<D>(version));
+        path.extend(self.layout.metadata_components::<D>(meta_path, version));
+        path
+    }
+
+    fn snapshot_merkle_proof_path(&self, role: &MetadataPath) -> PathBuf {
+        let mut path = self.metadata_path.clone();
+        path.extend(self.layout.snapshot_merkle_proof_components::<D>(role));
         path
     }
 
     fn target_path(&self, target_path: &TargetPath) -> PathBuf {
         let mut path = self.targets_path.clone();
-        path.extend(target_path.components());
+        path.extend(self.layout.target_components(target_path));
         path
     }
 
@@ -175,6 +193,41 @@ where
         .boxed()
     }
 
+    fn store_metadata_to_path<'a>(
+        &'a self,
+        path: PathBuf,
+        metadata: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            if path.exists() {
+                debug!("Metadata path exists. Overwriting: {:?}", path);
+            }
+
+            let mut temp_file = AllowStdIo::new(create_temp_file(&path)?);
+            if let Err(err) = copy(metadata, &mut temp_file).await {
+                return Err(Error::IoPath { path, err });
+            }
+
+            // Lock the version counter to prevent other writers from manipulating the repository to
+            // avoid race conditions.
+            let mut version = self.version.write().unwrap();
+
+            temp_file
+                .into_inner()
+                .persist(&path)
+                .map_err(|err| Error::IoPath {
+                    path,
+                    err: err.error,
+                })?;
+
+            // Increment our version since the repository changed.
+            *version += 1;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
     fn fetch_target_from_path(
         &self,
         target_path: &TargetPath,
@@ -220,6 +273,14 @@ where
         let path = self.target_path(target_path);
         self.fetch_target_from_path(target_path, &path)
     }
+
+    fn fetch_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let path = self.snapshot_merkle_proof_path(role);
+        self.fetch_metadata_from_path(role, MetadataVersion::None, &path)
+    }
 }
 
 impl<D> RepositoryStorage<D> for FileSystemRepository<D>
@@ -233,19 +294,26 @@ where
         metadata: &'a mut (dyn AsyncRead + Send + Unpin),
     ) -> BoxFuture<'a, Result<()>> {
         let path = self.metadata_path(meta_path, version);
+        self.store_metadata_to_path(path, metadata)
+    }
+
+    fn store_target<'a>(
+        &'a self,
+        target_path: &TargetPath,
+        read: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        let path = self.target_path(target_path);
 
         async move {
             if path.exists() {
-                debug!("Metadata path exists. Overwriting: {:?}", path);
+                debug!("Target path exists. Overwriting: {:?}", path);
             }
 
             let mut temp_file = AllowStdIo::new(create_temp_file(&path)?);
-            if let Err(err) = copy(metadata, &mut temp_file).await {
+            if let Err(err) = copy(read, &mut temp_file).await {
                 return Err(Error::IoPath { path, err });
             }
 
-            // Lock the version counter to prevent other writers from manipulating the repository to
-            // avoid race conditions.
             let mut version = self.version.write().unwrap();
 
             temp_file
@@ -264,40 +332,110 @@ where
         .boxed()
     }
 
-    fn store_target<'a>(
-        &'a self,
-        target_path: &TargetPath,
-        read: &'a mut (dyn AsyncRead + Send + Unpin),
-    ) -> BoxFuture<'a, Result<()>> {
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
         let path = self.target_path(target_path);
 
         async move {
-            if path.exists() {
-                debug!("Target path exists. Overwriting: {:?}", path);
+            let mut version = self.version.write().unwrap();
+
+            match fs::remove_file(&path) {
+                Ok(()) => {
+                    // Increment our version since the repository changed.
+                    *version += 1;
+                    Ok(())
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+                Err(err) => Err(Error::IoPath { path, err }),
             }
+        }
+        .boxed()
+    }
 
-            let mut temp_file = AllowStdIo::new(create_temp_file(&path)?);
-            if let Err(err) = copy(read, &mut temp_file).await {
-                return Err(Error::IoPath { path, err });
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+        async move {
+            // Files can only be mapped back to target paths with the default layout, and if the
+            // metadata is stored among the targets it would be listed as targets.
+            if !self.layout.has_default_targets_layout() {
+                return Err(Error::IllegalArgument(
+                    "Cannot list targets stored with a targets template".into(),
+                ));
+            }
+            if self.metadata_path.starts_with(&self.targets_path) {
+                return Err(Error::IllegalArgument(format!(
+                    "Cannot list targets in {:?}, which also contains the metadata",
+                    self.targets_path
+                )));
             }
 
-            let mut version = self.version.write().unwrap();
+            let mut targets = vec![];
+            list_target_files(&self.targets_path, &mut vec![], &mut targets)?;
 
-            temp_file
-                .into_inner()
-                .persist(&path)
-                .map_err(|err| Error::IoPath {
-                    path,
-                    err: err.error,
-                })?;
+            Ok(targets)
+        }
+        .boxed()
+    }
 
-            // Increment our version since the repository changed.
-            *version += 1;
+    fn store_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+        proof: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        let path = self.snapshot_merkle_proof_path(role);
+        self.store_metadata_to_path(path, proof)
+    }
+}
 
-            Ok(())
+/// Add the target path of every file under `dir` to `targets`, where `components` are the
+/// components of `dir` relative to the targets directory. Files whose names aren't valid target
+/// paths are skipped.
+fn list_target_files(
+    dir: &Path,
+    components: &mut Vec<String>,
+    targets: &mut Vec<TargetPath>,
+) -> Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => {
+            return Err(Error::IoPath {
+                path: dir.to_path_buf(),
+                err,
+            })
         }
-        .boxed()
+    };
+
+    for entry in entries {
+        let entry = entry.map_err(|err| Error::IoPath {
+            path: dir.to_path_buf(),
+            err,
+        })?;
+        let path = entry.path();
+        let file_type = entry.file_type().map_err(|err| Error::IoPath {
+            path: path.clone(),
+            err,
+        })?;
+
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => {
+                debug!("Skipping file that isn't a target: {:?}", path);
+                continue;
+            }
+        };
+
+        components.push(name);
+        if file_type.is_dir() {
+            list_target_files(&path, components, targets)?;
+        } else {
+            match TargetPath::new(components.join("/")) {
+                Ok(target_path) => targets.push(target_path),
+                Err(_) => debug!("Skipping file that isn't a target: {:?}", path),
+            }
+        }
+        components.pop();
     }
+
+    Ok(())
 }
 
 /// [FileSystemBatchUpdate] is a special repository that is designed to write the metadata and
@@ -311,6 +449,8 @@ pub struct FileSystemBatchUpdate<'a, D: Pouf> {
     parent_repo: &'a FileSystemRepository<D>,
     metadata: RwLock<HashMap<PathBuf, TempPath>>,
     targets: RwLock<HashMap<PathBuf, TempPath>>,
+    /// The targets to remove from the parent repository once the metadata is written.
+    removed_targets: RwLock<HashSet<PathBuf>>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -370,6 +510,14 @@ where
             })?;
         }
 
+        for path in self.removed_targets.into_inner().unwrap() {
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(CommitError::IoPath { path, err }),
+            }
+        }
+
         // Increment the version because we wrote to it.
         *parent_version += 1;
 
@@ -377,6 +525,31 @@ where
     }
 }
 
+impl<D> FileSystemBatchUpdate<'_, D>
+where
+    D: Pouf,
+{
+    fn stage_metadata<'a>(
+        &'a self,
+        path: PathBuf,
+        read: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let mut temp_file = AllowStdIo::new(create_temp_file(&path)?);
+            if let Err(err) = copy(read, &mut temp_file).await {
+                return Err(Error::IoPath { path, err });
+            }
+            self.metadata
+                .write()
+                .unwrap()
+                .insert(path, temp_file.into_inner().into_temp_path());
+
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
 impl<D> RepositoryProvider<D> for FileSystemBatchUpdate<'_, D>
 where
     D: Pouf,
@@ -404,10 +577,27 @@ where
         if let Some(temp_path) = self.targets.read().unwrap().get(&path) {
             self.parent_repo
                 .fetch_target_from_path(target_path, temp_path)
+        } else if self.removed_targets.read().unwrap().contains(&path) {
+            let target_path = target_path.clone();
+            async move { Err(Error::TargetNotFound(target_path)) }.boxed()
         } else {
             self.parent_repo.fetch_target_from_path(target_path, &path)
         }
     }
+
+    fn fetch_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let path = self.parent_repo.snapshot_merkle_proof_path(role);
+        if let Some(temp_path) = self.metadata.read().unwrap().get(&path) {
+            self.parent_repo
+                .fetch_metadata_from_path(role, MetadataVersion::None, temp_path)
+        } else {
+            self.parent_repo
+                .fetch_metadata_from_path(role, MetadataVersion::None, &path)
+        }
+    }
 }
 
 impl<D> RepositoryStorage<D> for FileSystemBatchUpdate<'_, D>
@@ -421,20 +611,7 @@ where
         read: &'a mut (dyn AsyncRead + Send + Unpin),
     ) -> BoxFuture<'a, Result<()>> {
         let path = self.parent_repo.metadata_path(meta_path, version);
-
-        async move {
-            let mut temp_file = AllowStdIo::new(create_temp_file(&path)?);
-            if let Err(err) = copy(read, &mut temp_file).await {
-                return Err(Error::IoPath { path, err });
-            }
-            self.metadata
-                .write()
-                .unwrap()
-                .insert(path, temp_file.into_inner().into_temp_path());
-
-            Ok(())
-        }
-        .boxed()
+        self.stage_metadata(path, read)
     }
 
     fn store_target<'a>(
@@ -449,6 +626,7 @@ where
             if let Err(err) = copy(read, &mut temp_file).await {
                 return Err(Error::IoPath { path, err });
             }
+            self.removed_targets.write().unwrap().remove(&path);
             self.targets
                 .write()
                 .unwrap()
@@ -458,9 +636,26 @@ where
         }
         .boxed()
     }
+
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+        let path = self.parent_repo.target_path(target_path);
+        self.targets.write().unwrap().remove(&path);
+        self.removed_targets.write().unwrap().insert(path);
+
+        async { Ok(()) }.boxed()
+    }
+
+    fn store_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+        proof: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        let path = self.parent_repo.snapshot_merkle_proof_path(role);
+        self.stage_metadata(path, proof)
+    }
 }
 
-fn create_temp_file(path: &Path) -> Result<NamedTempFile> {
+pub(crate) fn create_temp_file(path: &Path) -> Result<NamedTempFile> {
     // We want to atomically write the file to make sure clients can never see a partially written
     // file.  In order to do this, we'll write to a temporary file in the same directory as our
     // target, otherwise we risk writing the temporary file to one mountpoint, and then
@@ -569,6 +764,95 @@ mod test {
         })
     }
 
+    #[test]
+    fn file_system_repo_list_targets() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let repo = FileSystemRepository::<Pouf1>::new(temp_dir.path());
+
+            // Nothing was stored yet.
+            assert_eq!(repo.list_targets().await.unwrap(), vec![]);
+
+            let paths = vec![
+                TargetPath::new("foo").unwrap(),
+                TargetPath::new("foo-dir/bar").unwrap(),
+                TargetPath::new("foo-dir/baz/qux").unwrap(),
+            ];
+            for path in &paths {
+                repo.store_target(path, &mut &b"data"[..]).await.unwrap();
+            }
+            repo.store_metadata(
+                &MetadataPath::root(),
+                MetadataVersion::None,
+                &mut &b"root"[..],
+            )
+            .await
+            .unwrap();
+
+            let mut listed = repo.list_targets().await.unwrap();
+            listed.sort();
+            assert_eq!(listed, paths);
+
+            // Targets can't be listed when the metadata is stored among them.
+            let repo = FileSystemRepositoryBuilder::<Pouf1>::new(temp_dir.path()).build();
+            assert_matches!(repo.list_targets().await, Err(Error::IllegalArgument(_)));
+        })
+    }
+
+    #[test]
+    fn file_system_repo_layout() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let layout = RepositoryLayout::new()
+                .versioned_metadata_template("v2/meta/{version}.{role}.{ext}")
+                .unwrap()
+                .targets_template("{dirname}/blobs/{filename}")
+                .unwrap();
+            let repo = FileSystemRepositoryBuilder::<Pouf1>::new(temp_dir.path().to_path_buf())
+                .layout(layout)
+                .build();
+
+            let data: &[u8] = b"some metadata";
+            repo.store_metadata(
+                &MetadataPath::root(),
+                MetadataVersion::Number(3),
+                &mut &*data,
+            )
+            .await
+            .unwrap();
+            assert!(temp_dir
+                .path()
+                .join("v2")
+                .join("meta")
+                .join("3.root.json")
+                .exists());
+
+            repo.store_metadata(&MetadataPath::root(), MetadataVersion::None, &mut &*data)
+                .await
+                .unwrap();
+            assert!(temp_dir.path().join("root.json").exists());
+
+            let path = TargetPath::new("foo/bar").unwrap();
+            repo.store_target(&path, &mut &*data).await.unwrap();
+            assert!(temp_dir
+                .path()
+                .join("foo")
+                .join("blobs")
+                .join("bar")
+                .exists());
+            assert_eq!(
+                fetch_target_to_string(&repo, &path).await.unwrap(),
+                "some metadata"
+            );
+        })
+    }
+
     #[test]
     fn file_system_repo_batch_update() {
         block_on(async {
@@ -9,18 +9,53 @@ use hyper::client::connect::Connect;
 use hyper::Client;
 use hyper::Request;
 use percent_encoding::utf8_percent_encode;
+use std::fmt;
 use std::future::Future;
 use std::io;
 use std::marker::PhantomData;
+use std::sync::Arc;
 use url::Url;
 
 use crate::error::Error;
 use crate::metadata::{MetadataPath, MetadataVersion, TargetPath};
 use crate::pouf::Pouf;
-use crate::repository::RepositoryProvider;
+use crate::repository::{RepositoryLayout, RepositoryProvider};
 use crate::util::SafeAsyncRead;
 use crate::Result;
 
+/// A hook that rewrites the URI of a target just before it is requested.
+///
+/// This allows targets to be served from private CDNs that require signed URLs (such as CloudFront
+/// or GCS signed URLs) without needing a custom transport. The signer is given the path of the
+/// target as it will be requested (including any consistent snapshot hash prefix), the length of
+/// the target from the trusted metadata if it is known, and the unsigned URI.
+///
+/// Any closure of the form `Fn(&TargetPath, Option<u64>, Uri) -> Result<Uri>` implements this
+/// trait.
+pub trait TargetUriSigner: Send + Sync {
+    /// Return the URI that should be used to fetch `target_path`.
+    fn sign_target_uri(
+        &self,
+        target_path: &TargetPath,
+        length: Option<u64>,
+        uri: Uri,
+    ) -> Result<Uri>;
+}
+
+impl<F> TargetUriSigner for F
+where
+    F: Fn(&TargetPath, Option<u64>, Uri) -> Result<Uri> + Send + Sync,
+{
+    fn sign_target_uri(
+        &self,
+        target_path: &TargetPath,
+        length: Option<u64>,
+        uri: Uri,
+    ) -> Result<Uri> {
+        (self)(target_path, length, uri)
+    }
+}
+
 /// A builder to create a repository accessible over HTTP.
 pub struct HttpRepositoryBuilder<C, D>
 where
@@ -33,6 +68,8 @@ where
     metadata_prefix: Option<Vec<String>>,
     targets_prefix: Option<Vec<String>>,
     min_bytes_per_second: u32,
+    layout: RepositoryLayout,
+    target_uri_signer: Option<Arc<dyn TargetUriSigner>>,
     _pouf: PhantomData<D>,
 }
 
@@ -50,6 +87,8 @@ where
             metadata_prefix: None,
             targets_prefix: None,
             min_bytes_per_second: 4096,
+            layout: RepositoryLayout::new(),
+            target_uri_signer: None,
             _pouf: PhantomData,
         }
     }
@@ -63,6 +102,8 @@ where
             metadata_prefix: None,
             targets_prefix: None,
             min_bytes_per_second: 4096,
+            layout: RepositoryLayout::new(),
+            target_uri_signer: None,
             _pouf: PhantomData,
         }
     }
@@ -103,6 +144,23 @@ where
         self
     }
 
+    /// Set the [RepositoryLayout] used to locate metadata and targets underneath the metadata and
+    /// targets prefixes. This defaults to the standard TUF layout.
+    pub fn layout(mut self, layout: RepositoryLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Set a [TargetUriSigner] that will be used to rewrite the URI of every target request just
+    /// before it is issued. Metadata requests are not affected.
+    pub fn target_uri_signer<S>(mut self, signer: S) -> Self
+    where
+        S: TargetUriSigner + 'static,
+    {
+        self.target_uri_signer = Some(Arc::new(signer));
+        self
+    }
+
     /// Build a `HttpRepository`.
     pub fn build(self) -> HttpRepository<C, D> {
         let user_agent = match self.user_agent {
@@ -117,13 +175,14 @@ where
             metadata_prefix: self.metadata_prefix,
             targets_prefix: self.targets_prefix,
             min_bytes_per_second: self.min_bytes_per_second,
+            layout: self.layout,
+            target_uri_signer: self.target_uri_signer,
             _pouf: PhantomData,
         }
     }
 }
 
 /// A repository accessible over HTTP.
-#[derive(Debug)]
 pub struct HttpRepository<C, D>
 where
     C: Connect + Sync + 'static,
@@ -135,9 +194,30 @@ where
     metadata_prefix: Option<Vec<String>>,
     targets_prefix: Option<Vec<String>>,
     min_bytes_per_second: u32,
+    layout: RepositoryLayout,
+    target_uri_signer: Option<Arc<dyn TargetUriSigner>>,
     _pouf: PhantomData<D>,
 }
 
+impl<C, D> fmt::Debug for HttpRepository<C, D>
+where
+    C: Connect + Sync + 'static,
+    D: Pouf,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpRepository")
+            .field("uri", &self.uri)
+            .field("client", &self.client)
+            .field("user_agent", &self.user_agent)
+            .field("metadata_prefix", &self.metadata_prefix)
+            .field("targets_prefix", &self.targets_prefix)
+            .field("min_bytes_per_second", &self.min_bytes_per_second)
+            .field("layout", &self.layout)
+            .field("target_uri_signer", &self.target_uri_signer.is_some())
+            .finish()
+    }
+}
+
 // Configuration for urlencoding URI path elements.
 // From https://url.spec.whatwg.org/#path-percent-encode-set
 const URLENCODE_FRAGMENT: &percent_encoding::AsciiSet = &percent_encoding::CONTROLS
@@ -225,21 +305,24 @@ where
             err,
         }))
     }
-}
 
-impl<C, D> RepositoryProvider<D> for HttpRepository<C, D>
-where
-    C: Connect + Clone + Send + Sync + 'static,
-    D: Pouf,
-{
-    fn fetch_metadata<'a>(
+    fn target_uri(&self, target_path: &TargetPath, length: Option<u64>) -> Result<Uri> {
+        let components = self.layout.target_components(target_path);
+        let uri = extend_uri(&self.uri, &self.targets_prefix, &components)?;
+
+        match &self.target_uri_signer {
+            Some(signer) => signer.sign_target_uri(target_path, length, uri),
+            None => Ok(uri),
+        }
+    }
+
+    fn fetch_target_impl<'a>(
         &'a self,
-        meta_path: &MetadataPath,
-        version: MetadataVersion,
+        target_path: &TargetPath,
+        length: Option<u64>,
     ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
-        let meta_path = meta_path.clone();
-        let components = meta_path.components::<D>(version);
-        let uri = extend_uri(&self.uri, &self.metadata_prefix, &components);
+        let target_path = target_path.clone();
+        let uri = self.target_uri(&target_path, length);
 
         async move {
             // TODO(#278) check content length if known and fail early if the payload is too large.
@@ -258,10 +341,7 @@ where
                 let reader: Box<dyn AsyncRead + Send + Unpin> = Box::new(reader);
                 Ok(reader)
             } else if status == StatusCode::NOT_FOUND {
-                Err(Error::MetadataNotFound {
-                    path: meta_path,
-                    version,
-                })
+                Err(Error::TargetNotFound(target_path))
             } else {
                 Err(Error::BadHttpStatus {
                     uri: uri.to_string(),
@@ -272,13 +352,16 @@ where
         .boxed()
     }
 
-    fn fetch_target<'a>(
+    /// Fetch the metadata of `meta_path`, or its snapshot Merkle proof, from the location given by
+    /// `components`.
+    fn fetch_metadata_impl<'a>(
         &'a self,
-        target_path: &TargetPath,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+        components: &[String],
     ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
-        let target_path = target_path.clone();
-        let components = target_path.components();
-        let uri = extend_uri(&self.uri, &self.targets_prefix, &components);
+        let meta_path = meta_path.clone();
+        let uri = extend_uri(&self.uri, &self.metadata_prefix, components);
 
         async move {
             // TODO(#278) check content length if known and fail early if the payload is too large.
@@ -297,7 +380,10 @@ where
                 let reader: Box<dyn AsyncRead + Send + Unpin> = Box::new(reader);
                 Ok(reader)
             } else if status == StatusCode::NOT_FOUND {
-                Err(Error::TargetNotFound(target_path))
+                Err(Error::MetadataNotFound {
+                    path: meta_path,
+                    version,
+                })
             } else {
                 Err(Error::BadHttpStatus {
                     uri: uri.to_string(),
@@ -309,6 +395,44 @@ where
     }
 }
 
+impl<C, D> RepositoryProvider<D> for HttpRepository<C, D>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+    D: Pouf,
+{
+    fn fetch_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let components = self.layout.metadata_components::<D>(meta_path, version);
+        self.fetch_metadata_impl(meta_path, version, &components)
+    }
+
+    fn fetch_target<'a>(
+        &'a self,
+        target_path: &TargetPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        self.fetch_target_impl(target_path, None)
+    }
+
+    fn fetch_target_with_length<'a>(
+        &'a self,
+        target_path: &TargetPath,
+        length: u64,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        self.fetch_target_impl(target_path, Some(length))
+    }
+
+    fn fetch_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let components = self.layout.snapshot_merkle_proof_components::<D>(role);
+        self.fetch_metadata_impl(role, MetadataVersion::None, &components)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -454,4 +578,29 @@ mod test {
             "http://[aaaa::aaaa:aaaa:aaaa:1234%252]:80/prefix/componenents_one/components_two"
         );
     }
+
+    #[test]
+    fn http_repository_target_uri_signer() {
+        let repo = HttpRepositoryBuilder::<_, crate::pouf::Pouf1>::new_with_uri(
+            "http://example.com/one".parse::<Uri>().unwrap(),
+            Client::new(),
+        )
+        .targets_prefix(vec![String::from("targets")])
+        .target_uri_signer(|path: &TargetPath, length: Option<u64>, uri: Uri| {
+            format!("{}?path={}&length={:?}", uri, path, length)
+                .parse::<Uri>()
+                .map_err(|_| Error::IllegalArgument("bad uri".into()))
+        })
+        .build();
+
+        let target_path = TargetPath::new("foo/bar").unwrap();
+        assert_eq!(
+            repo.target_uri(&target_path, Some(42)).unwrap().to_string(),
+            "http://example.com/one/targets/foo/bar?path=foo/bar&length=Some(42)"
+        );
+        assert_eq!(
+            repo.target_uri(&target_path, None).unwrap().to_string(),
+            "http://example.com/one/targets/foo/bar?path=foo/bar&length=None"
+        );
+    }
 }
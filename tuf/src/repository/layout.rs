@@ -0,0 +1,629 @@
+//! Configurable path layouts for metadata and targets stored in a repository.
+
+use std::borrow::Cow;
+
+use crate::error::{Error, Result};
+use crate::metadata::{MetadataPath, MetadataVersion, TargetPath};
+use crate::pouf::Pouf;
+
+/// Describes where metadata and targets are located inside of a repository.
+///
+/// By default, metadata is stored at `{role}.{ext}` (or `{version}.{role}.{ext}` when a specific
+/// version is requested) and targets are stored at `{path}`. Repositories that use a different
+/// layout can override any of these with a template. Templates are `/`-separated paths that may
+/// contain the following placeholders:
+///
+/// * Metadata templates: `{role}` (the [MetadataPath], e.g. `root` or `delegations/foo`) and
+///   `{ext}` (the [Pouf] extension, e.g. `json`). Versioned metadata templates must also contain
+///   `{version}`. The directories of a namespaced role are placed in front of the component that
+///   contains `{role}`, so `{version}.{role}.{ext}` stores version 5 of `foo/bar` at
+///   `foo/5.bar.json`.
+/// * Target templates: `{path}` (the full [TargetPath]), or `{dirname}` and `{filename}`.
+/// * Consistent target templates: the target placeholders, along with `{hash}`, which must be
+///   present. These are used for targets whose file name carries the `HASH.` prefix that is
+///   applied when consistent snapshots are in use, and `{path}` and `{filename}` are expanded
+///   without that prefix. Without a consistent target template, hash-prefixed targets are stored
+///   with the target template, with the prefix left on `{filename}`.
+///
+/// * Snapshot Merkle proof templates: the metadata placeholders, other than `{version}`. The
+///   [snapshot Merkle proofs](crate::merkle) of roles are stored in their own
+///   `snapshot-merkle-proofs` directory by default, so they don't share names with metadata.
+///
+/// Delegated roles may be namespaced with `/`, such as `projects/foo/bin-07`. By default their
+/// metadata is stored in subdirectories, but see [RoleNameMapping] to store it in a single file
+/// with an escaped name instead.
+///
+/// ```
+/// # use tuf::metadata::{MetadataPath, MetadataVersion, TargetPath};
+/// # use tuf::pouf::Pouf1;
+/// # use tuf::repository::RepositoryLayout;
+/// let layout = RepositoryLayout::new()
+///     .versioned_metadata_template("v2/meta/{version}.{role}.{ext}")
+///     .unwrap()
+///     .targets_template("blobs/{dirname}/{filename}")
+///     .unwrap()
+///     .consistent_targets_template("blobs/{dirname}/{hash}/{filename}")
+///     .unwrap();
+///
+/// assert_eq!(
+///     layout.metadata_components::<Pouf1>(&MetadataPath::root(), MetadataVersion::Number(3)),
+///     ["v2", "meta", "3.root.json"],
+/// );
+/// assert_eq!(
+///     layout.metadata_components::<Pouf1>(&MetadataPath::root(), MetadataVersion::None),
+///     ["root.json"],
+/// );
+/// assert_eq!(
+///     layout.target_components(&TargetPath::new("foo/bar").unwrap()),
+///     ["blobs", "foo", "bar"],
+/// );
+///
+/// let hash = "a".repeat(64);
+/// assert_eq!(
+///     layout.target_components(&TargetPath::new(format!("foo/{}.bar", hash)).unwrap()),
+///     ["blobs", "foo", hash.as_str(), "bar"],
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepositoryLayout {
+    metadata: Option<PathTemplate>,
+    versioned_metadata: Option<PathTemplate>,
+    targets: Option<PathTemplate>,
+    consistent_targets: Option<PathTemplate>,
+    snapshot_merkle_proofs: Option<PathTemplate>,
+    role_name_mapping: RoleNameMapping,
+}
+
+/// The directory that snapshot Merkle proofs are stored in without a template.
+const SNAPSHOT_MERKLE_PROOF_DIR: &str = "snapshot-merkle-proofs";
+
+/// How the name of a role that contains `/` is mapped to the location of its metadata.
+///
+/// ```
+/// # use tuf::metadata::{MetadataPath, MetadataVersion};
+/// # use tuf::pouf::Pouf1;
+/// # use tuf::repository::{RepositoryLayout, RoleNameMapping};
+/// let role = MetadataPath::new("projects/foo/bin-07").unwrap();
+///
+/// assert_eq!(
+///     RepositoryLayout::new()
+///         .metadata_components::<Pouf1>(&role, MetadataVersion::Number(3)),
+///     ["projects", "foo", "3.bin-07.json"],
+/// );
+/// assert_eq!(
+///     RepositoryLayout::new()
+///         .role_name_mapping(RoleNameMapping::Escaped)
+///         .metadata_components::<Pouf1>(&role, MetadataVersion::Number(3)),
+///     ["3.projects%2Ffoo%2Fbin-07.json"],
+/// );
+/// ```
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RoleNameMapping {
+    /// Each `/`-separated component of the role name is a directory, and the metadata is stored
+    /// in the last one, like targets are.
+    #[default]
+    Subdirectories,
+    /// The role name is stored in a single file name, with `/` and `%` percent-encoded, the way
+    /// some other TUF implementations store namespaced roles. See [MetadataPath::escaped].
+    Escaped,
+}
+
+impl RepositoryLayout {
+    /// Create a new `RepositoryLayout` that uses the default TUF layout.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the template used for metadata that is fetched without a version.
+    pub fn metadata_template(mut self, template: &str) -> Result<Self> {
+        self.metadata = Some(PathTemplate::parse(template, &["role", "ext"], &["role"])?);
+        Ok(self)
+    }
+
+    /// Set the template used for metadata that is fetched with a specific version.
+    pub fn versioned_metadata_template(mut self, template: &str) -> Result<Self> {
+        self.versioned_metadata = Some(PathTemplate::parse(
+            template,
+            &["role", "version", "ext"],
+            &["role", "version"],
+        )?);
+        Ok(self)
+    }
+
+    /// Set how the names of roles that contain `/` are mapped to the location of their metadata.
+    /// This defaults to [RoleNameMapping::Subdirectories].
+    pub fn role_name_mapping(mut self, mapping: RoleNameMapping) -> Self {
+        self.role_name_mapping = mapping;
+        self
+    }
+
+    /// Set the template used for targets.
+    pub fn targets_template(mut self, template: &str) -> Result<Self> {
+        let parsed = PathTemplate::parse(template, &["path", "dirname", "filename"], &[])?;
+        if !parsed.contains("path") && !parsed.contains("filename") {
+            return Err(Error::IllegalArgument(format!(
+                "Target template {:?} must contain {{path}} or {{filename}}",
+                template
+            )));
+        }
+        self.targets = Some(parsed);
+        Ok(self)
+    }
+
+    /// Set the template used for targets that are stored with a hash prefix, when consistent
+    /// snapshots are in use. The template must contain `{hash}`.
+    pub fn consistent_targets_template(mut self, template: &str) -> Result<Self> {
+        let parsed = PathTemplate::parse(
+            template,
+            &["path", "dirname", "filename", "hash"],
+            &["hash"],
+        )?;
+        if !parsed.contains("path") && !parsed.contains("filename") {
+            return Err(Error::IllegalArgument(format!(
+                "Target template {:?} must contain {{path}} or {{filename}}",
+                template
+            )));
+        }
+        self.consistent_targets = Some(parsed);
+        Ok(self)
+    }
+
+    /// Set the template used for the snapshot Merkle proofs of roles. The template must not
+    /// produce the location of any metadata, or the proofs will overwrite it.
+    pub fn snapshot_merkle_proof_template(mut self, template: &str) -> Result<Self> {
+        self.snapshot_merkle_proofs =
+            Some(PathTemplate::parse(template, &["role", "ext"], &["role"])?);
+        Ok(self)
+    }
+
+    /// Split the location of `meta_path` at `version` into path components.
+    pub fn metadata_components<D>(
+        &self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> Vec<String>
+    where
+        D: Pouf,
+    {
+        let template = match version {
+            MetadataVersion::None => &self.metadata,
+            MetadataVersion::Number(_) => &self.versioned_metadata,
+        };
+
+        match template {
+            Some(template) => {
+                let role = self.role_name(meta_path);
+                let version = match version {
+                    MetadataVersion::None => String::new(),
+                    MetadataVersion::Number(n) => n.to_string(),
+                };
+                template.expand_nested(
+                    &[("version", &version), ("ext", D::extension())],
+                    ("role", &role),
+                )
+            }
+            None => self.default_metadata_components::<D>(meta_path, version),
+        }
+    }
+
+    /// The name of `meta_path` that is substituted for `{role}`.
+    fn role_name<'a>(&self, meta_path: &'a MetadataPath) -> Cow<'a, str> {
+        match self.role_name_mapping {
+            RoleNameMapping::Subdirectories => Cow::Borrowed(meta_path.as_str()),
+            RoleNameMapping::Escaped => Cow::Owned(meta_path.escaped()),
+        }
+    }
+
+    /// The components of `meta_path` at `version` in the default layout.
+    fn default_metadata_components<D>(
+        &self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> Vec<String>
+    where
+        D: Pouf,
+    {
+        match self.role_name_mapping {
+            RoleNameMapping::Subdirectories => meta_path.components::<D>(version),
+            RoleNameMapping::Escaped => vec![format!(
+                "{}{}.{}",
+                version.prefix(),
+                meta_path.escaped(),
+                D::extension()
+            )],
+        }
+    }
+
+    /// Split the location of the snapshot Merkle proof of `role` into path components.
+    pub fn snapshot_merkle_proof_components<D>(&self, role: &MetadataPath) -> Vec<String>
+    where
+        D: Pouf,
+    {
+        match &self.snapshot_merkle_proofs {
+            Some(template) => {
+                let role = self.role_name(role);
+                template.expand_nested(&[("ext", D::extension())], ("role", &role))
+            }
+            None => {
+                let mut components = vec![SNAPSHOT_MERKLE_PROOF_DIR.to_string()];
+                components
+                    .extend(self.default_metadata_components::<D>(role, MetadataVersion::None));
+                components
+            }
+        }
+    }
+
+    /// Split the location of `target_path` into path components.
+    ///
+    /// Targets whose file name starts with a hex encoded SHA-256 or SHA-512 digest followed by `.`
+    /// are taken to be hash-prefixed, and are split with the consistent target template if one
+    /// was set.
+    pub fn target_components(&self, target_path: &TargetPath) -> Vec<String> {
+        let path = target_path.as_str();
+        let (dirname, filename) = match path.rfind('/') {
+            Some(idx) => (&path[..idx], &path[idx + 1..]),
+            None => ("", path),
+        };
+
+        if let Some(template) = &self.consistent_targets {
+            if let Some((hash, filename)) = split_hash_prefix(filename) {
+                let path = if dirname.is_empty() {
+                    filename.to_string()
+                } else {
+                    format!("{}/{}", dirname, filename)
+                };
+                return template.expand(&[
+                    ("path", &path),
+                    ("dirname", dirname),
+                    ("filename", filename),
+                    ("hash", hash),
+                ]);
+            }
+        }
+
+        match &self.targets {
+            Some(template) => {
+                template.expand(&[("path", path), ("dirname", dirname), ("filename", filename)])
+            }
+            None => target_path.components(),
+        }
+    }
+
+    /// Whether targets are stored at their target path, so the target path of a stored file can
+    /// be recovered from its location.
+    pub(crate) fn has_default_targets_layout(&self) -> bool {
+        self.targets.is_none() && self.consistent_targets.is_none()
+    }
+}
+
+/// Split the `HASH.` prefix off of `filename`, if it starts with the hex encoding of a SHA-256 or
+/// SHA-512 digest.
+fn split_hash_prefix(filename: &str) -> Option<(&str, &str)> {
+    let (hash, rest) = filename.split_at(filename.find('.')?);
+    let rest = &rest[1..];
+
+    if (hash.len() == 64 || hash.len() == 128)
+        && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        && !rest.is_empty()
+    {
+        Some((hash, rest))
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Placeholder(String),
+}
+
+/// A parsed `/`-separated path template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PathTemplate {
+    segments: Vec<Segment>,
+}
+
+impl PathTemplate {
+    fn parse(template: &str, allowed: &[&str], required: &[&str]) -> Result<Self> {
+        let mut segments = vec![];
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].into()));
+            }
+
+            let end = rest[start..].find('}').ok_or_else(|| {
+                Error::IllegalArgument(format!("Unterminated placeholder in {:?}", template))
+            })? + start;
+
+            let name = &rest[start + 1..end];
+            if !allowed.contains(&name) {
+                return Err(Error::IllegalArgument(format!(
+                    "Unknown placeholder {{{}}} in {:?}",
+                    name, template
+                )));
+            }
+            segments.push(Segment::Placeholder(name.into()));
+
+            rest = &rest[end + 1..];
+        }
+
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.into()));
+        }
+
+        if segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Literal(s) if s.contains('}')))
+        {
+            return Err(Error::IllegalArgument(format!(
+                "Unmatched '}}' in {:?}",
+                template
+            )));
+        }
+
+        if template.split('/').any(|c| c == ".." || c == ".") {
+            return Err(Error::IllegalArgument(format!(
+                "Template {:?} cannot contain '.' or '..' components",
+                template
+            )));
+        }
+
+        let parsed = PathTemplate { segments };
+
+        for name in required {
+            if !parsed.contains(name) {
+                return Err(Error::IllegalArgument(format!(
+                    "Template {:?} must contain {{{}}}",
+                    template, name
+                )));
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Placeholder(n) if n == name))
+    }
+
+    fn expand(&self, vars: &[(&str, &str)]) -> Vec<String> {
+        self.expand_nested(vars, ("", ""))
+    }
+
+    /// Expand the template like [PathTemplate::expand], except that the directories of the
+    /// `/`-separated `nested` value are placed in front of the component that contains its
+    /// placeholder, rather than inside of it.
+    fn expand_nested(&self, vars: &[(&str, &str)], nested: (&str, &str)) -> Vec<String> {
+        let mut path = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(s) => path.push_str(s),
+                Segment::Placeholder(name) if name == nested.0 => {
+                    let value = nested.1;
+                    match value.rfind('/') {
+                        Some(idx) => {
+                            let start = path.rfind('/').map_or(0, |i| i + 1);
+                            path.insert_str(start, &value[..=idx]);
+                            path.push_str(&value[idx + 1..]);
+                        }
+                        None => path.push_str(value),
+                    }
+                }
+                Segment::Placeholder(name) => {
+                    if let Some((_, value)) = vars.iter().find(|(n, _)| n == name) {
+                        path.push_str(value);
+                    }
+                }
+            }
+        }
+
+        path.split('/')
+            .filter(|component| !component.is_empty())
+            .map(String::from)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pouf::Pouf1;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn default_layout_matches_components() {
+        let layout = RepositoryLayout::new();
+        let meta_path = MetadataPath::new("foo/bar").unwrap();
+        let target_path = TargetPath::new("foo/bar").unwrap();
+
+        for version in [MetadataVersion::None, MetadataVersion::Number(2)] {
+            assert_eq!(
+                layout.metadata_components::<Pouf1>(&meta_path, version),
+                meta_path.components::<Pouf1>(version),
+            );
+        }
+        assert_eq!(
+            layout.target_components(&target_path),
+            target_path.components()
+        );
+    }
+
+    #[test]
+    fn snapshot_merkle_proof_layout() {
+        let role = MetadataPath::new("foo/bar").unwrap();
+        let suffixed_role = MetadataPath::new("foo/bar-snapshot").unwrap();
+
+        let layout = RepositoryLayout::new();
+        assert_eq!(
+            layout.snapshot_merkle_proof_components::<Pouf1>(&role),
+            ["snapshot-merkle-proofs", "foo", "bar.json"],
+        );
+        assert_ne!(
+            layout.snapshot_merkle_proof_components::<Pouf1>(&role),
+            layout.metadata_components::<Pouf1>(&suffixed_role, MetadataVersion::None),
+        );
+
+        assert_eq!(
+            RepositoryLayout::new()
+                .role_name_mapping(RoleNameMapping::Escaped)
+                .snapshot_merkle_proof_components::<Pouf1>(&role),
+            ["snapshot-merkle-proofs", "foo%2Fbar.json"],
+        );
+
+        let layout = RepositoryLayout::new()
+            .snapshot_merkle_proof_template("proofs/{role}.proof.{ext}")
+            .unwrap();
+        assert_eq!(
+            layout.snapshot_merkle_proof_components::<Pouf1>(&role),
+            ["proofs", "foo", "bar.proof.json"],
+        );
+        assert_matches!(
+            RepositoryLayout::new().snapshot_merkle_proof_template("proofs/{version}.{role}"),
+            Err(Error::IllegalArgument(_))
+        );
+    }
+
+    #[test]
+    fn custom_layout() {
+        let layout = RepositoryLayout::new()
+            .metadata_template("/meta/{role}.{ext}")
+            .unwrap()
+            .versioned_metadata_template("/v2/meta/{version}.{role}.{ext}")
+            .unwrap()
+            .targets_template("objects/{path}")
+            .unwrap();
+
+        let meta_path = MetadataPath::new("foo/bar").unwrap();
+        assert_eq!(
+            layout.metadata_components::<Pouf1>(&meta_path, MetadataVersion::None),
+            ["meta", "foo", "bar.json"],
+        );
+        assert_eq!(
+            layout.metadata_components::<Pouf1>(&meta_path, MetadataVersion::Number(5)),
+            ["v2", "meta", "foo", "5.bar.json"],
+        );
+        assert_eq!(
+            layout.target_components(&TargetPath::new("a/b/c").unwrap()),
+            ["objects", "a", "b", "c"],
+        );
+    }
+
+    #[test]
+    fn consistent_targets_layout() {
+        let hash = "0123456789abcdef".repeat(4);
+        let layout = RepositoryLayout::new()
+            .targets_template("objects/{path}")
+            .unwrap()
+            .consistent_targets_template("objects/{dirname}/{hash}/{filename}")
+            .unwrap();
+
+        assert_eq!(
+            layout.target_components(&TargetPath::new(format!("a/b/{}.c", hash)).unwrap()),
+            ["objects", "a", "b", hash.as_str(), "c"],
+        );
+        assert_eq!(
+            layout.target_components(&TargetPath::new(format!("{}.c", hash)).unwrap()),
+            ["objects", hash.as_str(), "c"],
+        );
+
+        // Targets without a hash prefix use the target template.
+        assert_eq!(
+            layout.target_components(&TargetPath::new("a/b/c").unwrap()),
+            ["objects", "a", "b", "c"],
+        );
+        assert_eq!(
+            layout.target_components(&TargetPath::new("a/0123.c").unwrap()),
+            ["objects", "a", "0123.c"],
+        );
+
+        // Without a consistent target template, the prefix stays on the file name.
+        let layout = RepositoryLayout::new()
+            .targets_template("objects/{filename}")
+            .unwrap();
+        assert_eq!(
+            layout.target_components(&TargetPath::new(format!("a/{}.c", hash)).unwrap()),
+            ["objects", format!("{}.c", hash).as_str()],
+        );
+    }
+
+    #[test]
+    fn escaped_role_names() {
+        let layout = RepositoryLayout::new().role_name_mapping(RoleNameMapping::Escaped);
+        let meta_path = MetadataPath::new("projects/foo%/bin-07").unwrap();
+
+        assert_eq!(
+            layout.metadata_components::<Pouf1>(&meta_path, MetadataVersion::None),
+            ["projects%2Ffoo%25%2Fbin-07.json"],
+        );
+        assert_eq!(
+            layout.metadata_components::<Pouf1>(&MetadataPath::root(), MetadataVersion::Number(2)),
+            ["2.root.json"],
+        );
+
+        let layout = layout
+            .versioned_metadata_template("meta/{version}.{role}.{ext}")
+            .unwrap();
+        assert_eq!(
+            layout.metadata_components::<Pouf1>(&meta_path, MetadataVersion::Number(5)),
+            ["meta", "5.projects%2Ffoo%25%2Fbin-07.json"],
+        );
+    }
+
+    #[test]
+    fn filename_layout_without_directory() {
+        let layout = RepositoryLayout::new()
+            .targets_template("{dirname}/t/{filename}")
+            .unwrap();
+
+        assert_eq!(
+            layout.target_components(&TargetPath::new("foo").unwrap()),
+            ["t", "foo"],
+        );
+        assert_eq!(
+            layout.target_components(&TargetPath::new("a/b/foo").unwrap()),
+            ["a", "b", "t", "foo"],
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert_matches!(
+            RepositoryLayout::new().metadata_template("meta/{version}.{role}.json"),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            RepositoryLayout::new().versioned_metadata_template("meta/{role}.json"),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            RepositoryLayout::new().metadata_template("meta/{role.json"),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            RepositoryLayout::new().metadata_template("meta/role}.json"),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            RepositoryLayout::new().targets_template("../{path}"),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            RepositoryLayout::new().targets_template("{dirname}"),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            RepositoryLayout::new().consistent_targets_template("{dirname}/{filename}"),
+            Err(Error::IllegalArgument(_))
+        );
+        assert_matches!(
+            RepositoryLayout::new().consistent_targets_template("{hash}"),
+            Err(Error::IllegalArgument(_))
+        );
+    }
+}
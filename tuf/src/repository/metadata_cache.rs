@@ -0,0 +1,617 @@
+//! A local metadata cache for use by a [Client](crate::client::Client).
+
+use {
+    crate::{
+        crypto::{HashAlgorithm, HashValue},
+        error::{Error, Result},
+        metadata::{MetadataPath, MetadataVersion, TargetPath},
+        pouf::Pouf,
+        repository::{
+            create_temp_file, FileSystemRepository, FileSystemRepositoryBuilder, RepositoryLayout,
+            RepositoryProvider, RepositoryStorage,
+        },
+    },
+    futures_io::AsyncRead,
+    futures_util::future::{BoxFuture, FutureExt},
+    futures_util::io::{AsyncReadExt, Cursor},
+    log::{debug, warn},
+    std::{
+        collections::BTreeMap,
+        fs::{self, DirBuilder},
+        io::{self, Write},
+        marker::PhantomData,
+        path::{Path, PathBuf},
+        sync::RwLock,
+    },
+};
+
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// A local repository purpose-built to cache the trusted metadata of a [Client].
+///
+/// Unlike [FileSystemRepository], this cache:
+///
+/// * Only keeps the latest version of the timestamp, snapshot, targets, and delegated targets
+///   metadata. Versioned copies are only kept for root metadata, so that the chain of previously
+///   trusted roots is preserved.
+/// * Records the SHA-256 of every metadata file it writes in a manifest, and verifies every
+///   file against that manifest when the cache is opened and whenever metadata is read.
+/// * Self-heals when a file is missing, truncated, or otherwise corrupted. Rather than failing, the
+///   corrupted entry is discarded and reported as [Error::MetadataNotFound], which causes the
+///   [Client] to refetch that metadata from the remote repository and store it again.
+///
+/// Targets are stored underneath the `targets` directory of the cache. They are not tracked by the
+/// manifest, since they are always verified against their trusted
+/// [TargetDescription](crate::metadata::TargetDescription) before use.
+///
+/// [Client]: crate::client::Client
+#[derive(Debug)]
+pub struct MetadataCacheRepository<D>
+where
+    D: Pouf,
+{
+    metadata_path: PathBuf,
+    manifest_path: PathBuf,
+    manifest: RwLock<BTreeMap<String, HashValue>>,
+    targets: FileSystemRepository<D>,
+    _pouf: PhantomData<D>,
+}
+
+impl<D> MetadataCacheRepository<D>
+where
+    D: Pouf,
+{
+    /// Open the metadata cache stored at `local_path`, creating it if it does not exist.
+    ///
+    /// Every entry in the cache is checked against the manifest. Entries that are missing or
+    /// corrupted are removed from the cache so they will be refetched by the [Client]. If the
+    /// manifest itself cannot be read, all the cached metadata is discarded.
+    ///
+    /// [Client]: crate::client::Client
+    pub fn open<P: Into<PathBuf>>(local_path: P) -> Result<Self> {
+        let local_path = local_path.into();
+        let metadata_path = local_path.join("metadata");
+        let manifest_path = local_path.join(MANIFEST_FILE_NAME);
+
+        DirBuilder::new()
+            .recursive(true)
+            .create(&metadata_path)
+            .map_err(|err| Error::IoPath {
+                path: metadata_path.clone(),
+                err,
+            })?;
+
+        let manifest = match fs::read(&manifest_path) {
+            Ok(buf) => match serde_json::from_slice(&buf) {
+                Ok(manifest) => manifest,
+                Err(err) => {
+                    warn!(
+                        "Metadata cache manifest {:?} is corrupted, discarding cache: {}",
+                        manifest_path, err
+                    );
+                    BTreeMap::new()
+                }
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => {
+                return Err(Error::IoPath {
+                    path: manifest_path,
+                    err,
+                })
+            }
+        };
+
+        let cache = MetadataCacheRepository {
+            targets: FileSystemRepositoryBuilder::new(&local_path)
+                .targets_prefix("targets")
+                .build(),
+            metadata_path,
+            manifest_path,
+            manifest: RwLock::new(manifest),
+            _pouf: PhantomData,
+        };
+
+        cache.check_integrity()?;
+
+        Ok(cache)
+    }
+
+    /// Verify every cached metadata file against the manifest, discarding any entries that are
+    /// missing or corrupted. Returns the names of the discarded entries.
+    ///
+    /// Entries whose names this cache could not have written are discarded without touching the
+    /// file system, so that a tampered manifest can't remove files outside of the cache.
+    pub fn check_integrity(&self) -> Result<Vec<String>> {
+        let mut manifest = self.manifest.write().unwrap();
+
+        let corrupted = manifest
+            .iter()
+            .filter(|(name, hash)| {
+                if !Self::is_entry_name(name) {
+                    return true;
+                }
+
+                let path = self.metadata_path.join(name);
+                match fs::read(&path) {
+                    Ok(buf) => &sha256(&buf) != *hash,
+                    Err(_) => true,
+                }
+            })
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+
+        if corrupted.is_empty() {
+            return Ok(corrupted);
+        }
+
+        for name in &corrupted {
+            manifest.remove(name);
+            if Self::is_entry_name(name) {
+                warn!("Discarding corrupted metadata cache entry {:?}", name);
+                remove_file(&self.metadata_path.join(name))?;
+            } else {
+                warn!("Discarding invalid metadata cache entry {:?}", name);
+            }
+        }
+
+        self.write_manifest(&manifest)?;
+
+        Ok(corrupted)
+    }
+
+    /// Returns the manifest key for the metadata, or `None` if this cache does not keep this
+    /// version of the metadata.
+    fn entry_name(meta_path: &MetadataPath, version: MetadataVersion) -> Option<String> {
+        if version != MetadataVersion::None && meta_path != &MetadataPath::root() {
+            return None;
+        }
+
+        Some(meta_path.components::<D>(version).join("/"))
+    }
+
+    /// Returns the manifest key for the snapshot Merkle proof of `role`, which is stored where the
+    /// default [RepositoryLayout] stores it.
+    fn snapshot_merkle_proof_entry_name(role: &MetadataPath) -> String {
+        RepositoryLayout::new()
+            .snapshot_merkle_proof_components::<D>(role)
+            .join("/")
+    }
+
+    /// Returns whether `name` is a manifest key that [Self::entry_name] or
+    /// [Self::snapshot_merkle_proof_entry_name] could have produced.
+    fn is_entry_name(name: &str) -> bool {
+        let stem = match name.strip_suffix(&format!(".{}", D::extension())) {
+            Some(stem) => stem,
+            None => return false,
+        };
+
+        // A versioned root metadata.
+        if let Some(version) = stem.strip_suffix(".root").and_then(|v| v.parse().ok()) {
+            let name_of = Self::entry_name(&MetadataPath::root(), MetadataVersion::Number(version));
+            if name_of.as_deref() == Some(name) {
+                return true;
+            }
+        }
+
+        // The latest version of a metadata.
+        if let Ok(role) = MetadataPath::new(stem.to_string()) {
+            if Self::entry_name(&role, MetadataVersion::None).as_deref() == Some(name) {
+                return true;
+            }
+        }
+
+        // A snapshot Merkle proof, which is stored in a directory of its own.
+        match stem.split_once('/') {
+            Some((_, role)) => match MetadataPath::new(role.to_string()) {
+                Ok(role) => Self::snapshot_merkle_proof_entry_name(&role) == name,
+                Err(_) => false,
+            },
+            None => false,
+        }
+    }
+
+    fn write_manifest(&self, manifest: &BTreeMap<String, HashValue>) -> Result<()> {
+        let buf = serde_json::to_vec(manifest)?;
+
+        let mut temp_file = create_temp_file(&self.manifest_path)?;
+        temp_file.write_all(&buf).map_err(|err| Error::IoPath {
+            path: self.manifest_path.clone(),
+            err,
+        })?;
+        temp_file
+            .persist(&self.manifest_path)
+            .map_err(|err| Error::IoPath {
+                path: self.manifest_path.clone(),
+                err: err.error,
+            })?;
+
+        Ok(())
+    }
+
+    /// Read the entry `name`, which holds the metadata of `meta_path` at `version` or its
+    /// snapshot Merkle proof, discarding it if it is corrupted.
+    fn fetch_entry<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+        name: Option<String>,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let meta_path = meta_path.clone();
+
+        async move {
+            let not_found = || Error::MetadataNotFound {
+                path: meta_path.clone(),
+                version,
+            };
+
+            let name = name.ok_or_else(not_found)?;
+
+            let expected = match self.manifest.read().unwrap().get(&name) {
+                Some(hash) => hash.clone(),
+                None => return Err(not_found()),
+            };
+
+            let path = self.metadata_path.join(&name);
+            let buf = match fs::read(&path) {
+                Ok(buf) => Some(buf),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+                Err(err) => return Err(Error::IoPath { path, err }),
+            };
+
+            match buf {
+                Some(buf) if sha256(&buf) == expected => {
+                    let reader: Box<dyn AsyncRead + Send + Unpin> = Box::new(Cursor::new(buf));
+                    Ok(reader)
+                }
+                _ => {
+                    warn!("Discarding corrupted metadata cache entry {:?}", name);
+
+                    let mut manifest = self.manifest.write().unwrap();
+                    manifest.remove(&name);
+                    remove_file(&path)?;
+                    self.write_manifest(&manifest)?;
+
+                    Err(not_found())
+                }
+            }
+        }
+        .boxed()
+    }
+
+    /// Write `metadata` to the entry `name`, and record its hash in the manifest.
+    fn store_entry<'a>(
+        &'a self,
+        name: String,
+        metadata: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let mut buf = Vec::new();
+            metadata.read_to_end(&mut buf).await?;
+
+            let path = self.metadata_path.join(&name);
+            if path.exists() {
+                debug!("Metadata path exists. Overwriting: {:?}", path);
+            }
+
+            let mut temp_file = create_temp_file(&path)?;
+            temp_file.write_all(&buf).map_err(|err| Error::IoPath {
+                path: path.clone(),
+                err,
+            })?;
+
+            let mut manifest = self.manifest.write().unwrap();
+
+            temp_file.persist(&path).map_err(|err| Error::IoPath {
+                path,
+                err: err.error,
+            })?;
+
+            manifest.insert(name, sha256(&buf));
+            self.write_manifest(&manifest)
+        }
+        .boxed()
+    }
+}
+
+impl<D> RepositoryProvider<D> for MetadataCacheRepository<D>
+where
+    D: Pouf,
+{
+    fn fetch_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let name = Self::entry_name(meta_path, version);
+        self.fetch_entry(meta_path, version, name)
+    }
+
+    fn fetch_target<'a>(
+        &'a self,
+        target_path: &TargetPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        self.targets.fetch_target(target_path)
+    }
+
+    fn fetch_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        let name = Self::snapshot_merkle_proof_entry_name(role);
+        self.fetch_entry(role, MetadataVersion::None, Some(name))
+    }
+}
+
+impl<D> RepositoryStorage<D> for MetadataCacheRepository<D>
+where
+    D: Pouf,
+{
+    fn store_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+        metadata: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        // Only the latest version of non-root metadata is cached.
+        let version = if meta_path == &MetadataPath::root() {
+            version
+        } else {
+            MetadataVersion::None
+        };
+        let name = meta_path.components::<D>(version).join("/");
+        self.store_entry(name, metadata)
+    }
+
+    fn store_target<'a>(
+        &'a self,
+        target_path: &TargetPath,
+        target: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        self.targets.store_target(target_path, target)
+    }
+
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+        self.targets.remove_target(target_path)
+    }
+
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+        self.targets.list_targets()
+    }
+
+    fn store_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+        proof: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        self.store_entry(Self::snapshot_merkle_proof_entry_name(role), proof)
+    }
+}
+
+fn sha256(buf: &[u8]) -> HashValue {
+    let mut context = HashAlgorithm::Sha256.digest_context().unwrap();
+    context.update(buf);
+    HashValue::new(context.finish().as_ref().to_vec())
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(Error::IoPath {
+            path: path.to_path_buf(),
+            err,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pouf::Pouf1;
+    use crate::repository::fetch_metadata_to_string;
+    use assert_matches::assert_matches;
+    use futures_executor::block_on;
+    use tempfile;
+
+    #[test]
+    fn metadata_cache_only_keeps_latest_non_root_metadata() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+            let cache = MetadataCacheRepository::<Pouf1>::open(temp_dir.path()).unwrap();
+
+            let timestamp = MetadataPath::timestamp();
+            cache
+                .store_metadata(&timestamp, MetadataVersion::Number(3), &mut &b"ts"[..])
+                .await
+                .unwrap();
+
+            assert_matches!(
+                cache
+                    .fetch_metadata(&timestamp, MetadataVersion::Number(3))
+                    .await
+                    .map(|_| ()),
+                Err(Error::MetadataNotFound { .. })
+            );
+            assert_eq!(
+                fetch_metadata_to_string(&cache, &timestamp, MetadataVersion::None)
+                    .await
+                    .unwrap(),
+                "ts"
+            );
+
+            let root = MetadataPath::root();
+            cache
+                .store_metadata(&root, MetadataVersion::Number(1), &mut &b"root1"[..])
+                .await
+                .unwrap();
+            cache
+                .store_metadata(&root, MetadataVersion::Number(2), &mut &b"root2"[..])
+                .await
+                .unwrap();
+            assert_eq!(
+                fetch_metadata_to_string(&cache, &root, MetadataVersion::Number(1))
+                    .await
+                    .unwrap(),
+                "root1"
+            );
+            assert_eq!(
+                fetch_metadata_to_string(&cache, &root, MetadataVersion::Number(2))
+                    .await
+                    .unwrap(),
+                "root2"
+            );
+        })
+    }
+
+    #[test]
+    fn metadata_cache_discards_corrupted_entries() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+
+            {
+                let cache = MetadataCacheRepository::<Pouf1>::open(temp_dir.path()).unwrap();
+                for path in [MetadataPath::timestamp(), MetadataPath::snapshot()] {
+                    cache
+                        .store_metadata(&path, MetadataVersion::None, &mut &b"data"[..])
+                        .await
+                        .unwrap();
+                }
+            }
+
+            let metadata_dir = temp_dir.path().join("metadata");
+            fs::write(metadata_dir.join("timestamp.json"), b"corrupted").unwrap();
+
+            // Corruption is detected when the cache is opened.
+            let cache = MetadataCacheRepository::<Pouf1>::open(temp_dir.path()).unwrap();
+            assert!(!metadata_dir.join("timestamp.json").exists());
+            assert_matches!(
+                cache
+                    .fetch_metadata(&MetadataPath::timestamp(), MetadataVersion::None)
+                    .await
+                    .map(|_| ()),
+                Err(Error::MetadataNotFound { .. })
+            );
+
+            // Corruption is detected when the metadata is read.
+            fs::write(metadata_dir.join("snapshot.json"), b"corrupted").unwrap();
+            assert_matches!(
+                cache
+                    .fetch_metadata(&MetadataPath::snapshot(), MetadataVersion::None)
+                    .await
+                    .map(|_| ()),
+                Err(Error::MetadataNotFound { .. })
+            );
+            assert!(!metadata_dir.join("snapshot.json").exists());
+
+            // The entry heals once it is stored again.
+            cache
+                .store_metadata(
+                    &MetadataPath::snapshot(),
+                    MetadataVersion::None,
+                    &mut &b"fixed"[..],
+                )
+                .await
+                .unwrap();
+            assert_eq!(
+                fetch_metadata_to_string(&cache, &MetadataPath::snapshot(), MetadataVersion::None)
+                    .await
+                    .unwrap(),
+                "fixed"
+            );
+        })
+    }
+
+    #[test]
+    fn metadata_cache_discards_everything_with_corrupted_manifest() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+
+            {
+                let cache = MetadataCacheRepository::<Pouf1>::open(temp_dir.path()).unwrap();
+                cache
+                    .store_metadata(
+                        &MetadataPath::targets(),
+                        MetadataVersion::None,
+                        &mut &b"data"[..],
+                    )
+                    .await
+                    .unwrap();
+            }
+
+            fs::write(temp_dir.path().join(MANIFEST_FILE_NAME), b"{").unwrap();
+
+            let cache = MetadataCacheRepository::<Pouf1>::open(temp_dir.path()).unwrap();
+            assert_matches!(
+                cache
+                    .fetch_metadata(&MetadataPath::targets(), MetadataVersion::None)
+                    .await
+                    .map(|_| ()),
+                Err(Error::MetadataNotFound { .. })
+            );
+        })
+    }
+
+    #[test]
+    fn metadata_cache_ignores_invalid_manifest_entries() {
+        block_on(async {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("rust-tuf")
+                .tempdir()
+                .unwrap();
+
+            {
+                let cache = MetadataCacheRepository::<Pouf1>::open(temp_dir.path()).unwrap();
+                cache
+                    .store_metadata(
+                        &MetadataPath::root(),
+                        MetadataVersion::Number(1),
+                        &mut &b"root"[..],
+                    )
+                    .await
+                    .unwrap();
+                cache
+                    .store_snapshot_merkle_proof(&MetadataPath::targets(), &mut &b"proof"[..])
+                    .await
+                    .unwrap();
+            }
+
+            // A tampered manifest names a file outside of the cache.
+            let outside = temp_dir.path().join("outside.json");
+            fs::write(&outside, b"precious").unwrap();
+            let manifest_path = temp_dir.path().join(MANIFEST_FILE_NAME);
+            let mut manifest: BTreeMap<String, HashValue> =
+                serde_json::from_slice(&fs::read(&manifest_path).unwrap()).unwrap();
+            let _ = manifest.insert("../outside.json".into(), sha256(b"other"));
+            fs::write(&manifest_path, serde_json::to_vec(&manifest).unwrap()).unwrap();
+
+            let cache = MetadataCacheRepository::<Pouf1>::open(temp_dir.path()).unwrap();
+            assert_eq!(fs::read(&outside).unwrap(), b"precious");
+            assert!(!cache
+                .manifest
+                .read()
+                .unwrap()
+                .contains_key("../outside.json"));
+
+            // The valid entries are kept.
+            assert_eq!(
+                fetch_metadata_to_string(&cache, &MetadataPath::root(), MetadataVersion::Number(1))
+                    .await
+                    .unwrap(),
+                "root"
+            );
+            cache
+                .fetch_snapshot_merkle_proof(&MetadataPath::targets())
+                .await
+                .unwrap();
+        })
+    }
+}
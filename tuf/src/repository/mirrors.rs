@@ -0,0 +1,303 @@
+//! A repository that serves metadata and targets from different places.
+
+use {
+    crate::{
+        error::{Error, Result},
+        metadata::{MetadataPath, MetadataVersion, TargetPath},
+        pouf::Pouf,
+        repository::RepositoryProvider,
+    },
+    futures_io::AsyncRead,
+    futures_util::future::{BoxFuture, FutureExt},
+    log::warn,
+    std::{
+        marker::PhantomData,
+        sync::atomic::{AtomicUsize, Ordering},
+    },
+};
+
+/// A [RepositoryProvider] that fetches metadata from one repository and targets from a distinct
+/// list of target mirrors, for example when targets are served by a different CDN than the
+/// metadata.
+///
+/// Each mirror is a separate repository, so it can be configured independently with its own host,
+/// authentication, or throttling. Target fetches are spread across the mirrors in a round-robin
+/// fashion. If a mirror fails to serve a target, the fetch falls back to the next mirror, until
+/// every mirror has been tried. Targets are always verified by the [Client] against the trusted
+/// metadata, so a misbehaving mirror can't cause an untrusted target to be accepted.
+///
+/// If there are no target mirrors, targets are fetched from the metadata repository.
+///
+/// ```
+/// # use tuf::{
+/// #     pouf::Pouf1,
+/// #     repository::{EphemeralRepository, MirroredRepository, RepositoryProvider},
+/// # };
+/// # fn main() {
+/// let metadata = EphemeralRepository::<Pouf1>::new();
+/// let cdn1: Box<dyn RepositoryProvider<Pouf1> + Send + Sync> =
+///     Box::new(EphemeralRepository::<Pouf1>::new());
+/// let cdn2: Box<dyn RepositoryProvider<Pouf1> + Send + Sync> =
+///     Box::new(EphemeralRepository::<Pouf1>::new());
+///
+/// let remote = MirroredRepository::<Pouf1, _, _>::new(metadata, vec![cdn1, cdn2]);
+/// assert_eq!(remote.target_mirrors().len(), 2);
+///
+/// // `remote` can now be passed to a `Client` as its remote repository.
+/// # }
+/// ```
+///
+/// [Client]: crate::client::Client
+#[derive(Debug)]
+pub struct MirroredRepository<D, M, T> {
+    metadata: M,
+    target_mirrors: Vec<T>,
+    next_mirror: AtomicUsize,
+    _pouf: PhantomData<D>,
+}
+
+impl<D, M, T> MirroredRepository<D, M, T>
+where
+    D: Pouf,
+    M: RepositoryProvider<D>,
+    T: RepositoryProvider<D>,
+{
+    /// Create a repository that fetches metadata from `metadata` and targets from
+    /// `target_mirrors`.
+    pub fn new(metadata: M, target_mirrors: Vec<T>) -> Self {
+        Self {
+            metadata,
+            target_mirrors,
+            next_mirror: AtomicUsize::new(0),
+            _pouf: PhantomData,
+        }
+    }
+
+    /// The repository metadata is fetched from.
+    pub fn metadata_repo(&self) -> &M {
+        &self.metadata
+    }
+
+    /// The repositories targets are fetched from.
+    pub fn target_mirrors(&self) -> &[T] {
+        &self.target_mirrors
+    }
+
+    /// Pick the mirrors to try for the next fetch, starting with the next mirror in the rotation.
+    fn mirrors_for_fetch(&self) -> impl Iterator<Item = &T> {
+        let len = self.target_mirrors.len();
+        let start = if len == 0 {
+            0
+        } else {
+            self.next_mirror.fetch_add(1, Ordering::Relaxed) % len
+        };
+
+        self.target_mirrors[start..]
+            .iter()
+            .chain(self.target_mirrors[..start].iter())
+    }
+}
+
+impl<D, M, T> RepositoryProvider<D> for MirroredRepository<D, M, T>
+where
+    D: Pouf,
+    M: RepositoryProvider<D> + Sync,
+    T: RepositoryProvider<D> + Sync,
+{
+    fn fetch_metadata<'a>(
+        &'a self,
+        meta_path: &MetadataPath,
+        version: MetadataVersion,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        self.metadata.fetch_metadata(meta_path, version)
+    }
+
+    fn fetch_target<'a>(
+        &'a self,
+        target_path: &TargetPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        if self.target_mirrors.is_empty() {
+            return self.metadata.fetch_target(target_path);
+        }
+
+        let target_path = target_path.clone();
+        async move {
+            fetch_from_mirrors(self.mirrors_for_fetch(), &target_path, |mirror| {
+                mirror.fetch_target(&target_path)
+            })
+            .await
+        }
+        .boxed()
+    }
+
+    fn fetch_target_with_length<'a>(
+        &'a self,
+        target_path: &TargetPath,
+        length: u64,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        if self.target_mirrors.is_empty() {
+            return self.metadata.fetch_target_with_length(target_path, length);
+        }
+
+        let target_path = target_path.clone();
+        async move {
+            fetch_from_mirrors(self.mirrors_for_fetch(), &target_path, |mirror| {
+                mirror.fetch_target_with_length(&target_path, length)
+            })
+            .await
+        }
+        .boxed()
+    }
+
+    fn fetch_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        self.metadata.fetch_snapshot_merkle_proof(role)
+    }
+}
+
+/// Try to fetch a target from each of the `mirrors` in turn, returning the first success. If all
+/// the mirrors fail, returns [Error::TargetNotFound] if none of them had the target, and otherwise
+/// the last error that is not [Error::TargetNotFound].
+async fn fetch_from_mirrors<'a, T, F>(
+    mirrors: impl Iterator<Item = &'a T>,
+    target_path: &TargetPath,
+    mut fetch: F,
+) -> Result<Box<dyn AsyncRead + Send + Unpin + 'a>>
+where
+    T: 'a,
+    F: FnMut(&'a T) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>>,
+{
+    let mut last_err = None;
+
+    for mirror in mirrors {
+        match fetch(mirror).await {
+            Ok(target) => return Ok(target),
+            Err(Error::TargetNotFound(_)) => {}
+            Err(err) => {
+                warn!(
+                    "failed to fetch target {} from mirror: {}",
+                    target_path, err
+                );
+                last_err = Some(err);
+            }
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| Error::TargetNotFound(target_path.clone())))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pouf::Pouf1;
+    use crate::repository::{EphemeralRepository, RepositoryStorage};
+    use assert_matches::assert_matches;
+    use futures_executor::block_on;
+    use futures_util::io::AsyncReadExt;
+
+    async fn fetch_target_to_string<R>(repo: &R, path: &TargetPath) -> Result<String>
+    where
+        R: RepositoryProvider<Pouf1>,
+    {
+        let mut reader = repo.fetch_target(path).await?;
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf).await?;
+        Ok(buf)
+    }
+
+    #[test]
+    fn mirrored_repo_splits_metadata_and_targets() {
+        block_on(async {
+            let path = TargetPath::new("foo").unwrap();
+
+            let metadata = EphemeralRepository::<Pouf1>::new();
+            metadata
+                .store_metadata(
+                    &MetadataPath::timestamp(),
+                    MetadataVersion::None,
+                    &mut &b"timestamp"[..],
+                )
+                .await
+                .unwrap();
+            metadata
+                .store_target(&path, &mut &b"metadata"[..])
+                .await
+                .unwrap();
+
+            let mirror = EphemeralRepository::<Pouf1>::new();
+            mirror
+                .store_target(&path, &mut &b"mirror"[..])
+                .await
+                .unwrap();
+
+            let repo = MirroredRepository::<Pouf1, _, _>::new(metadata, vec![mirror]);
+
+            let mut buf = String::new();
+            repo.fetch_metadata(&MetadataPath::timestamp(), MetadataVersion::None)
+                .await
+                .unwrap()
+                .read_to_string(&mut buf)
+                .await
+                .unwrap();
+            assert_eq!(buf, "timestamp");
+
+            assert_eq!(
+                fetch_target_to_string(&repo, &path).await.unwrap(),
+                "mirror"
+            );
+        })
+    }
+
+    #[test]
+    fn mirrored_repo_rotates_and_falls_back() {
+        block_on(async {
+            let foo = TargetPath::new("foo").unwrap();
+            let bar = TargetPath::new("bar").unwrap();
+
+            let mirror1 = EphemeralRepository::<Pouf1>::new();
+            mirror1.store_target(&foo, &mut &b"foo1"[..]).await.unwrap();
+
+            let mirror2 = EphemeralRepository::<Pouf1>::new();
+            mirror2.store_target(&foo, &mut &b"foo2"[..]).await.unwrap();
+            mirror2.store_target(&bar, &mut &b"bar2"[..]).await.unwrap();
+
+            let repo = MirroredRepository::<Pouf1, _, _>::new(
+                EphemeralRepository::<Pouf1>::new(),
+                vec![mirror1, mirror2],
+            );
+
+            // Fetches alternate between the mirrors.
+            assert_eq!(fetch_target_to_string(&repo, &foo).await.unwrap(), "foo1");
+            assert_eq!(fetch_target_to_string(&repo, &foo).await.unwrap(), "foo2");
+
+            // A target missing from one mirror is fetched from the other.
+            assert_eq!(fetch_target_to_string(&repo, &bar).await.unwrap(), "bar2");
+            assert_eq!(fetch_target_to_string(&repo, &bar).await.unwrap(), "bar2");
+
+            let baz = TargetPath::new("baz").unwrap();
+            assert_matches!(
+                fetch_target_to_string(&repo, &baz).await,
+                Err(Error::TargetNotFound(p)) if p == baz
+            );
+        })
+    }
+
+    #[test]
+    fn mirrored_repo_without_mirrors_uses_metadata_repo() {
+        block_on(async {
+            let path = TargetPath::new("foo").unwrap();
+
+            let metadata = EphemeralRepository::<Pouf1>::new();
+            metadata
+                .store_target(&path, &mut &b"foo"[..])
+                .await
+                .unwrap();
+
+            let repo =
+                MirroredRepository::<Pouf1, _, EphemeralRepository<Pouf1>>::new(metadata, vec![]);
+            assert_eq!(fetch_target_to_string(&repo, &path).await.unwrap(), "foo");
+        })
+    }
+}
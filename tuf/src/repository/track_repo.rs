@@ -139,6 +139,22 @@ where
     ) -> BoxFuture<'a, Result<()>> {
         self.repo.store_target(target_path, target)
     }
+
+    fn remove_target<'a>(&'a self, target_path: &TargetPath) -> BoxFuture<'a, Result<()>> {
+        self.repo.remove_target(target_path)
+    }
+
+    fn list_targets(&self) -> BoxFuture<'_, Result<Vec<TargetPath>>> {
+        self.repo.list_targets()
+    }
+
+    fn store_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+        proof: &'a mut (dyn AsyncRead + Send + Unpin),
+    ) -> BoxFuture<'a, Result<()>> {
+        self.repo.store_snapshot_merkle_proof(role, proof)
+    }
 }
 
 impl<D, R> RepositoryProvider<D> for TrackRepository<R>
@@ -187,4 +203,11 @@ where
     ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
         self.repo.fetch_target(target_path)
     }
+
+    fn fetch_snapshot_merkle_proof<'a>(
+        &'a self,
+        role: &MetadataPath,
+    ) -> BoxFuture<'a, Result<Box<dyn AsyncRead + Send + Unpin + 'a>>> {
+        self.repo.fetch_snapshot_merkle_proof(role)
+    }
 }
@@ -0,0 +1,191 @@
+//! Persistent records of the highest metadata versions a client has trusted.
+//!
+//! The protection TUF provides against rollback attacks relies on the client remembering the
+//! versions of the metadata it has trusted. That memory normally lives in the local metadata
+//! cache, so a device whose cache is wiped, such as by a factory reset, will accept any metadata
+//! that is correctly signed, including old-but-valid metadata replayed by an attacker.
+//!
+//! A [RollbackState] records the highest trusted version of every role separately from the
+//! metadata cache, so it can be kept somewhere that survives the cache being wiped. Once it is
+//! set with [Database::set_rollback_state](crate::Database::set_rollback_state), metadata with a
+//! version lower than the recorded one is rejected with
+//! [Error::AttemptedMetadataRollBack].
+
+use {
+    crate::{
+        error::{Error, Result},
+        metadata::MetadataPath,
+        repository::create_temp_file,
+    },
+    std::{
+        collections::BTreeMap,
+        fs,
+        io::{self, Write},
+        path::{Path, PathBuf},
+        sync::Mutex,
+    },
+};
+
+/// A store of the highest version of each role's metadata that has been trusted.
+pub trait RollbackState: Send + Sync {
+    /// The highest recorded version of the metadata for `role`, or `None` if no version has been
+    /// recorded.
+    fn highest_version(&self, role: &MetadataPath) -> Result<Option<u32>>;
+
+    /// Record that `version` of the metadata for `role` has been trusted. Versions lower than the
+    /// highest recorded version are ignored.
+    fn record_version(&self, role: &MetadataPath, version: u32) -> Result<()>;
+}
+
+/// A [RollbackState] that is kept in memory.
+#[derive(Debug, Default)]
+pub struct EphemeralRollbackState {
+    versions: Mutex<BTreeMap<MetadataPath, u32>>,
+}
+
+impl EphemeralRollbackState {
+    /// Create an empty `EphemeralRollbackState`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RollbackState for EphemeralRollbackState {
+    fn highest_version(&self, role: &MetadataPath) -> Result<Option<u32>> {
+        Ok(self.versions.lock().unwrap().get(role).copied())
+    }
+
+    fn record_version(&self, role: &MetadataPath, version: u32) -> Result<()> {
+        let mut versions = self.versions.lock().unwrap();
+        let highest = versions.entry(role.clone()).or_insert(version);
+        *highest = (*highest).max(version);
+        Ok(())
+    }
+}
+
+/// A [RollbackState] that is stored as a JSON file mapping each role to its highest version.
+///
+/// The file is rewritten atomically whenever a higher version is recorded. It should be kept
+/// outside of the directory of the local metadata cache, so that it survives the cache being
+/// wiped.
+#[derive(Debug)]
+pub struct FileRollbackState {
+    path: PathBuf,
+    versions: Mutex<BTreeMap<MetadataPath, u32>>,
+}
+
+impl FileRollbackState {
+    /// Open the rollback state stored at `path`. If the file does not exist, the state starts out
+    /// empty and the file is created when the first version is recorded.
+    ///
+    /// Unlike the metadata cache, a file that cannot be parsed is an error rather than being
+    /// discarded, since discarding it would defeat the purpose of keeping it.
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        let path = path.into();
+
+        let versions = match fs::read(&path) {
+            Ok(buf) => serde_json::from_slice(&buf).map_err(|err| {
+                Error::Encoding(format!("invalid rollback state {:?}: {}", path, err))
+            })?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(Error::IoPath { path, err }),
+        };
+
+        Ok(FileRollbackState {
+            path,
+            versions: Mutex::new(versions),
+        })
+    }
+
+    /// The path of the file the state is stored in.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn write(&self, versions: &BTreeMap<MetadataPath, u32>) -> Result<()> {
+        let buf = serde_json::to_vec(versions)?;
+
+        let mut temp_file = create_temp_file(&self.path)?;
+        temp_file.write_all(&buf).map_err(|err| Error::IoPath {
+            path: self.path.clone(),
+            err,
+        })?;
+        temp_file.persist(&self.path).map_err(|err| Error::IoPath {
+            path: self.path.clone(),
+            err: err.error,
+        })?;
+
+        Ok(())
+    }
+}
+
+impl RollbackState for FileRollbackState {
+    fn highest_version(&self, role: &MetadataPath) -> Result<Option<u32>> {
+        Ok(self.versions.lock().unwrap().get(role).copied())
+    }
+
+    fn record_version(&self, role: &MetadataPath, version: u32) -> Result<()> {
+        let mut versions = self.versions.lock().unwrap();
+        if versions.get(role).copied() >= Some(version) {
+            return Ok(());
+        }
+
+        let mut updated = versions.clone();
+        let _ = updated.insert(role.clone(), version);
+        self.write(&updated)?;
+        *versions = updated;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn ephemeral_rollback_state_keeps_highest_version() {
+        let state = EphemeralRollbackState::new();
+        let role = MetadataPath::timestamp();
+
+        assert_eq!(state.highest_version(&role).unwrap(), None);
+        state.record_version(&role, 3).unwrap();
+        state.record_version(&role, 2).unwrap();
+        assert_eq!(state.highest_version(&role).unwrap(), Some(3));
+        assert_eq!(state.highest_version(&MetadataPath::root()).unwrap(), None);
+    }
+
+    #[test]
+    fn file_rollback_state_persists() {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("rust-tuf")
+            .tempdir()
+            .unwrap();
+        let path = temp_dir.path().join("state").join("rollback.json");
+
+        {
+            let state = FileRollbackState::open(&path).unwrap();
+            assert_eq!(state.highest_version(&MetadataPath::root()).unwrap(), None);
+            assert!(!path.exists());
+
+            state.record_version(&MetadataPath::root(), 2).unwrap();
+            state.record_version(&MetadataPath::snapshot(), 7).unwrap();
+            state.record_version(&MetadataPath::snapshot(), 5).unwrap();
+        }
+
+        let state = FileRollbackState::open(&path).unwrap();
+        assert_eq!(state.path(), path);
+        assert_eq!(
+            state.highest_version(&MetadataPath::root()).unwrap(),
+            Some(2)
+        );
+        assert_eq!(
+            state.highest_version(&MetadataPath::snapshot()).unwrap(),
+            Some(7)
+        );
+
+        fs::write(&path, b"{").unwrap();
+        assert_matches!(FileRollbackState::open(&path), Err(Error::Encoding(_)));
+    }
+}
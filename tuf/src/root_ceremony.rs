@@ -0,0 +1,425 @@
+//! Orchestrating the signing ceremony of the first root metadata of a repository.
+//!
+//! The root keys of a repository are usually kept offline, by several custodians, so the first
+//! root metadata is signed in a ceremony:
+//!
+//! 1. The public keys of every top-level role are collected, and the thresholds are set, with a
+//!    [RootMetadataBuilder].
+//! 2. [RootCeremony::new] checks the root metadata, and produces the unsigned payload.
+//! 3. Each custodian signs the [RootCeremony::payload] on their own machine, after comparing its
+//!    [RootCeremony::digest] with the other custodians. The ceremony is carried between machines
+//!    with [RootCeremony::to_vec] and [RootCeremony::from_slice], and every signature is verified
+//!    as it is added with [RootCeremony::add_signature].
+//! 4. [RootCeremony::finish] checks that a threshold of root keys signed the metadata, and returns
+//!    the signed `1.root.json`, which [RootCeremony::publish] also stores in a repository.
+//!
+//! ```
+//! # use chrono::{offset::Utc, Duration};
+//! # use futures_executor::block_on;
+//! # use tuf::crypto::{Ed25519PrivateKey, PrivateKey};
+//! # use tuf::metadata::RootMetadataBuilder;
+//! # use tuf::pouf::Pouf1;
+//! # use tuf::repository::EphemeralRepository;
+//! # use tuf::root_ceremony::RootCeremony;
+//! #
+//! # let load = |bytes: &[u8]| Ed25519PrivateKey::from_pkcs8(bytes).unwrap();
+//! # let custodian_1 = load(include_bytes!("../tests/ed25519/ed25519-1.pk8.der"));
+//! # let custodian_2 = load(include_bytes!("../tests/ed25519/ed25519-2.pk8.der"));
+//! # let targets_key = load(include_bytes!("../tests/ed25519/ed25519-3.pk8.der"));
+//! # let online_key = load(include_bytes!("../tests/ed25519/ed25519-4.pk8.der"));
+//! # let timestamp_key = load(include_bytes!("../tests/ed25519/ed25519-5.pk8.der"));
+//! let builder = RootMetadataBuilder::new()
+//!     .expires(Utc::now() + Duration::days(365))
+//!     .root_key(custodian_1.public().clone())
+//!     .root_key(custodian_2.public().clone())
+//!     .root_threshold(2)
+//!     .targets_key(targets_key.public().clone())
+//!     .snapshot_key(online_key.public().clone())
+//!     .timestamp_key(timestamp_key.public().clone());
+//! let ceremony = RootCeremony::<Pouf1>::new(builder, &Utc::now()).unwrap();
+//! let mut saved = ceremony.to_vec().unwrap();
+//!
+//! // Each custodian signs the payload on their own machine.
+//! for custodian in [&custodian_1, &custodian_2] {
+//!     let mut ceremony = RootCeremony::<Pouf1>::from_slice(&saved).unwrap();
+//!     let sig = custodian.sign(ceremony.payload()).unwrap();
+//!     ceremony
+//!         .add_signature(custodian.public(), sig.value().as_bytes())
+//!         .unwrap();
+//!     saved = ceremony.to_vec().unwrap();
+//! }
+//!
+//! let repo = EphemeralRepository::<Pouf1>::new();
+//! # block_on(async {
+//! let _root = RootCeremony::<Pouf1>::from_slice(&saved)
+//!     .unwrap()
+//!     .publish(&repo)
+//!     .await
+//!     .unwrap();
+//! # });
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+use crate::crypto::{self, HashAlgorithm, HashValue, KeyId, PublicKey, Signature, SignatureValue};
+use crate::database::Database;
+use crate::error::{Error, Result};
+use crate::metadata::{
+    self, Metadata as _, MetadataPath, MetadataVersion, RawSignedMetadata, RootMetadata,
+    RootMetadataBuilder, SignedMetadataBuilder,
+};
+use crate::pouf::Pouf;
+use crate::repository::RepositoryStorage;
+use crate::verify::{self, SignatureReport};
+
+/// The state of a root signing ceremony: the root metadata, and the signatures collected for it
+/// so far. See the [module documentation](self) for details.
+#[derive(Debug, Clone)]
+pub struct RootCeremony<D> {
+    root: RootMetadata,
+    payload: Vec<u8>,
+    digest: HashValue,
+    signatures: BTreeMap<KeyId, (PublicKey, Vec<u8>)>,
+    _pouf: PhantomData<D>,
+}
+
+impl<D: Pouf> RootCeremony<D> {
+    /// Start a ceremony for the first root metadata of a repository, built by `builder`. The
+    /// version is always 1, and the metadata is checked more strictly than by
+    /// [RootMetadataBuilder::build]:
+    ///
+    /// * No key may be trusted by more than one top-level role, see
+    ///   [RootMetadataBuilder::forbid_key_reuse].
+    /// * The metadata must expire after `now`.
+    pub fn new(builder: RootMetadataBuilder, now: &DateTime<Utc>) -> Result<Self> {
+        let root = builder.version(1).forbid_key_reuse(true).build()?;
+        if root.expires() <= now {
+            return Err(Error::IllegalArgument(format!(
+                "root metadata would expire at {}, before the ceremony",
+                root.expires()
+            )));
+        }
+
+        let payload = metadata::canonical_bytes::<D, _>(&root)?;
+        Self::with_payload(root, payload)
+    }
+
+    fn with_payload(root: RootMetadata, payload: Vec<u8>) -> Result<Self> {
+        let digest = crypto::calculate_hashes_from_slice(&payload, &[HashAlgorithm::Sha256])?
+            .remove(&HashAlgorithm::Sha256)
+            .expect("the requested hash is calculated");
+
+        Ok(RootCeremony {
+            root,
+            payload,
+            digest,
+            signatures: BTreeMap::new(),
+            _pouf: PhantomData,
+        })
+    }
+
+    /// The root metadata that is signed.
+    pub fn root(&self) -> &RootMetadata {
+        &self.root
+    }
+
+    /// The canonical bytes of the root metadata, which every custodian signs.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// The SHA-256 digest of the [RootCeremony::payload], for custodians to compare before they
+    /// sign it.
+    pub fn digest(&self) -> &HashValue {
+        &self.digest
+    }
+
+    /// The root keys that signed the metadata so far.
+    pub fn signers(&self) -> impl Iterator<Item = &PublicKey> {
+        self.signatures.values().map(|(public_key, _)| public_key)
+    }
+
+    /// Add a signature by the root key `public_key` over the [RootCeremony::payload], replacing
+    /// any earlier signature by the same key. Returns an error if the key is not one of the root
+    /// keys, or if the signature doesn't verify.
+    pub fn add_signature(&mut self, public_key: &PublicKey, sig_bytes: &[u8]) -> Result<()> {
+        if !self.root.root().key_ids().contains(public_key.key_id()) {
+            return Err(Error::IllegalArgument(format!(
+                "key {:?} is not a root key",
+                public_key.key_id()
+            )));
+        }
+
+        let sig = Signature::new(
+            public_key.key_id().clone(),
+            SignatureValue::new(sig_bytes.to_vec()),
+        );
+        public_key.verify(&MetadataPath::root(), &self.payload, &sig)?;
+
+        let _ = self.signatures.insert(
+            public_key.key_id().clone(),
+            (public_key.clone(), sig_bytes.to_vec()),
+        );
+
+        Ok(())
+    }
+
+    /// Check which root keys signed the metadata so far, and how many signatures are still
+    /// needed. See [verify::check_signatures].
+    pub fn signature_report(&self) -> Result<SignatureReport> {
+        verify::check_signatures(
+            &MetadataPath::root(),
+            &self.to_raw()?,
+            self.root.root().threshold(),
+            self.root.root_keys(),
+        )
+    }
+
+    /// Finish the ceremony, and return the signed root metadata. The metadata is checked like a
+    /// client checks a trusted root metadata, so this returns an error if fewer than a threshold
+    /// of root keys signed it.
+    pub fn finish(self) -> Result<RawSignedMetadata<D, RootMetadata>> {
+        let raw = self.to_raw()?;
+        let _ = Database::from_trusted_root(&raw)?;
+        Ok(raw)
+    }
+
+    /// Finish the ceremony like [RootCeremony::finish], and store the signed root metadata in
+    /// `repo` as both `1.root.json` and `root.json`, or the equivalent for the [Pouf].
+    pub async fn publish<R>(self, repo: &R) -> Result<RawSignedMetadata<D, RootMetadata>>
+    where
+        R: RepositoryStorage<D>,
+    {
+        let raw = self.finish()?;
+
+        repo.store_metadata(
+            &MetadataPath::root(),
+            MetadataVersion::Number(1),
+            &mut raw.as_bytes(),
+        )
+        .await?;
+        repo.store_metadata(
+            &MetadataPath::root(),
+            MetadataVersion::None,
+            &mut raw.as_bytes(),
+        )
+        .await?;
+
+        Ok(raw)
+    }
+
+    fn to_raw(&self) -> Result<RawSignedMetadata<D, RootMetadata>> {
+        let mut builder = SignedMetadataBuilder::<D, _>::from_metadata(&self.root)?;
+        for (public_key, sig_bytes) in self.signatures.values() {
+            builder = builder.add_signature(public_key, sig_bytes)?;
+        }
+        builder.build().to_raw()
+    }
+
+    /// Serialize the ceremony, such as to carry it to the machine of the next custodian. The
+    /// payload and signatures are base64 encoded, so that they are preserved byte for byte.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let encode = |bytes: &[u8]| data_encoding::BASE64.encode(bytes);
+        let shim = RootCeremonyShim {
+            payload: encode(&self.payload),
+            signatures: self
+                .signatures
+                .values()
+                .map(|(public_key, sig_bytes)| RootCeremonySignatureShim {
+                    public_key: public_key.clone(),
+                    signature: encode(sig_bytes),
+                })
+                .collect(),
+        };
+
+        Ok(serde_json::to_vec_pretty(&shim)?)
+    }
+
+    /// Deserialize a ceremony serialized with [RootCeremony::to_vec]. The root metadata is checked
+    /// again like by [RootCeremony::new], except for its expiration, and every signature is
+    /// verified again.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self> {
+        fn decode(encoded: &str) -> Result<Vec<u8>> {
+            data_encoding::BASE64
+                .decode(encoded.as_bytes())
+                .map_err(|e| Error::Encoding(format!("invalid base64 in root ceremony: {}", e)))
+        }
+
+        let shim: RootCeremonyShim = serde_json::from_slice(bytes)?;
+        let payload = decode(&shim.payload)?;
+        let root: RootMetadata = D::from_slice(&payload)?;
+
+        if root.version() != 1 {
+            return Err(Error::IllegalArgument(format!(
+                "root ceremony is for version {}, not 1",
+                root.version()
+            )));
+        }
+        if let Some((key_id, roles)) = root.reused_key_ids().into_iter().next() {
+            return Err(Error::KeyReuse { key_id, roles });
+        }
+
+        // The payload must be exactly the canonical form of the metadata it describes.
+        if metadata::canonical_bytes::<D, _>(&root)? != payload {
+            return Err(Error::Encoding(
+                "root ceremony payload is not in canonical form".into(),
+            ));
+        }
+
+        let mut ceremony = Self::with_payload(root, payload)?;
+        for sig in shim.signatures {
+            ceremony.add_signature(&sig.public_key, &decode(&sig.signature)?)?;
+        }
+
+        Ok(ceremony)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RootCeremonyShim {
+    payload: String,
+    signatures: Vec<RootCeremonySignatureShim>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RootCeremonySignatureShim {
+    public_key: PublicKey,
+    signature: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::{Ed25519PrivateKey, PrivateKey};
+    use crate::pouf::Pouf1;
+    use crate::repository::{EphemeralRepository, RepositoryProvider};
+    use assert_matches::assert_matches;
+    use chrono::{offset::TimeZone as _, Duration};
+    use futures_executor::block_on;
+    use futures_util::io::AsyncReadExt;
+    use lazy_static::lazy_static;
+    use pretty_assertions::assert_eq;
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[
+                include_bytes!("../tests/ed25519/ed25519-1.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-2.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-3.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-4.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-5.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-6.pk8.der"),
+            ];
+            keys.iter()
+                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+                .collect()
+        };
+    }
+
+    fn builder() -> RootMetadataBuilder {
+        RootMetadataBuilder::new()
+            .expires(Utc.timestamp_opt(1_000_000, 0).unwrap())
+            .root_key(KEYS[0].public().clone())
+            .root_key(KEYS[1].public().clone())
+            .root_key(KEYS[2].public().clone())
+            .root_threshold(2)
+            .targets_key(KEYS[3].public().clone())
+            .snapshot_key(KEYS[4].public().clone())
+            .timestamp_key(KEYS[5].public().clone())
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.timestamp_opt(0, 0).unwrap()
+    }
+
+    #[test]
+    fn root_ceremony() {
+        block_on(async {
+            let ceremony = RootCeremony::<Pouf1>::new(builder(), &now()).unwrap();
+            assert_eq!(ceremony.root().version(), 1);
+            assert_eq!(
+                ceremony.digest(),
+                &crypto::calculate_hashes_from_slice(ceremony.payload(), &[HashAlgorithm::Sha256])
+                    .unwrap()[&HashAlgorithm::Sha256]
+            );
+            let mut saved = ceremony.to_vec().unwrap();
+
+            for key in [&KEYS[0], &KEYS[2]] {
+                let mut ceremony = RootCeremony::<Pouf1>::from_slice(&saved).unwrap();
+                let sig = key.sign(ceremony.payload()).unwrap();
+                ceremony
+                    .add_signature(key.public(), sig.value().as_bytes())
+                    .unwrap();
+                saved = ceremony.to_vec().unwrap();
+            }
+
+            let ceremony = RootCeremony::<Pouf1>::from_slice(&saved).unwrap();
+            assert_eq!(ceremony.signers().count(), 2);
+
+            let report = ceremony.signature_report().unwrap();
+            assert!(report.is_threshold_met());
+            assert!(report.missing_key_ids().contains(KEYS[1].public().key_id()));
+
+            let repo = EphemeralRepository::<Pouf1>::new();
+            let raw = ceremony.publish(&repo).await.unwrap();
+            let db = Database::from_trusted_root(&raw).unwrap();
+            assert_eq!(db.trusted_root().version(), 1);
+
+            for version in [MetadataVersion::Number(1), MetadataVersion::None] {
+                let mut buf = vec![];
+                repo.fetch_metadata(&MetadataPath::root(), version)
+                    .await
+                    .unwrap()
+                    .read_to_end(&mut buf)
+                    .await
+                    .unwrap();
+                assert_eq!(buf, raw.as_bytes());
+            }
+        })
+    }
+
+    #[test]
+    fn root_ceremony_errors() {
+        // Keys may not be shared between roles.
+        assert_matches!(
+            RootCeremony::<Pouf1>::new(builder().timestamp_key(KEYS[0].public().clone()), &now()),
+            Err(Error::KeyReuse { .. })
+        );
+
+        // The metadata may not expire before the ceremony.
+        assert_matches!(
+            RootCeremony::<Pouf1>::new(builder(), &(now() + Duration::days(365))),
+            Err(Error::IllegalArgument(_))
+        );
+
+        let mut ceremony = RootCeremony::<Pouf1>::new(builder(), &now()).unwrap();
+
+        // Only root keys may sign.
+        let sig = KEYS[3].sign(ceremony.payload()).unwrap();
+        assert_matches!(
+            ceremony.add_signature(KEYS[3].public(), sig.value().as_bytes()),
+            Err(Error::IllegalArgument(_))
+        );
+
+        // The signature must be over the payload.
+        let sig = KEYS[0].sign(b"not the payload").unwrap();
+        assert_matches!(
+            ceremony.add_signature(KEYS[0].public(), sig.value().as_bytes()),
+            Err(Error::BadSignature(_))
+        );
+
+        // A threshold of root keys must sign.
+        let sig = KEYS[0].sign(ceremony.payload()).unwrap();
+        ceremony
+            .add_signature(KEYS[0].public(), sig.value().as_bytes())
+            .unwrap();
+        assert_eq!(ceremony.signature_report().unwrap().signatures_needed(), 1);
+        assert_matches!(
+            ceremony.finish(),
+            Err(Error::MetadataMissingSignatures { .. })
+        );
+    }
+}
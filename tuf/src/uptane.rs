@@ -0,0 +1,349 @@
+//! A client for the dual repository architecture of [Uptane](https://uptane.github.io).
+//!
+//! Uptane secures software updates for automotive systems with two TUF repositories:
+//!
+//! * The *image repository* holds every image the OEM has released, signed with offline keys.
+//! * The *director repository* decides which images a particular vehicle should install, and signs
+//!   per-vehicle targets metadata with online keys.
+//!
+//! An [UptaneClient] updates both repositories, and only accepts a target that is listed by the
+//! director and by the image repository with a matching length and hashes. A compromise of the
+//! online director keys therefore can't cause a vehicle to install an image that was not released
+//! through the image repository.
+
+use chrono::{offset::Utc, DateTime};
+use futures_io::AsyncRead;
+use std::path::Path;
+
+use crate::client::{Client, TargetReader};
+use crate::error::{Error, Result};
+use crate::metadata::{MetadataPath, MetadataVersion, TargetDescription, TargetPath};
+use crate::pouf::Pouf;
+use crate::repository::{RepositoryProvider, RepositoryStorage};
+
+/// A client that updates an Uptane director and image repository, and only fetches targets that
+/// both repositories agree on.
+///
+/// Each repository is accessed through its own [Client], with its own trusted root, local
+/// storage, and [Config](crate::client::Config).
+#[derive(Debug)]
+pub struct UptaneClient<D, L, R>
+where
+    D: Pouf,
+    L: RepositoryProvider<D> + RepositoryStorage<D>,
+    R: RepositoryProvider<D>,
+{
+    director: Client<D, L, R>,
+    image: Client<D, L, R>,
+}
+
+impl<D, L, R> UptaneClient<D, L, R>
+where
+    D: Pouf,
+    L: RepositoryProvider<D> + RepositoryStorage<D>,
+    R: RepositoryProvider<D>,
+{
+    /// Create a client from a client for the director repository and a client for the image
+    /// repository.
+    pub fn new(director: Client<D, L, R>, image: Client<D, L, R>) -> Self {
+        UptaneClient { director, image }
+    }
+
+    /// The client for the director repository.
+    pub fn director(&self) -> &Client<D, L, R> {
+        &self.director
+    }
+
+    /// The client for the director repository.
+    pub fn director_mut(&mut self) -> &mut Client<D, L, R> {
+        &mut self.director
+    }
+
+    /// The client for the image repository.
+    pub fn image(&self) -> &Client<D, L, R> {
+        &self.image
+    }
+
+    /// The client for the image repository.
+    pub fn image_mut(&mut self) -> &mut Client<D, L, R> {
+        &mut self.image
+    }
+
+    /// Consume the client and return the director and image clients.
+    pub fn into_parts(self) -> (Client<D, L, R>, Client<D, L, R>) {
+        (self.director, self.image)
+    }
+
+    /// Update the metadata of the director repository, and then of the image repository.
+    ///
+    /// Returns `true` if either repository was updated.
+    pub async fn update(&mut self) -> Result<bool> {
+        let start_time = self.director.database().clock().now();
+        self.update_with_start_time(&start_time).await
+    }
+
+    /// Update the metadata of both repositories, using the specified time to determine if the
+    /// metadata is expired.
+    ///
+    /// See [UptaneClient::update] for more details.
+    pub async fn update_with_start_time(&mut self, start_time: &DateTime<Utc>) -> Result<bool> {
+        let director = self.director.update_with_start_time(start_time).await?;
+        let image = self.image.update_with_start_time(start_time).await?;
+        Ok(director || image)
+    }
+
+    /// The targets the director has assigned to this client, sorted by path.
+    ///
+    /// The director signs all of its targets in its top-level targets metadata, so this does not
+    /// consult any delegations.
+    pub fn director_targets(&self) -> Result<Vec<TargetPath>> {
+        let targets =
+            self.director
+                .database()
+                .trusted_targets()
+                .ok_or_else(|| Error::MetadataNotFound {
+                    path: MetadataPath::targets(),
+                    version: MetadataVersion::None,
+                })?;
+
+        let mut paths = targets.targets().keys().cloned().collect::<Vec<_>>();
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Look up the description of `target` in both repositories, and return the description from
+    /// the director if it matches the one from the image repository.
+    pub async fn fetch_target_description(
+        &mut self,
+        target: &TargetPath,
+    ) -> Result<TargetDescription> {
+        let start_time = self.director.database().clock().now();
+        self.fetch_target_description_with_start_time(target, &start_time)
+            .await
+    }
+
+    /// Look up the description of `target` in both repositories, using the specified time to
+    /// determine if the metadata is expired.
+    ///
+    /// See [UptaneClient::fetch_target_description] for more details.
+    pub async fn fetch_target_description_with_start_time(
+        &mut self,
+        target: &TargetPath,
+        start_time: &DateTime<Utc>,
+    ) -> Result<TargetDescription> {
+        let director = self
+            .director
+            .fetch_target_description_with_start_time(target, start_time)
+            .await?;
+        let image = self
+            .image
+            .fetch_target_description_with_start_time(target, start_time)
+            .await?;
+
+        check_descriptions_match(target, &director, &image)?;
+
+        Ok(director)
+    }
+
+    /// Fetch a target from the image repository, after checking that the director and image
+    /// repository agree on its description.
+    ///
+    /// As with [Client::fetch_target], it is **critical** that none of the bytes from the returned
+    /// reader are used until it has been fully consumed as the data is untrusted.
+    pub async fn fetch_target(
+        &mut self,
+        target: &TargetPath,
+    ) -> Result<TargetReader<impl AsyncRead + Send + Unpin + '_>> {
+        let start_time = self.director.database().clock().now();
+        self.fetch_target_description_with_start_time(target, &start_time)
+            .await?;
+        self.image
+            .fetch_target_with_start_time(target, &start_time)
+            .await
+    }
+
+    /// Fetch a target from the image repository and atomically install it at `path`, after
+    /// checking that the director and image repository agree on its description.
+    pub async fn fetch_target_to_path<P>(&mut self, target: &TargetPath, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let start_time = self.director.database().clock().now();
+        self.fetch_target_description_with_start_time(target, &start_time)
+            .await?;
+        self.image
+            .fetch_target_to_path_with_start_time(target, path, &start_time)
+            .await
+    }
+}
+
+/// Check that the director and image repository describe `target` identically: the lengths must
+/// be equal, they must share at least one hash algorithm, and every shared hash must be equal.
+fn check_descriptions_match(
+    target: &TargetPath,
+    director: &TargetDescription,
+    image: &TargetDescription,
+) -> Result<()> {
+    let mismatch = |reason: &str| {
+        Err(Error::UptaneTargetMismatch {
+            target: target.clone(),
+            reason: reason.into(),
+        })
+    };
+
+    if director.length() != image.length() {
+        return mismatch("lengths differ");
+    }
+
+    let mut shared = 0;
+    for (alg, value) in director.hashes() {
+        if let Some(image_value) = image.hashes().get(alg) {
+            if image_value != value {
+                return mismatch("hashes differ");
+            }
+            shared += 1;
+        }
+    }
+
+    if shared == 0 {
+        return mismatch("no hash algorithm in common");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::Config;
+    use crate::crypto::{Ed25519PrivateKey, HashAlgorithm};
+    use crate::pouf::Pouf1;
+    use crate::repo_builder::RepoBuilder;
+    use crate::repository::EphemeralRepository;
+    use assert_matches::assert_matches;
+    use futures_executor::block_on;
+    use futures_util::io::{AsyncReadExt, Cursor};
+    use lazy_static::lazy_static;
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[
+                include_bytes!("../tests/ed25519/ed25519-1.pk8.der"),
+                include_bytes!("../tests/ed25519/ed25519-2.pk8.der"),
+            ];
+            keys.iter()
+                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+                .collect()
+        };
+    }
+
+    async fn create_client(
+        key: &Ed25519PrivateKey,
+        targets: &[(&str, &'static [u8])],
+    ) -> Client<Pouf1, EphemeralRepository<Pouf1>, EphemeralRepository<Pouf1>> {
+        let mut remote = EphemeralRepository::<Pouf1>::new();
+
+        let mut builder = RepoBuilder::create(&mut remote)
+            .trusted_root_keys(&[key])
+            .trusted_targets_keys(&[key])
+            .trusted_snapshot_keys(&[key])
+            .trusted_timestamp_keys(&[key])
+            .stage_root()
+            .unwrap();
+        for (path, contents) in targets {
+            builder = builder
+                .add_target(TargetPath::new(*path).unwrap(), Cursor::new(*contents))
+                .await
+                .unwrap();
+        }
+        let metadata = builder.commit().await.unwrap();
+
+        Client::with_trusted_root(
+            Config::default(),
+            metadata.root().unwrap(),
+            EphemeralRepository::new(),
+            remote,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn uptane_client_requires_director_and_image_to_agree() {
+        block_on(async {
+            let director = create_client(
+                &KEYS[0],
+                &[
+                    ("ecu/app", &b"app v2"[..]),
+                    ("ecu/fw", &b"evil fw"[..]),
+                    ("ecu/new", &b"new"[..]),
+                ],
+            )
+            .await;
+            let image = create_client(
+                &KEYS[1],
+                &[
+                    ("ecu/app", &b"app v2"[..]),
+                    ("ecu/fw", &b"fw v7"[..]),
+                    ("ecu/old", &b"old"[..]),
+                ],
+            )
+            .await;
+
+            let mut client = UptaneClient::new(director, image);
+            assert_matches!(client.update().await, Ok(_));
+
+            assert_eq!(
+                client.director_targets().unwrap(),
+                vec![
+                    TargetPath::new("ecu/app").unwrap(),
+                    TargetPath::new("ecu/fw").unwrap(),
+                    TargetPath::new("ecu/new").unwrap(),
+                ]
+            );
+
+            let app = TargetPath::new("ecu/app").unwrap();
+            let description = client.fetch_target_description(&app).await.unwrap();
+            assert_eq!(
+                description,
+                TargetDescription::from_slice(b"app v2", &[HashAlgorithm::Sha256]).unwrap()
+            );
+
+            let mut buf = Vec::new();
+            client
+                .fetch_target(&app)
+                .await
+                .unwrap()
+                .read_to_end(&mut buf)
+                .await
+                .unwrap();
+            assert_eq!(buf, b"app v2");
+
+            // The director can't install an image the image repository doesn't agree with.
+            assert_matches!(
+                client
+                    .fetch_target_description(&TargetPath::new("ecu/fw").unwrap())
+                    .await,
+                Err(Error::UptaneTargetMismatch { .. })
+            );
+
+            // Or one that the image repository doesn't have at all.
+            assert_matches!(
+                client
+                    .fetch_target(&TargetPath::new("ecu/new").unwrap())
+                    .await
+                    .map(|_| ()),
+                Err(Error::TargetNotFound(_))
+            );
+
+            // And images are only installed if the director lists them.
+            assert_matches!(
+                client
+                    .fetch_target(&TargetPath::new("ecu/old").unwrap())
+                    .await
+                    .map(|_| ()),
+                Err(Error::TargetNotFound(_))
+            );
+        })
+    }
+}
@@ -1,13 +1,17 @@
 use futures_io::AsyncRead;
 use futures_util::ready;
 use ring::digest;
+use ring::rand::{SecureRandom as _, SystemRandom};
 use std::io::{self, ErrorKind};
 use std::marker::Unpin;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::{Duration, Instant};
 
+use crate::client::TargetFetchProgress;
 use crate::crypto::{HashAlgorithm, HashValue};
+use crate::metadata::TargetPath;
 use crate::Result;
 
 pub(crate) trait SafeAsyncRead: AsyncRead + Sized + Unpin {
@@ -31,6 +35,33 @@ pub(crate) trait SafeAsyncRead: AsyncRead + Sized + Unpin {
     ) -> Result<SafeReader<Self>> {
         SafeReader::new(self, max_length, hash_data)
     }
+
+    /// Like [SafeAsyncRead::check_length_and_hash], but the underlying `AsyncRead` must also
+    /// provide exactly `length` bytes, so a truncated transfer is an `Err` at the end of the stream.
+    fn check_exact_length_and_hash(
+        self,
+        length: u64,
+        hash_data: Vec<(&'static HashAlgorithm, HashValue)>,
+    ) -> Result<SafeReader<Self>> {
+        Ok(SafeReader::new(self, length, hash_data)?.require_exact_length())
+    }
+
+    /// Creates an `AsyncRead` adapter that reports the number of bytes read so far for
+    /// `target_path` to `progress`, if provided.
+    fn report_progress(
+        self,
+        target_path: &TargetPath,
+        expected_length: Option<u64>,
+        progress: Option<Arc<dyn TargetFetchProgress>>,
+    ) -> ReportProgress<Self> {
+        ReportProgress {
+            inner: self,
+            target_path: target_path.clone(),
+            expected_length: expected_length.unwrap_or(0),
+            bytes_read: 0,
+            progress,
+        }
+    }
 }
 
 impl<R: AsyncRead + Unpin> SafeAsyncRead for R {}
@@ -93,6 +124,50 @@ impl<R: AsyncRead + Unpin> AsyncRead for EnforceMinimumBitrate<R> {
     }
 }
 
+/// Returns a random duration between zero and `jitter`, inclusive, with millisecond precision.
+pub(crate) fn random_jitter(rng: &SystemRandom, jitter: Duration) -> Duration {
+    let max = jitter.as_millis() as u64;
+    if max == 0 {
+        return Duration::ZERO;
+    }
+
+    let mut bytes = [0; 8];
+    if rng.fill(&mut bytes).is_err() {
+        return Duration::ZERO;
+    }
+
+    Duration::from_millis(u64::from_le_bytes(bytes) % (max + 1))
+}
+
+/// Wraps an `AsyncRead` to report how many bytes of a target have been read.
+pub(crate) struct ReportProgress<R> {
+    inner: R,
+    target_path: TargetPath,
+    expected_length: u64,
+    bytes_read: u64,
+    progress: Option<Arc<dyn TargetFetchProgress>>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ReportProgress<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let read_bytes = ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+
+        if read_bytes != 0 {
+            self.bytes_read += read_bytes as u64;
+
+            if let Some(progress) = &self.progress {
+                progress.on_progress(&self.target_path, self.bytes_read, self.expected_length);
+            }
+        }
+
+        Poll::Ready(Ok(read_bytes))
+    }
+}
+
 /// Wrapper to verify a byte stream as it is read.
 ///
 /// Wraps an `AsyncRead` to ensure that the consumer can't read more than a capped maximum number of
@@ -106,6 +181,7 @@ impl<R: AsyncRead + Unpin> AsyncRead for EnforceMinimumBitrate<R> {
 pub(crate) struct SafeReader<R> {
     inner: R,
     max_size: u64,
+    exact_length: bool,
     hashers: Vec<(digest::Context, HashValue)>,
     bytes_read: u64,
 }
@@ -130,10 +206,17 @@ impl<R: AsyncRead> SafeReader<R> {
         Ok(SafeReader {
             inner: read,
             max_size,
+            exact_length: false,
             hashers,
             bytes_read: 0,
         })
     }
+
+    /// Fail at the end of the stream if fewer than the maximum number of bytes were read.
+    pub(crate) fn require_exact_length(mut self) -> Self {
+        self.exact_length = true;
+        self
+    }
 }
 
 impl<R: AsyncRead + Unpin> AsyncRead for SafeReader<R> {
@@ -145,6 +228,13 @@ impl<R: AsyncRead + Unpin> AsyncRead for SafeReader<R> {
         let read_bytes = ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
 
         if read_bytes == 0 {
+            if self.exact_length && self.bytes_read != self.max_size {
+                return Poll::Ready(Err(io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "Read ended before the expected length.",
+                )));
+            }
+
             for (context, expected_hash) in self.hashers.drain(..) {
                 let generated_hash = context.finish();
                 if generated_hash.as_ref() != expected_hash.value() {
@@ -226,6 +316,28 @@ mod test {
         })
     }
 
+    #[test]
+    fn invalid_read_below_exact_length() {
+        block_on(async {
+            let bytes: &[u8] = &[0x00, 0x01, 0x02, 0x03];
+            let mut reader = SafeReader::new(bytes, (bytes.len() as u64) + 1, vec![])
+                .unwrap()
+                .require_exact_length();
+            let mut buf = Vec::new();
+            assert_eq!(
+                reader.read_to_end(&mut buf).await.unwrap_err().kind(),
+                ErrorKind::UnexpectedEof
+            );
+
+            let mut reader = SafeReader::new(bytes, bytes.len() as u64, vec![])
+                .unwrap()
+                .require_exact_length();
+            let mut buf = Vec::new();
+            assert!(reader.read_to_end(&mut buf).await.is_ok());
+            assert_eq!(buf, bytes);
+        })
+    }
+
     #[test]
     fn invalid_read_above_max_size_large_data() {
         block_on(async {
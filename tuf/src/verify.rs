@@ -2,11 +2,12 @@
 
 use log::{debug, warn};
 use serde_derive::Deserialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::crypto::{KeyId, PublicKey, Signature};
 use crate::error::Error;
-use crate::metadata::{Metadata, MetadataPath, RawSignedMetadata};
+use crate::lazy_targets::LazyTargetsMetadata;
+use crate::metadata::{Metadata, MetadataPath, RawSignedMetadata, TargetsMetadata};
 use crate::pouf::Pouf;
 
 /// `Verified` is a wrapper type that signifies the inner type has had it's signature verified.
@@ -21,6 +22,12 @@ impl<T> Verified<T> {
     fn new(value: T) -> Self {
         Verified { value }
     }
+
+    // Restore a `Verified` that was verified before it was written to a local cache of trusted
+    // state. This must only be used by `database::cache`, which checksums the cache it reads.
+    pub(crate) fn from_trusted_cache(value: T) -> Self {
+        Verified { value }
+    }
 }
 
 impl<T> std::ops::Deref for Verified<T> {
@@ -82,12 +89,131 @@ impl<T> std::ops::Deref for Verified<T> {
 ///     1,
 ///     &[],
 /// ).is_err());
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(role = %role, threshold, key_ids),
+        err,
+    )
+)]
 pub fn verify_signatures<'a, D, M, I>(
     role: &MetadataPath,
     raw_metadata: &RawSignedMetadata<D, M>,
     threshold: u32,
     authorized_keys: I,
 ) -> Result<Verified<M>, Error>
+where
+    D: Pouf,
+    M: Metadata,
+    I: IntoIterator<Item = &'a PublicKey>,
+{
+    let canonical_bytes = verify_canonical_bytes(role, raw_metadata, threshold, authorized_keys)?;
+
+    // Everything looks good so deserialize the metadata.
+    //
+    // Note: Canonicalization (or any other transformation of data) could modify or filter out
+    // information about the data. Therefore, while we've confirmed the canonical bytes are signed,
+    // we shouldn't interpret this as if the raw bytes were signed. So we deserialize from the
+    // `canonical_bytes`, rather than from `raw_meta.as_bytes()`.
+    let verified_metadata = D::from_slice(&canonical_bytes)?;
+
+    Ok(Verified::new(verified_metadata))
+}
+
+/// Verify this targets metadata like [verify_signatures], but only index its targets instead of
+/// parsing them. See [LazyTargetsMetadata] for details.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(role = %role, threshold, key_ids),
+        err,
+    )
+)]
+pub fn verify_signatures_lazily<'a, D, I>(
+    role: &MetadataPath,
+    raw_metadata: &RawSignedMetadata<D, TargetsMetadata>,
+    threshold: u32,
+    authorized_keys: I,
+) -> Result<Verified<LazyTargetsMetadata>, Error>
+where
+    D: Pouf,
+    I: IntoIterator<Item = &'a PublicKey>,
+{
+    let canonical_bytes = verify_canonical_bytes(role, raw_metadata, threshold, authorized_keys)?;
+
+    // As with `verify_signatures`, only the canonical bytes are trusted.
+    let verified_metadata = LazyTargetsMetadata::from_canonical_bytes(canonical_bytes)?;
+
+    Ok(Verified::new(verified_metadata))
+}
+
+/// Verify a single piece of signed metadata against a known set of keys, without a
+/// [Database](crate::database::Database).
+///
+/// This is meant for tooling outside of the client, such as webhooks or admission controllers,
+/// that already knows which keys are trusted to sign `role`. Unlike [verify_signatures], this
+/// also checks that `role` names metadata of type `M`. Only the signatures are checked; callers
+/// are responsible for checking the expiration and version of the returned metadata.
+///
+/// ```
+/// # use tuf::crypto::{Ed25519PrivateKey, PrivateKey};
+/// # use tuf::pouf::Pouf1;
+/// # use tuf::metadata::{Metadata, MetadataPath, SnapshotMetadataBuilder};
+/// # use tuf::verify::verify_raw;
+///
+/// let key_1: &[u8] = include_bytes!("../tests/ed25519/ed25519-1.pk8.der");
+/// let key_1 = Ed25519PrivateKey::from_pkcs8(&key_1).unwrap();
+///
+/// let raw_snapshot = SnapshotMetadataBuilder::new()
+///     .signed::<Pouf1>(&key_1)
+///     .unwrap()
+///     .to_raw()
+///     .unwrap();
+///
+/// let snapshot = verify_raw(&MetadataPath::snapshot(), &raw_snapshot, vec![key_1.public()], 1)
+///     .unwrap();
+/// assert_eq!(snapshot.version(), 1);
+///
+/// // fail when the role doesn't name snapshot metadata
+/// assert!(verify_raw(&MetadataPath::root(), &raw_snapshot, vec![key_1.public()], 1).is_err());
+///
+/// // fail with a threshold of zero
+/// assert!(verify_raw(&MetadataPath::snapshot(), &raw_snapshot, vec![key_1.public()], 0).is_err());
+/// ```
+pub fn verify_raw<'a, D, M, I>(
+    role: &MetadataPath,
+    raw_metadata: &RawSignedMetadata<D, M>,
+    authorized_keys: I,
+    threshold: u32,
+) -> Result<Verified<M>, Error>
+where
+    D: Pouf,
+    M: Metadata,
+    I: IntoIterator<Item = &'a PublicKey>,
+{
+    if !M::ROLE.fuzzy_matches_path(role) {
+        return Err(Error::IllegalArgument(format!(
+            "{} is not a {} role",
+            role,
+            M::ROLE.name()
+        )));
+    }
+
+    verify_signatures(role, raw_metadata, threshold, authorized_keys)
+}
+
+/// Check that the metadata is signed by a threshold of the authorized keys, and return the
+/// canonical bytes that were signed.
+fn verify_canonical_bytes<'a, D, M, I>(
+    role: &MetadataPath,
+    raw_metadata: &RawSignedMetadata<D, M>,
+    threshold: u32,
+    authorized_keys: I,
+) -> Result<Vec<u8>, Error>
 where
     D: Pouf,
     M: Metadata,
@@ -102,22 +228,15 @@ where
         .map(|k| (k.key_id(), k))
         .collect::<HashMap<&KeyId, &PublicKey>>();
 
-    // Extract the signatures and canonicalize the bytes.
-    let (signatures, canonical_bytes) = {
-        #[derive(Deserialize)]
-        pub struct SignedMetadata<D: Pouf> {
-            signatures: Vec<Signature>,
-            signed: D::RawData,
-        }
-
-        let unverified: SignedMetadata<D> = D::from_slice(raw_metadata.as_bytes())?;
-
-        let canonical_bytes = D::canonicalize(&unverified.signed)?;
-        (unverified.signatures, canonical_bytes)
-    };
+    let (signatures, canonical_bytes) = parse_signatures(raw_metadata)?;
 
     let mut signatures_needed = threshold;
 
+    // The IDs of the keys with good signatures, which are recorded in the `key_ids` field of the
+    // span of the caller.
+    #[cfg(feature = "tracing")]
+    let mut good_key_ids = vec![];
+
     // Create a key_id->signature map to deduplicate the key_ids.
     let signatures = signatures
         .iter()
@@ -128,14 +247,23 @@ where
         match authorized_keys.get(key_id) {
             Some(pub_key) => match pub_key.verify(role, &canonical_bytes, sig) {
                 Ok(()) => {
+                    #[cfg(feature = "tracing")]
+                    {
+                        tracing::debug!(key_id = ?pub_key.key_id(), "good signature");
+                        good_key_ids.push(pub_key.key_id());
+                    }
                     debug!("Good signature from key ID {:?}", pub_key.key_id());
                     signatures_needed -= 1;
                 }
                 Err(e) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(key_id = ?pub_key.key_id(), error = %e, "bad signature");
                     warn!("Bad signature from key ID {:?}: {:?}", pub_key.key_id(), e);
                 }
             },
             None => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(key_id = ?sig.key_id(), "signature from unauthorized key");
                 warn!(
                     "Key ID {:?} was not found in the set of authorized keys.",
                     sig.key_id()
@@ -147,21 +275,190 @@ where
         }
     }
 
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("key_ids", tracing::field::debug(&good_key_ids));
+
     if signatures_needed > 0 {
         return Err(Error::MetadataMissingSignatures {
             role: role.clone(),
+            trusted_version: None,
             number_of_valid_signatures: threshold - signatures_needed,
             threshold,
         });
     }
 
-    // Everything looks good so deserialize the metadata.
-    //
-    // Note: Canonicalization (or any other transformation of data) could modify or filter out
-    // information about the data. Therefore, while we've confirmed the canonical bytes are signed,
-    // we shouldn't interpret this as if the raw bytes were signed. So we deserialize from the
-    // `canonical_bytes`, rather than from `raw_meta.as_bytes()`.
-    let verified_metadata = D::from_slice(&canonical_bytes)?;
+    Ok(canonical_bytes)
+}
 
-    Ok(Verified::new(verified_metadata))
+/// Extract the signatures of the metadata, and canonicalize the bytes they sign.
+fn parse_signatures<D, M>(
+    raw_metadata: &RawSignedMetadata<D, M>,
+) -> Result<(Vec<Signature>, Vec<u8>), Error>
+where
+    D: Pouf,
+    M: Metadata,
+{
+    #[derive(Deserialize)]
+    pub struct SignedMetadata<D: Pouf> {
+        signatures: Vec<Signature>,
+        signed: D::RawData,
+    }
+
+    let unverified: SignedMetadata<D> = D::from_slice(raw_metadata.as_bytes())?;
+
+    let canonical_bytes = D::canonicalize(&unverified.signed)?;
+    Ok((unverified.signatures, canonical_bytes))
+}
+
+/// The result of checking every signature of a metadata against the keys authorized to sign it.
+/// See [check_signatures].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureReport {
+    role: MetadataPath,
+    threshold: u32,
+    valid_key_ids: HashSet<KeyId>,
+    invalid_key_ids: HashSet<KeyId>,
+    unknown_key_ids: HashSet<KeyId>,
+    missing_key_ids: HashSet<KeyId>,
+}
+
+impl SignatureReport {
+    /// The role the metadata was checked as.
+    pub fn role(&self) -> &MetadataPath {
+        &self.role
+    }
+
+    /// The number of valid signatures from authorized keys required to trust the metadata.
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    /// The IDs of the authorized keys with a valid signature.
+    pub fn valid_key_ids(&self) -> &HashSet<KeyId> {
+        &self.valid_key_ids
+    }
+
+    /// The IDs of the authorized keys with a signature that failed to verify.
+    pub fn invalid_key_ids(&self) -> &HashSet<KeyId> {
+        &self.invalid_key_ids
+    }
+
+    /// The IDs of the keys with a signature that are not authorized to sign the metadata.
+    pub fn unknown_key_ids(&self) -> &HashSet<KeyId> {
+        &self.unknown_key_ids
+    }
+
+    /// The IDs of the authorized keys without a signature.
+    pub fn missing_key_ids(&self) -> &HashSet<KeyId> {
+        &self.missing_key_ids
+    }
+
+    /// The number of valid signatures from authorized keys that are still needed to meet the
+    /// threshold.
+    pub fn signatures_needed(&self) -> u32 {
+        let valid = u32::try_from(self.valid_key_ids.len()).unwrap_or(u32::MAX);
+        self.threshold.saturating_sub(valid)
+    }
+
+    /// Whether there are enough valid signatures to trust the metadata.
+    pub fn is_threshold_met(&self) -> bool {
+        self.threshold > 0 && self.valid_key_ids.len() as u64 >= u64::from(self.threshold)
+    }
+}
+
+/// Check every signature of this metadata against the `authorized_keys`, and report which keys
+/// signed it validly, which signatures failed to verify, which were made by keys that are not
+/// authorized, and which authorized keys did not sign it at all.
+///
+/// Unlike [verify_signatures], this does not stop once `threshold` valid signatures are found,
+/// and does not fail if the threshold is not met. It only fails if the signatures cannot be
+/// parsed. This is meant for tooling, such as to tell the operators of a signing ceremony whose
+/// signature is still missing. Metadata should only be trusted through [verify_signatures].
+///
+/// ```
+/// # use tuf::crypto::{Ed25519PrivateKey, PrivateKey};
+/// # use tuf::pouf::Pouf1;
+/// # use tuf::metadata::{MetadataPath, SnapshotMetadataBuilder};
+/// # use tuf::verify::check_signatures;
+/// let key_1: &[u8] = include_bytes!("../tests/ed25519/ed25519-1.pk8.der");
+/// let key_1 = Ed25519PrivateKey::from_pkcs8(&key_1).unwrap();
+///
+/// let key_2: &[u8] = include_bytes!("../tests/ed25519/ed25519-2.pk8.der");
+/// let key_2 = Ed25519PrivateKey::from_pkcs8(&key_2).unwrap();
+///
+/// let raw_snapshot = SnapshotMetadataBuilder::new()
+///     .signed::<Pouf1>(&key_1)
+///     .unwrap()
+///     .to_raw()
+///     .unwrap();
+///
+/// let report = check_signatures(
+///     &MetadataPath::snapshot(),
+///     &raw_snapshot,
+///     2,
+///     vec![key_1.public(), key_2.public()],
+/// )
+/// .unwrap();
+///
+/// assert!(report.valid_key_ids().contains(key_1.public().key_id()));
+/// assert!(report.missing_key_ids().contains(key_2.public().key_id()));
+/// assert!(!report.is_threshold_met());
+/// ```
+pub fn check_signatures<'a, D, M, I>(
+    role: &MetadataPath,
+    raw_metadata: &RawSignedMetadata<D, M>,
+    threshold: u32,
+    authorized_keys: I,
+) -> Result<SignatureReport, Error>
+where
+    D: Pouf,
+    M: Metadata,
+    I: IntoIterator<Item = &'a PublicKey>,
+{
+    let authorized_keys = authorized_keys
+        .into_iter()
+        .map(|k| (k.key_id(), k))
+        .collect::<HashMap<&KeyId, &PublicKey>>();
+
+    let (signatures, canonical_bytes) = parse_signatures(raw_metadata)?;
+
+    let mut report = SignatureReport {
+        role: role.clone(),
+        threshold,
+        valid_key_ids: HashSet::new(),
+        invalid_key_ids: HashSet::new(),
+        unknown_key_ids: HashSet::new(),
+        missing_key_ids: HashSet::new(),
+    };
+
+    for sig in &signatures {
+        let key_id = sig.key_id();
+        match authorized_keys.get(key_id) {
+            Some(pub_key) => {
+                if pub_key.verify(role, &canonical_bytes, sig).is_ok() {
+                    let _ = report.valid_key_ids.insert(key_id.clone());
+                } else {
+                    let _ = report.invalid_key_ids.insert(key_id.clone());
+                }
+            }
+            None => {
+                let _ = report.unknown_key_ids.insert(key_id.clone());
+            }
+        }
+    }
+
+    // A key with both a valid and an invalid signature did sign the metadata.
+    report
+        .invalid_key_ids
+        .retain(|key_id| !report.valid_key_ids.contains(key_id));
+
+    report.missing_key_ids = authorized_keys
+        .keys()
+        .filter(|key_id| {
+            !report.valid_key_ids.contains(*key_id) && !report.invalid_key_ids.contains(*key_id)
+        })
+        .map(|key_id| (*key_id).clone())
+        .collect();
+
+    Ok(report)
 }
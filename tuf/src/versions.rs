@@ -0,0 +1,310 @@
+//! Selecting targets by their semantic version.
+//!
+//! Repositories often publish several releases of the same artifact, such as
+//! `app/foo/foo-2.0.0.tar.gz` and `app/foo/foo-2.1.0.tar.gz`. Rather than parsing the version out
+//! of the [TargetPath], the version of a release can be recorded in the `version` custom field of
+//! its [TargetDescription]:
+//!
+//! ```json
+//! "custom": {
+//!   "version": "2.1.0"
+//! }
+//! ```
+//!
+//! [Client::latest_target_matching] then answers queries like "the latest 2.x of `app/foo`" from
+//! the trusted metadata, including targets signed by delegated roles.
+
+use chrono::{offset::Utc, DateTime};
+use log::warn;
+use semver::{Version, VersionReq};
+
+use crate::client::{Client, TrustedTarget};
+use crate::error::{Error, Result};
+use crate::metadata::{MetadataPath, TargetDescription, TargetPath};
+use crate::pouf::Pouf;
+use crate::repository::{RepositoryProvider, RepositoryStorage};
+
+/// The name of the custom field of a [TargetDescription] that holds the version of a target.
+pub const VERSION_CUSTOM_FIELD: &str = "version";
+
+/// Parse the version in the custom metadata of `description`. Returns `None` if the target does
+/// not have a version.
+pub fn target_version(description: &TargetDescription) -> Result<Option<Version>> {
+    let version = match description.custom().get(VERSION_CUSTOM_FIELD) {
+        Some(version) => version,
+        None => return Ok(None),
+    };
+
+    let version = version.as_str().ok_or_else(|| {
+        Error::Encoding(format!(
+            "custom field {:?} must be a string",
+            VERSION_CUSTOM_FIELD
+        ))
+    })?;
+
+    Version::parse(version)
+        .map(Some)
+        .map_err(|err| Error::Encoding(format!("invalid version {:?}: {}", version, err)))
+}
+
+/// A trusted target with a version, as returned by [Client::versioned_targets].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionedTarget {
+    target: TrustedTarget,
+    version: Version,
+}
+
+impl VersionedTarget {
+    /// The path of the target.
+    pub fn path(&self) -> &TargetPath {
+        self.target.path()
+    }
+
+    /// The trusted description of the target.
+    pub fn description(&self) -> &TargetDescription {
+        self.target.description()
+    }
+
+    /// The targets role that signed the description of the target.
+    pub fn role(&self) -> &MetadataPath {
+        self.target.role()
+    }
+
+    /// The version of the target.
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+}
+
+/// Returns `true` if `path` is `prefix` or is underneath the directory `prefix`.
+fn has_prefix(path: &TargetPath, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    if prefix.is_empty() {
+        return true;
+    }
+
+    match path.as_str().strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+impl<D, L, R> Client<D, L, R>
+where
+    D: Pouf,
+    L: RepositoryProvider<D> + RepositoryStorage<D>,
+    R: RepositoryProvider<D>,
+{
+    /// List the trusted targets at or underneath `prefix` that have a version, sorted from the
+    /// oldest to the newest version. An empty `prefix` lists every versioned target.
+    ///
+    /// Targets whose version can't be parsed are skipped with a warning.
+    pub async fn versioned_targets(&mut self, prefix: &str) -> Result<Vec<VersionedTarget>> {
+        self.versioned_targets_with_start_time(prefix, &self.database().clock().now())
+            .await
+    }
+
+    /// List the trusted targets at or underneath `prefix` that have a version, using the specified
+    /// time to determine if the metadata is expired.
+    ///
+    /// See [Client::versioned_targets] for more details.
+    pub async fn versioned_targets_with_start_time(
+        &mut self,
+        prefix: &str,
+        start_time: &DateTime<Utc>,
+    ) -> Result<Vec<VersionedTarget>> {
+        let mut targets = self
+            .trusted_targets_iter_with_start_time(start_time)
+            .await?
+            .filter(|target| has_prefix(target.path(), prefix))
+            .filter_map(|target| match target_version(target.description()) {
+                Ok(Some(version)) => Some(VersionedTarget { target, version }),
+                Ok(None) => None,
+                Err(err) => {
+                    warn!("Ignoring the version of target {}: {}", target.path(), err);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        targets.sort_by(|a, b| {
+            a.version
+                .cmp(&b.version)
+                .then_with(|| a.path().cmp(b.path()))
+        });
+
+        Ok(targets)
+    }
+
+    /// Find the trusted target at or underneath `prefix` with the newest version that matches
+    /// `req`, or `None` if there is no such target.
+    ///
+    /// ```no_run
+    /// # use futures_executor::block_on;
+    /// # use semver::VersionReq;
+    /// # use tuf::client::Client;
+    /// # use tuf::pouf::Pouf1;
+    /// # use tuf::repository::EphemeralRepository;
+    /// # type Repo = EphemeralRepository<Pouf1>;
+    /// # fn latest(client: &mut Client<Pouf1, Repo, Repo>) {
+    /// # block_on(async {
+    /// let req = VersionReq::parse("2.x").unwrap();
+    /// if let Some(target) = client.latest_target_matching("app/foo", &req).await.unwrap() {
+    ///     println!("latest 2.x of app/foo is {} at {}", target.version(), target.path());
+    /// }
+    /// # })
+    /// # }
+    /// ```
+    pub async fn latest_target_matching(
+        &mut self,
+        prefix: &str,
+        req: &VersionReq,
+    ) -> Result<Option<VersionedTarget>> {
+        self.latest_target_matching_with_start_time(prefix, req, &self.database().clock().now())
+            .await
+    }
+
+    /// Find the trusted target at or underneath `prefix` with the newest version that matches
+    /// `req`, using the specified time to determine if the metadata is expired.
+    ///
+    /// See [Client::latest_target_matching] for more details.
+    pub async fn latest_target_matching_with_start_time(
+        &mut self,
+        prefix: &str,
+        req: &VersionReq,
+        start_time: &DateTime<Utc>,
+    ) -> Result<Option<VersionedTarget>> {
+        Ok(self
+            .versioned_targets_with_start_time(prefix, start_time)
+            .await?
+            .into_iter()
+            .rev()
+            .find(|target| req.matches(target.version())))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::Config;
+    use crate::crypto::Ed25519PrivateKey;
+    use crate::pouf::Pouf1;
+    use crate::repo_builder::RepoBuilder;
+    use crate::repository::EphemeralRepository;
+    use futures_executor::block_on;
+    use futures_util::io::Cursor;
+    use lazy_static::lazy_static;
+    use std::collections::HashMap;
+
+    lazy_static! {
+        static ref KEYS: Vec<Ed25519PrivateKey> = {
+            let keys: &[&[u8]] = &[include_bytes!("../tests/ed25519/ed25519-1.pk8.der")];
+            keys.iter()
+                .map(|b| Ed25519PrivateKey::from_pkcs8(b).unwrap())
+                .collect()
+        };
+    }
+
+    fn version_custom(version: &str) -> HashMap<String, serde_json::Value> {
+        let mut custom = HashMap::new();
+        custom.insert(VERSION_CUSTOM_FIELD.into(), version.into());
+        custom
+    }
+
+    #[test]
+    fn test_has_prefix() {
+        let path = TargetPath::new("app/foo/foo-2.0.0.tar.gz").unwrap();
+        assert!(has_prefix(&path, ""));
+        assert!(has_prefix(&path, "app"));
+        assert!(has_prefix(&path, "app/foo/"));
+        assert!(has_prefix(&path, "app/foo/foo-2.0.0.tar.gz"));
+        assert!(!has_prefix(&path, "app/fo"));
+        assert!(!has_prefix(&path, "app/bar"));
+    }
+
+    #[test]
+    fn test_latest_target_matching() {
+        block_on(async {
+            let mut remote = EphemeralRepository::<Pouf1>::new();
+
+            let mut builder = RepoBuilder::create(&mut remote)
+                .trusted_root_keys(&[&KEYS[0]])
+                .trusted_targets_keys(&[&KEYS[0]])
+                .trusted_snapshot_keys(&[&KEYS[0]])
+                .trusted_timestamp_keys(&[&KEYS[0]])
+                .stage_root()
+                .unwrap();
+
+            for (path, version) in [
+                ("app/foo/foo-1.9.0", "1.9.0"),
+                ("app/foo/foo-2.0.0", "2.0.0"),
+                ("app/foo/foo-2.3.1", "2.3.1"),
+                ("app/foo/foo-3.0.0-rc.1", "3.0.0-rc.1"),
+                ("app/foo/foo-broken", "not a version"),
+                ("app/bar/bar-2.5.0", "2.5.0"),
+            ] {
+                builder = builder
+                    .add_target_with_custom(
+                        TargetPath::new(path).unwrap(),
+                        Cursor::new(path.as_bytes()),
+                        version_custom(version),
+                    )
+                    .await
+                    .unwrap();
+            }
+            builder = builder
+                .add_target(
+                    TargetPath::new("app/foo/README").unwrap(),
+                    Cursor::new(&b"readme"[..]),
+                )
+                .await
+                .unwrap();
+
+            let metadata = builder.commit().await.unwrap();
+
+            let mut client = Client::with_trusted_root(
+                Config::default(),
+                metadata.root().unwrap(),
+                EphemeralRepository::new(),
+                remote,
+            )
+            .await
+            .unwrap();
+            client.update().await.unwrap();
+
+            let versions = client
+                .versioned_targets("app/foo")
+                .await
+                .unwrap()
+                .iter()
+                .map(|target| target.version().to_string())
+                .collect::<Vec<_>>();
+            assert_eq!(versions, vec!["1.9.0", "2.0.0", "2.3.1", "3.0.0-rc.1"]);
+
+            let latest = client
+                .latest_target_matching("app/foo", &VersionReq::parse("2.x").unwrap())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(latest.path().as_str(), "app/foo/foo-2.3.1");
+            assert_eq!(latest.version(), &Version::new(2, 3, 1));
+            assert_eq!(latest.role(), &MetadataPath::targets());
+
+            // Pre-releases only match requirements that ask for them.
+            let latest = client
+                .latest_target_matching("app/foo", &VersionReq::parse("*").unwrap())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(latest.version(), &Version::new(2, 3, 1));
+
+            assert_eq!(
+                client
+                    .latest_target_matching("app/foo", &VersionReq::parse("4").unwrap())
+                    .await
+                    .unwrap(),
+                None
+            );
+        })
+    }
+}
@@ -260,6 +260,7 @@ fn rejects_bad_delegation_signatures() {
             ),
             Err(Error::MetadataMissingSignatures {
                 role,
+                trusted_version: None,
                 number_of_valid_signatures: 0,
                 threshold: 1,
             })
@@ -458,6 +459,7 @@ fn diamond_delegation() {
             ),
             Err(Error::MetadataMissingSignatures {
                 role,
+                trusted_version: None,
                 number_of_valid_signatures: 0,
                 threshold: 1,
             })